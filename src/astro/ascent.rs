@@ -0,0 +1,369 @@
+//! Rough estimates of launching from a body's surface into a circular orbit,
+//! by flying a gravity turn in the plane of its equator.
+//!
+//! The rocket launches eastward from the equator, with a single stage of
+//! constant thrust. It goes straight up until it reaches some altitude, then
+//! pitches over gradually, following a fixed program, until its apoapsis
+//! reaches the target orbit. It then coasts up to apoapsis, and circularizes
+//! there with an impulsive burn.
+
+use nalgebra::Vector3;
+
+use super::integrate::{propagate_until, IntegratorKind, IntegratorSettings, PhaseState};
+
+// The ascent changes quickly, so it needs much smaller steps than orbits do
+const ASCENT_INTEGRATOR: IntegratorSettings = IntegratorSettings {
+    kind: IntegratorKind::DormandPrince,
+    step_size: 1.0,
+    tolerance: 1e-9,
+};
+// How long to let the rocket coast up to its apoapsis before giving up, in
+// seconds
+const MAX_COAST_TIME: f64 = 86400.0;
+// How often the profile is sampled, in seconds
+const PROFILE_INTERVAL: f64 = 5.0;
+// For atmospheres described only by their height: how many scale heights
+// thick they are, which is about how thin they are at the top
+const SCALE_HEIGHTS_PER_ATMOSPHERE: f64 = 12.5;
+
+/// An exponential atmosphere, which ends abruptly at some altitude.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Atmosphere {
+    /// In kg/m^3
+    pub surface_density: f64,
+    /// How far up the density falls by a factor of e, in meters.
+    pub scale_height: f64,
+    /// Altitude of the top of the atmosphere, in meters.
+    pub height: f64,
+}
+
+impl Atmosphere {
+    /// Makes up an atmosphere of the given height, whose density falls off to
+    /// a few millionths of its surface density at the top, like Kerbin's.
+    pub fn from_height(height: f64, surface_density: f64) -> Self {
+        Self {
+            surface_density,
+            scale_height: height / SCALE_HEIGHTS_PER_ATMOSPHERE,
+            height,
+        }
+    }
+
+    pub fn density(&self, altitude: f64) -> f64 {
+        if altitude >= self.height {
+            0.0
+        } else {
+            self.surface_density * (-altitude.max(0.0) / self.scale_height).exp()
+        }
+    }
+}
+
+/// The body being launched from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LaunchSite {
+    pub mu: f64,
+    pub radius: f64,
+    /// Rate of rotation, in radians per second, which gives the rocket a head
+    /// start when it launches eastward.
+    pub angular_velocity: f64,
+    pub atmosphere: Option<Atmosphere>,
+}
+
+/// A single-stage rocket.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LaunchVehicle {
+    /// Mass on the launchpad, in kg
+    pub mass: f64,
+    /// How much of that is propellant, in kg
+    pub propellant_mass: f64,
+    /// In N
+    pub thrust: f64,
+    /// In m/s
+    pub exhaust_velocity: f64,
+    /// Drag coefficient times cross-sectional area, in m^2
+    pub drag_area: f64,
+}
+
+/// How the rocket pitches over. It flies straight up until
+/// `turn_start_altitude`, and then its pitch, measured from vertical, goes as
+/// `90° * fraction^shape`, where `fraction` is how far it's gotten from there
+/// to `turn_end_altitude`. Above that, it thrusts horizontally.
+///
+/// Shapes below 1 pitch over quickly at first, and more gently later.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GravityTurn {
+    pub turn_start_altitude: f64,
+    pub turn_end_altitude: f64,
+    pub shape: f64,
+}
+
+impl GravityTurn {
+    /// Pitch from vertical, in radians, at the given altitude.
+    pub fn pitch(&self, altitude: f64) -> f64 {
+        let fraction = (altitude - self.turn_start_altitude)
+            / (self.turn_end_altitude - self.turn_start_altitude);
+        std::f64::consts::FRAC_PI_2 * fraction.clamp(0.0, 1.0).powf(self.shape)
+    }
+}
+
+/// Where the rocket is partway through its ascent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AscentPoint {
+    /// Since launch, in seconds
+    pub time: f64,
+    /// Distance traveled over the ground, measured along the surface in
+    /// the non-rotating frame, in meters
+    pub downrange: f64,
+    pub altitude: f64,
+    /// Relative to the body's center, in the non-rotating frame
+    pub speed: f64,
+}
+
+/// The cost and duration of a launch into orbit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AscentEstimate {
+    /// Spent on the way up, including circularizing, in m/s.
+    pub delta_v: f64,
+    /// Spent circularizing, at the end, in m/s.
+    pub circularization_delta_v: f64,
+    /// How much more was spent than it would take to just speed up from the
+    /// surface to orbital speed, lost to gravity and drag, in m/s.
+    pub losses: f64,
+    /// How long the engine burned for, before coasting, in seconds.
+    pub burn_time: f64,
+    /// How long it takes to get to orbit, including coasting to apoapsis, in
+    /// seconds.
+    pub time_to_orbit: f64,
+    /// Altitude of the orbit reached, which may be a little lower than the
+    /// target, if drag lowered it during the coast.
+    pub altitude: f64,
+    /// Samples of the path taken, up to apoapsis.
+    pub profile: Vec<AscentPoint>,
+}
+
+/// Flies a gravity turn from the equator of the launch site, eastward, to a
+/// circular orbit at `target_altitude`. Returns an error if the rocket can't
+/// make it there.
+pub fn estimate_ascent(
+    site: &LaunchSite,
+    vehicle: &LaunchVehicle,
+    turn: &GravityTurn,
+    target_altitude: f64,
+) -> Result<AscentEstimate, String> {
+    let atmosphere_height = site.atmosphere.map_or(0.0, |a| a.height);
+    if target_altitude <= atmosphere_height {
+        return Err(format!(
+            "Target altitude {:.0} m isn't above the atmosphere ({:.0} m)",
+            target_altitude, atmosphere_height
+        ));
+    }
+    let surface_gravity = site.mu / site.radius.powi(2);
+    if vehicle.thrust <= vehicle.mass * surface_gravity {
+        return Err(String::from("Rocket is too heavy to lift off"));
+    }
+
+    let mass_flow = vehicle.thrust / vehicle.exhaust_velocity;
+    let max_burn_time = vehicle.propellant_mass / mass_flow;
+    let spin = site.angular_velocity * Vector3::z();
+    let drag = |state: &PhaseState, mass: f64| -> Vector3<f64> {
+        let atmosphere = match site.atmosphere {
+            Some(atmosphere) => atmosphere,
+            None => return Vector3::zeros(),
+        };
+        let altitude = state.position.norm() - site.radius;
+        let air_velocity = state.velocity - spin.cross(&state.position);
+        let density = atmosphere.density(altitude);
+        -0.5 * density * air_velocity.norm() * air_velocity * vehicle.drag_area / mass
+    };
+    let gravity = |state: &PhaseState| {
+        let r = state.position.norm();
+        -site.mu / (r * r * r) * state.position
+    };
+
+    // Burn until the apoapsis is high enough
+    let target_radius = site.radius + target_altitude;
+    let burn_accel = |t: f64, state: &PhaseState| {
+        let mass = vehicle.mass - mass_flow * t;
+        let up = state.position.normalize();
+        let east = Vector3::z().cross(&up).normalize();
+        let pitch = turn.pitch(state.position.norm() - site.radius);
+        let thrust = vehicle.thrust / mass * (pitch.cos() * up + pitch.sin() * east);
+        gravity(state) + drag(state, mass) + thrust
+    };
+    let crashed = |state: &PhaseState| state.position.norm() < site.radius;
+    let start = PhaseState::new(
+        site.radius * Vector3::x(),
+        spin.cross(&(site.radius * Vector3::x())),
+    );
+    let mut integrator = ASCENT_INTEGRATOR.build();
+    let burn = propagate_until(
+        integrator.as_mut(),
+        &burn_accel,
+        0.0,
+        start,
+        max_burn_time,
+        |_, state| crashed(state) || apoapsis(site.mu, state) >= target_radius,
+    );
+    let burn_time = burn.end_time();
+    let cutoff = burn.end_state();
+    if crashed(&cutoff) {
+        return Err(String::from("Rocket fell back to the ground"));
+    }
+    if apoapsis(site.mu, &cutoff) < target_radius {
+        return Err(String::from("Rocket ran out of propellant on the way up"));
+    }
+
+    // Coast up to apoapsis, which drag might lower a bit
+    let cutoff_mass = vehicle.mass - mass_flow * burn_time;
+    let coast_accel = |_: f64, state: &PhaseState| gravity(state) + drag(state, cutoff_mass);
+    let coast = propagate_until(
+        integrator.as_mut(),
+        &coast_accel,
+        burn_time,
+        cutoff,
+        burn_time + MAX_COAST_TIME,
+        |_, state| crashed(state) || state.position.dot(&state.velocity) <= 0.0,
+    );
+    let top = coast.end_state();
+    let radius = top.position.norm();
+    if crashed(&top) || radius < site.radius + atmosphere_height {
+        return Err(String::from(
+            "Drag pulled the apoapsis back into the atmosphere",
+        ));
+    }
+
+    // Circularize there
+    let horizontal_speed = top.position.cross(&top.velocity).norm() / radius;
+    let orbital_speed = (site.mu / radius).sqrt();
+    let circularization_delta_v = orbital_speed - horizontal_speed;
+    let final_mass = cutoff_mass * (-circularization_delta_v / vehicle.exhaust_velocity).exp();
+    if final_mass < vehicle.mass - vehicle.propellant_mass {
+        return Err(String::from("Rocket ran out of propellant circularizing"));
+    }
+    let delta_v =
+        vehicle.exhaust_velocity * (vehicle.mass / cutoff_mass).ln() + circularization_delta_v;
+    let surface_speed = site.angular_velocity * site.radius;
+
+    let time_to_orbit = coast.end_time();
+    let num_samples = (time_to_orbit / PROFILE_INTERVAL).ceil() as usize;
+    let profile = (0..=num_samples)
+        .map(|i| {
+            let time = f64::min(i as f64 * PROFILE_INTERVAL, time_to_orbit);
+            let state = if time <= burn_time {
+                burn.state_at_time(time)
+            } else {
+                coast.state_at_time(time)
+            };
+            let angle = start.position.angle(&state.position);
+            AscentPoint {
+                time,
+                downrange: angle * site.radius,
+                altitude: state.position.norm() - site.radius,
+                speed: state.velocity.norm(),
+            }
+        })
+        .collect();
+
+    Ok(AscentEstimate {
+        delta_v,
+        circularization_delta_v,
+        losses: delta_v - (orbital_speed - surface_speed),
+        burn_time,
+        time_to_orbit,
+        altitude: radius - site.radius,
+        profile,
+    })
+}
+
+/// Distance of the apoapsis from the center of the body, or infinity if the
+/// orbit is open.
+fn apoapsis(mu: f64, state: &PhaseState) -> f64 {
+    let r = state.position.norm();
+    let energy = state.velocity.norm_squared() / 2.0 - mu / r;
+    if energy >= 0.0 {
+        return f64::INFINITY;
+    }
+    let h = state.position.cross(&state.velocity).norm();
+    let semimajor_axis = -mu / (2.0 * energy);
+    let eccentricity = (1.0 + 2.0 * energy * h * h / (mu * mu)).max(0.0).sqrt();
+    semimajor_axis * (1.0 + eccentricity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Kerbin
+    const KERBIN: LaunchSite = LaunchSite {
+        mu: 3.5316e12,
+        radius: 600e3,
+        angular_velocity: 2.0 * std::f64::consts::PI / 21549.425,
+        atmosphere: Some(Atmosphere {
+            surface_density: 1.225,
+            scale_height: 5600.0,
+            height: 70e3,
+        }),
+    };
+    const ROCKET: LaunchVehicle = LaunchVehicle {
+        mass: 20000.0,
+        propellant_mass: 15000.0,
+        thrust: 400e3,
+        exhaust_velocity: 3000.0,
+        drag_area: 1.0,
+    };
+    const TURN: GravityTurn = GravityTurn {
+        turn_start_altitude: 1000.0,
+        turn_end_altitude: 45e3,
+        shape: 0.5,
+    };
+
+    #[test]
+    fn test_kerbin_ascent() {
+        let estimate = estimate_ascent(&KERBIN, &ROCKET, &TURN, 80e3).unwrap();
+        // Getting to orbit around Kerbin takes about 3.2-3.4 km/s in the game
+        assert!(
+            (3000.0..3600.0).contains(&estimate.delta_v),
+            "{:?}",
+            estimate.delta_v
+        );
+        assert!(estimate.losses > 0.0);
+        assert!(estimate.circularization_delta_v > 0.0);
+        assert!(estimate.burn_time < estimate.time_to_orbit);
+        assert!((70e3..=80e3).contains(&estimate.altitude));
+
+        let first = estimate.profile.first().unwrap();
+        let last = estimate.profile.last().unwrap();
+        assert_eq!((first.time, first.altitude), (0.0, 0.0));
+        assert_eq!(last.time, estimate.time_to_orbit);
+        assert!(last.downrange > 0.0);
+    }
+
+    #[test]
+    fn test_drag_costs_delta_v() {
+        let airless = LaunchSite {
+            atmosphere: None,
+            ..KERBIN
+        };
+        let with_air = estimate_ascent(&KERBIN, &ROCKET, &TURN, 80e3).unwrap();
+        let without_air = estimate_ascent(&airless, &ROCKET, &TURN, 80e3).unwrap();
+        assert!(without_air.delta_v < with_air.delta_v);
+        // Without drag, nothing lowers the apoapsis on the way up to it
+        assert!((without_air.altitude - 80e3).abs() < 10.0);
+    }
+
+    #[test]
+    fn test_failed_ascents() {
+        assert!(estimate_ascent(&KERBIN, &ROCKET, &TURN, 60e3).is_err());
+
+        let heavy = LaunchVehicle {
+            thrust: 150e3,
+            ..ROCKET
+        };
+        assert!(estimate_ascent(&KERBIN, &heavy, &TURN, 80e3).is_err());
+
+        let small = LaunchVehicle {
+            propellant_mass: 8000.0,
+            ..ROCKET
+        };
+        assert!(estimate_ascent(&KERBIN, &small, &TURN, 80e3).is_err());
+    }
+}
@@ -0,0 +1,759 @@
+//! Numerical integrators, for propagating states that can't be handled
+//! analytically (e.g., under thrust, or perturbed by other bodies).
+//!
+//! All integrators implement the [Integrator] trait, and are normally created
+//! from an [IntegratorSettings], which picks the algorithm and its parameters.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use nalgebra::Vector3;
+
+/// Position and velocity, with no primary body attached.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhaseState {
+    pub position: Vector3<f64>,
+    pub velocity: Vector3<f64>,
+}
+
+/// Computes the acceleration at a given time and state.
+pub type AccelerationFn<'a> = dyn Fn(f64, &PhaseState) -> Vector3<f64> + 'a;
+
+/// A method for numerically integrating a [PhaseState] forward (or backward)
+/// in time.
+pub trait Integrator {
+    /// Advances `state`, starting at `time`, by a step no longer than `max_dt`
+    /// (which may be negative, to go backwards). Returns the new state and the
+    /// length of the step actually taken.
+    fn step(
+        &mut self,
+        accel: &AccelerationFn,
+        time: f64,
+        state: &PhaseState,
+        max_dt: f64,
+    ) -> (PhaseState, f64);
+}
+
+/// Which integration algorithm to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IntegratorKind {
+    /// Classic fixed-step, fourth-order Runge-Kutta.
+    Rk4,
+    /// Adaptive Dormand-Prince 5(4), which picks its own step size to meet the
+    /// tolerance.
+    #[default]
+    DormandPrince,
+    /// Fixed-step, second-order, kick-drift-kick leapfrog. This is symplectic,
+    /// so energy doesn't drift over long periods, but only when the
+    /// acceleration depends on position alone.
+    Leapfrog,
+}
+
+/// Everything needed to construct an [Integrator].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IntegratorSettings {
+    pub kind: IntegratorKind,
+    /// Step size, in seconds. For adaptive integrators, this is the size of
+    /// the first step attempted, and the largest step allowed.
+    pub step_size: f64,
+    /// Maximum relative error allowed per step. Only used by adaptive
+    /// integrators.
+    pub tolerance: f64,
+}
+
+impl Default for IntegratorSettings {
+    fn default() -> Self {
+        Self {
+            kind: IntegratorKind::default(),
+            step_size: 60.0,
+            tolerance: 1e-10,
+        }
+    }
+}
+
+impl IntegratorSettings {
+    pub fn build(&self) -> Box<dyn Integrator> {
+        match self.kind {
+            IntegratorKind::Rk4 => Box::new(Rk4 {
+                step_size: self.step_size,
+            }),
+            IntegratorKind::DormandPrince => Box::new(DormandPrince {
+                max_step_size: self.step_size,
+                next_step_size: self.step_size,
+                tolerance: self.tolerance,
+            }),
+            IntegratorKind::Leapfrog => Box::new(Leapfrog {
+                step_size: self.step_size,
+            }),
+        }
+    }
+}
+
+/// Integrates from `start_time` to `end_time`, taking as many steps as
+/// necessary.
+pub fn propagate(
+    integrator: &mut dyn Integrator,
+    accel: &AccelerationFn,
+    start_time: f64,
+    state: PhaseState,
+    end_time: f64,
+) -> PhaseState {
+    let mut time = start_time;
+    let mut state = state;
+    while time != end_time {
+        let (new_state, dt) = integrator.step(accel, time, &state, end_time - time);
+        state = new_state;
+        // Avoid getting stuck a rounding error away from the end
+        time = if (end_time - time - dt).abs() <= f64::EPSILON * end_time.abs() {
+            end_time
+        } else {
+            time + dt
+        };
+    }
+    state
+}
+
+// Number of bisection steps used to pin down when a stopping condition is met
+const NUM_ITERATIONS_STOP: usize = 60;
+
+/// The output of an integration, sampled after every step.
+///
+/// States between samples are interpolated with cubic Hermite splines, which
+/// use both the position and velocity at each sample.
+#[derive(Debug, Clone)]
+pub struct SampledPath {
+    // Invariant: there's at least one sample, and they're sorted by time.
+    samples: Vec<(f64, PhaseState)>,
+}
+
+/// Integrates forward from `start_time` until `stop` returns true, or until
+/// `max_time`, recording every step along the way.
+///
+/// When `stop` becomes true partway through a step, the exact time is found by
+/// bisection on the interpolated path, and the path ends there.
+pub fn propagate_until(
+    integrator: &mut dyn Integrator,
+    accel: &AccelerationFn,
+    start_time: f64,
+    state: PhaseState,
+    max_time: f64,
+    stop: impl Fn(f64, &PhaseState) -> bool,
+) -> SampledPath {
+    assert!(
+        start_time <= max_time,
+        "Can only integrate forwards: {} > {}",
+        start_time,
+        max_time
+    );
+
+    let mut path = SampledPath {
+        samples: vec![(start_time, state)],
+    };
+    let mut time = start_time;
+    let mut state = state;
+    while time != max_time {
+        let (new_state, dt) = integrator.step(accel, time, &state, max_time - time);
+        let new_time = if (max_time - time - dt).abs() <= f64::EPSILON * max_time.abs() {
+            max_time
+        } else {
+            time + dt
+        };
+        path.samples.push((new_time, new_state));
+
+        if stop(new_time, &new_state) {
+            // Narrow down the first time the condition holds, and cut the path off there
+            let (mut lo, mut hi) = (time, new_time);
+            for _ in 0..NUM_ITERATIONS_STOP {
+                let mid = (lo + hi) / 2.0;
+                if stop(mid, &path.state_at_time(mid)) {
+                    hi = mid;
+                } else {
+                    lo = mid;
+                }
+            }
+            let end_state = path.state_at_time(hi);
+            *path.samples.last_mut().unwrap() = (hi, end_state);
+            break;
+        }
+
+        time = new_time;
+        state = new_state;
+    }
+    path
+}
+
+impl SampledPath {
+    pub fn start_time(&self) -> f64 {
+        self.samples[0].0
+    }
+
+    pub fn end_time(&self) -> f64 {
+        self.samples.last().unwrap().0
+    }
+
+    pub fn end_state(&self) -> PhaseState {
+        self.samples.last().unwrap().1
+    }
+
+    /// Whether the time is within the path, including both endpoints.
+    pub fn contains_time(&self, time: f64) -> bool {
+        self.start_time() <= time && time <= self.end_time()
+    }
+
+    /// Interpolates the state at the given time. Times outside the path are
+    /// extrapolated from the nearest step, which is unlikely to be accurate.
+    pub fn state_at_time(&self, time: f64) -> PhaseState {
+        if self.samples.len() == 1 {
+            return self.samples[0].1;
+        }
+
+        // Find the step containing this time
+        let idx = self
+            .samples
+            .partition_point(|(t, _)| *t <= time)
+            .clamp(1, self.samples.len() - 1);
+        let (t0, s0) = &self.samples[idx - 1];
+        let (t1, s1) = &self.samples[idx];
+
+        let h = t1 - t0;
+        let u = (time - t0) / h;
+        let (u2, u3) = (u * u, u * u * u);
+
+        // Hermite basis functions, and their derivatives with respect to u
+        let h00 = 2.0 * u3 - 3.0 * u2 + 1.0;
+        let h10 = u3 - 2.0 * u2 + u;
+        let h01 = -2.0 * u3 + 3.0 * u2;
+        let h11 = u3 - u2;
+        let dh00 = 6.0 * u2 - 6.0 * u;
+        let dh10 = 3.0 * u2 - 4.0 * u + 1.0;
+        let dh01 = -6.0 * u2 + 6.0 * u;
+        let dh11 = 3.0 * u2 - 2.0 * u;
+
+        let position =
+            h00 * s0.position + h10 * h * s0.velocity + h01 * s1.position + h11 * h * s1.velocity;
+        let velocity = (dh00 * s0.position
+            + dh10 * h * s0.velocity
+            + dh01 * s1.position
+            + dh11 * h * s1.velocity)
+            / h;
+        PhaseState::new(position, velocity)
+    }
+}
+
+/// Relative drift in specific orbital energy, per revolution, above which an
+/// integrator is considered too inaccurate.
+pub const MAX_ENERGY_DRIFT: f64 = 1e-8;
+// Limits on how far we'll push an integrator before recommending a different
+// one. Below these, round-off error starts to dominate, or steps get
+// impractically small.
+const MIN_STEP_SIZE: f64 = 1.0;
+const MIN_TOLERANCE: f64 = 1e-13;
+
+/// How well an integrator conserves energy on a particular orbit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnergyDrift {
+    /// Relative change in specific orbital energy after one revolution.
+    pub per_revolution: f64,
+    /// If the drift exceeds [MAX_ENERGY_DRIFT], some settings that should do
+    /// better.
+    pub recommendation: Option<IntegratorSettings>,
+}
+
+impl EnergyDrift {
+    pub fn is_acceptable(&self) -> bool {
+        self.per_revolution <= MAX_ENERGY_DRIFT
+    }
+}
+
+/// Measures how much the specific orbital energy of a path, integrated with
+/// the given settings around a point mass, drifts from what it should be.
+/// It's scaled to one revolution of the orbit the path starts on.
+///
+/// Anything in `accel` besides the point mass's gravity, like thrust or
+/// another body, changes the energy for real, so the work it does along the
+/// path is taken off first. That's found by Simpson's rule on each step.
+///
+/// Returns `None` if the path doesn't start on a closed orbit, since there's
+/// no revolution to scale to, or if it doesn't go anywhere.
+pub fn measure_energy_drift(
+    settings: &IntegratorSettings,
+    mu: f64,
+    accel: &AccelerationFn,
+    path: &SampledPath,
+) -> Option<EnergyDrift> {
+    let energy = |s: &PhaseState| s.velocity.norm_squared() / 2.0 - mu / s.position.norm();
+    let power = |t: f64, s: &PhaseState| {
+        let r = s.position.norm();
+        let other_accel = accel(t, s) + mu / (r * r * r) * s.position;
+        s.velocity.dot(&other_accel)
+    };
+
+    let (start_time, start_state) = path.samples[0];
+    let start_energy = energy(&start_state);
+    let duration = path.end_time() - start_time;
+    if start_energy >= 0.0 || duration <= 0.0 {
+        return None;
+    }
+
+    let mut work = 0.0;
+    for pair in path.samples.windows(2) {
+        let ((t0, s0), (t1, s1)) = (pair[0], pair[1]);
+        let midpoint = (t0 + t1) / 2.0;
+        let sm = path.state_at_time(midpoint);
+        work += (t1 - t0) / 6.0 * (power(t0, &s0) + 4.0 * power(midpoint, &sm) + power(t1, &s1));
+    }
+    let drift = ((energy(&path.end_state()) - start_energy - work) / start_energy).abs();
+
+    let sma = -mu / (2.0 * start_energy);
+    let period = 2.0 * std::f64::consts::PI * (sma.powi(3) / mu).sqrt();
+    let per_revolution = drift * period / duration;
+    Some(EnergyDrift {
+        per_revolution,
+        recommendation: settings.recommend_for_drift(per_revolution),
+    })
+}
+
+impl IntegratorSettings {
+    /// Suggests settings that should bring the given energy drift (per
+    /// revolution) under [MAX_ENERGY_DRIFT], or `None` if it's already fine.
+    ///
+    /// Fixed-step integrators get a smaller step, based on their order, and
+    /// adaptive ones a tighter tolerance. If that would go past what's
+    /// practical, we switch to Dormand-Prince instead.
+    pub fn recommend_for_drift(&self, drift: f64) -> Option<IntegratorSettings> {
+        if drift <= MAX_ENERGY_DRIFT {
+            return None;
+        }
+
+        // Aim for half the limit, so we're not right on the edge
+        let improvement = 0.5 * MAX_ENERGY_DRIFT / drift;
+        let fixed_step = |order: i32| {
+            let step_size = self.step_size * improvement.powf(1.0 / order as f64);
+            if step_size >= MIN_STEP_SIZE {
+                IntegratorSettings { step_size, ..*self }
+            } else {
+                IntegratorSettings {
+                    kind: IntegratorKind::DormandPrince,
+                    ..Default::default()
+                }
+            }
+        };
+
+        let recommendation = match self.kind {
+            IntegratorKind::Rk4 => fixed_step(4),
+            IntegratorKind::Leapfrog => fixed_step(2),
+            IntegratorKind::DormandPrince => {
+                // Nothing left to try
+                if self.tolerance <= MIN_TOLERANCE {
+                    return None;
+                }
+                IntegratorSettings {
+                    tolerance: f64::max(self.tolerance * improvement, MIN_TOLERANCE),
+                    ..*self
+                }
+            }
+        };
+        Some(recommendation)
+    }
+}
+
+impl PhaseState {
+    pub fn new(position: Vector3<f64>, velocity: Vector3<f64>) -> Self {
+        Self { position, velocity }
+    }
+
+    /// Returns the time derivative of this state: (velocity, acceleration).
+    fn derivative(&self, accel: &AccelerationFn, time: f64) -> PhaseState {
+        PhaseState {
+            position: self.velocity,
+            velocity: accel(time, self),
+        }
+    }
+
+    /// Returns this state plus a weighted sum of derivatives, scaled by `dt`.
+    fn offset(&self, dt: f64, terms: &[(f64, &PhaseState)]) -> PhaseState {
+        let mut result = *self;
+        for (weight, derivative) in terms {
+            result.position += dt * weight * derivative.position;
+            result.velocity += dt * weight * derivative.velocity;
+        }
+        result
+    }
+}
+
+/// Limits `step_size` to be no longer than `max_dt`, and in the same direction.
+fn clamp_step(step_size: f64, max_dt: f64) -> f64 {
+    step_size.abs().min(max_dt.abs()).copysign(max_dt)
+}
+
+struct Rk4 {
+    step_size: f64,
+}
+
+impl Integrator for Rk4 {
+    fn step(
+        &mut self,
+        accel: &AccelerationFn,
+        time: f64,
+        state: &PhaseState,
+        max_dt: f64,
+    ) -> (PhaseState, f64) {
+        let dt = clamp_step(self.step_size, max_dt);
+
+        let k1 = state.derivative(accel, time);
+        let k2 = state
+            .offset(dt, &[(0.5, &k1)])
+            .derivative(accel, time + dt / 2.0);
+        let k3 = state
+            .offset(dt, &[(0.5, &k2)])
+            .derivative(accel, time + dt / 2.0);
+        let k4 = state.offset(dt, &[(1.0, &k3)]).derivative(accel, time + dt);
+
+        let new_state = state.offset(
+            dt,
+            &[
+                (1.0 / 6.0, &k1),
+                (1.0 / 3.0, &k2),
+                (1.0 / 3.0, &k3),
+                (1.0 / 6.0, &k4),
+            ],
+        );
+        (new_state, dt)
+    }
+}
+
+struct Leapfrog {
+    step_size: f64,
+}
+
+impl Integrator for Leapfrog {
+    fn step(
+        &mut self,
+        accel: &AccelerationFn,
+        time: f64,
+        state: &PhaseState,
+        max_dt: f64,
+    ) -> (PhaseState, f64) {
+        let dt = clamp_step(self.step_size, max_dt);
+
+        // Kick, drift, kick
+        let half_v = state.velocity + dt / 2.0 * accel(time, state);
+        let new_position = state.position + dt * half_v;
+        let drifted = PhaseState::new(new_position, half_v);
+        let new_v = half_v + dt / 2.0 * accel(time + dt, &drifted);
+
+        (PhaseState::new(new_position, new_v), dt)
+    }
+}
+
+struct DormandPrince {
+    max_step_size: f64,
+    next_step_size: f64,
+    tolerance: f64,
+}
+
+// Butcher tableau for Dormand-Prince 5(4)
+const DP_C: [f64; 7] = [0.0, 1.0 / 5.0, 3.0 / 10.0, 4.0 / 5.0, 8.0 / 9.0, 1.0, 1.0];
+const DP_A: [&[f64]; 7] = [
+    &[],
+    &[1.0 / 5.0],
+    &[3.0 / 40.0, 9.0 / 40.0],
+    &[44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0],
+    &[
+        19372.0 / 6561.0,
+        -25360.0 / 2187.0,
+        64448.0 / 6561.0,
+        -212.0 / 729.0,
+    ],
+    &[
+        9017.0 / 3168.0,
+        -355.0 / 33.0,
+        46732.0 / 5247.0,
+        49.0 / 176.0,
+        -5103.0 / 18656.0,
+    ],
+    &[
+        35.0 / 384.0,
+        0.0,
+        500.0 / 1113.0,
+        125.0 / 192.0,
+        -2187.0 / 6784.0,
+        11.0 / 84.0,
+    ],
+];
+// Difference between the fifth-order weights (the last row of A) and the
+// fourth-order ones
+const DP_ERROR: [f64; 7] = [
+    71.0 / 57600.0,
+    0.0,
+    -71.0 / 16695.0,
+    71.0 / 1920.0,
+    -17253.0 / 339200.0,
+    22.0 / 525.0,
+    -1.0 / 40.0,
+];
+
+impl Integrator for DormandPrince {
+    fn step(
+        &mut self,
+        accel: &AccelerationFn,
+        time: f64,
+        state: &PhaseState,
+        max_dt: f64,
+    ) -> (PhaseState, f64) {
+        loop {
+            let dt = clamp_step(self.next_step_size, max_dt);
+
+            let mut k: Vec<PhaseState> = Vec::with_capacity(7);
+            for (c, a) in DP_C.iter().zip(DP_A) {
+                let terms: Vec<_> = a.iter().copied().zip(k.iter()).collect();
+                let stage = state.offset(dt, &terms);
+                k.push(stage.derivative(accel, time + c * dt));
+            }
+
+            // The seventh stage is evaluated at the fifth-order solution, so we can
+            // reconstruct it from k.
+            let terms: Vec<_> = DP_A[6].iter().copied().zip(k.iter()).collect();
+            let new_state = state.offset(dt, &terms);
+            let terms: Vec<_> = DP_ERROR.iter().copied().zip(k.iter()).collect();
+            let error = PhaseState::new(Vector3::zeros(), Vector3::zeros()).offset(dt, &terms);
+
+            // Compare the error to the size of the state, separately for position and
+            // velocity, since they have different units.
+            let scale = |x: f64, y: f64| self.tolerance * f64::max(x, y).max(f64::MIN_POSITIVE);
+            let error_ratio = f64::max(
+                error.position.norm() / scale(state.position.norm(), new_state.position.norm()),
+                error.velocity.norm() / scale(state.velocity.norm(), new_state.velocity.norm()),
+            );
+
+            // Standard step-size controller, with some safety margins
+            let factor = if error_ratio == 0.0 {
+                5.0
+            } else {
+                (0.9 * error_ratio.powf(-0.2)).clamp(0.2, 5.0)
+            };
+            self.next_step_size = f64::min(dt.abs() * factor, self.max_step_size);
+
+            if error_ratio <= 1.0 {
+                return (new_state, dt);
+            }
+        }
+    }
+}
+
+impl FromStr for IntegratorKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "rk4" => Ok(IntegratorKind::Rk4),
+            "dormand-prince" => Ok(IntegratorKind::DormandPrince),
+            "leapfrog" => Ok(IntegratorKind::Leapfrog),
+            _ => Err(format!(
+                "Unknown integrator {:?}; expected rk4, dormand-prince, or leapfrog",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for IntegratorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            IntegratorKind::Rk4 => "rk4",
+            IntegratorKind::DormandPrince => "dormand-prince",
+            IntegratorKind::Leapfrog => "leapfrog",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Display for IntegratorSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            IntegratorKind::DormandPrince => write!(
+                f,
+                "{} (max step {} s, tolerance {:e})",
+                self.kind, self.step_size, self.tolerance
+            ),
+            IntegratorKind::Rk4 | IntegratorKind::Leapfrog => {
+                write!(f, "{} (step {} s)", self.kind, self.step_size)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::testing_utils::assert_vectors_close;
+
+    fn kepler_accel(mu: f64) -> impl Fn(f64, &PhaseState) -> Vector3<f64> {
+        move |_, state| {
+            let r = state.position.norm();
+            -mu / (r * r * r) * state.position
+        }
+    }
+
+    fn energy(mu: f64, state: &PhaseState) -> f64 {
+        state.velocity.norm_squared() / 2.0 - mu / state.position.norm()
+    }
+
+    #[test]
+    fn test_circular_orbit() {
+        // Unit circular orbit, with period 2pi
+        let mu = 1.0;
+        let accel = kepler_accel(mu);
+        let start = PhaseState::new(Vector3::x(), Vector3::y());
+
+        // (kind, step size, expected accuracy after one orbit)
+        let cases = [
+            (IntegratorKind::Rk4, 0.01, 1e-8),
+            (IntegratorKind::DormandPrince, 1.0, 1e-8),
+            (IntegratorKind::Leapfrog, 0.001, 1e-5),
+        ];
+
+        for (kind, step_size, accuracy) in cases {
+            let settings = IntegratorSettings {
+                kind,
+                step_size,
+                tolerance: 1e-10,
+            };
+            let mut integrator = settings.build();
+
+            // Half an orbit should put us on the other side
+            let half = propagate(integrator.as_mut(), &accel, 0.0, start, PI);
+            assert_vectors_close(&-Vector3::x(), &half.position, accuracy);
+            assert_vectors_close(&-Vector3::y(), &half.velocity, accuracy);
+
+            let full = propagate(integrator.as_mut(), &accel, PI, half, 2.0 * PI);
+            assert_vectors_close(&start.position, &full.position, accuracy);
+            assert_vectors_close(&start.velocity, &full.velocity, accuracy);
+
+            // And going backwards should get us back to the middle
+            let back = propagate(integrator.as_mut(), &accel, 2.0 * PI, full, PI);
+            assert_vectors_close(&half.position, &back.position, accuracy);
+        }
+    }
+
+    #[test]
+    fn test_leapfrog_energy() {
+        // An eccentric orbit, integrated for many revolutions. The leapfrog
+        // integrator shouldn't drift in energy, even with a coarse step.
+        let mu = 1.0;
+        let accel = kepler_accel(mu);
+        let start = PhaseState::new(Vector3::x(), Vector3::y() * 1.2);
+        let initial_energy = energy(mu, &start);
+
+        let settings = IntegratorSettings {
+            kind: IntegratorKind::Leapfrog,
+            step_size: 0.01,
+            tolerance: 0.0,
+        };
+        let mut integrator = settings.build();
+
+        let mut state = start;
+        for i in 0..50 {
+            state = propagate(integrator.as_mut(), &accel, i as f64, state, (i + 1) as f64);
+        }
+        let drift = (energy(mu, &state) - initial_energy) / initial_energy;
+        assert!(drift.abs() < 1e-3, "Energy drifted by {}", drift);
+    }
+
+    #[test]
+    fn test_propagate_until() {
+        // Unit circular orbit, stopping when we cross the x-axis from above
+        let mu = 1.0;
+        let accel = kepler_accel(mu);
+        let start = PhaseState::new(Vector3::x(), Vector3::y());
+
+        let mut integrator = IntegratorSettings {
+            kind: IntegratorKind::DormandPrince,
+            step_size: 0.1,
+            tolerance: 1e-12,
+        }
+        .build();
+        let path = propagate_until(integrator.as_mut(), &accel, 0.0, start, 10.0, |_, s| {
+            s.position.y < 0.0
+        });
+
+        // We should stop at the far side, half an orbit later
+        assert!((path.end_time() - PI).abs() < 1e-8);
+        assert_vectors_close(&-Vector3::x(), &path.end_state().position, 1e-8);
+
+        // Interpolating in between steps should stay on the circle
+        for i in 0..30 {
+            let time = PI * i as f64 / 30.0;
+            assert!(path.contains_time(time));
+            let state = path.state_at_time(time);
+            let expected = Vector3::new(time.cos(), time.sin(), 0.0);
+            assert_vectors_close(&expected, &state.position, 1e-6);
+        }
+
+        // If we never stop, we go until the max time
+        let path = propagate_until(integrator.as_mut(), &accel, 0.0, start, 1.0, |_, _| false);
+        assert_eq!(path.end_time(), 1.0);
+    }
+
+    #[test]
+    fn test_energy_drift() {
+        // An eccentric orbit around Kerbin, starting at periapsis
+        let mu: f64 = 3.5316e12;
+        let (r_p, a): (f64, f64) = (700_000.0, 1_400_000.0);
+        let v_p = (mu * (2.0 / r_p - 1.0 / a)).sqrt();
+        let start = PhaseState::new(Vector3::x() * r_p, Vector3::y() * v_p);
+        let period = 2.0 * PI * (a * a * a / mu).sqrt();
+
+        // A coarse RK4 drifts too much
+        let coarse = IntegratorSettings {
+            kind: IntegratorKind::Rk4,
+            step_size: 60.0,
+            tolerance: 0.0,
+        };
+        let accel = kepler_accel(mu);
+        let drift_with = |settings: &IntegratorSettings, accel: &AccelerationFn| {
+            let path = propagate_until(
+                settings.build().as_mut(),
+                accel,
+                0.0,
+                start,
+                period,
+                |_, _| false,
+            );
+            measure_energy_drift(settings, mu, accel, &path).unwrap()
+        };
+        let drift = drift_with(&coarse, &accel);
+        assert!(!drift.is_acceptable());
+
+        // ...but following the recommendation fixes it
+        let recommendation = drift.recommendation.unwrap();
+        assert_eq!(recommendation.kind, IntegratorKind::Rk4);
+        assert!(recommendation.step_size < coarse.step_size);
+        let drift = drift_with(&recommendation, &accel);
+        assert!(drift.is_acceptable(), "Drift was {}", drift.per_revolution);
+        assert_eq!(drift.recommendation, None);
+
+        // Thrust changes the energy, but that's not drift
+        let thrust = |t: f64, s: &PhaseState| accel(t, s) + s.velocity.normalize() * 0.01;
+        let drift = drift_with(&recommendation, &thrust);
+        assert!(drift.is_acceptable(), "Drift was {}", drift.per_revolution);
+
+        // A fixed step that would need to be too short gets swapped out
+        let drift = coarse.recommend_for_drift(1.0).unwrap();
+        assert_eq!(drift.kind, IntegratorKind::DormandPrince);
+    }
+
+    #[test]
+    fn test_parse_kind() {
+        for kind in [
+            IntegratorKind::Rk4,
+            IntegratorKind::DormandPrince,
+            IntegratorKind::Leapfrog,
+        ] {
+            assert_eq!(kind.to_string().parse::<IntegratorKind>(), Ok(kind));
+        }
+        assert!("euler".parse::<IntegratorKind>().is_err());
+    }
+}
@@ -0,0 +1,205 @@
+//! Planning for continuous, low-thrust transfers between circular orbits, such
+//! as with ion engines.
+//!
+//! Thrust is modeled as a constant acceleration, which is a fair approximation
+//! as long as the propellant used is a small fraction of the ship's mass.
+
+use std::f64::consts::PI;
+
+use nalgebra::Vector3;
+
+use super::integrate::{propagate_until, IntegratorSettings, PhaseState, SampledPath};
+
+// Number of intervals used when integrating the revolution count
+const NUM_REVOLUTION_INTERVALS: usize = 1000;
+// How many times per revolution of the smaller orbit to sample a numeric path
+// when counting its revolutions
+const SAMPLES_PER_REVOLUTION: f64 = 32.0;
+
+/// The cost and duration of a low-thrust transfer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpiralTransfer {
+    pub delta_v: f64,
+    pub time_of_flight: f64,
+    /// How many times the ship goes around its primary during the transfer.
+    pub revolutions: f64,
+}
+
+/// Estimates a transfer between two circular orbits, of radius `r0` and `r1`,
+/// with an inclination change of `inclination_change` radians, under a
+/// constant `acceleration`.
+///
+/// This uses Edelbaum's analytic solution, which assumes the orbit stays
+/// nearly circular the whole way, and that the thrust direction is held
+/// constant within each revolution (yawing out of plane, with its sign flipping
+/// at the antinodes). This works well when the acceleration is small compared
+/// to the local gravity.
+pub fn edelbaum_transfer(
+    mu: f64,
+    r0: f64,
+    r1: f64,
+    inclination_change: f64,
+    acceleration: f64,
+) -> SpiralTransfer {
+    let v0 = (mu / r0).sqrt();
+    let v1 = (mu / r1).sqrt();
+    let half_angle = PI / 2.0 * inclination_change.abs();
+
+    let delta_v = (v0 * v0 - 2.0 * v0 * v1 * half_angle.cos() + v1 * v1).sqrt();
+    let time_of_flight = delta_v / acceleration;
+
+    // Initial yaw angle, between the thrust and the velocity
+    let yaw = half_angle.sin().atan2(v0 / v1 - half_angle.cos());
+
+    // The circular speed varies with time, and we go around at v^3 / mu radians
+    // per second. Integrate that with Simpson's rule.
+    let speed = |t: f64| {
+        (v0 * v0 - 2.0 * v0 * acceleration * t * yaw.cos() + (acceleration * t).powi(2)).sqrt()
+    };
+    let angular_rate = |t: f64| speed(t).powi(3) / mu;
+    let h = time_of_flight / NUM_REVOLUTION_INTERVALS as f64;
+    let interior: f64 = (1..NUM_REVOLUTION_INTERVALS)
+        .map(|i| {
+            let weight = if i % 2 == 1 { 4.0 } else { 2.0 };
+            weight * angular_rate(i as f64 * h)
+        })
+        .sum();
+    let angle = h / 3.0 * (angular_rate(0.0) + interior + angular_rate(time_of_flight));
+
+    SpiralTransfer {
+        delta_v,
+        time_of_flight,
+        revolutions: angle / (2.0 * PI),
+    }
+}
+
+/// Numerically integrates a coplanar transfer from a circular orbit of radius
+/// `r0`, thrusting along (or against) the velocity with a constant
+/// `acceleration`, until the semi-major axis reaches `r1`. The final orbit
+/// isn't quite circular, but for low thrust it's close.
+///
+/// Returns the transfer, and the path that was integrated, starting on the x
+/// axis and going counterclockwise around the z axis, at time zero.
+pub fn propagate_spiral(
+    settings: &IntegratorSettings,
+    mu: f64,
+    r0: f64,
+    r1: f64,
+    acceleration: f64,
+) -> (SpiralTransfer, SampledPath) {
+    let direction = if r1 > r0 { 1.0 } else { -1.0 };
+    let accel = |_: f64, s: &PhaseState| {
+        let r = s.position.norm();
+        -mu / (r * r * r) * s.position + direction * acceleration * s.velocity.normalize()
+    };
+    let semimajor_axis =
+        |s: &PhaseState| 1.0 / (2.0 / s.position.norm() - s.velocity.norm_squared() / mu);
+    let stop = |_: f64, s: &PhaseState| direction * (semimajor_axis(s) - r1) >= 0.0;
+
+    // Give it plenty of time; thrust can't be more efficient than impulsive
+    let start = PhaseState::new(r0 * Vector3::x(), (mu / r0).sqrt() * Vector3::y());
+    let max_delta_v = edelbaum_transfer(mu, r0, r1, 0.0, acceleration).delta_v;
+    let max_time = 2.0 * max_delta_v / acceleration;
+    let mut integrator = settings.build();
+    let path = propagate_until(integrator.as_mut(), &accel, 0.0, start, max_time, stop);
+
+    // Count how far we went around, sampling finely enough not to skip a
+    // revolution
+    let time_of_flight = path.end_time();
+    let min_radius = f64::min(r0, r1);
+    let min_period = 2.0 * PI * (min_radius.powi(3) / mu).sqrt();
+    let num_samples = (time_of_flight / min_period * SAMPLES_PER_REVOLUTION).ceil() as usize;
+    let mut angle = 0.0;
+    let mut previous = start.position;
+    for i in 1..=num_samples {
+        let time = time_of_flight * i as f64 / num_samples as f64;
+        let position = path.state_at_time(time).position;
+        angle += previous.cross(&position).z.atan2(previous.dot(&position));
+        previous = position;
+    }
+
+    let transfer = SpiralTransfer {
+        delta_v: acceleration * time_of_flight,
+        time_of_flight,
+        revolutions: angle / (2.0 * PI),
+    };
+    (transfer, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    // Kerbin
+    const MU: f64 = 3.5316e12;
+
+    #[test]
+    fn test_edelbaum_coplanar() {
+        // Without a plane change, it's the difference in circular speeds, and
+        // the revolutions have a closed form.
+        let (r0, r1, acceleration) = (700e3, 2863.33e3, 0.01);
+        let transfer = edelbaum_transfer(MU, r0, r1, 0.0, acceleration);
+        let (v0, v1) = ((MU / r0).sqrt(), (MU / r1).sqrt());
+        assert_relative_eq!(transfer.delta_v, v0 - v1);
+        assert_relative_eq!(transfer.time_of_flight, (v0 - v1) / acceleration);
+        assert_relative_eq!(
+            transfer.revolutions,
+            (v0.powi(4) - v1.powi(4)) / (8.0 * PI * MU * acceleration),
+            max_relative = 1e-9
+        );
+
+        // Going down costs the same
+        let reverse = edelbaum_transfer(MU, r1, r0, 0.0, acceleration);
+        assert_relative_eq!(reverse.delta_v, transfer.delta_v);
+        assert_relative_eq!(
+            reverse.revolutions,
+            transfer.revolutions,
+            max_relative = 1e-9
+        );
+    }
+
+    #[test]
+    fn test_edelbaum_plane_change() {
+        // A pure plane change at constant radius
+        let r = 700e3;
+        let v = (MU / r).sqrt();
+        let di = 30f64.to_radians();
+        let transfer = edelbaum_transfer(MU, r, r, di, 0.01);
+        assert_relative_eq!(transfer.delta_v, 2.0 * v * (PI * di / 4.0).sin());
+
+        // Which is more than the impulsive cost, but not absurdly so
+        let impulsive = 2.0 * v * (di / 2.0).sin();
+        assert!(transfer.delta_v > impulsive);
+        assert!(transfer.delta_v < 1.6 * impulsive);
+
+        // Combining it with a raise is cheaper than doing them separately
+        let combined = edelbaum_transfer(MU, r, 4.0 * r, di, 0.01);
+        let raise = edelbaum_transfer(MU, r, 4.0 * r, 0.0, 0.01);
+        let high_plane_change = edelbaum_transfer(MU, 4.0 * r, 4.0 * r, di, 0.01);
+        assert!(combined.delta_v < raise.delta_v + high_plane_change.delta_v);
+        assert!(combined.delta_v > raise.delta_v);
+    }
+
+    #[test]
+    fn test_numeric_spiral() {
+        let (r0, r1, acceleration) = (700e3, 2863.33e3, 0.01);
+        let analytic = edelbaum_transfer(MU, r0, r1, 0.0, acceleration);
+
+        // Raising and lowering should both agree with the estimate
+        for (from, to) in [(r0, r1), (r1, r0)] {
+            let (transfer, path) =
+                propagate_spiral(&IntegratorSettings::default(), MU, from, to, acceleration);
+            assert_relative_eq!(transfer.delta_v, analytic.delta_v, max_relative = 0.01);
+            assert_relative_eq!(
+                transfer.revolutions,
+                analytic.revolutions,
+                max_relative = 0.01
+            );
+
+            // And we should end up near the right radius
+            assert_relative_eq!(path.end_state().position.norm(), to, max_relative = 0.01);
+        }
+    }
+}
@@ -8,6 +8,9 @@
 //!     [TimedOrbit]
 //! - [CartesianState], which represents a position and velocity
 
+pub mod ascent;
+pub mod integrate;
+pub mod low_thrust;
 mod orbit;
 mod orbit_methods;
 mod state;
@@ -15,7 +18,10 @@ mod state;
 // Newton's gravitational constant, in N m^2 / kg^2
 pub const NEWTON_G: f64 = 6.6743015e-11;
 
-pub use orbit::{BareOrbit, Orbit, OrbitBase, PhysicalOrbit, TimedOrbit};
+pub use orbit::{
+    AngleConvention, BPlane, BareOrbit, KeplerianElements, Orbit, OrbitBase, PhysicalOrbit,
+    RelativeNodes, TimedOrbit,
+};
 pub use state::CartesianState;
 
 /// A point mass with no other physical properties.
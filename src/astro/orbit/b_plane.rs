@@ -0,0 +1,135 @@
+use nalgebra::{Unit, Vector3};
+
+use super::OrbitBase;
+
+/// The B-plane parameters of a hyperbolic orbit, the standard way of
+/// describing where a flyby is aimed.
+///
+/// The B-plane passes through the primary, perpendicular to the incoming
+/// asymptote S. The B vector goes from the primary to where the asymptote
+/// pierces that plane, and is measured along two axes within it: T, which is
+/// parallel to the reference plane, and R = S x T, which points "down" from it.
+#[derive(Debug, Clone, Copy)]
+pub struct BPlane {
+    /// Direction the ship approaches from infinity along.
+    pub incoming_asymptote: Unit<Vector3<f64>>,
+    pub b_vector: Vector3<f64>,
+    pub b_dot_t: f64,
+    pub b_dot_r: f64,
+}
+
+impl BPlane {
+    /// Length of the B vector, i.e., the impact parameter.
+    pub fn magnitude(&self) -> f64 {
+        self.b_vector.norm()
+    }
+
+    /// Angle of the B vector, measured from T towards R.
+    pub fn angle(&self) -> f64 {
+        self.b_dot_r.atan2(self.b_dot_t)
+    }
+}
+
+impl<P, S, E> OrbitBase<P, S, E> {
+    /// Computes the B-plane parameters of this orbit, relative to the plane
+    /// with the given normal (usually the primary's equator).
+    ///
+    /// Returns `None` if the orbit isn't hyperbolic, or if the incoming
+    /// asymptote is parallel to the pole, in which case T is undefined.
+    pub fn b_plane(&self, pole: &Unit<Vector3<f64>>) -> Option<BPlane> {
+        let ecc = self.eccentricity();
+        if ecc <= 1.0 {
+            return None;
+        }
+
+        // In the perifocal frame, the incoming asymptote is at true anomaly
+        // -acos(-1/e), which works out to this.
+        let periapsis = self.periapse_vector();
+        let normal = self.normal_vector();
+        let q = normal.cross(&periapsis);
+        let incoming_asymptote = Unit::new_normalize(
+            periapsis.into_inner() / ecc + (1.0 - 1.0 / (ecc * ecc)).sqrt() * q,
+        );
+
+        // The impact parameter is the semi-minor axis, which points towards
+        // periapsis, away from the asymptote.
+        let b_magnitude = self.semilatus_rectum() / (ecc * ecc - 1.0).sqrt();
+        let b_vector = b_magnitude * incoming_asymptote.cross(&normal);
+
+        let t_axis = Unit::try_new(incoming_asymptote.cross(pole), 1e-12)?;
+        let r_axis = incoming_asymptote.cross(&t_axis);
+
+        Some(BPlane {
+            incoming_asymptote,
+            b_vector,
+            b_dot_t: b_vector.dot(&t_axis),
+            b_dot_r: b_vector.dot(&r_axis),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::astro::{Orbit, PointMass};
+    use crate::testing_utils::assert_vectors_close;
+
+    #[test]
+    fn test_b_plane() {
+        let mu = 1.0;
+        let pole = nalgebra::Vector3::z_axis();
+        let make_orbit = |incl: f64| {
+            Orbit::from_kepler(
+                PointMass::with_mu(mu),
+                (),
+                -2.0,
+                1.5,
+                incl.to_radians(),
+                0.3,
+                0.7,
+            )
+        };
+
+        // Prograde equatorial orbits have B entirely along T
+        let b_plane = make_orbit(0.0).b_plane(&pole).unwrap();
+        let expected_b = 2.0 * (1.5_f64.powi(2) - 1.0).sqrt();
+        assert_relative_eq!(b_plane.magnitude(), expected_b, epsilon = 1e-12);
+        assert_relative_eq!(b_plane.b_dot_t, expected_b, epsilon = 1e-12);
+        assert_relative_eq!(b_plane.b_dot_r, 0.0, epsilon = 1e-12);
+
+        // Retrograde ones have it pointing the other way
+        let b_plane = make_orbit(180.0).b_plane(&pole).unwrap();
+        assert_relative_eq!(b_plane.b_dot_t, -expected_b, epsilon = 1e-12);
+
+        for incl in [30.0, 60.0, 120.0] {
+            let orbit = make_orbit(incl);
+            let b_plane = orbit.b_plane(&pole).unwrap();
+
+            // B is in the B-plane, and T and R together capture all of it
+            assert_relative_eq!(
+                b_plane.b_vector.dot(&b_plane.incoming_asymptote),
+                0.0,
+                epsilon = 1e-12
+            );
+            assert_relative_eq!(
+                b_plane.b_dot_t.hypot(b_plane.b_dot_r),
+                b_plane.magnitude(),
+                epsilon = 1e-12
+            );
+
+            // Far in the past, the ship is moving along the asymptote, offset by B
+            let state = orbit.get_state_at_tsp(-1e6);
+            let velocity = state.velocity().normalize();
+            assert_vectors_close(&b_plane.incoming_asymptote.into_inner(), &velocity, 1e-6);
+            let offset = state.position()
+                - state.position().dot(&b_plane.incoming_asymptote)
+                    * b_plane.incoming_asymptote.into_inner();
+            assert_vectors_close(&b_plane.b_vector, &offset, 1e-4);
+        }
+
+        // Elliptical orbits don't have a B-plane
+        let orbit = Orbit::from_kepler(PointMass::with_mu(mu), (), 2.0, 0.5, 0.1, 0.2, 0.3);
+        assert!(orbit.b_plane(&pole).is_none());
+    }
+}
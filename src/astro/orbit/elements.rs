@@ -0,0 +1,188 @@
+use nalgebra::Vector3;
+
+use super::OrbitBase;
+use crate::math::geometry::wrap_angle;
+
+/// Orbits with an eccentricity below this are considered circular.
+const CIRCULAR_TOLERANCE: f64 = 1e-7;
+/// Orbits with sin(inclination) below this are considered equatorial.
+pub(super) const EQUATORIAL_TOLERANCE: f64 = 1e-12;
+
+/// Describes how the angles in [KeplerianElements] were chosen, since some of
+/// them are undefined for certain orbits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AngleConvention {
+    /// All angles have their usual meanings.
+    General,
+    /// The orbit lies in the xy plane, so there's no ascending node. Instead,
+    /// the ascending node is placed at periapsis: the longitude of the
+    /// ascending node is the longitude of periapsis (measured counterclockwise
+    /// around +z, even for retrograde orbits), and the argument of periapsis
+    /// is zero.
+    Equatorial,
+    /// The orbit is circular, so there's no periapsis. The argument of
+    /// periapsis instead measures the direction that anomalies (and the time at
+    /// periapsis) are measured from. This direction is arbitrary, but fixed
+    /// for a given orbit.
+    Circular,
+    /// Both of the above: the longitude of the ascending node measures the
+    /// direction anomalies are measured from, and the argument of periapsis is
+    /// zero.
+    CircularEquatorial,
+}
+
+/// The classical orbital elements of an orbit.
+///
+/// Angles are in radians. The inclination is in [0, pi], and the other angles
+/// are in [0, 2pi).
+#[derive(Debug, Clone, Copy)]
+pub struct KeplerianElements {
+    pub semimajor_axis: f64,
+    pub eccentricity: f64,
+    pub inclination: f64,
+    pub long_asc_node: f64,
+    pub arg_periapse: f64,
+    /// Which convention was used to pick the angles.
+    pub convention: AngleConvention,
+}
+
+impl<P, S, E> OrbitBase<P, S, E> {
+    /// Computes the Keplerian elements of this orbit.
+    pub fn elements(&self) -> KeplerianElements {
+        let normal = self.normal_vector();
+        let periapsis = self.periapse_vector();
+        let eccentricity = self.eccentricity();
+
+        // The ascending node points along z cross normal, and has length sin(incl)
+        let node = Vector3::z().cross(&normal);
+        let inclination = node.norm().atan2(normal.z);
+
+        let is_equatorial = node.norm() < EQUATORIAL_TOLERANCE;
+        let is_circular = eccentricity < CIRCULAR_TOLERANCE;
+
+        let (long_asc_node, arg_periapse) = if is_equatorial {
+            (periapsis.y.atan2(periapsis.x), 0.0)
+        } else {
+            // Both of these are directed angles, computed as atan2(sin, cos). The
+            // sine is measured around z for the LAN, and around the normal for argp.
+            let lan = node.y.atan2(node.x);
+            let argp = normal
+                .dot(&node.cross(&periapsis))
+                .atan2(node.dot(&periapsis));
+            (lan, argp)
+        };
+
+        let convention = match (is_circular, is_equatorial) {
+            (false, false) => AngleConvention::General,
+            (false, true) => AngleConvention::Equatorial,
+            (true, false) => AngleConvention::Circular,
+            (true, true) => AngleConvention::CircularEquatorial,
+        };
+
+        KeplerianElements {
+            semimajor_axis: self.semimajor_axis(),
+            eccentricity,
+            inclination,
+            long_asc_node: wrap_angle(long_asc_node),
+            arg_periapse: wrap_angle(arg_periapse),
+            convention,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::astro::Orbit;
+
+    #[test]
+    fn test_conventions() {
+        let make_orbit = |ecc, incl: f64, lan: f64, argp: f64| {
+            Orbit::from_kepler(
+                (),
+                (),
+                10.0,
+                ecc,
+                incl.to_radians(),
+                lan.to_radians(),
+                argp.to_radians(),
+            )
+        };
+
+        // (ecc, incl, lan, argp) in, then (lan, argp, convention) out
+        let cases = [
+            (0.5, 30.0, 40.0, 50.0, 40.0, 50.0, AngleConvention::General),
+            (0.5, 150.0, 40.0, 50.0, 40.0, 50.0, AngleConvention::General),
+            // Angles out of range get wrapped
+            (
+                0.5,
+                30.0,
+                -40.0,
+                410.0,
+                320.0,
+                50.0,
+                AngleConvention::General,
+            ),
+            // Equatorial orbits have the node at periapsis
+            (0.5, 0.0, 40.0, 50.0, 90.0, 0.0, AngleConvention::Equatorial),
+            (
+                0.5,
+                180.0,
+                40.0,
+                50.0,
+                350.0,
+                0.0,
+                AngleConvention::Equatorial,
+            ),
+            // Circular orbits keep their reference direction
+            (0.0, 30.0, 40.0, 50.0, 40.0, 50.0, AngleConvention::Circular),
+            (
+                0.0,
+                180.0,
+                40.0,
+                30.0,
+                10.0,
+                0.0,
+                AngleConvention::CircularEquatorial,
+            ),
+        ];
+
+        for (ecc, incl, lan, argp, expected_lan, expected_argp, expected_convention) in cases {
+            let elements = make_orbit(ecc, incl, lan, argp).elements();
+            assert_eq!(elements.convention, expected_convention);
+            assert_relative_eq!(elements.eccentricity, ecc);
+            assert_relative_eq!(elements.inclination.to_degrees(), incl, epsilon = 1e-12);
+            assert_relative_eq!(
+                elements.long_asc_node.to_degrees(),
+                expected_lan,
+                epsilon = 1e-12
+            );
+            assert_relative_eq!(
+                elements.arg_periapse.to_degrees(),
+                expected_argp,
+                epsilon = 1e-12
+            );
+        }
+    }
+
+    #[test]
+    fn test_ranges() {
+        // Sweep around a bunch of orientations, making sure nothing ever lands on 2pi
+        // or goes negative.
+        for i in 0..=24 {
+            for j in 0..=24 {
+                let angle = |k: i32| k as f64 * PI / 12.0;
+                let orbit =
+                    Orbit::from_kepler((), (), 10.0, 0.5, angle(i) / 2.0, angle(j), angle(i));
+                let elements = orbit.elements();
+                assert!((0.0..=PI).contains(&elements.inclination));
+                assert!((0.0..2.0 * PI).contains(&elements.long_asc_node));
+                assert!((0.0..2.0 * PI).contains(&elements.arg_periapse));
+            }
+        }
+    }
+}
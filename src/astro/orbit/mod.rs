@@ -1,12 +1,22 @@
+mod b_plane;
+mod elements;
+mod relative;
 mod timed_orbit;
 
 use std::f64::consts::PI;
 
+pub use b_plane::BPlane;
+use elements::EQUATORIAL_TOLERANCE;
+pub use elements::{AngleConvention, KeplerianElements};
 use nalgebra::{Rotation3, Unit, Vector3};
+pub use relative::RelativeNodes;
 pub use timed_orbit::TimedOrbit;
 
 use super::{HasMass, PointMass};
-use crate::math::geometry::{always_find_rotation, directed_angle};
+use crate::math::geometry::always_find_rotation;
+
+/// In meters per second
+const SPEED_OF_LIGHT: f64 = 299_792_458.0;
 
 /// The base class all other orbits are type aliases for.
 ///
@@ -41,7 +51,7 @@ pub type PhysicalOrbit = Orbit<PointMass, ()>;
 ///////////////////////////////////////////////////////////////////////////////
 impl<P, S, E> OrbitBase<P, S, E> {
     ///////////////////////////////////////////////////////////////////////////
-    /// Mapping primary, secondary, and extra
+    // Mapping primary, secondary, and extra
     ///////////////////////////////////////////////////////////////////////////
 
     pub fn primary(&self) -> &P {
@@ -125,7 +135,7 @@ impl<P, S, E> OrbitBase<P, S, E> {
     }
 
     ///////////////////////////////////////////////////////////////////////////
-    /// Geometric characteristics
+    // Geometric characteristics
     ///////////////////////////////////////////////////////////////////////////
 
     pub fn rotation(&self) -> Rotation3<f64> {
@@ -142,8 +152,10 @@ impl<P, S, E> OrbitBase<P, S, E> {
 
     pub fn asc_node_vector(&self) -> Unit<Vector3<f64>> {
         // TODO: the ambiguity here makes me think i might wanna just store the angles
+        // The length of v is sin(incl), so this also catches retrograde equatorial
+        // orbits, where sin(pi) isn't quite zero.
         let v = Vector3::z().cross(&self.normal_vector());
-        Unit::try_new(v, 1e-20).unwrap_or_else(|| self.periapse_vector())
+        Unit::try_new(v, EQUATORIAL_TOLERANCE).unwrap_or_else(|| self.periapse_vector())
     }
 
     pub fn semimajor_axis(&self) -> f64 {
@@ -168,24 +180,19 @@ impl<P, S, E> OrbitBase<P, S, E> {
     }
 
     pub fn inclination(&self) -> f64 {
-        // Inclination is the angle the normal makes with z
-        self.normal_vector().angle(&Vector3::z())
+        self.elements().inclination
     }
 
+    /// The longitude of the ascending node. See [KeplerianElements] for how
+    /// this is defined for equatorial orbits.
     pub fn long_asc_node(&self) -> f64 {
-        // Longitude of ascending node is the directed angle from x to the ascending
-        // node
-        directed_angle(&Vector3::x(), &self.asc_node_vector(), &Vector3::z())
+        self.elements().long_asc_node
     }
 
+    /// The argument of periapsis. See [KeplerianElements] for how this is
+    /// defined for equatorial and circular orbits.
     pub fn arg_periapse(&self) -> f64 {
-        // Argument of periapsis is the directed angle from the ascending node to the
-        // periapsis
-        directed_angle(
-            &self.asc_node_vector(),
-            &self.periapse_vector(),
-            &self.normal_vector(),
-        )
+        self.elements().arg_periapse
     }
 
     pub fn is_closed(&self) -> bool {
@@ -209,12 +216,51 @@ impl<P, S, E> OrbitBase<P, S, E> {
             None
         }
     }
+
+    ///////////////////////////////////////////////////////////////////////////
+    // Normalization
+    ///////////////////////////////////////////////////////////////////////////
+
+    /// Cleans up accumulated floating-point noise in the orbital elements,
+    /// without changing the shape or orientation of the orbit.
+    ///
+    /// Specifically:
+    /// - the rotation is rebuilt from the [KeplerianElements]
+    /// - equatorial orbits (prograde or retrograde) get an inclination of
+    ///   exactly 0 or pi
+    /// - closed orbits with a barely-negative e^2 are made exactly circular
+    pub fn normalize(&mut self) {
+        let elements = self.elements();
+        let incl = match elements.convention {
+            AngleConvention::General | AngleConvention::Circular => elements.inclination,
+            AngleConvention::Equatorial | AngleConvention::CircularEquatorial => {
+                if elements.inclination < PI / 2.0 {
+                    0.0
+                } else {
+                    PI
+                }
+            }
+        };
+        self.rotation = rotation_from_angles(incl, elements.long_asc_node, elements.arg_periapse);
+
+        if self.is_closed() && self.slr * self.alpha > 1.0 {
+            self.slr = self.alpha.recip();
+        }
+    }
+
+    /// Returns a normalized copy of this orbit. See [OrbitBase::normalize].
+    pub fn normalized(mut self) -> Self {
+        self.normalize();
+        self
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 /// Methods requiring P to have mass
 ///////////////////////////////////////////////////////////////////////////////
 impl<P: HasMass, S, E> OrbitBase<P, S, E> {
+    /// The radius of the Laplace sphere, a (m/M)^(2/5). This is the
+    /// approximation KSP uses for its spheres of influence.
     pub fn soi_radius(&self) -> f64
     where
         S: HasMass,
@@ -231,8 +277,25 @@ impl<P: HasMass, S, E> OrbitBase<P, S, E> {
         sma * (mu_2 / mu_1).powf(0.4)
     }
 
+    /// The radius of the Hill sphere, a (1-e) (m/3M)^(1/3), evaluated at
+    /// periapsis. Usually somewhat larger than the Laplace sphere.
+    pub fn hill_radius(&self) -> f64
+    where
+        S: HasMass,
+    {
+        let mu_1 = self.primary.mu();
+        let mu_2 = self.secondary.mu();
+
+        assert!(
+            self.is_closed(),
+            "Hill sphere approximation only works with elliptical orbits"
+        );
+
+        self.periapsis() * (mu_2 / (3.0 * mu_1)).cbrt()
+    }
+
     ///////////////////////////////////////////////////////////////////////////
-    /// Physical orbital characteristics
+    // Physical orbital characteristics
     ///////////////////////////////////////////////////////////////////////////
 
     pub fn energy(&self) -> f64 {
@@ -273,6 +336,19 @@ impl<P: HasMass, S, E> OrbitBase<P, S, E> {
             Some((2.0 * self.energy()).sqrt())
         }
     }
+
+    /// The rate (in radians per second) at which general relativity makes the
+    /// periapsis advance, averaged over an orbit. This is the first-order
+    /// post-Newtonian correction, 3 mu n / (c^2 a (1 - e^2)). Open orbits
+    /// don't come back around, so this is zero for them.
+    pub fn relativistic_precession_rate(&self) -> f64 {
+        let period = match self.period() {
+            Some(period) => period,
+            None => return 0.0,
+        };
+        let mean_motion = 2.0 * PI / period;
+        3.0 * self.primary.mu() * mean_motion / (SPEED_OF_LIGHT.powi(2) * self.slr)
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -450,6 +526,71 @@ mod tests {
         // including a test of rotation_from_angles!
     }
 
+    #[test]
+    fn test_normalize() {
+        let check_same_orientation = |a: &BareOrbit, b: &BareOrbit| {
+            assert_relative_eq!(a.periapse_vector(), b.periapse_vector(), epsilon = 1e-14);
+            assert_relative_eq!(a.normal_vector(), b.normal_vector(), epsilon = 1e-14);
+        };
+
+        // Out-of-range angles get wrapped
+        let orbit = Orbit::from_kepler(
+            (),
+            (),
+            10.0,
+            0.6,
+            15.0_f64.to_radians(),
+            -30.0_f64.to_radians(),
+            400.0_f64.to_radians(),
+        );
+        let normalized = orbit.normalized();
+        check_same_orientation(&orbit, &normalized);
+        assert_relative_eq!(
+            normalized.inclination().to_degrees(),
+            15.0,
+            max_relative = 1e-12
+        );
+        assert_relative_eq!(
+            normalized.long_asc_node().to_degrees(),
+            330.0,
+            max_relative = 1e-12
+        );
+        assert_relative_eq!(
+            normalized.arg_periapse().to_degrees(),
+            40.0,
+            max_relative = 1e-12
+        );
+
+        // Equatorial orbits put everything into the LAN, both prograde and retrograde
+        for (incl, expected_lan) in [(0.0, 100.0), (180.0, 20.0)] {
+            let orbit = Orbit::from_kepler(
+                (),
+                (),
+                10.0,
+                0.6,
+                f64::to_radians(incl),
+                60.0_f64.to_radians(),
+                40.0_f64.to_radians(),
+            );
+            let normalized = orbit.normalized();
+            check_same_orientation(&orbit, &normalized);
+            assert_eq!(normalized.inclination().to_degrees(), incl);
+            assert_relative_eq!(
+                normalized.long_asc_node().to_degrees(),
+                expected_lan,
+                max_relative = 1e-12
+            );
+            assert_eq!(normalized.arg_periapse(), 0.0);
+        }
+
+        // Slightly-noisy circular orbits are made exactly circular
+        let mut orbit = Orbit::from_kepler((), (), 10.0, 0.0, 0.0, 0.0, 0.0);
+        orbit.slr = 10.0 + 1e-12;
+        orbit.normalize();
+        assert_eq!(orbit.semilatus_rectum(), 10.0);
+        assert_eq!(orbit.eccentricity(), 0.0);
+    }
+
     #[test]
     fn test_kepler_constructor() {
         // TODO: more test cases! make sure to cover the edge cases
@@ -485,6 +626,55 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_retrograde_angles() {
+        // Inclined retrograde orbits behave just like prograde ones
+        for incl in [91.0, 135.0, 170.0, 179.0] {
+            let orbit = Orbit::from_kepler(
+                (),
+                (),
+                100.0,
+                0.4,
+                f64::to_radians(incl),
+                130.0_f64.to_radians(),
+                25.0_f64.to_radians(),
+            );
+            assert_relative_eq!(orbit.inclination().to_degrees(), incl, max_relative = 1e-12);
+            assert_relative_eq!(
+                orbit.long_asc_node().to_degrees(),
+                130.0,
+                max_relative = 1e-12
+            );
+            assert_relative_eq!(
+                orbit.arg_periapse().to_degrees(),
+                25.0,
+                max_relative = 1e-10
+            );
+            assert!(orbit.normal_vector().z < 0.0);
+        }
+
+        // Nearly-equatorial retrograde orbits should keep their tiny tilt, and a
+        // well-defined ascending node.
+        let tilt = 1e-9;
+        let orbit = Orbit::from_kepler((), (), 100.0, 0.4, PI - tilt, 1.0, 0.5);
+        assert_relative_eq!(PI - orbit.inclination(), tilt, max_relative = 1e-6);
+        assert_relative_eq!(orbit.long_asc_node(), 1.0, max_relative = 1e-6);
+        assert_relative_eq!(orbit.arg_periapse(), 0.5, max_relative = 1e-6);
+
+        // Exactly-equatorial retrograde orbits have no ascending node, so it gets
+        // placed at periapsis. Periapsis is at angle lan - argp, since the orbit
+        // goes clockwise.
+        let orbit = Orbit::from_kepler((), (), 100.0, 0.4, PI, 1.0, 0.5);
+        assert_eq!(orbit.inclination(), PI);
+        assert_relative_eq!(orbit.long_asc_node(), 0.5, max_relative = 1e-12);
+        assert_eq!(orbit.arg_periapse(), 0.0);
+        assert_relative_eq!(
+            orbit.periapse_vector().into_inner(),
+            Vector3::new(0.5_f64.cos(), 0.5_f64.sin(), 0.0),
+            epsilon = 1e-15
+        );
+    }
+
     // TODO: reduce this test a bit
     #[test]
     fn test_cartesian_constructor() {
@@ -522,7 +712,9 @@ mod tests {
         let orbit = make_orbit(Vector3::z(), Vector3::x(), 0.0);
         assert_relative_eq!(orbit.semimajor_axis(), radius / 2.0, max_relative = 1e-15);
         assert_relative_eq!(orbit.eccentricity(), 1.0);
-        // TODO: double check why these are they way they are!
+        // There's no angular momentum, so the orbital plane is picked by
+        // always_find_rotation: the normal is y, and so the ascending node is
+        // -x. Periapsis is in the direction the ship falls, -z.
         assert_relative_eq!(orbit.inclination(), PI / 2.0);
         assert_relative_eq!(orbit.long_asc_node(), PI);
         assert_relative_eq!(orbit.arg_periapse(), 3.0 * PI / 2.0);
@@ -549,7 +741,7 @@ mod tests {
         assert!(orbit.eccentricity() > 1.0);
         assert_relative_eq!(orbit.inclination(), PI / 2.0);
         assert_relative_eq!(orbit.long_asc_node(), 0.0);
-        assert_relative_eq!(orbit.arg_periapse(), 2.0 * PI);
+        assert_relative_eq!(orbit.arg_periapse(), 0.0, epsilon = 1e-15);
     }
 
     #[test]
@@ -583,5 +775,29 @@ mod tests {
             epsilon = 1.0
         );
         assert_relative_eq!(kerbin_orbit.soi_radius(), 84_159_286.0, epsilon = 1.0);
+        assert_relative_eq!(kerbin_orbit.hill_radius(), 136_186_352.0, epsilon = 1.0);
+    }
+
+    #[test]
+    fn test_relativistic_precession() {
+        // Mercury's famous 43 arcseconds per century
+        let sun_mu = 1.32712440018e20;
+        let mercury_orbit = Orbit::from_kepler(
+            PointMass(sun_mu),
+            (),
+            57_909_050_000.0,
+            0.2056,
+            0.0,
+            0.0,
+            0.0,
+        );
+        let century = 100.0 * 365.25 * 86400.0;
+        let arcseconds =
+            (mercury_orbit.relativistic_precession_rate() * century).to_degrees() * 3600.0;
+        assert_relative_eq!(arcseconds, 43.0, epsilon = 0.1);
+
+        // Open orbits don't precess
+        let hyperbolic = Orbit::from_kepler(PointMass(sun_mu), (), -1e10, 1.5, 0.0, 0.0, 0.0);
+        assert_eq!(hyperbolic.relativistic_precession_rate(), 0.0);
     }
 }
@@ -0,0 +1,139 @@
+use super::elements::EQUATORIAL_TOLERANCE;
+use super::OrbitBase;
+use crate::math::geometry::wrap_angle;
+
+/// Describes where one orbit crosses the plane of another, reference, orbit.
+#[derive(Debug, Clone, Copy)]
+pub struct RelativeNodes {
+    /// Angle between the two orbital planes, in [0, pi].
+    pub inclination: f64,
+    /// True anomaly of the ascending node, where the orbit crosses to the north
+    /// side of the reference plane, in [0, 2pi). The descending node is on the
+    /// opposite side. `None` if the planes coincide, since then every point
+    /// is on both.
+    pub asc_node_anomaly: Option<f64>,
+}
+
+impl RelativeNodes {
+    /// True anomaly of the descending node, in [0, 2pi).
+    pub fn desc_node_anomaly(&self) -> Option<f64> {
+        self.asc_node_anomaly
+            .map(|theta| wrap_angle(theta + std::f64::consts::PI))
+    }
+}
+
+impl<P, S, E> OrbitBase<P, S, E> {
+    /// Computes the nodes of this orbit relative to the plane of the reference
+    /// orbit. Only the orientations of the two orbits matter, so they don't
+    /// need to share a primary.
+    pub fn nodes_relative_to<P2, S2, E2>(
+        &self,
+        reference: &OrbitBase<P2, S2, E2>,
+    ) -> RelativeNodes {
+        let normal = self.normal_vector();
+        let periapsis = self.periapse_vector();
+
+        // Just like the usual ascending node, but with the reference normal instead
+        // of z. Its length is sin(inclination).
+        let node = reference.normal_vector().cross(&normal);
+        let inclination = node.norm().atan2(reference.normal_vector().dot(&normal));
+
+        let asc_node_anomaly = if node.norm() < EQUATORIAL_TOLERANCE {
+            None
+        } else {
+            // Directed angle from periapsis to the node, around the normal
+            let theta = normal
+                .dot(&periapsis.cross(&node))
+                .atan2(periapsis.dot(&node));
+            Some(wrap_angle(theta))
+        };
+
+        RelativeNodes {
+            inclination,
+            asc_node_anomaly,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::astro::Orbit;
+
+    fn make_orbit(incl: f64, lan: f64, argp: f64) -> Orbit<(), ()> {
+        Orbit::from_kepler(
+            (),
+            (),
+            10.0,
+            0.5,
+            incl.to_radians(),
+            lan.to_radians(),
+            argp.to_radians(),
+        )
+    }
+
+    #[test]
+    fn test_relative_to_equatorial() {
+        // Relative to the xy plane, these should be the usual elements
+        let reference = make_orbit(0.0, 0.0, 0.0);
+        let orbit = make_orbit(30.0, 40.0, 50.0);
+        let nodes = orbit.nodes_relative_to(&reference);
+        assert_relative_eq!(nodes.inclination.to_degrees(), 30.0, epsilon = 1e-12);
+        assert_relative_eq!(
+            nodes.asc_node_anomaly.unwrap().to_degrees(),
+            310.0,
+            epsilon = 1e-12
+        );
+        assert_relative_eq!(
+            nodes.desc_node_anomaly().unwrap().to_degrees(),
+            130.0,
+            epsilon = 1e-12
+        );
+
+        // The nodes should actually be on the reference plane, and we should be
+        // heading north at the ascending one
+        let an = orbit
+            .get_position_at_theta(nodes.asc_node_anomaly.unwrap())
+            .unwrap();
+        let dn = orbit
+            .get_position_at_theta(nodes.desc_node_anomaly().unwrap())
+            .unwrap();
+        assert_relative_eq!(an.z, 0.0, epsilon = 1e-12);
+        assert_relative_eq!(dn.z, 0.0, epsilon = 1e-12);
+        assert!(orbit.normal_vector().cross(&an).z > 0.0);
+    }
+
+    #[test]
+    fn test_relative_to_inclined() {
+        let reference = make_orbit(30.0, 40.0, 50.0);
+        let orbit = make_orbit(60.0, 100.0, 10.0);
+        let nodes = orbit.nodes_relative_to(&reference);
+
+        let expected_incl = orbit.normal_vector().dot(&reference.normal_vector()).acos();
+        assert_relative_eq!(nodes.inclination, expected_incl, epsilon = 1e-12);
+
+        // Swapping the orbits keeps the inclination
+        let swapped = reference.nodes_relative_to(&orbit);
+        assert_relative_eq!(swapped.inclination, nodes.inclination, epsilon = 1e-12);
+
+        // The ascending node should lie on the reference plane
+        let an = orbit
+            .get_position_at_theta(nodes.asc_node_anomaly.unwrap())
+            .unwrap();
+        assert_relative_eq!(an.dot(&reference.normal_vector()), 0.0, epsilon = 1e-12);
+        assert!(
+            orbit
+                .normal_vector()
+                .cross(&an)
+                .dot(&reference.normal_vector())
+                > 0.0
+        );
+
+        // Coplanar orbits don't have nodes
+        let coplanar = make_orbit(30.0, 40.0, 120.0);
+        let nodes = coplanar.nodes_relative_to(&reference);
+        assert_relative_eq!(nodes.inclination, 0.0, epsilon = 1e-12);
+        assert!(nodes.asc_node_anomaly.is_none());
+    }
+}
@@ -1,3 +1,5 @@
+use nalgebra::{Rotation3, Vector3};
+
 use super::{HasMass, Orbit, OrbitBase};
 use crate::astro::state::CartesianState;
 
@@ -16,6 +18,10 @@ impl<P, S> TimedOrbit<P, S> {
     pub fn without_time(&self) -> Orbit<&P, &S> {
         self.as_ref().with_extra(())
     }
+
+    pub fn time_at_periapsis(&self) -> f64 {
+        self.extra.time_at_periapsis
+    }
 }
 
 impl<P: HasMass, S> TimedOrbit<P, S> {
@@ -30,6 +36,54 @@ impl<P: HasMass, S> TimedOrbit<P, S> {
     pub fn time_at_s(&self, s: f64) -> f64 {
         self.extra.time_at_periapsis + self.s_to_tsp(s)
     }
+
+    /// Returns the first time after `time` that the orbit passes through the
+    /// given true anomaly, or `None` if it never does again (or at all, for
+    /// open orbits that don't reach it).
+    pub fn next_time_at_theta(&self, theta: f64, time: f64) -> Option<f64> {
+        let pass_time = self.time_at_s(self.s_at_theta(theta)?);
+        match self.period() {
+            Some(period) => {
+                let next = pass_time + period * ((time - pass_time) / period).ceil();
+                Some(if next <= time { next + period } else { next })
+            }
+            None => Some(pass_time).filter(|t| *t > time),
+        }
+    }
+
+    /// Shifts the time at periapsis by a whole number of periods, so that it
+    /// refers to the periapsis passage closest to `new_epoch`. This doesn't
+    /// change where the orbiting body is at any given time, but keeps
+    /// `time - time_at_periapsis` small, which helps the precision of the
+    /// anomaly computations.
+    ///
+    /// This matches the convention of [TimedOrbit::from_state], so the anomaly
+    /// at `new_epoch` is within half an orbit of zero.
+    ///
+    /// Open orbits only pass through periapsis once, so they are unchanged.
+    pub fn rebase_epoch(&mut self, new_epoch: f64) {
+        if let Some(period) = self.period() {
+            let num_periods = ((new_epoch - self.extra.time_at_periapsis) / period).round();
+            self.extra.time_at_periapsis += num_periods * period;
+        }
+    }
+
+    /// Rotates the orbit about its normal by the given angle, so that the
+    /// periapsis advances, while the orbiting body stays (to first order in the
+    /// angle) in the same direction at the given time. Its distance from the
+    /// primary changes by about `e r angle`, since it's now at a different
+    /// point on the ellipse. This is meant for applying small secular
+    /// corrections, such as
+    /// [relativistic precession](OrbitBase::relativistic_precession_rate).
+    pub fn advance_periapsis(&mut self, angle: f64, time: f64) {
+        // The body is now `angle` further behind the periapsis, which takes
+        // dt = dtheta / (h / r^2) to cover.
+        let radius = self.state_at_time(time).position().norm();
+        let dt = angle * radius.powi(2) / self.angular_momentum();
+
+        self.rotation *= Rotation3::from_axis_angle(&Vector3::z_axis(), angle);
+        self.extra.time_at_periapsis += dt;
+    }
 }
 
 impl<P: HasMass> TimedOrbit<P, ()> {
@@ -47,26 +101,159 @@ impl<P: HasMass> TimedOrbit<P, ()> {
 
         // TODO: find something that works for radial orbits!
         let theta = pos_in_plane.y.atan2(pos_in_plane.x);
-        let tan_half_theta = (theta / 2.0).tan();
-        let h = orbit.angular_momentum();
-        let r_p = orbit.periapsis();
-        let g2_over_g1 = r_p / h * tan_half_theta;
-
-        let beta: f64 = orbit.beta();
-        let beta_sqrt = beta.abs().sqrt();
-        let s = if beta > 0.0 {
-            // Elliptic: g2/g1 = tan(s sqrt(beta) / 2) / sqrt(beta)
-            (g2_over_g1 * beta_sqrt).atan() * 2.0 / beta_sqrt
-        } else if beta < 0.0 {
-            // Hyperbolic: g2/g1 = tanh(s sqrt(-beta) / 2) / sqrt(-beta)
-            (g2_over_g1 * beta_sqrt).atanh() * 2.0 / beta_sqrt
-        } else {
-            // Parabolic: s = h/mu tan_half_theta, and r_p = h^2/2mu, so
-            // g2/g1 = r_p/h mu/h s = s/2
-            2.0 * tan_half_theta
-        };
+        let s = orbit.s_at_theta(theta).unwrap_or(f64::NAN);
 
         let time_since_periapsis = orbit.s_to_tsp(s);
         Self::from_orbit(orbit, current_time - time_since_periapsis)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use approx::assert_relative_eq;
+
+    use nalgebra::Vector3;
+
+    use super::*;
+    use crate::astro::PointMass;
+
+    #[test]
+    fn test_rebase_epoch() {
+        let primary = PointMass::with_mu(1.0);
+        let orbit = Orbit::from_kepler(primary, (), 10.0, 0.5, 0.3, 0.2, 0.1);
+        let period = orbit.period().unwrap();
+        let orbit = TimedOrbit::from_orbit(orbit, 5.0);
+
+        let mut rebased = orbit;
+        rebased.rebase_epoch(5.0 + 1000.4 * period);
+        assert_relative_eq!(
+            rebased.time_at_periapsis(),
+            5.0 + 1000.0 * period,
+            max_relative = 1e-12
+        );
+
+        // Going backwards works too
+        let mut rebased_back = orbit;
+        rebased_back.rebase_epoch(5.0 - 2.6 * period);
+        assert_relative_eq!(
+            rebased_back.time_at_periapsis(),
+            5.0 - 3.0 * period,
+            max_relative = 1e-12
+        );
+
+        // States should agree (up to the precision lost in the original)
+        let time = 5.0 + 1000.7 * period;
+        let expected = orbit.state_at_time(time);
+        let actual = rebased.state_at_time(time);
+        assert_relative_eq!(expected.position(), actual.position(), max_relative = 1e-6);
+        assert_relative_eq!(expected.velocity(), actual.velocity(), max_relative = 1e-6);
+
+        // Hyperbolic orbits are left alone
+        let hyperbolic = Orbit::from_kepler(primary, (), -10.0, 1.5, 0.3, 0.2, 0.1);
+        let mut hyperbolic = TimedOrbit::from_orbit(hyperbolic, 5.0);
+        hyperbolic.rebase_epoch(1e6);
+        assert_eq!(hyperbolic.time_at_periapsis(), 5.0);
+    }
+
+    #[test]
+    fn test_advance_periapsis() {
+        let primary = PointMass::with_mu(1.0);
+        let orbit = Orbit::from_kepler(primary, (), 10.0, 0.5, 0.3, 0.2, 0.1);
+        let orbit = TimedOrbit::from_orbit(orbit, 5.0);
+
+        let angle = 1e-6;
+        let time = 12.0;
+        let mut advanced = orbit;
+        advanced.advance_periapsis(angle, time);
+
+        // The periapsis moves forward in the orbital plane
+        assert_relative_eq!(
+            orbit.periapse_vector().angle(&advanced.periapse_vector()),
+            angle,
+            max_relative = 1e-3
+        );
+        assert_relative_eq!(
+            orbit.normal_vector().into_inner(),
+            advanced.normal_vector().into_inner(),
+            epsilon = 1e-15
+        );
+        let direction = orbit.periapse_vector().cross(&advanced.periapse_vector());
+        assert!(direction.dot(&orbit.normal_vector()) > 0.0);
+
+        // But the body stays in the same direction, to first order, and only
+        // moves a little radially
+        let expected = orbit.state_at_time(time).position();
+        let actual = advanced.state_at_time(time).position();
+        assert_relative_eq!(expected.angle(&actual), 0.0, epsilon = angle * 1e-2);
+        assert_relative_eq!(
+            expected.norm(),
+            actual.norm(),
+            max_relative = orbit.eccentricity() * angle
+        );
+    }
+
+    #[test]
+    fn test_retrograde_round_trip() {
+        // Various retrograde states: equatorial circular, equatorial elliptic,
+        // inclined, and hyperbolic
+        let states = [
+            (
+                Vector3::new(10.0, 0.0, 0.0),
+                Vector3::new(0.0, -0.316227766, 0.0),
+            ),
+            (Vector3::new(0.0, 10.0, 0.0), Vector3::new(0.35, 0.0, 0.0)),
+            (Vector3::new(10.0, 0.0, 0.0), Vector3::new(0.0, -0.3, 0.05)),
+            (Vector3::new(3.0, 4.0, 0.0), Vector3::new(0.4, -0.3, -0.02)),
+            (Vector3::new(-6.0, 8.0, 0.0), Vector3::new(0.4, 0.3, 0.0)),
+        ];
+        let primary = PointMass::with_mu(1.0);
+
+        for (position, velocity) in states {
+            let state = CartesianState::new(primary, position, velocity);
+            let orbit = TimedOrbit::from_state(state, 100.0);
+            assert!(orbit.normal_vector().z <= 0.0);
+            assert!(orbit.inclination() > PI / 2.0);
+
+            // We should get the original state back. (The nearly-circular one loses a
+            // bit of precision in its eccentricity, so we can't be too strict.)
+            let new_state = orbit.state_at_time(100.0);
+            assert_relative_eq!(new_state.position(), position, epsilon = 1e-8);
+            assert_relative_eq!(new_state.velocity(), velocity, epsilon = 1e-8);
+
+            // And moving forward in time should move clockwise
+            let later = orbit.state_at_time(101.0).position();
+            assert!(position.cross(&later).z < 0.0);
+        }
+    }
+
+    #[test]
+    fn test_next_time_at_theta() {
+        let primary = PointMass::with_mu(1.0);
+        let orbit = Orbit::from_kepler(primary, (), 10.0, 0.5, 0.3, 0.2, 0.1);
+        let period = orbit.period().unwrap();
+        let orbit = TimedOrbit::from_orbit(orbit, 5.0);
+
+        // The next pass is within a period, and in the right direction
+        for theta in [-2.0, 0.0, 1.0, 3.0] {
+            let time = 5.0 + 3.7 * period;
+            let pass_time = orbit.next_time_at_theta(theta, time).unwrap();
+            assert!(time < pass_time && pass_time <= time + period);
+            let expected = orbit.get_position_at_theta(theta).unwrap();
+            let actual = orbit.state_at_time(pass_time).position();
+            assert_relative_eq!(expected, actual, max_relative = 1e-9);
+        }
+
+        // Periapsis is exactly a period later, not now
+        let pass_time = orbit.next_time_at_theta(0.0, 5.0).unwrap();
+        assert_relative_eq!(pass_time, 5.0 + period);
+
+        // Open orbits only pass once, and not at all beyond the asymptotes
+        let hyperbolic = Orbit::from_kepler(primary, (), -10.0, 1.5, 0.3, 0.2, 0.1);
+        let hyperbolic = TimedOrbit::from_orbit(hyperbolic, 5.0);
+        assert!(hyperbolic.next_time_at_theta(1.0, 0.0).is_some());
+        assert!(hyperbolic.next_time_at_theta(1.0, 100.0).is_none());
+        assert!(hyperbolic.next_time_at_theta(3.0, 0.0).is_none());
+    }
+}
@@ -123,6 +123,31 @@ impl<P: HasMass, S, E> OrbitBase<P, S, E> {
         self.ts_and_derivative(s).0
     }
 
+    /// Returns the universal anomaly at the given true anomaly, within half a
+    /// turn of periapsis. Returns `None` if the orbit is open and never gets
+    /// there.
+    pub fn s_at_theta(&self, theta: f64) -> Option<f64> {
+        let tan_half_theta = (theta / 2.0).tan();
+        let h = self.angular_momentum();
+        let r_p = self.periapsis();
+        let g2_over_g1 = r_p / h * tan_half_theta;
+
+        let beta: f64 = self.beta();
+        let beta_sqrt = beta.abs().sqrt();
+        let s = if beta > 0.0 {
+            // Elliptic: g2/g1 = tan(s sqrt(beta) / 2) / sqrt(beta)
+            (g2_over_g1 * beta_sqrt).atan() * 2.0 / beta_sqrt
+        } else if beta < 0.0 {
+            // Hyperbolic: g2/g1 = tanh(s sqrt(-beta) / 2) / sqrt(-beta)
+            (g2_over_g1 * beta_sqrt).atanh() * 2.0 / beta_sqrt
+        } else {
+            // Parabolic: s = h/mu tan_half_theta, and r_p = h^2/2mu, so
+            // g2/g1 = r_p/h mu/h s = s/2
+            2.0 * tan_half_theta
+        };
+        Some(s).filter(|s| s.is_finite())
+    }
+
     #[allow(non_snake_case)]
     pub fn get_s_at_radius(&self, radius: f64) -> Option<f64> {
         // We can actually solve this one exactly, using the relationship between r and
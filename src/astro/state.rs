@@ -19,6 +19,10 @@ impl<P> CartesianState<P> {
         }
     }
 
+    pub fn primary(&self) -> &P {
+        &self.primary
+    }
+
     pub fn position(&self) -> Vector3<f64> {
         self.position
     }
@@ -26,6 +30,20 @@ impl<P> CartesianState<P> {
     pub fn velocity(&self) -> Vector3<f64> {
         self.velocity
     }
+
+    /// How fast the distance from the primary is changing; negative when
+    /// falling towards it.
+    pub fn radial_velocity(&self) -> f64 {
+        self.position.dot(&self.velocity) / self.position.norm()
+    }
+
+    /// Angle of the velocity above the local horizontal, in radians, from
+    /// -pi/2 straight down to pi/2 straight up.
+    pub fn flight_path_angle(&self) -> f64 {
+        let r = self.position.norm();
+        let horizontal_velocity = self.position.cross(&self.velocity).norm() / r;
+        self.radial_velocity().atan2(horizontal_velocity)
+    }
 }
 
 impl<P: HasMass> CartesianState<P> {
@@ -128,6 +146,25 @@ mod tests {
         assert_relative_eq!(KERBIN_ORBIT_PERIOD, elapsed_time, max_relative = 1e-6);
     }
 
+    #[test]
+    fn test_flight_path_angle() {
+        let state = |velocity: Vector3<f64>| {
+            CartesianState::new(PointMass::with_mu(KERBOL_MU), Vector3::x() * 2.0, velocity)
+        };
+
+        let climbing = state(Vector3::new(3.0, 3.0, 0.0));
+        assert_relative_eq!(climbing.radial_velocity(), 3.0);
+        assert_relative_eq!(climbing.flight_path_angle(), PI / 4.0);
+
+        let level = state(Vector3::new(0.0, 1.0, 1.0));
+        assert_relative_eq!(level.radial_velocity(), 0.0);
+        assert_relative_eq!(level.flight_path_angle(), 0.0);
+
+        let falling = state(Vector3::new(-5.0, 0.0, 0.0));
+        assert_relative_eq!(falling.radial_velocity(), -5.0);
+        assert_relative_eq!(falling.flight_path_angle(), -PI / 2.0);
+    }
+
     #[test]
     fn test_perfect_circle() {
         // Build a perfectly circular orbit, and test what it looks like as we move
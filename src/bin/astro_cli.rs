@@ -1,18 +1,143 @@
+use std::path::PathBuf;
+
 use clap::Parser;
-use rust_ksp::file::read_file;
+use rust_ksp::astro::ascent::{
+    estimate_ascent, AscentEstimate, Atmosphere, GravityTurn, LaunchSite, LaunchVehicle,
+};
+use rust_ksp::astro::integrate::IntegratorSettings;
+use rust_ksp::astro::low_thrust::{edelbaum_transfer, propagate_spiral, SpiralTransfer};
+use rust_ksp::astro::HasMass;
+use rust_ksp::file::{read_preset, BodiesPreset};
+use rust_ksp::model::alignment::{search_for_alignments, AlignmentSearch};
+use rust_ksp::model::orrery::{BodyID, Orrery, Stage};
+use rust_ksp::model::transfers::{OrbitSpec, TransferMatrix};
 
 #[derive(Debug, Parser)]
 struct Args {
-    name: String,
+    /// Body to describe. Can be left out when only comparing transfers.
+    name: Option<String>,
+    /// Which bodies file to load: approximate or ksp-exact
+    #[arg(long, default_value_t = BodiesPreset::Approximate)]
+    preset: BodiesPreset,
+    /// Also plan a low-thrust spiral around the body, between circular orbits
+    /// at these two altitudes (in meters)
+    #[arg(long, num_args = 2, value_names = ["FROM", "TO"])]
+    spiral: Option<Vec<f64>>,
+    /// Thrust acceleration for the spiral, in m/s^2
+    #[arg(long, default_value_t = 0.01)]
+    acceleration: f64,
+    /// Inclination change for the spiral, in degrees
+    #[arg(long, default_value_t = 0.0)]
+    inclination_change: f64,
+    /// Also estimate launching from the body's equator to a circular orbit at
+    /// this altitude (in meters), flying a gravity turn
+    #[arg(long, value_name = "ALTITUDE")]
+    ascent: Option<f64>,
+    /// The rocket for the ascent: its empty and full masses in kg, its
+    /// engines' Isp in seconds and thrust in N
+    #[arg(
+        long,
+        value_name = "DRY,WET,ISP,THRUST",
+        default_value = "5000,20000,300,400000"
+    )]
+    launch_stage: Stage,
+    /// The rocket's drag coefficient times its cross-sectional area, in m^2
+    #[arg(long, default_value_t = 1.0)]
+    drag_area: f64,
+    /// Density of the body's air at the surface, in kg/m^3
+    #[arg(long, default_value_t = 1.225)]
+    surface_density: f64,
+    /// Altitudes (in meters) where the gravity turn starts and ends
+    #[arg(long, num_args = 2, value_names = ["START", "END"], default_values_t = [1000.0, 45000.0])]
+    turn_altitudes: Vec<f64>,
+    /// How the gravity turn pitches over: below 1 pitches over quickly at
+    /// first, above 1 later on
+    #[arg(long, default_value_t = 0.5)]
+    turn_shape: f64,
+    /// Also list the times when the body lines up with these other bodies
+    #[arg(long, num_args = 1.., value_name = "BODY")]
+    align_with: Vec<String>,
+    /// Which body the alignment is seen from. Defaults to the root body.
+    #[arg(long, value_name = "BODY")]
+    seen_from: Option<String>,
+    /// How closely the bodies have to line up, in degrees
+    #[arg(long, default_value_t = 1.0)]
+    tolerance: f64,
+    /// How many days to search for alignments
+    #[arg(long, default_value_t = 365.0)]
+    days: f64,
+    /// An orbit to compare transfer costs between, written
+    /// NAME=BODY:ALTITUDE, with the altitude in meters. Elliptic orbits are
+    /// written with both altitudes, as PERIAPSIS-APOAPSIS, and an inclination
+    /// in degrees can be added with @INCLINATION. Give two or more to print
+    /// the delta-v of going between every pair.
+    #[arg(long = "orbit", value_name = "ORBIT")]
+    orbits: Vec<OrbitSpec>,
+    /// Also write the transfer costs to this CSV file.
+    #[arg(long, requires = "orbits")]
+    transfer_csv: Option<PathBuf>,
+}
+
+fn find_body(orrery: &Orrery, name: &str) -> BodyID {
+    match orrery
+        .bodies()
+        .find(|b| b.info.name.to_lowercase() == name.to_lowercase())
+    {
+        Some(body) => body.id,
+        None => {
+            eprintln!("No body named {}", name);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn print_transfer(transfer: &SpiralTransfer) {
+    println!("  - Delta-v: {:.1} m/s", transfer.delta_v);
+    println!("  - Time of flight: {:.0} s", transfer.time_of_flight);
+    println!("  - Revolutions: {:.1}", transfer.revolutions);
+}
+
+fn print_ascent(estimate: &AscentEstimate) {
+    println!("  - Delta-v: {:.1} m/s", estimate.delta_v);
+    println!(
+        "  - Circularization: {:.1} m/s",
+        estimate.circularization_delta_v
+    );
+    println!("  - Gravity and drag losses: {:.1} m/s", estimate.losses);
+    println!("  - Burn time: {:.0} s", estimate.burn_time);
+    println!("  - Time to orbit: {:.0} s", estimate.time_to_orbit);
+    println!("  - Orbit altitude: {:.0} m", estimate.altitude);
+    println!("  - Profile (time, downrange, altitude, speed):");
+    // Every half minute, and at apoapsis
+    let last = estimate.profile.len() - 1;
+    for (_, point) in estimate
+        .profile
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| i % 6 == 0 || *i == last)
+    {
+        println!(
+            "    - {:4.0} s: {:6.1} km, {:5.1} km, {:6.1} m/s",
+            point.time,
+            point.downrange / 1000.0,
+            point.altitude / 1000.0,
+            point.speed
+        );
+    }
 }
 
 fn main() {
     let args = Args::parse();
 
-    let orrery = read_file("ksp-bodies.txt");
+    let orrery = read_preset(args.preset);
     for orbit in orrery.body_orbits() {
+        let orbit = orbit.normalized();
         let body = orbit.secondary();
-        if body.info.name.to_lowercase() != args.name.to_lowercase() {
+        if args
+            .name
+            .as_ref()
+            .is_none_or(|name| body.info.name.to_lowercase() != name.to_lowercase())
+        {
             continue;
         }
 
@@ -32,6 +157,7 @@ fn main() {
             orbit.arg_periapse().to_degrees()
         );
         println!("- LAN: {}", orbit.long_asc_node().to_degrees());
+        println!("- Angle convention: {:?}", orbit.elements().convention);
         println!("- Sidereal orbital period: {:?}", orbit.period());
         println!(
             "- Minimum orbital velocity: {:?}",
@@ -39,6 +165,122 @@ fn main() {
         );
         println!("- Maximum orbital velocity: {}", orbit.periapsis_velocity());
         println!("- SOI Radius: {:?}", orbit.soi_radius());
+        println!("- Hill sphere radius: {:?}", orbit.hill_radius());
+        if let Some(soi) = body.info.soi_radius {
+            println!("- Tabulated SOI radius: {}", soi);
+        }
+        if let Some(period) = body.info.rotation_period {
+            println!("- Sidereal rotation period: {}", period);
+        }
+        if let Some(height) = body.info.atmosphere_height {
+            println!("- Atmosphere height: {}", height);
+        }
+
+        if let Some(altitudes) = &args.spiral {
+            let radius = body.info.radius as f64;
+            let (r0, r1) = (radius + altitudes[0], radius + altitudes[1]);
+            let inclination_change = args.inclination_change.to_radians();
+
+            println!("- Low-thrust spiral, Edelbaum estimate:");
+            let transfer =
+                edelbaum_transfer(body.mu(), r0, r1, inclination_change, args.acceleration);
+            print_transfer(&transfer);
+            if inclination_change == 0.0 {
+                println!("- Low-thrust spiral, integrated:");
+                let (transfer, _) = propagate_spiral(
+                    &IntegratorSettings::default(),
+                    body.mu(),
+                    r0,
+                    r1,
+                    args.acceleration,
+                );
+                print_transfer(&transfer);
+            }
+        }
+
+        if let Some(altitude) = args.ascent {
+            let site = LaunchSite {
+                mu: body.mu(),
+                radius: body.info.radius as f64,
+                angular_velocity: body
+                    .info
+                    .rotation_period
+                    .map_or(0.0, |period| 2.0 * std::f64::consts::PI / period),
+                atmosphere: body
+                    .info
+                    .atmosphere_height
+                    .map(|height| Atmosphere::from_height(height, args.surface_density)),
+            };
+            let stage = &args.launch_stage;
+            let vehicle = LaunchVehicle {
+                mass: stage.wet_mass,
+                propellant_mass: stage.propellant_mass(),
+                thrust: stage.thrust,
+                exhaust_velocity: stage.exhaust_velocity(),
+                drag_area: args.drag_area,
+            };
+            let turn = GravityTurn {
+                turn_start_altitude: args.turn_altitudes[0],
+                turn_end_altitude: args.turn_altitudes[1],
+                shape: args.turn_shape,
+            };
+
+            println!("- Ascent to {:.0} m, gravity turn:", altitude);
+            match estimate_ascent(&site, &vehicle, &turn, altitude) {
+                Ok(estimate) => print_ascent(&estimate),
+                Err(e) => println!("  - Can't make it: {}", e),
+            }
+        }
+
+        if !args.align_with.is_empty() {
+            let mut body_ids = vec![body.id];
+            body_ids.extend(args.align_with.iter().map(|name| find_body(&orrery, name)));
+            let observer = match &args.seen_from {
+                Some(name) => find_body(&orrery, name),
+                None => {
+                    orrery
+                        .bodies()
+                        .find(|b| orrery.get_parent(b.id).is_none())
+                        .unwrap()
+                        .id
+                }
+            };
+            let search = AlignmentSearch {
+                body_ids,
+                observer,
+                tolerance: args.tolerance.to_radians(),
+            };
+            println!(
+                "- Alignments with {}, seen from {}:",
+                args.align_with.join(", "),
+                orrery.get_body(observer).info.name
+            );
+            for alignment in search_for_alignments(&orrery, &search, 0.0, args.days * 86400.0) {
+                println!(
+                    "  - Day {:.2}: within {:.3} degrees",
+                    alignment.time / 86400.0,
+                    alignment.spread.to_degrees()
+                );
+            }
+        }
         println!();
     }
+
+    if !args.orbits.is_empty() {
+        let matrix = match TransferMatrix::new(&orrery, &args.orbits) {
+            Ok(matrix) => matrix,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+        println!("Two-impulse transfer delta-v (m/s), ideal phasing:");
+        print!("{}", matrix.to_table());
+        if let Some(path) = &args.transfer_csv {
+            if let Err(e) = std::fs::write(path, matrix.to_csv()) {
+                eprintln!("Couldn't write {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
 }
@@ -1,24 +1,488 @@
-use kiss3d::light::Light;
+use std::env;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::Parser;
 use kiss3d::window::Window;
 use nalgebra::Vector3;
-use rust_ksp::file::read_file;
-use rust_ksp::gui::Simulation;
-use rust_ksp::model::orrery::BodyID;
+use rust_ksp::file::{parse_bodies, read_preset, BodiesPreset};
+use rust_ksp::generator::generate_system;
+use rust_ksp::gui::{Autosave, Bookmarks, Gamepad, PlanBundle, Session, Simulation};
+use rust_ksp::model::figure::{Figure, FigureCamera};
+use rust_ksp::model::gantt::GanttChart;
+use rust_ksp::model::orrery::{
+    Arrival, BodyID, Propagation, SailAttitude, SailSchedule, ShipPlacement, SoiModel, SolarSail,
+    Stage, StageStack, WalkerConstellation,
+};
+use rust_ksp::model::readouts::CustomReadout;
+use rust_ksp::model::stability::check_stability;
+use rust_ksp::model::templates::{TemplateLibrary, TemplateUse};
 use rust_ksp::model::timeline::Timeline;
+use rust_ksp::model::visibility::VisibilityMatrix;
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// Which bodies file to load: approximate or ksp-exact
+    #[arg(long, default_value_t = BodiesPreset::Approximate)]
+    preset: BodiesPreset,
+    /// Instead of a preset, make up a star system from this seed. The ship
+    /// starts around its first planet.
+    #[arg(long)]
+    generate: Option<u64>,
+    /// Also write the made-up system to this file, in the same format as the
+    /// presets.
+    #[arg(long, requires = "generate")]
+    generate_output: Option<PathBuf>,
+    /// How to compute sphere of influence radii: laplace, hill, or fixed.
+    /// Defaults to whatever suits the preset.
+    #[arg(long)]
+    soi_model: Option<SoiModel>,
+    /// Advance each orbit's periapsis to account for general relativity. Only
+    /// noticeable for real-scale systems over long spans of time.
+    #[arg(long)]
+    relativistic_precession: bool,
+    /// What to call the ship.
+    #[arg(long)]
+    ship_name: Option<String>,
+    /// Give the ship a solar sail, starting edge-on to the Sun. Its path is
+    /// integrated numerically.
+    #[arg(long)]
+    solar_sail: bool,
+    /// How much delta-v the ship has for its burns, in m/s. Plans that need
+    /// more are flagged.
+    #[arg(long)]
+    delta_v_capacity: Option<f64>,
+    /// What the ship does once its plan is over: capture or land. Its cost
+    /// is estimated from where the ship ends up, and added to the plan's.
+    #[arg(long)]
+    arrival: Option<Arrival>,
+    /// A stage of the ship, written dry,wet,isp,thrust, with the masses in kg,
+    /// the Isp in seconds and the thrust in N. Give one for each stage, in
+    /// the order they're burned.
+    #[arg(long)]
+    stage: Vec<Stage>,
+    /// Make the ship's burns over time with the engines of its stages,
+    /// instead of all at once. Needs --stage. Its path through each burn is
+    /// integrated numerically.
+    #[arg(long, requires = "stage")]
+    finite_burns: bool,
+    /// Mark when each ship passes into and out of the umbra and penumbra of
+    /// the body it's orbiting.
+    #[arg(long)]
+    eclipse_events: bool,
+    /// Instead of opening a window, write a Gantt chart of the mission to this
+    /// SVG file and exit.
+    #[arg(long)]
+    export_gantt: Option<PathBuf>,
+    /// Instead of opening a window, write which ships can see each other over
+    /// time to this CSV file and exit. There's a column for each pair of
+    /// ships, and a row for each sample.
+    #[arg(long)]
+    export_visibility: Option<PathBuf>,
+    /// Instead of opening a window, draw the system to this PNG file and
+    /// exit. This is a simplified picture, drawn without a GPU.
+    #[arg(long)]
+    render_png: Option<PathBuf>,
+    /// Time to draw the picture at, in seconds.
+    #[arg(long, default_value_t = 0.0)]
+    render_time: f64,
+    /// Name of the body the picture is centered on. Defaults to the one the
+    /// ship starts around.
+    #[arg(long)]
+    render_focus: Option<String>,
+    /// Azimuthal angle of the camera, in degrees.
+    #[arg(long, default_value_t = 0.0)]
+    render_theta: f64,
+    /// Polar angle of the camera, in degrees from straight above.
+    #[arg(long, default_value_t = 45.0)]
+    render_phi: f64,
+    /// Distance of the camera from the focus, in meters. Defaults to ten
+    /// times the focus's radius.
+    #[arg(long)]
+    render_distance: Option<f64>,
+    /// Width of the picture, in pixels.
+    #[arg(long, default_value_t = 1280)]
+    render_width: u32,
+    /// Height of the picture, in pixels.
+    #[arg(long, default_value_t = 960)]
+    render_height: u32,
+    /// How many hours the exported visibility covers.
+    #[arg(long, default_value_t = 24.0)]
+    visibility_hours: f64,
+    /// How often to sample visibility, in seconds.
+    #[arg(long, default_value_t = 60.0)]
+    visibility_step: f64,
+    /// Also reconstruct this many days of history before the start, by
+    /// searching backwards from the ships' starting states.
+    #[arg(long, default_value_t = 0.0)]
+    history_days: f64,
+    /// How many hours back ships' trails go, when they're shown.
+    #[arg(long, default_value_t = 6.0)]
+    trail_hours: f64,
+    /// How much bigger than usual to draw markers and text, e.g., 2 on
+    /// high-DPI displays.
+    #[arg(long, default_value_t = 1.0)]
+    ui_scale: f32,
+    /// Draw spheres and lines in batches, with instancing, instead of one at
+    /// a time. Faster with many bodies and long trails.
+    #[arg(long)]
+    instanced_rendering: bool,
+    /// File to load saved maneuver templates from.
+    #[arg(long, default_value = "templates.txt")]
+    templates: PathBuf,
+    /// Apply a saved maneuver template to the ship, written name@time, with
+    /// the time of its first burn in seconds. Can be given more than once.
+    #[arg(long)]
+    apply_template: Vec<TemplateUse>,
+    /// File to load and save bookmarked views in.
+    #[arg(long, default_value = "bookmarks.txt")]
+    bookmarks: PathBuf,
+    /// An extra readout for the focused object, written label=expression,
+    /// e.g., "Apo margin=apoapsis - atmosphere_height". Can be given more
+    /// than once.
+    #[arg(long)]
+    readout: Vec<CustomReadout>,
+    /// Joystick device to read a gamepad from, e.g., /dev/input/js0.
+    #[arg(long)]
+    gamepad: Option<PathBuf>,
+    /// Autosave the session every this many seconds. If the last session
+    /// left an autosave behind, because it didn't exit normally, you're asked
+    /// whether to restore it.
+    #[arg(long)]
+    autosave_seconds: Option<f64>,
+    /// Directory to keep autosaves in. Defaults to one in your
+    /// $XDG_STATE_HOME, or ~/.local/state.
+    #[arg(long, requires = "autosave_seconds")]
+    autosave_dir: Option<PathBuf>,
+    /// Directory to save screenshots in.
+    #[arg(long, default_value = "screenshots")]
+    screenshot_dir: PathBuf,
+    /// Directory to save plan bundles in, to share the plan with others.
+    #[arg(long, default_value = "bundles")]
+    bundle_dir: PathBuf,
+    /// Open a plan bundle someone else saved, read-only, instead of starting
+    /// a new plan. Options about the system and ships are ignored.
+    #[arg(long)]
+    open_bundle: Option<PathBuf>,
+    /// How many days the exported Gantt chart covers.
+    #[arg(long, default_value_t = 30.0)]
+    gantt_days: f64,
+    /// Also put a Walker delta constellation around Kerbin (or the first
+    /// planet of a made-up system), written
+    /// inclination:total/planes/phasing, with the inclination in degrees.
+    #[arg(long)]
+    walker: Option<WalkerConstellation>,
+    /// Altitude of the constellation above the surface, in meters.
+    #[arg(long, default_value_t = 2000000.0)]
+    walker_altitude: f64,
+    /// Ships in the constellation are named this, followed by their plane
+    /// and their position in it.
+    #[arg(long, default_value = "Sat")]
+    walker_name: String,
+}
 
 fn main() {
+    let args = Args::parse();
+    if let Some(path) = &args.open_bundle {
+        open_bundle(&args, path);
+        return;
+    }
+
+    let write_or_exit = |path: &PathBuf, contents: String| {
+        if let Err(e) = std::fs::write(path, contents) {
+            eprintln!("Couldn't write {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut orrery = match args.generate {
+        Some(seed) => {
+            let contents = generate_system(seed);
+            if let Some(path) = &args.generate_output {
+                write_or_exit(path, contents.clone());
+            }
+            parse_bodies(&contents)
+        }
+        None => read_preset(args.preset),
+    };
+    if let Some(soi_model) = args.soi_model {
+        orrery.set_soi_model(soi_model);
+    }
+    orrery.set_relativistic_precession(args.relativistic_precession);
+    for issue in check_stability(&orrery) {
+        eprintln!("Warning: {}", issue.describe(&orrery));
+    }
+    // Kerbin, or the first planet of a made-up system
+    let home = match args.generate {
+        Some(_) => BodyID(1),
+        None => BodyID(4),
+    };
+    let ship_id = match args.generate {
+        Some(_) => {
+            let radius = 3.0 * orrery.get_body(home).info.radius as f64;
+            let (position, velocity) = ShipPlacement::circular(home, radius, 0.0).state(&orrery);
+            orrery.add_ship(position, velocity, 0.0, home)
+        }
+        None => orrery.add_ship(Vector3::x() * 6000000.0, Vector3::y() * 1000.0, 0.0, home),
+    };
+    orrery.set_ship_name(ship_id, args.ship_name.clone());
+    if args.solar_sail {
+        orrery.set_ship_propagation(
+            ship_id,
+            Propagation::Numeric {
+                max_arc_duration: 6.0 * 3600.0,
+            },
+        );
+        orrery.set_ship_sail(
+            ship_id,
+            Some(SolarSail {
+                area: 1000.0,
+                mass: 100.0,
+                reflectivity: 0.9,
+                schedule: SailSchedule::new(SailAttitude {
+                    cone: std::f64::consts::FRAC_PI_2,
+                    clock: 0.0,
+                }),
+            }),
+        );
+    }
+
+    orrery.set_ship_delta_v_capacity(ship_id, args.delta_v_capacity);
+    orrery.set_ship_arrival(ship_id, args.arrival);
+    if !args.stage.is_empty() {
+        orrery.set_ship_stages(ship_id, Some(StageStack::new(args.stage.clone())));
+    }
+    orrery.set_ship_finite_burns(ship_id, args.finite_burns);
+
+    if let Some(walker) = &args.walker {
+        let radius = orrery.get_body(home).info.radius as f64 + args.walker_altitude;
+        orrery.add_constellation(walker, home, radius, 0.0, &args.walker_name);
+    }
+
+    if args.eclipse_events {
+        let ship_ids: Vec<_> = orrery.ships().map(|s| s.id).collect();
+        for id in ship_ids {
+            orrery.set_ship_eclipse_tracking(id, true);
+        }
+    }
+
+    let mut timeline = Timeline::new(orrery, 0.0);
+    timeline.extend_back_until(-args.history_days * 86400.0);
+    if !args.apply_template.is_empty() {
+        let library = match TemplateLibrary::load(args.templates.clone()) {
+            Ok(library) => library,
+            Err(e) => {
+                eprintln!("Couldn't read {}: {}", args.templates.display(), e);
+                std::process::exit(1);
+            }
+        };
+        for template_use in args.apply_template.iter() {
+            let template = match library.get(&template_use.name) {
+                Some(template) => template,
+                None => {
+                    eprintln!("No maneuver template named {:?}", template_use.name);
+                    std::process::exit(1);
+                }
+            };
+            if let Err(e) = template.apply(&mut timeline, ship_id, template_use.time) {
+                eprintln!("Couldn't apply {:?}: {}", template_use.name, e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(path) = &args.export_gantt {
+        let end_time = args.gantt_days * 86400.0;
+        timeline.extend_until(end_time);
+        let chart = GanttChart::from_timeline(&timeline, end_time);
+        write_or_exit(path, chart.to_svg());
+    }
+    if let Some(path) = &args.export_visibility {
+        let end_time = args.visibility_hours * 3600.0;
+        timeline.extend_until(end_time);
+        let mut ship_ids: Vec<_> = timeline
+            .get_orrery_at(0.0)
+            .unwrap()
+            .ships()
+            .map(|s| s.id)
+            .collect();
+        ship_ids.sort();
+        let matrix = VisibilityMatrix::from_timeline(
+            &timeline,
+            ship_ids,
+            0.0,
+            end_time,
+            args.visibility_step,
+        );
+        write_or_exit(path, matrix.to_csv());
+    }
+    if let Some(path) = &args.render_png {
+        timeline.extend_until(args.render_time);
+        let orrery = match timeline.get_orrery_at(args.render_time) {
+            Some(orrery) => orrery,
+            None => {
+                eprintln!("Can't draw before the start of the timeline");
+                std::process::exit(1);
+            }
+        };
+        let focus = match &args.render_focus {
+            Some(name) => match orrery.bodies().find(|b| &b.info.name == name) {
+                Some(body) => body.id,
+                None => {
+                    eprintln!("No body named {}", name);
+                    std::process::exit(1);
+                }
+            },
+            None => home,
+        };
+        let distance = args
+            .render_distance
+            .unwrap_or(10.0 * orrery.get_body(focus).info.radius as f64);
+        let figure = Figure {
+            camera: FigureCamera {
+                focus,
+                theta: args.render_theta.to_radians(),
+                phi: args.render_phi.to_radians(),
+                distance,
+            },
+            width: args.render_width,
+            height: args.render_height,
+        };
+        if let Err(e) = figure.save_png(&orrery, args.render_time, path) {
+            eprintln!("Couldn't write {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+    if args.export_gantt.is_some() || args.export_visibility.is_some() || args.render_png.is_some()
+    {
+        return;
+    }
+
+    // Ask before opening the window, so the question isn't hidden behind it.
+    // The session's changes are made on top of the timeline as it is now.
+    let fingerprint = Autosave::fingerprint(&timeline);
+    let autosave = match args.autosave_seconds {
+        Some(seconds) if seconds > 0.0 => {
+            let dir = match args.autosave_dir.clone().or_else(default_autosave_dir) {
+                Some(dir) => dir,
+                None => {
+                    eprintln!(
+                        "Couldn't find a directory for autosaves; give one with --autosave-dir"
+                    );
+                    std::process::exit(1);
+                }
+            };
+            Some((dir, Duration::from_secs_f64(seconds)))
+        }
+        _ => None,
+    };
+    let session = autosave
+        .as_ref()
+        .and_then(|(dir, _)| offer_autosave(dir, &fingerprint));
+
     let mut window = Window::new("KSP Orbit Simulator");
-    window.set_light(Light::StickToCamera);
     window.set_framerate_limit(Some(60));
 
-    let mut orrery = read_file("ksp-bodies.txt");
-    orrery.add_ship(
-        Vector3::x() * 6000000.0,
-        Vector3::y() * 1000.0,
-        0.0,
-        BodyID(4),
+    let bookmarks = match Bookmarks::load(args.bookmarks.clone()) {
+        Ok(bookmarks) => bookmarks,
+        Err(e) => {
+            eprintln!("Couldn't read {}: {}", args.bookmarks.display(), e);
+            std::process::exit(1);
+        }
+    };
+    let mut simulation = with_display_options(
+        Simulation::new(timeline, &mut window).with_bookmarks(bookmarks),
+        &args,
     );
+    if let Some(session) = session {
+        simulation = simulation.with_session(session);
+    }
+    if let Some((dir, interval)) = autosave {
+        simulation = simulation.with_autosave(Autosave::new(dir, interval, fingerprint));
+    }
+    window.render_loop(simulation);
+}
+
+/// Shows the plan in the bundle, without letting it be changed. Nothing about
+/// it is autosaved, since there's nothing to lose.
+fn open_bundle(args: &Args, path: &Path) {
+    let bundle = match PlanBundle::load(path) {
+        Ok(bundle) => bundle,
+        Err(e) => {
+            eprintln!("Couldn't read {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    };
+    let timeline = match bundle.timeline() {
+        Ok(timeline) => timeline,
+        Err(e) => {
+            eprintln!("Couldn't set up the plan in {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    };
 
-    let simulation = Simulation::new(Timeline::new(orrery, 0.0), &mut window);
+    let mut window = Window::new("KSP Orbit Simulator (read-only)");
+    window.set_framerate_limit(Some(60));
+    let simulation = with_display_options(
+        Simulation::new(timeline, &mut window).with_plan_bundle(bundle),
+        args,
+    );
     window.render_loop(simulation);
 }
+
+/// Sets the options for how things are shown and controlled, which don't
+/// depend on what plan is open.
+fn with_display_options(mut simulation: Simulation, args: &Args) -> Simulation {
+    simulation = simulation
+        .with_custom_readouts(args.readout.clone())
+        .with_trail_duration(args.trail_hours * 3600.0)
+        .with_ui_scale(args.ui_scale)
+        .with_instanced_rendering(args.instanced_rendering)
+        .with_screenshot_dir(args.screenshot_dir.clone())
+        .with_bundle_dir(args.bundle_dir.clone());
+    if let Some(path) = &args.gamepad {
+        match Gamepad::open(path) {
+            Ok(gamepad) => simulation = simulation.with_gamepad(gamepad),
+            Err(e) => {
+                eprintln!("Couldn't open {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+    simulation
+}
+
+/// Where autosaves go, unless --autosave-dir says otherwise: a directory of
+/// this user's, following the XDG base directory spec.
+fn default_autosave_dir() -> Option<PathBuf> {
+    let state_dir = match env::var_os("XDG_STATE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".local/state"),
+    };
+    Some(state_dir.join("ksp-orbit-planner/autosave"))
+}
+
+/// If the last session left an autosave behind, asks whether to pick up where
+/// it left off, and returns it if so. It's only meaningful with the same
+/// options as that session was started with, so it's only offered if it was
+/// made on a timeline with the same fingerprint.
+fn offer_autosave(dir: &Path, fingerprint: &str) -> Option<Session> {
+    let (session, saved_at) = match Autosave::find_latest(dir, fingerprint) {
+        Ok(Some(latest)) => latest,
+        Ok(None) => return None,
+        Err(e) => {
+            println!("Couldn't read the autosave in {}: {}", dir.display(), e);
+            return None;
+        }
+    };
+    let minutes_ago = saved_at.elapsed().map_or(0, |d| d.as_secs() / 60);
+    print!(
+        "The last session didn't exit normally. Restore its autosave ({} changes, from {} minutes ago)? [y/N] ",
+        session.changes.len(),
+        minutes_ago
+    );
+    io::stdout().flush().ok()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).ok()?;
+    answer.trim().eq_ignore_ascii_case("y").then_some(session)
+}
@@ -175,7 +175,7 @@ fn get_coeff_of_xn(n: usize, k: usize) -> f64 {
 
     // x^(odd) has zero coefficients on T_(even)
     // and vice versa
-    if (n - k) % 2 != 0 {
+    if !(n - k).is_multiple_of(2) {
         return 0.0;
     }
 
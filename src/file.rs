@@ -1,93 +1,347 @@
 use std::collections::HashMap;
 use std::f64::consts::PI;
+use std::fmt::Display;
 use std::fs;
+use std::str::FromStr;
 
 use nalgebra::Point3;
 
 use crate::astro::{Orbit, PointMass};
-use crate::model::orrery::{BodyInfo, Orrery};
+use crate::model::orrery::{BodyInfo, OrbitElements, Orrery, Rings, SoiModel};
 
-struct LineParser<I> {
-    iter: I,
+/// The columns written by [write_bodies], which are all the ones
+/// [parse_bodies] understands.
+const COLUMNS: [&str; 20] = [
+    "NAME",
+    "MU",
+    "RADIUS",
+    "COLOR",
+    "PARENT",
+    "SEMIMAJOR",
+    "ECC",
+    "INCL",
+    "LAN",
+    "ARGP",
+    "MAAE",
+    "SOI",
+    "ROTATION",
+    "ROTANGLE",
+    "ATMOSPHERE",
+    "TEXTURE",
+    "RINGINNER",
+    "RINGOUTER",
+    "RINGCOLOR",
+    "RINGTEXTURE",
+];
+
+/// The bodies files that ship with this repository.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BodiesPreset {
+    /// Approximate data for the Kerbol system. SOIs are computed rather than
+    /// looked up.
+    #[default]
+    Approximate,
+    /// KSP's published values, including its tabulated SOI radii, rotation
+    /// periods, and atmosphere heights.
+    KspExact,
 }
 
-impl<'a, I: Iterator<Item = &'a str>> LineParser<I> {
-    fn next_string(&mut self) -> &'a str {
-        self.iter.next().expect("No fields left in line")
+impl BodiesPreset {
+    pub fn filename(&self) -> &'static str {
+        match self {
+            BodiesPreset::Approximate => "ksp-bodies.txt",
+            BodiesPreset::KspExact => "ksp-bodies-exact.txt",
+        }
+    }
+
+    /// The SOI model that makes the most sense for this data.
+    pub fn soi_model(&self) -> SoiModel {
+        match self {
+            BodiesPreset::Approximate => SoiModel::Laplace,
+            BodiesPreset::KspExact => SoiModel::Fixed,
+        }
+    }
+}
+
+/// Looks up the fields of a line by the column names given in the header.
+///
+/// Lines may be shorter than the header; missing fields, as well as fields
+/// containing a single dash, are treated as absent.
+struct LineParser<'a> {
+    fields: HashMap<&'a str, &'a str>,
+}
+
+impl<'a> LineParser<'a> {
+    fn new(header: &[&'a str], line: &'a str) -> Self {
+        let fields = header
+            .iter()
+            .copied()
+            .zip(line.split_ascii_whitespace())
+            .collect();
+        Self { fields }
+    }
+
+    fn optional_string(&self, column: &str) -> Option<&'a str> {
+        self.fields.get(column).copied().filter(|s| *s != "-")
     }
 
-    fn next_f64(&mut self) -> f64 {
-        self.next_string().parse().expect("Could not parse as f64")
+    fn string(&self, column: &str) -> Result<&'a str, String> {
+        self.optional_string(column)
+            .ok_or_else(|| format!("No value in column {}", column))
     }
 
-    fn next_color(&mut self) -> Point3<f32> {
-        let s = self.next_string();
-        assert_eq!(s.len(), 6);
-        let r = u8::from_str_radix(&s[0..2], 16).unwrap();
-        let g = u8::from_str_radix(&s[2..4], 16).unwrap();
-        let b = u8::from_str_radix(&s[4..6], 16).unwrap();
+    fn optional_f64(&self, column: &str) -> Result<Option<f64>, String> {
+        self.optional_string(column)
+            .map(|s| parse_f64(column, s))
+            .transpose()
+    }
 
-        Point3::new(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0)
+    fn f64(&self, column: &str) -> Result<f64, String> {
+        parse_f64(column, self.string(column)?)
     }
+
+    fn optional_color(&self, column: &str) -> Result<Option<Point3<f32>>, String> {
+        self.optional_string(column)
+            .map(|_| self.color(column))
+            .transpose()
+    }
+
+    fn color(&self, column: &str) -> Result<Point3<f32>, String> {
+        let s = self.string(column)?;
+        let channel = |i: usize| {
+            s.get(i..i + 2)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .map(|x| x as f32 / 255.0)
+        };
+        match (s.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Point3::new(r, g, b)),
+            _ => Err(format!(
+                "Could not parse {} in column {} as a color",
+                s, column
+            )),
+        }
+    }
+}
+
+fn parse_f64(column: &str, s: &str) -> Result<f64, String> {
+    s.parse()
+        .map_err(|_| format!("Could not parse {} in column {} as f64", s, column))
+}
+
+/// Loads the given preset, with the preset's preferred SOI model.
+pub fn read_preset(preset: BodiesPreset) -> Orrery {
+    let mut orrery = read_file(preset.filename());
+    orrery.set_soi_model(preset.soi_model());
+    orrery
 }
 
 pub fn read_file(filename: &str) -> Orrery {
+    let contents = fs::read_to_string(filename).unwrap();
+    parse_bodies(&contents)
+}
+
+/// Builds an orrery from the contents of a bodies file. Each body has to come
+/// after its parent. Panics if the file isn't valid; see [try_parse_bodies]
+/// for files that might not be.
+pub fn parse_bodies(contents: &str) -> Orrery {
+    try_parse_bodies(contents).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Like [parse_bodies], but returns what's wrong with the file instead of
+/// panicking.
+pub fn try_parse_bodies(contents: &str) -> Result<Orrery, String> {
     let mut orrery = Orrery::new();
 
     let mut name_to_id = HashMap::new();
     let mut name_to_mu = HashMap::new();
 
-    // Read lines, skipping header
-    for line in fs::read_to_string(filename).unwrap().lines().skip(1) {
-        let mut fields = LineParser {
-            iter: line.split_ascii_whitespace(),
-        };
+    let mut lines = contents.lines();
+    let header: Vec<_> = lines
+        .next()
+        .ok_or("File has no header")?
+        .split_ascii_whitespace()
+        .collect();
+
+    for line in lines {
+        let fields = LineParser::new(&header, line);
 
         // Get name
-        let name = fields.next_string();
+        let name = fields.string("NAME")?;
 
         // Get body-info
-        let mu = fields.next_f64();
+        let mu = fields.f64("MU")?;
+        let radius = fields.f64("RADIUS")?;
+        if !(mu.is_finite() && mu > 0.0 && radius.is_finite() && radius > 0.0) {
+            return Err(format!("{} needs a positive mu and radius", name));
+        }
+        let color = fields.color("COLOR")?;
+        // Rings take the body's color unless given their own
+        let rings = match (
+            fields.optional_f64("RINGINNER")?,
+            fields.optional_f64("RINGOUTER")?,
+        ) {
+            (Some(inner), Some(outer)) => Some(Rings {
+                inner_radius: inner as f32,
+                outer_radius: outer as f32,
+                color: fields.optional_color("RINGCOLOR")?.unwrap_or(color),
+                texture: fields.optional_string("RINGTEXTURE").map(str::to_owned),
+            }),
+            (None, None) => None,
+            _ => {
+                return Err(format!(
+                    "{} needs both an inner and outer ring radius",
+                    name
+                ))
+            }
+        };
         let body_info = BodyInfo {
             name: name.to_owned(),
             mu,
-            radius: fields.next_f64() as f32,
-            color: fields.next_color(),
+            radius: radius as f32,
+            color,
+            soi_radius: fields.optional_f64("SOI")?,
+            rotation_period: fields.optional_f64("ROTATION")?,
+            initial_rotation: fields.optional_f64("ROTANGLE")?.unwrap_or(0.0).to_radians(),
+            atmosphere_height: fields.optional_f64("ATMOSPHERE")?,
+            texture: fields.optional_string("TEXTURE").map(str::to_owned),
+            rings,
         };
 
         // Figure out what our orbit is
-        let parent = fields.next_string();
-
-        let id = if parent == "-" {
-            orrery.add_fixed_body(body_info)
-        } else {
-            let parent_id = name_to_id[parent];
-            let parent_mu = name_to_mu[parent];
-
-            let (a, ecc, incl, lan, argp, maae) = (
-                fields.next_f64(),
-                fields.next_f64(),
-                fields.next_f64().to_radians(),
-                fields.next_f64().to_radians(),
-                fields.next_f64().to_radians(),
-                fields.next_f64(), // already in radians!
-            );
-
-            assert!(ecc < 1.0, "Currently can only load elliptic orbits");
-
-            let orbit =
-                Orbit::from_kepler(PointMass::with_mu(parent_mu), (), a, ecc, incl, lan, argp);
-            // M = 2pi/P (t - t_periapse)
-            let time_since_periapsis = maae * orbit.period().unwrap() / 2.0 / PI;
-            let time_at_periapsis = -time_since_periapsis;
-
-            orrery.add_body(body_info, orbit, time_at_periapsis, parent_id)
+        let id = match fields.optional_string("PARENT") {
+            None => orrery.add_fixed_body(body_info),
+            Some(parent) => {
+                let (parent_id, parent_mu) = match (name_to_id.get(parent), name_to_mu.get(parent))
+                {
+                    (Some(&id), Some(&mu)) => (id, mu),
+                    _ => {
+                        return Err(format!(
+                            "{}'s parent {} isn't listed before it",
+                            name, parent
+                        ))
+                    }
+                };
+
+                let (a, ecc, incl, lan, argp, maae) = (
+                    fields.f64("SEMIMAJOR")?,
+                    fields.f64("ECC")?,
+                    fields.f64("INCL")?.to_radians(),
+                    fields.f64("LAN")?.to_radians(),
+                    fields.f64("ARGP")?.to_radians(),
+                    fields.f64("MAAE")?, // already in radians!
+                );
+
+                if !(a.is_finite() && a > 0.0 && (0.0..1.0).contains(&ecc)) {
+                    return Err(format!(
+                        "Currently can only load elliptic orbits, unlike {}'s",
+                        name
+                    ));
+                }
+
+                let orbit =
+                    Orbit::from_kepler(PointMass::with_mu(parent_mu), (), a, ecc, incl, lan, argp);
+                // M = 2pi/P (t - t_periapse)
+                let time_since_periapsis = maae * orbit.period().unwrap() / 2.0 / PI;
+                let time_at_periapsis = -time_since_periapsis;
+
+                orrery.add_body(body_info, orbit, time_at_periapsis, parent_id)
+            }
         };
         name_to_id.insert(name, id);
         name_to_mu.insert(name, mu);
     }
 
-    orrery
+    Ok(orrery)
+}
+
+/// Writes the orrery's bodies as a bodies file, which [parse_bodies] reads
+/// back. Orbits are written as they are at time zero, so only elliptic ones
+/// can be written, and names and texture paths can't have spaces in them.
+pub fn write_bodies(orrery: &Orrery) -> String {
+    let mut bodies: Vec<_> = orrery.bodies().collect();
+    // Bodies get their IDs in the order they're added, which is parents first
+    bodies.sort_by_key(|b| b.id);
+
+    let mut contents = COLUMNS.join(" ");
+    contents.push('\n');
+    let optional = |x: Option<f64>| x.map_or(String::from("-"), |x| x.to_string());
+    for body in bodies {
+        let info = &body.info;
+        let mut fields = vec![
+            info.name.clone(),
+            info.mu.to_string(),
+            info.radius.to_string(),
+            color_to_hex(&info.color),
+        ];
+        match orrery.orbit_of_body(body.id) {
+            Some(orbit) => {
+                let elements = OrbitElements::of_orbit(&orbit, 0.0)
+                    .expect("Currently can only write elliptic orbits");
+                fields.extend([
+                    orbit.primary().info.name.clone(),
+                    elements.semimajor_axis.to_string(),
+                    elements.eccentricity.to_string(),
+                    elements.inclination.to_degrees().to_string(),
+                    elements.long_asc_node.to_degrees().to_string(),
+                    elements.arg_periapsis.to_degrees().to_string(),
+                    elements.mean_anomaly.to_string(),
+                ]);
+            }
+            None => fields.extend(std::iter::repeat_n(String::from("-"), 7)),
+        }
+        let rings = info.rings.as_ref();
+        fields.extend([
+            optional(info.soi_radius),
+            optional(info.rotation_period),
+            info.initial_rotation.to_degrees().to_string(),
+            optional(info.atmosphere_height),
+            info.texture.clone().unwrap_or_else(|| String::from("-")),
+            optional(rings.map(|r| r.inner_radius as f64)),
+            optional(rings.map(|r| r.outer_radius as f64)),
+            rings.map_or(String::from("-"), |r| color_to_hex(&r.color)),
+            rings
+                .and_then(|r| r.texture.clone())
+                .unwrap_or_else(|| String::from("-")),
+        ]);
+        contents.push_str(&fields.join(" "));
+        contents.push('\n');
+    }
+    contents
+}
+
+fn color_to_hex(color: &Point3<f32>) -> String {
+    let channel = |x: f32| (x * 255.0).round().clamp(0.0, 255.0) as u8;
+    format!(
+        "{:02X}{:02X}{:02X}",
+        channel(color.x),
+        channel(color.y),
+        channel(color.z)
+    )
+}
+
+impl FromStr for BodiesPreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "approximate" => Ok(BodiesPreset::Approximate),
+            "ksp-exact" => Ok(BodiesPreset::KspExact),
+            _ => Err(format!(
+                "Unknown preset {:?}; expected approximate or ksp-exact",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for BodiesPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            BodiesPreset::Approximate => "approximate",
+            BodiesPreset::KspExact => "ksp-exact",
+        };
+        write!(f, "{}", name)
+    }
 }
 
 #[cfg(test)]
@@ -95,7 +349,7 @@ mod tests {
     use approx::assert_relative_eq;
 
     use super::*;
-    use crate::model::orrery::BodyID;
+    use crate::model::orrery::{BodyID, Frame, SoiModel};
 
     #[test]
     fn test() {
@@ -118,4 +372,131 @@ mod tests {
             max_relative = 1e-14
         );
     }
+
+    #[test]
+    fn test_soi_models() {
+        let mut orrery = read_file("ksp-bodies.txt");
+        let kerbin = BodyID(4);
+
+        // Kerbol has no SOI, no matter how you compute it
+        for model in [SoiModel::Laplace, SoiModel::Hill, SoiModel::Fixed] {
+            orrery.set_soi_model(model);
+            assert_eq!(orrery.get_soi_radius(BodyID(0)), None);
+        }
+
+        // The Laplace sphere is KSP's formula, so with KSP's parameters, it should
+        // match the tabulated value pretty closely.
+        orrery.set_soi_model(SoiModel::Laplace);
+        let laplace = orrery.get_soi_radius(kerbin).unwrap();
+        assert_relative_eq!(laplace, 84_159_286.0, max_relative = 1e-6);
+
+        orrery.set_soi_model(SoiModel::Hill);
+        let hill = orrery.get_soi_radius(kerbin).unwrap();
+        assert!(hill > laplace);
+
+        orrery.set_soi_model(SoiModel::Fixed);
+        let fixed = orrery.get_soi_radius(kerbin).unwrap();
+        assert_eq!(fixed, 84_159_286.0);
+    }
+
+    #[test]
+    fn test_exact_preset() {
+        let orrery = read_preset(BodiesPreset::KspExact);
+        assert_eq!(orrery.soi_model(), SoiModel::Fixed);
+
+        let kerbol = orrery.get_body(BodyID(0));
+        assert_eq!(kerbol.info.name, "Kerbol");
+        assert_eq!(kerbol.info.rotation_period, Some(432_000.0));
+        assert_eq!(kerbol.info.atmosphere_height, Some(600_000.0));
+        assert_eq!(kerbol.info.soi_radius, None);
+
+        let kerbin = orrery.get_body(BodyID(4));
+        assert_eq!(kerbin.info.name, "Kerbin");
+        assert_eq!(kerbin.info.radius, 600_000.0);
+        assert_eq!(kerbin.info.rotation_period, Some(21_549.425));
+        assert_eq!(kerbin.info.atmosphere_height, Some(70_000.0));
+        assert_eq!(orrery.get_soi_radius(BodyID(4)), Some(84_159_286.0));
+
+        let mun = orrery.get_body(BodyID(5));
+        assert_eq!(mun.info.name, "Mun");
+        assert_eq!(mun.info.atmosphere_height, None);
+        assert_eq!(orrery.get_soi_radius(BodyID(5)), Some(2_429_559.1));
+
+        // Every orbiting body should have a tabulated SOI, and the Laplace formula
+        // should agree with it, since that's what KSP uses.
+        for orbit in orrery.body_orbits() {
+            let body = orbit.secondary();
+            let tabulated = body.info.soi_radius.unwrap();
+            assert_relative_eq!(orbit.soi_radius(), tabulated, max_relative = 1e-6);
+            assert!(body.info.rotation_period.is_some());
+        }
+    }
+    #[test]
+    fn test_textures() {
+        let contents = "\
+NAME   MU    RADIUS COLOR  PARENT SEMIMAJOR ECC INCL LAN ARGP MAAE TEXTURE
+Sun    1e18  1e8    FFFF00 -      -         -   -    -   -    -    -
+Planet 1e12  5e5    0000FF Sun    1e10      0   0    0   0    0    textures/planet.png
+Moon   1e10  1e5    B4B4B4 Planet 1e7       0   0    0   0    0";
+        let orrery = parse_bodies(contents);
+        assert_eq!(orrery.get_body(BodyID(0)).info.texture, None);
+        assert_eq!(
+            orrery.get_body(BodyID(1)).info.texture.as_deref(),
+            Some("textures/planet.png")
+        );
+        assert_eq!(orrery.get_body(BodyID(2)).info.texture, None);
+    }
+    #[test]
+    fn test_rings() {
+        let contents = "\
+NAME   MU    RADIUS COLOR  PARENT SEMIMAJOR ECC INCL LAN ARGP MAAE RINGINNER RINGOUTER RINGCOLOR
+Sun    1e18  1e8    FFFF00 -      -         -   -    -   -    -    -         -         -
+Giant  1e14  6e6    F0CD89 Sun    1e11      0   0    0   0    0    7e6       1.4e7     -
+Ice    1e14  5e6    1328C2 Sun    2e11      0   0    0   0    0    6e6       8e6       FFFFFF";
+        let orrery = parse_bodies(contents);
+        assert_eq!(orrery.get_body(BodyID(0)).info.rings, None);
+
+        // Rings default to the color of their body
+        let giant = &orrery.get_body(BodyID(1)).info;
+        assert_eq!(
+            giant.rings,
+            Some(Rings {
+                inner_radius: 7e6,
+                outer_radius: 1.4e7,
+                color: giant.color,
+                texture: None,
+            })
+        );
+        let ice = orrery.get_body(BodyID(2)).info.rings.as_ref().unwrap();
+        assert_eq!(ice.color, Point3::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_write_bodies() {
+        let original = read_preset(BodiesPreset::KspExact);
+        let written = parse_bodies(&write_bodies(&original));
+        assert_eq!(written.bodies().count(), original.bodies().count());
+        for body in original.bodies() {
+            let copy = written.get_body(body.id);
+            assert_eq!(copy.info.name, body.info.name);
+            assert_eq!(copy.info.mu, body.info.mu);
+            assert_eq!(copy.info.radius, body.info.radius);
+            assert_eq!(copy.info.color, body.info.color);
+            assert_eq!(copy.info.soi_radius, body.info.soi_radius);
+            assert_eq!(copy.info.rotation_period, body.info.rotation_period);
+            assert_eq!(copy.info.atmosphere_height, body.info.atmosphere_height);
+            assert_eq!(original.get_parent(body.id), written.get_parent(body.id));
+
+            // Bodies are in the same places, at any time
+            let time = 1e6;
+            let position = |orrery: &Orrery| {
+                orrery
+                    .get_body_state(body.id, time)
+                    .get_position(Frame::Root, time)
+            };
+            // Circular orbits pick up an eccentricity around the square root of
+            // the rounding error, so they can drift apart a little
+            assert_relative_eq!(position(&written), position(&original), max_relative = 1e-7);
+        }
+    }
 }
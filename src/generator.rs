@@ -0,0 +1,377 @@
+//! Makes up star systems, with a star, planets, and moons, for playing around
+//! in somewhere other than the Kerbol system. The same seed always makes the
+//! same system.
+
+use std::collections::HashSet;
+use std::f64::consts::TAU;
+use std::fmt::Write;
+
+use nalgebra::Point3;
+
+// Reference bodies, to scale the generated ones from
+const KERBOL_MU: f64 = 1.17233279483249e18;
+const KERBOL_RADIUS: f64 = 2.616e8;
+const KERBIN_MU: f64 = 3.5316e12;
+const KERBIN_RADIUS: f64 = 600000.0;
+const JOOL_MU: f64 = 2.82528004209995e14;
+const JOOL_RADIUS: f64 = 6e6;
+
+/// How many mutual Hill radii apart neighboring orbits have to be. Around ten
+/// keeps systems stable over long times.
+const HILL_SPACING: f64 = 10.0;
+/// Moons stay within this fraction of their planet's SOI.
+const MAX_MOON_SOI_FRACTION: f64 = 0.4;
+
+const SYLLABLES: [&str; 24] = [
+    "ka", "ro", "mi", "lo", "ve", "du", "na", "ee", "jo", "ty", "po", "la", "bo", "zar", "quin",
+    "tel", "os", "ar", "im", "un", "dre", "sk", "vo", "th",
+];
+
+/// A small, fast random number generator (SplitMix64). Not suitable for
+/// anything but making things up.
+pub(crate) struct Rng(pub(crate) u64);
+
+impl Rng {
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1).
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub(crate) fn uniform(&mut self, min: f64, max: f64) -> f64 {
+        min + (max - min) * self.next_f64()
+    }
+
+    /// Uniform in log space, for quantities that span orders of magnitude.
+    fn log_uniform(&mut self, min: f64, max: f64) -> f64 {
+        self.uniform(min.ln(), max.ln()).exp()
+    }
+
+    /// Uniform over the integers from `min` to `max`, inclusive.
+    fn int(&mut self, min: usize, max: usize) -> usize {
+        min + (self.next_u64() % (max - min + 1) as u64) as usize
+    }
+}
+
+/// One line of the bodies file.
+struct GeneratedBody {
+    name: String,
+    mu: f64,
+    radius: f64,
+    color: Point3<f32>,
+    orbit: Option<GeneratedOrbit>,
+}
+
+struct GeneratedOrbit {
+    parent: String,
+    semimajor_axis: f64,
+    eccentricity: f64,
+    /// In degrees, like the bodies file
+    inclination: f64,
+    long_asc_node: f64,
+    arg_periapsis: f64,
+    /// In radians
+    mean_anomaly: f64,
+}
+
+/// Makes up a system from the given seed, and returns it in the same format
+/// as the bodies files, ready for [parse_bodies](crate::file::parse_bodies).
+///
+/// Planets are spaced far enough apart, and moons kept close enough to their
+/// planets, that nothing should leave its SOI unless a ship pushes it.
+pub fn generate_system(seed: u64) -> String {
+    let mut generator = Generator {
+        rng: Rng(seed),
+        names: HashSet::new(),
+        bodies: vec![],
+    };
+    generator.generate();
+
+    let mut contents = String::from(
+        "NAME       MU             RADIUS     COLOR    PARENT     SEMIMAJOR      ECC    INCL   LAN    ARGP   MAAE\n",
+    );
+    for body in generator.bodies.iter() {
+        let color = body.color.map(|c| (c * 255.0).round() as u8);
+        let indent = match &body.orbit {
+            Some(orbit) if orbit.parent != generator.bodies[0].name => "  ",
+            _ => "",
+        };
+        write!(
+            contents,
+            "{}{:<10} {:<14.6e} {:<10.0} {:02X}{:02X}{:02X}",
+            indent, body.name, body.mu, body.radius, color.x, color.y, color.z
+        )
+        .unwrap();
+        match &body.orbit {
+            Some(orbit) => writeln!(
+                contents,
+                "   {:<10} {:<14.0} {:<6.3} {:<6.2} {:<6.1} {:<6.1} {:.2}",
+                orbit.parent,
+                orbit.semimajor_axis,
+                orbit.eccentricity,
+                orbit.inclination,
+                orbit.long_asc_node,
+                orbit.arg_periapsis,
+                orbit.mean_anomaly
+            ),
+            None => writeln!(contents, "   -"),
+        }
+        .unwrap();
+    }
+    contents
+}
+
+struct Generator {
+    rng: Rng,
+    names: HashSet<String>,
+    // Parents come before their children
+    bodies: Vec<GeneratedBody>,
+}
+
+impl Generator {
+    fn generate(&mut self) {
+        // A star somewhere between a third and three times Kerbol's mass
+        let star_mass = self.rng.log_uniform(0.3, 3.0);
+        let star_mu = star_mass * KERBOL_MU;
+        let star = GeneratedBody {
+            name: self.name(),
+            mu: star_mu,
+            radius: KERBOL_RADIUS * star_mass.powf(0.8),
+            color: star_color(star_mass),
+            orbit: None,
+        };
+        let star_name = star.name.clone();
+        let star_radius = star.radius;
+        self.bodies.push(star);
+
+        // Planets, each further out than the last by at least a little more
+        // than they need to be stable. Gas giants only form further out.
+        let num_planets = self.rng.int(3, 8);
+        let first_orbit = star_radius * self.rng.uniform(15.0, 40.0);
+        let mut inner: Option<(f64, f64, f64)> = None;
+        for _ in 0..num_planets {
+            let is_gas_giant =
+                inner.is_some_and(|(a, _, _)| a > 3.0 * first_orbit) && self.rng.next_f64() < 0.6;
+            let (mu, radius) = if is_gas_giant {
+                let mu = self.rng.log_uniform(1e13, 5e14).min(1e-3 * star_mu);
+                (mu, JOOL_RADIUS * (mu / JOOL_MU).powf(0.1))
+            } else {
+                let mu = self.rng.log_uniform(1e10, 5e12);
+                (mu, rocky_radius(mu))
+            };
+            let eccentricity = self.rng.uniform(0.0, 0.1);
+            let semimajor_axis = match inner {
+                None => first_orbit,
+                Some((inner_a, inner_mu, inner_ecc)) => {
+                    let min_ratio = stable_spacing(inner_mu + mu, star_mu) * (1.0 + inner_ecc)
+                        / (1.0 - eccentricity);
+                    inner_a * f64::max(min_ratio, self.rng.uniform(1.4, 2.0))
+                }
+            };
+            inner = Some((semimajor_axis, mu, eccentricity));
+
+            let name = self.name();
+            let color = if is_gas_giant {
+                self.color((0.4, 0.8), (0.7, 1.0))
+            } else {
+                self.color((0.15, 0.5), (0.55, 0.9))
+            };
+            let orbit = self.orbit(&star_name, semimajor_axis, eccentricity, 5.0);
+            let soi_radius = semimajor_axis * (mu / star_mu).powf(0.4);
+            self.bodies.push(GeneratedBody {
+                name: name.clone(),
+                mu,
+                radius,
+                color,
+                orbit: Some(orbit),
+            });
+
+            let max_moons = if is_gas_giant { 5 } else { 2 };
+            self.generate_moons(&name, mu, radius, soi_radius, max_moons);
+        }
+    }
+
+    /// Adds up to `max_moons` moons around the planet, stopping early if the
+    /// next one wouldn't fit in the planet's SOI.
+    fn generate_moons(
+        &mut self,
+        planet: &str,
+        planet_mu: f64,
+        planet_radius: f64,
+        soi_radius: f64,
+        max_moons: usize,
+    ) {
+        let num_moons = self.rng.int(0, max_moons);
+        let max_apoapsis = MAX_MOON_SOI_FRACTION * soi_radius;
+        let mut inner: Option<(f64, f64, f64)> = None;
+        for _ in 0..num_moons {
+            let mu = planet_mu * self.rng.log_uniform(1e-4, 2e-2);
+            let eccentricity = self.rng.uniform(0.0, 0.05);
+            let semimajor_axis = match inner {
+                None => planet_radius * self.rng.uniform(4.0, 10.0),
+                Some((inner_a, inner_mu, inner_ecc)) => {
+                    let min_ratio = stable_spacing(inner_mu + mu, planet_mu) * (1.0 + inner_ecc)
+                        / (1.0 - eccentricity);
+                    inner_a * f64::max(min_ratio, self.rng.uniform(1.5, 2.2))
+                }
+            };
+            if semimajor_axis * (1.0 + eccentricity) > max_apoapsis {
+                break;
+            }
+            inner = Some((semimajor_axis, mu, eccentricity));
+
+            let name = self.name();
+            let color = self.color((0.0, 0.3), (0.5, 0.9));
+            let orbit = self.orbit(planet, semimajor_axis, eccentricity, 10.0);
+            self.bodies.push(GeneratedBody {
+                name,
+                mu,
+                radius: rocky_radius(mu),
+                color,
+                orbit: Some(orbit),
+            });
+        }
+    }
+
+    fn orbit(
+        &mut self,
+        parent: &str,
+        semimajor_axis: f64,
+        eccentricity: f64,
+        max_inclination: f64,
+    ) -> GeneratedOrbit {
+        GeneratedOrbit {
+            parent: parent.to_owned(),
+            semimajor_axis,
+            eccentricity,
+            inclination: self.rng.uniform(0.0, max_inclination),
+            long_asc_node: self.rng.uniform(0.0, 360.0),
+            arg_periapsis: self.rng.uniform(0.0, 360.0),
+            mean_anomaly: self.rng.uniform(0.0, TAU),
+        }
+    }
+
+    /// Makes up a name that hasn't been used yet.
+    fn name(&mut self) -> String {
+        loop {
+            let num_syllables = self.rng.int(2, 3);
+            let name: String = (0..num_syllables)
+                .map(|_| SYLLABLES[self.rng.int(0, SYLLABLES.len() - 1)])
+                .collect();
+            let mut chars = name.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            let name = std::iter::once(first).chain(chars).collect::<String>();
+            if self.names.insert(name.clone()) {
+                return name;
+            }
+        }
+    }
+
+    /// A random color, with saturation and value in the given ranges.
+    fn color(&mut self, saturation: (f64, f64), value: (f64, f64)) -> Point3<f32> {
+        let hue = self.rng.next_f64();
+        let saturation = self.rng.uniform(saturation.0, saturation.1);
+        let value = self.rng.uniform(value.0, value.1);
+        hsv_to_color(hue, saturation, value)
+    }
+}
+
+/// How many times further out an orbit has to be than the one inside it, for
+/// the two to be [HILL_SPACING] mutual Hill radii apart, when the bodies have
+/// the given combined mu.
+fn stable_spacing(combined_mu: f64, primary_mu: f64) -> f64 {
+    // a2 - a1 >= K (m/3M)^(1/3) (a1 + a2) / 2
+    let half_width = HILL_SPACING * (combined_mu / (3.0 * primary_mu)).cbrt() / 2.0;
+    (1.0 + half_width) / (1.0 - half_width).max(0.1)
+}
+
+/// Rocky bodies are scaled from Kerbin.
+fn rocky_radius(mu: f64) -> f64 {
+    KERBIN_RADIUS * (mu / KERBIN_MU).powf(0.3)
+}
+
+/// Light stars are orange, Kerbol-sized ones yellow, and heavy ones white,
+/// going on blue.
+fn star_color(mass: f64) -> Point3<f32> {
+    let orange = Point3::new(1.0, 0.55, 0.25);
+    let yellow = Point3::new(1.0, 1.0, 0.0);
+    let blue_white = Point3::new(0.75, 0.85, 1.0);
+    // Masses range from 1/3 to 3, so this goes from -1 to 1
+    let t = (mass.ln() / 3f64.ln()).clamp(-1.0, 1.0) as f32;
+    if t < 0.0 {
+        yellow + (orange - yellow) * -t
+    } else {
+        yellow + (blue_white - yellow) * t
+    }
+}
+
+fn hsv_to_color(hue: f64, saturation: f64, value: f64) -> Point3<f32> {
+    let channel = |n: f64| {
+        let k = (n + 6.0 * hue).rem_euclid(6.0);
+        value - value * saturation * k.min(4.0 - k).clamp(0.0, 1.0)
+    };
+    Point3::new(channel(5.0), channel(3.0), channel(1.0)).map(|c| c as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::parse_bodies;
+    use crate::model::orrery::BodyID;
+
+    #[test]
+    fn test_generated_systems() {
+        // The same seed makes the same system
+        assert_eq!(generate_system(7), generate_system(7));
+        assert_ne!(generate_system(7), generate_system(8));
+
+        for seed in 0..20 {
+            let orrery = parse_bodies(&generate_system(seed));
+            assert!(orrery.get_parent(BodyID(0)).is_none());
+            let num_planets = orrery
+                .bodies()
+                .filter(|b| orrery.get_parent(b.id) == Some(BodyID(0)))
+                .count();
+            assert!((3..=8).contains(&num_planets));
+
+            for orbit in orrery.body_orbits() {
+                let body = orbit.secondary();
+                let parent = orbit.primary();
+
+                // Everything stays well clear of its parent, and moons stay
+                // inside their planets' SOIs
+                let periapsis = orbit.semimajor_axis() * (1.0 - orbit.eccentricity());
+                let apoapsis = orbit.semimajor_axis() * (1.0 + orbit.eccentricity());
+                assert!(periapsis > 3.0 * parent.info.radius as f64);
+                if let Some(soi_radius) = orrery.get_soi_radius(parent.id) {
+                    assert!(
+                        apoapsis < 0.5 * soi_radius,
+                        "{} leaves its SOI",
+                        body.info.name
+                    );
+                }
+
+                // Siblings' orbits don't cross, and neither do their SOIs
+                for other in orrery.body_orbits() {
+                    let is_outer_sibling = other.primary().id == parent.id
+                        && other.semimajor_axis() > orbit.semimajor_axis();
+                    if !is_outer_sibling {
+                        continue;
+                    }
+                    let other_periapsis = other.semimajor_axis() * (1.0 - other.eccentricity());
+                    let gap = other_periapsis - apoapsis;
+                    let soi_radii = orrery.get_soi_radius(body.id).unwrap()
+                        + orrery.get_soi_radius(other.secondary().id).unwrap();
+                    assert!(gap > soi_radii, "{} is too close", body.info.name);
+                }
+            }
+        }
+    }
+}
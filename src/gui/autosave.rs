@@ -0,0 +1,631 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use nalgebra::Vector3;
+
+use super::bookmarks::Bookmark;
+use super::notes::Note;
+use crate::file::write_bodies;
+use crate::model::orrery::{
+    BodyEdit, BodyID, Maneuver, ManeuverPlan, OrbitElements, SailAttitude, SailSchedule, ShipID,
+    ShipPlacement,
+};
+use crate::model::timeline::Timeline;
+
+const HEADER: &str =
+    "# Changes made to the timeline, to be redone on top of the one it started with";
+// How many autosaves are kept, so that one being written when the program
+// dies doesn't take the only copy with it
+const NUM_SLOTS: usize = 3;
+
+/// A change the user made to the timeline.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionChange {
+    AddShip(ShipPlacement),
+    RemoveShip(ShipID),
+    EditBody {
+        body_id: BodyID,
+        edit: BodyEdit,
+        time: f64,
+    },
+    SetShipOrbit {
+        ship_id: ShipID,
+        elements: OrbitElements,
+        time: f64,
+    },
+    SetManeuverPlan(ShipID, ManeuverPlan),
+    SetSailSchedule(ShipID, SailSchedule),
+    RenameShip(ShipID, Option<String>),
+}
+
+impl SessionChange {
+    /// Makes the change to the timeline. Ships get their IDs in the order
+    /// they're added, so replaying changes in order on the same starting
+    /// timeline gives the same ships.
+    ///
+    /// Returns an error, without changing anything, if the change is to a
+    /// ship or body that isn't in the timeline, or can't be made to it.
+    pub fn apply(&self, timeline: &mut Timeline) -> Result<(), String> {
+        self.check(timeline)?;
+        match self {
+            SessionChange::AddShip(placement) => {
+                timeline.add_ship(placement);
+            }
+            SessionChange::RemoveShip(ship_id) => timeline.remove_ship(*ship_id)?,
+            SessionChange::EditBody {
+                body_id,
+                edit,
+                time,
+            } => timeline.edit_body(*body_id, *edit, *time)?,
+            SessionChange::SetShipOrbit {
+                ship_id,
+                elements,
+                time,
+            } => timeline.set_ship_orbit(*ship_id, *elements, *time),
+            SessionChange::SetManeuverPlan(ship_id, plan) => {
+                timeline.set_maneuver_plan(*ship_id, plan.clone())
+            }
+            SessionChange::SetSailSchedule(ship_id, schedule) => {
+                timeline.set_sail_schedule(*ship_id, schedule.clone())
+            }
+            SessionChange::RenameShip(ship_id, name) => {
+                timeline.set_ship_name(*ship_id, name.clone())
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the ships and bodies the change is to are there, and that
+    /// the change makes sense for them.
+    fn check(&self, timeline: &Timeline) -> Result<(), String> {
+        let orrery = timeline.get_orrery_at(timeline.start_time()).unwrap();
+        let has_body = |id: BodyID| {
+            if orrery.bodies().any(|b| b.id == id) {
+                Ok(())
+            } else {
+                Err(format!("No body {}", id.0))
+            }
+        };
+        let ship = |id: ShipID| {
+            orrery
+                .ships()
+                .find(|s| s.id == id)
+                .ok_or_else(|| format!("No ship {}", id.0))
+        };
+
+        // Orbits are built from these, which only works for ellipses
+        let elliptic = |elements: &OrbitElements| {
+            if elements.is_elliptic() && elements.semimajor_axis.is_finite() {
+                Ok(())
+            } else {
+                Err(format!(
+                    "Orbit with semimajor axis {} and eccentricity {} isn't elliptic",
+                    elements.semimajor_axis, elements.eccentricity
+                ))
+            }
+        };
+        let positive = |name: &str, value: Option<f64>| match value {
+            Some(x) if !(x.is_finite() && x > 0.0) => {
+                Err(format!("{} must be positive, not {}", name, x))
+            }
+            _ => Ok(()),
+        };
+
+        match self {
+            SessionChange::AddShip(placement) => {
+                has_body(placement.parent_id)?;
+                elliptic(&placement.elements())
+            }
+            SessionChange::EditBody { body_id, edit, .. } => {
+                has_body(*body_id)?;
+                positive("Mu", edit.mu)?;
+                positive("Radius", edit.radius.map(f64::from))?;
+                positive("SOI radius", edit.soi_radius)?;
+                if let Some(elements) = &edit.orbit {
+                    if orrery.get_parent(*body_id).is_none() {
+                        return Err(format!("Body {} can't be given an orbit", body_id.0));
+                    }
+                    elliptic(elements)?;
+                }
+                Ok(())
+            }
+            SessionChange::SetSailSchedule(ship_id, _) => match ship(*ship_id)?.sail {
+                Some(_) => Ok(()),
+                None => Err(format!("Ship {} doesn't have a sail", ship_id.0)),
+            },
+            // Removing a ship checks for itself
+            SessionChange::RemoveShip(_) => Ok(()),
+            SessionChange::SetShipOrbit {
+                ship_id, elements, ..
+            } => {
+                ship(*ship_id)?;
+                elliptic(elements)
+            }
+            SessionChange::SetManeuverPlan(ship_id, _) | SessionChange::RenameShip(ship_id, _) => {
+                ship(*ship_id).map(|_| ())
+            }
+        }
+    }
+
+    pub(super) fn to_line(&self) -> String {
+        match self {
+            SessionChange::AddShip(p) => format!(
+                "add {} {} {} {} {} {} {} {}",
+                p.parent_id.0,
+                p.semimajor_axis,
+                p.eccentricity,
+                p.inclination,
+                p.long_asc_node,
+                p.arg_periapsis,
+                p.mean_anomaly,
+                p.epoch
+            ),
+            SessionChange::RemoveShip(ship_id) => format!("remove {}", ship_id.0),
+            SessionChange::EditBody {
+                body_id,
+                edit,
+                time,
+            } => {
+                let mut line = format!(
+                    "body {} {} {} {} {}",
+                    body_id.0,
+                    time,
+                    optional_to_string(edit.mu),
+                    optional_to_string(edit.radius),
+                    optional_to_string(edit.soi_radius)
+                );
+                // Older autosaves don't have orbits, so only write one if there is one
+                if let Some(elements) = &edit.orbit {
+                    line.push(' ');
+                    line.push_str(&elements_to_string(elements));
+                }
+                line
+            }
+            SessionChange::SetShipOrbit {
+                ship_id,
+                elements,
+                time,
+            } => format!(
+                "orbit {} {} {}",
+                ship_id.0,
+                time,
+                elements_to_string(elements)
+            ),
+            SessionChange::SetManeuverPlan(ship_id, plan) => {
+                let mut line = format!("plan {}", ship_id.0);
+                for m in plan.maneuvers() {
+                    line.push_str(&format!(
+                        " {} {} {} {}",
+                        m.time, m.delta_v.x, m.delta_v.y, m.delta_v.z
+                    ));
+                }
+                line
+            }
+            SessionChange::SetSailSchedule(ship_id, schedule) => {
+                let mut line = format!("sail {}", ship_id.0);
+                for (time, attitude) in schedule.steps() {
+                    line.push_str(&format!(" {} {} {}", time, attitude.cone, attitude.clock));
+                }
+                line
+            }
+            // The name comes last, since it may contain spaces
+            SessionChange::RenameShip(ship_id, name) => match name {
+                Some(name) => format!("name {} {}", ship_id.0, name),
+                None => format!("name {}", ship_id.0),
+            },
+        }
+    }
+
+    pub(super) fn from_line(line: &str) -> Option<Self> {
+        if let Some(rest) = line.strip_prefix("name ") {
+            let (id, name) = rest.split_once(' ').unwrap_or((rest, ""));
+            let name = (!name.is_empty()).then(|| name.to_owned());
+            return Some(SessionChange::RenameShip(ShipID(id.parse().ok()?), name));
+        }
+
+        let mut fields = line.split_whitespace();
+        let kind = fields.next()?;
+        let id = fields.next()?.parse().ok()?;
+        let numbers: Vec<f64> = fields.map(|f| f.parse().ok()).collect::<Option<_>>()?;
+        let change = match (kind, &numbers[..]) {
+            (
+                "add",
+                &[semimajor_axis, eccentricity, inclination, long_asc_node, arg_periapsis, mean_anomaly, epoch],
+            ) => SessionChange::AddShip(ShipPlacement {
+                parent_id: BodyID(id),
+                semimajor_axis,
+                eccentricity,
+                inclination,
+                long_asc_node,
+                arg_periapsis,
+                mean_anomaly,
+                epoch,
+            }),
+            ("remove", &[]) => SessionChange::RemoveShip(ShipID(id)),
+            ("body", &[time, mu, radius, soi_radius, ref orbit @ ..])
+                if orbit.is_empty() || orbit.len() == 6 =>
+            {
+                SessionChange::EditBody {
+                    body_id: BodyID(id),
+                    edit: BodyEdit {
+                        mu: (!mu.is_nan()).then_some(mu),
+                        radius: (!radius.is_nan()).then_some(radius as f32),
+                        soi_radius: (!soi_radius.is_nan()).then_some(soi_radius),
+                        orbit: (!orbit.is_empty()).then(|| elements_from_slice(orbit)),
+                    },
+                    time,
+                }
+            }
+            ("orbit", &[time, ref elements @ ..]) if elements.len() == 6 => {
+                SessionChange::SetShipOrbit {
+                    ship_id: ShipID(id),
+                    elements: elements_from_slice(elements),
+                    time,
+                }
+            }
+            ("plan", numbers) if numbers.len() % 4 == 0 => {
+                let mut plan = ManeuverPlan::new();
+                for m in numbers.chunks(4) {
+                    plan.set_maneuver(Maneuver {
+                        time: m[0],
+                        delta_v: Vector3::new(m[1], m[2], m[3]),
+                    });
+                }
+                SessionChange::SetManeuverPlan(ShipID(id), plan)
+            }
+            ("sail", numbers) if !numbers.is_empty() && numbers.len() % 3 == 0 => {
+                let attitude = |s: &[f64]| SailAttitude {
+                    cone: s[1],
+                    clock: s[2],
+                };
+                let mut steps = numbers.chunks(3);
+                let mut schedule = SailSchedule::new(attitude(steps.next()?));
+                for step in steps {
+                    schedule.set_attitude(step[0], attitude(step));
+                }
+                SessionChange::SetSailSchedule(ShipID(id), schedule)
+            }
+            _ => return None,
+        };
+        Some(change)
+    }
+}
+
+fn elements_to_string(elements: &OrbitElements) -> String {
+    format!(
+        "{} {} {} {} {} {}",
+        elements.semimajor_axis,
+        elements.eccentricity,
+        elements.inclination,
+        elements.long_asc_node,
+        elements.arg_periapsis,
+        elements.mean_anomaly
+    )
+}
+
+fn elements_from_slice(numbers: &[f64]) -> OrbitElements {
+    OrbitElements {
+        semimajor_axis: numbers[0],
+        eccentricity: numbers[1],
+        inclination: numbers[2],
+        long_asc_node: numbers[3],
+        arg_periapsis: numbers[4],
+        mean_anomaly: numbers[5],
+    }
+}
+
+/// Writes unset values as NaN, which is never a valid value.
+fn optional_to_string<T: ToString>(value: Option<T>) -> String {
+    value.map_or(String::from("NaN"), |v| v.to_string())
+}
+
+/// What's needed to pick up where a session left off: the changes made to
+/// the timeline, in order, the notes made about it, and what the view was
+/// looking at.
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    pub changes: Vec<SessionChange>,
+    pub notes: Vec<Note>,
+    pub view: Option<Bookmark>,
+}
+
+impl Session {
+    /// Writes the session, for the timeline with the given
+    /// [fingerprint](Autosave::fingerprint).
+    fn to_text(&self, fingerprint: &str) -> String {
+        let mut contents = format!("{}\nstart {}\n", HEADER, fingerprint);
+        if let Some(view) = &self.view {
+            contents.push_str(&format!("view {}\n", view.to_line()));
+        }
+        for change in self.changes.iter() {
+            contents.push_str(&change.to_line());
+            contents.push('\n');
+        }
+        for note in self.notes.iter() {
+            contents.push_str(&format!("note {}\n", note.to_line()));
+        }
+        contents
+    }
+
+    /// Reads a session, along with the fingerprint of the timeline it was
+    /// made on, if it has one.
+    fn parse(contents: &str) -> Result<(Self, Option<String>), String> {
+        let mut session = Session::default();
+        let mut fingerprint = None;
+        for (idx, line) in contents.lines().enumerate() {
+            if line.starts_with('#') || line.trim().is_empty() {
+                continue;
+            }
+            let malformed = || format!("Line {} is malformed: {}", idx + 1, line);
+            if let Some(rest) = line.strip_prefix("start ") {
+                fingerprint = Some(rest.trim().to_owned());
+            } else if let Some(view) = line.strip_prefix("view ") {
+                session.view = Some(Bookmark::from_line(view).ok_or_else(malformed)?);
+            } else if let Some(note) = line.strip_prefix("note ") {
+                session
+                    .notes
+                    .push(Note::from_line(note).ok_or_else(malformed)?);
+            } else {
+                session
+                    .changes
+                    .push(SessionChange::from_line(line).ok_or_else(malformed)?);
+            }
+        }
+        Ok((session, fingerprint))
+    }
+}
+
+/// Saves the session every so often, to a few files in turn, so that it can
+/// be restored if the program dies. The files are removed when it exits
+/// normally, so any that are left over are from a session that didn't.
+///
+/// The changes in a session only make sense on the timeline they were made
+/// on, so each autosave starts with a fingerprint of it, and is only restored
+/// onto a timeline with the same one.
+#[derive(Debug)]
+pub struct Autosave {
+    dir: PathBuf,
+    interval: Duration,
+    fingerprint: String,
+    last_save: Instant,
+    next_slot: usize,
+}
+
+impl Autosave {
+    /// Starts autosaving to the given directory. The fingerprint is of the
+    /// timeline as it was set up, before any of the session's changes.
+    pub fn new(dir: PathBuf, interval: Duration, fingerprint: String) -> Self {
+        Self {
+            dir,
+            interval,
+            fingerprint,
+            last_save: Instant::now(),
+            next_slot: 0,
+        }
+    }
+
+    /// Sums up everything about the timeline that the changes in a session
+    /// depend on: the bodies, and the ships and everything about them. Take
+    /// it before any changes are made.
+    ///
+    /// This is FNV-1a, which, unlike the standard library's hasher, is the
+    /// same from one build to the next.
+    pub fn fingerprint(timeline: &Timeline) -> String {
+        let orrery = timeline.get_orrery_at(timeline.start_time()).unwrap();
+        let mut ships: Vec<_> = orrery.ships().collect();
+        ships.sort_by_key(|s| s.id);
+        let mut description = format!(
+            "{} {} {}\n{}",
+            timeline.start_time(),
+            orrery.soi_model(),
+            orrery.relativistic_precession(),
+            write_bodies(&orrery)
+        );
+        for ship in ships {
+            description.push_str(&format!("{:?}\n", ship));
+        }
+
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in description.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        format!("{:016x}", hash)
+    }
+
+    fn slot_path(dir: &Path, slot: usize) -> PathBuf {
+        dir.join(format!("autosave-{}.txt", slot))
+    }
+
+    /// Loads the most recent autosave left in the directory, along with when
+    /// it was saved, if there is one. It's an error if it wasn't made on a
+    /// timeline with the given fingerprint.
+    pub fn find_latest(dir: &Path, fingerprint: &str) -> io::Result<Option<(Session, SystemTime)>> {
+        let mut latest = None;
+        for slot in 0..NUM_SLOTS {
+            let path = Self::slot_path(dir, slot);
+            let modified = match fs::metadata(&path) {
+                Ok(metadata) => metadata.modified()?,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            if latest.as_ref().is_none_or(|(_, t)| modified > *t) {
+                latest = Some((path, modified));
+            }
+        }
+
+        let (path, modified) = match latest {
+            Some(latest) => latest,
+            None => return Ok(None),
+        };
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+        let (session, saved_fingerprint) =
+            Session::parse(&fs::read_to_string(&path)?).map_err(invalid)?;
+        if saved_fingerprint.as_deref() != Some(fingerprint) {
+            return Err(invalid(format!(
+                "{} was made with different options, so it can't be restored",
+                path.display()
+            )));
+        }
+        Ok(Some((session, modified)))
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.last_save.elapsed() >= self.interval
+    }
+
+    /// Writes the session to the next of the files, in turn.
+    pub fn save(&mut self, session: &Session) -> io::Result<()> {
+        self.last_save = Instant::now();
+        fs::create_dir_all(&self.dir)?;
+        let path = Self::slot_path(&self.dir, self.next_slot);
+        self.next_slot = (self.next_slot + 1) % NUM_SLOTS;
+        // Write it all at once, so a half-written file never replaces a good one
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, session.to_text(&self.fingerprint))?;
+        fs::rename(temp_path, path)
+    }
+}
+
+impl Drop for Autosave {
+    fn drop(&mut self) {
+        // Keep the autosaves if we're going down because of a panic
+        if thread::panicking() {
+            return;
+        }
+        for slot in 0..NUM_SLOTS {
+            let _ = fs::remove_file(Self::slot_path(&self.dir, slot));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::read_file;
+
+    const KERBIN: BodyID = BodyID(4);
+
+    fn kerbin_timeline() -> (Timeline, ShipID) {
+        let mut orrery = read_file("ksp-bodies.txt");
+        let id = orrery.add_ship(Vector3::x() * 1e6, Vector3::y() * 2200.0, 0.0, KERBIN);
+        (Timeline::new(orrery, 0.0), id)
+    }
+
+    #[test]
+    fn test_unknown_objects() {
+        let (mut timeline, id) = kerbin_timeline();
+        let missing = ShipID(id.0 + 1);
+        let changes = [
+            SessionChange::AddShip(ShipPlacement::circular(BodyID(100), 1e6, 0.0)),
+            SessionChange::EditBody {
+                body_id: BodyID(100),
+                edit: BodyEdit::default(),
+                time: 0.0,
+            },
+            SessionChange::RemoveShip(missing),
+            SessionChange::SetShipOrbit {
+                ship_id: missing,
+                elements: OrbitElements {
+                    semimajor_axis: 1e6,
+                    eccentricity: 0.0,
+                    inclination: 0.0,
+                    long_asc_node: 0.0,
+                    arg_periapsis: 0.0,
+                    mean_anomaly: 0.0,
+                },
+                time: 0.0,
+            },
+            SessionChange::SetManeuverPlan(missing, ManeuverPlan::new()),
+            SessionChange::RenameShip(missing, None),
+            // The ship's there, but it doesn't have a sail
+            SessionChange::SetSailSchedule(
+                id,
+                SailSchedule::new(SailAttitude {
+                    cone: 0.0,
+                    clock: 0.0,
+                }),
+            ),
+        ];
+        for change in changes.iter() {
+            assert!(change.apply(&mut timeline).is_err(), "{:?}", change);
+        }
+    }
+
+    #[test]
+    fn test_invalid_changes() {
+        let (mut timeline, id) = kerbin_timeline();
+        let hyperbolic = OrbitElements {
+            semimajor_axis: 800000.0,
+            eccentricity: 1.5,
+            inclination: 0.0,
+            long_asc_node: 0.0,
+            arg_periapsis: 0.0,
+            mean_anomaly: 0.0,
+        };
+        let mut placement = ShipPlacement::circular(KERBIN, 1e6, 0.0);
+        placement.eccentricity = 1.5;
+        let changes = [
+            SessionChange::from_line(&format!("orbit {} 10 800000 1.5 0 0 0 0", id.0)).unwrap(),
+            SessionChange::AddShip(placement),
+            SessionChange::EditBody {
+                body_id: KERBIN,
+                edit: BodyEdit {
+                    orbit: Some(hyperbolic),
+                    ..BodyEdit::default()
+                },
+                time: 0.0,
+            },
+            SessionChange::EditBody {
+                body_id: KERBIN,
+                edit: BodyEdit {
+                    mu: Some(-1.0),
+                    ..BodyEdit::default()
+                },
+                time: 0.0,
+            },
+            SessionChange::EditBody {
+                body_id: KERBIN,
+                edit: BodyEdit {
+                    radius: Some(f32::INFINITY),
+                    ..BodyEdit::default()
+                },
+                time: 0.0,
+            },
+        ];
+        for change in changes.iter() {
+            assert!(change.apply(&mut timeline).is_err(), "{:?}", change);
+        }
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let (timeline, id) = kerbin_timeline();
+        let fingerprint = Autosave::fingerprint(&timeline);
+        assert_eq!(Autosave::fingerprint(&timeline), fingerprint);
+
+        // Anything about the starting ships changes it
+        let (mut other, _) = kerbin_timeline();
+        other.set_ship_name(id, Some(String::from("Relay 1")));
+        assert_ne!(Autosave::fingerprint(&other), fingerprint);
+        other.set_ship_name(id, None);
+        assert_eq!(Autosave::fingerprint(&other), fingerprint);
+
+        // And only an autosave with the same one is restored
+        let dir = std::env::temp_dir().join(format!("autosave-{}", std::process::id()));
+        let session = Session {
+            changes: vec![SessionChange::RenameShip(id, None)],
+            ..Default::default()
+        };
+        let mut autosave = Autosave::new(dir.clone(), Duration::ZERO, fingerprint.clone());
+        autosave.save(&session).unwrap();
+        let (loaded, _) = Autosave::find_latest(&dir, &fingerprint).unwrap().unwrap();
+        assert_eq!(loaded.changes, session.changes);
+        assert!(Autosave::find_latest(&dir, "0123456789abcdef").is_err());
+        drop(autosave);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
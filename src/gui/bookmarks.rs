@@ -0,0 +1,171 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use super::view::{FocusPoint, SpeedMode};
+use crate::model::orrery::{BodyID, ShipID};
+
+const HEADER: &str = "# time focus target theta phi distance inertial speed name";
+
+/// A saved view: what the camera was looking at, from where, and when.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    pub name: String,
+    pub time: f64,
+    pub focus: FocusPoint,
+    pub target: Option<FocusPoint>,
+    /// Azimuthal and polar angles of the camera, in radians.
+    pub camera_angles: (f32, f32),
+    pub camera_distance: f32,
+    pub ship_camera_inertial: bool,
+    pub speed_mode: SpeedMode,
+}
+
+impl Bookmark {
+    pub(super) fn to_line(&self) -> String {
+        format!(
+            "{} {} {} {} {} {} {} {} {}",
+            self.time,
+            focus_to_string(Some(self.focus)),
+            focus_to_string(self.target),
+            self.camera_angles.0,
+            self.camera_angles.1,
+            self.camera_distance,
+            self.ship_camera_inertial,
+            self.speed_mode,
+            self.name,
+        )
+    }
+
+    pub(super) fn from_line(line: &str) -> Option<Self> {
+        // The name comes last, since it may contain spaces
+        let mut fields = line.splitn(9, ' ');
+        let mut next = || fields.next();
+        let time = next()?.parse().ok()?;
+        let focus = focus_from_str(next()?)??;
+        let target = focus_from_str(next()?)?;
+        let theta = next()?.parse().ok()?;
+        let phi = next()?.parse().ok()?;
+        let camera_distance = next()?.parse().ok()?;
+        let ship_camera_inertial = next()?.parse().ok()?;
+        let speed_mode = next()?.parse().ok()?;
+        let name = next()?.to_owned();
+        Some(Self {
+            name,
+            time,
+            focus,
+            target,
+            camera_angles: (theta, phi),
+            camera_distance,
+            ship_camera_inertial,
+            speed_mode,
+        })
+    }
+}
+
+fn focus_to_string(point: Option<FocusPoint>) -> String {
+    match point {
+        Some(FocusPoint::Body(id)) => format!("body:{}", id.0),
+        Some(FocusPoint::Ship(id)) => format!("ship:{}", id.0),
+        None => String::from("-"),
+    }
+}
+
+/// Parses the output of [focus_to_string]. The outer option is `None` if the
+/// string isn't valid.
+fn focus_from_str(s: &str) -> Option<Option<FocusPoint>> {
+    if s == "-" {
+        return Some(None);
+    }
+    let (kind, id) = s.split_once(':')?;
+    let id = id.parse().ok()?;
+    match kind {
+        "body" => Some(Some(FocusPoint::Body(BodyID(id)))),
+        "ship" => Some(Some(FocusPoint::Ship(ShipID(id)))),
+        _ => None,
+    }
+}
+
+/// The user's bookmarks, kept in sync with a file if one was given.
+#[derive(Debug, Default)]
+pub struct Bookmarks {
+    path: Option<PathBuf>,
+    bookmarks: Vec<Bookmark>,
+}
+
+impl Bookmarks {
+    /// Bookmarks that aren't saved anywhere.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The given bookmarks, which aren't saved anywhere either.
+    pub fn unsaved(bookmarks: Vec<Bookmark>) -> Self {
+        Self {
+            path: None,
+            bookmarks,
+        }
+    }
+
+    /// Loads bookmarks from the given file, which is created when the first
+    /// bookmark is saved if it doesn't exist yet. Lines that can't be parsed
+    /// are skipped.
+    pub fn load(path: PathBuf) -> io::Result<Self> {
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+
+        let mut bookmarks = vec![];
+        for line in contents.lines() {
+            if line.starts_with('#') || line.trim().is_empty() {
+                continue;
+            }
+            match Bookmark::from_line(line) {
+                Some(bookmark) => bookmarks.push(bookmark),
+                None => println!("Skipping malformed bookmark: {}", line),
+            }
+        }
+
+        Ok(Self {
+            path: Some(path),
+            bookmarks,
+        })
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&Bookmark> {
+        self.bookmarks.get(idx)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Bookmark> {
+        self.bookmarks.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bookmarks.is_empty()
+    }
+
+    /// Adds the bookmark, replacing any with the same name, and saves the
+    /// bookmarks to their file.
+    pub fn add(&mut self, bookmark: Bookmark) -> io::Result<()> {
+        match self.bookmarks.iter_mut().find(|b| b.name == bookmark.name) {
+            Some(existing) => *existing = bookmark,
+            None => self.bookmarks.push(bookmark),
+        }
+        self.save()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let mut contents = format!("{}\n", HEADER);
+        for bookmark in self.bookmarks.iter() {
+            contents.push_str(&bookmark.to_line());
+            contents.push('\n');
+        }
+        fs::write(path, contents)
+    }
+}
@@ -0,0 +1,460 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use miniz_oxide::deflate::compress_to_vec;
+use miniz_oxide::inflate::decompress_to_vec;
+use nalgebra::{Point3, Vector3};
+
+use super::autosave::SessionChange;
+use super::bookmarks::Bookmark;
+use super::notes::Note;
+use crate::astro::integrate::IntegratorSettings;
+use crate::file::{try_parse_bodies, write_bodies};
+use crate::model::orrery::{BodyID, Propagation, Ship, ShipID, SoiModel};
+use crate::model::timeline::Timeline;
+
+// Starts every bundle, so that other files aren't mistaken for one
+const MAGIC: &[u8] = b"KSPPLAN1";
+const COMPRESSION_LEVEL: u8 = 9;
+const HEADER: &str = "# A mission plan, to be opened read-only";
+
+/// A ship as it is at the start of the plan.
+#[derive(Debug, Clone, PartialEq)]
+struct BundledShip {
+    id: ShipID,
+    parent_id: BodyID,
+    /// Relative to the parent.
+    position: Vector3<f64>,
+    velocity: Vector3<f64>,
+    color: Option<Point3<f32>>,
+}
+
+/// A mission plan packed into one compressed file, for someone else to open
+/// and look over, but not change.
+///
+/// Unlike an autosave, which only has the changes made on top of a timeline
+/// set up from the command line, this has everything needed to build the
+/// timeline again: the bodies, the ships as they were at the start, and the
+/// changes made to them since, along with the bookmarks and notes. Only the
+/// ships' paths, burns, names and colors are kept, though, so plans with
+/// ships that have anything else, like stages or sails, can't be bundled.
+#[derive(Debug, Clone)]
+pub struct PlanBundle {
+    start_time: f64,
+    soi_model: SoiModel,
+    relativistic_precession: bool,
+    // A bodies file, as written by write_bodies
+    bodies: String,
+    ships: Vec<BundledShip>,
+    changes: Vec<SessionChange>,
+    pub bookmarks: Vec<Bookmark>,
+    pub notes: Vec<Note>,
+}
+
+impl PlanBundle {
+    /// Packs up the plan in the timeline, given the changes that were made
+    /// to it since it started. Fails if any ship has settings that a bundle
+    /// can't keep, since it'd be a different plan without them.
+    pub fn new(
+        timeline: &Timeline,
+        session_changes: &[SessionChange],
+        bookmarks: Vec<Bookmark>,
+        notes: Vec<Note>,
+    ) -> Result<Self, String> {
+        let start_time = timeline.start_time();
+        let orrery = timeline.get_orrery_at(start_time).unwrap();
+        for ship in orrery.ships() {
+            let settings = unbundled_settings(ship);
+            if !settings.is_empty() {
+                return Err(format!(
+                    "Ship {} can't be bundled, because bundles don't keep its {}",
+                    ship.id.0,
+                    settings.join(", ")
+                ));
+            }
+        }
+        let mut ships: Vec<_> = orrery
+            .ships()
+            .map(|ship| {
+                let state = ship.state_at_time(start_time);
+                BundledShip {
+                    id: ship.id,
+                    parent_id: ship.parent_id(),
+                    position: state.position(),
+                    velocity: state.velocity(),
+                    color: ship.color,
+                }
+            })
+            .collect();
+        ships.sort_by_key(|s| s.id);
+
+        // Edits to paths partway through have to be made again, in order, but
+        // the ships already have everything else, as it is now
+        let has_ship = |id: &ShipID| ships.iter().any(|s| s.id == *id);
+        let mut changes: Vec<_> = session_changes
+            .iter()
+            .filter(|change| match change {
+                SessionChange::EditBody { .. } => true,
+                SessionChange::SetShipOrbit { ship_id, .. } => has_ship(ship_id),
+                _ => false,
+            })
+            .cloned()
+            .collect();
+        for ship in ships.iter() {
+            let ship = orrery.get_ship(ship.id);
+            if !ship.maneuvers.maneuvers().is_empty() {
+                changes.push(SessionChange::SetManeuverPlan(
+                    ship.id,
+                    ship.maneuvers.clone(),
+                ));
+            }
+            if ship.name.is_some() {
+                changes.push(SessionChange::RenameShip(ship.id, ship.name.clone()));
+            }
+        }
+
+        Ok(Self {
+            start_time,
+            soi_model: orrery.soi_model(),
+            relativistic_precession: orrery.relativistic_precession(),
+            bodies: write_bodies(&orrery),
+            ships,
+            changes,
+            bookmarks,
+            notes,
+        })
+    }
+
+    /// Builds the plan's timeline again.
+    pub fn timeline(&self) -> Result<Timeline, String> {
+        let mut orrery = try_parse_bodies(&self.bodies)?;
+        orrery.set_soi_model(self.soi_model);
+        orrery.set_relativistic_precession(self.relativistic_precession);
+        for ship in self.ships.iter() {
+            if !orrery.bodies().any(|b| b.id == ship.parent_id) {
+                return Err(format!("Ship {} orbits a body that isn't there", ship.id.0));
+            }
+            // Keep the ship's ID, which the changes, bookmarks and notes refer to
+            let id = orrery.add_ship(
+                ship.position,
+                ship.velocity,
+                self.start_time,
+                ship.parent_id,
+            );
+            let mut added = orrery.remove_ship(id).unwrap();
+            added.id = ship.id;
+            added.color = ship.color;
+            orrery.insert_ship(added);
+        }
+
+        let mut timeline = Timeline::new(orrery, self.start_time);
+        for change in self.changes.iter() {
+            change.apply(&mut timeline)?;
+        }
+        Ok(timeline)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(compress_to_vec(
+            self.to_text().as_bytes(),
+            COMPRESSION_LEVEL,
+        ));
+        fs::write(path, bytes)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+        let bytes = fs::read(path)?;
+        let compressed = bytes
+            .strip_prefix(MAGIC)
+            .ok_or_else(|| invalid(String::from("Not a plan bundle")))?;
+        let text = decompress_to_vec(compressed)
+            .map_err(|e| invalid(format!("Couldn't decompress: {:?}", e)))?;
+        let text = String::from_utf8(text).map_err(|e| invalid(e.to_string()))?;
+        let bundle = Self::parse(&text).map_err(invalid)?;
+        // Build it once, so that anything wrong with the bodies or changes
+        // turns up here, rather than when it's opened
+        bundle.timeline().map_err(invalid)?;
+        Ok(bundle)
+    }
+
+    fn to_text(&self) -> String {
+        let mut lines = vec![
+            HEADER.to_owned(),
+            format!(
+                "start {} {} {}",
+                self.start_time, self.soi_model, self.relativistic_precession
+            ),
+        ];
+        // The bodies file's lines are marked, so they can be told apart
+        lines.extend(self.bodies.lines().map(|line| format!("| {}", line)));
+        for ship in self.ships.iter() {
+            let color = match ship.color {
+                Some(c) => format!("{},{},{}", c.x, c.y, c.z),
+                None => String::from("-"),
+            };
+            lines.push(format!(
+                "ship {} {} {} {} {} {} {} {} {}",
+                ship.id.0,
+                ship.parent_id.0,
+                ship.position.x,
+                ship.position.y,
+                ship.position.z,
+                ship.velocity.x,
+                ship.velocity.y,
+                ship.velocity.z,
+                color
+            ));
+        }
+        lines.extend(self.changes.iter().map(SessionChange::to_line));
+        lines.extend(
+            self.bookmarks
+                .iter()
+                .map(|b| format!("bookmark {}", b.to_line())),
+        );
+        lines.extend(self.notes.iter().map(|n| format!("note {}", n.to_line())));
+        lines.join("\n")
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut start = None;
+        let mut bodies = String::new();
+        let mut ships = vec![];
+        let mut changes = vec![];
+        let mut bookmarks = vec![];
+        let mut notes = vec![];
+        for (idx, line) in text.lines().enumerate() {
+            if line.starts_with('#') || line.trim().is_empty() {
+                continue;
+            }
+            let malformed = || format!("Line {} is malformed: {}", idx + 1, line);
+            if let Some(body) = line.strip_prefix("| ") {
+                bodies.push_str(body);
+                bodies.push('\n');
+            } else if let Some(rest) = line.strip_prefix("start ") {
+                start = Some(parse_start(rest).ok_or_else(malformed)?);
+            } else if let Some(rest) = line.strip_prefix("ship ") {
+                ships.push(parse_ship(rest).ok_or_else(malformed)?);
+            } else if let Some(rest) = line.strip_prefix("bookmark ") {
+                bookmarks.push(Bookmark::from_line(rest).ok_or_else(malformed)?);
+            } else if let Some(rest) = line.strip_prefix("note ") {
+                notes.push(Note::from_line(rest).ok_or_else(malformed)?);
+            } else {
+                changes.push(SessionChange::from_line(line).ok_or_else(malformed)?);
+            }
+        }
+
+        let (start_time, soi_model, relativistic_precession) =
+            start.ok_or_else(|| String::from("Missing the start line"))?;
+        if bodies.is_empty() {
+            return Err(String::from("Missing the bodies"));
+        }
+        Ok(Self {
+            start_time,
+            soi_model,
+            relativistic_precession,
+            bodies,
+            ships,
+            changes,
+            bookmarks,
+            notes,
+        })
+    }
+}
+
+/// Returns the ship's settings that aren't kept in a bundle, but aren't
+/// their defaults either.
+fn unbundled_settings(ship: &Ship) -> Vec<&'static str> {
+    let mut settings = vec![];
+    if ship.stages.is_some() {
+        settings.push("stages");
+    }
+    if ship.finite_burns {
+        settings.push("finite burns");
+    }
+    if ship.propagation != Propagation::default() {
+        settings.push("propagation");
+    }
+    if ship.integrator != IntegratorSettings::default() {
+        settings.push("integrator");
+    }
+    if ship.sail.is_some() {
+        settings.push("sail");
+    }
+    if ship.srp.is_some() {
+        settings.push("solar radiation pressure");
+    }
+    if ship.track_eclipses {
+        settings.push("eclipse tracking");
+    }
+    if ship.line_of_sight_target.is_some() {
+        settings.push("line of sight target");
+    }
+    if ship.arrival.is_some() {
+        settings.push("arrival");
+    }
+    if ship.delta_v_capacity.is_some() {
+        settings.push("delta-v capacity");
+    }
+    settings
+}
+
+fn parse_start(s: &str) -> Option<(f64, SoiModel, bool)> {
+    let mut fields = s.split_whitespace();
+    let start_time = fields.next()?.parse().ok()?;
+    let soi_model = fields.next()?.parse().ok()?;
+    let relativistic_precession = fields.next()?.parse().ok()?;
+    Some((start_time, soi_model, relativistic_precession))
+}
+
+fn parse_ship(s: &str) -> Option<BundledShip> {
+    let fields: Vec<_> = s.split_whitespace().collect();
+    let [id, parent_id, numbers @ .., color] = &fields[..] else {
+        return None;
+    };
+    let numbers: Vec<f64> = numbers
+        .iter()
+        .map(|n| n.parse().ok())
+        .collect::<Option<_>>()?;
+    let &[x, y, z, vx, vy, vz] = &numbers[..] else {
+        return None;
+    };
+    let (position, velocity) = (Vector3::new(x, y, z), Vector3::new(vx, vy, vz));
+    if position == Vector3::zeros()
+        || !position
+            .iter()
+            .chain(velocity.iter())
+            .all(|x| x.is_finite())
+    {
+        return None;
+    }
+    let color = match *color {
+        "-" => None,
+        color => {
+            let channels: Vec<f32> = color
+                .split(',')
+                .map(|c| c.parse().ok())
+                .collect::<Option<_>>()?;
+            let &[r, g, b] = &channels[..] else {
+                return None;
+            };
+            Some(Point3::new(r, g, b))
+        }
+    };
+    Some(BundledShip {
+        id: ShipID(id.parse().ok()?),
+        parent_id: BodyID(parent_id.parse().ok()?),
+        position,
+        velocity,
+        color,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::file::read_file;
+    use crate::model::orrery::{Frame, Maneuver, ManeuverPlan};
+
+    #[test]
+    fn test_round_trip() {
+        let mut orrery = read_file("ksp-bodies.txt");
+        let kerbin = BodyID(4);
+        let first = orrery.add_ship(Vector3::x() * 1e6, Vector3::y() * 2200.0, 0.0, kerbin);
+        let second = orrery.add_ship(Vector3::x() * 2e6, Vector3::y() * 1500.0, 0.0, kerbin);
+        let third = orrery.add_ship(Vector3::x() * 3e6, Vector3::y() * 1300.0, 0.0, kerbin);
+        orrery.set_ship_color(third, Some(Point3::new(1.0, 0.5, 0.0)));
+        let mut timeline = Timeline::new(orrery, 0.0);
+
+        // Removed ships leave gaps in the IDs, which have to be kept
+        timeline.remove_ship(second).unwrap();
+        let mut plan = ManeuverPlan::new();
+        plan.set_maneuver(Maneuver {
+            time: 1000.0,
+            delta_v: Vector3::new(100.0, 0.0, 0.0),
+        });
+        let changes = [
+            SessionChange::SetManeuverPlan(first, plan),
+            SessionChange::RenameShip(third, Some(String::from("Relay 1"))),
+        ];
+        for change in changes.iter() {
+            change.apply(&mut timeline).unwrap();
+        }
+
+        let bundle = PlanBundle::new(&timeline, &changes, vec![], vec![]).unwrap();
+        let dir = std::env::temp_dir().join(format!("plan-bundle-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("plan.kspplan");
+        bundle.save(&path).unwrap();
+        let loaded = PlanBundle::load(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let mut original = timeline;
+        let mut copy = loaded.timeline().unwrap();
+        let time = 5000.0;
+        original.extend_until(time);
+        copy.extend_until(time);
+        let (original, copy) = (
+            original.get_orrery_at(time).unwrap(),
+            copy.get_orrery_at(time).unwrap(),
+        );
+        let mut ids: Vec<_> = copy.ships().map(|s| s.id).collect();
+        ids.sort();
+        assert_eq!(ids, vec![first, third]);
+        for id in ids {
+            let position = |orrery: &crate::model::orrery::Orrery| {
+                orrery
+                    .get_ship_state(id, time)
+                    .get_position(Frame::Root, time)
+            };
+            assert_relative_eq!(position(&copy), position(&original), max_relative = 1e-6);
+            assert_eq!(copy.get_ship(id).name, original.get_ship(id).name);
+            assert_eq!(copy.get_ship(id).color, original.get_ship(id).color);
+        }
+    }
+
+    #[test]
+    fn test_refuses_unbundled_settings() {
+        let mut orrery = read_file("ksp-bodies.txt");
+        let id = orrery.add_ship(Vector3::x() * 1e6, Vector3::y() * 2200.0, 0.0, BodyID(4));
+        orrery.set_ship_eclipse_tracking(id, true);
+        let timeline = Timeline::new(orrery, 0.0);
+
+        let error = PlanBundle::new(&timeline, &[], vec![], vec![]).unwrap_err();
+        assert!(error.contains("eclipse tracking"), "{}", error);
+    }
+
+    #[test]
+    fn test_rejects_invalid_plans() {
+        let path = std::env::temp_dir().join(format!("bad-bundle-{}", std::process::id()));
+        let start = "start 0 laplace false";
+        let bodies = "| NAME MU RADIUS COLOR\n| Kerbol 1e12 1e5 ffffff";
+        for text in [
+            format!("{}\n| NAME MU\n| Kerbol notanumber", start),
+            format!("{}\n{}\nship 0 0 0 0 0 0 1000 0 -", start, bodies),
+            format!(
+                "{}\n{}\nship 0 0 1e6 0 0 0 1000 0 -\norbit 0 10 800000 1.5 0 0 0 0",
+                start, bodies
+            ),
+        ] {
+            let mut bytes = MAGIC.to_vec();
+            bytes.extend(compress_to_vec(text.as_bytes(), COMPRESSION_LEVEL));
+            fs::write(&path, bytes).unwrap();
+            let error = PlanBundle::load(&path).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{}", text);
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_other_files() {
+        let path = std::env::temp_dir().join(format!("not-a-bundle-{}", std::process::id()));
+        fs::write(&path, "hello").unwrap();
+        assert!(PlanBundle::load(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
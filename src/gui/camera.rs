@@ -94,6 +94,20 @@ impl ZoomableCamera {
         self.radius
     }
 
+    pub fn set_distance(&mut self, radius: f32) {
+        self.radius = nalgebra::clamp(radius, self.radius_limits.0, self.radius_limits.1);
+    }
+
+    /// Returns the azimuthal and polar angles of the camera, in radians.
+    pub fn angles(&self) -> (f32, f32) {
+        (self.theta, self.phi)
+    }
+
+    pub fn set_angles(&mut self, theta: f32, phi: f32) {
+        self.theta = theta % (2.0 * PI);
+        self.phi = nalgebra::clamp(phi, self.phi_limit, PI - self.phi_limit);
+    }
+
     pub fn width(&self) -> u32 {
         self.width
     }
@@ -111,6 +125,25 @@ impl ZoomableCamera {
         self.phi = nalgebra::clamp(self.phi + dphi, self.phi_limit, PI - self.phi_limit);
     }
 
+    /// Returns whether any part of the given sphere is inside the view
+    /// frustum. Spheres that aren't would be entirely clipped, so there's no
+    /// point drawing them.
+    pub fn sphere_in_view(&self, center: &Point3<f32>, radius: f32) -> bool {
+        let projection = self.projection();
+        let p = self.view_transform() * center;
+        // The camera looks down -z
+        let depth = -p.z;
+        if depth + radius < projection.znear() || depth - radius > projection.zfar() {
+            return false;
+        }
+
+        // Each side plane goes through the eye, at these slopes
+        let tan_y = (self.fovy / 2.0).tan();
+        let tan_x = tan_y * self.width as f32 / self.height as f32;
+        let outside = |offset: f32, tan: f32| (offset.abs() - depth * tan) / tan.hypot(1.0);
+        outside(p.x, tan_x) <= radius && outside(p.y, tan_y) <= radius
+    }
+
     pub fn zoom(&mut self, factor: f32) {
         self.radius = nalgebra::clamp(
             self.radius * factor,
@@ -1,8 +1,10 @@
 use std::time::Instant;
 
-use kiss3d::event::{Action, Event, Key, WindowEvent};
+use kiss3d::event::{Action, Event, Key, MouseButton, WindowEvent};
+use nalgebra::Point2;
 
-use super::view::View;
+use super::gamepad::Gamepad;
+use super::view::{FocusPoint, OrbitMenuItem, View};
 
 // Key config, all in one place
 const KEY_PREV_FOCUS: Key = Key::Q;
@@ -11,11 +13,137 @@ const KEY_SPEED_UP: Key = Key::Period;
 const KEY_SLOW_DOWN: Key = Key::Comma;
 const KEY_REWIND: Key = Key::R;
 const KEY_TOGGLE_PAUSE: Key = Key::Space;
+const KEY_TOGGLE_AUTO_PAUSE: Key = Key::P;
+const KEY_TOGGLE_AUTO_TIMESTEP: Key = Key::F;
+const KEY_NEXT_MOMENT: Key = Key::M;
+const KEY_PREV_MOMENT: Key = Key::N;
 const KEY_CAMERA_SWAP: Key = Key::C;
+const KEY_NEXT_TARGET: Key = Key::T;
+const KEY_CYCLE_SPEED_MODE: Key = Key::V;
+const KEY_TOGGLE_MAP: Key = Key::Tab;
+const KEY_CYCLE_ORBIT_COLORING: Key = Key::G;
+const KEY_TOGGLE_ALL_SOIS: Key = Key::O;
+const KEY_TOGGLE_SOI_WIREFRAME: Key = Key::L;
+const KEY_TOGGLE_FOG: Key = Key::Backslash;
+const KEY_TOGGLE_ORBIT_REFERENCE: Key = Key::J;
+const KEY_TOGGLE_TRAILS: Key = Key::H;
+const KEY_TOGGLE_GHOST: Key = Key::Y;
+const KEY_SAIL_CONE_DOWN: Key = Key::LBracket;
+const KEY_SAIL_CONE_UP: Key = Key::RBracket;
+const KEY_SAIL_CLOCK_DOWN: Key = Key::Semicolon;
+const KEY_SAIL_CLOCK_UP: Key = Key::Apostrophe;
+const KEY_SAVE_BOOKMARK: Key = Key::F5;
+const KEY_EXPORT_BUNDLE: Key = Key::F6;
+const KEY_SCREENSHOT: Key = Key::F12;
+const KEY_PLACE_SHIP: Key = Key::I;
+const KEY_REMOVE_SHIP: Key = Key::Delete;
+const KEY_RENAME_SHIP: Key = Key::F2;
+const KEY_EDIT_NOTE: Key = Key::X;
+const KEY_INSPECT_BODY: Key = Key::B;
+const KEY_EDIT_NODE: Key = Key::K;
+const KEY_EDIT_ORBIT: Key = Key::Z;
+const KEY_PLACEMENT_ANOMALY_DOWN: Key = Key::Left;
+const KEY_PLACEMENT_ANOMALY_UP: Key = Key::Right;
+const KEY_PLACEMENT_SMA_DOWN: Key = Key::Down;
+const KEY_PLACEMENT_SMA_UP: Key = Key::Up;
+const KEY_PLACEMENT_INCL_DOWN: Key = Key::PageDown;
+const KEY_PLACEMENT_INCL_UP: Key = Key::PageUp;
+const KEY_PLACEMENT_UNCERTAINTY: Key = Key::U;
+// Each restores the bookmark listed with that number
+const KEYS_RESTORE_BOOKMARK: [Key; 9] = [
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::Key5,
+    Key::Key6,
+    Key::Key7,
+    Key::Key8,
+    Key::Key9,
+];
+const BUTTON_SCRUB: MouseButton = MouseButton::Button1;
+const BUTTON_CLICK: MouseButton = MouseButton::Button1;
+const BUTTON_ORBIT_MENU: MouseButton = MouseButton::Button2;
+
+// Gamepad config, for the usual layout of an Xbox-style controller on Linux.
+// The left stick orbits the camera, and the right stick zooms.
+const GAMEPAD_BUTTON_COMMANDS: [(u8, Command); 6] = [
+    (0, Command::TogglePause), // A
+    (1, Command::Rewind),      // B
+    (2, Command::PrevFocus),   // X
+    (3, Command::NextFocus),   // Y
+    (4, Command::SlowDown),    // Left bumper
+    (5, Command::SpeedUp),     // Right bumper
+];
+const GAMEPAD_AXIS_ORBIT_X: u8 = 0;
+const GAMEPAD_AXIS_ORBIT_Y: u8 = 1;
+const GAMEPAD_AXIS_ZOOM: u8 = 4;
+// How fast the camera orbits with the stick all the way over, in radians per
+// second, and how many times further away it gets each second
+const GAMEPAD_ORBIT_RATE: f32 = 2.0;
+const GAMEPAD_ZOOM_RATE: f32 = 4.0;
+
+/// Things that can be done from either the keyboard or a gamepad.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    NextFocus,
+    PrevFocus,
+    SpeedUp,
+    SlowDown,
+    Rewind,
+    TogglePause,
+}
+
+const KEY_COMMANDS: [(Key, Command); 6] = [
+    (KEY_NEXT_FOCUS, Command::NextFocus),
+    (KEY_PREV_FOCUS, Command::PrevFocus),
+    (KEY_SPEED_UP, Command::SpeedUp),
+    (KEY_SLOW_DOWN, Command::SlowDown),
+    (KEY_REWIND, Command::Rewind),
+    (KEY_TOGGLE_PAUSE, Command::TogglePause),
+];
+
+// How far each keypress turns the sail, in degrees
+const SAIL_CONE_STEP: f64 = 5.0;
+const SAIL_CLOCK_STEP: f64 = 15.0;
+// How much each keypress changes the orbit of a ship being placed
+const PLACEMENT_ANOMALY_STEP: f64 = 5.0;
+const PLACEMENT_SMA_FACTOR: f64 = 1.1;
+const PLACEMENT_INCL_STEP: f64 = 5.0;
+// And how much it changes their uncertainty, in degrees and meters
+const UNCERTAINTY_ANOMALY_STEP: f64 = 1.0;
+const UNCERTAINTY_SMA_STEP: f64 = 1000.0;
+const UNCERTAINTY_INCL_STEP: f64 = 0.5;
+// How much each keypress scales a body's property in the inspector
+const INSPECTOR_FACTOR: f64 = 1.1;
+// When the timestep is picked automatically, how much of the focused orbit
+// goes by each frame
+const AUTO_TIMESTEP_FRACTION: f64 = 1.0 / 1000.0;
 
 pub struct Controller {
     timestep: f64,
+    // Whether to pick the timestep from the focused orbit whenever the focus
+    // changes, and what it was last picked for
+    auto_timestep: bool,
+    timestep_picked_for: Option<FocusPoint>,
     paused: bool,
+    // Whether to pause when a ship changes SOI
+    pause_on_soi_changes: bool,
+    // Last known cursor position, in window pixels
+    cursor: Point2<f32>,
+    // Whether the time scrubber is being dragged
+    scrubbing: bool,
+    // The name of the bookmark being typed in, if any
+    bookmark_name: Option<String>,
+    // The new name of the focused ship, while it's being typed in
+    ship_name: Option<String>,
+    // Whether to save a screenshot before the next frame is drawn
+    screenshot_requested: bool,
+    // Whether to save a bundle of the plan before the next frame
+    bundle_requested: bool,
+    // Gamepad to read, if there is one, and when it was last read
+    gamepad: Option<Gamepad>,
+    last_gamepad_poll: Instant,
     // TODO: i think this belongs in the view or similar
     fps_counter: FpsCounter,
 }
@@ -61,49 +189,341 @@ impl Controller {
     pub fn new() -> Self {
         Controller {
             timestep: 21600.0 / 60.0, // one Kerbin-day
+            auto_timestep: true,
+            timestep_picked_for: None,
             paused: true,
+            pause_on_soi_changes: false,
+            cursor: Point2::origin(),
+            scrubbing: false,
+            bookmark_name: None,
+            ship_name: None,
+            screenshot_requested: false,
+            bundle_requested: false,
+            gamepad: None,
+            last_gamepad_poll: Instant::now(),
             fps_counter: FpsCounter::new(1000),
         }
     }
 
-    pub fn process_event(&mut self, event: Event, view: &mut View) {
+    pub fn process_event(&mut self, mut event: Event, view: &mut View) {
+        if self.is_modal_open(view) {
+            if let Some(input) = ModalInput::from_event(event.value) {
+                self.process_modal_input(input, view);
+            }
+            // Don't let the keys we're typing do anything else
+            event.inhibited = true;
+            return;
+        }
+
         match event.value {
-            WindowEvent::Key(KEY_NEXT_FOCUS, Action::Press, _) => {
-                view.camera_focus_next();
+            // Dragging the scrubber pauses time, and shouldn't move the camera
+            WindowEvent::CursorPos(x, y, _) => {
+                self.cursor = Point2::new(x as f32, y as f32);
+                if self.scrubbing {
+                    view.scrub_to(self.cursor);
+                    event.inhibited = true;
+                }
+            }
+            WindowEvent::MouseButton(BUTTON_SCRUB, Action::Press, _)
+                if view.is_over_scrubber(self.cursor) =>
+            {
+                self.scrubbing = true;
+                self.paused = true;
+                view.scrub_to(self.cursor);
+                event.inhibited = true;
+            }
+            WindowEvent::MouseButton(BUTTON_SCRUB, Action::Release, _) if self.scrubbing => {
+                self.scrubbing = false;
+                event.inhibited = true;
+            }
+            // While the orbit menu's open, clicks go to it
+            WindowEvent::MouseButton(BUTTON_CLICK, Action::Press, _)
+                if view.is_orbit_menu_open() =>
+            {
+                if view.click_orbit_menu(self.cursor) == Some(OrbitMenuItem::Warp) {
+                    self.paused = true;
+                }
+                event.inhibited = true;
+            }
+            WindowEvent::MouseButton(BUTTON_ORBIT_MENU, Action::Press, _)
+                if view.open_orbit_menu(self.cursor) =>
+            {
+                event.inhibited = true;
+            }
+            WindowEvent::MouseButton(BUTTON_CLICK, Action::Press, _)
+                if view.click_panel(self.cursor) || view.click_event_marker(self.cursor) =>
+            {
+                event.inhibited = true;
+            }
+            WindowEvent::Key(key, Action::Press, _) if KEY_COMMANDS.iter().any(|c| c.0 == key) => {
+                let (_, command) = KEY_COMMANDS.iter().find(|c| c.0 == key).unwrap();
+                self.run_command(*command, view);
+            }
+            WindowEvent::Key(KEY_TOGGLE_AUTO_PAUSE, Action::Press, _) => {
+                self.pause_on_soi_changes = !self.pause_on_soi_changes;
+            }
+            WindowEvent::Key(KEY_TOGGLE_AUTO_TIMESTEP, Action::Press, _) => {
+                self.auto_timestep = !self.auto_timestep;
+                // Pick one for what's focused now, when it's turned back on
+                self.timestep_picked_for = None;
+            }
+            WindowEvent::Key(KEY_NEXT_MOMENT, Action::Press, _) => {
+                self.paused = true;
+                view.jump_to_moment(true);
+            }
+            WindowEvent::Key(KEY_PREV_MOMENT, Action::Press, _) => {
+                self.paused = true;
+                view.jump_to_moment(false);
+            }
+            WindowEvent::Key(KEY_CAMERA_SWAP, Action::Press, _) => {
+                view.camera_inertial_toggle();
+            }
+            WindowEvent::Key(KEY_TOGGLE_MAP, Action::Press, _) => {
+                view.toggle_map_mode();
+            }
+            WindowEvent::Key(KEY_CYCLE_ORBIT_COLORING, Action::Press, _) => {
+                view.cycle_orbit_coloring();
+            }
+            WindowEvent::Key(KEY_TOGGLE_ALL_SOIS, Action::Press, _) => {
+                view.toggle_all_sois();
+            }
+            WindowEvent::Key(KEY_TOGGLE_SOI_WIREFRAME, Action::Press, _) => {
+                view.toggle_soi_wireframe();
+            }
+            WindowEvent::Key(KEY_TOGGLE_FOG, Action::Press, _) => view.toggle_fog(),
+            WindowEvent::Key(KEY_TOGGLE_ORBIT_REFERENCE, Action::Press, _) => {
+                view.toggle_orbit_reference();
+            }
+            WindowEvent::Key(KEY_TOGGLE_TRAILS, Action::Press, _) => {
+                view.toggle_trails();
+            }
+            WindowEvent::Key(KEY_TOGGLE_GHOST, Action::Press, _) => {
+                view.toggle_ghost_trajectory();
+            }
+            WindowEvent::Key(KEY_NEXT_TARGET, Action::Press, _) => {
+                view.target_next();
             }
-            WindowEvent::Key(KEY_PREV_FOCUS, Action::Press, _) => {
-                view.camera_focus_prev();
+            WindowEvent::Key(KEY_CYCLE_SPEED_MODE, Action::Press, _) => {
+                view.cycle_speed_mode();
             }
-            WindowEvent::Key(KEY_SPEED_UP, Action::Press, _) => {
+            WindowEvent::Key(KEY_SAIL_CONE_DOWN, Action::Press, _) => {
+                view.turn_sail(-SAIL_CONE_STEP.to_radians(), 0.0);
+            }
+            WindowEvent::Key(KEY_SAIL_CONE_UP, Action::Press, _) => {
+                view.turn_sail(SAIL_CONE_STEP.to_radians(), 0.0);
+            }
+            WindowEvent::Key(KEY_SAIL_CLOCK_DOWN, Action::Press, _) => {
+                view.turn_sail(0.0, -SAIL_CLOCK_STEP.to_radians());
+            }
+            WindowEvent::Key(KEY_SAIL_CLOCK_UP, Action::Press, _) => {
+                view.turn_sail(0.0, SAIL_CLOCK_STEP.to_radians());
+            }
+            WindowEvent::Key(KEY_SAVE_BOOKMARK, Action::Press, _) => {
+                self.bookmark_name = Some(String::new());
+            }
+            WindowEvent::Key(KEY_SCREENSHOT, Action::Press, _) => {
+                self.screenshot_requested = true;
+            }
+            WindowEvent::Key(KEY_EXPORT_BUNDLE, Action::Press, _) => {
+                self.bundle_requested = true;
+            }
+            WindowEvent::Key(KEY_PLACE_SHIP, Action::Press, _) => {
+                self.paused = true;
+                view.start_placement();
+            }
+            WindowEvent::Key(KEY_REMOVE_SHIP, Action::Press, _) => view.remove_focused_ship(),
+            WindowEvent::Key(KEY_EDIT_NOTE, Action::Press, _) => view.start_editing_note(),
+            WindowEvent::Key(KEY_EDIT_ORBIT, Action::Press, _) => view.start_editing_orbit(),
+            WindowEvent::Key(KEY_RENAME_SHIP, Action::Press, _) if view.check_editable() => {
+                self.ship_name = view.focused_ship_name();
+            }
+            WindowEvent::Key(KEY_INSPECT_BODY, Action::Press, _) => {
+                self.paused = true;
+                view.start_inspecting();
+            }
+            WindowEvent::Key(KEY_EDIT_NODE, Action::Press, _) => {
+                self.paused = true;
+                view.start_editing_node();
+            }
+            WindowEvent::Key(key, Action::Press, _) if KEYS_RESTORE_BOOKMARK.contains(&key) => {
+                let idx = KEYS_RESTORE_BOOKMARK
+                    .iter()
+                    .position(|k| *k == key)
+                    .unwrap();
+                view.restore_bookmark(idx);
+            }
+            _ => {}
+        }
+    }
+
+    fn run_command(&mut self, command: Command, view: &mut View) {
+        match command {
+            Command::NextFocus => view.camera_focus_next(),
+            Command::PrevFocus => view.camera_focus_prev(),
+            Command::SpeedUp => {
                 self.timestep *= 2.0;
                 println!("Timestep is {} s / s", (60.0 * self.timestep).round())
             }
-            WindowEvent::Key(KEY_SLOW_DOWN, Action::Press, _) => {
+            Command::SlowDown => {
                 self.timestep /= 2.0;
                 println!("Timestep is {} s / s", (60.0 * self.timestep).round())
             }
-            WindowEvent::Key(KEY_REWIND, Action::Press, _) => {
+            Command::Rewind => {
                 self.timestep *= -1.0;
                 self.paused = false;
             }
-            WindowEvent::Key(KEY_TOGGLE_PAUSE, Action::Press, _) => {
-                self.paused = !self.paused;
+            Command::TogglePause => self.paused = !self.paused,
+        }
+    }
+
+    /// Scales the timestep to the focused object's orbit, when the focus has
+    /// changed since it was last picked, so that moons don't whip around at a
+    /// timestep meant for planets. Does nothing if that's turned off, or the
+    /// focused object isn't in a closed orbit. Should be called every frame.
+    pub fn update_auto_timestep(&mut self, view: &View) {
+        let focus = view.focus_point();
+        if !self.auto_timestep || self.timestep_picked_for == Some(focus) {
+            return;
+        }
+        self.timestep_picked_for = Some(focus);
+        if let Some(period) = view.focused_period() {
+            self.timestep = self.timestep.signum() * period * AUTO_TIMESTEP_FRACTION;
+            println!("Timestep is {} s / s", (60.0 * self.timestep).round());
+        }
+    }
+
+    pub fn set_gamepad(&mut self, gamepad: Gamepad) {
+        self.gamepad = Some(gamepad);
+    }
+
+    /// Reads the gamepad, if there is one. Unlike keys, the sticks are held
+    /// over, so this should be called every frame, and moves the camera by
+    /// how long it's been since the last call.
+    pub fn process_gamepad(&mut self, view: &mut View) {
+        let elapsed = self.last_gamepad_poll.elapsed().as_secs_f32();
+        self.last_gamepad_poll = Instant::now();
+        let gamepad = match &mut self.gamepad {
+            Some(gamepad) => gamepad,
+            None => return,
+        };
+
+        let buttons = gamepad.poll();
+        let orbit_x = gamepad.axis(GAMEPAD_AXIS_ORBIT_X);
+        let orbit_y = gamepad.axis(GAMEPAD_AXIS_ORBIT_Y);
+        let zoom = gamepad.axis(GAMEPAD_AXIS_ZOOM);
+
+        // Like the keys, the buttons don't do anything while something's
+        // being typed in or edited
+        if !self.is_modal_open(view) {
+            for (button, _) in buttons.into_iter().filter(|(_, pressed)| *pressed) {
+                if let Some((_, command)) = GAMEPAD_BUTTON_COMMANDS.iter().find(|c| c.0 == button) {
+                    self.run_command(*command, view);
+                }
             }
-            WindowEvent::Key(KEY_CAMERA_SWAP, Action::Press, _) => {
-                view.camera_inertial_toggle();
+        }
+
+        // Pushing the left stick up tilts the camera up, like W, and pushing
+        // the right one up zooms in
+        let orbit_step = GAMEPAD_ORBIT_RATE * elapsed;
+        if orbit_x != 0.0 || orbit_y != 0.0 {
+            view.rotate_camera(orbit_x * orbit_step, orbit_y * orbit_step);
+        }
+        if zoom != 0.0 {
+            view.zoom_camera(GAMEPAD_ZOOM_RATE.powf(zoom * elapsed));
+        }
+    }
+
+    /// Whether something's being typed in or edited, in which case the keys
+    /// go to that instead of their usual commands.
+    fn is_modal_open(&self, view: &View) -> bool {
+        self.bookmark_name.is_some()
+            || self.ship_name.is_some()
+            || view.is_placing()
+            || view.is_inspecting()
+            || view.is_editing_node()
+            || view.is_editing_note()
+            || view.is_editing_orbit()
+    }
+
+    /// Passes the input to whichever window is open. If there's more than
+    /// one, the first of them gets it.
+    fn process_modal_input(&mut self, input: ModalInput, view: &mut View) {
+        if self.bookmark_name.is_some() {
+            self.process_bookmark_name_input(input, view);
+        } else if self.ship_name.is_some() {
+            self.process_ship_name_input(input, view);
+        } else if view.is_placing() {
+            process_placement_input(input, view);
+        } else if view.is_inspecting() {
+            process_inspector_input(input, view);
+        } else if view.is_editing_node() {
+            process_node_editor_input(input, view);
+        } else if view.is_editing_note() {
+            process_note_editor_input(input, view);
+        } else if view.is_editing_orbit() {
+            process_orbit_editor_input(input, view);
+        }
+    }
+
+    /// Handles typing in the name of a new bookmark. Enter saves it, and
+    /// Escape cancels.
+    fn process_bookmark_name_input(&mut self, input: ModalInput, view: &mut View) {
+        if let Some(name) = edit_text(&mut self.bookmark_name, input) {
+            if !name.trim().is_empty() {
+                view.save_bookmark(name.trim().to_owned());
             }
-            _ => {}
         }
     }
 
+    /// Handles typing in a new name for the focused ship. Enter renames it,
+    /// or takes its name away if it's left blank, and Escape cancels.
+    fn process_ship_name_input(&mut self, input: ModalInput, view: &mut View) {
+        if let Some(name) = edit_text(&mut self.ship_name, input) {
+            let name = name.trim();
+            view.rename_focused_ship((!name.is_empty()).then(|| name.to_owned()));
+        }
+    }
+
+    /// The name of the bookmark being typed in, if any.
+    pub fn bookmark_name(&self) -> Option<&str> {
+        self.bookmark_name.as_deref()
+    }
+
+    /// The new name of the focused ship, while it's being typed in.
+    pub fn ship_name(&self) -> Option<&str> {
+        self.ship_name.as_deref()
+    }
+
+    /// Returns whether a screenshot was asked for since the last call.
+    pub fn take_screenshot_request(&mut self) -> bool {
+        std::mem::take(&mut self.screenshot_requested)
+    }
+
+    /// Returns whether a plan bundle was asked for since the last call.
+    pub fn take_bundle_request(&mut self) -> bool {
+        std::mem::take(&mut self.bundle_requested)
+    }
+
     pub fn is_paused(&self) -> bool {
         self.paused
     }
 
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn pauses_on_soi_changes(&self) -> bool {
+        self.pause_on_soi_changes
+    }
+
     pub fn timestep(&self) -> f64 {
         self.timestep
     }
 
+    pub fn picks_timestep_automatically(&self) -> bool {
+        self.auto_timestep
+    }
+
     pub fn fps(&self) -> f64 {
         self.fps_counter.value()
     }
@@ -112,3 +532,140 @@ impl Controller {
         self.fps_counter.increment()
     }
 }
+
+/// A key or character, as it matters to whatever's being typed in or edited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ModalInput {
+    Char(char),
+    Backspace,
+    Confirm,
+    Cancel,
+    /// Any other key being pressed
+    Key(Key),
+}
+
+impl ModalInput {
+    fn from_event(event: WindowEvent) -> Option<Self> {
+        match event {
+            WindowEvent::Char(c) if !c.is_control() => Some(ModalInput::Char(c)),
+            WindowEvent::Key(Key::Back, Action::Press, _) => Some(ModalInput::Backspace),
+            WindowEvent::Key(Key::Return | Key::NumpadEnter, Action::Press, _) => {
+                Some(ModalInput::Confirm)
+            }
+            // The window closes when Escape is released, so wait for that
+            WindowEvent::Key(Key::Escape, Action::Release, _) => Some(ModalInput::Cancel),
+            WindowEvent::Key(Key::Escape, Action::Press, _) => None,
+            WindowEvent::Key(key, Action::Press, _) => Some(ModalInput::Key(key)),
+            _ => None,
+        }
+    }
+}
+
+/// Edits a line of text that's being typed in, if there is one. When Enter is
+/// pressed, returns the text and stops editing it. Escape stops without
+/// returning anything.
+fn edit_text(text: &mut Option<String>, input: ModalInput) -> Option<String> {
+    let current = text.as_mut()?;
+    match input {
+        ModalInput::Char(c) => current.push(c),
+        ModalInput::Backspace => {
+            current.pop();
+        }
+        ModalInput::Confirm => return text.take(),
+        ModalInput::Cancel => *text = None,
+        ModalInput::Key(_) => {}
+    }
+    None
+}
+
+/// Handles adjusting the orbit of a new ship. Enter adds it, and Escape
+/// cancels.
+fn process_placement_input(input: ModalInput, view: &mut View) {
+    let key = match input {
+        ModalInput::Key(key) => key,
+        ModalInput::Confirm => return view.confirm_placement(),
+        ModalInput::Cancel => return view.cancel_placement(),
+        ModalInput::Char(_) | ModalInput::Backspace => return,
+    };
+    // The same keys change either the elements or their uncertainty, in
+    // these directions
+    let (anomaly, sma, incl) = match key {
+        KEY_PLACEMENT_ANOMALY_DOWN => (-1.0, 0.0, 0.0),
+        KEY_PLACEMENT_ANOMALY_UP => (1.0, 0.0, 0.0),
+        KEY_PLACEMENT_SMA_DOWN => (0.0, -1.0, 0.0),
+        KEY_PLACEMENT_SMA_UP => (0.0, 1.0, 0.0),
+        KEY_PLACEMENT_INCL_DOWN => (0.0, 0.0, -1.0),
+        KEY_PLACEMENT_INCL_UP => (0.0, 0.0, 1.0),
+        KEY_PLACEMENT_UNCERTAINTY => return view.toggle_editing_uncertainty(),
+        _ => return,
+    };
+    if view.is_editing_uncertainty() {
+        view.adjust_placement_uncertainty(
+            anomaly * UNCERTAINTY_ANOMALY_STEP.to_radians(),
+            sma * UNCERTAINTY_SMA_STEP,
+            incl * UNCERTAINTY_INCL_STEP.to_radians(),
+        );
+    } else {
+        view.adjust_placement(
+            anomaly * PLACEMENT_ANOMALY_STEP.to_radians(),
+            PLACEMENT_SMA_FACTOR.powf(sma),
+            incl * PLACEMENT_INCL_STEP.to_radians(),
+        );
+    }
+}
+
+/// Handles editing a body's properties. Enter applies the changes, and Escape
+/// cancels.
+fn process_inspector_input(input: ModalInput, view: &mut View) {
+    match input {
+        ModalInput::Key(Key::Up) => view.select_inspector_field(false),
+        ModalInput::Key(Key::Down) => view.select_inspector_field(true),
+        ModalInput::Key(Key::Left) => view.scale_inspector_field(INSPECTOR_FACTOR.recip()),
+        ModalInput::Key(Key::Right) => view.scale_inspector_field(INSPECTOR_FACTOR),
+        ModalInput::Confirm => view.apply_inspector(),
+        ModalInput::Cancel => view.cancel_inspector(),
+        _ => {}
+    }
+}
+
+/// Handles typing in a maneuver node's values. Enter keeps the node, and
+/// Escape puts back the plan from before.
+fn process_node_editor_input(input: ModalInput, view: &mut View) {
+    match input {
+        ModalInput::Char(c) => view.type_into_node(c),
+        ModalInput::Backspace => view.backspace_node(),
+        ModalInput::Key(Key::Up) => view.select_node_field(false),
+        ModalInput::Key(Key::Down) => view.select_node_field(true),
+        ModalInput::Key(Key::Tab) => view.cycle_node_time_reference(),
+        ModalInput::Confirm => view.finish_node(),
+        ModalInput::Cancel => view.cancel_node(),
+        ModalInput::Key(_) => {}
+    }
+}
+
+/// Handles typing in new orbital elements. Enter moves the body or ship onto
+/// the new orbit, and Escape cancels.
+fn process_orbit_editor_input(input: ModalInput, view: &mut View) {
+    match input {
+        ModalInput::Char(c) => view.type_into_orbit(c),
+        ModalInput::Backspace => view.backspace_orbit(),
+        ModalInput::Key(Key::Up) => view.select_orbit_field(false),
+        ModalInput::Key(Key::Down) => view.select_orbit_field(true),
+        ModalInput::Confirm => view.finish_orbit(),
+        ModalInput::Cancel => view.cancel_orbit(),
+        ModalInput::Key(_) => {}
+    }
+}
+
+/// Handles typing in a note. Tab switches what it's attached to, Enter keeps
+/// it, and Escape cancels.
+fn process_note_editor_input(input: ModalInput, view: &mut View) {
+    match input {
+        ModalInput::Char(c) => view.type_into_note(c),
+        ModalInput::Backspace => view.backspace_note(),
+        ModalInput::Key(Key::Tab) => view.cycle_note_target(),
+        ModalInput::Confirm => view.finish_note(),
+        ModalInput::Cancel => view.cancel_note(),
+        ModalInput::Key(_) => {}
+    }
+}
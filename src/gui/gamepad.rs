@@ -0,0 +1,106 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+// Types of events from the Linux joystick interface. Events sent to describe
+// the initial state of the device are also marked with JS_EVENT_INIT.
+const JS_EVENT_BUTTON: u8 = 0x01;
+const JS_EVENT_AXIS: u8 = 0x02;
+const JS_EVENT_INIT: u8 = 0x80;
+// Sticks that are this close to the center count as centered
+const AXIS_DEAD_ZONE: f32 = 0.15;
+
+/// Something that happened on a gamepad.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GamepadInput {
+    /// A button was pressed or released.
+    Button(u8, bool),
+    /// An axis moved to a new position, from -1 to 1.
+    Axis(u8, f32),
+}
+
+/// A gamepad or joystick, read through the Linux joystick interface (e.g.,
+/// `/dev/input/js0`).
+///
+/// Reads from the device block, so they're done on a separate thread, and the
+/// inputs are picked up from there once per frame.
+pub struct Gamepad {
+    inputs: Receiver<GamepadInput>,
+    // Where each axis was last seen
+    axes: Vec<f32>,
+    connected: bool,
+}
+
+impl Gamepad {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let (sender, inputs) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buf = [0; 8];
+            // Stop once the device is unplugged, or the gamepad is dropped
+            while file.read_exact(&mut buf).is_ok() {
+                if let Some(input) = parse_event(buf) {
+                    if sender.send(input).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(Self {
+            inputs,
+            axes: vec![],
+            connected: true,
+        })
+    }
+
+    /// Returns the buttons pressed or released since the last call. Axes are
+    /// kept track of, to be read with [Gamepad::axis].
+    pub fn poll(&mut self) -> Vec<(u8, bool)> {
+        let mut buttons = vec![];
+        while self.connected {
+            match self.inputs.try_recv() {
+                Ok(GamepadInput::Button(number, pressed)) => buttons.push((number, pressed)),
+                Ok(GamepadInput::Axis(number, value)) => {
+                    let number = number as usize;
+                    if self.axes.len() <= number {
+                        self.axes.resize(number + 1, 0.0);
+                    }
+                    self.axes[number] = value;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    println!("Gamepad disconnected");
+                    self.connected = false;
+                    self.axes.clear();
+                }
+            }
+        }
+        buttons
+    }
+
+    /// Where the given axis is, from -1 to 1, or 0 if it's near the center.
+    pub fn axis(&self, number: u8) -> f32 {
+        let value = self.axes.get(number as usize).copied().unwrap_or(0.0);
+        if value.abs() < AXIS_DEAD_ZONE {
+            0.0
+        } else {
+            value
+        }
+    }
+}
+
+/// Decodes an event from the joystick interface, which is a timestamp in
+/// milliseconds, a signed 16-bit value, the event's type, and which button or
+/// axis it's for, all in native byte order.
+fn parse_event(buf: [u8; 8]) -> Option<GamepadInput> {
+    let value = i16::from_ne_bytes([buf[4], buf[5]]);
+    let kind = buf[6] & !JS_EVENT_INIT;
+    let number = buf[7];
+    match kind {
+        JS_EVENT_BUTTON => Some(GamepadInput::Button(number, value != 0)),
+        JS_EVENT_AXIS => Some(GamepadInput::Axis(number, value as f32 / i16::MAX as f32)),
+        _ => None,
+    }
+}
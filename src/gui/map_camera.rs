@@ -0,0 +1,100 @@
+use kiss3d::event::WindowEvent;
+use kiss3d::planar_camera::PlanarCamera;
+use kiss3d::resource::ShaderUniform;
+use kiss3d::window::Canvas;
+use nalgebra::{Matrix3, Point2, Vector2, Vector3};
+
+// A top-down camera for the map view. It looks straight down onto the xy-plane
+// of focus space, so planar coordinates are just the x and y coordinates of
+// focus space, in meters.
+//
+// The camera doesn't zoom by itself; instead it's told how much to show, so
+// that it can follow the zoom of the 3D camera, and switching between the two
+// keeps the same scale.
+pub struct MapCamera {
+    // Distance from the center of the window to the top edge, in meters
+    half_height: f32,
+    // Size of the window, in pixels
+    width: u32,
+    height: u32,
+}
+
+impl MapCamera {
+    pub fn new() -> Self {
+        MapCamera {
+            half_height: 1.0,
+            width: 800,
+            height: 600,
+        }
+    }
+
+    pub fn set_half_height(&mut self, half_height: f32) {
+        self.half_height = half_height;
+    }
+
+    /// Returns how many meters one pixel covers.
+    pub fn pixel_size(&self) -> f32 {
+        2.0 * self.half_height / self.height as f32
+    }
+
+    /// Returns whether any part of the given circle, in planar coordinates,
+    /// is in the window.
+    pub fn circle_in_view(&self, center: Point2<f32>, radius: f32) -> bool {
+        let half_width = self.half_height * self.width as f32 / self.height as f32;
+        center.x.abs() <= half_width + radius && center.y.abs() <= self.half_height + radius
+    }
+
+    fn projection(&self) -> Matrix3<f32> {
+        let aspect = self.width as f32 / self.height as f32;
+        Matrix3::from_diagonal(&Vector3::new(
+            1.0 / (self.half_height * aspect),
+            1.0 / self.half_height,
+            1.0,
+        ))
+    }
+
+    /// Converts from window pixels, with the origin at the top-left like the
+    /// cursor's, to planar coordinates.
+    pub fn window_to_planar(&self, point: Point2<f32>) -> Point2<f32> {
+        let center = Vector2::new(self.width as f32, self.height as f32) / 2.0;
+        let pixel_size = self.pixel_size();
+        Point2::new(
+            (point.x - center.x) * pixel_size,
+            (center.y - point.y) * pixel_size,
+        )
+    }
+
+    /// The inverse of [MapCamera::window_to_planar].
+    pub fn planar_to_window(&self, point: Point2<f32>) -> Point2<f32> {
+        let center = Vector2::new(self.width as f32, self.height as f32) / 2.0;
+        let pixel_size = self.pixel_size();
+        Point2::new(
+            center.x + point.x / pixel_size,
+            center.y - point.y / pixel_size,
+        )
+    }
+}
+
+impl PlanarCamera for MapCamera {
+    fn handle_event(&mut self, _canvas: &Canvas, event: &WindowEvent) {
+        if let WindowEvent::FramebufferSize(w, h) = *event {
+            self.width = w;
+            self.height = h;
+        }
+    }
+
+    fn update(&mut self, _canvas: &Canvas) {}
+
+    fn upload(
+        &self,
+        proj: &mut ShaderUniform<Matrix3<f32>>,
+        view: &mut ShaderUniform<Matrix3<f32>>,
+    ) {
+        proj.upload(&self.projection());
+        view.upload(&Matrix3::identity());
+    }
+
+    fn unproject(&self, window_coord: &Point2<f32>, _window_size: &Vector2<f32>) -> Point2<f32> {
+        self.window_to_planar(*window_coord)
+    }
+}
@@ -1,3 +1,7 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
 use kiss3d::camera::Camera;
 use kiss3d::event::EventManager;
 use kiss3d::planar_camera::PlanarCamera;
@@ -5,18 +9,40 @@ use kiss3d::post_processing::PostProcessingEffect;
 use kiss3d::renderer::Renderer;
 use kiss3d::window::{State, Window};
 
+pub use self::autosave::{Autosave, Session};
+pub use self::bookmarks::Bookmarks;
+pub use self::bundle::PlanBundle;
 use self::controller::Controller;
+pub use self::gamepad::Gamepad;
+pub use self::screenshot::save_screenshot;
+use self::screenshot::unused_path;
 use self::view::View;
+use crate::model::readouts::CustomReadout;
 use crate::model::timeline::Timeline;
 
+mod autosave;
+mod bookmarks;
+mod bundle;
 mod camera;
 mod controller;
+mod gamepad;
+mod map_camera;
+mod node_editor;
+mod notes;
+mod orbit_editor;
+mod panels;
 mod renderers;
+mod screenshot;
+mod scrubber;
 mod view;
 
 pub struct Simulation {
     view: View,
     controller: Controller,
+    // Where screenshots and plan bundles are saved
+    screenshot_dir: PathBuf,
+    bundle_dir: PathBuf,
+    autosave: Option<Autosave>,
 }
 
 impl Simulation {
@@ -24,14 +50,89 @@ impl Simulation {
         Self {
             view: View::new(timeline, window),
             controller: Controller::new(),
+            screenshot_dir: PathBuf::from("screenshots"),
+            bundle_dir: PathBuf::from("bundles"),
+            autosave: None,
         }
     }
 
+    pub fn with_bookmarks(mut self, bookmarks: Bookmarks) -> Self {
+        self.view.set_bookmarks(bookmarks);
+        self
+    }
+
+    /// Adds readouts for the focused object to the HUD, which are worked out
+    /// from the given expressions.
+    pub fn with_custom_readouts(mut self, readouts: Vec<CustomReadout>) -> Self {
+        self.view.set_custom_readouts(readouts);
+        self
+    }
+
+    /// Sets how far back, in seconds, ships' trails go.
+    pub fn with_trail_duration(mut self, duration: f64) -> Self {
+        self.view.set_trail_duration(duration);
+        self
+    }
+
+    /// Sets how much bigger than usual markers and text are drawn, e.g., 2
+    /// for high-DPI displays.
+    pub fn with_ui_scale(mut self, scale: f32) -> Self {
+        self.view.set_ui_scale(scale);
+        self
+    }
+
+    /// Draws spheres and lines in batches, with instancing, instead of one
+    /// draw call at a time.
+    pub fn with_instanced_rendering(mut self, instanced: bool) -> Self {
+        self.view.set_instanced_rendering(instanced);
+        self
+    }
+
+    /// Moves the camera and controls time with the gamepad, as well as the
+    /// keyboard.
+    pub fn with_gamepad(mut self, gamepad: Gamepad) -> Self {
+        self.controller.set_gamepad(gamepad);
+        self
+    }
+
+    pub fn with_screenshot_dir(mut self, dir: PathBuf) -> Self {
+        self.screenshot_dir = dir;
+        self
+    }
+
+    pub fn with_bundle_dir(mut self, dir: PathBuf) -> Self {
+        self.bundle_dir = dir;
+        self
+    }
+
+    /// Shows the plan in the bundle, which should be the one the timeline was
+    /// built from, without letting it be changed.
+    pub fn with_plan_bundle(mut self, bundle: PlanBundle) -> Self {
+        self.view.open_bundle(bundle);
+        self
+    }
+
+    /// Saves the session every so often, so it can be restored if the program
+    /// dies.
+    pub fn with_autosave(mut self, autosave: Autosave) -> Self {
+        self.autosave = Some(autosave);
+        self
+    }
+
+    /// Picks up where an earlier session left off, redoing the changes it
+    /// made to the timeline. It should have started with the same timeline
+    /// as this one.
+    pub fn with_session(mut self, session: Session) -> Self {
+        self.view.restore_session(session);
+        self
+    }
+
     fn process_user_input(&mut self, mut events: EventManager) {
         // Process events
         for event in events.iter() {
             self.controller.process_event(event, &mut self.view);
         }
+        self.controller.process_gamepad(&mut self.view);
     }
 }
 
@@ -49,8 +150,44 @@ impl State for Simulation {
 
     fn step(&mut self, window: &mut Window) {
         self.process_user_input(window.events());
+        self.controller.update_auto_timestep(&self.view);
+        // The window still holds the frame that was just drawn
+        if self.controller.take_screenshot_request() {
+            match save_screenshot(window, &self.screenshot_dir) {
+                Ok(path) => println!("Saved screenshot to {}", path.display()),
+                Err(e) => println!("Couldn't save screenshot: {}", e),
+            }
+        }
+        if self.controller.take_bundle_request() {
+            let path = unused_path(&self.bundle_dir, "plan", "kspplan");
+            let saved = self
+                .view
+                .plan_bundle()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+                .and_then(|bundle| {
+                    fs::create_dir_all(&self.bundle_dir)?;
+                    bundle.save(&path)
+                });
+            match saved {
+                Ok(()) => println!("Saved plan bundle to {}", path.display()),
+                Err(e) => println!("Couldn't save plan bundle: {}", e),
+            }
+        }
         if !self.controller.is_paused() {
-            self.view.update_state_by(self.controller.timestep());
+            let timestep = self.controller.timestep();
+            if !self.controller.pauses_on_soi_changes() {
+                self.view.update_state_by(timestep);
+            } else if self.view.update_state_until_soi_change(timestep) {
+                self.controller.pause();
+            }
+        }
+        // Don't save a maneuver node that's still being typed in
+        if let Some(autosave) = &mut self.autosave {
+            if autosave.is_due() && !self.view.is_editing_node() {
+                if let Err(e) = autosave.save(&self.view.session()) {
+                    println!("Couldn't autosave: {}", e);
+                }
+            }
         }
         self.view.prerender_scene(window, &self.controller);
         self.controller.increment_frame_counter();
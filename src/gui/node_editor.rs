@@ -0,0 +1,182 @@
+use std::fmt;
+
+use nalgebra::Vector3;
+
+use crate::model::orrery::{Maneuver, ManeuverPlan, ShipID};
+
+/// Which value in the node editor is being typed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeField {
+    Time,
+    Prograde,
+    Normal,
+    Radial,
+}
+
+impl NodeField {
+    const ALL: [NodeField; 4] = [
+        NodeField::Time,
+        NodeField::Prograde,
+        NodeField::Normal,
+        NodeField::Radial,
+    ];
+
+    fn index(self) -> usize {
+        Self::ALL.iter().position(|f| *f == self).unwrap()
+    }
+}
+
+/// What the node's time is measured from. Apart from absolute times, these
+/// are landmarks the ship passes, and the time is how long after the next one
+/// the node is; typing in zero snaps the node to the landmark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeReference {
+    Absolute,
+    Periapsis,
+    Apoapsis,
+    /// Ascending node, relative to the target's orbit.
+    AscendingNode,
+    /// Likewise for the descending node.
+    DescendingNode,
+    /// Entering or leaving an SOI.
+    SoiChange,
+}
+
+impl fmt::Display for TimeReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            TimeReference::Absolute => "absolute",
+            TimeReference::Periapsis => "after periapsis",
+            TimeReference::Apoapsis => "after apoapsis",
+            TimeReference::AscendingNode => "after AN",
+            TimeReference::DescendingNode => "after DN",
+            TimeReference::SoiChange => "after SOI change",
+        };
+        f.write_str(s)
+    }
+}
+
+/// A maneuver node whose values are being typed in. Each value is kept as
+/// the text typed so far, so that half-typed numbers like "-" or "1e" can be
+/// shown as they are.
+#[derive(Debug, Clone)]
+pub struct NodeEditor {
+    pub ship_id: ShipID,
+    /// The ship's plan before editing started, to put back if the edit is
+    /// cancelled.
+    pub original_plan: ManeuverPlan,
+    /// The plan without the node being edited, which the node is added to.
+    pub base_plan: ManeuverPlan,
+    pub selected: NodeField,
+    pub time_reference: TimeReference,
+    /// When the ship next passes the landmark the time is measured from, if
+    /// it does.
+    pub landmark_time: Option<f64>,
+    /// The node the typed values work out to, if they're valid.
+    pub node: Option<Maneuver>,
+    texts: [String; 4],
+}
+
+impl NodeEditor {
+    /// Starts editing a node at the given time, with a change in velocity
+    /// along prograde, normal and radial-out.
+    pub fn new(
+        ship_id: ShipID,
+        original_plan: ManeuverPlan,
+        base_plan: ManeuverPlan,
+        time_reference: TimeReference,
+        time: f64,
+        delta_v: Vector3<f64>,
+    ) -> Self {
+        Self {
+            ship_id,
+            original_plan,
+            base_plan,
+            selected: NodeField::Time,
+            time_reference,
+            landmark_time: None,
+            node: None,
+            texts: [
+                format!("{:.0}", time),
+                format_velocity(delta_v.x),
+                format_velocity(delta_v.y),
+                format_velocity(delta_v.z),
+            ],
+        }
+    }
+
+    /// Selects the next or previous field.
+    pub fn select(&mut self, forward: bool) {
+        let n = NodeField::ALL.len();
+        let idx = self.selected.index();
+        let idx = if forward {
+            (idx + 1) % n
+        } else {
+            (idx + n - 1) % n
+        };
+        self.selected = NodeField::ALL[idx];
+    }
+
+    /// Switches what the time is measured from. The time is cleared, since
+    /// it means something else now.
+    pub fn cycle_time_reference(&mut self) {
+        self.time_reference = match self.time_reference {
+            TimeReference::Absolute => TimeReference::Periapsis,
+            TimeReference::Periapsis => TimeReference::Apoapsis,
+            TimeReference::Apoapsis => TimeReference::AscendingNode,
+            TimeReference::AscendingNode => TimeReference::DescendingNode,
+            TimeReference::DescendingNode => TimeReference::SoiChange,
+            TimeReference::SoiChange => TimeReference::Absolute,
+        };
+        self.texts[NodeField::Time.index()] = String::from("0");
+    }
+
+    /// Types a character into the selected field. Only characters that can
+    /// be part of a number are accepted; returns whether it was.
+    pub fn type_char(&mut self, c: char) -> bool {
+        if !(c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E')) {
+            return false;
+        }
+        self.texts[self.selected.index()].push(c);
+        true
+    }
+
+    pub fn backspace(&mut self) {
+        self.texts[self.selected.index()].pop();
+    }
+
+    /// What's been typed into the given field.
+    pub fn text(&self, field: NodeField) -> &str {
+        &self.texts[field.index()]
+    }
+
+    /// The value of the given field, if it's a valid number. Empty fields
+    /// count as zero.
+    pub fn value(&self, field: NodeField) -> Option<f64> {
+        let text = self.text(field).trim();
+        if text.is_empty() {
+            return Some(0.0);
+        }
+        text.parse().ok().filter(|x: &f64| x.is_finite())
+    }
+
+    /// The change in velocity along prograde, normal and radial-out, if all
+    /// of them are valid.
+    pub fn local_delta_v(&self) -> Option<Vector3<f64>> {
+        Some(Vector3::new(
+            self.value(NodeField::Prograde)?,
+            self.value(NodeField::Normal)?,
+            self.value(NodeField::Radial)?,
+        ))
+    }
+}
+
+fn format_velocity(x: f64) -> String {
+    // Enough precision to round-trip anything typed in by hand
+    let s = format!("{:.3}", x);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    match s {
+        "-0" => String::from("0"),
+        s => s.to_owned(),
+    }
+}
@@ -0,0 +1,126 @@
+use crate::model::orrery::ShipID;
+
+/// What a note is attached to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoteTarget {
+    Ship(ShipID),
+    /// One of a ship's burns, by when it's planned for.
+    Maneuver(ShipID, f64),
+    /// A moment in the timeline, marked on the time bar.
+    Time(f64),
+}
+
+/// A bit of free-form text the user attached to something, to explain what
+/// it's for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Note {
+    pub target: NoteTarget,
+    pub text: String,
+}
+
+impl Note {
+    pub(super) fn to_line(&self) -> String {
+        // The text comes last, since it may contain spaces
+        match self.target {
+            NoteTarget::Ship(id) => format!("ship {} {}", id.0, self.text),
+            NoteTarget::Maneuver(id, time) => format!("burn {} {} {}", id.0, time, self.text),
+            NoteTarget::Time(time) => format!("time {} {}", time, self.text),
+        }
+    }
+
+    pub(super) fn from_line(line: &str) -> Option<Self> {
+        let (kind, rest) = line.split_once(' ')?;
+        let (target, text) = match kind {
+            "ship" => {
+                let (id, text) = rest.split_once(' ')?;
+                (NoteTarget::Ship(ShipID(id.parse().ok()?)), text)
+            }
+            "burn" => {
+                let mut fields = rest.splitn(3, ' ');
+                let id = fields.next()?.parse().ok()?;
+                let time = fields.next()?.parse().ok()?;
+                (NoteTarget::Maneuver(ShipID(id), time), fields.next()?)
+            }
+            "time" => {
+                let (time, text) = rest.split_once(' ')?;
+                (NoteTarget::Time(time.parse().ok()?), text)
+            }
+            _ => return None,
+        };
+        Some(Self {
+            target,
+            text: text.to_owned(),
+        })
+    }
+}
+
+/// The notes the user has made, at most one per target.
+#[derive(Debug, Clone, Default)]
+pub struct Notes {
+    notes: Vec<Note>,
+}
+
+impl Notes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, target: NoteTarget) -> Option<&str> {
+        self.notes
+            .iter()
+            .find(|n| n.target == target)
+            .map(|n| n.text.as_str())
+    }
+
+    /// Attaches the text to the target, replacing any note already there. A
+    /// blank note takes it away instead.
+    pub fn set(&mut self, target: NoteTarget, text: &str) {
+        self.notes.retain(|n| n.target != target);
+        if !text.trim().is_empty() {
+            self.notes.push(Note {
+                target,
+                text: text.trim().to_owned(),
+            });
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Note> {
+        self.notes.iter()
+    }
+
+    /// The times that have notes, and their notes, in order.
+    pub fn times(&self) -> Vec<(f64, &str)> {
+        let mut times: Vec<_> = self
+            .notes
+            .iter()
+            .filter_map(|n| match n.target {
+                NoteTarget::Time(time) => Some((time, n.text.as_str())),
+                _ => None,
+            })
+            .collect();
+        times.sort_by(|a, b| a.0.total_cmp(&b.0));
+        times
+    }
+
+    /// Keeps the note on a burn attached to it when it's moved to another
+    /// time, or drops it if the burn's gone.
+    pub fn move_maneuver(&mut self, ship_id: ShipID, old_time: f64, new_time: Option<f64>) {
+        let old_target = NoteTarget::Maneuver(ship_id, old_time);
+        let text = match self.get(old_target) {
+            Some(text) => text.to_owned(),
+            None => return,
+        };
+        self.set(old_target, "");
+        if let Some(new_time) = new_time {
+            self.set(NoteTarget::Maneuver(ship_id, new_time), &text);
+        }
+    }
+
+    /// Drops the notes about a ship and its burns.
+    pub fn remove_ship(&mut self, ship_id: ShipID) {
+        self.notes.retain(|n| match n.target {
+            NoteTarget::Ship(id) | NoteTarget::Maneuver(id, _) => id != ship_id,
+            NoteTarget::Time(_) => true,
+        });
+    }
+}
@@ -0,0 +1,136 @@
+use std::f64::consts::TAU;
+
+use super::view::FocusPoint;
+use crate::model::orrery::OrbitElements;
+
+/// Which element in the orbit editor is being typed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrbitField {
+    SemimajorAxis,
+    Eccentricity,
+    Inclination,
+    LongAscNode,
+    ArgPeriapsis,
+    MeanAnomaly,
+}
+
+impl OrbitField {
+    pub const ALL: [OrbitField; 6] = [
+        OrbitField::SemimajorAxis,
+        OrbitField::Eccentricity,
+        OrbitField::Inclination,
+        OrbitField::LongAscNode,
+        OrbitField::ArgPeriapsis,
+        OrbitField::MeanAnomaly,
+    ];
+
+    fn index(self) -> usize {
+        Self::ALL.iter().position(|f| *f == self).unwrap()
+    }
+
+    /// How the field is labeled, with the units it's typed in.
+    pub fn label(self) -> &'static str {
+        match self {
+            OrbitField::SemimajorAxis => "SMA (km)",
+            OrbitField::Eccentricity => "Eccentricity",
+            OrbitField::Inclination => "Inclination (deg)",
+            OrbitField::LongAscNode => "LAN (deg)",
+            OrbitField::ArgPeriapsis => "Arg. periapsis (deg)",
+            OrbitField::MeanAnomaly => "Mean anomaly (deg)",
+        }
+    }
+}
+
+/// New Keplerian elements being typed in for a body or ship, which it's moved
+/// onto at the time editing started. Like in the
+/// [NodeEditor](super::node_editor::NodeEditor), each value is kept as the
+/// text typed so far.
+#[derive(Debug, Clone)]
+pub struct OrbitEditor {
+    pub point: FocusPoint,
+    pub time: f64,
+    pub selected: OrbitField,
+    texts: [String; 6],
+}
+
+impl OrbitEditor {
+    /// Starts editing from the given elements, which are shown in km and
+    /// degrees.
+    pub fn new(point: FocusPoint, time: f64, elements: &OrbitElements) -> Self {
+        Self {
+            point,
+            time,
+            selected: OrbitField::SemimajorAxis,
+            texts: [
+                format_value(elements.semimajor_axis / 1000.0),
+                format_value(elements.eccentricity),
+                format_value(elements.inclination.to_degrees()),
+                format_value(elements.long_asc_node.to_degrees()),
+                format_value(elements.arg_periapsis.to_degrees()),
+                format_value(elements.mean_anomaly.to_degrees()),
+            ],
+        }
+    }
+
+    /// Selects the next or previous field.
+    pub fn select(&mut self, forward: bool) {
+        let n = OrbitField::ALL.len();
+        let idx = self.selected.index();
+        let idx = if forward {
+            (idx + 1) % n
+        } else {
+            (idx + n - 1) % n
+        };
+        self.selected = OrbitField::ALL[idx];
+    }
+
+    /// Types a character into the selected field, if it can be part of a
+    /// number.
+    pub fn type_char(&mut self, c: char) {
+        if c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E') {
+            self.texts[self.selected.index()].push(c);
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        self.texts[self.selected.index()].pop();
+    }
+
+    /// What's been typed into the given field.
+    pub fn text(&self, field: OrbitField) -> &str {
+        &self.texts[field.index()]
+    }
+
+    fn value(&self, field: OrbitField) -> Option<f64> {
+        self.text(field)
+            .trim()
+            .parse()
+            .ok()
+            .filter(|x: &f64| x.is_finite())
+    }
+
+    /// The elements typed in, if they're all numbers and make an elliptic
+    /// orbit.
+    pub fn elements(&self) -> Option<OrbitElements> {
+        let angle = |field| self.value(field).map(f64::to_radians);
+        let elements = OrbitElements {
+            semimajor_axis: self.value(OrbitField::SemimajorAxis)? * 1000.0,
+            eccentricity: self.value(OrbitField::Eccentricity)?,
+            inclination: angle(OrbitField::Inclination)?,
+            long_asc_node: angle(OrbitField::LongAscNode)?,
+            arg_periapsis: angle(OrbitField::ArgPeriapsis)?,
+            mean_anomaly: angle(OrbitField::MeanAnomaly)?.rem_euclid(TAU),
+        };
+        elements.is_elliptic().then_some(elements)
+    }
+}
+
+fn format_value(x: f64) -> String {
+    // Enough precision to not move anything noticeably when left alone
+    let s = format!("{:.6}", x);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    match s {
+        "-0" => String::from("0"),
+        s => s.to_owned(),
+    }
+}
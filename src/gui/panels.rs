@@ -0,0 +1,205 @@
+use std::fmt::Display;
+
+use kiss3d::text::Font;
+use kiss3d::window::Window;
+use nalgebra::{Point2, Point3, Vector2};
+
+/// How many text coordinates there are to a window pixel. kiss3d's text
+/// shader maps text coordinates from zero to twice the window's size onto the
+/// window, so everything drawn as text is positioned at double the window's
+/// pixel coordinates. This has nothing to do with the display's DPI.
+pub const TEXT_COORDS_PER_PIXEL: f32 = 2.0;
+
+// All sizes are in text coordinates, at a UI scale of 1.
+const FONT_SIZE: f32 = 40.0;
+const LINE_HEIGHT: f32 = 48.0;
+// We don't have access to the font metrics, so this is a generous estimate of
+// the average glyph width, used to right-align panels.
+const CHAR_WIDTH: f32 = 0.55 * FONT_SIZE;
+const INDENT: &str = "    ";
+const MARGIN: f32 = 20.0;
+const PANEL_SPACING: f32 = 24.0;
+
+const TITLE_COLOR: [f32; 3] = [1.0, 0.8, 0.3];
+const TEXT_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+
+/// A titled block of text on the HUD.
+#[derive(Debug, Clone)]
+pub struct Panel {
+    title: String,
+    lines: Vec<String>,
+}
+
+impl Panel {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            lines: vec![],
+        }
+    }
+
+    /// Adds a line of free-form text.
+    pub fn line(mut self, text: impl Into<String>) -> Self {
+        self.lines.push(text.into());
+        self
+    }
+
+    /// Adds a line of the form `name: value`.
+    pub fn field(self, name: &str, value: impl Display) -> Self {
+        self.line(format!("{}: {}", name, value))
+    }
+
+    fn num_lines(&self) -> usize {
+        1 + self.lines.len()
+    }
+
+    fn width(&self, scale: f32) -> f32 {
+        let title_chars = self.title.chars().count();
+        let line_chars = self
+            .lines
+            .iter()
+            .map(|line| INDENT.len() + line.chars().count());
+        let max_chars = line_chars.fold(title_chars, usize::max);
+        max_chars as f32 * CHAR_WIDTH * scale
+    }
+
+    fn height(&self, scale: f32) -> f32 {
+        self.num_lines() as f32 * LINE_HEIGHT * scale
+    }
+
+    /// Draws the panel with its top-left corner at the given point, with
+    /// text the given multiple of its usual size.
+    fn draw(&self, window: &mut Window, top_left: Point2<f32>, scale: f32) {
+        let font = Font::default();
+        let title_color = Point3::from(TITLE_COLOR);
+        let text_color = Point3::from(TEXT_COLOR);
+        let font_size = FONT_SIZE * scale;
+        window.draw_text(&self.title, &top_left, font_size, &font, &title_color);
+        for (i, line) in self.lines.iter().enumerate() {
+            let position = top_left + Vector2::new(0.0, (i + 1) as f32 * LINE_HEIGHT * scale);
+            window.draw_text(
+                &format!("{}{}", INDENT, line),
+                &position,
+                font_size,
+                &font,
+                &text_color,
+            );
+        }
+    }
+}
+
+/// Which corner of the window a panel is stacked in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Where a panel goes on the window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Placement {
+    Stacked(Corner),
+    /// Top-left corner at the given point, in window pixels
+    At(Point2<f32>),
+}
+
+/// Collects the panels for a frame, and lays them out. Panels in the top
+/// corners stack downwards in the order they were added, and ones in the bottom
+/// corners stack upwards. Panels can also be put at a given point, like menus,
+/// on top of the rest.
+#[derive(Debug, Default)]
+pub struct PanelLayout {
+    panels: Vec<(Placement, Panel)>,
+    // Where each panel's top-left corner was drawn, in text coordinates, and
+    // at what scale, so that clicks can be matched up with lines
+    positions: Vec<Point2<f32>>,
+    scale: f32,
+}
+
+impl PanelLayout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a panel, returning its index, for use with
+    /// [PanelLayout::line_at].
+    pub fn add(&mut self, corner: Corner, panel: Panel) -> usize {
+        self.panels.push((Placement::Stacked(corner), panel));
+        self.panels.len() - 1
+    }
+
+    /// Adds a panel with its top-left corner at the given point, in window
+    /// pixels, moved over if it'd go off the window. Returns its index, like
+    /// [PanelLayout::add].
+    pub fn add_at(&mut self, top_left: Point2<f32>, panel: Panel) -> usize {
+        self.panels.push((Placement::At(top_left), panel));
+        self.panels.len() - 1
+    }
+
+    /// Draws the panels, scaled up by the given UI scale.
+    pub fn draw(&mut self, window: &mut Window, scale: f32) {
+        let width = window.width() as f32 * TEXT_COORDS_PER_PIXEL;
+        let height = window.height() as f32 * TEXT_COORDS_PER_PIXEL;
+        let margin = MARGIN * scale;
+
+        self.positions = vec![Point2::origin(); self.panels.len()];
+        self.scale = scale;
+        for corner in [
+            Corner::TopLeft,
+            Corner::TopRight,
+            Corner::BottomLeft,
+            Corner::BottomRight,
+        ] {
+            let mut offset = margin;
+            for (idx, (_, panel)) in self
+                .panels
+                .iter()
+                .enumerate()
+                .filter(|(_, (p, _))| *p == Placement::Stacked(corner))
+            {
+                let x = match corner {
+                    Corner::TopLeft | Corner::BottomLeft => margin,
+                    Corner::TopRight | Corner::BottomRight => width - margin - panel.width(scale),
+                };
+                let y = match corner {
+                    Corner::TopLeft | Corner::TopRight => offset,
+                    Corner::BottomLeft | Corner::BottomRight => {
+                        height - offset - panel.height(scale)
+                    }
+                };
+                self.positions[idx] = Point2::new(x, y);
+                panel.draw(window, self.positions[idx], scale);
+                offset += panel.height(scale) + PANEL_SPACING * scale;
+            }
+        }
+
+        for (idx, (placement, panel)) in self.panels.iter().enumerate() {
+            if let Placement::At(top_left) = placement {
+                let top_left = top_left * TEXT_COORDS_PER_PIXEL;
+                let x = top_left.x.min(width - margin - panel.width(scale));
+                let y = top_left.y.min(height - margin - panel.height(scale));
+                self.positions[idx] = Point2::new(x.max(0.0), y.max(0.0));
+                panel.draw(window, self.positions[idx], scale);
+            }
+        }
+    }
+
+    /// Finds the panel and line under the given cursor position (in window
+    /// pixels), as they were last drawn. Line 0 is the title, and the panel's
+    /// own lines start at 1.
+    pub fn line_at(&self, cursor: Point2<f32>) -> Option<(usize, usize)> {
+        let point = cursor * TEXT_COORDS_PER_PIXEL;
+        self.panels
+            .iter()
+            .zip(self.positions.iter())
+            .enumerate()
+            .find_map(|(idx, ((_, panel), top_left))| {
+                let offset = point - top_left;
+                let inside = (0.0..panel.width(self.scale)).contains(&offset.x)
+                    && (0.0..panel.height(self.scale)).contains(&offset.y);
+                inside.then(|| (idx, (offset.y / (LINE_HEIGHT * self.scale)) as usize))
+            })
+    }
+}
@@ -0,0 +1,183 @@
+use kiss3d::camera::Camera;
+use kiss3d::context::Context;
+use kiss3d::light::Light;
+use kiss3d::resource::{Effect, Material, Mesh, ShaderAttribute, ShaderUniform};
+use kiss3d::scene::ObjectData;
+use nalgebra::{Isometry3, Matrix3, Matrix4, Point2, Point3, Vector3};
+
+// How bright the night side of a body is, compared to the day side
+const NIGHT_BRIGHTNESS: f32 = 0.04;
+
+/// Shades bodies by how directly the light falls on them, so that their
+/// night sides are dark. Stars make their own light, so they can be drawn
+/// unshaded instead.
+///
+/// Unlike kiss3d's default material, there's no specular highlight or
+/// wireframe support, since bodies don't need them.
+pub struct BodyMaterial {
+    shader: Effect,
+    position: ShaderAttribute<Point3<f32>>,
+    normal: ShaderAttribute<Vector3<f32>>,
+    tex_coord: ShaderAttribute<Point2<f32>>,
+    light: ShaderUniform<Point3<f32>>,
+    color: ShaderUniform<Point3<f32>>,
+    night_brightness: ShaderUniform<f32>,
+    transform: ShaderUniform<Matrix4<f32>>,
+    scale: ShaderUniform<Matrix3<f32>>,
+    ntransform: ShaderUniform<Matrix3<f32>>,
+    proj: ShaderUniform<Matrix4<f32>>,
+    view: ShaderUniform<Matrix4<f32>>,
+    shaded: bool,
+}
+
+impl BodyMaterial {
+    pub fn new(shaded: bool) -> Self {
+        let mut shader = Effect::new_from_str(VERTEX_SRC, FRAGMENT_SRC);
+
+        shader.use_program();
+
+        BodyMaterial {
+            position: shader
+                .get_attrib::<Point3<f32>>("position")
+                .expect("Failed to get shader attribute."),
+            normal: shader
+                .get_attrib::<Vector3<f32>>("normal")
+                .expect("Failed to get shader attribute."),
+            tex_coord: shader
+                .get_attrib::<Point2<f32>>("tex_coord")
+                .expect("Failed to get shader attribute."),
+            light: shader
+                .get_uniform::<Point3<f32>>("light_position")
+                .expect("Failed to get shader uniform."),
+            color: shader
+                .get_uniform::<Point3<f32>>("color")
+                .expect("Failed to get shader uniform."),
+            night_brightness: shader
+                .get_uniform::<f32>("night_brightness")
+                .expect("Failed to get shader uniform."),
+            transform: shader
+                .get_uniform::<Matrix4<f32>>("transform")
+                .expect("Failed to get shader uniform."),
+            scale: shader
+                .get_uniform::<Matrix3<f32>>("scale")
+                .expect("Failed to get shader uniform."),
+            ntransform: shader
+                .get_uniform::<Matrix3<f32>>("ntransform")
+                .expect("Failed to get shader uniform."),
+            proj: shader
+                .get_uniform::<Matrix4<f32>>("proj")
+                .expect("Failed to get shader uniform."),
+            view: shader
+                .get_uniform::<Matrix4<f32>>("view")
+                .expect("Failed to get shader uniform."),
+            shader,
+            shaded,
+        }
+    }
+}
+
+impl Material for BodyMaterial {
+    fn render(
+        &mut self,
+        pass: usize,
+        transform: &Isometry3<f32>,
+        scale: &Vector3<f32>,
+        camera: &mut dyn Camera,
+        light: &Light,
+        data: &ObjectData,
+        mesh: &mut Mesh,
+    ) {
+        if !data.surface_rendering_active() {
+            return;
+        }
+
+        self.shader.use_program();
+        self.position.enable();
+        self.normal.enable();
+        self.tex_coord.enable();
+
+        camera.upload(pass, &mut self.proj, &mut self.view);
+        let light_position = match *light {
+            Light::Absolute(p) => p,
+            Light::StickToCamera => camera.eye(),
+        };
+        self.light.upload(&light_position);
+        // Unshaded bodies are as bright at night as during the day
+        self.night_brightness
+            .upload(if self.shaded { &NIGHT_BRIGHTNESS } else { &1.0 });
+
+        self.transform.upload(&transform.to_homogeneous());
+        self.ntransform
+            .upload(&transform.rotation.to_rotation_matrix().into_inner());
+        self.scale.upload(&Matrix3::from_diagonal(scale));
+        self.color.upload(data.color());
+
+        mesh.bind(&mut self.position, &mut self.normal, &mut self.tex_coord);
+
+        let ctxt = Context::get();
+        ctxt.active_texture(Context::TEXTURE0);
+        ctxt.bind_texture(Context::TEXTURE_2D, Some(data.texture()));
+        if data.backface_culling_enabled() {
+            ctxt.enable(Context::CULL_FACE);
+        } else {
+            ctxt.disable(Context::CULL_FACE);
+        }
+        ctxt.draw_elements(
+            Context::TRIANGLES,
+            mesh.num_pts() as i32,
+            Context::UNSIGNED_SHORT,
+            0,
+        );
+
+        mesh.unbind();
+        self.position.disable();
+        self.normal.disable();
+        self.tex_coord.disable();
+    }
+}
+
+/// Vertex shader that works out which way the light is in world space,
+/// rather than relative to the camera like kiss3d's default one.
+static VERTEX_SRC: &str = "#version 100
+    attribute vec3 position;
+    attribute vec2 tex_coord;
+    attribute vec3 normal;
+    varying   vec3 vLightDirection;
+    varying   vec3 vNormal;
+    varying   vec2 vTexCoord;
+    uniform   mat3 ntransform;
+    uniform   mat3 scale;
+    uniform   mat4 proj;
+    uniform   mat4 view;
+    uniform   mat4 transform;
+    uniform   vec3 light_position;
+
+    void main() {
+        vec4 world_position = transform * vec4(scale * position, 1.0);
+        gl_Position = proj * view * world_position;
+        vLightDirection = light_position - world_position.xyz;
+        vNormal = ntransform * normal;
+        vTexCoord = tex_coord;
+    }";
+
+/// Fragment shader that blends from the night brightness to full brightness
+/// as the light falls more directly on the surface.
+static FRAGMENT_SRC: &str = "#version 100
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+   precision highp float;
+#else
+   precision mediump float;
+#endif
+
+    varying vec3  vLightDirection;
+    varying vec3  vNormal;
+    varying vec2  vTexCoord;
+    uniform vec3  color;
+    uniform float night_brightness;
+    uniform sampler2D tex;
+
+    void main() {
+        float lambertian = max(dot(normalize(vLightDirection), normalize(vNormal)), 0.0);
+        float brightness = mix(night_brightness, 1.0, lambertian);
+        gl_FragColor = texture2D(tex, vTexCoord) * vec4(brightness * color, 1.0);
+    }";
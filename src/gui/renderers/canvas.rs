@@ -0,0 +1,181 @@
+use nalgebra::{Isometry3, Point3, Unit, UnitQuaternion, Vector3};
+
+use super::marker_renderer::MarkerType;
+use super::orbit_renderer::{ApsisMarker, ColorGradient, LineStyle, OrbitPatch, OrbitRenderer};
+use crate::model::orrery::Rings;
+
+/// Something the scene can be drawn onto, without caring how it actually
+/// gets drawn. All positions are in focus space.
+///
+/// A backend only has to provide the four primitives: lines, spheres, markers
+/// and text. Everything else is built out of those, though backends can
+/// override the rest if they can do better, like [super::CompoundRenderer]
+/// does for orbits.
+pub trait Canvas {
+    fn draw_line(&mut self, a: Point3<f32>, b: Point3<f32>, color: Point3<f32>);
+
+    fn draw_sphere(&mut self, center: Point3<f32>, radius: f32, color: Point3<f32>);
+
+    /// Draws a marker that stays the same size on the screen. `height` is
+    /// relative to the height of the screen.
+    fn draw_marker(
+        &mut self,
+        mtype: MarkerType,
+        center: Point3<f32>,
+        height: f32,
+        color: Point3<f32>,
+    );
+
+    /// Draws some text next to a point, facing the screen. Where labels
+    /// would overlap, ones with lower `rank`s are kept.
+    fn draw_label(&mut self, position: Point3<f32>, text: &str, color: Point3<f32>, rank: u32);
+
+    fn draw_path(&mut self, points: &[Point3<f32>], color: Point3<f32>, closed: bool) {
+        for pts in points.windows(2) {
+            self.draw_line(pts[0], pts[1], color);
+        }
+        if closed && points.len() > 2 {
+            self.draw_line(points[points.len() - 1], points[0], color);
+        }
+    }
+
+    /// Draws a circle around the given center, parallel to the xy-plane.
+    fn draw_flat_circle(&mut self, center: Point3<f32>, radius: f32, color: Point3<f32>) {
+        const NUM_SEGMENTS: usize = 64;
+        let points: Vec<_> = (0..NUM_SEGMENTS)
+            .map(|i| {
+                let theta = (i as f32) / (NUM_SEGMENTS as f32) * std::f32::consts::TAU;
+                center + radius * Vector3::new(theta.cos(), theta.sin(), 0.0)
+            })
+            .collect();
+        self.draw_path(&points, color, true);
+    }
+
+    /// Draws a body's rings around the given center, in the xy-plane of the
+    /// given orientation.
+    fn draw_rings(&mut self, rings: &Rings, center: Point3<f32>, orientation: UnitQuaternion<f32>) {
+        draw_ring_outlines(self, rings, center, orientation)
+    }
+
+    /// Draws the orbit as it'd appear with the given transform from its
+    /// parent's frame to focus space, and returns where its apsides are. The
+    /// transform is in f64, so that big orbits far from the focus don't lose
+    /// precision.
+    fn draw_orbit(
+        &mut self,
+        orbit: OrbitPatch,
+        color: Point3<f32>,
+        transform: Isometry3<f64>,
+    ) -> Vec<ApsisMarker> {
+        draw_orbit_as_path(self, orbit, color, transform)
+    }
+
+    fn draw_grid(&mut self, camera_distance: f32) {
+        const NUM_SQUARES: i32 = 40;
+
+        // We use the camera distance to determine the grid spacing.
+        // If G is the grid subdivision, and R is a corrective factor, if the camera is
+        // between G^k and G^(k+1) away, we draw a bright grid with spacing
+        // R*G^(k+1) and a dimmer one with spacing G^k, getting dimmer as we get
+        // closer to R*G^(k+1).
+        const GRID_SUBDIV: i32 = 10;
+        const GRID_SUBDIV_FLOAT: f32 = GRID_SUBDIV as f32;
+        const CORRECTIVE: f32 = 0.4;
+
+        // Figure out which interval we're in and how far along
+        let log_distance = camera_distance.log(GRID_SUBDIV_FLOAT);
+        let k = camera_distance.log(GRID_SUBDIV_FLOAT).floor();
+        let interp = log_distance - k;
+
+        // Determine square size and color
+        let square_size = CORRECTIVE * GRID_SUBDIV_FLOAT.powf(k);
+        let color = Point3::new(0.5, 0.5, 0.5);
+        let dim_color = color * (1.0 - interp);
+
+        // Draw the squares
+        let max_coord = square_size * (NUM_SQUARES as f32);
+        for i in (-NUM_SQUARES)..(NUM_SQUARES + 1) {
+            let coord = square_size * (i as f32);
+            let color = if i % GRID_SUBDIV == 0 {
+                color
+            } else {
+                dim_color
+            };
+
+            // horizontal
+            self.draw_line(
+                Point3::new(-max_coord, coord, 0.0),
+                Point3::new(max_coord, coord, 0.0),
+                color,
+            );
+            // vertical
+            self.draw_line(
+                Point3::new(coord, -max_coord, 0.0),
+                Point3::new(coord, max_coord, 0.0),
+                color,
+            );
+        }
+    }
+
+    fn draw_axes(
+        &mut self,
+        axes: &[(Unit<Vector3<f32>>, Point3<f32>)],
+        axis_length: f32,
+        transform: Isometry3<f32>,
+    ) {
+        let origin = transform * Point3::origin();
+        for (v, color) in axes {
+            let end_pt = origin + axis_length * (transform * v.into_inner());
+            self.draw_line(origin, end_pt, *color);
+        }
+    }
+}
+
+/// Draws the inner and outer edges of some rings. This is what
+/// [Canvas::draw_rings] does by default.
+pub fn draw_ring_outlines<C: Canvas + ?Sized>(
+    canvas: &mut C,
+    rings: &Rings,
+    center: Point3<f32>,
+    orientation: UnitQuaternion<f32>,
+) {
+    const NUM_SEGMENTS: usize = 64;
+    for radius in [rings.inner_radius, rings.outer_radius] {
+        let points: Vec<_> = (0..NUM_SEGMENTS)
+            .map(|i| {
+                let theta = (i as f32) / (NUM_SEGMENTS as f32) * std::f32::consts::TAU;
+                center + orientation * (radius * Vector3::new(theta.cos(), theta.sin(), 0.0))
+            })
+            .collect();
+        canvas.draw_path(&points, rings.color, true);
+    }
+}
+
+/// Draws an orbit out of line segments. This is what [Canvas::draw_orbit]
+/// does by default, for canvases that override it but still need it sometimes.
+pub fn draw_orbit_as_path<C: Canvas + ?Sized>(
+    canvas: &mut C,
+    orbit: OrbitPatch,
+    color: Point3<f32>,
+    transform: Isometry3<f64>,
+) -> Vec<ApsisMarker> {
+    let total_transform = OrbitRenderer::native_to_focus_space(&orbit, transform);
+    let points: Vec<_> = OrbitRenderer::get_orbit_points(&orbit, total_transform).collect();
+    if orbit.style == LineStyle::Solid && orbit.gradient == ColorGradient::Uniform {
+        canvas.draw_path(&points, color, false);
+        return OrbitRenderer::apsis_markers(&orbit, total_transform);
+    }
+
+    // Otherwise, each segment has to be drawn on its own. Each dash needs at
+    // least a segment on and one off.
+    let colors: Vec<_> = OrbitRenderer::get_orbit_colors(&orbit, color).collect();
+    let num_segments = points.len() - 1;
+    let dashes = usize::min(OrbitRenderer::num_dashes(&orbit), num_segments / 2);
+    for (i, pts) in points.windows(2).enumerate() {
+        let u = (i as f64 + 0.5) / num_segments as f64;
+        if (u * dashes as f64).fract() < 0.5 {
+            canvas.draw_line(pts[0], pts[1], nalgebra::center(&colors[i], &colors[i + 1]));
+        }
+    }
+    OrbitRenderer::apsis_markers(&orbit, total_transform)
+}
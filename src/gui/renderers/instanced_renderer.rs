@@ -0,0 +1,381 @@
+use std::f32::consts::{PI, TAU};
+
+use glow::HasContext;
+use kiss3d::camera::Camera;
+use kiss3d::context::Context;
+use kiss3d::renderer::Renderer;
+use nalgebra::{Point3, Vector3};
+
+use super::sphere_renderer::{
+    unit_sphere_triangles, EDGE_OPACITY, FACING_OPACITY, NUM_MERIDIANS, NUM_PARALLELS, NUM_SLICES,
+    NUM_STACKS,
+};
+
+/// Draws spheres and lines in batches, instead of one draw call per sphere or
+/// per line. Spheres look the same as with [super::SphereRenderer], but all
+/// of them are drawn in two instanced draw calls, one per side, and every
+/// line in the frame goes into one vertex buffer.
+///
+/// kiss3d doesn't expose instancing, so this talks to OpenGL directly,
+/// through the same context kiss3d uses.
+pub struct InstancedRenderer {
+    sphere_program: glow::Program,
+    sphere_transform: Option<glow::UniformLocation>,
+    sphere_eye: Option<glow::UniformLocation>,
+    sphere_opacity: Option<glow::UniformLocation>,
+    sphere_position: u32,
+    sphere_instance: u32,
+    sphere_color: u32,
+    // Triangles making up a unit sphere, shared by every instance
+    sphere_vertices: glow::Buffer,
+    num_sphere_vertices: i32,
+    // Per-instance data: center and radius, then color
+    instance_buffer: glow::Buffer,
+
+    line_program: glow::Program,
+    line_transform: Option<glow::UniformLocation>,
+    line_position: u32,
+    line_color: u32,
+    line_buffer: glow::Buffer,
+
+    wireframe: bool,
+    // Data storage
+    spheres: Vec<(Point3<f32>, f32, Point3<f32>)>,
+    // Position and color of each end of each line
+    lines: Vec<[f32; 6]>,
+}
+
+impl InstancedRenderer {
+    pub fn new() -> Self {
+        let gl = &Context::get().ctxt.context;
+
+        let sphere_program = link_program(gl, SPHERE_VERTEX_SRC, SPHERE_FRAGMENT_SRC);
+        let line_program = link_program(gl, LINE_VERTEX_SRC, LINE_FRAGMENT_SRC);
+        let attrib = |program, name| unsafe {
+            gl.get_attrib_location(program, name)
+                .expect("Failed to get shader attribute.")
+        };
+
+        let triangles: Vec<f32> = unit_sphere_triangles()
+            .iter()
+            .flat_map(|p| [p.x, p.y, p.z])
+            .collect();
+        let (sphere_vertices, instance_buffer, line_buffer) = unsafe {
+            let sphere_vertices = gl.create_buffer().expect("Failed to create buffer.");
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(sphere_vertices));
+            gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, as_bytes(&triangles), glow::STATIC_DRAW);
+            gl.bind_buffer(glow::ARRAY_BUFFER, None);
+            (
+                sphere_vertices,
+                gl.create_buffer().expect("Failed to create buffer."),
+                gl.create_buffer().expect("Failed to create buffer."),
+            )
+        };
+
+        unsafe {
+            InstancedRenderer {
+                sphere_transform: gl.get_uniform_location(sphere_program, "transform"),
+                sphere_eye: gl.get_uniform_location(sphere_program, "eye"),
+                sphere_opacity: gl.get_uniform_location(sphere_program, "opacity"),
+                sphere_position: attrib(sphere_program, "position"),
+                sphere_instance: attrib(sphere_program, "instance"),
+                sphere_color: attrib(sphere_program, "instanceColor"),
+                sphere_program,
+                sphere_vertices,
+                num_sphere_vertices: (triangles.len() / 3) as i32,
+                instance_buffer,
+                line_transform: gl.get_uniform_location(line_program, "transform"),
+                line_position: attrib(line_program, "position"),
+                line_color: attrib(line_program, "color"),
+                line_program,
+                line_buffer,
+                wireframe: false,
+                spheres: vec![],
+                lines: vec![],
+            }
+        }
+    }
+
+    /// Sets whether spheres get lines of latitude and longitude, as well as
+    /// their shell.
+    pub fn set_wireframe(&mut self, wireframe: bool) {
+        self.wireframe = wireframe;
+    }
+
+    pub fn add_sphere(&mut self, center: Point3<f32>, radius: f32, color: Point3<f32>) {
+        self.spheres.push((center, radius, color));
+    }
+
+    pub fn add_line(&mut self, a: Point3<f32>, b: Point3<f32>, color: Point3<f32>) {
+        self.lines.push([a.x, a.y, a.z, color.x, color.y, color.z]);
+        self.lines.push([b.x, b.y, b.z, color.x, color.y, color.z]);
+    }
+
+    fn add_wireframe(&mut self, center: Point3<f32>, radius: f32, color: Point3<f32>) {
+        let point = |theta: f32, phi: f32| {
+            let direction = Vector3::new(
+                theta.sin() * phi.cos(),
+                theta.sin() * phi.sin(),
+                theta.cos(),
+            );
+            center + radius * direction
+        };
+
+        for i in 0..NUM_MERIDIANS {
+            let phi = TAU * i as f32 / NUM_MERIDIANS as f32;
+            for j in 0..NUM_STACKS {
+                let theta = |j: usize| PI * j as f32 / NUM_STACKS as f32;
+                self.add_line(point(theta(j), phi), point(theta(j + 1), phi), color);
+            }
+        }
+        for i in 1..=NUM_PARALLELS {
+            let theta = PI * i as f32 / (NUM_PARALLELS + 1) as f32;
+            for j in 0..NUM_SLICES {
+                let phi = |j: usize| TAU * j as f32 / NUM_SLICES as f32;
+                self.add_line(point(theta, phi(j)), point(theta, phi(j + 1)), color);
+            }
+        }
+    }
+
+    /// Draws the lines added this frame, including sphere wireframes. Like
+    /// kiss3d's line renderer, these are opaque, so they should go before
+    /// anything translucent.
+    pub fn render_lines(&mut self, camera: &dyn Camera) {
+        if self.wireframe {
+            let spheres = self.spheres.clone();
+            for (center, radius, color) in spheres {
+                self.add_wireframe(center, radius, color);
+            }
+        }
+        if self.lines.is_empty() {
+            return;
+        }
+
+        let gl = Context::get().ctxt.context;
+        let data: Vec<f32> = self.lines.drain(..).flatten().collect();
+        let transform = camera.transformation();
+        unsafe {
+            gl.use_program(Some(self.line_program));
+            gl.uniform_matrix_4_f32_slice(
+                self.line_transform.as_ref(),
+                false,
+                transform.as_slice(),
+            );
+
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.line_buffer));
+            gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, as_bytes(&data), glow::STREAM_DRAW);
+            gl.enable_vertex_attrib_array(self.line_position);
+            gl.enable_vertex_attrib_array(self.line_color);
+            gl.vertex_attrib_pointer_f32(self.line_position, 3, glow::FLOAT, false, 24, 0);
+            gl.vertex_attrib_pointer_f32(self.line_color, 3, glow::FLOAT, false, 24, 12);
+
+            gl.draw_arrays(glow::LINES, 0, (data.len() / 6) as i32);
+
+            // kiss3d shares one vertex array between all its renderers, so
+            // leave it the way we found it
+            gl.disable_vertex_attrib_array(self.line_position);
+            gl.disable_vertex_attrib_array(self.line_color);
+            gl.bind_buffer(glow::ARRAY_BUFFER, None);
+        }
+    }
+
+    /// Draws the spheres added this frame.
+    pub fn render_spheres(&mut self, camera: &dyn Camera) {
+        if self.spheres.is_empty() {
+            return;
+        }
+
+        // Translucent things have to be drawn back to front. Instances are
+        // drawn in order, so sorting them is enough.
+        let eye = camera.eye();
+        self.spheres.sort_by(|a, b| {
+            let dist_a = (a.0 - eye).norm();
+            let dist_b = (b.0 - eye).norm();
+            dist_b.total_cmp(&dist_a)
+        });
+        let instances: Vec<f32> = self
+            .spheres
+            .drain(..)
+            .flat_map(|(c, r, color)| [c.x, c.y, c.z, r, color.x, color.y, color.z])
+            .collect();
+        let num_instances = (instances.len() / 7) as i32;
+
+        let gl = Context::get().ctxt.context;
+
+        let transform = camera.transformation();
+        unsafe {
+            gl.use_program(Some(self.sphere_program));
+            gl.uniform_matrix_4_f32_slice(
+                self.sphere_transform.as_ref(),
+                false,
+                transform.as_slice(),
+            );
+            gl.uniform_3_f32(self.sphere_eye.as_ref(), eye.x, eye.y, eye.z);
+
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.sphere_vertices));
+            gl.enable_vertex_attrib_array(self.sphere_position);
+            gl.vertex_attrib_pointer_f32(self.sphere_position, 3, glow::FLOAT, false, 0, 0);
+
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.instance_buffer));
+            gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, as_bytes(&instances), glow::STREAM_DRAW);
+            gl.enable_vertex_attrib_array(self.sphere_instance);
+            gl.enable_vertex_attrib_array(self.sphere_color);
+            gl.vertex_attrib_pointer_f32(self.sphere_instance, 4, glow::FLOAT, false, 28, 0);
+            gl.vertex_attrib_pointer_f32(self.sphere_color, 3, glow::FLOAT, false, 28, 16);
+            gl.vertex_attrib_divisor(self.sphere_instance, 1);
+            gl.vertex_attrib_divisor(self.sphere_color, 1);
+
+            gl.enable(glow::BLEND);
+            gl.blend_func_separate(
+                glow::SRC_ALPHA,
+                glow::ONE_MINUS_SRC_ALPHA,
+                glow::ONE,
+                glow::ONE_MINUS_SRC_ALPHA,
+            );
+            // Faces are picked in the fragment shader instead. Unlike the
+            // one-at-a-time renderer, all the far sides go before all the near
+            // sides, which only matters where spheres overlap.
+            gl.disable(glow::CULL_FACE);
+            for side in [-1.0, 1.0] {
+                gl.uniform_3_f32(
+                    self.sphere_opacity.as_ref(),
+                    FACING_OPACITY,
+                    EDGE_OPACITY,
+                    side,
+                );
+                gl.draw_arrays_instanced(
+                    glow::TRIANGLES,
+                    0,
+                    self.num_sphere_vertices,
+                    num_instances,
+                );
+            }
+            gl.enable(glow::CULL_FACE);
+            gl.cull_face(glow::BACK);
+            gl.disable(glow::BLEND);
+
+            // Same as for lines, and kiss3d doesn't know about divisors at all
+            gl.vertex_attrib_divisor(self.sphere_instance, 0);
+            gl.vertex_attrib_divisor(self.sphere_color, 0);
+            gl.disable_vertex_attrib_array(self.sphere_position);
+            gl.disable_vertex_attrib_array(self.sphere_instance);
+            gl.disable_vertex_attrib_array(self.sphere_color);
+            gl.bind_buffer(glow::ARRAY_BUFFER, None);
+        }
+    }
+}
+
+impl Renderer for InstancedRenderer {
+    fn render(&mut self, _pass: usize, camera: &mut dyn Camera) {
+        self.render_lines(camera);
+        self.render_spheres(camera);
+    }
+}
+
+fn link_program(gl: &glow::Context, vertex_src: &str, fragment_src: &str) -> glow::Program {
+    unsafe {
+        let program = gl.create_program().expect("Failed to create program.");
+        let mut shaders = vec![];
+        for (kind, src) in [
+            (glow::VERTEX_SHADER, vertex_src),
+            (glow::FRAGMENT_SHADER, fragment_src),
+        ] {
+            let shader = gl.create_shader(kind).expect("Failed to create shader.");
+            gl.shader_source(shader, src);
+            gl.compile_shader(shader);
+            if !gl.get_shader_compile_status(shader) {
+                panic!(
+                    "Shader compilation failed: {}",
+                    gl.get_shader_info_log(shader)
+                );
+            }
+            gl.attach_shader(program, shader);
+            shaders.push(shader);
+        }
+        gl.link_program(program);
+        if !gl.get_program_link_status(program) {
+            panic!(
+                "Shader linking failed: {}",
+                gl.get_program_info_log(program)
+            );
+        }
+        for shader in shaders {
+            gl.detach_shader(program, shader);
+            gl.delete_shader(shader);
+        }
+        program
+    }
+}
+
+fn as_bytes(data: &[f32]) -> &[u8] {
+    // SAFETY: f32 has no padding or invalid bit patterns, and u8 has no
+    // alignment requirements
+    unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data)) }
+}
+
+/// Vertex shader that scales and moves the unit sphere into place, once per
+/// instance.
+///
+/// `instance` is the center and radius, and `vEdge` is the same as for
+/// [super::SphereRenderer], but worked out in world space, since kiss3d only
+/// gives us the combined transform.
+static SPHERE_VERTEX_SRC: &str = "#version 100
+    attribute vec3 position;
+    attribute vec4 instance;
+    attribute vec3 instanceColor;
+    varying   float vEdge;
+    varying   vec3 vColor;
+    uniform   mat4 transform;
+    uniform   vec3 eye;
+
+    void main() {
+        vec3 world = instance.xyz + instance.w * position;
+        gl_Position = transform * vec4(world, 1.0);
+        vEdge = 1.0 - abs(dot(position, normalize(world - eye)));
+        vColor = instanceColor;
+    }";
+
+/// Fragment shader that only draws one side of the sphere at a time. See
+/// [super::SphereRenderer] for what `opacity` means.
+static SPHERE_FRAGMENT_SRC: &str = "#version 100
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+   precision highp float;
+#else
+   precision mediump float;
+#endif
+
+    varying float vEdge;
+    varying vec3  vColor;
+    uniform vec3  opacity;
+
+    void main() {
+        if (gl_FrontFacing != (opacity.z > 0.0)) {
+            discard;
+        }
+        float alpha = mix(opacity.x, opacity.y, vEdge * vEdge);
+        gl_FragColor = vec4(vColor, alpha);
+    }";
+
+static LINE_VERTEX_SRC: &str = "#version 100
+    attribute vec3 position;
+    attribute vec3 color;
+    varying   vec3 vColor;
+    uniform   mat4 transform;
+
+    void main() {
+        gl_Position = transform * vec4(position, 1.0);
+        vColor = color;
+    }";
+
+static LINE_FRAGMENT_SRC: &str = "#version 100
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+   precision highp float;
+#else
+   precision mediump float;
+#endif
+
+    varying vec3 vColor;
+
+    void main() {
+        gl_FragColor = vec4(vColor, 1.0);
+    }";
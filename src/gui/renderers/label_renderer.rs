@@ -0,0 +1,142 @@
+use kiss3d::text::Font;
+use kiss3d::window::Window;
+use nalgebra::{Point2, Point3, Vector2};
+
+use crate::gui::panels::TEXT_COORDS_PER_PIXEL;
+
+// Font size, in window pixels, at a UI scale of 1
+const FONT_SIZE: f32 = 20.0;
+// Rough width of a character, relative to the font size
+const CHAR_WIDTH: f32 = 0.55;
+// How far the label is from the point it names, in window pixels
+const LABEL_OFFSET: Vector2<f32> = Vector2::new(8.0, 8.0);
+// Labels are fully bright until they're this many times further from the
+// camera than the camera is from the focus, and then fade out until they're
+// FADE_END times further.
+const FADE_START: f32 = 20.0;
+const FADE_END: f32 = 2000.0;
+
+/// A label to be drawn next to a point in focus space.
+struct Label {
+    position: Point3<f32>,
+    text: String,
+    color: Point3<f32>,
+    // Lower ranks get first pick of where to go
+    rank: u32,
+}
+
+/// Where a label ended up on the screen, in window pixels.
+struct Placement {
+    top_left: Point2<f32>,
+    size: Vector2<f32>,
+}
+
+impl Placement {
+    fn overlaps(&self, other: &Placement) -> bool {
+        let (a_min, a_max) = (self.top_left, self.top_left + self.size);
+        let (b_min, b_max) = (other.top_left, other.top_left + other.size);
+        a_min.x < b_max.x && b_min.x < a_max.x && a_min.y < b_max.y && b_min.y < a_max.y
+    }
+}
+
+/// Draws names next to points in space, always facing the screen. Labels
+/// dim as they get further from the camera, and ones that would overlap a
+/// more important label are nudged out of the way, or dropped if there's no
+/// room. Labels are ranked by the caller, and then by how close they are.
+///
+/// Text can only be drawn through the window, so unlike the other renderers,
+/// this one doesn't draw in [kiss3d::renderer::Renderer::render]; instead it
+/// draws once per frame in [LabelRenderer::draw].
+pub struct LabelRenderer {
+    labels: Vec<Label>,
+    // How much bigger than usual labels are
+    scale: f32,
+}
+
+impl LabelRenderer {
+    pub fn new() -> Self {
+        LabelRenderer {
+            labels: vec![],
+            scale: 1.0,
+        }
+    }
+
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale;
+    }
+
+    pub fn add_label(&mut self, position: Point3<f32>, text: &str, color: Point3<f32>, rank: u32) {
+        self.labels.push(Label {
+            position,
+            text: text.to_owned(),
+            color,
+            rank,
+        });
+    }
+
+    /// Draws all the labels added since the last call, and forgets them.
+    ///
+    /// `project` returns where a point in focus space is in the window, with
+    /// the origin at the top-left, and how far it is from the camera, or
+    /// `None` if the camera can't see it. `focus_distance` is how far the
+    /// camera is from the focus, which sets the scale for fading.
+    pub fn draw(
+        &mut self,
+        window: &mut Window,
+        project: impl Fn(&Point3<f32>) -> Option<(Point2<f32>, f32)>,
+        focus_distance: f32,
+    ) {
+        let mut projected: Vec<_> = self
+            .labels
+            .drain(..)
+            .filter_map(|label| {
+                let (screen_pt, distance) = project(&label.position)?;
+                let brightness = fade(distance / focus_distance);
+                (brightness > 0.0).then_some((label, screen_pt, distance, brightness))
+            })
+            .collect();
+        // More important labels get first pick of where to go, then closer
+        // ones
+        projected.sort_by(|a, b| a.0.rank.cmp(&b.0.rank).then(a.2.total_cmp(&b.2)));
+
+        let font = Font::default();
+        let font_size = FONT_SIZE * self.scale;
+        let offset = LABEL_OFFSET * self.scale;
+        let mut placed: Vec<Placement> = vec![];
+        for (label, screen_pt, _, brightness) in projected {
+            let size = Vector2::new(
+                CHAR_WIDTH * font_size * label.text.chars().count() as f32,
+                font_size,
+            );
+            // Try below-right of the point first, then above-right
+            let candidates = [
+                screen_pt + offset,
+                screen_pt + Vector2::new(offset.x, -offset.y - font_size),
+            ];
+            let placement = candidates
+                .into_iter()
+                .map(|top_left| Placement { top_left, size })
+                .find(|p| !placed.iter().any(|other| p.overlaps(other)));
+            let placement = match placement {
+                Some(placement) => placement,
+                None => continue,
+            };
+
+            window.draw_text(
+                &label.text,
+                &Point2::from(placement.top_left.coords * TEXT_COORDS_PER_PIXEL),
+                TEXT_COORDS_PER_PIXEL * font_size,
+                &font,
+                &(label.color * brightness),
+            );
+            placed.push(placement);
+        }
+    }
+}
+
+/// Returns how bright a label should be, given how many times further it is
+/// from the camera than the focus is. Fades evenly in log space.
+fn fade(relative_distance: f32) -> f32 {
+    let t = (relative_distance / FADE_START).ln() / (FADE_END / FADE_START).ln();
+    1.0 - t.clamp(0.0, 1.0)
+}
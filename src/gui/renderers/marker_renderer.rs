@@ -4,7 +4,7 @@ use kiss3d::renderer::Renderer;
 use kiss3d::resource::{
     AllocationType, BufferType, Effect, GPUVec, ShaderAttribute, ShaderUniform,
 };
-use nalgebra::Point3;
+use nalgebra::{Point2, Point3};
 
 // TODO: would it be a better idea to render a rectangle, and then use various
 // fragment shaders to draw circles / textures, etc?
@@ -12,6 +12,82 @@ use nalgebra::Point3;
 pub enum MarkerType {
     Square,
     Circle,
+    /// Points up the screen
+    TriangleUp,
+    /// Points down the screen
+    TriangleDown,
+    Diamond,
+    /// Hollow square, rotated 45 degrees
+    HollowDiamond,
+    /// Four-pointed star, for burns
+    Star,
+    /// Hollow circle, for SOI changes
+    Ring,
+    /// An X, for impacts
+    Cross,
+}
+
+impl MarkerType {
+    /// Returns the outline of the marker, going around it, at the same size
+    /// that it's drawn at.
+    pub fn outline(&self) -> Vec<Point2<f32>> {
+        use std::f32::consts::{FRAC_1_SQRT_2, TAU};
+
+        match self {
+            MarkerType::Square => vec![
+                Point2::new(-1.0, 1.0),
+                Point2::new(-1.0, -1.0),
+                Point2::new(1.0, -1.0),
+                Point2::new(1.0, 1.0),
+            ],
+            MarkerType::Circle | MarkerType::Ring => (0..16)
+                .map(|i| {
+                    let theta = (i as f32) / 16.0 * TAU;
+                    Point2::new(theta.cos(), theta.sin())
+                })
+                .collect(),
+            MarkerType::TriangleUp => vec![
+                Point2::new(0.0, 1.0),
+                Point2::new(-1.0, -1.0),
+                Point2::new(1.0, -1.0),
+            ],
+            MarkerType::TriangleDown => vec![
+                Point2::new(0.0, -1.0),
+                Point2::new(1.0, 1.0),
+                Point2::new(-1.0, 1.0),
+            ],
+            MarkerType::Diamond | MarkerType::HollowDiamond => vec![
+                Point2::new(0.0, 1.0),
+                Point2::new(-1.0, 0.0),
+                Point2::new(0.0, -1.0),
+                Point2::new(1.0, 0.0),
+            ],
+            MarkerType::Star => (0..8)
+                .map(|i| {
+                    // Alternate between the points and the corners between them
+                    let theta = (i as f32) / 8.0 * TAU;
+                    let r = if i % 2 == 0 { 1.0 } else { 0.3 };
+                    Point2::new(r * theta.cos(), r * theta.sin())
+                })
+                .collect(),
+            MarkerType::Cross => {
+                // Each arm is a diagonal bar; between arms, the outline dips
+                // in toward the center
+                let (s, w) = (FRAC_1_SQRT_2, 0.25 * FRAC_1_SQRT_2);
+                let arm = [
+                    Point2::new(s + w, s - w),
+                    Point2::new(s - w, s + w),
+                    Point2::new(0.0, 2.0 * w),
+                ];
+                (0..4)
+                    .flat_map(|i| {
+                        let (sin, cos) = (i as f32 * TAU / 4.0).sin_cos();
+                        arm.map(|p| Point2::new(cos * p.x - sin * p.y, sin * p.x + cos * p.y))
+                    })
+                    .collect()
+            }
+        }
+    }
 }
 
 /// Represents a marker to be drawn on-screen.
@@ -103,6 +179,76 @@ impl MarkerRenderer {
         let dr = Point3::new(1.0, -1.0, 0.0);
         vec![ul, dr, ur, ul, dl, dr]
     }
+
+    fn gen_triangle_marker_triangles(pointing_up: bool) -> Vec<Point3<f32>> {
+        // Oriented CCW either way
+        let sign = if pointing_up { 1.0 } else { -1.0 };
+        let tip = Point3::new(0.0, sign, 0.0);
+        let base1 = Point3::new(-sign, -sign, 0.0);
+        let base2 = Point3::new(sign, -sign, 0.0);
+        vec![tip, base1, base2]
+    }
+
+    fn gen_diamond_marker_triangles() -> Vec<Point3<f32>> {
+        // two triangles, oriented CCW
+        let up = Point3::new(0.0, 1.0, 0.0);
+        let down = Point3::new(0.0, -1.0, 0.0);
+        let left = Point3::new(-1.0, 0.0, 0.0);
+        let right = Point3::new(1.0, 0.0, 0.0);
+        vec![up, left, down, up, down, right]
+    }
+
+    /// Fills in an outline that every point of can be seen from the center,
+    /// like a star, with a fan of triangles.
+    fn gen_fan_triangles(outline: &[Point2<f32>]) -> Vec<Point3<f32>> {
+        (0..outline.len())
+            .flat_map(|i| {
+                let a = outline[i];
+                let b = outline[(i + 1) % outline.len()];
+                [
+                    Point3::origin(),
+                    Point3::new(a.x, a.y, 0.0),
+                    Point3::new(b.x, b.y, 0.0),
+                ]
+            })
+            .collect()
+    }
+
+    fn gen_ring_marker_triangles(n: usize, thickness: f32) -> Vec<Point3<f32>> {
+        use std::f32::consts::TAU;
+
+        // Like the hollow diamond, a quad between the outer and inner circles
+        // for each segment
+        let outer: Vec<_> = (0..n)
+            .map(|i| {
+                let theta = (i as f32) / (n as f32) * TAU;
+                Point3::new(theta.cos(), theta.sin(), 0.0)
+            })
+            .collect();
+        let inner: Vec<_> = outer
+            .iter()
+            .map(|p| Point3::from(p.coords * (1.0 - thickness)))
+            .collect();
+        (0..n)
+            .flat_map(|i| {
+                let j = (i + 1) % n;
+                [outer[i], outer[j], inner[j], outer[i], inner[j], inner[i]]
+            })
+            .collect()
+    }
+
+    fn gen_hollow_diamond_marker_triangles(thickness: f32) -> Vec<Point3<f32>> {
+        // Each side of the diamond is a quad between the outer and inner outlines
+        let outer =
+            [(0.0, 1.0), (-1.0, 0.0), (0.0, -1.0), (1.0, 0.0)].map(|(x, y)| Point3::new(x, y, 0.0));
+        let inner = outer.map(|p| Point3::from(p.coords * (1.0 - thickness)));
+        (0..4)
+            .flat_map(|i| {
+                let j = (i + 1) % 4;
+                [outer[i], outer[j], inner[j], outer[i], inner[j], inner[i]]
+            })
+            .collect()
+    }
 }
 
 impl Renderer for MarkerRenderer {
@@ -122,6 +268,41 @@ impl Renderer for MarkerRenderer {
             BufferType::Array,
             AllocationType::StaticDraw,
         );
+        let mut up_triangles = GPUVec::new(
+            Self::gen_triangle_marker_triangles(true),
+            BufferType::Array,
+            AllocationType::StaticDraw,
+        );
+        let mut down_triangles = GPUVec::new(
+            Self::gen_triangle_marker_triangles(false),
+            BufferType::Array,
+            AllocationType::StaticDraw,
+        );
+        let mut diamond_triangles = GPUVec::new(
+            Self::gen_diamond_marker_triangles(),
+            BufferType::Array,
+            AllocationType::StaticDraw,
+        );
+        let mut hollow_diamond_triangles = GPUVec::new(
+            Self::gen_hollow_diamond_marker_triangles(0.4),
+            BufferType::Array,
+            AllocationType::StaticDraw,
+        );
+        let mut star_triangles = GPUVec::new(
+            Self::gen_fan_triangles(&MarkerType::Star.outline()),
+            BufferType::Array,
+            AllocationType::StaticDraw,
+        );
+        let mut ring_triangles = GPUVec::new(
+            Self::gen_ring_marker_triangles(16, 0.35),
+            BufferType::Array,
+            AllocationType::StaticDraw,
+        );
+        let mut cross_triangles = GPUVec::new(
+            Self::gen_fan_triangles(&MarkerType::Cross.outline()),
+            BufferType::Array,
+            AllocationType::StaticDraw,
+        );
 
         // Deduce the aspect ratio of the window -- it's the inverse of the aspect ratio
         // caused by the camera
@@ -145,16 +326,25 @@ impl Renderer for MarkerRenderer {
             let center = vp_transform * marker.center.to_homogeneous();
             let center = Point3::from(center.xyz() / center.w);
 
-            self.offset.bind(match marker.mtype {
+            let triangles = match marker.mtype {
                 MarkerType::Square => &mut square_triangles,
                 MarkerType::Circle => &mut circle_triangles,
-            });
+                MarkerType::TriangleUp => &mut up_triangles,
+                MarkerType::TriangleDown => &mut down_triangles,
+                MarkerType::Diamond => &mut diamond_triangles,
+                MarkerType::HollowDiamond => &mut hollow_diamond_triangles,
+                MarkerType::Star => &mut star_triangles,
+                MarkerType::Ring => &mut ring_triangles,
+                MarkerType::Cross => &mut cross_triangles,
+            };
+            let num_vertices = triangles.len() as i32;
+            self.offset.bind(triangles);
             self.center.upload(&center);
             self.height.upload(&marker.height);
             self.color.upload(&marker.color);
 
             let ctxt = Context::get();
-            ctxt.draw_arrays(Context::TRIANGLES, 0, circle_triangles.len() as i32);
+            ctxt.draw_arrays(Context::TRIANGLES, 0, num_vertices);
         }
 
         self.offset.disable();
@@ -1,121 +1,297 @@
 use kiss3d::camera::Camera;
 use kiss3d::renderer::{LineRenderer, Renderer};
-use nalgebra::{Isometry3, Point3, Unit, Vector3};
+use kiss3d::window::Window;
+use nalgebra::{Isometry3, Point2, Point3, UnitQuaternion, Vector3};
 
+use self::canvas::{draw_orbit_as_path, draw_ring_outlines};
+use self::instanced_renderer::InstancedRenderer;
+use self::label_renderer::LabelRenderer;
 use self::marker_renderer::MarkerRenderer;
-use self::orbit_renderer::OrbitRenderer;
+use self::ring_renderer::RingRenderer;
 use self::sphere_renderer::SphereRenderer;
+use self::star_renderer::StarRenderer;
 
+mod body_material;
+mod canvas;
+mod instanced_renderer;
+mod label_renderer;
 mod marker_renderer;
 mod orbit_renderer;
+mod ring_renderer;
 mod sphere_renderer;
+mod star_renderer;
 mod utils;
 
+pub use body_material::BodyMaterial;
+pub use canvas::Canvas;
 pub use marker_renderer::MarkerType;
-pub use orbit_renderer::OrbitPatch;
+pub use orbit_renderer::{
+    ApsisKind, ApsisMarker, ColorGradient, LineStyle, OrbitPatch, OrbitRenderer,
+};
+
+use crate::model::orrery::Rings;
+
+/// How far past the focus it takes for fog to dim things to about a third,
+/// in multiples of the camera's distance from the focus.
+const FOG_SCALE: f32 = 2.0;
+/// Fog never dims things more than this, so that far off orbits are still
+/// faintly there.
+const FOG_MIN_BRIGHTNESS: f32 = 0.1;
+/// How many pieces long lines are broken into at most, so that they fade
+/// along their length.
+const FOG_MAX_LINE_PIECES: usize = 32;
+
+/// Dims things the further they are past the focus, so that what's at the
+/// scale of the current zoom stands out from everything much further away.
+#[derive(Debug, Clone, Copy)]
+pub struct Fog {
+    /// Where the camera is, in focus space
+    pub eye: Point3<f32>,
+    /// How far the camera is from the focus
+    pub focus_distance: f32,
+}
+
+impl Fog {
+    /// How bright something at the given point is, from 1 at the focus or
+    /// closer, falling off exponentially beyond it. The orbit shader does the
+    /// same thing.
+    pub fn brightness(&self, point: &Point3<f32>) -> f32 {
+        fog_brightness((point - self.eye).norm(), self.focus_distance)
+    }
+}
+
+fn fog_brightness(distance: f32, focus_distance: f32) -> f32 {
+    let beyond = f32::max(distance - focus_distance, 0.0);
+    f32::max(
+        (-beyond / (FOG_SCALE * focus_distance)).exp(),
+        FOG_MIN_BRIGHTNESS,
+    )
+}
 
 pub struct CompoundRenderer {
+    star_renderer: StarRenderer,
     sphere_renderer: SphereRenderer,
+    ring_renderer: RingRenderer,
     orbit_renderer: OrbitRenderer,
     line_renderer: LineRenderer,
     marker_renderer: MarkerRenderer,
+    label_renderer: LabelRenderer,
+    // If present, spheres and lines are batched and drawn by this instead
+    instanced_renderer: Option<InstancedRenderer>,
+    wireframe: bool,
+    // In map mode, everything is flattened onto the xy-plane, and drawn as
+    // planar lines instead. This is how far it is from the center of the
+    // window to the top edge, in meters, which sets the size of markers.
+    map_half_height: Option<f32>,
+    planar_lines: Vec<(Point2<f32>, Point2<f32>, Point3<f32>)>,
+    // Ignored in map mode, where everything's as far away as everything else
+    fog: Option<Fog>,
 }
 
 impl CompoundRenderer {
     pub fn new() -> Self {
         CompoundRenderer {
+            star_renderer: StarRenderer::new(),
             sphere_renderer: SphereRenderer::new(),
+            ring_renderer: RingRenderer::new(),
             orbit_renderer: OrbitRenderer::new(),
             line_renderer: LineRenderer::new(),
             marker_renderer: MarkerRenderer::new(),
+            label_renderer: LabelRenderer::new(),
+            instanced_renderer: None,
+            wireframe: false,
+            map_half_height: None,
+            planar_lines: vec![],
+            fog: None,
+        }
+    }
+
+    /// Turns fog on or off. It has to be set again whenever the camera moves.
+    pub fn set_fog(&mut self, fog: Option<Fog>) {
+        self.fog = fog;
+        self.orbit_renderer
+            .set_fog_distance(fog.map(|f| f.focus_distance));
+    }
+
+    /// The fog, unless it's off or this is map mode.
+    fn active_fog(&self) -> Option<Fog> {
+        self.fog.filter(|_| self.map_half_height.is_none())
+    }
+
+    /// Switches to or from map mode. See [CompoundRenderer::draw_planar_lines].
+    pub fn set_map_mode(&mut self, half_height: Option<f32>) {
+        self.map_half_height = half_height;
+    }
+
+    /// Sets the rotation from the root frame to focus space, which keeps the
+    /// background stars fixed relative to the root frame.
+    pub fn set_sky_orientation(&mut self, orientation: UnitQuaternion<f32>) {
+        self.star_renderer.set_orientation(orientation);
+    }
+
+    /// Sets how much bigger than usual labels are drawn.
+    pub fn set_ui_scale(&mut self, scale: f32) {
+        self.label_renderer.set_scale(scale);
+    }
+
+    /// Sets whether spheres are drawn with lines of latitude and longitude on
+    /// their shells. Doesn't affect map mode, where they're just circles.
+    pub fn set_sphere_wireframe(&mut self, wireframe: bool) {
+        self.wireframe = wireframe;
+        self.sphere_renderer.set_wireframe(wireframe);
+        if let Some(renderer) = self.instanced_renderer.as_mut() {
+            renderer.set_wireframe(wireframe);
+        }
+    }
+
+    /// Sets whether spheres and lines are drawn in batches by an
+    /// [InstancedRenderer], instead of one draw call at a time by kiss3d's
+    /// renderers. It only changes how many draw calls there are, except for
+    /// where translucent spheres overlap.
+    pub fn set_instanced(&mut self, instanced: bool) {
+        if !instanced {
+            self.instanced_renderer = None;
+        } else if self.instanced_renderer.is_none() {
+            let mut renderer = InstancedRenderer::new();
+            renderer.set_wireframe(self.wireframe);
+            self.instanced_renderer = Some(renderer);
+        }
+    }
+
+    fn add_line(&mut self, a: Point3<f32>, b: Point3<f32>, color: Point3<f32>) {
+        match self.instanced_renderer.as_mut() {
+            Some(renderer) => renderer.add_line(a, b, color),
+            None => self.line_renderer.draw_line(a, b, color),
         }
     }
 
-    pub fn draw_grid(&mut self, camera_distance: f32) {
-        const NUM_SQUARES: i32 = 40;
-
-        // We use the camera distance to determine the grid spacing.
-        // If G is the grid subdivision, and R is a corrective factor, if the camera is
-        // between G^k and G^(k+1) away, we draw a bright grid with spacing
-        // R*G^(k+1) and a dimmer one with spacing G^k, getting dimmer as we get
-        // closer to R*G^(k+1).
-        const GRID_SUBDIV: i32 = 10;
-        const GRID_SUBDIV_FLOAT: f32 = GRID_SUBDIV as f32;
-        const CORRECTIVE: f32 = 0.4;
-
-        // Figure out which interval we're in and how far along
-        let log_distance = camera_distance.log(GRID_SUBDIV_FLOAT);
-        let k = camera_distance.log(GRID_SUBDIV_FLOAT).floor();
-        let interp = log_distance - k;
-
-        // Determine square size and color
-        let square_size = CORRECTIVE * GRID_SUBDIV_FLOAT.powf(k);
-        let color = Point3::new(0.5, 0.5, 0.5);
-        let dim_color = color * (1.0 - interp);
-
-        // Draw the squares
-        let max_coord = square_size * (NUM_SQUARES as f32);
-        for i in (-NUM_SQUARES)..(NUM_SQUARES + 1) {
-            let coord = square_size * (i as f32);
-            let color = if i % GRID_SUBDIV == 0 {
-                color
-            } else {
-                dim_color
-            };
-
-            // horizontal
-            self.line_renderer.draw_line(
-                Point3::new(-max_coord, coord, 0.0),
-                Point3::new(max_coord, coord, 0.0),
-                color,
-            );
-            // vertical
-            self.line_renderer.draw_line(
-                Point3::new(coord, -max_coord, 0.0),
-                Point3::new(coord, max_coord, 0.0),
-                color,
-            );
+    /// Draws everything that was flattened in map mode onto the window's
+    /// planar scene. This has to be called every frame, since kiss3d only
+    /// keeps planar lines for one frame.
+    pub fn draw_planar_lines(&mut self, window: &mut Window) {
+        for (a, b, color) in self.planar_lines.drain(..) {
+            window.draw_planar_line(&a, &b, &color);
         }
     }
 
-    pub fn draw_axes(
+    /// Draws the labels added this frame onto the window. Like
+    /// [CompoundRenderer::draw_planar_lines], this has to be called every
+    /// frame. See [LabelRenderer::draw] for what the arguments mean.
+    pub fn draw_labels(
         &mut self,
-        axes: &[(Unit<Vector3<f32>>, Point3<f32>)],
-        axis_length: f32,
-        transform: Isometry3<f32>,
+        window: &mut Window,
+        project: impl Fn(&Point3<f32>) -> Option<(Point2<f32>, f32)>,
+        focus_distance: f32,
     ) {
-        let origin = transform * Point3::origin();
-        for (v, color) in axes {
-            let end_pt = origin + axis_length * (transform * v.into_inner());
-            self.line_renderer.draw_line(origin, end_pt, *color);
+        self.label_renderer.draw(window, project, focus_distance);
+    }
+}
+
+impl Canvas for CompoundRenderer {
+    fn draw_line(&mut self, a: Point3<f32>, b: Point3<f32>, color: Point3<f32>) {
+        match self.map_half_height {
+            Some(_) => {
+                self.planar_lines
+                    .push((Point2::new(a.x, a.y), Point2::new(b.x, b.y), color))
+            }
+            None => match self.active_fog() {
+                Some(fog) => {
+                    // Break it up so that the far end fades out more
+                    let piece_length = 0.25 * FOG_SCALE * fog.focus_distance;
+                    let num_pieces = ((b - a).norm() / piece_length).ceil() as usize;
+                    let num_pieces = num_pieces.clamp(1, FOG_MAX_LINE_PIECES);
+                    let point_at = |i: usize| a + (b - a) * (i as f32 / num_pieces as f32);
+                    for i in 0..num_pieces {
+                        let (start, end) = (point_at(i), point_at(i + 1));
+                        let brightness = fog.brightness(&nalgebra::center(&start, &end));
+                        self.add_line(start, end, color * brightness);
+                    }
+                }
+                None => self.add_line(a, b, color),
+            },
         }
     }
 
-    pub fn draw_soi(&mut self, center: Point3<f32>, radius: f32, color: Point3<f32>) {
-        self.sphere_renderer.add_sphere(center, radius, color);
+    fn draw_sphere(&mut self, center: Point3<f32>, radius: f32, color: Point3<f32>) {
+        match self.map_half_height {
+            Some(_) => self.draw_flat_circle(center, radius, color),
+            None => match self.instanced_renderer.as_mut() {
+                Some(renderer) => renderer.add_sphere(center, radius, color),
+                None => self.sphere_renderer.add_sphere(center, radius, color),
+            },
+        }
     }
 
-    pub fn draw_orbit(&mut self, orbit: OrbitPatch, color: Point3<f32>, transform: Isometry3<f32>) {
-        self.orbit_renderer.add_orbit(orbit, color, transform);
+    fn draw_rings(&mut self, rings: &Rings, center: Point3<f32>, orientation: UnitQuaternion<f32>) {
+        match self.map_half_height {
+            Some(_) => draw_ring_outlines(self, rings, center, orientation),
+            None => self.ring_renderer.add_rings(rings, center, orientation),
+        }
     }
 
-    pub fn draw_marker(
+    fn draw_marker(
         &mut self,
         mtype: MarkerType,
         center: Point3<f32>,
         height: f32,
         color: Point3<f32>,
     ) {
-        self.marker_renderer
-            .add_marker(mtype, center, height, color);
+        match self.map_half_height {
+            Some(half_height) => {
+                // Markers are sized in NDC, where the window is 2 units tall
+                let size = height * half_height;
+                let points: Vec<_> = mtype
+                    .outline()
+                    .into_iter()
+                    .map(|pt| center + size * Vector3::new(pt.x, pt.y, 0.0))
+                    .collect();
+                self.draw_path(&points, color, true);
+            }
+            None => {
+                let brightness = self.active_fog().map_or(1.0, |f| f.brightness(&center));
+                self.marker_renderer
+                    .add_marker(mtype, center, height, color * brightness)
+            }
+        }
+    }
+
+    fn draw_label(&mut self, position: Point3<f32>, text: &str, color: Point3<f32>, rank: u32) {
+        self.label_renderer.add_label(position, text, color, rank);
+    }
+
+    fn draw_orbit(
+        &mut self,
+        orbit: OrbitPatch,
+        color: Point3<f32>,
+        transform: Isometry3<f64>,
+    ) -> Vec<ApsisMarker> {
+        // Orbits get their own shader, unless they're being flattened
+        match self.map_half_height {
+            Some(_) => draw_orbit_as_path(self, orbit, color, transform),
+            None => self.orbit_renderer.add_orbit(orbit, color, transform),
+        }
     }
 }
 
 impl Renderer for CompoundRenderer {
     fn render(&mut self, pass: usize, camera: &mut dyn Camera) {
-        self.sphere_renderer.render(pass, camera);
+        // The map is flat, so there's no sky to see
+        if self.map_half_height.is_none() {
+            self.star_renderer.render(pass, camera);
+        }
         self.orbit_renderer.render(pass, camera);
         self.line_renderer.render(pass, camera);
+        if let Some(renderer) = self.instanced_renderer.as_mut() {
+            renderer.render_lines(camera);
+        }
         self.marker_renderer.render(pass, camera);
+        // Rings and spheres are translucent, so they go last, to be blended
+        // over everything behind them. SOI spheres are bigger than rings, so
+        // they're drawn over them.
+        self.ring_renderer.render(pass, camera);
+        self.sphere_renderer.render(pass, camera);
+        if let Some(renderer) = self.instanced_renderer.as_mut() {
+            renderer.render_spheres(camera);
+        }
     }
 }
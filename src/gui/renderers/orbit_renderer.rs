@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::f64::consts::PI;
 
 use kiss3d::camera::Camera;
@@ -6,11 +7,12 @@ use kiss3d::renderer::Renderer;
 use kiss3d::resource::{
     AllocationType, BufferType, Effect, GPUVec, ShaderAttribute, ShaderUniform,
 };
-use nalgebra::{Isometry3, Matrix4, Point3, Vector3};
+use nalgebra::{
+    Isometry3, Matrix4, Point2, Point3, Translation3, UnitQuaternion, Vector2, Vector3, Vector4,
+};
 
-use super::utils::path_iter_parametric;
-use crate::astro::{PhysicalOrbit, TimedOrbit};
-use crate::model::orrery::{Body, BodyID};
+use super::utils::{distance_to_segment, path_iter_parametric};
+use crate::astro::{HasMass, PhysicalOrbit, TimedOrbit};
 
 // TODO: re-evaluate if we need this
 #[derive(Debug, Clone)]
@@ -18,42 +20,279 @@ pub struct OrbitPatch {
     pub orbit: PhysicalOrbit,
     pub start_anomaly: f64,
     pub end_anomaly: Option<f64>,
-    pub parent_id: BodyID,
+    pub show_apsides: bool,
+    pub style: LineStyle,
+    pub gradient: ColorGradient,
+    /// How many meters a pixel covers near the orbit, if known. Only used
+    /// when the orbit is broken into segments on the CPU.
+    pub pixel_size: Option<f64>,
+}
+
+/// How an orbit's line is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineStyle {
+    #[default]
+    Solid,
+    /// Broken into dashes, e.g., for parts of a trajectory that haven't been
+    /// flown yet.
+    Dashed,
+}
+
+/// How an orbit's color changes along it. Orbits with a gradient are drawn
+/// at full brightness at one end of it, and dimmer towards the other.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ColorGradient {
+    /// The same color the whole way around.
+    #[default]
+    Uniform,
+    /// Brightest where the object moves fastest, and dimmest where it moves
+    /// slowest.
+    Speed,
+    /// Fades out toward the future. `offset` is how long it is until the
+    /// start of the drawn part, and the orbit is as dim as it gets once it's
+    /// `fade_time` away.
+    TimeUntil { offset: f64, fade_time: f64 },
+    /// Instead of changing brightness, goes from green at periapsis to red at
+    /// the edge of the primary's SOI, which has the given radius, so that the
+    /// parts of the orbit near escaping stand out.
+    SoiFraction { soi_radius: f64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApsisKind {
+    Periapsis,
+    Apoapsis,
+}
+
+/// An apsis along a drawn orbit, as emitted by [OrbitRenderer::add_orbit].
+#[derive(Debug, Clone, Copy)]
+pub struct ApsisMarker {
+    pub kind: ApsisKind,
+    /// Position in focus space
+    pub position: Point3<f32>,
+    /// Distance from the center of the primary
+    pub radius: f64,
 }
 
 impl OrbitPatch {
-    pub fn new<S>(orbit: &TimedOrbit<&Body, S>, start_time: f64) -> OrbitPatch {
+    pub fn new<P: HasMass, S>(orbit: &TimedOrbit<P, S>, start_time: f64) -> OrbitPatch {
         let start_anomaly = orbit.s_at_time(start_time);
-        let parent_id = orbit.primary().id;
 
         Self {
             orbit: orbit.to_physical(),
             start_anomaly,
             end_anomaly: None,
-            parent_id,
+            show_apsides: false,
+            style: LineStyle::Solid,
+            gradient: ColorGradient::Uniform,
+            pixel_size: None,
         }
     }
+
+    /// Also emit markers for the periapsis and apoapsis, if they lie on the
+    /// drawn part of the orbit.
+    pub fn with_apsides(mut self) -> OrbitPatch {
+        self.show_apsides = true;
+        self
+    }
+
+    pub fn with_style(mut self, style: LineStyle) -> OrbitPatch {
+        self.style = style;
+        self
+    }
+
+    pub fn with_gradient(mut self, gradient: ColorGradient) -> OrbitPatch {
+        self.gradient = gradient;
+        self
+    }
+
+    /// Lets [OrbitRenderer::get_orbit_points] pick how many points to use
+    /// from how big the orbit looks, instead of always using the same number.
+    pub fn with_pixel_size(mut self, pixel_size: f64) -> OrbitPatch {
+        self.pixel_size = Some(pixel_size);
+        self
+    }
+
+    /// The furthest the drawn part of the orbit gets from the primary.
+    pub fn max_radius(&self) -> f64 {
+        if let Some(apoapsis) = self.orbit.apoapsis() {
+            return apoapsis;
+        }
+        // Hyperbolas get further away in both directions from periapsis
+        let (start_s, end_s) = OrbitRenderer::anomaly_range(self);
+        let radius = |s| self.orbit.get_state_native_frame(s).position().norm();
+        f64::max(radius(start_s), radius(end_s))
+    }
+
+    /// Returns the anomalies of the first periapsis and apoapsis at or after
+    /// the start of the patch, skipping any that lie past the end.
+    fn apsis_anomalies(&self) -> Vec<(ApsisKind, f64)> {
+        let beta = self.orbit.beta();
+        let candidates = if beta > 0.0 {
+            // Periapsis is at every multiple of the period (in s), and apoapsis
+            // is halfway between them.
+            let s_period = 2.0 * PI / beta.sqrt();
+            let next_after_start =
+                |offset: f64| offset + s_period * ((self.start_anomaly - offset) / s_period).ceil();
+            vec![
+                (ApsisKind::Periapsis, next_after_start(0.0)),
+                (ApsisKind::Apoapsis, next_after_start(s_period / 2.0)),
+            ]
+        } else if self.start_anomaly <= 0.0 {
+            vec![(ApsisKind::Periapsis, 0.0)]
+        } else {
+            vec![]
+        };
+
+        candidates
+            .into_iter()
+            .filter(|(_, s)| self.end_anomaly.is_none_or(|end| *s <= end))
+            .collect()
+    }
+
+    /// Like [OrbitPatch::new], but stops drawing at the given time. If the
+    /// orbit is closed and would wrap around more than once, the full ellipse
+    /// is drawn instead.
+    pub fn with_end_time<P: HasMass, S>(
+        orbit: &TimedOrbit<P, S>,
+        start_time: f64,
+        end_time: f64,
+    ) -> OrbitPatch {
+        let mut patch = Self::new(orbit, start_time);
+        let end_anomaly = orbit.s_at_time(end_time);
+
+        let wraps_around = match orbit.period() {
+            Some(period) => end_time - start_time >= period,
+            None => false,
+        };
+        if !wraps_around {
+            patch.end_anomaly = Some(end_anomaly);
+        }
+        patch
+    }
+}
+
+// How many line segments each orbit is drawn with. They're evaluated on the
+// GPU, and share one buffer, so we can afford plenty.
+const NUM_SEGMENTS: usize = 2048;
+// How many points to try when looking for the part of an orbit nearest the
+// focus, before narrowing it down.
+const NUM_REFERENCE_SAMPLES: usize = 64;
+// When orbits are broken into segments on the CPU, how far the segments can
+// stray from the true orbit, in pixels, and the bounds on how many of them
+// to use for a whole orbit.
+const MAX_CPU_ERROR: f64 = 0.5;
+const MIN_CPU_SEGMENTS: usize = 16;
+const MAX_CPU_SEGMENTS: usize = 1024;
+// How many segments to use when we don't know how big the orbit looks.
+const DEFAULT_CPU_SEGMENTS: usize = 180;
+// How many dashes a dashed orbit has, per turn of true anomaly.
+const DASHES_PER_TURN: f64 = 48.0;
+// How bright the dim end of a gradient is, relative to the bright end.
+const MIN_GRADIENT_BRIGHTNESS: f64 = 0.2;
+// Colors of an SOI fraction gradient, at periapsis and at the SOI's edge.
+const SOI_NEAR_COLOR: [f32; 3] = [0.0, 1.0, 0.0];
+const SOI_FAR_COLOR: [f32; 3] = [1.0, 0.0, 0.0];
+
+/// The uniforms for drawing one orbit, apart from its color; see
+/// [VERTEX_SRC].
+#[derive(Debug, Clone, Copy)]
+struct OrbitShape {
+    model: Matrix4<f32>,
+    conic: Vector3<f32>,
+    anomalies: Vector3<f32>,
+    dashes: f32,
+    gradient: Vector4<f32>,
+    gradient_range: Vector2<f32>,
 }
 
 struct OrbitData {
-    // Orbit path, stored as (pt, color, pt, color)
-    // Evaluated in the orbit's natural space (z normal, x periapsis)
-    orbit_lines: GPUVec<Point3<f32>>,
-    // Transform from the orbit's natural space to focus space
-    transform: Matrix4<f32>,
+    shape: OrbitShape,
+    color: Point3<f32>,
+}
+
+// How many numbers go into an [OrbitKey]
+const KEY_LEN: usize = 25;
+
+/// Everything an orbit's [OrbitShape] is worked out from, so that it can be
+/// reused while none of it changes. The numbers are compared bit for bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct OrbitKey([u64; KEY_LEN]);
+
+impl OrbitKey {
+    fn new(orbit: &OrbitPatch, transform: Isometry3<f64>) -> Self {
+        let (start_s, end_s) = OrbitRenderer::anomaly_range(orbit);
+        let style = match orbit.style {
+            LineStyle::Solid => 0.0,
+            LineStyle::Dashed => 1.0,
+        };
+        let (param_a, param_b) = match orbit.gradient {
+            ColorGradient::Uniform | ColorGradient::Speed => (0.0, 0.0),
+            ColorGradient::TimeUntil { offset, fade_time } => (offset, fade_time),
+            ColorGradient::SoiFraction { soi_radius } => (soi_radius, 0.0),
+        };
+
+        let rotation = orbit.orbit.rotation();
+        let values = [
+            orbit.orbit.primary().mu(),
+            orbit.orbit.angular_momentum(),
+            orbit.orbit.beta(),
+            start_s,
+            end_s,
+        ]
+        .into_iter()
+        .chain(rotation.matrix().iter().copied())
+        .chain(transform.translation.vector.iter().copied())
+        .chain(transform.rotation.coords.iter().copied())
+        .chain([style, orbit.gradient.shader_kind(), param_a, param_b]);
+
+        let mut bits = [0; KEY_LEN];
+        for (bit, x) in bits.iter_mut().zip(values) {
+            *bit = x.to_bits();
+        }
+        OrbitKey(bits)
+    }
+}
+
+impl ColorGradient {
+    /// How the vertex shader tells gradients apart.
+    fn shader_kind(&self) -> f64 {
+        match self {
+            ColorGradient::Uniform => 0.0,
+            ColorGradient::Speed => 1.0,
+            ColorGradient::TimeUntil { .. } => 2.0,
+            ColorGradient::SoiFraction { .. } => 3.0,
+        }
+    }
 }
 
 pub struct OrbitRenderer {
     // OpenGL stuff
     shader: Effect,
-    pos: ShaderAttribute<Point3<f32>>,
-    color: ShaderAttribute<Point3<f32>>,
+    param: ShaderAttribute<f32>,
     model: ShaderUniform<Matrix4<f32>>,
     view: ShaderUniform<Matrix4<f32>>,
     proj: ShaderUniform<Matrix4<f32>>,
+    conic: ShaderUniform<Vector3<f32>>,
+    anomalies: ShaderUniform<Vector3<f32>>,
+    color: ShaderUniform<Point3<f32>>,
+    dashes: ShaderUniform<f32>,
+    gradient: ShaderUniform<Vector4<f32>>,
+    gradient_range: ShaderUniform<Vector2<f32>>,
+    fog: ShaderUniform<f32>,
     line_width: f32,
+    // The camera's distance from the focus, if there's fog
+    fog_distance: Option<f32>,
+    // How far along the orbit each vertex is, from 0 to 1, stored in pairs
+    // for drawing lines. Every orbit uses the same ones.
+    params: GPUVec<f32>,
     // Data storage
     orbits: Vec<OrbitData>,
+    // Shapes of the orbits drawn last frame, and the ones drawn so far this
+    // frame. Finding the reference point is the slow part of adding an orbit,
+    // and usually nothing changes while time is paused.
+    shapes: HashMap<OrbitKey, OrbitShape>,
+    next_shapes: HashMap<OrbitKey, OrbitShape>,
 }
 
 impl OrbitRenderer {
@@ -62,12 +301,14 @@ impl OrbitRenderer {
 
         shader.use_program();
 
+        let params = (0..NUM_SEGMENTS)
+            .flat_map(|i| [i, i + 1])
+            .map(|i| i as f32 / NUM_SEGMENTS as f32)
+            .collect();
+
         OrbitRenderer {
-            pos: shader
-                .get_attrib::<Point3<f32>>("position")
-                .expect("Failed to get shader attribute."),
-            color: shader
-                .get_attrib::<Point3<f32>>("color")
+            param: shader
+                .get_attrib::<f32>("param")
                 .expect("Failed to get shader attribute."),
             model: shader
                 .get_uniform::<Matrix4<f32>>("model")
@@ -78,40 +319,168 @@ impl OrbitRenderer {
             proj: shader
                 .get_uniform::<Matrix4<f32>>("proj")
                 .expect("Failed to get shader uniform."),
+            conic: shader
+                .get_uniform::<Vector3<f32>>("conic")
+                .expect("Failed to get shader uniform."),
+            anomalies: shader
+                .get_uniform::<Vector3<f32>>("anomalies")
+                .expect("Failed to get shader uniform."),
+            color: shader
+                .get_uniform::<Point3<f32>>("color")
+                .expect("Failed to get shader uniform."),
+            dashes: shader
+                .get_uniform::<f32>("dashes")
+                .expect("Failed to get shader uniform."),
+            gradient: shader
+                .get_uniform::<Vector4<f32>>("gradient")
+                .expect("Failed to get shader uniform."),
+            gradient_range: shader
+                .get_uniform::<Vector2<f32>>("gradient_range")
+                .expect("Failed to get shader uniform."),
+            fog: shader
+                .get_uniform::<f32>("fog")
+                .expect("Failed to get shader uniform."),
             shader,
             line_width: 1.0,
+            fog_distance: None,
+            params: GPUVec::new(params, BufferType::Array, AllocationType::StaticDraw),
             orbits: vec![],
+            shapes: HashMap::new(),
+            next_shapes: HashMap::new(),
         }
     }
 
-    pub fn add_orbit(&mut self, orbit: OrbitPatch, color: Point3<f32>, transform: Isometry3<f32>) {
-        // Collect points and put them into the GPUVec
-        let points: Vec<_> = OrbitRenderer::get_orbit_points(&orbit).collect();
-        let mut data = Vec::with_capacity(4 * points.len());
-        for pts in points.windows(2) {
-            data.push(pts[0]);
-            data.push(color);
-            data.push(pts[1]);
-            data.push(color);
-        }
+    /// Fades orbits out beyond the given distance from the camera, like
+    /// [Fog](super::Fog) does, or stops fading them.
+    pub fn set_fog_distance(&mut self, distance: Option<f32>) {
+        self.fog_distance = distance;
+    }
 
-        // The transform we're given is from the parent body's space to focusspace, but
-        // we want to start out in the orbit's native space.
-        let transform2: Isometry3<f32> = nalgebra::convert(orbit.orbit.rotation());
-        let total_transform = transform * transform2;
+    /// Queues the orbit to be drawn. If the patch asks for apsides, returns
+    /// the ones it passes through, so that the caller can mark them.
+    ///
+    /// The transform is from the orbit's parent's frame to focus space. It's
+    /// in f64, since orbits far from the focus are big, and the points on them
+    /// only become small enough for f32 once they're relative to the focus.
+    /// The GPU only works in f32, so the orbit is drawn relative to its point
+    /// nearest the focus, which is found here in f64.
+    pub fn add_orbit(
+        &mut self,
+        orbit: OrbitPatch,
+        color: Point3<f32>,
+        transform: Isometry3<f64>,
+    ) -> Vec<ApsisMarker> {
+        let key = OrbitKey::new(&orbit, transform);
+        let shape = match self.shapes.get(&key) {
+            Some(shape) => *shape,
+            None => Self::orbit_shape(&orbit, transform),
+        };
+        self.next_shapes.insert(key, shape);
+        self.orbits.push(OrbitData { shape, color });
 
-        let orbit_data = OrbitData {
-            orbit_lines: GPUVec::new(data, BufferType::Array, AllocationType::StreamDraw),
-            transform: total_transform.to_homogeneous(),
+        let total_transform = Self::native_to_focus_space(&orbit, transform);
+        Self::apsis_markers(&orbit, total_transform)
+    }
+
+    /// Works out the uniforms for drawing the orbit with the given transform.
+    fn orbit_shape(orbit: &OrbitPatch, transform: Isometry3<f64>) -> OrbitShape {
+        let total_transform = Self::native_to_focus_space(orbit, transform);
+        let (start_s, end_s) = Self::anomaly_range(orbit);
+        let ref_s = Self::reference_anomaly(orbit, total_transform, start_s, end_s);
+
+        let beta = orbit.orbit.beta();
+        let ref_angle = if beta > 0.0 {
+            (beta.sqrt() * ref_s).rem_euclid(2.0 * PI)
+        } else if beta < 0.0 {
+            (-beta).sqrt() * ref_s
+        } else {
+            ref_s
         };
+        let ref_state = orbit.orbit.get_state_native_frame(ref_s);
+        let ref_position = ref_state.position();
+        let model = Isometry3::from_parts(
+            Translation3::from(total_transform * Point3::from(ref_position)),
+            total_transform.rotation,
+        );
+
+        OrbitShape {
+            model: nalgebra::convert::<_, Isometry3<f32>>(model).to_homogeneous(),
+            conic: nalgebra::convert(Vector3::new(
+                orbit.orbit.primary().mu(),
+                orbit.orbit.angular_momentum(),
+                beta,
+            )),
+            anomalies: nalgebra::convert(Vector3::new(ref_angle, start_s - ref_s, end_s - ref_s)),
+            dashes: Self::num_dashes(orbit) as f32,
+            gradient: nalgebra::convert(Vector4::new(
+                orbit.gradient.shader_kind(),
+                ref_position.norm(),
+                ref_position.dot(&ref_state.velocity()),
+                MIN_GRADIENT_BRIGHTNESS,
+            )),
+            gradient_range: nalgebra::convert(Self::gradient_range(orbit, ref_s)),
+        }
+    }
+
+    /// The transform we're given is from the parent body's space to focus
+    /// space, but the orbit is evaluated in its native space, so this returns
+    /// the transform from that to focus space.
+    pub fn native_to_focus_space(orbit: &OrbitPatch, transform: Isometry3<f64>) -> Isometry3<f64> {
+        transform * UnitQuaternion::from_rotation_matrix(&orbit.orbit.rotation())
+    }
+
+    /// Returns the apsides the patch passes through, if it asks for them.
+    pub fn apsis_markers(orbit: &OrbitPatch, total_transform: Isometry3<f64>) -> Vec<ApsisMarker> {
+        if !orbit.show_apsides {
+            return vec![];
+        }
+        orbit
+            .apsis_anomalies()
+            .into_iter()
+            .map(|(kind, s)| {
+                let position = orbit.orbit.get_state_native_frame(s).position();
+                ApsisMarker {
+                    kind,
+                    position: nalgebra::convert(total_transform * Point3::from(position)),
+                    radius: position.norm(),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns how far the given point on the screen is from the orbit, as it
+    /// would be drawn with the given transform, and the true anomaly of the
+    /// closest point on it. `project` takes points in focus space to the
+    /// screen, or returns `None` for points that aren't on it; segments with
+    /// either end off the screen are ignored.
+    pub fn screen_pick(
+        orbit: &OrbitPatch,
+        transform: Isometry3<f64>,
+        project: impl Fn(&Point3<f32>) -> Option<Point2<f32>>,
+        point: Point2<f32>,
+    ) -> Option<(f32, f64)> {
+        let total_transform = Self::native_to_focus_space(orbit, transform);
+        let projected: Vec<_> = Self::get_orbit_points(orbit, total_transform)
+            .map(|pt| project(&pt))
+            .collect();
 
-        self.orbits.push(orbit_data);
+        // Points are evenly spaced in true anomaly, like get_orbit_points
+        // spaces them
+        let (start_theta, end_theta) = Self::true_anomaly_range(orbit);
+        let step = (end_theta - start_theta) / (projected.len() - 1) as f64;
+        projected
+            .windows(2)
+            .enumerate()
+            .filter_map(|(i, pts)| {
+                let (distance, t) = distance_to_segment(point, pts[0]?, pts[1]?);
+                Some((distance, start_theta + (i as f64 + t as f64) * step))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
     }
 
-    /// Returns a sequence of points tracing out the orbit's path, evaluated in
-    /// the orbit's native frame.
-    fn get_orbit_points(orbit: &OrbitPatch) -> impl Iterator<Item = Point3<f32>> + '_ {
-        // Find the starting and ending anomalies
+    /// Returns the range of universal anomaly to draw. Orbits without an end
+    /// go all the way around once, if they can.
+    fn anomaly_range(orbit: &OrbitPatch) -> (f64, f64) {
         let start_s = orbit.start_anomaly;
         let end_s = match orbit.end_anomaly {
             Some(s) => s,
@@ -127,16 +496,245 @@ impl OrbitRenderer {
             }
         };
         assert!(end_s >= start_s);
+        (start_s, end_s)
+    }
+
+    /// Finds the anomaly, between the given ones, whose point is closest to
+    /// the focus. Points are sampled, and then the best one is refined by
+    /// golden-section search between its neighbors.
+    fn reference_anomaly(
+        orbit: &OrbitPatch,
+        total_transform: Isometry3<f64>,
+        start_s: f64,
+        end_s: f64,
+    ) -> f64 {
+        let distance = |s: f64| {
+            let v = orbit.orbit.get_state_native_frame(s).position();
+            (total_transform * Point3::from(v)).coords.norm()
+        };
+        let step = (end_s - start_s) / NUM_REFERENCE_SAMPLES as f64;
+        let closest = (0..=NUM_REFERENCE_SAMPLES)
+            .map(|i| start_s + step * i as f64)
+            .min_by(|a, b| distance(*a).total_cmp(&distance(*b)))
+            .unwrap();
+
+        let inv_phi = (5.0_f64.sqrt() - 1.0) / 2.0;
+        let (mut lo, mut hi) = (
+            f64::max(closest - step, start_s),
+            f64::min(closest + step, end_s),
+        );
+        for _ in 0..40 {
+            let a = hi - inv_phi * (hi - lo);
+            let b = lo + inv_phi * (hi - lo);
+            if distance(a) < distance(b) {
+                hi = b;
+            } else {
+                lo = a;
+            }
+        }
+        (lo + hi) / 2.0
+    }
+
+    /// Returns a sequence of points tracing out the orbit's path, in focus
+    /// space. They're evaluated in the orbit's native frame, and moved to
+    /// focus space with `total_transform` before being converted to f32.
+    ///
+    /// The points are evenly spaced in true anomaly, so they bunch up near
+    /// periapsis, where eccentric orbits curve the most. If the patch knows
+    /// its pixel size, the number of points is picked so that the path looks
+    /// smooth at that size.
+    ///
+    /// This is only for things that need the points on the CPU, like picking,
+    /// or canvases that can't use [OrbitRenderer] itself.
+    pub fn get_orbit_points(
+        orbit: &OrbitPatch,
+        total_transform: Isometry3<f64>,
+    ) -> impl Iterator<Item = Point3<f32>> + '_ {
+        let (start_theta, end_theta) = Self::true_anomaly_range(orbit);
+        let num_segments = Self::num_cpu_segments(orbit, end_theta - start_theta);
 
         // Get some points around the orbit
-        let f = move |s| {
+        let slr = orbit.orbit.semilatus_rectum();
+        let ecc = orbit.orbit.eccentricity();
+        let f = move |theta: f64| {
+            let r = slr / (1.0 + ecc * theta.cos());
+            let v = Vector3::new(r * theta.cos(), r * theta.sin(), 0.0);
+            nalgebra::convert(total_transform * Point3::from(v))
+        };
+
+        path_iter_parametric(f, start_theta, end_theta, num_segments)
+    }
+
+    /// Returns the range of true anomaly to draw; like [OrbitRenderer::anomaly_range],
+    /// but with the end unwrapped so that it's after the start.
+    fn true_anomaly_range(orbit: &OrbitPatch) -> (f64, f64) {
+        let (start_s, end_s) = Self::anomaly_range(orbit);
+        let theta = |s| {
             let v = orbit.orbit.get_state_native_frame(s).position();
-            let v: Vector3<f32> = nalgebra::convert(v);
-            Point3::from(v)
+            v.y.atan2(v.x)
+        };
+
+        let start_theta = theta(start_s);
+        let sweep = if orbit.end_anomaly.is_none() && orbit.orbit.is_closed() {
+            2.0 * PI
+        } else {
+            (theta(end_s) - start_theta).rem_euclid(2.0 * PI)
+        };
+        (start_theta, start_theta + sweep)
+    }
+
+    /// Returns the color of each of the points from
+    /// [OrbitRenderer::get_orbit_points], according to the patch's gradient.
+    /// Most gradients scale `color` from full brightness down to
+    /// [MIN_GRADIENT_BRIGHTNESS].
+    pub fn get_orbit_colors(
+        orbit: &OrbitPatch,
+        color: Point3<f32>,
+    ) -> impl Iterator<Item = Point3<f32>> + '_ {
+        let (start_theta, end_theta) = Self::true_anomaly_range(orbit);
+        let num_segments = Self::num_cpu_segments(orbit, end_theta - start_theta);
+
+        // Find the anomalies on the same turn as the start of the patch
+        let offset = orbit.start_anomaly - Self::anomaly_at_true_anomaly(orbit, start_theta);
+        let range = Self::gradient_range(orbit, orbit.start_anomaly);
+        (0..=num_segments).map(move |i| {
+            let u = i as f64 / num_segments as f64;
+            let theta = start_theta + u * (end_theta - start_theta);
+            let s = Self::anomaly_at_true_anomaly(orbit, theta) + offset;
+            let value = Self::gradient_value(orbit, s, orbit.start_anomaly);
+            match orbit.gradient {
+                ColorGradient::SoiFraction { .. } => {
+                    soi_fraction_color(gradient_fraction(value, range) as f32, color)
+                }
+                _ => color * Self::gradient_brightness(value, range) as f32,
+            }
+        })
+    }
+
+    /// Returns the anomaly at the given true anomaly, within half a turn of
+    /// periapsis (unless the true anomaly is further than that).
+    fn anomaly_at_true_anomaly(orbit: &OrbitPatch, theta: f64) -> f64 {
+        let ecc = orbit.orbit.eccentricity();
+        let r = orbit.orbit.semilatus_rectum() / (1.0 + ecc * theta.cos());
+        // In the native frame, the position is (r_p - mu G2, h G1, 0)
+        let g1 = r * theta.sin() / orbit.orbit.angular_momentum();
+        let g2 = (orbit.orbit.periapsis() - r * theta.cos()) / orbit.orbit.primary().mu();
+
+        let beta = orbit.orbit.beta();
+        if beta > 0.0 {
+            // The eccentric anomaly is on the same turn as the true anomaly
+            let x = beta.sqrt();
+            let turns = ((theta - theta.sin().atan2(theta.cos())) / (2.0 * PI)).round();
+            let angle = (x * g1).atan2(1.0 - beta * g2) + 2.0 * PI * turns;
+            angle / x
+        } else if beta < 0.0 {
+            let x = (-beta).sqrt();
+            (x * g1).asinh() / x
+        } else {
+            g1
+        }
+    }
+
+    /// Returns the quantity the orbit's gradient follows, at the given
+    /// anomaly. Times are measured from `ref_s`, so that they're small near
+    /// there, which the vertex shader needs for precision.
+    fn gradient_value(orbit: &OrbitPatch, s: f64, ref_s: f64) -> f64 {
+        match orbit.gradient {
+            ColorGradient::Uniform => 0.0,
+            ColorGradient::Speed => orbit.orbit.get_state_native_frame(s).velocity().norm(),
+            ColorGradient::SoiFraction { .. } => {
+                orbit.orbit.get_state_native_frame(s).position().norm()
+            }
+            // Later is dimmer
+            ColorGradient::TimeUntil { .. } => {
+                orbit.orbit.s_to_tsp(ref_s) - orbit.orbit.s_to_tsp(s)
+            }
+        }
+    }
+
+    /// Returns the values of [OrbitRenderer::gradient_value] at which the
+    /// orbit is dimmest and brightest.
+    fn gradient_range(orbit: &OrbitPatch, ref_s: f64) -> Vector2<f64> {
+        match orbit.gradient {
+            ColorGradient::Uniform => Vector2::zeros(),
+            ColorGradient::Speed => {
+                let mu = orbit.orbit.primary().mu();
+                let slowest = (2.0 * mu / orbit.max_radius() - orbit.orbit.beta())
+                    .max(0.0)
+                    .sqrt();
+                Vector2::new(slowest, orbit.orbit.periapsis_velocity())
+            }
+            ColorGradient::TimeUntil { offset, fade_time } => {
+                let start = Self::gradient_value(orbit, orbit.start_anomaly, ref_s) - offset;
+                Vector2::new(start - fade_time, start)
+            }
+            // Not actually brightness, but where the colors are pure green
+            // and pure red
+            ColorGradient::SoiFraction { soi_radius } => {
+                Vector2::new(orbit.orbit.periapsis(), soi_radius)
+            }
+        }
+    }
+
+    fn gradient_brightness(value: f64, range: Vector2<f64>) -> f64 {
+        if range.y <= range.x {
+            return 1.0;
+        }
+        let fraction = gradient_fraction(value, range);
+        MIN_GRADIENT_BRIGHTNESS + (1.0 - MIN_GRADIENT_BRIGHTNESS) * fraction
+    }
+
+    /// Returns how many dashes to draw the orbit with, or zero if it's solid.
+    /// Dashes are about the same length no matter how much of the orbit is
+    /// drawn.
+    pub fn num_dashes(orbit: &OrbitPatch) -> usize {
+        match orbit.style {
+            LineStyle::Solid => 0,
+            LineStyle::Dashed => {
+                let (start_theta, end_theta) = Self::true_anomaly_range(orbit);
+                let dashes = DASHES_PER_TURN * (end_theta - start_theta) / (2.0 * PI);
+                usize::max(dashes.round() as usize, 1)
+            }
+        }
+    }
+
+    /// Picks how many segments to break the given sweep of true anomaly into.
+    ///
+    /// With even steps in true anomaly, a segment strays furthest from the
+    /// orbit where it's furthest from the primary, by about r dtheta^2 / 8.
+    /// Keeping that under [MAX_CPU_ERROR] pixels takes pi sqrt(r / 2 err)
+    /// segments for a whole turn.
+    fn num_cpu_segments(orbit: &OrbitPatch, sweep: f64) -> usize {
+        let pixel_size = match orbit.pixel_size {
+            Some(pixel_size) => pixel_size,
+            None => return DEFAULT_CPU_SEGMENTS,
         };
+        let radius = orbit.max_radius() / pixel_size;
+        let full_turn = PI * (radius / (2.0 * MAX_CPU_ERROR)).sqrt();
+        let num_segments = (full_turn * sweep / (2.0 * PI)).ceil();
+        if num_segments.is_nan() {
+            return MAX_CPU_SEGMENTS;
+        }
+        (num_segments as usize).clamp(MIN_CPU_SEGMENTS, MAX_CPU_SEGMENTS)
+    }
+}
 
-        path_iter_parametric(f, start_s, end_s, 180)
+/// How far along the given range the value is, from 0 to 1.
+fn gradient_fraction(value: f64, range: Vector2<f64>) -> f64 {
+    if range.y <= range.x {
+        return 0.0;
     }
+    ((value - range.x) / (range.y - range.x)).clamp(0.0, 1.0)
+}
+
+/// The color of an SOI fraction gradient, the given fraction of the way from
+/// periapsis to the edge of the SOI. It's as bright as the brightest channel
+/// of `color`, so that dimmed orbits stay dim.
+fn soi_fraction_color(fraction: f32, color: Point3<f32>) -> Point3<f32> {
+    let near = Vector3::from(SOI_NEAR_COLOR);
+    let far = Vector3::from(SOI_FAR_COLOR);
+    let brightness = color.x.max(color.y).max(color.z);
+    Point3::from(near.lerp(&far, fraction) * brightness)
 }
 
 impl Renderer for OrbitRenderer {
@@ -146,41 +744,174 @@ impl Renderer for OrbitRenderer {
         }
 
         self.shader.use_program();
-        self.pos.enable();
-        self.color.enable();
+        self.param.enable();
 
         camera.upload(pass, &mut self.proj, &mut self.view);
+        self.param.bind_sub_buffer(&mut self.params, 0, 0);
+        self.fog.upload(&self.fog_distance.unwrap_or(0.0));
 
-        for orbit in self.orbits.iter_mut() {
-            self.pos.bind_sub_buffer(&mut orbit.orbit_lines, 1, 0);
-            self.color.bind_sub_buffer(&mut orbit.orbit_lines, 1, 1);
-
-            self.model.upload(&orbit.transform);
-
-            let ctxt = Context::get();
-            ctxt.draw_arrays(Context::LINES, 0, (orbit.orbit_lines.len() / 2) as i32);
-            ctxt.line_width(self.line_width);
+        let ctxt = Context::get();
+        ctxt.line_width(self.line_width);
+        for orbit in self.orbits.iter() {
+            let shape = &orbit.shape;
+            self.model.upload(&shape.model);
+            self.conic.upload(&shape.conic);
+            self.anomalies.upload(&shape.anomalies);
+            self.color.upload(&orbit.color);
+            self.dashes.upload(&shape.dashes);
+            self.gradient.upload(&shape.gradient);
+            self.gradient_range.upload(&shape.gradient_range);
+            ctxt.draw_arrays(Context::LINES, 0, self.params.len() as i32);
         }
 
-        self.pos.disable();
-        self.color.disable();
+        self.param.disable();
 
-        // TODO keep the GPUVecs around each loop?
         self.orbits.clear();
+        // Only keep the shapes that are still being drawn
+        self.shapes = std::mem::take(&mut self.next_shapes);
     }
 }
 
-/// Vertex shader used by the material to display line.
+/// Vertex shader that evaluates points along a conic, in its native frame.
+///
+/// The position at universal anomaly s is (r_p - mu G2(s), h G1(s), 0), where
+/// G1 and G2 are Stumpff G-functions. To keep precision in f32, everything is
+/// relative to a reference point on the orbit: `model` takes the native frame
+/// to focus space, but with the reference point as the origin, and the
+/// differences in G1 and G2 are written as products, so that they're accurate
+/// near the reference point.
+///
+/// - `conic` is (mu, h, beta)
+/// - `anomalies` is the reference angle (sqrt(|beta|) s, or just s for
+///   parabolas), and the start and end of the drawn range, relative to the
+///   reference s
+/// - `dashes` is how many dashes to break the orbit into, or zero for a solid
+///   line; the fragment shader drops every other half-dash
+/// - `gradient` is the kind of gradient (0 for none, 1 for speed, 2 for time,
+///   3 for SOI fraction), the distance and r.v at the reference point, and
+///   the minimum brightness; `gradient_range` is the values at which the
+///   orbit is dimmest and brightest. The distance, speed and time are worked
+///   out from the reference point with the G-functions of ds, which are
+///   accurate enough for colors. SOI fractions are shown with
+///   `soi_near_color` and `soi_far_color`, which match [SOI_NEAR_COLOR] and
+///   [SOI_FAR_COLOR].
+/// - `fog` is the camera's distance from the focus, or zero for no fog. With
+///   fog, orbits dim with their distance from the camera beyond that, in
+///   the same way as [Fog](super::Fog).
 static VERTEX_SRC: &str = "#version 100
-    attribute vec3 position;
-    attribute vec3 color;
+    attribute float param;
     varying   vec3 vColor;
-    uniform   mat4 model;
+    varying   float vDash;
     uniform   mat4 proj;
     uniform   mat4 view;
+    uniform   mat4 model;
+    uniform   vec3 conic;
+    uniform   vec3 anomalies;
+    uniform   vec3 color;
+    uniform   float dashes;
+    uniform   vec4 gradient;
+    uniform   vec2 gradient_range;
+    uniform   float fog;
+
+    const vec3 soi_near_color = vec3(0.0, 1.0, 0.0);
+    const vec3 soi_far_color = vec3(1.0, 0.0, 0.0);
+    const float fog_scale = 2.0;
+    const float fog_min_brightness = 0.1;
+
+    float hyp_sin(float x) {
+        return 0.5 * (exp(x) - exp(-x));
+    }
+
+    float hyp_cos(float x) {
+        return 0.5 * (exp(x) + exp(-x));
+    }
+
     void main() {
-        gl_Position = proj * view * model * vec4(position, 1.0);
-        vColor = color;
+        float mu = conic.x;
+        float h = conic.y;
+        float beta = conic.z;
+        float ds = mix(anomalies.y, anomalies.z, param);
+
+        float g1;
+        float g2;
+        if (beta > 0.0) {
+            float x = sqrt(beta);
+            float mid = anomalies.x + 0.5 * x * ds;
+            float half_diff = 0.5 * x * ds;
+            g1 = 2.0 * cos(mid) * sin(half_diff) / x;
+            g2 = 2.0 * sin(mid) * sin(half_diff) / beta;
+        } else if (beta < 0.0) {
+            float x = sqrt(-beta);
+            float mid = anomalies.x + 0.5 * x * ds;
+            float half_diff = 0.5 * x * ds;
+            g1 = 2.0 * hyp_cos(mid) * hyp_sin(half_diff) / x;
+            g2 = -2.0 * hyp_sin(mid) * hyp_sin(half_diff) / beta;
+        } else {
+            g1 = ds;
+            g2 = ds * (anomalies.x + 0.5 * ds);
+        }
+
+        vec3 position = vec3(-mu * g2, h * g1, 0.0);
+        vec4 eye_position = view * model * vec4(position, 1.0);
+        gl_Position = proj * eye_position;
+        float brightness = 1.0;
+        float fraction = 0.0;
+        if (gradient.x > 0.0) {
+            float c0;
+            float c1;
+            float c2;
+            float c3;
+            if (beta > 0.0) {
+                float x = sqrt(beta);
+                float y = x * ds;
+                float half_sin = sin(0.5 * y);
+                c0 = cos(y);
+                c1 = sin(y) / x;
+                c2 = 2.0 * half_sin * half_sin / beta;
+                c3 = (y - sin(y)) / (beta * x);
+            } else if (beta < 0.0) {
+                float x = sqrt(-beta);
+                float y = x * ds;
+                float half_sin = hyp_sin(0.5 * y);
+                c0 = hyp_cos(y);
+                c1 = hyp_sin(y) / x;
+                c2 = -2.0 * half_sin * half_sin / beta;
+                c3 = (hyp_sin(y) - y) / (-beta * x);
+            } else {
+                c0 = 1.0;
+                c1 = ds;
+                c2 = 0.5 * ds * ds;
+                c3 = ds * ds * ds / 6.0;
+            }
+
+            float value;
+            float r = gradient.y * c0 + gradient.z * c1 + mu * c2;
+            if (gradient.x == 1.0) {
+                value = sqrt(max(2.0 * mu / r - beta, 0.0));
+            } else if (gradient.x == 2.0) {
+                value = -(gradient.y * c1 + gradient.z * c2 + mu * c3);
+            } else {
+                value = r;
+            }
+            float dimmest = gradient_range.x;
+            float brightest = gradient_range.y;
+            if (brightest > dimmest) {
+                fraction = clamp((value - dimmest) / (brightest - dimmest), 0.0, 1.0);
+                brightness = mix(gradient.w, 1.0, fraction);
+            }
+        }
+
+        if (gradient.x == 3.0) {
+            float peak = max(color.r, max(color.g, color.b));
+            vColor = mix(soi_near_color, soi_far_color, fraction) * peak;
+        } else {
+            vColor = color * brightness;
+        }
+        if (fog > 0.0) {
+            float beyond = max(length(eye_position.xyz) - fog, 0.0);
+            vColor *= max(exp(-beyond / (fog_scale * fog)), fog_min_brightness);
+        }
+        vDash = param * dashes;
     }";
 
 /// Fragment shader used by the material to display line.
@@ -192,6 +923,10 @@ static FRAGMENT_SRC: &str = "#version 100
 #endif
 
     varying vec3 vColor;
+    varying float vDash;
     void main() {
+        if (fract(vDash) > 0.5) {
+            discard;
+        }
         gl_FragColor = vec4(vColor, 1.0);
     }";
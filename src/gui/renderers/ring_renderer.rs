@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::path::Path;
+use std::rc::Rc;
+
+use kiss3d::camera::Camera;
+use kiss3d::context::Context;
+use kiss3d::renderer::Renderer;
+use kiss3d::resource::{
+    AllocationType, BufferType, Effect, GPUVec, ShaderAttribute, ShaderUniform, Texture,
+    TextureManager,
+};
+use nalgebra::{
+    Isometry3, Matrix4, Point2, Point3, Translation3, UnitQuaternion, Vector2, Vector4,
+};
+
+use crate::model::orrery::Rings;
+
+// How many pieces to break the rings into around the body
+const NUM_SEGMENTS: usize = 128;
+// How opaque untextured rings are
+const RING_OPACITY: f32 = 0.6;
+
+struct RingData {
+    model: Matrix4<f32>,
+    radii: Vector2<f32>,
+    color: Vector4<f32>,
+    texture: Option<Rc<Texture>>,
+}
+
+/// Draws flat, translucent rings around bodies, either in a single color, or
+/// with a texture that varies with distance from the body.
+pub struct RingRenderer {
+    // OpenGL stuff
+    shader: Effect,
+    coords: ShaderAttribute<Point2<f32>>,
+    model: ShaderUniform<Matrix4<f32>>,
+    view: ShaderUniform<Matrix4<f32>>,
+    proj: ShaderUniform<Matrix4<f32>>,
+    radii: ShaderUniform<Vector2<f32>>,
+    color: ShaderUniform<Vector4<f32>>,
+    textured: ShaderUniform<f32>,
+    // The angle and how far out each vertex is, from 0 at the inner edge to
+    // 1 at the outer edge, in triangles. Every ring uses the same ones.
+    vertices: GPUVec<Point2<f32>>,
+    // Textures by path, or None if they couldn't be found, so that we only
+    // complain about each once
+    textures: HashMap<String, Option<Rc<Texture>>>,
+    // Data storage
+    rings: Vec<RingData>,
+}
+
+impl RingRenderer {
+    pub fn new() -> Self {
+        let mut shader = Effect::new_from_str(VERTEX_SRC, FRAGMENT_SRC);
+
+        shader.use_program();
+
+        let vertices = (0..NUM_SEGMENTS)
+            .flat_map(|i| {
+                let a0 = TAU * i as f32 / NUM_SEGMENTS as f32;
+                let a1 = TAU * (i + 1) as f32 / NUM_SEGMENTS as f32;
+                [
+                    Point2::new(a0, 0.0),
+                    Point2::new(a0, 1.0),
+                    Point2::new(a1, 1.0),
+                    Point2::new(a0, 0.0),
+                    Point2::new(a1, 1.0),
+                    Point2::new(a1, 0.0),
+                ]
+            })
+            .collect();
+
+        RingRenderer {
+            coords: shader
+                .get_attrib::<Point2<f32>>("coords")
+                .expect("Failed to get shader attribute."),
+            model: shader
+                .get_uniform::<Matrix4<f32>>("model")
+                .expect("Failed to get shader uniform."),
+            view: shader
+                .get_uniform::<Matrix4<f32>>("view")
+                .expect("Failed to get shader uniform."),
+            proj: shader
+                .get_uniform::<Matrix4<f32>>("proj")
+                .expect("Failed to get shader uniform."),
+            radii: shader
+                .get_uniform::<Vector2<f32>>("radii")
+                .expect("Failed to get shader uniform."),
+            color: shader
+                .get_uniform::<Vector4<f32>>("color")
+                .expect("Failed to get shader uniform."),
+            textured: shader
+                .get_uniform::<f32>("textured")
+                .expect("Failed to get shader uniform."),
+            shader,
+            vertices: GPUVec::new(vertices, BufferType::Array, AllocationType::StaticDraw),
+            textures: HashMap::new(),
+            rings: vec![],
+        }
+    }
+
+    /// Queues the rings to be drawn around the given center, in the xy-plane
+    /// of the given orientation.
+    pub fn add_rings(
+        &mut self,
+        rings: &Rings,
+        center: Point3<f32>,
+        orientation: UnitQuaternion<f32>,
+    ) {
+        let texture = rings
+            .texture
+            .as_ref()
+            .and_then(|path| self.load_texture(path));
+        let model = Isometry3::from_parts(Translation3::from(center), orientation);
+        self.rings.push(RingData {
+            model: model.to_homogeneous(),
+            radii: Vector2::new(rings.inner_radius, rings.outer_radius),
+            color: rings.color.coords.push(RING_OPACITY),
+            texture,
+        });
+    }
+
+    fn load_texture(&mut self, path: &str) -> Option<Rc<Texture>> {
+        self.textures
+            .entry(path.to_owned())
+            .or_insert_with(|| {
+                // kiss3d panics on textures it can't load, so check for the
+                // easy mistake first
+                if !Path::new(path).is_file() {
+                    println!("Couldn't find ring texture {}", path);
+                    return None;
+                }
+                Some(TextureManager::get_global_manager(|tm| {
+                    tm.add(Path::new(path), path)
+                }))
+            })
+            .clone()
+    }
+}
+
+impl Renderer for RingRenderer {
+    fn render(&mut self, pass: usize, camera: &mut dyn Camera) {
+        if self.rings.is_empty() {
+            return;
+        }
+
+        self.shader.use_program();
+        self.coords.enable();
+
+        camera.upload(pass, &mut self.proj, &mut self.view);
+        self.coords.bind_sub_buffer(&mut self.vertices, 0, 0);
+
+        let ctxt = Context::get();
+        ctxt.enable(Context::BLEND);
+        ctxt.blend_func_separate(
+            Context::SRC_ALPHA,
+            Context::ONE_MINUS_SRC_ALPHA,
+            Context::ONE,
+            Context::ONE_MINUS_SRC_ALPHA,
+        );
+        // Rings are seen from both sides
+        ctxt.disable(Context::CULL_FACE);
+        ctxt.active_texture(Context::TEXTURE0);
+        for ring in self.rings.iter() {
+            self.model.upload(&ring.model);
+            self.radii.upload(&ring.radii);
+            self.color.upload(&ring.color);
+            match &ring.texture {
+                Some(texture) => {
+                    ctxt.bind_texture(Context::TEXTURE_2D, Some(texture));
+                    self.textured.upload(&1.0);
+                }
+                None => self.textured.upload(&0.0),
+            }
+            ctxt.draw_arrays(Context::TRIANGLES, 0, self.vertices.len() as i32);
+        }
+        ctxt.enable(Context::CULL_FACE);
+        ctxt.cull_face(Context::BACK);
+        ctxt.disable(Context::BLEND);
+
+        self.coords.disable();
+
+        self.rings.clear();
+    }
+}
+
+/// Vertex shader that places each vertex at its angle and radius.
+///
+/// - `coords` is the angle, and how far from the inner edge (0) to the outer
+///   edge (1) the vertex is
+/// - `radii` is the inner and outer radius
+static VERTEX_SRC: &str = "#version 100
+    attribute vec2 coords;
+    varying   float vRadial;
+    uniform   mat4 proj;
+    uniform   mat4 view;
+    uniform   mat4 model;
+    uniform   vec2 radii;
+
+    void main() {
+        float r = mix(radii.x, radii.y, coords.y);
+        vec3 position = vec3(r * cos(coords.x), r * sin(coords.x), 0.0);
+        gl_Position = proj * view * model * vec4(position, 1.0);
+        vRadial = coords.y;
+    }";
+
+/// Fragment shader that colors the rings, either with the middle row of the
+/// bound texture, or with a flat, translucent color if `textured` is zero.
+static FRAGMENT_SRC: &str = "#version 100
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+   precision highp float;
+#else
+   precision mediump float;
+#endif
+
+    varying float vRadial;
+    uniform vec4  color;
+    uniform float textured;
+    uniform sampler2D tex;
+
+    void main() {
+        if (textured > 0.5) {
+            gl_FragColor = texture2D(tex, vec2(vRadial, 0.5));
+        } else {
+            gl_FragColor = color;
+        }
+    }";
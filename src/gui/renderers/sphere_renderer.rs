@@ -1,28 +1,100 @@
+use std::f32::consts::{PI, TAU};
+
 use kiss3d::camera::Camera;
+use kiss3d::context::Context;
 use kiss3d::renderer::{LineRenderer, Renderer};
-use nalgebra::{Point3, Vector3};
+use kiss3d::resource::{
+    AllocationType, BufferType, Effect, GPUVec, ShaderAttribute, ShaderUniform,
+};
+use nalgebra::{Matrix4, Point3, Vector3};
 
 use super::utils::{draw_path, path_iter_parametric};
 
+// How finely the shell is divided, in latitude and longitude
+pub(super) const NUM_STACKS: usize = 24;
+pub(super) const NUM_SLICES: usize = 48;
+// How opaque the shell is where it faces the camera, and at its edges
+pub(super) const FACING_OPACITY: f32 = 0.05;
+pub(super) const EDGE_OPACITY: f32 = 0.35;
+// How many lines of longitude and latitude the wireframe has, not counting
+// the poles
+pub(super) const NUM_MERIDIANS: usize = 12;
+pub(super) const NUM_PARALLELS: usize = 5;
+
 struct SphereData {
     pub center: Point3<f32>,
     pub radius: f32,
     pub color: Point3<f32>,
 }
 
+/// Draws spheres as translucent shells, which are more opaque towards their
+/// edges, so that they look like bubbles. Optionally, they also get lines of
+/// latitude and longitude.
 pub struct SphereRenderer {
+    // OpenGL stuff
+    shader: Effect,
+    position: ShaderAttribute<Point3<f32>>,
+    view: ShaderUniform<Matrix4<f32>>,
+    proj: ShaderUniform<Matrix4<f32>>,
+    center: ShaderUniform<Point3<f32>>,
+    radius: ShaderUniform<f32>,
+    color: ShaderUniform<Point3<f32>>,
+    opacity: ShaderUniform<Vector3<f32>>,
+    // Triangles making up a unit sphere, wound counterclockwise when seen
+    // from outside. Every sphere uses the same ones.
+    vertices: GPUVec<Point3<f32>>,
     line_renderer: LineRenderer,
+    wireframe: bool,
+    // Data storage
     spheres: Vec<SphereData>,
 }
 
 impl SphereRenderer {
     pub fn new() -> Self {
+        let mut shader = Effect::new_from_str(VERTEX_SRC, FRAGMENT_SRC);
+
+        shader.use_program();
+
         SphereRenderer {
+            position: shader
+                .get_attrib::<Point3<f32>>("position")
+                .expect("Failed to get shader attribute."),
+            view: shader
+                .get_uniform::<Matrix4<f32>>("view")
+                .expect("Failed to get shader uniform."),
+            proj: shader
+                .get_uniform::<Matrix4<f32>>("proj")
+                .expect("Failed to get shader uniform."),
+            center: shader
+                .get_uniform::<Point3<f32>>("center")
+                .expect("Failed to get shader uniform."),
+            radius: shader
+                .get_uniform::<f32>("radius")
+                .expect("Failed to get shader uniform."),
+            color: shader
+                .get_uniform::<Point3<f32>>("color")
+                .expect("Failed to get shader uniform."),
+            opacity: shader
+                .get_uniform::<Vector3<f32>>("opacity")
+                .expect("Failed to get shader uniform."),
+            shader,
+            vertices: GPUVec::new(
+                unit_sphere_triangles(),
+                BufferType::Array,
+                AllocationType::StaticDraw,
+            ),
             line_renderer: LineRenderer::new(),
+            wireframe: false,
             spheres: vec![],
         }
     }
 
+    /// Sets whether spheres get lines of latitude and longitude, as well as
+    /// their shell.
+    pub fn set_wireframe(&mut self, wireframe: bool) {
+        self.wireframe = wireframe;
+    }
+
     pub fn add_sphere(&mut self, center: Point3<f32>, radius: f32, color: Point3<f32>) {
         let sphere = SphereData {
             center,
@@ -32,35 +104,162 @@ impl SphereRenderer {
         self.spheres.push(sphere);
     }
 
-    fn load_sphere_into_renderer(
-        line_renderer: &mut LineRenderer,
-        camera: &dyn Camera,
-        sphere: &SphereData,
-    ) {
-        // Transform the screen x and y vectors into whatever frame we're currently
-        // focused on
-        let camera_transform = camera.view_transform().inverse();
-        let x_vec = camera_transform.transform_vector(&Vector3::x()).normalize();
-        let y_vec = camera_transform.transform_vector(&Vector3::y()).normalize();
-
-        let f = |theta: f32| {
-            let v = x_vec * theta.cos() + y_vec * theta.sin();
-            sphere.center + sphere.radius * v
+    fn load_wireframe_into_renderer(line_renderer: &mut LineRenderer, sphere: &SphereData) {
+        let point = |theta: f32, phi: f32| {
+            let direction = Vector3::new(
+                theta.sin() * phi.cos(),
+                theta.sin() * phi.sin(),
+                theta.cos(),
+            );
+            sphere.center + sphere.radius * direction
         };
-        draw_path(
-            line_renderer,
-            path_iter_parametric(f, 0.0, std::f32::consts::TAU, 100),
-            &sphere.color,
-        );
+
+        for i in 0..NUM_MERIDIANS {
+            let phi = TAU * i as f32 / NUM_MERIDIANS as f32;
+            draw_path(
+                line_renderer,
+                path_iter_parametric(|theta| point(theta, phi), 0.0, PI, NUM_STACKS),
+                &sphere.color,
+            );
+        }
+        for i in 1..=NUM_PARALLELS {
+            let theta = PI * i as f32 / (NUM_PARALLELS + 1) as f32;
+            draw_path(
+                line_renderer,
+                path_iter_parametric(|phi| point(theta, phi), 0.0, TAU, NUM_SLICES),
+                &sphere.color,
+            );
+        }
+    }
+}
+
+/// Returns the corners of the triangles that make up a sphere of radius 1,
+/// divided into stacks and slices like a globe.
+pub(super) fn unit_sphere_triangles() -> Vec<Point3<f32>> {
+    let point = |stack: usize, slice: usize| {
+        let theta = PI * stack as f32 / NUM_STACKS as f32;
+        let phi = TAU * slice as f32 / NUM_SLICES as f32;
+        Point3::new(
+            theta.sin() * phi.cos(),
+            theta.sin() * phi.sin(),
+            theta.cos(),
+        )
+    };
+
+    let mut triangles = Vec::with_capacity(NUM_STACKS * NUM_SLICES * 6);
+    for i in 0..NUM_STACKS {
+        for j in 0..NUM_SLICES {
+            // Going down a stack and then east along a slice turns
+            // counterclockwise when seen from outside
+            let (a, b, c, d) = (
+                point(i, j),
+                point(i + 1, j),
+                point(i + 1, j + 1),
+                point(i, j + 1),
+            );
+            triangles.extend([a, b, c, a, c, d]);
+        }
     }
+    triangles
 }
 
 impl Renderer for SphereRenderer {
     fn render(&mut self, pass: usize, camera: &mut dyn Camera) {
-        for sphere in self.spheres.iter() {
-            SphereRenderer::load_sphere_into_renderer(&mut self.line_renderer, camera, sphere);
+        if self.wireframe {
+            for sphere in self.spheres.iter() {
+                SphereRenderer::load_wireframe_into_renderer(&mut self.line_renderer, sphere);
+            }
         }
         self.line_renderer.render(pass, camera);
+
+        if self.spheres.is_empty() {
+            return;
+        }
+
+        // Translucent things have to be drawn back to front, so that the ones
+        // in front are blended over the ones behind
+        let eye = camera.eye();
+        self.spheres.sort_by(|a, b| {
+            let dist_a = (a.center - eye).norm();
+            let dist_b = (b.center - eye).norm();
+            dist_b.total_cmp(&dist_a)
+        });
+
+        self.shader.use_program();
+        self.position.enable();
+
+        camera.upload(pass, &mut self.proj, &mut self.view);
+        self.position.bind_sub_buffer(&mut self.vertices, 0, 0);
+
+        let ctxt = Context::get();
+        ctxt.enable(Context::BLEND);
+        ctxt.blend_func_separate(
+            Context::SRC_ALPHA,
+            Context::ONE_MINUS_SRC_ALPHA,
+            Context::ONE,
+            Context::ONE_MINUS_SRC_ALPHA,
+        );
+        // Faces are picked in the fragment shader instead
+        ctxt.disable(Context::CULL_FACE);
+        for sphere in self.spheres.iter() {
+            self.center.upload(&sphere.center);
+            self.radius.upload(&sphere.radius);
+            self.color.upload(&sphere.color);
+            // The far side of each shell goes first, for the same reason
+            for side in [-1.0, 1.0] {
+                let opacity = Vector3::new(FACING_OPACITY, EDGE_OPACITY, side);
+                self.opacity.upload(&opacity);
+                ctxt.draw_arrays(Context::TRIANGLES, 0, self.vertices.len() as i32);
+            }
+        }
+        ctxt.enable(Context::CULL_FACE);
+        ctxt.cull_face(Context::BACK);
+        ctxt.disable(Context::BLEND);
+
+        self.position.disable();
+
         self.spheres.clear();
     }
 }
+
+/// Vertex shader that scales and moves the unit sphere into place.
+///
+/// `vEdge` is how close the surface is to edge-on as seen from the camera,
+/// from 0 facing it, to 1 at the silhouette.
+static VERTEX_SRC: &str = "#version 100
+    attribute vec3 position;
+    varying   float vEdge;
+    uniform   mat4 proj;
+    uniform   mat4 view;
+    uniform   vec3 center;
+    uniform   float radius;
+
+    void main() {
+        vec4 eye_position = view * vec4(center + radius * position, 1.0);
+        vec3 eye_normal = (view * vec4(position, 0.0)).xyz;
+        gl_Position = proj * eye_position;
+        vEdge = 1.0 - abs(dot(normalize(eye_normal), normalize(eye_position.xyz)));
+    }";
+
+/// Fragment shader that only draws one side of the sphere at a time.
+///
+/// `opacity` is the opacity facing the camera and edge-on, and which side
+/// to draw: 1 for the near side, and -1 for the far side.
+static FRAGMENT_SRC: &str = "#version 100
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+   precision highp float;
+#else
+   precision mediump float;
+#endif
+
+    varying float vEdge;
+    uniform vec3  color;
+    uniform vec3  opacity;
+
+    void main() {
+        if (gl_FrontFacing != (opacity.z > 0.0)) {
+            discard;
+        }
+        float alpha = mix(opacity.x, opacity.y, vEdge * vEdge);
+        gl_FragColor = vec4(color, alpha);
+    }";
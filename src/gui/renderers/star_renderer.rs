@@ -0,0 +1,146 @@
+use std::f64::consts::TAU;
+
+use kiss3d::camera::Camera;
+use kiss3d::context::Context;
+use kiss3d::renderer::Renderer;
+use kiss3d::resource::{
+    AllocationType, BufferType, Effect, GPUVec, ShaderAttribute, ShaderUniform,
+};
+use nalgebra::{Matrix4, Point3, UnitQuaternion};
+
+use crate::generator::Rng;
+
+// Same stars every time
+const STAR_SEED: u64 = 0x5eed_57a2;
+const NUM_STARS: usize = 3000;
+const STAR_SIZE: f32 = 2.0;
+// Brightness of the dimmest stars; most stars are close to this
+const MIN_BRIGHTNESS: f64 = 0.15;
+// How much more red or blue than white stars can be
+const MAX_TINT: f64 = 0.15;
+
+/// Draws a fixed background of stars, infinitely far away, so that turning
+/// the camera is easier to follow.
+pub struct StarRenderer {
+    // OpenGL stuff
+    shader: Effect,
+    position: ShaderAttribute<Point3<f32>>,
+    color: ShaderAttribute<Point3<f32>>,
+    view: ShaderUniform<Matrix4<f32>>,
+    proj: ShaderUniform<Matrix4<f32>>,
+    orientation: ShaderUniform<Matrix4<f32>>,
+    // Direction and color of each star
+    directions: GPUVec<Point3<f32>>,
+    colors: GPUVec<Point3<f32>>,
+    // Rotates the stars from the root frame into focus space
+    sky_orientation: UnitQuaternion<f32>,
+}
+
+impl StarRenderer {
+    pub fn new() -> Self {
+        let mut shader = Effect::new_from_str(VERTEX_SRC, FRAGMENT_SRC);
+
+        shader.use_program();
+
+        let (directions, colors) = random_stars();
+
+        StarRenderer {
+            position: shader
+                .get_attrib::<Point3<f32>>("position")
+                .expect("Failed to get shader attribute."),
+            color: shader
+                .get_attrib::<Point3<f32>>("color")
+                .expect("Failed to get shader attribute."),
+            view: shader
+                .get_uniform::<Matrix4<f32>>("view")
+                .expect("Failed to get shader uniform."),
+            proj: shader
+                .get_uniform::<Matrix4<f32>>("proj")
+                .expect("Failed to get shader uniform."),
+            orientation: shader
+                .get_uniform::<Matrix4<f32>>("orientation")
+                .expect("Failed to get shader uniform."),
+            shader,
+            directions: GPUVec::new(directions, BufferType::Array, AllocationType::StaticDraw),
+            colors: GPUVec::new(colors, BufferType::Array, AllocationType::StaticDraw),
+            sky_orientation: UnitQuaternion::identity(),
+        }
+    }
+
+    /// Sets the rotation from the root frame to focus space, so that the
+    /// stars stay fixed relative to the root frame.
+    pub fn set_orientation(&mut self, orientation: UnitQuaternion<f32>) {
+        self.sky_orientation = orientation;
+    }
+}
+
+/// Makes up where the stars are and how they look. They're spread evenly
+/// over the sky, and mostly dim, with a few bright ones.
+fn random_stars() -> (Vec<Point3<f32>>, Vec<Point3<f32>>) {
+    let mut rng = Rng(STAR_SEED);
+    let mut directions = Vec::with_capacity(NUM_STARS);
+    let mut colors = Vec::with_capacity(NUM_STARS);
+    for _ in 0..NUM_STARS {
+        let z = rng.uniform(-1.0, 1.0);
+        let angle = rng.uniform(0.0, TAU);
+        let r = (1.0 - z * z).sqrt();
+        directions.push(Point3::new(r * angle.cos(), r * angle.sin(), z).cast());
+
+        let brightness = MIN_BRIGHTNESS + (1.0 - MIN_BRIGHTNESS) * rng.next_f64().powi(6);
+        let tint = rng.uniform(-MAX_TINT, MAX_TINT);
+        let color = Point3::new(1.0 + tint, 1.0, 1.0 - tint) * brightness;
+        colors.push(color.cast());
+    }
+    (directions, colors)
+}
+
+impl Renderer for StarRenderer {
+    fn render(&mut self, pass: usize, camera: &mut dyn Camera) {
+        self.shader.use_program();
+        self.position.enable();
+        self.color.enable();
+
+        camera.upload(pass, &mut self.proj, &mut self.view);
+        self.orientation
+            .upload(&self.sky_orientation.to_homogeneous());
+        self.position.bind_sub_buffer(&mut self.directions, 0, 0);
+        self.color.bind_sub_buffer(&mut self.colors, 0, 0);
+
+        let ctxt = Context::get();
+        ctxt.point_size(STAR_SIZE);
+        ctxt.draw_arrays(Context::POINTS, 0, self.directions.len() as i32);
+
+        self.position.disable();
+        self.color.disable();
+    }
+}
+
+/// Vertex shader that puts each star infinitely far away in its direction.
+/// Only the camera's rotation affects it, and it's pushed to the very back
+/// of the depth buffer, so anything else is drawn in front of it.
+static VERTEX_SRC: &str = "#version 100
+    attribute vec3 position;
+    attribute vec3 color;
+    varying   vec3 vColor;
+    uniform   mat4 proj;
+    uniform   mat4 view;
+    uniform   mat4 orientation;
+
+    void main() {
+        vec4 clip = proj * view * orientation * vec4(position, 0.0);
+        gl_Position = vec4(clip.xy, 0.99999 * clip.w, clip.w);
+        vColor = color;
+    }";
+
+static FRAGMENT_SRC: &str = "#version 100
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+   precision highp float;
+#else
+   precision mediump float;
+#endif
+
+    varying vec3 vColor;
+
+    void main() {
+        gl_FragColor = vec4(vColor, 1.0);
+    }";
@@ -1,5 +1,5 @@
 use kiss3d::renderer::LineRenderer;
-use nalgebra::Point3;
+use nalgebra::{Point2, Point3};
 
 pub fn path_iter_parametric<F, S>(
     f: F,
@@ -37,3 +37,15 @@ pub fn draw_path<I: Iterator<Item = Point3<f32>>>(
         prev_pt = Some(pt);
     }
 }
+
+/// Returns the distance from the point to the closest point on the segment
+/// from `a` to `b`, and how far along the segment that closest point is, from
+/// 0 at `a` to 1 at `b`.
+pub fn distance_to_segment(point: Point2<f32>, a: Point2<f32>, b: Point2<f32>) -> (f32, f32) {
+    let segment = b - a;
+    let t = match segment.norm_squared() {
+        0.0 => 0.0,
+        length_sq => ((point - a).dot(&segment) / length_sq).clamp(0.0, 1.0),
+    };
+    ((a + t * segment - point).norm(), t)
+}
@@ -0,0 +1,62 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use kiss3d::window::Window;
+
+/// Saves what's currently in the window to a PNG in the given directory,
+/// creating it if needed. The file is named after the current (UTC) time, like
+/// `screenshot-20240131-235959.png`, and returns its path.
+pub fn save_screenshot(window: &Window, dir: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = unused_path(dir, "screenshot", "png");
+    window.snap_image().save(&path).map_err(io::Error::other)?;
+    Ok(path)
+}
+
+/// Picks a file name in the directory that isn't taken yet, named after the
+/// current time, like `prefix-20240131-235959.extension`. Files saved within
+/// the same second get a number added.
+pub(super) fn unused_path(dir: &Path, prefix: &str, extension: &str) -> PathBuf {
+    let timestamp = timestamp(SystemTime::now());
+    let mut path = dir.join(format!("{}-{}.{}", prefix, timestamp, extension));
+    let mut n = 1;
+    while path.exists() {
+        n += 1;
+        path = dir.join(format!("{}-{}-{}.{}", prefix, timestamp, n, extension));
+    }
+    path
+}
+
+/// Formats the time as YYYYMMDD-HHMMSS, in UTC.
+fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+
+    // Converts days since 1970-01-01 into a date, using Howard Hinnant's
+    // civil_from_days algorithm, with eras of 400 years starting on March 1st
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
@@ -0,0 +1,185 @@
+use kiss3d::text::Font;
+use kiss3d::window::Window;
+use nalgebra::{Point2, Point3, Vector2};
+
+use super::map_camera::MapCamera;
+use super::panels::TEXT_COORDS_PER_PIXEL;
+use crate::model::timeline::Timeline;
+
+// Geometry of the bar, in window pixels at a UI scale of 1. It sits at the
+// bottom of the window, between the panels in the bottom corners.
+const LEFT_FRACTION: f32 = 0.3;
+const RIGHT_FRACTION: f32 = 0.7;
+const BOTTOM_OFFSET: f32 = 30.0;
+const TICK_HEIGHT: f32 = 6.0;
+const HANDLE_HEIGHT: f32 = 12.0;
+// How far above or below the bar a click still grabs it
+const GRAB_DISTANCE: f32 = 15.0;
+// In text coordinates
+const FONT_SIZE: f32 = 32.0;
+
+const BAR_COLOR: [f32; 3] = [0.6, 0.6, 0.6];
+const TICK_COLOR: [f32; 3] = [1.0, 0.8, 0.3];
+const MODIFICATION_COLOR: [f32; 3] = [1.0, 0.3, 1.0];
+const NOTE_COLOR: [f32; 3] = [0.3, 0.9, 1.0];
+const HANDLE_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+
+/// A bar along the bottom of the window spanning the known extent of the
+/// timeline, which can be dragged to jump to any time in it.
+#[derive(Debug)]
+pub struct TimeScrubber {
+    // The extent of the timeline, as of the last time it was drawn
+    start_time: f64,
+    end_time: f64,
+    // In window pixels, origin at the top-left, like the cursor
+    window_size: Vector2<f32>,
+    // UI scale it was last drawn at
+    scale: f32,
+}
+
+impl TimeScrubber {
+    pub fn new() -> Self {
+        Self {
+            start_time: 0.0,
+            end_time: 0.0,
+            window_size: Vector2::zeros(),
+            scale: 1.0,
+        }
+    }
+
+    fn left(&self) -> f32 {
+        LEFT_FRACTION * self.window_size.x
+    }
+
+    fn right(&self) -> f32 {
+        RIGHT_FRACTION * self.window_size.x
+    }
+
+    fn y(&self) -> f32 {
+        self.window_size.y - BOTTOM_OFFSET * self.scale
+    }
+
+    fn x_of(&self, time: f64) -> f32 {
+        let duration = self.end_time - self.start_time;
+        let fraction = if duration > 0.0 {
+            ((time - self.start_time) / duration).clamp(0.0, 1.0) as f32
+        } else {
+            0.0
+        };
+        self.left() + fraction * (self.right() - self.left())
+    }
+
+    /// Whether a click at the given cursor position should grab the bar.
+    pub fn contains(&self, cursor: Point2<f32>) -> bool {
+        (self.left()..=self.right()).contains(&cursor.x)
+            && (cursor.y - self.y()).abs() <= GRAB_DISTANCE * self.scale
+    }
+
+    /// Returns the time under the given cursor position, clamped to the ends
+    /// of the bar.
+    pub fn time_at(&self, cursor: Point2<f32>) -> f64 {
+        let width = self.right() - self.left();
+        if width <= 0.0 {
+            return self.start_time;
+        }
+        let fraction = ((cursor.x - self.left()) / width).clamp(0.0, 1.0) as f64;
+        self.start_time + fraction * (self.end_time - self.start_time)
+    }
+
+    /// Draws the bar for the timeline's known extent, with a tick at each
+    /// segment boundary, a taller one at each modification and each noted
+    /// time, and a handle at the current time, all scaled up by the given UI scale. In map mode,
+    /// planar lines go through the map camera, so it needs to be passed in.
+    pub fn draw(
+        &mut self,
+        window: &mut Window,
+        map_camera: Option<&MapCamera>,
+        timeline: &Timeline,
+        note_times: &[f64],
+        current_time: f64,
+        ui_scale: f32,
+    ) {
+        let (start_time, end_time) = (timeline.start_time(), timeline.end_time());
+        self.start_time = start_time;
+        self.end_time = end_time;
+        self.window_size = Vector2::new(window.width() as f32, window.height() as f32);
+        self.scale = ui_scale;
+        let tick_height = TICK_HEIGHT * ui_scale;
+        let handle_height = HANDLE_HEIGHT * ui_scale;
+
+        let bar_color = Point3::from(BAR_COLOR);
+        let tick_color = Point3::from(TICK_COLOR);
+        let modification_color = Point3::from(MODIFICATION_COLOR);
+        let note_color = Point3::from(NOTE_COLOR);
+        let handle_color = Point3::from(HANDLE_COLOR);
+
+        let y = self.y();
+        let (left, right) = (self.left(), self.right());
+        let scale = window.scale_factor() as f32;
+        let planar = |x: f32, y: f32| match map_camera {
+            Some(camera) => camera.window_to_planar(Point2::new(x, y)),
+            None => self.to_planar(scale, Point2::new(x, y)),
+        };
+        window.draw_planar_line(&planar(left, y), &planar(right, y), &bar_color);
+        for time in timeline.segment_boundaries() {
+            let x = self.x_of(time);
+            window.draw_planar_line(
+                &planar(x, y - tick_height),
+                &planar(x, y + tick_height),
+                &tick_color,
+            );
+        }
+        for time in timeline.modifications().map(|m| m.time) {
+            let x = self.x_of(time);
+            window.draw_planar_line(
+                &planar(x, y - handle_height),
+                &planar(x, y + tick_height),
+                &modification_color,
+            );
+        }
+        for &time in note_times {
+            let x = self.x_of(time);
+            window.draw_planar_line(
+                &planar(x, y - tick_height),
+                &planar(x, y + handle_height),
+                &note_color,
+            );
+        }
+        let x = self.x_of(current_time);
+        window.draw_planar_line(
+            &planar(x, y - handle_height),
+            &planar(x, y + handle_height),
+            &handle_color,
+        );
+
+        // Label the ends, in days since the start
+        let font = Font::default();
+        let font_size = FONT_SIZE * ui_scale;
+        let text_y = TEXT_COORDS_PER_PIXEL * (y - handle_height) - font_size;
+        let end_label = format!("{:.1}d", (end_time - start_time) / 86400.0);
+        let end_width = 0.55 * font_size * end_label.len() as f32;
+        window.draw_text(
+            "0d",
+            &Point2::new(TEXT_COORDS_PER_PIXEL * left, text_y),
+            font_size,
+            &font,
+            &bar_color,
+        );
+        window.draw_text(
+            &end_label,
+            &Point2::new(TEXT_COORDS_PER_PIXEL * right - end_width, text_y),
+            font_size,
+            &font,
+            &bar_color,
+        );
+    }
+
+    /// Converts from window pixels to the coordinates used for planar
+    /// drawing, which are centered, upwards, and unscaled by the DPI.
+    fn to_planar(&self, scale: f32, point: Point2<f32>) -> Point2<f32> {
+        Point2::new(
+            (point.x - self.window_size.x / 2.0) / scale,
+            (self.window_size.y / 2.0 - point.y) / scale,
+        )
+    }
+}
@@ -1,23 +1,123 @@
+use std::cell::RefCell;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::f64::consts::{FRAC_PI_2, PI};
+use std::fmt::Display;
+use std::path::Path;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::time::Instant;
 
 use kiss3d::camera::Camera;
+use kiss3d::light::Light;
 use kiss3d::planar_camera::PlanarCamera;
 use kiss3d::post_processing::PostProcessingEffect;
 use kiss3d::renderer::Renderer;
+use kiss3d::resource::Material;
 use kiss3d::scene::SceneNode;
 use kiss3d::window::Window;
-use nalgebra::{Isometry3, Point3, Translation3, Unit};
+use nalgebra::{Isometry3, Point2, Point3, Translation3, Unit, UnitQuaternion, Vector2, Vector3};
 
+use super::autosave::{Session, SessionChange};
+use super::bookmarks::{Bookmark, Bookmarks};
+use super::bundle::PlanBundle;
 use super::camera::ZoomableCamera;
 use super::controller::Controller;
-use super::renderers::{CompoundRenderer, OrbitPatch};
-use crate::astro::BareOrbit;
+use super::map_camera::MapCamera;
+use super::node_editor::{NodeEditor, NodeField, TimeReference};
+use super::notes::{NoteTarget, Notes};
+use super::orbit_editor::{OrbitEditor, OrbitField};
+use super::panels::{Corner, Panel, PanelLayout, TEXT_COORDS_PER_PIXEL};
+use super::renderers::{
+    ApsisKind, ApsisMarker, BodyMaterial, Canvas, ColorGradient, CompoundRenderer, Fog, LineStyle,
+    OrbitPatch, OrbitRenderer,
+};
+use super::scrubber::TimeScrubber;
+use crate::astro::{BareOrbit, CartesianState, PhysicalOrbit, TimedOrbit};
 use crate::gui::renderers::MarkerType;
-use crate::model::orrery::{Body, BodyID, Frame, Orrery, Ship, ShipID};
-use crate::model::timeline::Timeline;
+use crate::math::frame::FrameTransform;
+use crate::model::arrival::mission_ledger;
+use crate::model::circularize::{add_circularization, Apsis};
+use crate::model::departure::{
+    ejection_angle, hohmann_phase_angle, ideal_ejection_angle, phase_angle,
+};
+use crate::model::events::{search_for_apoapsis, search_for_periapsis, Event, EventData};
+use crate::model::moments::{find_interesting_moments, Moment, MomentKind};
+use crate::model::orrery::{
+    orbit_frame, Body, BodyEdit, BodyID, Frame, Maneuver, ObjectState, OrbitElements, Orrery,
+    ShadowRegion, Ship, ShipID, ShipPlacement, SoiModel,
+};
+use crate::model::plane_change::{add_plane_change, Node};
+use crate::model::readouts::CustomReadout;
+use crate::model::targeting::{closest_approach, next_encounter_b_plane, ApproachTarget};
+use crate::model::timeline::{Timeline, Trajectory, TrajectoryPatch};
+use crate::model::uncertainty::{ElementUncertainty, Envelope, UncertaintyEnvelope};
+use crate::model::visits::VisitLog;
 
 const TEST_SHIP_SIZE: f32 = 1.0;
 
+/// How far ahead of the current time to search for events, so that we can
+/// draw the upcoming trajectory of each ship.
+const PREDICTION_HORIZON: f64 = 30.0 * 24.0 * 3600.0;
+/// The maximum number of conic patches to draw for each ship, including the
+/// current one.
+const MAX_PATCHES_DRAWN: usize = 4;
+/// When orbits fade out toward the future, how far ahead they're fully faded.
+const ORBIT_FADE_TIME: f64 = PREDICTION_HORIZON;
+/// How many upcoming events to list on the HUD.
+const MAX_LISTED_EVENTS: usize = 6;
+/// How many bookmarks to list on the HUD; each gets a number key to restore it.
+const MAX_LISTED_BOOKMARKS: usize = 9;
+/// How long notifications stay on screen, and how fast they flash, in seconds.
+const NOTIFICATION_DURATION: f32 = 4.0;
+const NOTIFICATION_FLASH_PERIOD: f32 = 0.5;
+/// Font sizes of notifications, and of the text next to markers and orbits,
+/// in text coordinates at a UI scale of 1.
+const NOTIFICATION_FONT_SIZE: f32 = 60.0;
+const LABEL_FONT_SIZE: f32 = 40.0;
+/// How many upcoming interesting moments to list on the HUD.
+const MAX_LISTED_MOMENTS: usize = 4;
+/// How many upcoming notes on times to list on the HUD.
+const MAX_LISTED_NOTES: usize = 4;
+/// How many of the focused ship's upcoming burns to list on the HUD.
+const MAX_LISTED_BURNS: usize = 5;
+/// How many of the latest visits to the focused body's SOI to list on the HUD.
+const MAX_LISTED_VISITS: usize = 5;
+/// Interesting moments are looked for again when the timeline has been
+/// extended this much further, in seconds.
+const MOMENTS_REFRESH_INTERVAL: f64 = 24.0 * 3600.0;
+/// How long it takes the camera to move to a newly focused object, in seconds.
+const FOCUS_TRANSITION_DURATION: f32 = 0.4;
+/// Color of the preview of a ship that's being placed.
+const PLACEMENT_COLOR: [f32; 3] = [0.3, 1.0, 0.3];
+// How bright the extremes of an uncertain placement are, compared to it
+const UNCERTAINTY_BRIGHTNESS: f32 = 0.4;
+// Size of the markers at burns, SOI changes and impacts, in window pixels at a
+// UI scale of 1. Clicks this close to one pick it.
+const EVENT_MARKER_SIZE: f32 = 12.0;
+// How close the cursor has to be to an orbit to hover over it, in window pixels
+const ORBIT_HOVER_DISTANCE: f32 = 6.0;
+// How far a hovered orbit's color is moved towards white
+const ORBIT_HIGHLIGHT: f32 = 0.6;
+// How far past the focused orbit's nodes its parent's equator is drawn
+const EQUATOR_RING_SCALE: f64 = 1.2;
+const NUM_EQUATOR_SEGMENTS: usize = 128;
+// Colors of the parent's equator, the node line (the same as the ascending
+// node axis), and the reference direction longitudes are measured from
+const EQUATOR_RING_BRIGHTNESS: f32 = 0.4;
+const NODE_LINE_COLOR: [f32; 3] = [0.0, 0.7, 0.0];
+const REFERENCE_DIRECTION_COLOR: [f32; 3] = [0.4, 0.4, 0.4];
+// How long a ship's trail is, unless told otherwise, and how many points it's
+// drawn with. It fades out with age, down to the given brightness at its end.
+const DEFAULT_TRAIL_DURATION: f64 = 6.0 * 3600.0;
+const NUM_TRAIL_POINTS: usize = 256;
+const MIN_TRAIL_BRIGHTNESS: f32 = 0.1;
+// Color of a pinned trajectory, which new plans are compared against
+const GHOST_COLOR: [f32; 3] = [0.5, 0.5, 0.5];
+// Numerically integrated patches have no conic, so they're drawn as a
+// polyline through this many points
+const NUM_ARC_POINTS: usize = 128;
+
 pub struct View {
     // Object state
     timeline: Timeline,
@@ -27,18 +127,252 @@ pub struct View {
     ship_objects: HashMap<ShipID, SceneNode>,
     // Camera
     camera: ZoomableCamera,
+    // Top-down camera used instead of the 3D one in map mode
+    map_camera: MapCamera,
+    map_mode: bool,
+    orbit_coloring: OrbitColoring,
+    // Whether to show every body's SOI, instead of just the focused one's,
+    // and whether to draw them with lines of latitude and longitude
+    show_all_sois: bool,
+    soi_wireframe: bool,
+    // Whether to fade out orbits, lines and markers far beyond the focus
+    fog: bool,
+    // Whether to show the focused orbit's parent's equator and node line
+    show_orbit_reference: bool,
+    // Whether to show where each ship has been, and how far back to go
+    show_trails: bool,
+    trail_duration: f64,
+    // How much bigger than usual markers and text are, e.g., for high-DPI
+    // displays
+    ui_scale: f32,
     camera_focus: CameraFocus,
     ship_camera_inertial: bool,
+    // The frame we were focused on before the focus last changed, and when it
+    // changed, while the camera is still moving away from it
+    focus_transition: Option<(Frame, Instant)>,
+    // Object that the focused object's orbit is compared against
+    target: Option<FocusPoint>,
+    speed_mode: SpeedMode,
+    // Apsides of the focused object's orbits, along with the body they're
+    // around. Refreshed every frame when the orbits are drawn.
+    apsis_markers: Vec<(ApsisMarker, BodyID)>,
+    // Upcoming burns, SOI changes and impacts along the drawn trajectories,
+    // with the body the ship's around, and where they are in focus space.
+    // Also refreshed every frame.
+    event_markers: Vec<(Event, BodyID, Point3<f32>)>,
+    // Event whose marker was last clicked on, and the body the ship's around
+    // then, to show the details of
+    selected_event: Option<(Event, BodyID)>,
+    // Bar for dragging through time
+    scrubber: TimeScrubber,
+    bookmarks: Bookmarks,
+    // Extra readouts for the focused object, given by the user
+    custom_readouts: Vec<CustomReadout>,
+    // Interesting moments in the timeline, along with the number of events
+    // and end time of the timeline when they were found
+    moments: Vec<Moment>,
+    moments_computed_for: Option<(usize, f64)>,
+    // Message to flash on the screen, and when it was first shown
+    notification: Option<(String, Instant)>,
+    // New ship being placed, which is previewed until it's confirmed
+    placement: Option<ShipPlacement>,
+    // How far off the placement's elements might be, and whether the
+    // placement keys are changing that instead of the elements themselves
+    placement_uncertainty: ElementUncertainty,
+    editing_uncertainty: bool,
+    // For ships that were placed with uncertain elements, how much their
+    // orbits and encounters could vary
+    uncertainty_envelopes: HashMap<ShipID, UncertaintyEnvelope>,
+    // Body whose properties are being edited, until the edit is applied
+    inspector: Option<BodyInspector>,
+    // Maneuver node whose values are being typed in
+    node_editor: Option<NodeEditor>,
+    // New orbital elements being typed in for a body or ship
+    orbit_editor: Option<OrbitEditor>,
+    // A ship's trajectory as it was when it was pinned, drawn alongside its
+    // current one so changes to its plan can be compared against it
+    ghost_trajectory: Option<Trajectory>,
+    // Changes made to the timeline since it was given to us, in order, so the
+    // session can be saved and redone
+    session_changes: Vec<SessionChange>,
+    // Notes the user has attached to ships, burns and times, and the one
+    // being typed in
+    notes: Notes,
+    note_editor: Option<NoteEditor>,
+    // Whether this is someone else's plan, opened to look at, which can't be
+    // changed
+    read_only: bool,
+    // The HUD as it was last drawn, and which of its panels lists the ships,
+    // so that clicks on it can be handled
+    hud: PanelLayout,
+    ships_panel_idx: Option<usize>,
+    orbit_menu_panel_idx: Option<usize>,
+    point_panel_idx: Option<usize>,
+    // Orbit under the cursor, found while drawing the orbits, and highlighted
+    // on the next frame
+    orbit_hover: Option<OrbitHover>,
+    // Menu of things to do at a point picked on an orbit, while it's open
+    orbit_menu: Option<OrbitMenu>,
+    // Point on an orbit whose state is shown on the HUD
+    shown_point: Option<OrbitPoint>,
     // Misc
     renderer: CompoundRenderer,
 }
 
-#[derive(Debug, Clone, Copy)]
+/// A note being typed in, and the things it could be attached to.
+struct NoteEditor {
+    choices: Vec<NoteTarget>,
+    selected: usize,
+    text: String,
+}
+
+/// A body being edited, and the changes that will be made to it.
+struct BodyInspector {
+    body_id: BodyID,
+    edit: BodyEdit,
+    selected: InspectorField,
+}
+
+/// An orbit near the cursor, and what to show in its tooltip.
+struct OrbitHover {
+    owner: FocusPoint,
+    // Which of a ship's drawn patches it is; always 0 for bodies
+    patch_idx: usize,
+    // From the cursor, in window pixels
+    distance: f32,
+    text: String,
+    color: Point3<f32>,
+    // The point on the orbit closest to the cursor, if it's on the part
+    // that's drawn
+    point: Option<OrbitPoint>,
+}
+
+/// A point on an orbit, picked with the cursor.
+#[derive(Debug, Clone)]
+struct OrbitPoint {
+    owner: FocusPoint,
+    parent_id: BodyID,
+    time: f64,
+    // In the parent's inertial frame
+    position: Vector3<f64>,
+    velocity: Vector3<f64>,
+}
+
+/// Where the focus, or the body it's around, is relative to the target.
+struct Phasing {
+    /// Whichever of the focus and the bodies it's around orbits the same
+    /// body as the target
+    mover: FocusPoint,
+    /// Phase angle from the mover to the target, now and for a Hohmann
+    /// transfer, in radians
+    current: f64,
+    hohmann: f64,
+    /// Whether the target is further out than the mover
+    outward: bool,
+}
+
+/// The menu of things to do at a point on an orbit, opened where it was
+/// picked.
+struct OrbitMenu {
+    point: OrbitPoint,
+    // Where the menu was opened, in window pixels
+    position: Point2<f32>,
+    items: Vec<OrbitMenuItem>,
+}
+
+/// Something to do at a point picked on an orbit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrbitMenuItem {
+    /// Plan a new burn there, in the maneuver node editor
+    AddManeuver,
+    /// Jump to when the orbiting object gets there
+    Warp,
+    /// Show where the orbiting object is there, and how fast it's going
+    ShowState,
+    /// Plan a burn to circularize at the next pass through the apsis
+    Circularize(Apsis),
+    /// Plan a burn into the target's plane at the next pass through the node
+    MatchPlane(Node),
+}
+
+impl OrbitMenuItem {
+    fn label(&self) -> &'static str {
+        match self {
+            OrbitMenuItem::AddManeuver => "Add maneuver here",
+            OrbitMenuItem::Warp => "Warp here",
+            OrbitMenuItem::ShowState => "Show state at this point",
+            OrbitMenuItem::Circularize(Apsis::Periapsis) => "Circularize at next periapsis",
+            OrbitMenuItem::Circularize(Apsis::Apoapsis) => "Circularize at next apoapsis",
+            OrbitMenuItem::MatchPlane(Node::Ascending) => "Match target's plane at next AN",
+            OrbitMenuItem::MatchPlane(Node::Descending) => "Match target's plane at next DN",
+        }
+    }
+}
+
+/// What the brightness along each orbit shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OrbitColoring {
+    Uniform,
+    TimeUntil,
+    Speed,
+    /// Only the focused ship's orbit is colored, by how close it gets to the
+    /// edge of its parent's SOI; the rest are uniform.
+    SoiFraction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InspectorField {
+    Mu,
+    Radius,
+    Soi,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FocusPoint {
     Body(BodyID),
     Ship(ShipID),
 }
 
+/// Which frame the speed on the HUD is measured in, like the modes of KSP's
+/// navball.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpeedMode {
+    /// Relative to the parent body's inertial frame.
+    #[default]
+    Orbit,
+    /// Relative to the parent body's surface, which rotates with it.
+    Surface,
+    /// Relative to the target.
+    Target,
+}
+
+impl FromStr for SpeedMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "orbit" => Ok(SpeedMode::Orbit),
+            "surface" => Ok(SpeedMode::Surface),
+            "target" => Ok(SpeedMode::Target),
+            _ => Err(format!(
+                "Unknown speed mode {:?}; expected orbit, surface, or target",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for SpeedMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            SpeedMode::Orbit => "orbit",
+            SpeedMode::Surface => "surface",
+            SpeedMode::Target => "target",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 pub struct CameraFocus {
     focus_points: Vec<FocusPoint>,
     focus_idx: usize,
@@ -82,12 +416,29 @@ impl CameraFocus {
     pub fn point(&self) -> FocusPoint {
         self.focus_points[self.focus_idx]
     }
+
+    pub fn points(&self) -> &[FocusPoint] {
+        &self.focus_points
+    }
+
+    /// Focuses on the given point, if it's one of the options. Returns whether
+    /// it was.
+    pub fn set_point(&mut self, point: FocusPoint) -> bool {
+        match self.focus_points.iter().position(|p| *p == point) {
+            Some(idx) => {
+                self.focus_idx = idx;
+                true
+            }
+            None => false,
+        }
+    }
 }
 
 impl View {
-    pub fn new(timeline: Timeline, window: &mut Window) -> Self {
+    pub fn new(mut timeline: Timeline, window: &mut Window) -> Self {
         let start_time = timeline.start_time();
-        let orrery = timeline.get_orrery_at(start_time).unwrap().clone();
+        timeline.extend_until(start_time + PREDICTION_HORIZON);
+        let orrery = timeline.get_orrery_at(start_time).unwrap();
 
         // Set up camera
         // Initial distance doesn't matter, since we're about to call fix_camera_zoom
@@ -95,10 +446,19 @@ impl View {
         let camera_focus = CameraFocus::new(&orrery);
         let ship_camera_inertial = true;
 
-        // Create objects for bodies
+        // Create objects for bodies. The root body is the star that lights
+        // everything else, so it isn't shaded itself.
+        let shaded_material: Rc<RefCell<Box<dyn Material>>> =
+            Rc::new(RefCell::new(Box::new(BodyMaterial::new(true))));
+        let star_material: Rc<RefCell<Box<dyn Material>>> =
+            Rc::new(RefCell::new(Box::new(BodyMaterial::new(false))));
         let mut body_spheres = HashMap::new();
         for body in orrery.bodies() {
-            let sphere = Self::create_body_object(window, body);
+            let material = match orrery.orbit_of_body(body.id) {
+                Some(_) => shaded_material.clone(),
+                None => star_material.clone(),
+            };
+            let sphere = Self::create_body_object(window, body, material);
             body_spheres.insert(body.id, sphere);
         }
 
@@ -116,8 +476,49 @@ impl View {
             body_spheres,
             ship_objects,
             camera,
+            map_camera: MapCamera::new(),
+            map_mode: false,
+            orbit_coloring: OrbitColoring::Uniform,
+            show_all_sois: false,
+            soi_wireframe: false,
+            fog: false,
+            show_orbit_reference: false,
+            show_trails: false,
+            trail_duration: DEFAULT_TRAIL_DURATION,
+            ui_scale: 1.0,
             camera_focus,
             ship_camera_inertial,
+            focus_transition: None,
+            target: None,
+            speed_mode: SpeedMode::default(),
+            apsis_markers: vec![],
+            event_markers: vec![],
+            selected_event: None,
+            scrubber: TimeScrubber::new(),
+            bookmarks: Bookmarks::new(),
+            custom_readouts: vec![],
+            moments: vec![],
+            moments_computed_for: None,
+            notification: None,
+            placement: None,
+            placement_uncertainty: ElementUncertainty::default(),
+            editing_uncertainty: false,
+            uncertainty_envelopes: HashMap::new(),
+            inspector: None,
+            node_editor: None,
+            orbit_editor: None,
+            ghost_trajectory: None,
+            session_changes: vec![],
+            notes: Notes::new(),
+            note_editor: None,
+            read_only: false,
+            hud: PanelLayout::new(),
+            ships_panel_idx: None,
+            orbit_hover: None,
+            orbit_menu: None,
+            shown_point: None,
+            orbit_menu_panel_idx: None,
+            point_panel_idx: None,
             renderer: CompoundRenderer::new(),
         };
         simulation.fix_camera_zoom();
@@ -126,40 +527,127 @@ impl View {
         simulation
     }
 
-    fn create_body_object(window: &mut Window, body: &Body) -> SceneNode {
+    fn create_body_object(
+        window: &mut Window,
+        body: &Body,
+        material: Rc<RefCell<Box<dyn Material>>>,
+    ) -> SceneNode {
         // Make the sphere that represents the body
         let mut sphere = window.add_sphere(body.info.radius);
-        let color = &body.info.color;
-        sphere.set_color(color.x, color.y, color.z);
+        sphere.set_material(material);
+        match &body.info.texture {
+            // kiss3d panics on textures it can't load, so check for the easy
+            // mistake first
+            Some(path) if Path::new(path).is_file() => {
+                sphere.set_texture_from_file(Path::new(path), path);
+            }
+            texture => {
+                if let Some(path) = texture {
+                    println!("Couldn't find texture {} for {}", path, body.info.name);
+                }
+                let color = &body.info.color;
+                sphere.set_color(color.x, color.y, color.z);
+            }
+        }
         sphere
     }
 
-    fn create_ship_object(window: &mut Window, _: &Ship) -> SceneNode {
+    fn create_ship_object(window: &mut Window, ship: &Ship) -> SceneNode {
         // Make the cube that represents the ship
         let mut cube = window.add_cube(TEST_SHIP_SIZE, TEST_SHIP_SIZE, TEST_SHIP_SIZE);
-        cube.set_color(1.0, 1.0, 1.0);
+        let color = ship_color(ship);
+        cube.set_color(color.x, color.y, color.z);
         cube
     }
 
     pub fn update_state_by(&mut self, timestep: f64) {
+        self.set_time(f64::max(self.time + timestep, 0.0));
+    }
+
+    /// Like [View::update_state_by], but if any ship changes SOI along the
+    /// way, stops exactly at the first such change, and shows a notification
+    /// about it. Returns whether we stopped early.
+    pub fn update_state_until_soi_change(&mut self, timestep: f64) -> bool {
+        let old_time = self.time;
+        let new_time = f64::max(self.time + timestep, 0.0);
+        self.timeline.extend_until(new_time + PREDICTION_HORIZON);
+
+        // Don't count an event we're already sitting on, or we'd never get
+        // past it
+        let is_crossed = |time: f64| {
+            if new_time >= old_time {
+                old_time < time && time <= new_time
+            } else {
+                new_time <= time && time < old_time
+            }
+        };
+        let crossed: Vec<Event> = self
+            .timeline
+            .events()
+            .filter(|e| {
+                is_crossed(e.point.time)
+                    && matches!(e.data, EventData::EnteringSOI(_) | EventData::ExitingSOI(_))
+            })
+            .cloned()
+            .collect();
+        // Events are in chronological order, so take the first one we reach
+        let event = if new_time >= old_time {
+            crossed.first()
+        } else {
+            crossed.last()
+        };
+
+        match event {
+            Some(event) => {
+                self.set_time(event.point.time);
+                self.notification = Some((
+                    format!("Ship {}: {}", event.ship_id.0, self.describe_event(event)),
+                    Instant::now(),
+                ));
+                true
+            }
+            None => {
+                self.set_time(new_time);
+                false
+            }
+        }
+    }
+
+    /// Whether the given cursor position, in window pixels, is over the time
+    /// scrubber.
+    pub fn is_over_scrubber(&self, cursor: Point2<f32>) -> bool {
+        self.scrubber.contains(cursor)
+    }
+
+    /// Jumps to the time under the given cursor position on the scrubber.
+    pub fn scrub_to(&mut self, cursor: Point2<f32>) {
+        let time = self.scrubber.time_at(cursor);
+        self.set_time(f64::max(time, self.timeline.start_time()));
+    }
+
+    fn set_time(&mut self, time: f64) {
         // Update the universe, then move scene objects to the right places
-        self.time = f64::max(self.time + timestep, 0.0);
-        self.timeline.extend_until(self.time);
+        self.time = time;
+        if let Some(placement) = &mut self.placement {
+            placement.move_epoch(&self.orrery, time);
+        }
+        self.timeline.extend_until(self.time + PREDICTION_HORIZON);
         self.orrery = self
             .timeline
             .get_orrery_at(self.time)
-            .expect("Lookup before universe start")
-            .clone();
+            .expect("Lookup before universe start");
         self.update_scene_objects();
     }
 
     pub fn camera_focus_next(&mut self) {
+        self.start_focus_transition();
         self.camera_focus.next();
         self.fix_camera_zoom();
         self.update_scene_objects();
     }
 
     pub fn camera_focus_prev(&mut self) {
+        self.start_focus_transition();
         self.camera_focus.prev();
         self.fix_camera_zoom();
         self.update_scene_objects();
@@ -171,308 +659,3722 @@ impl View {
         self.update_scene_objects();
     }
 
-    fn fix_camera_zoom(&mut self) {
-        let dist = match self.camera_focus.point() {
-            FocusPoint::Body(id) => self.orrery.get_body(id).info.radius * 2.0,
-            FocusPoint::Ship(_) => TEST_SHIP_SIZE * 2.0,
+    /// Orbits the camera around the focus, like dragging with the mouse.
+    pub fn rotate_camera(&mut self, dtheta: f32, dphi: f32) {
+        self.camera.rotate(dtheta, dphi);
+    }
+
+    /// Moves the camera the given factor further from the focus.
+    pub fn zoom_camera(&mut self, factor: f32) {
+        self.camera.zoom(factor);
+    }
+
+    /// Switches the HUD speed between orbital, surface, and target-relative.
+    pub fn cycle_speed_mode(&mut self) {
+        self.speed_mode = match self.speed_mode {
+            SpeedMode::Orbit => SpeedMode::Surface,
+            SpeedMode::Surface => SpeedMode::Target,
+            SpeedMode::Target => SpeedMode::Orbit,
         };
-        self.camera.set_min_distance(dist);
+        println!("Showing {} speed", self.speed_mode);
     }
 
-    fn update_scene_objects(&mut self) {
-        // does some nice conversions
-        fn set_position_helper(obj: &mut SceneNode, position: Point3<f64>) {
-            let position: Point3<f32> = nalgebra::convert(position);
-            obj.set_local_translation(Translation3::from(position));
+    /// Cycles through possible targets, skipping the focused object, and
+    /// ending with no target at all.
+    pub fn target_next(&mut self) {
+        let points = self.camera_focus.points();
+        let mut idx = match self.target {
+            None => 0,
+            Some(target) => points.iter().position(|p| *p == target).unwrap() + 1,
+        };
+        if points.get(idx) == Some(&self.camera_focus.point()) {
+            idx += 1;
         }
+        self.target = points.get(idx).copied();
 
-        // TODO apply rotations too!
-        let camera_frame = self.focused_object_frame();
-        for (id, sphere) in self.body_spheres.iter_mut() {
-            let state = self.orrery.get_body_state(*id, self.time);
-            let position = state.get_position(camera_frame, self.time);
-            set_position_helper(sphere, position);
+        match self.target {
+            Some(target) => println!("Targeting {}", self.point_name(target)),
+            None => println!("Target cleared"),
         }
+    }
 
-        for (id, cube) in self.ship_objects.iter_mut() {
-            let state = self.orrery.get_ship_state(*id, self.time);
-            let position = state.get_position(camera_frame, self.time);
-            set_position_helper(cube, position);
+    /// Turns the focused ship's sail, starting now. Everything planned
+    /// afterwards is recomputed.
+    pub fn turn_sail(&mut self, delta_cone: f64, delta_clock: f64) {
+        if !self.check_editable() {
+            return;
         }
+        let ship = match self.camera_focus.point() {
+            FocusPoint::Body(_) => return,
+            FocusPoint::Ship(id) => self.orrery.get_ship(id),
+        };
+        let mut schedule = match &ship.sail {
+            Some(sail) => sail.schedule.clone(),
+            None => {
+                println!("Ship {} doesn't have a sail", ship.id.0);
+                return;
+            }
+        };
+
+        let mut attitude = schedule.attitude_at(self.time);
+        attitude.cone = (attitude.cone + delta_cone).clamp(0.0, FRAC_PI_2);
+        attitude.clock = (attitude.clock + delta_clock).rem_euclid(2.0 * PI);
+        schedule.set_attitude(self.time, attitude);
+        println!(
+            "Sail attitude from {} is cone {:.0}, clock {:.0}",
+            format_seconds(self.time),
+            attitude.cone.to_degrees(),
+            attitude.clock.to_degrees()
+        );
+
+        let ship_id = ship.id;
+        self.timeline.set_sail_schedule(ship_id, schedule.clone());
+        self.session_changes
+            .push(SessionChange::SetSailSchedule(ship_id, schedule));
+        self.update_state_by(0.0);
     }
 
-    fn focused_object_frame(&self) -> Frame {
-        match self.camera_focus.point() {
-            FocusPoint::Body(id) => Frame::BodyInertial(id),
-            FocusPoint::Ship(id) => match self.ship_camera_inertial {
-                true => Frame::ShipInertial(id),
-                false => Frame::ShipOrbital(id),
-            },
+    pub fn set_bookmarks(&mut self, bookmarks: Bookmarks) {
+        self.bookmarks = bookmarks;
+    }
+
+    /// Opens a plan bundle's bookmarks and notes, and stops the plan from
+    /// being changed. Its timeline should already be the view's.
+    pub fn open_bundle(&mut self, bundle: PlanBundle) {
+        self.bookmarks = Bookmarks::unsaved(bundle.bookmarks);
+        for note in bundle.notes {
+            self.notes.set(note.target, &note.text);
         }
+        self.read_only = true;
     }
 
-    fn transform_to_focus_space(&self, frame: Frame) -> Isometry3<f32> {
-        let transform = self
-            .orrery
-            .convert_frames(frame, self.focused_object_frame(), self.time);
-        nalgebra::convert(*transform.isometry())
+    /// Packs up the plan, to be opened by someone else, unless it has
+    /// something a bundle can't keep.
+    pub fn plan_bundle(&self) -> Result<PlanBundle, String> {
+        PlanBundle::new(
+            &self.timeline,
+            &self.session_changes,
+            self.bookmarks.iter().cloned().collect(),
+            self.notes.iter().cloned().collect(),
+        )
     }
 
-    // the big boy
-    pub fn prerender_scene(&mut self, window: &mut Window, controller: &Controller) {
-        // Draw a bunch of stuff
-        self.renderer.draw_grid(self.camera.distance());
-        self.draw_orbits();
-        self.draw_orbital_axes();
-        self.draw_soi();
-        self.draw_markers();
+    /// Returns whether the plan can be changed, and says why not if it can't.
+    pub fn check_editable(&mut self) -> bool {
+        if self.read_only {
+            self.notification = Some((String::from("This plan is read-only"), Instant::now()));
+        }
+        !self.read_only
+    }
 
-        // Draw text
-        use nalgebra::Point2;
-        let default_font = kiss3d::text::Font::default();
-        let text_color = Point3::new(1.0, 1.0, 1.0);
-        window.draw_text(
-            &self.left_hand_text(),
-            &Point2::origin(),
-            60.0,
-            &default_font,
-            &text_color,
-        );
-        window.draw_text(
-            &self.time_summary_text(controller.timestep(), controller.fps()),
-            // no idea why i have to multiply by 2.0, but there it is
-            &Point2::new(window.width() as f32 * 2.0 - 600.0, 0.0),
-            60.0,
-            &default_font,
-            &text_color,
-        );
+    pub fn set_custom_readouts(&mut self, readouts: Vec<CustomReadout>) {
+        self.custom_readouts = readouts;
     }
 
-    fn draw_orbits(&mut self) {
-        for orbit in self.orrery.body_orbits() {
-            let secondary = orbit.secondary();
+    /// Sets how far back, in seconds, ships' trails go.
+    pub fn set_trail_duration(&mut self, duration: f64) {
+        self.trail_duration = duration;
+    }
 
-            let color = secondary.info.color;
-            let frame = Frame::BodyInertial(orbit.primary().id);
-            self.renderer.draw_orbit(
-                OrbitPatch::new(&orbit, self.time),
-                color,
-                self.transform_to_focus_space(frame),
-            );
+    /// Sets how much bigger than usual markers and text are drawn.
+    pub fn set_ui_scale(&mut self, scale: f32) {
+        self.ui_scale = scale;
+        self.renderer.set_ui_scale(scale);
+    }
+
+    /// Sets whether spheres and lines are drawn in batches, with instancing.
+    pub fn set_instanced_rendering(&mut self, instanced: bool) {
+        self.renderer.set_instanced(instanced);
+    }
+
+    /// Saves the current view as a bookmark with the given name, replacing
+    /// any bookmark already named that.
+    pub fn save_bookmark(&mut self, name: String) {
+        let bookmark = self.current_view(name);
+        println!("Saving bookmark {}", bookmark.name);
+        if let Err(e) = self.bookmarks.add(bookmark) {
+            println!("Couldn't save bookmarks: {}", e);
         }
+    }
 
-        for ship in self.orrery.ships() {
-            let orbit = self.orrery.orbit_of_ship(ship.id);
-            let color = Point3::new(1.0, 1.0, 1.0);
-            let frame = Frame::BodyInertial(orbit.primary().id);
-            self.renderer.draw_orbit(
-                OrbitPatch::new(&orbit, self.time),
-                color,
-                self.transform_to_focus_space(frame),
+    /// Restores the view saved in the bookmark at the given position in the
+    /// bookmarks panel, if there is one.
+    pub fn restore_bookmark(&mut self, idx: usize) {
+        let bookmark = match self.bookmarks.get(idx) {
+            Some(bookmark) => bookmark.clone(),
+            None => return,
+        };
+        self.restore_view(&bookmark);
+    }
+
+    fn restore_view(&mut self, bookmark: &Bookmark) {
+        if !self.camera_focus.set_point(bookmark.focus) {
+            println!(
+                "Bookmark {} is focused on something that doesn't exist",
+                bookmark.name
             );
         }
+        self.target = bookmark
+            .target
+            .filter(|t| self.camera_focus.points().contains(t));
+        self.ship_camera_inertial = bookmark.ship_camera_inertial;
+        self.speed_mode = bookmark.speed_mode;
+        self.fix_camera_zoom();
+        self.camera
+            .set_angles(bookmark.camera_angles.0, bookmark.camera_angles.1);
+        self.camera.set_distance(bookmark.camera_distance);
+        self.set_time(f64::max(bookmark.time, self.timeline.start_time()));
     }
 
-    fn draw_orbital_axes(&mut self) {
-        // TODO: this renders the axes at the center of the body; I think we probably
-        // want center of the orbit instead. But only do that if you're doing
-        // this only for the focused body.
-        for orbit in self.orrery.body_orbits() {
-            let body = orbit.secondary();
+    /// A bookmark of the current view, with the given name.
+    fn current_view(&self, name: String) -> Bookmark {
+        Bookmark {
+            name,
+            time: self.time,
+            focus: self.camera_focus.point(),
+            target: self.target,
+            speed_mode: self.speed_mode,
+            camera_angles: self.camera.angles(),
+            camera_distance: self.camera.distance(),
+            ship_camera_inertial: self.ship_camera_inertial,
+        }
+    }
 
-            let axes = [
-                (orbit.periapse_vector(), Point3::new(1.0, 0.0, 0.0)),
-                (orbit.asc_node_vector(), Point3::new(0.0, 1.0, 0.0)),
-                (orbit.normal_vector(), Point3::new(0.0, 0.0, 1.0)),
-            ]
-            .map(|(v, color)| {
-                let v = Unit::new_unchecked(nalgebra::convert(v.into_inner()));
-                (v, color)
-            });
+    /// The changes made to the timeline so far, and the current view.
+    pub fn session(&self) -> Session {
+        Session {
+            changes: self.session_changes.clone(),
+            notes: self.notes.iter().cloned().collect(),
+            view: Some(self.current_view(String::from("autosave"))),
+        }
+    }
 
-            self.renderer.draw_axes(
-                &axes,
-                2.0 * body.info.radius,
-                self.transform_to_focus_space(Frame::BodyInertial(body.id)),
-            );
+    /// Redoes the changes from an earlier session, and goes back to what it
+    /// was looking at. Changes that can't be made anymore are skipped.
+    pub fn restore_session(&mut self, session: Session) {
+        for change in session.changes {
+            match change.apply(&mut self.timeline) {
+                Ok(()) => self.session_changes.push(change),
+                Err(e) => println!("Couldn't redo {:?}: {}", change, e),
+            }
         }
+        for note in session.notes {
+            self.notes.set(note.target, &note.text);
+        }
+        self.set_time(self.time);
+        self.camera_focus = CameraFocus::new(&self.orrery);
+        match session.view {
+            Some(view) => self.restore_view(&view),
+            None => self.fix_camera_zoom(),
+        }
+        println!(
+            "Restored {} changes from the last session",
+            self.session_changes.len()
+        );
     }
 
-    fn draw_soi(&mut self) {
-        let soi_id = match self.camera_focus.point() {
-            FocusPoint::Body(id) => id,
-            FocusPoint::Ship(id) => self.orrery.get_ship(id).parent_id(),
+    /// Jumps to the next interesting moment after the current time, or the
+    /// last one before it, focusing on what makes it interesting.
+    pub fn jump_to_moment(&mut self, forwards: bool) {
+        self.refresh_moments();
+        let moment = if forwards {
+            self.moments.iter().find(|m| m.time > self.time)
+        } else {
+            self.moments.iter().rev().find(|m| m.time < self.time)
+        };
+        let moment = match moment {
+            Some(moment) => moment.clone(),
+            None => return,
         };
 
-        let soi_radius = match self.orrery.get_soi_radius(soi_id) {
-            Some(r) => r,
-            None => return, // early return if Sun
+        let (focus, target) = match moment.kind {
+            MomentKind::ClosestApproach {
+                ship_id, target, ..
+            } => {
+                let target = match target {
+                    ApproachTarget::Body(id) => FocusPoint::Body(id),
+                    ApproachTarget::Ship(id) => FocusPoint::Ship(id),
+                };
+                (FocusPoint::Ship(ship_id), Some(target))
+            }
+            MomentKind::Eclipse { ship_id, .. } | MomentKind::RecordAltitude { ship_id, .. } => {
+                (FocusPoint::Ship(ship_id), None)
+            }
+            MomentKind::Alignment { parent_id, .. } => (FocusPoint::Body(parent_id), None),
         };
+        self.start_focus_transition();
+        self.camera_focus.set_point(focus);
+        self.target = target;
+        self.fix_camera_zoom();
+        self.set_time(moment.time);
+        println!(
+            "Jumped to {}: {}",
+            format_seconds(moment.time),
+            self.describe_moment(&moment)
+        );
+    }
 
-        // The SOI body is located at the origin in its own frame, which might not be
-        // the focus frame (for example, if we are focused on a ship).
-        let body_pt = self.transform_to_focus_space(Frame::BodyInertial(soi_id)) * Point3::origin();
+    /// Starts placing a new ship in a circular orbit around the focused body,
+    /// or the focused ship's parent.
+    pub fn start_placement(&mut self) {
+        if !self.check_editable() {
+            return;
+        }
+        let parent_id = match self.camera_focus.point() {
+            FocusPoint::Body(id) => id,
+            FocusPoint::Ship(id) => self.orrery.get_ship(id).parent_id(),
+        };
+        let radius = 2.0 * self.orrery.get_body(parent_id).info.radius as f64;
+        self.placement = Some(ShipPlacement::circular(parent_id, radius, self.time));
+        self.placement_uncertainty = ElementUncertainty::default();
+        self.editing_uncertainty = false;
+    }
 
-        // Make an okayish SOI color by dimming the body color.
-        let body_color = self.orrery.get_body(soi_id).info.color;
-        let soi_color = Point3::from(body_color.coords * 0.5);
+    pub fn is_editing_uncertainty(&self) -> bool {
+        self.editing_uncertainty
+    }
 
-        self.renderer
-            .draw_soi(body_pt, soi_radius as f32, soi_color);
+    /// Switches between changing the new ship's elements and how uncertain
+    /// they are.
+    pub fn toggle_editing_uncertainty(&mut self) {
+        self.editing_uncertainty = !self.editing_uncertainty;
     }
 
-    fn draw_markers(&mut self) {
-        // We draw the marker if we're far enough away that the body is too
-        // small to see, but not if we're far enough away that the orbit is too
-        // small.
+    /// Changes how uncertain the new ship's elements are. None of them go
+    /// below zero.
+    pub fn adjust_placement_uncertainty(
+        &mut self,
+        delta_anomaly: f64,
+        delta_sma: f64,
+        delta_incl: f64,
+    ) {
+        let uncertainty = &mut self.placement_uncertainty;
+        uncertainty.mean_anomaly = (uncertainty.mean_anomaly + delta_anomaly).clamp(0.0, PI);
+        uncertainty.semimajor_axis = (uncertainty.semimajor_axis + delta_sma).max(0.0);
+        uncertainty.inclination = (uncertainty.inclination + delta_incl).clamp(0.0, PI);
+    }
 
-        // These sizes are in pixels
-        const MARKER_SIZE: f32 = 18.0;
-        const BODY_CUTOFF: f32 = 3.0;
-        const ORBIT_CUTOFF: f32 = MARKER_SIZE;
+    pub fn is_placing(&self) -> bool {
+        self.placement.is_some()
+    }
 
-        // Figure out the ratio of pixel size to worldspace lengths.
-        // That's determined from the camera distance, the field of view,
-        // and the window size.
-        let pixel_size_ndc = 2.0 / self.camera.height() as f32;
-        let pixel_size_worldspace = {
-            // half of the screen height, in worldspace
-            let half_height = self.camera.distance() * (self.camera.fovy() / 2.0).tan();
-            half_height * 2.0 / self.camera.height() as f32
+    /// Tweaks the orbit of the ship being placed. The semimajor axis is
+    /// multiplied by `sma_factor`, but kept above the surface and inside the
+    /// sphere of influence.
+    pub fn adjust_placement(&mut self, delta_anomaly: f64, sma_factor: f64, delta_incl: f64) {
+        let placement = match &mut self.placement {
+            Some(placement) => placement,
+            None => return,
         };
+        let min_sma = 1.01 * self.orrery.get_body(placement.parent_id).info.radius as f64;
+        let max_sma = self
+            .orrery
+            .get_soi_radius(placement.parent_id)
+            .unwrap_or(f64::INFINITY);
+        placement.set_mean_anomaly(placement.mean_anomaly + delta_anomaly);
+        placement.semimajor_axis = (placement.semimajor_axis * sma_factor).clamp(min_sma, max_sma);
+        placement.inclination = (placement.inclination + delta_incl).clamp(0.0, PI);
+    }
 
-        let should_draw = |radius: f32, orbit: BareOrbit| -> bool {
-            // Figure out the apparent size of objects in screenspace
-            let apparent_body_radius = radius / pixel_size_worldspace;
-            let apparent_orbit_apoapsis = match orbit.apoapsis() {
-                Some(a) => a as f32 / pixel_size_worldspace,
-                None => return true, // always draw markers when orbit is open
-            };
-
-            // Draw marker if body is too small, unless orbit is also too small
-            apparent_body_radius < BODY_CUTOFF && apparent_orbit_apoapsis > ORBIT_CUTOFF
+    /// Adds the ship being placed to the timeline, and focuses on it.
+    pub fn confirm_placement(&mut self) {
+        let placement = match self.placement.take() {
+            Some(placement) => placement,
+            None => return,
         };
+        // Work this out before the ship is in the orrery, so it isn't
+        // propagated alongside its own extremes
+        let envelope = (!self.placement_uncertainty.is_zero()).then(|| {
+            UncertaintyEnvelope::propagate(
+                &self.orrery,
+                &placement,
+                self.placement_uncertainty,
+                PREDICTION_HORIZON,
+            )
+        });
+        let id = self.timeline.add_ship(&placement);
+        self.session_changes.push(SessionChange::AddShip(placement));
+        if let Some(envelope) = envelope {
+            self.uncertainty_envelopes.insert(id, envelope);
+        }
+        println!(
+            "Added ship {} around {} at {}",
+            id.0,
+            self.orrery.get_body(placement.parent_id).info.name,
+            format_seconds(placement.epoch)
+        );
 
-        for orbit in self.orrery.body_orbits() {
-            let body = orbit.secondary();
-
-            if !should_draw(body.info.radius, orbit.to_bare()) {
-                continue;
-            }
+        self.set_time(self.time);
+        self.camera_focus = CameraFocus::new(&self.orrery);
+        self.camera_focus.set_point(FocusPoint::Ship(id));
+        self.fix_camera_zoom();
+        self.update_scene_objects();
+    }
 
-            let body_pt =
-                self.transform_to_focus_space(Frame::BodyInertial(body.id)) * Point3::origin();
+    pub fn cancel_placement(&mut self) {
+        self.placement = None;
+    }
 
-            self.renderer.draw_marker(
-                MarkerType::Circle,
-                body_pt,
-                MARKER_SIZE * pixel_size_ndc,
-                body.info.color,
-            );
+    /// The focused ship's name, or an empty string if it doesn't have one.
+    /// Returns `None` if a body is focused.
+    pub fn focused_ship_name(&self) -> Option<String> {
+        match self.camera_focus.point() {
+            FocusPoint::Body(_) => None,
+            FocusPoint::Ship(id) => Some(self.orrery.get_ship(id).name.clone().unwrap_or_default()),
         }
-        for ship in self.orrery.ships() {
-            if !should_draw(TEST_SHIP_SIZE / 2.0, ship.orbit.to_bare()) {
-                continue;
-            }
+    }
 
-            let ship_pt =
-                self.transform_to_focus_space(Frame::ShipInertial(ship.id)) * Point3::origin();
+    /// Renames the focused ship, or takes its name away. Does nothing if a
+    /// body is focused.
+    pub fn rename_focused_ship(&mut self, name: Option<String>) {
+        let ship_id = match self.camera_focus.point() {
+            FocusPoint::Body(_) => return,
+            FocusPoint::Ship(id) => id,
+        };
+        self.timeline.set_ship_name(ship_id, name.clone());
+        self.session_changes
+            .push(SessionChange::RenameShip(ship_id, name));
+        self.set_time(self.time);
+    }
 
-            self.renderer.draw_marker(
-                MarkerType::Square,
-                ship_pt,
-                MARKER_SIZE * pixel_size_ndc,
-                Point3::new(1.0, 1.0, 1.0),
-            );
+    /// Handles a click on the HUD, at the given cursor position. Clicking on a
+    /// ship in the ships panel focuses it, and clicking on the state of a
+    /// picked point hides it. Returns whether the click was on a panel.
+    pub fn click_panel(&mut self, cursor: Point2<f32>) -> bool {
+        let (panel_idx, line) = match self.hud.line_at(cursor) {
+            Some(hit) => hit,
+            None => return false,
+        };
+        // The title is line 0
+        let clicked_ship = line
+            .checked_sub(1)
+            .filter(|_| Some(panel_idx) == self.ships_panel_idx)
+            .and_then(|idx| self.listed_ships().get(idx).copied());
+        if let Some(ship_id) = clicked_ship {
+            self.start_focus_transition();
+            self.camera_focus.set_point(FocusPoint::Ship(ship_id));
+            self.fix_camera_zoom();
+            self.update_scene_objects();
+        }
+        if Some(panel_idx) == self.point_panel_idx {
+            self.shown_point = None;
         }
+        true
     }
 
-    fn left_hand_text(&self) -> String {
-        let (state, frame) = match self.camera_focus.point() {
-            FocusPoint::Body(id) => {
-                let frame = match self.orrery.get_parent(id) {
-                    Some(id) => Frame::BodyInertial(id),
-                    None => Frame::Root,
-                };
-                (self.orrery.get_body_state(id, self.time), frame)
-            }
-            FocusPoint::Ship(id) => {
-                let frame = Frame::BodyInertial(self.orrery.get_ship(id).parent_id());
-                (self.orrery.get_ship_state(id, self.time), frame)
-            }
+    /// Takes the focused ship out of the timeline, and focuses on its parent
+    /// instead. Does nothing if a body is focused.
+    pub fn remove_focused_ship(&mut self) {
+        if !self.check_editable() {
+            return;
+        }
+        let ship_id = match self.camera_focus.point() {
+            FocusPoint::Body(_) => return,
+            FocusPoint::Ship(id) => id,
         };
+        let parent_id = self.orrery.get_ship(ship_id).parent_id();
+        if let Err(e) = self.timeline.remove_ship(ship_id) {
+            self.notification = Some((format!("Can't remove ship: {}", e), Instant::now()));
+            return;
+        }
+        self.session_changes
+            .push(SessionChange::RemoveShip(ship_id));
+        self.notification = Some((
+            format!("Removed {}", self.point_name(FocusPoint::Ship(ship_id))),
+            Instant::now(),
+        ));
 
-        format!(
-            "Focused on: {}
-State:
-    Radius: {:.0} m
-    Speed: {:.0} m/s
-Orbiting: {}",
-            self.focused_body_name(),
-            state.get_position(frame, self.time).coords.norm(),
-            state.get_velocity(frame, self.time).norm(),
-            self.orbit_summary_text(),
-        )
+        // Forget anything that was about the ship
+        self.uncertainty_envelopes.remove(&ship_id);
+        self.notes.remove_ship(ship_id);
+        self.moments_computed_for = None;
+        if self.target == Some(FocusPoint::Ship(ship_id)) {
+            self.target = None;
+        }
+        if self
+            .ghost_trajectory
+            .as_ref()
+            .is_some_and(|t| t.ship_id() == ship_id)
+        {
+            self.ghost_trajectory = None;
+        }
+
+        self.set_time(self.time);
+        self.start_focus_transition();
+        self.camera_focus = CameraFocus::new(&self.orrery);
+        self.camera_focus.set_point(FocusPoint::Body(parent_id));
+        self.fix_camera_zoom();
+        self.update_scene_objects();
     }
 
-    fn focused_body_name(&self) -> String {
-        match self.camera_focus.point() {
-            FocusPoint::Body(id) => {
-                let body = self.orrery.get_body(id);
-                body.info.name.clone()
-            }
-            FocusPoint::Ship(_) => {
-                format!(
-                    "<Ship> ({})",
-                    if self.ship_camera_inertial {
-                        "inertial"
-                    } else {
-                        "orbital"
-                    }
-                )
-            }
+    /// Starts editing the focused body's properties. Does nothing if a ship is
+    /// focused.
+    pub fn start_inspecting(&mut self) {
+        if !self.check_editable() {
+            return;
+        }
+        if let FocusPoint::Body(body_id) = self.camera_focus.point() {
+            self.inspector = Some(BodyInspector {
+                body_id,
+                edit: BodyEdit::default(),
+                selected: InspectorField::Mu,
+            });
         }
     }
 
-    fn orbit_summary_text(&self) -> String {
+    pub fn is_inspecting(&self) -> bool {
+        self.inspector.is_some()
+    }
+
+    /// The fields the inspector can edit. SOIs can only be set when they
+    /// aren't computed, and only for bodies that have one.
+    fn inspector_fields(&self, body_id: BodyID) -> Vec<InspectorField> {
+        let mut fields = vec![InspectorField::Mu, InspectorField::Radius];
+        let has_soi = self.orrery.get_soi_radius(body_id).is_some();
+        if has_soi && self.orrery.soi_model() == SoiModel::Fixed {
+            fields.push(InspectorField::Soi);
+        }
+        fields
+    }
+
+    /// Selects the next or previous field in the inspector.
+    pub fn select_inspector_field(&mut self, forward: bool) {
+        let body_id = match &self.inspector {
+            Some(inspector) => inspector.body_id,
+            None => return,
+        };
+        let fields = self.inspector_fields(body_id);
+        let inspector = self.inspector.as_mut().unwrap();
+        let idx = fields
+            .iter()
+            .position(|f| *f == inspector.selected)
+            .unwrap_or(0);
+        let idx = if forward {
+            (idx + 1) % fields.len()
+        } else {
+            (idx + fields.len() - 1) % fields.len()
+        };
+        inspector.selected = fields[idx];
+    }
+
+    /// Multiplies the selected field in the inspector by the given factor.
+    pub fn scale_inspector_field(&mut self, factor: f64) {
+        let inspector = match &mut self.inspector {
+            Some(inspector) => inspector,
+            None => return,
+        };
+        let id = inspector.body_id;
+        let info = &self.orrery.get_body(id).info;
+        let edit = &mut inspector.edit;
+        match inspector.selected {
+            InspectorField::Mu => edit.mu = Some(edit.mu.unwrap_or(info.mu) * factor),
+            InspectorField::Radius => {
+                edit.radius = Some(edit.radius.unwrap_or(info.radius) * factor as f32)
+            }
+            InspectorField::Soi => {
+                let soi_radius = self.orrery.get_soi_radius(id).unwrap();
+                edit.soi_radius = Some(edit.soi_radius.unwrap_or(soi_radius) * factor)
+            }
+        }
+    }
+
+    /// Makes the changes in the inspector, from the current time on.
+    pub fn apply_inspector(&mut self) {
+        let inspector = match self.inspector.take() {
+            Some(inspector) => inspector,
+            None => return,
+        };
+        if inspector.edit.is_empty() {
+            return;
+        }
+        // Leave the inspector open, so the edit can be fixed
+        if let Err(e) = self
+            .timeline
+            .edit_body(inspector.body_id, inspector.edit, self.time)
+        {
+            self.notification = Some((format!("Can't edit body: {}", e), Instant::now()));
+            self.inspector = Some(inspector);
+            return;
+        }
+        println!(
+            "Edited {} at {}: {:?}",
+            self.orrery.get_body(inspector.body_id).info.name,
+            format_seconds(self.time),
+            inspector.edit
+        );
+        self.session_changes.push(SessionChange::EditBody {
+            body_id: inspector.body_id,
+            edit: inspector.edit,
+            time: self.time,
+        });
+        self.set_time(self.time);
+        self.fix_camera_zoom();
+    }
+
+    pub fn cancel_inspector(&mut self) {
+        self.inspector = None;
+    }
+
+    /// Starts typing in new orbital elements for the focused body or ship,
+    /// starting from the ones it has now. Only elliptic orbits can be edited,
+    /// and the root body has no orbit at all.
+    pub fn start_editing_orbit(&mut self) {
+        if !self.check_editable() {
+            return;
+        }
+        let point = self.camera_focus.point();
+        let elements = match point {
+            FocusPoint::Body(id) => self
+                .orrery
+                .orbit_of_body(id)
+                .and_then(|orbit| OrbitElements::of_orbit(&orbit, self.time)),
+            FocusPoint::Ship(id) => {
+                // A ship being integrated isn't following its orbit, so use
+                // the one it's on right now
+                let ship = self.orrery.get_ship(id);
+                let state = ship.state_at_time(self.time);
+                let orbit = TimedOrbit::from_state(
+                    CartesianState::new(
+                        ship.orbit.primary().clone(),
+                        state.position(),
+                        state.velocity(),
+                    ),
+                    self.time,
+                );
+                OrbitElements::of_orbit(&orbit, self.time)
+            }
+        };
+        match elements {
+            Some(elements) => {
+                self.orbit_editor = Some(OrbitEditor::new(point, self.time, &elements))
+            }
+            None => {
+                self.notification = Some((
+                    format!("{} isn't on an elliptic orbit", self.point_name(point)),
+                    Instant::now(),
+                ))
+            }
+        }
+    }
+
+    pub fn is_editing_orbit(&self) -> bool {
+        self.orbit_editor.is_some()
+    }
+
+    pub fn select_orbit_field(&mut self, forward: bool) {
+        if let Some(editor) = &mut self.orbit_editor {
+            editor.select(forward);
+        }
+    }
+
+    pub fn type_into_orbit(&mut self, c: char) {
+        if let Some(editor) = &mut self.orbit_editor {
+            editor.type_char(c);
+        }
+    }
+
+    pub fn backspace_orbit(&mut self) {
+        if let Some(editor) = &mut self.orbit_editor {
+            editor.backspace();
+        }
+    }
+
+    /// Moves the body or ship onto the orbit typed in, from when editing
+    /// started. Everything after that is worked out again.
+    pub fn finish_orbit(&mut self) {
+        let elements = match self.orbit_editor.as_ref().map(OrbitEditor::elements) {
+            Some(Some(elements)) => elements,
+            Some(None) => {
+                self.notification = Some((
+                    String::from("Orbit needs a positive SMA and an eccentricity below 1"),
+                    Instant::now(),
+                ));
+                return;
+            }
+            None => return,
+        };
+        let editor = self.orbit_editor.take().unwrap();
+        let time = editor.time;
+
+        let change = match editor.point {
+            FocusPoint::Body(body_id) => {
+                let edit = BodyEdit {
+                    orbit: Some(elements),
+                    ..BodyEdit::default()
+                };
+                if let Err(e) = self.timeline.edit_body(body_id, edit, time) {
+                    self.notification = Some((format!("Can't move body: {}", e), Instant::now()));
+                    self.orbit_editor = Some(editor);
+                    return;
+                }
+                SessionChange::EditBody {
+                    body_id,
+                    edit,
+                    time,
+                }
+            }
+            FocusPoint::Ship(ship_id) => {
+                self.timeline.set_ship_orbit(ship_id, elements, time);
+                // Its orbit isn't the uncertain one it was placed on anymore
+                self.uncertainty_envelopes.remove(&ship_id);
+                SessionChange::SetShipOrbit {
+                    ship_id,
+                    elements,
+                    time,
+                }
+            }
+        };
+        println!(
+            "Moved {} onto a new orbit at {}: {:?}",
+            self.point_name(editor.point),
+            format_seconds(time),
+            elements
+        );
+        self.session_changes.push(change);
+        self.moments_computed_for = None;
+        self.set_time(self.time);
+        self.fix_camera_zoom();
+    }
+
+    pub fn cancel_orbit(&mut self) {
+        self.orbit_editor = None;
+    }
+
+    /// Starts typing in a note. It's attached to the focused ship's next burn,
+    /// if it has one, and the others can be picked instead: the ship itself,
+    /// or the current time.
+    pub fn start_editing_note(&mut self) {
+        if !self.check_editable() {
+            return;
+        }
+        let mut choices = vec![];
+        if let FocusPoint::Ship(id) = self.camera_focus.point() {
+            let ship = self.orrery.get_ship(id);
+            if let Some(maneuver) = ship.maneuvers.next_after(self.time) {
+                choices.push(NoteTarget::Maneuver(id, maneuver.time));
+            }
+            choices.push(NoteTarget::Ship(id));
+        }
+        choices.push(NoteTarget::Time(self.time));
+        let text = self.notes.get(choices[0]).unwrap_or_default().to_owned();
+        self.note_editor = Some(NoteEditor {
+            choices,
+            selected: 0,
+            text,
+        });
+    }
+
+    pub fn is_editing_note(&self) -> bool {
+        self.note_editor.is_some()
+    }
+
+    /// Switches what the note is attached to, starting over from that one's
+    /// note.
+    pub fn cycle_note_target(&mut self) {
+        if let Some(editor) = &mut self.note_editor {
+            editor.selected = (editor.selected + 1) % editor.choices.len();
+            let target = editor.choices[editor.selected];
+            editor.text = self.notes.get(target).unwrap_or_default().to_owned();
+        }
+    }
+
+    pub fn type_into_note(&mut self, c: char) {
+        if let Some(editor) = &mut self.note_editor {
+            editor.text.push(c);
+        }
+    }
+
+    pub fn backspace_note(&mut self) {
+        if let Some(editor) = &mut self.note_editor {
+            editor.text.pop();
+        }
+    }
+
+    /// Keeps the note, or takes the note away if it's blank.
+    pub fn finish_note(&mut self) {
+        if let Some(editor) = self.note_editor.take() {
+            self.notes
+                .set(editor.choices[editor.selected], &editor.text);
+        }
+    }
+
+    pub fn cancel_note(&mut self) {
+        self.note_editor = None;
+    }
+
+    /// Starts typing in a maneuver node for the focused ship. Its next planned
+    /// maneuver is edited, if it has one; otherwise, a new node is started at
+    /// its next periapsis.
+    pub fn start_editing_node(&mut self) {
+        if !self.check_editable() {
+            return;
+        }
+        let ship_id = match self.camera_focus.point() {
+            FocusPoint::Body(_) => return,
+            FocusPoint::Ship(id) => id,
+        };
+        let ship = self.orrery.get_ship(ship_id);
+        if ship.has_crashed() {
+            return;
+        }
+        let original_plan = ship.maneuvers.clone();
+        let mut base_plan = original_plan.clone();
+
+        let editor = match original_plan.next_after(self.time) {
+            Some(maneuver) => {
+                // Work out which way the burn points from the orbit it starts on
+                base_plan.remove_maneuver(maneuver.time);
+                self.timeline.set_maneuver_plan(ship_id, base_plan.clone());
+                self.timeline.extend_until(maneuver.time);
+                let orrery = self.timeline.get_orrery_at(maneuver.time).unwrap();
+                let state = orrery.get_ship(ship_id).state_at_time(maneuver.time);
+                let frame = orbit_frame(state.position(), state.velocity());
+                NodeEditor::new(
+                    ship_id,
+                    original_plan.clone(),
+                    base_plan,
+                    TimeReference::Absolute,
+                    maneuver.time,
+                    frame.transpose() * maneuver.delta_v,
+                )
+            }
+            None => NodeEditor::new(
+                ship_id,
+                original_plan.clone(),
+                base_plan,
+                TimeReference::Periapsis,
+                0.0,
+                Vector3::zeros(),
+            ),
+        };
+        self.node_editor = Some(editor);
+        self.refresh_node();
+    }
+
+    pub fn is_editing_node(&self) -> bool {
+        self.node_editor.is_some()
+    }
+
+    pub fn select_node_field(&mut self, forward: bool) {
+        if let Some(editor) = &mut self.node_editor {
+            editor.select(forward);
+        }
+    }
+
+    pub fn cycle_node_time_reference(&mut self) {
+        if let Some(editor) = &mut self.node_editor {
+            editor.cycle_time_reference();
+            self.refresh_node();
+        }
+    }
+
+    pub fn type_into_node(&mut self, c: char) {
+        if let Some(editor) = &mut self.node_editor {
+            if editor.type_char(c) {
+                self.refresh_node();
+            }
+        }
+    }
+
+    pub fn backspace_node(&mut self) {
+        if let Some(editor) = &mut self.node_editor {
+            editor.backspace();
+            self.refresh_node();
+        }
+    }
+
+    /// Keeps the node as it's been typed in.
+    pub fn finish_node(&mut self) {
+        let editor = match self.node_editor.take() {
+            Some(editor) => editor,
+            None => return,
+        };
+        match editor.node {
+            Some(node) => println!(
+                "Planned a {:.1} m/s burn at {}",
+                node.delta_v.norm(),
+                format_seconds(node.time)
+            ),
+            None => println!("Removed the maneuver node"),
+        }
+
+        // Keep any note on the burn with it
+        let edited = editor
+            .original_plan
+            .maneuvers()
+            .iter()
+            .find(|m| editor.base_plan.maneuver_at(m.time).is_none());
+        if let Some(edited) = edited {
+            self.notes
+                .move_maneuver(editor.ship_id, edited.time, editor.node.map(|n| n.time));
+        }
+
+        let ship = self.orrery.get_ship(editor.ship_id);
+        self.session_changes.push(SessionChange::SetManeuverPlan(
+            editor.ship_id,
+            ship.maneuvers.clone(),
+        ));
+        if let Some(remaining) = ship.delta_v_ledger(self.time).remaining() {
+            if remaining < 0.0 {
+                self.notification = Some((
+                    format!(
+                        "{} needs {:.1} m/s more than it has",
+                        ship.display_name(),
+                        -remaining
+                    ),
+                    Instant::now(),
+                ));
+            }
+        }
+    }
+
+    /// Puts the ship's plan back the way it was before editing started.
+    pub fn cancel_node(&mut self) {
+        let editor = match self.node_editor.take() {
+            Some(editor) => editor,
+            None => return,
+        };
+        self.timeline
+            .set_maneuver_plan(editor.ship_id, editor.original_plan);
+        self.update_state_by(0.0);
+    }
+
+    /// Replans the ship's maneuvers with the node as it's currently typed in,
+    /// so that the predicted trajectory follows along. If the values aren't
+    /// valid yet, the node is left out.
+    fn refresh_node(&mut self) {
+        let mut editor = match self.node_editor.take() {
+            Some(editor) => editor,
+            None => return,
+        };
+        self.timeline
+            .set_maneuver_plan(editor.ship_id, editor.base_plan.clone());
+        editor.landmark_time = self.landmark_time(editor.ship_id, editor.time_reference);
+        editor.node = self.resolve_node(&editor);
+        if let Some(node) = editor.node {
+            let mut plan = editor.base_plan.clone();
+            plan.set_maneuver(node);
+            self.timeline.set_maneuver_plan(editor.ship_id, plan);
+        }
+        self.node_editor = Some(editor);
+        self.update_state_by(0.0);
+    }
+
+    /// Returns the next time after now that the ship passes the given
+    /// landmark, following its plan without the node being edited. The nodes
+    /// are relative to the target's orbit, so there aren't any without one.
+    fn landmark_time(&mut self, ship_id: ShipID, reference: TimeReference) -> Option<f64> {
+        let orrery = self.timeline.get_orrery_at(self.time)?;
+        if orrery.get_ship(ship_id).has_crashed() {
+            return None;
+        }
+        let orbit = orrery.orbit_of_ship(ship_id);
+        match reference {
+            TimeReference::Absolute => None,
+            TimeReference::Periapsis => search_for_periapsis(&orrery, ship_id, self.time)
+                .event()
+                .map(|e| e.point.time),
+            TimeReference::Apoapsis => search_for_apoapsis(&orrery, ship_id, self.time)
+                .event()
+                .map(|e| e.point.time),
+            TimeReference::AscendingNode | TimeReference::DescendingNode => {
+                let target_orbit = self.orbit_of_point(self.target?)?;
+                let nodes = orbit.nodes_relative_to(&target_orbit);
+                let theta = if reference == TimeReference::AscendingNode {
+                    nodes.asc_node_anomaly
+                } else {
+                    nodes.desc_node_anomaly()
+                };
+                orbit.next_time_at_theta(theta?, self.time)
+            }
+            TimeReference::SoiChange => {
+                self.timeline.extend_until(self.time + PREDICTION_HORIZON);
+                self.timeline
+                    .trajectory(ship_id)
+                    .patches()
+                    .iter()
+                    .filter_map(|p| p.ending_event())
+                    .filter(|e| {
+                        matches!(e.data, EventData::EnteringSOI(_) | EventData::ExitingSOI(_))
+                    })
+                    .map(|e| e.point.time)
+                    .find(|t| *t > self.time)
+            }
+        }
+    }
+
+    /// Works out the time and direction of the node being edited, using the
+    /// trajectory the ship takes without it. Nodes have to be in the future,
+    /// and actually change the ship's velocity.
+    fn resolve_node(&mut self, editor: &NodeEditor) -> Option<Maneuver> {
+        let ship_id = editor.ship_id;
+        let offset = editor.value(NodeField::Time)?;
+        let local_delta_v = editor.local_delta_v()?;
+        if local_delta_v == Vector3::zeros() {
+            return None;
+        }
+
+        let time = match editor.time_reference {
+            TimeReference::Absolute => offset,
+            _ => editor.landmark_time? + offset,
+        };
+        if time <= self.time {
+            return None;
+        }
+
+        self.timeline.extend_until(time);
+        let orrery = self.timeline.get_orrery_at(time)?;
+        let ship = orrery.get_ship(ship_id);
+        if ship.has_crashed() {
+            return None;
+        }
+        let state = ship.state_at_time(time);
+        Some(Maneuver {
+            time,
+            delta_v: orbit_frame(state.position(), state.velocity()) * local_delta_v,
+        })
+    }
+
+    /// Looks for interesting moments again, if the timeline has changed much
+    /// since we last did.
+    fn refresh_moments(&mut self) {
+        let num_events = self.timeline.events().count();
+        let end_time = self.timeline.end_time();
+        let is_stale = match self.moments_computed_for {
+            None => true,
+            Some((old_num_events, old_end_time)) => {
+                num_events != old_num_events || end_time - old_end_time > MOMENTS_REFRESH_INTERVAL
+            }
+        };
+        if is_stale {
+            self.moments =
+                find_interesting_moments(&self.timeline, self.timeline.start_time(), end_time);
+            self.moments_computed_for = Some((num_events, end_time));
+        }
+    }
+
+    fn fix_camera_zoom(&mut self) {
+        let dist = match self.camera_focus.point() {
+            FocusPoint::Body(id) => self.orrery.get_body(id).info.radius * 2.0,
+            FocusPoint::Ship(_) => TEST_SHIP_SIZE * 2.0,
+        };
+        self.camera.set_min_distance(dist);
+    }
+
+    fn update_scene_objects(&mut self) {
+        // Positions are worked out in f64, relative to the focus, so that
+        // objects near the camera don't jitter, however far from the root
+        // they are
+        let snapshot = self.orrery.snapshot(self.time, Frame::Root);
+        let root_to_focus = self.root_to_focus_space();
+        let to_focus_space = |state: &ObjectState| -> Point3<f32> {
+            nalgebra::convert(root_to_focus.convert_point(&state.position))
+        };
+        let body_positions: Vec<_> = self
+            .body_spheres
+            .keys()
+            .map(|id| (*id, to_focus_space(snapshot.body(*id))))
+            .collect();
+        let ship_positions: Vec<_> = self
+            .ship_objects
+            .keys()
+            .map(|id| (*id, to_focus_space(snapshot.ship(*id))))
+            .collect();
+
+        // kiss3d's spheres have their poles along y, and bodies along z
+        let sphere_to_body = UnitQuaternion::from_axis_angle(&Vector3::x_axis(), FRAC_PI_2);
+        let body_rotations: Vec<_> = body_positions
+            .iter()
+            .map(|(id, _)| {
+                let body_to_focus = self
+                    .transform_to_focus_space(Frame::BodyFixed(*id))
+                    .rotation;
+                nalgebra::convert::<_, UnitQuaternion<f32>>(body_to_focus * sphere_to_body)
+            })
+            .collect();
+
+        // TODO apply rotations to ships too!
+        for ((id, position), rotation) in body_positions.into_iter().zip(body_rotations) {
+            let sphere = self.body_spheres.get_mut(&id).unwrap();
+            // Bodies can be resized partway through the timeline
+            let diameter = 2.0 * self.orrery.get_body(id).info.radius;
+            sphere.set_local_scale(diameter, diameter, diameter);
+            sphere.set_local_rotation(rotation);
+            sphere.set_local_translation(Translation3::from(position));
+        }
+
+        for (id, position) in ship_positions {
+            let cube = self.ship_objects.get_mut(&id).unwrap();
+            cube.set_local_translation(Translation3::from(position));
+        }
+    }
+
+    /// Switches between the 3D view and the top-down map, which flattens
+    /// everything onto the xy-plane of the focused object's frame.
+    pub fn toggle_map_mode(&mut self) {
+        self.map_mode = !self.map_mode;
+        // Bodies and ships are drawn as outlines in map mode
+        let nodes = self
+            .body_spheres
+            .values_mut()
+            .chain(self.ship_objects.values_mut());
+        for node in nodes {
+            node.set_visible(!self.map_mode);
+        }
+    }
+
+    /// Switches between uniformly colored orbits, ones that fade out toward
+    /// the future, ones that are brighter where they're faster, and showing
+    /// how near the focused ship gets to leaving its parent's SOI.
+    pub fn cycle_orbit_coloring(&mut self) {
+        self.orbit_coloring = match self.orbit_coloring {
+            OrbitColoring::Uniform => OrbitColoring::TimeUntil,
+            OrbitColoring::TimeUntil => OrbitColoring::Speed,
+            OrbitColoring::Speed => OrbitColoring::SoiFraction,
+            OrbitColoring::SoiFraction => OrbitColoring::Uniform,
+        };
+    }
+
+    /// Switches between showing the SOI of the focused object's parent, and
+    /// those of all bodies.
+    pub fn toggle_all_sois(&mut self) {
+        self.show_all_sois = !self.show_all_sois;
+    }
+
+    pub fn toggle_soi_wireframe(&mut self) {
+        self.soi_wireframe = !self.soi_wireframe;
+        self.renderer.set_sphere_wireframe(self.soi_wireframe);
+    }
+
+    /// Switches fading with distance on or off.
+    pub fn toggle_fog(&mut self) {
+        self.fog = !self.fog;
+    }
+
+    /// Switches the focused orbit's equator ring and node line on or off.
+    pub fn toggle_orbit_reference(&mut self) {
+        self.show_orbit_reference = !self.show_orbit_reference;
+    }
+
+    /// Switches the trails of where ships have been on or off.
+    pub fn toggle_trails(&mut self) {
+        self.show_trails = !self.show_trails;
+    }
+
+    /// Pins the focused ship's predicted trajectory, so that it keeps being
+    /// drawn while the ship's plan is changed. If a trajectory is already
+    /// pinned, unpins it instead.
+    pub fn toggle_ghost_trajectory(&mut self) {
+        if self.ghost_trajectory.take().is_some() {
+            println!("Unpinned trajectory");
+            return;
+        }
+        let ship_id = match self.camera_focus.point() {
+            FocusPoint::Body(_) => return,
+            FocusPoint::Ship(id) => id,
+        };
+        self.ghost_trajectory = Some(self.timeline.trajectory(ship_id));
+        println!("Pinned trajectory of ship {}", ship_id.0);
+    }
+
+    /// The gradient to draw an orbit with, if it starts at the given time.
+    fn orbit_gradient(&self, start_time: f64) -> ColorGradient {
+        match self.orbit_coloring {
+            // The focused ship's orbits are handled in draw_trajectory
+            OrbitColoring::Uniform | OrbitColoring::SoiFraction => ColorGradient::Uniform,
+            OrbitColoring::TimeUntil => ColorGradient::TimeUntil {
+                offset: start_time - self.time,
+                fade_time: ORBIT_FADE_TIME,
+            },
+            OrbitColoring::Speed => ColorGradient::Speed,
+        }
+    }
+
+    /// Remembers the current focus, so that the camera can move smoothly from
+    /// it to whatever gets focused next. If we're already moving, the camera
+    /// jumps to the end of that move first.
+    fn start_focus_transition(&mut self) {
+        self.focus_transition = Some((self.focused_object_frame(), Instant::now()));
+    }
+
+    /// How far along the current focus transition is, from 0 to 1, eased in
+    /// and out. Returns `None` if there's no transition, or it's over.
+    fn focus_transition_progress(&self) -> Option<(Frame, f64)> {
+        let (old_frame, start) = self.focus_transition?;
+        let t = start.elapsed().as_secs_f32() / FOCUS_TRANSITION_DURATION;
+        if t >= 1.0 {
+            return None;
+        }
+        let t = t as f64;
+        Some((old_frame, t * t * (3.0 - 2.0 * t)))
+    }
+
+    fn focused_object_frame(&self) -> Frame {
+        match self.camera_focus.point() {
+            FocusPoint::Body(id) => Frame::BodyInertial(id),
+            FocusPoint::Ship(id) => match self.ship_camera_inertial {
+                true => Frame::ShipInertial(id),
+                false => Frame::ShipOrbital(id),
+            },
+        }
+    }
+
+    /// Returns the transform from the root frame to the one the camera is in.
+    /// That's the frame of the focused object, or while the focus is changing,
+    /// somewhere between the old and new ones.
+    fn root_to_focus_space(&self) -> FrameTransform<f64> {
+        let new_transform =
+            self.orrery
+                .convert_frames(Frame::Root, self.focused_object_frame(), self.time);
+        match self.focus_transition_progress() {
+            Some((old_frame, progress)) => {
+                let old_transform = self
+                    .orrery
+                    .convert_frames(Frame::Root, old_frame, self.time);
+                old_transform.interpolate(&new_transform, progress)
+            }
+            None => new_transform,
+        }
+    }
+
+    /// Returns the transform from the given frame to focus space. This is in
+    /// f64, since both frames can be very far from the root; only things that
+    /// are already in focus space are close enough to the camera for f32.
+    fn transform_to_focus_space(&self, frame: Frame) -> Isometry3<f64> {
+        let transform = self
+            .orrery
+            .convert_frames(frame, Frame::Root, self.time)
+            .append_transformation(&self.root_to_focus_space());
+        *transform.isometry()
+    }
+
+    /// Converts a point in the given frame to focus space, only dropping down
+    /// to f32 once it's relative to the focus.
+    fn to_focus_space(&self, frame: Frame, point: Point3<f64>) -> Point3<f32> {
+        nalgebra::convert(self.transform_to_focus_space(frame) * point)
+    }
+
+    // the big boy
+    pub fn prerender_scene(&mut self, window: &mut Window, controller: &Controller) {
+        // Ships can be added after we start, so make objects for any new ones,
+        // and get rid of the ones for ships that were removed
+        let mut needs_update = false;
+        for ship in self.orrery.ships() {
+            if let Entry::Vacant(entry) = self.ship_objects.entry(ship.id) {
+                let cube = entry.insert(Self::create_ship_object(window, ship));
+                cube.set_visible(!self.map_mode);
+                needs_update = true;
+            }
+        }
+        let orrery = &self.orrery;
+        self.ship_objects.retain(|id, cube| {
+            let exists = orrery.ships().any(|s| s.id == *id);
+            if !exists {
+                window.remove_node(cube);
+            }
+            exists
+        });
+        // Keep moving the camera if the focus just changed
+        if self.focus_transition.is_some() {
+            if self.focus_transition_progress().is_none() {
+                self.focus_transition = None;
+            }
+            needs_update = true;
+        }
+        if needs_update {
+            self.update_scene_objects();
+        }
+
+        // The map shows the same area as a 3D camera looking straight down
+        if self.map_mode {
+            let half_height = self.camera.distance() * (self.camera.fovy() / 2.0).tan();
+            self.map_camera.set_half_height(half_height);
+            self.renderer.set_map_mode(Some(half_height));
+            self.draw_body_outlines();
+        } else {
+            self.renderer.set_map_mode(None);
+        }
+        // The light comes from the root body
+        window.set_light(Light::Absolute(
+            self.to_focus_space(Frame::Root, Point3::origin()),
+        ));
+        let sky_orientation = self.root_to_focus_space().isometry().rotation;
+        self.renderer
+            .set_sky_orientation(nalgebra::convert(sky_orientation));
+        self.renderer.set_fog(self.fog.then(|| Fog {
+            eye: self.camera.eye(),
+            focus_distance: self.camera.distance(),
+        }));
+
+        // Draw a bunch of stuff
+        self.renderer.draw_grid(self.camera.distance());
+        let cursor = window
+            .cursor_pos()
+            .map(|(x, y)| Point2::new(x as f32, y as f32));
+        self.draw_orbits(cursor);
+        self.draw_trails();
+        self.draw_orbital_axes();
+        self.draw_soi();
+        self.draw_orbit_reference();
+        self.draw_rings();
+        self.draw_markers();
+        self.draw_relative_nodes();
+        self.draw_apsis_markers(window);
+        self.draw_event_markers(window);
+        self.draw_closest_approach(window);
+        self.draw_body_labels();
+        self.draw_orbit_tooltip(window, cursor);
+        self.renderer.draw_planar_lines(window);
+
+        // Labels are placed by where they are on the screen
+        let camera = &self.camera;
+        let map_camera = self.map_mode.then_some(&self.map_camera);
+        // The map is seen from above the focus
+        let eye = match map_camera {
+            Some(_) => Point3::new(0.0, 0.0, camera.distance()),
+            None => camera.eye(),
+        };
+        self.renderer.draw_labels(
+            window,
+            |point| {
+                // Points behind the camera would be projected onto the screen
+                // as if they were in front
+                if map_camera.is_none() && (point - eye).dot(&-eye.coords) <= 0.0 {
+                    return None;
+                }
+                let screen_pt = project_to_window(camera, map_camera, point);
+                Some((screen_pt, (point - eye).norm()))
+            },
+            camera.distance(),
+        );
+
+        // Draw panels
+        self.refresh_moments();
+        let mut panels = PanelLayout::new();
+        panels.add(Corner::TopLeft, self.focus_panel());
+        panels.add(Corner::TopLeft, self.orbit_panel());
+        let optional_panels = [
+            self.placement_panel(),
+            self.uncertainty_panel(),
+            self.inspector_panel(),
+            self.node_editor_panel(),
+            self.note_editor_panel(),
+            self.orbit_editor_panel(),
+            self.integrator_panel(),
+            self.sail_panel(),
+            self.delta_v_panel(),
+            self.event_panel(),
+            self.target_panel(),
+            self.encounter_panel(),
+            self.readouts_panel(),
+        ];
+        for panel in optional_panels.into_iter().flatten() {
+            panels.add(Corner::TopLeft, panel);
+        }
+        panels.add(Corner::TopRight, self.time_panel(controller));
+        if let Some(panel) = self.bookmarks_panel(controller) {
+            panels.add(Corner::TopRight, panel);
+        }
+        panels.add(Corner::BottomLeft, self.events_panel());
+        if let Some(panel) = self.moments_panel() {
+            panels.add(Corner::BottomLeft, panel);
+        }
+        if let Some(panel) = self.notes_panel() {
+            panels.add(Corner::BottomLeft, panel);
+        }
+        self.ships_panel_idx = Some(panels.add(Corner::BottomRight, self.ships_panel(controller)));
+        self.point_panel_idx = self
+            .point_panel()
+            .map(|panel| panels.add(Corner::BottomRight, panel));
+        self.orbit_menu_panel_idx = self
+            .orbit_menu_panel()
+            .map(|(position, panel)| panels.add_at(position, panel));
+        panels.draw(window, self.ui_scale);
+        self.hud = panels;
+
+        self.draw_notification(window);
+        let note_times: Vec<f64> = self.notes.times().iter().map(|(t, _)| *t).collect();
+        self.scrubber.draw(
+            window,
+            self.map_mode.then_some(&self.map_camera),
+            &self.timeline,
+            &note_times,
+            self.time,
+            self.ui_scale,
+        );
+    }
+
+    fn draw_orbits(&mut self, cursor: Option<Point2<f32>>) {
+        self.apsis_markers.clear();
+        self.event_markers.clear();
+        // Highlight what was under the cursor last frame, and look for what's
+        // under it now
+        let highlighted = self.orbit_hover.take().map(|h| (h.owner, h.patch_idx));
+        let mut hover = None;
+
+        for orbit in self.orrery.body_orbits() {
+            let secondary = orbit.secondary();
+            let owner = FocusPoint::Body(secondary.id);
+
+            let mut color = secondary.info.color;
+            if highlighted == Some((owner, 0)) {
+                color = highlight(color);
+            }
+            let frame = Frame::BodyInertial(orbit.primary().id);
+            let mut orbit_patch =
+                OrbitPatch::new(&orbit, self.time).with_gradient(self.orbit_gradient(self.time));
+            if self.camera_focus.point() == owner {
+                orbit_patch = orbit_patch.with_apsides();
+            }
+
+            let transform = self.transform_to_focus_space(frame);
+            if !self.is_orbit_in_view(&orbit_patch, transform) {
+                continue;
+            }
+            orbit_patch = self.with_pixel_size(orbit_patch, transform);
+            if let Some(cursor) = cursor {
+                let pick = OrbitRenderer::screen_pick(
+                    &orbit_patch,
+                    transform,
+                    |pt| self.visible_screen_position(pt),
+                    cursor,
+                );
+                update_orbit_hover(&mut hover, pick, |distance, theta| OrbitHover {
+                    owner,
+                    patch_idx: 0,
+                    distance,
+                    text: describe_orbit(
+                        &secondary.info.name,
+                        &orbit.primary().info.name,
+                        &orbit_patch.orbit,
+                    ),
+                    color: secondary.info.color,
+                    point: orbit.next_time_at_theta(theta, self.time).map(|time| {
+                        let state = orbit.state_at_time(time);
+                        OrbitPoint {
+                            owner,
+                            parent_id: orbit.primary().id,
+                            time,
+                            position: state.position(),
+                            velocity: state.velocity(),
+                        }
+                    }),
+                });
+            }
+
+            let apsides = self.renderer.draw_orbit(orbit_patch, color, transform);
+            let primary_id = orbit.primary().id;
+            self.apsis_markers
+                .extend(apsides.into_iter().map(|apsis| (apsis, primary_id)));
+        }
+
+        let ship_ids: Vec<_> = self.orrery.ships().map(|s| s.id).collect();
+        for id in ship_ids {
+            self.draw_trajectory(id, cursor, highlighted, &mut hover);
+        }
+        self.draw_ghost_trajectory();
+
+        if let Some(placement) = &self.placement {
+            let orbit = placement.orbit(&self.orrery);
+            let transform = self.transform_to_focus_space(Frame::BodyInertial(placement.parent_id));
+            let orbit_patch = self.with_pixel_size(OrbitPatch::new(&orbit, self.time), transform);
+            self.renderer
+                .draw_orbit(orbit_patch, Point3::from(PLACEMENT_COLOR), transform);
+            // Also show the range it could be in, more faintly
+            if !self.placement_uncertainty.is_zero() {
+                for extreme in self.placement_uncertainty.extremes(placement) {
+                    let orbit = extreme.orbit(&self.orrery);
+                    self.renderer.draw_orbit(
+                        self.with_pixel_size(OrbitPatch::new(&orbit, self.time), transform),
+                        Point3::from(PLACEMENT_COLOR) * UNCERTAINTY_BRIGHTNESS,
+                        transform,
+                    );
+                }
+            }
+        }
+
+        self.orbit_hover = hover;
+    }
+
+    /// Draws the current and upcoming conic patches of a ship's trajectory.
+    /// Like in KSP, each future patch is drawn around where its parent body
+    /// will be when the ship enters that patch.
+    ///
+    /// Also checks whether the cursor is over any of the patches, and
+    /// highlights the `highlighted` patch if it's one of this ship's.
+    fn draw_trajectory(
+        &mut self,
+        ship_id: ShipID,
+        cursor: Option<Point2<f32>>,
+        highlighted: Option<(FocusPoint, usize)>,
+        hover: &mut Option<OrbitHover>,
+    ) {
+        let trajectory = self.timeline.trajectory(ship_id);
+        let owner = FocusPoint::Ship(ship_id);
+        let now = self.time;
+
+        let upcoming_patches = trajectory
+            .patches()
+            .iter()
+            .filter(|p| p.end_time().is_none_or(|end| end > now))
+            .take(MAX_PATCHES_DRAWN);
+
+        for (i, patch) in upcoming_patches.enumerate() {
+            // Dim future patches so they're distinguishable from the current
+            // orbit, unless they're already fading out
+            let brightness = match self.orbit_coloring {
+                OrbitColoring::TimeUntil => 1.0,
+                _ => 1.0 / (i + 1) as f32,
+            };
+            let ship_color = ship_color(self.orrery.get_ship(ship_id));
+            let mut color = ship_color * brightness;
+            if highlighted == Some((owner, i)) {
+                color = highlight(color);
+            }
+
+            let transform = self.patch_to_focus_space(ship_id, patch);
+
+            // The event's location is in the frame of the patch it ends
+            if let Some(event) = patch.ending_event() {
+                if event_marker_type(&event.data).is_some() {
+                    let location: Point3<f32> = nalgebra::convert(transform * event.point.location);
+                    self.event_markers
+                        .push((event.clone(), patch.parent_id(), location));
+                }
+            }
+
+            if patch.numeric_arc().is_some() {
+                let points = self.sampled_patch_points(patch, transform);
+                self.draw_sampled_patch(&points, color);
+                continue;
+            }
+
+            // What's still to come is dashed
+            let start_time = f64::max(patch.start_time(), self.time);
+            let mut orbit_patch = match patch.end_time() {
+                Some(end_time) => OrbitPatch::with_end_time(patch.orbit(), start_time, end_time),
+                None => OrbitPatch::new(patch.orbit(), start_time),
+            };
+            orbit_patch = orbit_patch
+                .with_style(LineStyle::Dashed)
+                .with_gradient(self.orbit_gradient(start_time));
+            // The focused ship's orbit can show how close it gets to escaping,
+            // both where it's been and where it's going
+            let soi_gradient = match self.orbit_coloring {
+                OrbitColoring::SoiFraction if self.camera_focus.point() == owner => self
+                    .orrery
+                    .get_soi_radius(patch.parent_id())
+                    .map(|soi_radius| ColorGradient::SoiFraction { soi_radius }),
+                _ => None,
+            };
+            if let Some(gradient) = soi_gradient {
+                orbit_patch = orbit_patch.with_gradient(gradient);
+            }
+            if self.camera_focus.point() == owner {
+                orbit_patch = orbit_patch.with_apsides();
+            }
+            orbit_patch = self.with_pixel_size(orbit_patch, transform);
+
+            // The part of the current patch that's already been flown is
+            // solid, going back at most one turn
+            if patch.start_time() < self.time {
+                let flown_start = match patch.orbit().period() {
+                    Some(period) => f64::max(patch.start_time(), self.time - period),
+                    None => patch.start_time(),
+                };
+                let mut flown = OrbitPatch::with_end_time(patch.orbit(), flown_start, self.time);
+                if let Some(gradient) = soi_gradient {
+                    flown = flown.with_gradient(gradient);
+                }
+                if self.is_orbit_in_view(&flown, transform) {
+                    let flown = self.with_pixel_size(flown, transform);
+                    self.renderer.draw_orbit(flown, color, transform);
+                }
+            }
+
+            if !self.is_orbit_in_view(&orbit_patch, transform) {
+                continue;
+            }
+            if let Some(cursor) = cursor {
+                let pick = OrbitRenderer::screen_pick(
+                    &orbit_patch,
+                    transform,
+                    |pt| self.visible_screen_position(pt),
+                    cursor,
+                );
+                update_orbit_hover(hover, pick, |distance, theta| OrbitHover {
+                    owner,
+                    patch_idx: i,
+                    distance,
+                    text: describe_orbit(
+                        &self.point_name(owner),
+                        &self.orrery.get_body(patch.parent_id()).info.name,
+                        &orbit_patch.orbit,
+                    ),
+                    color: ship_color,
+                    point: patch
+                        .orbit()
+                        .next_time_at_theta(theta, start_time)
+                        .filter(|time| patch.end_time().is_none_or(|end| *time <= end))
+                        .map(|time| {
+                            let state = patch.state_at_time(time);
+                            OrbitPoint {
+                                owner,
+                                parent_id: patch.parent_id(),
+                                time,
+                                position: state.position(),
+                                velocity: state.velocity(),
+                            }
+                        }),
+                });
+            }
+
+            let apsides = self.renderer.draw_orbit(orbit_patch, color, transform);
+            let parent_id = patch.parent_id();
+            self.apsis_markers
+                .extend(apsides.into_iter().map(|apsis| (apsis, parent_id)));
+        }
+    }
+
+    /// Draws the upcoming patches of the pinned trajectory, if there is one,
+    /// placed the same way as the ship's current trajectory.
+    fn draw_ghost_trajectory(&mut self) {
+        let trajectory = match &self.ghost_trajectory {
+            Some(trajectory) => trajectory,
+            None => return,
+        };
+        let ship_id = trajectory.ship_id();
+
+        let mut orbit_patches = vec![];
+        let mut sampled_patches = vec![];
+        let upcoming_patches = trajectory
+            .patches()
+            .iter()
+            .filter(|p| p.end_time().is_none_or(|end| end > self.time))
+            .take(MAX_PATCHES_DRAWN);
+        for patch in upcoming_patches {
+            if patch.numeric_arc().is_some() {
+                let transform = self.patch_to_focus_space(ship_id, patch);
+                sampled_patches.push(self.sampled_patch_points(patch, transform));
+                continue;
+            }
+            let start_time = f64::max(patch.start_time(), self.time);
+            let orbit_patch = match patch.end_time() {
+                Some(end_time) => OrbitPatch::with_end_time(patch.orbit(), start_time, end_time),
+                None => OrbitPatch::new(patch.orbit(), start_time),
+            };
+            let orbit_patch = orbit_patch.with_style(LineStyle::Dashed);
+            let transform = self.patch_to_focus_space(ship_id, patch);
+            if self.is_orbit_in_view(&orbit_patch, transform) {
+                orbit_patches.push((self.with_pixel_size(orbit_patch, transform), transform));
+            }
+        }
+
+        for (orbit_patch, transform) in orbit_patches {
+            self.renderer
+                .draw_orbit(orbit_patch, Point3::from(GHOST_COLOR), transform);
+        }
+        for points in sampled_patches {
+            self.draw_sampled_patch(&points, Point3::from(GHOST_COLOR));
+        }
+    }
+
+    /// Samples a numerically integrated patch evenly from where its arc starts
+    /// to where the patch ends, and at the current time, so the flown and
+    /// upcoming parts meet at the ship. Points are in focus space.
+    fn sampled_patch_points(
+        &self,
+        patch: &TrajectoryPatch,
+        transform: Isometry3<f64>,
+    ) -> Vec<(f64, Point3<f32>)> {
+        let path = match patch.numeric_arc() {
+            Some(arc) => &arc.path,
+            None => return vec![],
+        };
+        let start_time = f64::max(patch.start_time(), path.start_time());
+        let end_time = patch
+            .end_time()
+            .map_or(path.end_time(), |end| f64::min(end, path.end_time()));
+        if start_time >= end_time {
+            return vec![];
+        }
+
+        let mut times: Vec<f64> = (0..=NUM_ARC_POINTS)
+            .map(|i| start_time + (end_time - start_time) * i as f64 / NUM_ARC_POINTS as f64)
+            .collect();
+        if start_time < self.time && self.time < end_time {
+            times.push(self.time);
+            times.sort_by(f64::total_cmp);
+        }
+        times
+            .into_iter()
+            .map(|t| {
+                let position = Point3::from(patch.state_at_time(t).position());
+                (t, nalgebra::convert(transform * position))
+            })
+            .collect()
+    }
+
+    /// Draws the points from [sampled_patch_points](Self::sampled_patch_points)
+    /// as a line, solid where it's been flown and dashed where it's to come,
+    /// like the conic patches.
+    fn draw_sampled_patch(&mut self, points: &[(f64, Point3<f32>)], color: Point3<f32>) {
+        for (i, pair) in points.windows(2).enumerate() {
+            let ((t, a), (_, b)) = (pair[0], pair[1]);
+            if t >= self.time && i % 2 == 1 {
+                continue;
+            }
+            self.renderer.draw_line(a, b, color);
+        }
+    }
+
+    /// Shows the elements of the orbit under the cursor next to it.
+    fn draw_orbit_tooltip(&self, window: &mut Window, cursor: Option<Point2<f32>>) {
+        let (hover, cursor) = match (&self.orbit_hover, cursor) {
+            (Some(hover), Some(cursor)) => (hover, cursor),
+            _ => return,
+        };
+        // Keep it out from under the cursor
+        let position = cursor + Vector2::new(16.0, 16.0);
+        self.draw_label(window, position, &hover.text, &hover.color);
+    }
+
+    /// Returns the transform from a patch's parent frame to focus space. The
+    /// patch is placed relative to the ship's current parent, using the
+    /// position of the patch's parent at the start of the patch.
+    fn patch_to_focus_space(&self, ship_id: ShipID, patch: &TrajectoryPatch) -> Isometry3<f64> {
+        let current_parent = Frame::BodyInertial(self.orrery.get_ship(ship_id).parent_id());
+        let start_time = f64::max(patch.start_time(), self.time);
+        let patch_to_current_parent = self.orrery.convert_frames(
+            Frame::BodyInertial(patch.parent_id()),
+            current_parent,
+            start_time,
+        );
+        self.transform_to_focus_space(current_parent) * patch_to_current_parent.isometry()
+    }
+
+    /// Returns where the ship will be at the given time, in focus space, placed
+    /// on its trajectory the same way [View::draw_trajectory] draws it.
+    fn trajectory_point(&self, ship_id: ShipID, time: f64) -> Option<Point3<f32>> {
+        let trajectory = self.timeline.trajectory(ship_id);
+        let patch = trajectory.patch_at_time(time)?;
+        let position = Point3::from(patch.state_at_time(time).position());
+        Some(nalgebra::convert(
+            self.patch_to_focus_space(ship_id, patch) * position,
+        ))
+    }
+
+    /// Converts a point in focus space to window coordinates, with the origin
+    /// at the top-left, like the cursor's.
+    fn screen_position(&self, point: &Point3<f32>) -> Point2<f32> {
+        let map_camera = self.map_mode.then_some(&self.map_camera);
+        project_to_window(&self.camera, map_camera, point)
+    }
+
+    /// Tells the patch how many meters a pixel covers near it, so that it
+    /// isn't broken into more segments than it needs. `transform` takes the
+    /// orbit's parent's frame to focus space.
+    fn with_pixel_size(&self, orbit_patch: OrbitPatch, transform: Isometry3<f64>) -> OrbitPatch {
+        if self.map_mode {
+            let pixel_size = self.map_camera.pixel_size() as f64;
+            return orbit_patch.with_pixel_size(pixel_size);
+        }
+
+        // Use the closest the orbit could get to the camera, but not closer
+        // than the focus; anything nearer than that is mostly off the screen.
+        let eye: Point3<f64> = nalgebra::convert(self.camera.eye());
+        let center = transform * Point3::origin();
+        let distance = f64::max(
+            (center - eye).norm() - orbit_patch.max_radius(),
+            self.camera.distance() as f64,
+        );
+        let fovy = self.camera.fovy() as f64;
+        let pixel_size = 2.0 * distance * (fovy / 2.0).tan() / self.camera.height() as f64;
+        orbit_patch.with_pixel_size(pixel_size)
+    }
+
+    /// Returns whether any part of a sphere in focus space could be on the
+    /// screen. Things that can't be are skipped, rather than drawn for
+    /// nothing.
+    fn is_in_view(&self, center: &Point3<f32>, radius: f32) -> bool {
+        if self.map_mode {
+            self.map_camera
+                .circle_in_view(Point2::new(center.x, center.y), radius)
+        } else {
+            self.camera.sphere_in_view(center, radius)
+        }
+    }
+
+    /// Like [View::is_in_view], for an orbit drawn with the given transform
+    /// from its parent's frame to focus space.
+    fn is_orbit_in_view(&self, orbit_patch: &OrbitPatch, transform: Isometry3<f64>) -> bool {
+        let center = nalgebra::convert(transform * Point3::origin());
+        self.is_in_view(&center, orbit_patch.max_radius() as f32)
+    }
+
+    /// Returns whether a marker at the given point, with the given size in
+    /// pixels, would be on the screen.
+    fn is_marker_in_view(&self, point: &Point3<f32>, size: f32) -> bool {
+        let screen_pt = match self.visible_screen_position(point) {
+            Some(screen_pt) => screen_pt,
+            None => return false,
+        };
+        let width = self.camera.width() as f32;
+        let height = self.camera.height() as f32;
+        (-size..=width + size).contains(&screen_pt.x)
+            && (-size..=height + size).contains(&screen_pt.y)
+    }
+
+    /// Like [View::screen_position], but returns `None` for points behind the
+    /// camera, which would otherwise be projected as if they were in front.
+    fn visible_screen_position(&self, point: &Point3<f32>) -> Option<Point2<f32>> {
+        if !self.map_mode {
+            let eye = self.camera.eye();
+            if (point - eye).dot(&-eye.coords) <= 0.0 {
+                return None;
+            }
+        }
+        Some(self.screen_position(point))
+    }
+
+    /// Names each body next to where it is.
+    fn draw_body_labels(&mut self) {
+        let body_ids: Vec<_> = self.orrery.bodies().map(|b| b.id).collect();
+        for id in body_ids {
+            let body = self.orrery.get_body(id);
+            let center = self.to_focus_space(Frame::BodyInertial(id), Point3::origin());
+            let rank = self.declutter_rank(FocusPoint::Body(id));
+            self.renderer
+                .draw_label(center, &body.info.name, body.info.color, rank);
+        }
+    }
+
+    /// How much a point's marker and label matter when they'd overlap
+    /// others'; lower ranks are kept first. The focus comes first, then the
+    /// target, then bodies from the top of the hierarchy down, with ships
+    /// after their parents' moons.
+    fn declutter_rank(&self, point: FocusPoint) -> u32 {
+        if point == self.camera_focus.point() {
+            return 0;
+        }
+        if Some(point) == self.target {
+            return 1;
+        }
+        let (mut body_id, extra) = match point {
+            FocusPoint::Body(id) => (id, 0),
+            FocusPoint::Ship(id) => (self.orrery.get_ship(id).parent_id(), 2),
+        };
+        let mut depth = 0;
+        while let Some(parent_id) = self.orrery.get_parent(body_id) {
+            body_id = parent_id;
+            depth += 1;
+        }
+        2 + depth + extra
+    }
+
+    /// Draws the outline of each body, for map mode, where the spheres are
+    /// hidden.
+    fn draw_body_outlines(&mut self) {
+        let body_ids: Vec<_> = self.orrery.bodies().map(|b| b.id).collect();
+        for id in body_ids {
+            let body = self.orrery.get_body(id);
+            let (radius, color) = (body.info.radius, body.info.color);
+            let center = self.to_focus_space(Frame::BodyInertial(id), Point3::origin());
+            self.renderer.draw_flat_circle(center, radius, color);
+        }
+    }
+
+    fn draw_orbital_axes(&mut self) {
+        // TODO: this renders the axes at the center of the body; I think we probably
+        // want center of the orbit instead. But only do that if you're doing
+        // this only for the focused body.
+        for orbit in self.orrery.body_orbits() {
+            let body = orbit.secondary();
+
+            let axes = [
+                (orbit.periapse_vector(), Point3::new(1.0, 0.0, 0.0)),
+                (orbit.asc_node_vector(), Point3::new(0.0, 1.0, 0.0)),
+                (orbit.normal_vector(), Point3::new(0.0, 0.0, 1.0)),
+            ]
+            .map(|(v, color)| {
+                let v = Unit::new_unchecked(nalgebra::convert(v.into_inner()));
+                (v, color)
+            });
+
+            self.renderer.draw_axes(
+                &axes,
+                2.0 * body.info.radius,
+                nalgebra::convert(self.transform_to_focus_space(Frame::BodyInertial(body.id))),
+            );
+        }
+    }
+
+    fn draw_soi(&mut self) {
+        let soi_ids: Vec<_> = if self.show_all_sois {
+            self.orrery.bodies().map(|b| b.id).collect()
+        } else {
+            match self.camera_focus.point() {
+                FocusPoint::Body(id) => vec![id],
+                FocusPoint::Ship(id) => vec![self.orrery.get_ship(id).parent_id()],
+            }
+        };
+
+        for soi_id in soi_ids {
+            let soi_radius = match self.orrery.get_soi_radius(soi_id) {
+                Some(r) => r,
+                None => continue, // skip the Sun
+            };
+
+            // The SOI body is located at the origin in its own frame, which might not be
+            // the focus frame (for example, if we are focused on a ship).
+            let body_pt = self.to_focus_space(Frame::BodyInertial(soi_id), Point3::origin());
+
+            // Make an okayish SOI color by dimming the body color.
+            let body_color = self.orrery.get_body(soi_id).info.color;
+            let soi_color = Point3::from(body_color.coords * 0.5);
+
+            self.renderer
+                .draw_sphere(body_pt, soi_radius as f32, soi_color);
+        }
+    }
+
+    /// Draws where each ship actually was over the last while, following it
+    /// through any SOI changes and burns along the way, rather than its
+    /// current orbit. Each point is placed relative to the ship's current
+    /// parent as it was at that time, like [View::patch_to_focus_space] does
+    /// for the future. Older points are dimmer.
+    fn draw_trails(&mut self) {
+        if !self.show_trails || self.trail_duration <= 0.0 {
+            return;
+        }
+
+        let ship_ids: Vec<_> = self.orrery.ships().map(|s| s.id).collect();
+        for id in ship_ids {
+            let trajectory = self.timeline.trajectory(id);
+            let start_time = f64::max(self.time - self.trail_duration, trajectory.start_time());
+            if start_time >= self.time {
+                continue;
+            }
+
+            // Sample evenly, and also at each event, so that sharp turns
+            // happen in the right place
+            let mut times: Vec<f64> = (0..=NUM_TRAIL_POINTS)
+                .map(|i| start_time + (self.time - start_time) * i as f64 / NUM_TRAIL_POINTS as f64)
+                .collect();
+            times.extend(
+                trajectory
+                    .events()
+                    .map(|e| e.point.time)
+                    .filter(|t| start_time < *t && *t < self.time),
+            );
+            times.sort_by(f64::total_cmp);
+
+            let current_parent = Frame::BodyInertial(self.orrery.get_ship(id).parent_id());
+            let to_focus_space = self.transform_to_focus_space(current_parent);
+            let points: Vec<_> = times
+                .iter()
+                .filter_map(|&t| {
+                    let patch = trajectory.patch_at_time(t)?;
+                    let position = patch.state_at_time(t).position();
+                    let to_current_parent = self.orrery.convert_frames(
+                        Frame::BodyInertial(patch.parent_id()),
+                        current_parent,
+                        t,
+                    );
+                    let point = to_current_parent.isometry() * Point3::from(position);
+                    Some((
+                        t,
+                        nalgebra::convert::<_, Point3<f32>>(to_focus_space * point),
+                    ))
+                })
+                .collect();
+
+            let color = ship_color(self.orrery.get_ship(id));
+            for pair in points.windows(2) {
+                let ((t, a), (_, b)) = (pair[0], pair[1]);
+                let age = ((self.time - t) / self.trail_duration) as f32;
+                let brightness = MIN_TRAIL_BRIGHTNESS + (1.0 - MIN_TRAIL_BRIGHTNESS) * (1.0 - age);
+                self.renderer.draw_line(a, b, color * brightness);
+            }
+        }
+    }
+
+    /// Draws the focused orbit's parent's equator, which is the plane that
+    /// inclination is measured from, and the line where the orbit crosses it.
+    /// There's also a line in the reference direction, which the longitude
+    /// of the ascending node is measured from.
+    fn draw_orbit_reference(&mut self) {
+        if !self.show_orbit_reference {
+            return;
+        }
+
+        let (orbit, parent_id) = match self.camera_focus.point() {
+            FocusPoint::Body(id) => match self.orrery.orbit_of_body(id) {
+                Some(orbit) => (orbit.to_bare(), orbit.primary().id),
+                None => return, // the Sun has no orbit
+            },
+            FocusPoint::Ship(id) => {
+                let orbit = self.orrery.orbit_of_ship(id);
+                (orbit.to_bare(), orbit.primary().id)
+            }
+        };
+
+        // Make the ring a bit bigger than where the orbit crosses it. Hyperbolic
+        // orbits may only cross it once, so it's at least the periapsis.
+        let node_anomaly = -orbit.arg_periapse();
+        let node_radius = [node_anomaly, node_anomaly + PI]
+            .into_iter()
+            .filter_map(|theta| orbit.get_position_at_theta(theta))
+            .map(|v| v.norm())
+            .fold(orbit.periapsis(), f64::max);
+        let radius = (EQUATOR_RING_SCALE * node_radius) as f32;
+
+        let transform: Isometry3<f32> =
+            nalgebra::convert(self.transform_to_focus_space(Frame::BodyInertial(parent_id)));
+        let ring_points: Vec<_> = (0..NUM_EQUATOR_SEGMENTS)
+            .map(|i| {
+                let theta = i as f32 / NUM_EQUATOR_SEGMENTS as f32 * std::f32::consts::TAU;
+                transform * Point3::new(radius * theta.cos(), radius * theta.sin(), 0.0)
+            })
+            .collect();
+        let ring_color = self.orrery.get_body(parent_id).info.color * EQUATOR_RING_BRIGHTNESS;
+        self.renderer.draw_path(&ring_points, ring_color, true);
+
+        let node: Vector3<f32> = nalgebra::convert(orbit.asc_node_vector().into_inner());
+        self.renderer.draw_line(
+            transform * Point3::from(-radius * node),
+            transform * Point3::from(radius * node),
+            Point3::from(NODE_LINE_COLOR),
+        );
+        self.renderer.draw_line(
+            transform * Point3::origin(),
+            transform * Point3::new(radius, 0.0, 0.0),
+            Point3::from(REFERENCE_DIRECTION_COLOR),
+        );
+    }
+
+    fn draw_markers(&mut self) {
+        // We draw the marker if we're far enough away that the body is too
+        // small to see, but not if we're far enough away that the orbit is too
+        // small.
+
+        // These sizes are in pixels, at a UI scale of 1
+        const MARKER_SIZE: f32 = 18.0;
+        const BODY_CUTOFF: f32 = 3.0;
+        const ORBIT_CUTOFF: f32 = MARKER_SIZE;
+        let marker_size = MARKER_SIZE * self.ui_scale;
+
+        // Figure out the ratio of pixel size to worldspace lengths.
+        // That's determined from the camera distance, the field of view,
+        // and the window size.
+        let pixel_size_ndc = 2.0 / self.camera.height() as f32;
+        let pixel_size_worldspace = {
+            // half of the screen height, in worldspace
+            let half_height = self.camera.distance() * (self.camera.fovy() / 2.0).tan();
+            half_height * 2.0 / self.camera.height() as f32
+        };
+
+        let should_draw = |radius: f32, orbit: BareOrbit| -> bool {
+            // Figure out the apparent size of objects in screenspace
+            let apparent_body_radius = radius / pixel_size_worldspace;
+            let apparent_orbit_apoapsis = match orbit.apoapsis() {
+                Some(a) => a as f32 / pixel_size_worldspace,
+                None => return true, // always draw markers when orbit is open
+            };
+
+            // Draw marker if body is too small, unless orbit is also too small
+            apparent_body_radius < BODY_CUTOFF
+                && apparent_orbit_apoapsis > ORBIT_CUTOFF * self.ui_scale
+        };
+
+        let mut markers = vec![];
+        for orbit in self.orrery.body_orbits() {
+            let body = orbit.secondary();
+
+            if !should_draw(body.info.radius, orbit.to_bare()) {
+                continue;
+            }
+
+            let body_pt = self.to_focus_space(Frame::BodyInertial(body.id), Point3::origin());
+            if !self.is_marker_in_view(&body_pt, marker_size) {
+                continue;
+            }
+
+            let rank = self.declutter_rank(FocusPoint::Body(body.id));
+            markers.push((rank, MarkerType::Circle, body_pt, body.info.color));
+        }
+        for ship in self.orrery.ships() {
+            if !should_draw(TEST_SHIP_SIZE / 2.0, ship.orbit.to_bare()) {
+                continue;
+            }
+
+            let ship_pt = self.to_focus_space(Frame::ShipInertial(ship.id), Point3::origin());
+            if !self.is_marker_in_view(&ship_pt, marker_size) {
+                continue;
+            }
+
+            let rank = self.declutter_rank(FocusPoint::Ship(ship.id));
+            markers.push((rank, MarkerType::Square, ship_pt, ship_color(ship)));
+        }
+
+        // Markers that would overlap a more important one are left out, e.g.,
+        // the moons of a planet seen from far away
+        markers.sort_by_key(|(rank, ..)| *rank);
+        let mut drawn: Vec<Point2<f32>> = vec![];
+        for (_, mtype, point, color) in markers {
+            let screen_pt = self.screen_position(&point);
+            let overlaps = drawn.iter().any(|other| {
+                (screen_pt.x - other.x).abs() < marker_size
+                    && (screen_pt.y - other.y).abs() < marker_size
+            });
+            if overlaps {
+                continue;
+            }
+            drawn.push(screen_pt);
+            self.renderer
+                .draw_marker(mtype, point, marker_size * pixel_size_ndc, color);
+        }
+
+        // Always mark the ship being placed, since it has no other object
+        if let Some(placement) = &self.placement {
+            let (position, _) = placement.state(&self.orrery);
+            let ship_pt =
+                self.to_focus_space(Frame::BodyInertial(placement.parent_id), position.into());
+
+            self.renderer.draw_marker(
+                MarkerType::Diamond,
+                ship_pt,
+                marker_size * pixel_size_ndc,
+                Point3::from(PLACEMENT_COLOR),
+            );
+        }
+    }
+
+    /// Draws the ascending and descending nodes of the focused object's orbit,
+    /// relative to the target's orbital plane.
+    fn draw_relative_nodes(&mut self) {
+        const MARKER_SIZE: f32 = 14.0;
+        let marker_size = MARKER_SIZE * self.ui_scale;
+
+        let target = match self.target {
+            Some(target) => target,
+            None => return,
+        };
+        let (orbit, reference) = match (
+            self.orbit_of_point(self.camera_focus.point()),
+            self.orbit_of_point(target),
+        ) {
+            (Some(orbit), Some(reference)) => (orbit, reference),
+            _ => return,
+        };
+
+        let nodes = orbit.nodes_relative_to(&reference);
+        let transform = self.transform_to_focus_space(Frame::BodyInertial(orbit.primary().id));
+        // Hyperbolic orbits might not reach the nodes
+        let markers: Vec<_> = [
+            (nodes.asc_node_anomaly, MarkerType::TriangleUp),
+            (nodes.desc_node_anomaly(), MarkerType::TriangleDown),
+        ]
+        .into_iter()
+        .filter_map(|(anomaly, mtype)| {
+            let position = orbit.get_position_at_theta(anomaly?)?;
+            Some((nalgebra::convert(transform * Point3::from(position)), mtype))
+        })
+        .collect();
+
+        let pixel_size_ndc = 2.0 / self.camera.height() as f32;
+        let color = Point3::new(0.0, 1.0, 0.5);
+        for (position, mtype) in markers {
+            self.renderer
+                .draw_marker(mtype, position, marker_size * pixel_size_ndc, color);
+        }
+    }
+
+    /// Marks the apsides collected while drawing the orbits, and labels any
+    /// that the cursor is hovering over with their altitude.
+    fn draw_apsis_markers(&mut self, window: &mut Window) {
+        const MARKER_SIZE: f32 = 14.0;
+        const HOVER_RADIUS: f32 = MARKER_SIZE;
+        let marker_size = MARKER_SIZE * self.ui_scale;
+
+        let pixel_size_ndc = 2.0 / self.camera.height() as f32;
+        let cursor = window
+            .cursor_pos()
+            .map(|(x, y)| Point2::new(x as f32, y as f32));
+
+        let color = Point3::new(0.2, 0.6, 1.0);
+        for (apsis, body_id) in self.apsis_markers.iter() {
+            let (mtype, label) = match apsis.kind {
+                ApsisKind::Periapsis => (MarkerType::Diamond, "Pe"),
+                ApsisKind::Apoapsis => (MarkerType::HollowDiamond, "Ap"),
+            };
+            self.renderer
+                .draw_marker(mtype, apsis.position, marker_size * pixel_size_ndc, color);
+
+            let screen_pt = self.screen_position(&apsis.position);
+            let is_hovered =
+                cursor.is_some_and(|c| (c - screen_pt).norm() < HOVER_RADIUS * self.ui_scale);
+            if !is_hovered {
+                continue;
+            }
+
+            let altitude = apsis.radius - self.orrery.get_body(*body_id).info.radius as f64;
+            self.draw_label(
+                window,
+                screen_pt,
+                &format!("{}: {:.0} m", label, altitude),
+                &color,
+            );
+        }
+    }
+
+    /// Draws where the focused ship and the target will be when they're
+    /// closest, over the part of the trajectory that's drawn, and labels them
+    /// with the separation.
+    fn draw_closest_approach(&mut self, window: &mut Window) {
+        const MARKER_SIZE: f32 = 14.0;
+        let marker_size = MARKER_SIZE * self.ui_scale;
+
+        let ship_id = match self.camera_focus.point() {
+            FocusPoint::Ship(id) => id,
+            FocusPoint::Body(_) => return,
+        };
+        let target = match self.target {
+            Some(FocusPoint::Body(id)) => ApproachTarget::Body(id),
+            Some(FocusPoint::Ship(id)) if id != ship_id => ApproachTarget::Ship(id),
+            _ => return,
+        };
+
+        // Same cutoff as draw_trajectory
+        let trajectory = self.timeline.trajectory(ship_id);
+        let last_drawn_end = trajectory
+            .patches()
+            .iter()
+            .filter(|p| p.end_time().is_none_or(|end| end > self.time))
+            .take(MAX_PATCHES_DRAWN)
+            .last()
+            .and_then(|p| p.end_time());
+        let end_time = match last_drawn_end {
+            Some(end) => f64::min(end, self.time + PREDICTION_HORIZON),
+            None => self.time + PREDICTION_HORIZON,
+        };
+
+        let approach = match closest_approach(&self.timeline, ship_id, target, self.time, end_time)
+        {
+            Some(approach) => approach,
+            None => return,
+        };
+
+        let target_pt = match target {
+            ApproachTarget::Body(id) => match self.orrery.orbit_of_body(id) {
+                Some(orbit) => self.to_focus_space(
+                    Frame::BodyInertial(orbit.primary().id),
+                    orbit.state_at_time(approach.time).position().into(),
+                ),
+                None => self.to_focus_space(Frame::BodyInertial(id), Point3::origin()),
+            },
+            ApproachTarget::Ship(id) => match self.trajectory_point(id, approach.time) {
+                Some(pt) => pt,
+                None => return,
+            },
+        };
+        let ship_pt = match self.trajectory_point(ship_id, approach.time) {
+            Some(pt) => pt,
+            None => return,
+        };
+
+        let pixel_size_ndc = 2.0 / self.camera.height() as f32;
+        let color = Point3::new(1.0, 0.5, 0.0);
+        for (mtype, pt) in [
+            (MarkerType::Square, ship_pt),
+            (MarkerType::Circle, target_pt),
+        ] {
+            self.renderer
+                .draw_marker(mtype, pt, marker_size * pixel_size_ndc, color);
+        }
+
+        let label_pt = self.screen_position(&ship_pt) + Vector2::new(marker_size, marker_size);
+        self.draw_label(
+            window,
+            label_pt,
+            &format!(
+                "Sep: {:.0} m\nT-{}",
+                approach.distance,
+                format_seconds(approach.time - self.time)
+            ),
+            &color,
+        );
+    }
+
+    /// Marks where each drawn trajectory will enter or leave an SOI, labeled
+    /// with how long until it happens.
+    fn draw_rings(&mut self) {
+        let ringed_bodies: Vec<_> = self
+            .orrery
+            .bodies()
+            .filter_map(|body| Some((body.id, body.info.rings.clone()?)))
+            .collect();
+        for (id, rings) in ringed_bodies {
+            // Rings lie in the body's equatorial plane
+            let transform = self.transform_to_focus_space(Frame::BodyFixed(id));
+            let center = nalgebra::convert(transform * Point3::origin());
+            let orientation = nalgebra::convert(transform.rotation);
+            self.renderer.draw_rings(&rings, center, orientation);
+        }
+    }
+
+    /// Draws an icon at each upcoming burn, SOI change and impact along the
+    /// drawn trajectories, labeled with what happens and when.
+    fn draw_event_markers(&mut self, window: &mut Window) {
+        let marker_size = EVENT_MARKER_SIZE * self.ui_scale;
+
+        let pixel_size_ndc = 2.0 / self.camera.height() as f32;
+        let markers = std::mem::take(&mut self.event_markers);
+        for (event, _, position) in markers.iter() {
+            let mtype = event_marker_type(&event.data).expect("Only marked events are collected");
+            // Burns take the ship's color, and the rest the smaller of the
+            // bodies involved
+            let color = match &event.data {
+                EventData::EnteringSOI(change) => self.orrery.get_body(change.new).info.color,
+                EventData::ExitingSOI(change) => self.orrery.get_body(change.old).info.color,
+                EventData::Impact(id) => self.orrery.get_body(*id).info.color,
+                _ => ship_color(self.orrery.get_ship(event.ship_id)),
+            };
+            let is_selected = self
+                .selected_event
+                .as_ref()
+                .is_some_and(|(selected, _)| selected == event);
+            let color = if is_selected { highlight(color) } else { color };
+
+            self.renderer
+                .draw_marker(mtype, *position, marker_size * pixel_size_ndc, color);
+
+            let label_pt = self.screen_position(position) + Vector2::new(marker_size, marker_size);
+            self.draw_label(
+                window,
+                label_pt,
+                &format!(
+                    "{}\nT-{}",
+                    self.describe_event(event),
+                    format_seconds(event.point.time - self.time)
+                ),
+                &color,
+            );
+        }
+        self.event_markers = markers;
+    }
+
+    /// Handles a click on the scene, at the given cursor position. Clicking on
+    /// an event's marker shows its details, or hides them if they were already
+    /// shown. Returns whether the click was on a marker.
+    pub fn click_event_marker(&mut self, cursor: Point2<f32>) -> bool {
+        let marker_size = EVENT_MARKER_SIZE * self.ui_scale;
+        let clicked = self
+            .event_markers
+            .iter()
+            .filter_map(|(event, body_id, position)| {
+                let distance = (self.visible_screen_position(position)? - cursor).norm();
+                Some((distance, event, *body_id))
+            })
+            .filter(|(distance, ..)| *distance <= marker_size)
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        let (event, body_id) = match clicked {
+            Some((_, event, body_id)) => (event.clone(), body_id),
+            None => return false,
+        };
+
+        let was_selected = self
+            .selected_event
+            .as_ref()
+            .is_some_and(|(selected, _)| *selected == event);
+        self.selected_event = if was_selected {
+            None
+        } else {
+            Some((event, body_id))
+        };
+        true
+    }
+
+    /// Opens the menu of things to do at the point on the orbit under the
+    /// given cursor position, if there is one. Returns whether it was opened.
+    pub fn open_orbit_menu(&mut self, cursor: Point2<f32>) -> bool {
+        let point = match self.orbit_hover.as_ref().and_then(|h| h.point.clone()) {
+            Some(point) => point,
+            None => return false,
+        };
+        let items = match point.owner {
+            FocusPoint::Body(_) => vec![OrbitMenuItem::Warp, OrbitMenuItem::ShowState],
+            FocusPoint::Ship(_) => {
+                let mut items = vec![
+                    OrbitMenuItem::AddManeuver,
+                    OrbitMenuItem::Circularize(Apsis::Periapsis),
+                    OrbitMenuItem::Circularize(Apsis::Apoapsis),
+                ];
+                if self.target.is_some_and(|t| t != point.owner) {
+                    items.push(OrbitMenuItem::MatchPlane(Node::Ascending));
+                    items.push(OrbitMenuItem::MatchPlane(Node::Descending));
+                }
+                items.extend([OrbitMenuItem::Warp, OrbitMenuItem::ShowState]);
+                items
+            }
+        };
+        self.orbit_menu = Some(OrbitMenu {
+            point,
+            position: cursor,
+            items,
+        });
+        true
+    }
+
+    pub fn is_orbit_menu_open(&self) -> bool {
+        self.orbit_menu.is_some()
+    }
+
+    /// Handles a click while the orbit menu is open, at the given cursor
+    /// position. Clicking one of its items does it; clicking anywhere else
+    /// just closes the menu. Returns the item that was clicked, if any.
+    pub fn click_orbit_menu(&mut self, cursor: Point2<f32>) -> Option<OrbitMenuItem> {
+        let menu = self.orbit_menu.take()?;
+        let (panel_idx, line) = self.hud.line_at(cursor)?;
+        if Some(panel_idx) != self.orbit_menu_panel_idx {
+            return None;
+        }
+        // The title is line 0
+        let item = *menu.items.get(line.checked_sub(1)?)?;
+
+        let point = menu.point;
+        match item {
+            OrbitMenuItem::AddManeuver => {
+                if let FocusPoint::Ship(ship_id) = point.owner {
+                    self.start_node_at(ship_id, point.time);
+                }
+            }
+            OrbitMenuItem::Warp => self.set_time(point.time),
+            OrbitMenuItem::ShowState => self.shown_point = Some(point),
+            OrbitMenuItem::Circularize(apsis) => {
+                if let FocusPoint::Ship(ship_id) = point.owner {
+                    self.circularize(ship_id, apsis, point.time);
+                }
+            }
+            OrbitMenuItem::MatchPlane(node) => {
+                if let FocusPoint::Ship(ship_id) = point.owner {
+                    self.match_target_plane(ship_id, node, point.time);
+                }
+            }
+        }
+        Some(item)
+    }
+
+    /// Adds a burn to the ship's plan that circularizes it at the next pass
+    /// through the apsis after the given time.
+    fn circularize(&mut self, ship_id: ShipID, apsis: Apsis, time: f64) {
+        if !self.check_editable() {
+            return;
+        }
+        let message = match add_circularization(&mut self.timeline, ship_id, apsis, time) {
+            Ok(maneuver) => {
+                self.update_state_by(0.0);
+                let ship = self.orrery.get_ship(ship_id);
+                self.session_changes.push(SessionChange::SetManeuverPlan(
+                    ship_id,
+                    ship.maneuvers.clone(),
+                ));
+                format!(
+                    "Planned a {:.1} m/s burn to circularize at {}",
+                    maneuver.delta_v.norm(),
+                    format_seconds(maneuver.time)
+                )
+            }
+            Err(e) => format!("Can't circularize: {}", e),
+        };
+        self.notification = Some((message, Instant::now()));
+    }
+
+    /// Adds a burn to the ship's plan that puts it in the target's plane, at
+    /// the next pass through the node after the given time.
+    fn match_target_plane(&mut self, ship_id: ShipID, node: Node, time: f64) {
+        if !self.check_editable() {
+            return;
+        }
+        let target = match self.target {
+            Some(FocusPoint::Body(id)) => ApproachTarget::Body(id),
+            Some(FocusPoint::Ship(id)) if id != ship_id => ApproachTarget::Ship(id),
+            _ => return,
+        };
+        let message = match add_plane_change(&mut self.timeline, ship_id, target, Some(node), time)
+        {
+            Ok(plane_change) => {
+                self.update_state_by(0.0);
+                let ship = self.orrery.get_ship(ship_id);
+                self.session_changes.push(SessionChange::SetManeuverPlan(
+                    ship_id,
+                    ship.maneuvers.clone(),
+                ));
+                format!(
+                    "Planned a {:.1} m/s burn at the {} to take out {:.2} degrees of inclination",
+                    plane_change.maneuver.delta_v.norm(),
+                    plane_change.node,
+                    plane_change.inclination.to_degrees()
+                )
+            }
+            Err(e) => format!("Can't match the target's plane: {}", e),
+        };
+        self.notification = Some((message, Instant::now()));
+    }
+
+    /// Focuses the ship, and starts typing in a new maneuver node for it at
+    /// the given time.
+    fn start_node_at(&mut self, ship_id: ShipID, time: f64) {
+        if !self.check_editable() || self.orrery.get_ship(ship_id).has_crashed() {
+            return;
+        }
+        self.start_focus_transition();
+        self.camera_focus.set_point(FocusPoint::Ship(ship_id));
+        self.fix_camera_zoom();
+        self.update_scene_objects();
+
+        let plan = self.orrery.get_ship(ship_id).maneuvers.clone();
+        self.node_editor = Some(NodeEditor::new(
+            ship_id,
+            plan.clone(),
+            plan,
+            TimeReference::Absolute,
+            time,
+            Vector3::zeros(),
+        ));
+        self.refresh_node();
+    }
+
+    /// Flashes the current notification at the top of the window, until it
+    /// expires.
+    fn draw_notification(&mut self, window: &mut Window) {
+        let (text, shown_at) = match &self.notification {
+            Some(notification) => notification,
+            None => return,
+        };
+        let elapsed = shown_at.elapsed().as_secs_f32();
+        if elapsed > NOTIFICATION_DURATION {
+            self.notification = None;
+            return;
+        }
+
+        // Alternate between two colors, and center it as best we can
+        let color = if ((elapsed / NOTIFICATION_FLASH_PERIOD) as u32).is_multiple_of(2) {
+            Point3::new(1.0, 0.8, 0.3)
+        } else {
+            Point3::new(1.0, 1.0, 1.0)
+        };
+        let font_size = NOTIFICATION_FONT_SIZE * self.ui_scale;
+        let text_width = 0.55 * font_size * text.chars().count() as f32;
+        let center = window.width() as f32 * TEXT_COORDS_PER_PIXEL / 2.0;
+        let position = Point2::new(center - text_width / 2.0, 20.0 * self.ui_scale);
+        window.draw_text(
+            text,
+            &position,
+            font_size,
+            &kiss3d::text::Font::default(),
+            &color,
+        );
+    }
+
+    /// Draws a small piece of text with its top-left corner at the given
+    /// window coordinates.
+    fn draw_label(
+        &self,
+        window: &mut Window,
+        screen_pt: Point2<f32>,
+        text: &str,
+        color: &Point3<f32>,
+    ) {
+        window.draw_text(
+            text,
+            &Point2::from(screen_pt.coords * TEXT_COORDS_PER_PIXEL),
+            LABEL_FONT_SIZE * self.ui_scale,
+            &kiss3d::text::Font::default(),
+            color,
+        );
+    }
+
+    /// Returns the orbit of the given object, if it has a meaningful one.
+    fn orbit_of_point(&self, point: FocusPoint) -> Option<TimedOrbit<&Body, ()>> {
+        match point {
+            FocusPoint::Body(id) => self
+                .orrery
+                .orbit_of_body(id)
+                .map(|orbit| orbit.with_secondary(())),
+            FocusPoint::Ship(id) => {
+                let ship = self.orrery.get_ship(id);
+                if ship.has_crashed() || ship.numeric_arc.is_some() {
+                    return None;
+                }
+                Some(self.orrery.orbit_of_ship(id).with_secondary(()))
+            }
+        }
+    }
+
+    fn point_name(&self, point: FocusPoint) -> String {
+        match point {
+            FocusPoint::Body(id) => self.orrery.get_body(id).info.name.clone(),
+            FocusPoint::Ship(id) => self.orrery.get_ship(id).display_name(),
+        }
+    }
+
+    /// The menu of things to do at a picked point on an orbit, and where to
+    /// put it, in window pixels.
+    fn orbit_menu_panel(&self) -> Option<(Point2<f32>, Panel)> {
+        let menu = self.orbit_menu.as_ref()?;
+        let mut panel = Panel::new(format!(
+            "{} in {}",
+            self.point_name(menu.point.owner),
+            format_seconds(menu.point.time - self.time)
+        ));
+        for item in &menu.items {
+            panel = panel.line(item.label());
+        }
+        Some((menu.position, panel))
+    }
+
+    /// Where the object whose orbit was picked will be at the picked point, and
+    /// how fast it'll be going, until it gets there.
+    fn point_panel(&self) -> Option<Panel> {
+        let point = self.shown_point.as_ref()?;
+        if point.time <= self.time {
+            return None;
+        }
+
+        let body = self.orrery.get_body(point.parent_id);
+        let radius = point.position.norm();
+        Some(
+            Panel::new(format!(
+                "{} in {}",
+                self.point_name(point.owner),
+                format_seconds(point.time - self.time)
+            ))
+            .field(
+                "Altitude",
+                format!(
+                    "{:.1} km above {}",
+                    (radius - body.info.radius as f64) / 1000.0,
+                    body.info.name
+                ),
+            )
+            .field("Speed", format!("{:.1} m/s", point.velocity.norm()))
+            .field(
+                "Vertical speed",
+                format!("{:.1} m/s", point.velocity.dot(&point.position) / radius),
+            )
+            .line("Click to close"),
+        )
+    }
+
+    /// Details of the event whose marker was clicked on, until it's happened.
+    fn event_panel(&self) -> Option<Panel> {
+        let (event, body_id) = self.selected_event.as_ref()?;
+        if event.point.time <= self.time {
+            return None;
+        }
+
+        let body = self.orrery.get_body(*body_id);
+        let mut panel = Panel::new(format!(
+            "{}: {}",
+            self.point_name(FocusPoint::Ship(event.ship_id)),
+            self.describe_event(event)
+        ))
+        .field("In", format_seconds(event.point.time - self.time))
+        .field(
+            "Altitude",
+            format!(
+                "{:.1} km above {}",
+                (event.point.location.coords.norm() - body.info.radius as f64) / 1000.0,
+                body.info.name
+            ),
+        );
+
+        let ship = self.orrery.get_ship(event.ship_id);
+        if let Some(maneuver) = ship.maneuvers.maneuver_at(event.point.time) {
+            let delta_v = maneuver.delta_v;
+            panel = panel.field(
+                "Delta-v",
+                format!("({:.1}, {:.1}, {:.1}) m/s", delta_v.x, delta_v.y, delta_v.z),
+            );
+        }
+        Some(panel.line("Click its marker again to close"))
+    }
+
+    fn target_panel(&self) -> Option<Panel> {
+        let target = self.target?;
+
+        let relative_inclination = match (
+            self.orbit_of_point(self.camera_focus.point()),
+            self.orbit_of_point(target),
+        ) {
+            (Some(orbit), Some(reference)) => format!(
+                "{:.3}",
+                orbit.nodes_relative_to(&reference).inclination.to_degrees()
+            ),
+            _ => String::from("N/A"),
+        };
+
+        let mut panel = Panel::new(format!("Target: {}", self.point_name(target)))
+            .field("Rel. inclination", relative_inclination);
+        if let Some(phasing) = self.phasing(target) {
+            let name = match phasing.mover {
+                FocusPoint::Body(id) if phasing.mover != self.camera_focus.point() => {
+                    format!(
+                        "Phase angle (from {})",
+                        self.point_name(FocusPoint::Body(id))
+                    )
+                }
+                _ => String::from("Phase angle"),
+            };
+            panel = panel.field(
+                &name,
+                format!(
+                    "{:+.1} (Hohmann {:+.1})",
+                    phasing.current.to_degrees(),
+                    phasing.hohmann.to_degrees()
+                ),
+            );
+            if let Some((angle, ideal)) = self.ejection_angles(&phasing) {
+                let direction = if phasing.outward {
+                    "prograde"
+                } else {
+                    "retrograde"
+                };
+                let ideal = match ideal {
+                    Some(ideal) => format!(", ideally {:.1}", ideal.to_degrees()),
+                    None => String::new(),
+                };
+                panel = panel.field(
+                    "Ejection angle",
+                    format!("{:.1} to {}{}", angle.to_degrees(), direction, ideal),
+                );
+            }
+        }
+        Some(panel)
+    }
+
+    /// Works out the phase angle to the target, around the body it orbits.
+    /// If the focus orbits something else, it's from whichever of the bodies
+    /// it's around orbits the same body as the target, e.g., from Kerbin to
+    /// Duna for a ship around Kerbin.
+    fn phasing(&self, target: FocusPoint) -> Option<Phasing> {
+        let parent_of = |point: FocusPoint| match point {
+            FocusPoint::Body(id) => self.orrery.get_parent(id),
+            FocusPoint::Ship(id) => Some(self.orrery.get_ship(id).parent_id()),
+        };
+        let primary_id = parent_of(target)?;
+        let mut mover = self.camera_focus.point();
+        while parent_of(mover)? != primary_id {
+            mover = FocusPoint::Body(parent_of(mover)?);
+        }
+        if mover == target {
+            return None;
+        }
+
+        let frame = Frame::BodyInertial(primary_id);
+        let state_of = |point: FocusPoint| match point {
+            FocusPoint::Body(id) => self.orrery.get_body_state(id, self.time),
+            FocusPoint::Ship(id) => self.orrery.get_ship_state(id, self.time),
+        };
+        let position = state_of(mover).get_position(frame, self.time).coords;
+        let velocity = state_of(mover).get_velocity(frame, self.time);
+        let target_position = state_of(target).get_position(frame, self.time).coords;
+        let normal = Unit::try_new(position.cross(&velocity), 0.0)?;
+
+        let mu = self.orrery.get_body(primary_id).info.mu;
+        Some(Phasing {
+            mover,
+            current: phase_angle(&position, &target_position, &normal),
+            hohmann: hohmann_phase_angle(mu, position.norm(), target_position.norm()),
+            outward: target_position.norm() > position.norm(),
+        })
+    }
+
+    /// For a ship leaving a body for a target around the body's own primary,
+    /// how far its next planned burn is from the body's prograde direction
+    /// (or retrograde, if the target's further in), and how far it'd be for
+    /// the burn to send it the right way, if it escapes. The ideal is for a
+    /// burn at periapsis, which a departure from a low orbit nearly is.
+    fn ejection_angles(&self, phasing: &Phasing) -> Option<(f64, Option<f64>)> {
+        let ship_id = match self.camera_focus.point() {
+            FocusPoint::Ship(id) => id,
+            FocusPoint::Body(_) => return None,
+        };
+        let body_id = match phasing.mover {
+            FocusPoint::Body(id) => id,
+            FocusPoint::Ship(_) => return None,
+        };
+        let ship = self.orrery.get_ship(ship_id);
+        if ship.parent_id() != body_id {
+            return None;
+        }
+        let burn = ship.maneuvers.next_after(self.time)?;
+        let orbit = self.orbit_of_point(FocusPoint::Ship(ship_id))?;
+
+        let state = orbit.state_at_time(burn.time);
+        let (position, velocity) = (state.position(), state.velocity() + burn.delta_v);
+        let normal = Unit::try_new(position.cross(&state.velocity()), 0.0)?;
+        let body_frame = Frame::BodyInertial(self.orrery.get_parent(body_id)?);
+        let body_velocity = self
+            .orrery
+            .get_body_state(body_id, burn.time)
+            .get_velocity(body_frame, burn.time);
+        let direction = if phasing.outward {
+            body_velocity
+        } else {
+            -body_velocity
+        };
+
+        let mu = self.orrery.get_body(body_id).info.mu;
+        let energy = velocity.norm_squared() / 2.0 - mu / position.norm();
+        let ideal = (energy > 0.0)
+            .then(|| ideal_ejection_angle(mu, position.norm(), (2.0 * energy).sqrt()));
+        Some((ejection_angle(&position, &direction, &normal), ideal))
+    }
+
+    fn encounter_panel(&self) -> Option<Panel> {
+        let ship_id = match self.camera_focus.point() {
+            FocusPoint::Body(_) => return None,
+            FocusPoint::Ship(id) => id,
+        };
+
+        let (target_id, encounter_time) = self.timeline.events().find_map(|e| match &e.data {
+            EventData::EnteringSOI(soi_change)
+                if e.ship_id == ship_id && e.point.time > self.time =>
+            {
+                Some((soi_change.new, e.point.time))
+            }
+            _ => None,
+        })?;
+        let b_plane = next_encounter_b_plane(&self.timeline, ship_id, target_id, self.time)?;
+
+        let asymptote = b_plane.incoming_asymptote;
+        Some(
+            Panel::new(format!(
+                "Encounter: {} in {}",
+                self.orrery.get_body(target_id).info.name,
+                format_seconds(encounter_time - self.time),
+            ))
+            .field("B.T", format!("{:.1} km", b_plane.b_dot_t / 1000.0))
+            .field("B.R", format!("{:.1} km", b_plane.b_dot_r / 1000.0))
+            .field(
+                "Asymptote RA",
+                format!("{:.1}", asymptote.y.atan2(asymptote.x).to_degrees()),
+            )
+            .field(
+                "Asymptote Dec",
+                format!("{:.1}", asymptote.z.asin().to_degrees()),
+            ),
+        )
+    }
+
+    fn focus_panel(&self) -> Panel {
+        let focus = self.camera_focus.point();
+        let (state, parent) = match focus {
+            FocusPoint::Body(id) => (
+                self.orrery.get_body_state(id, self.time),
+                self.orrery.get_parent(id),
+            ),
+            FocusPoint::Ship(id) => (
+                self.orrery.get_ship_state(id, self.time),
+                Some(self.orrery.get_ship(id).parent_id()),
+            ),
+        };
+        let frame = match parent {
+            Some(id) => Frame::BodyInertial(id),
+            None => Frame::Root,
+        };
+
+        // The root body has no surface to move across, and there might not be
+        // a target, so there's not always a speed to show
+        let speed_frame = match self.speed_mode {
+            SpeedMode::Orbit => Some(frame),
+            SpeedMode::Surface => parent.map(Frame::BodyFixed),
+            SpeedMode::Target => {
+                self.target
+                    .filter(|target| *target != focus)
+                    .map(|target| match target {
+                        FocusPoint::Body(id) => Frame::BodyInertial(id),
+                        FocusPoint::Ship(id) => Frame::ShipInertial(id),
+                    })
+            }
+        };
+        let speed = match speed_frame {
+            Some(speed_frame) => format!(
+                "{:.0} m/s",
+                state.get_velocity(speed_frame, self.time).norm()
+            ),
+            None => String::from("-"),
+        };
+
+        let panel = Panel::new(format!("Focused on: {}", self.focused_body_name()))
+            .field(
+                "Radius",
+                format!(
+                    "{:.0} m",
+                    state.get_position(frame, self.time).coords.norm()
+                ),
+            )
+            .field(&format!("Speed ({})", self.speed_mode), speed);
+
+        // For ships, also show how steeply they're climbing or falling, which
+        // is what matters for captures, landings, and aerobraking
+        match focus {
+            FocusPoint::Body(id) => self.add_visits(panel, id),
+            FocusPoint::Ship(id) => {
+                let ship_state = self.orrery.get_ship(id).state_at_time(self.time);
+                let panel = panel
+                    .field(
+                        "Vertical speed",
+                        format!("{:+.0} m/s", ship_state.radial_velocity()),
+                    )
+                    .field(
+                        "Flight path angle",
+                        format!("{:+.1}", ship_state.flight_path_angle().to_degrees()),
+                    );
+                match self.notes.get(NoteTarget::Ship(id)) {
+                    Some(note) => panel.field("Note", note),
+                    None => panel,
+                }
+            }
+        }
+    }
+
+    /// Adds who's been through the body's SOI so far, and when, to its
+    /// panel, latest first.
+    fn add_visits(&self, mut panel: Panel, body_id: BodyID) -> Panel {
+        let log =
+            VisitLog::from_events(self.timeline.events().filter(|e| e.point.time <= self.time));
+        let visits = log.visits(body_id);
+        if visits.is_empty() {
+            return panel;
+        }
+
+        panel = panel.field(
+            "Visits",
+            format!("{} by {} ships", visits.len(), log.num_ships(body_id)),
+        );
+        for visit in visits.iter().rev().take(MAX_LISTED_VISITS) {
+            let entered = visit.entered.map_or(String::from("start"), format_seconds);
+            let exited = visit.exited.map_or(String::from("now"), format_seconds);
+            panel = panel.line(format!(
+                "{}: {} to {}",
+                self.point_name(FocusPoint::Ship(visit.ship_id)),
+                entered,
+                exited
+            ));
+        }
+        if visits.len() > MAX_LISTED_VISITS {
+            panel = panel.line(format!(
+                "...and {} earlier",
+                visits.len() - MAX_LISTED_VISITS
+            ));
+        }
+        panel
+    }
+
+    fn readouts_panel(&self) -> Option<Panel> {
+        if self.custom_readouts.is_empty() {
+            return None;
+        }
+
+        let lookup = |name: &str| self.readout_quantity(name);
+        let mut panel = Panel::new("Readouts");
+        for readout in self.custom_readouts.iter() {
+            let value = match readout.expression.evaluate(&lookup) {
+                Some(x) if x.abs() >= 1000.0 => format!("{:.0}", x),
+                Some(x) => format!("{:.3}", x),
+                None => String::from("-"),
+            };
+            panel = panel.field(&readout.label, value);
+        }
+        Some(panel)
+    }
+
+    /// Works out one of the [QUANTITIES](crate::model::readouts::QUANTITIES)
+    /// for the focused object, if it makes sense for it.
+    fn readout_quantity(&self, name: &str) -> Option<f64> {
+        if name == "time" {
+            return Some(self.time);
+        }
+
+        // Crashed ships sit on the surface, so their state isn't on their orbit
+        let (position, velocity, orbit) = match self.camera_focus.point() {
+            FocusPoint::Body(id) => {
+                let orbit = self.orrery.orbit_of_body(id)?.with_secondary(());
+                let state = orbit.state_at_time(self.time);
+                (state.position(), state.velocity(), orbit)
+            }
+            FocusPoint::Ship(id) => {
+                let state = self.orrery.get_ship(id).state_at_time(self.time);
+                let orbit = self.orrery.orbit_of_ship(id).with_secondary(());
+                (state.position(), state.velocity(), orbit)
+            }
+        };
+        let parent = orbit.primary();
+        let body_radius = parent.info.radius as f64;
+
+        let value = match name {
+            "altitude" => position.norm() - body_radius,
+            "radius" => position.norm(),
+            "speed" => velocity.norm(),
+            "vertical_speed" => position.dot(&velocity) / position.norm(),
+            "apoapsis" => orbit.apoapsis()? - body_radius,
+            "periapsis" => orbit.periapsis() - body_radius,
+            "sma" => orbit.semimajor_axis(),
+            "eccentricity" => orbit.eccentricity(),
+            "inclination" => orbit.inclination().to_degrees(),
+            "period" => orbit.period()?,
+            "body_radius" => body_radius,
+            "atmosphere_height" => parent.info.atmosphere_height?,
+            "soi_radius" => self.orrery.get_soi_radius(parent.id)?,
+            _ => unreachable!("Unknown quantity {}", name),
+        };
+        Some(value)
+    }
+
+    fn integrator_panel(&self) -> Option<Panel> {
+        let ship = match self.camera_focus.point() {
+            FocusPoint::Body(_) => return None,
+            FocusPoint::Ship(id) => self.orrery.get_ship(id),
+        };
+        if !ship.is_integrated() {
+            return None;
+        }
+
+        // Measured along the arc the ship's on now, when it was integrated
+        let drift = ship.numeric_arc.as_ref().and_then(|arc| arc.energy_drift);
+        let drift_text = match drift {
+            None => String::from("N/A"),
+            Some(drift) => match drift.recommendation {
+                None => format!("{:.1e}/rev", drift.per_revolution),
+                Some(recommendation) => format!(
+                    "{:.1e}/rev (too high, try {})",
+                    drift.per_revolution, recommendation
+                ),
+            },
+        };
+
+        Some(
+            Panel::new(format!("Integrator: {}", ship.integrator))
+                .field("Energy drift", drift_text),
+        )
+    }
+
+    fn sail_panel(&self) -> Option<Panel> {
+        let sail = match self.camera_focus.point() {
+            FocusPoint::Body(_) => return None,
+            FocusPoint::Ship(id) => self.orrery.get_ship(id).sail.as_ref()?,
+        };
+
+        let attitude = sail.schedule.attitude_at(self.time);
+        let next_change = match sail.schedule.next_change_after(self.time) {
+            Some(time) => format!("T-{}", format_seconds(time - self.time)),
+            None => String::from("N/A"),
+        };
+
+        Some(
+            Panel::new("Sail")
+                .field("Cone", format!("{:.0}", attitude.cone.to_degrees()))
+                .field("Clock", format!("{:.0}", attitude.clock.to_degrees()))
+                .field("Next change", next_change),
+        )
+    }
+
+    /// Adds up the focused ship's burns, and the estimated cost of its
+    /// arrival, and compares them against how much delta-v it has, if that's
+    /// known. The next few burns are listed too,
+    /// with how long until each.
+    fn delta_v_panel(&self) -> Option<Panel> {
+        let ship = match self.camera_focus.point() {
+            FocusPoint::Body(_) => return None,
+            FocusPoint::Ship(id) => self.orrery.get_ship(id),
+        };
+        if ship.maneuvers.is_empty()
+            && ship.delta_v_capacity.is_none()
+            && ship.stages.is_none()
+            && ship.arrival.is_none()
+        {
+            return None;
+        }
+
+        let ledger = mission_ledger(&self.timeline, ship, self.time);
+        let mut panel = Panel::new("Delta-v")
+            .field("Spent", format!("{:.1} m/s", ledger.spent))
+            .field("Planned", format!("{:.1} m/s", ledger.planned));
+        if let Some(arrival) = ship.arrival {
+            panel = panel.field(
+                "Estimated",
+                format!("{:.1} m/s to {}", ledger.estimated, arrival),
+            );
+        }
+        if let Some(stages) = ship.stages_at(self.time) {
+            let stage = match stages.current_stage() {
+                Some(idx) => format!("{} of {}", idx + 1, stages.stages().len()),
+                None => String::from("all spent"),
+            };
+            panel = panel
+                .field("Stage", stage)
+                .field("Mass", format!("{:.0} kg", stages.mass()));
+            // TWR against wherever the ship is now, which is what matters
+            // for landing on it or taking off from it
+            let parent = ship.orbit.primary();
+            for summary in stages.summaries(parent.info.surface_gravity()) {
+                panel = panel.line(format!(
+                    "Stage {}: {:.1} m/s, TWR {:.2} at {}",
+                    summary.index + 1,
+                    summary.delta_v,
+                    summary.twr,
+                    parent.info.name
+                ));
+            }
+        }
+        if let (Some(capacity), Some(remaining)) = (ledger.capacity, ledger.remaining()) {
+            panel = panel
+                .field(
+                    "Total",
+                    format!("{:.1} of {:.1} m/s", ledger.total(), capacity),
+                )
+                .field("Left after plan", format!("{:.1} m/s", remaining));
+            if ledger.is_over_capacity() {
+                panel = panel.line(format!("WARNING: {:.1} m/s short", -remaining));
+            }
+        } else if !ship.maneuvers.is_empty() || ship.arrival.is_some() {
+            panel = panel.field("Total", format!("{:.1} m/s", ledger.total()));
+        }
+
+        let upcoming = ship.maneuvers.upcoming(self.time);
+        for maneuver in upcoming.iter().take(MAX_LISTED_BURNS) {
+            panel = panel.line(format!(
+                "T-{}: {:.1} m/s",
+                format_seconds(maneuver.time - self.time),
+                maneuver.delta_v.norm()
+            ));
+        }
+        if upcoming.len() > MAX_LISTED_BURNS {
+            panel = panel.line(format!(
+                "...and {} more burns",
+                upcoming.len() - MAX_LISTED_BURNS
+            ));
+        }
+        Some(panel)
+    }
+
+    fn placement_panel(&self) -> Option<Panel> {
+        let placement = self.placement.as_ref()?;
+        let parent = self.orrery.get_body(placement.parent_id);
+        let uncertainty = &self.placement_uncertainty;
+        let editing = if self.editing_uncertainty {
+            "uncertainty"
+        } else {
+            "elements"
+        };
+        Some(
+            Panel::new(format!("New ship around {}", parent.info.name))
+                .field(
+                    "SMA",
+                    format!(
+                        "{:.0} +/- {:.0}",
+                        placement.semimajor_axis, uncertainty.semimajor_axis
+                    ),
+                )
+                .field(
+                    "Altitude",
+                    format!(
+                        "{:.0}",
+                        placement.semimajor_axis - parent.info.radius as f64
+                    ),
+                )
+                .field(
+                    "Inclination",
+                    format!(
+                        "{:.0} +/- {:.1}",
+                        placement.inclination.to_degrees(),
+                        uncertainty.inclination.to_degrees()
+                    ),
+                )
+                .field(
+                    "Mean anomaly",
+                    format!(
+                        "{:.0} +/- {:.0}",
+                        placement.mean_anomaly.to_degrees(),
+                        uncertainty.mean_anomaly.to_degrees()
+                    ),
+                )
+                .line("Left/Right: anomaly, Up/Down: SMA")
+                .line("PgUp/PgDn: inclination")
+                .line(format!(
+                    "U: switch between elements and uncertainty (now {})",
+                    editing
+                ))
+                .line("Enter: add, Escape: cancel"),
+        )
+    }
+
+    /// Shows how far off the focused ship's orbit and encounters could be, if
+    /// it was placed with uncertain elements.
+    fn uncertainty_panel(&self) -> Option<Panel> {
+        let envelope = match self.camera_focus.point() {
+            FocusPoint::Body(_) => return None,
+            FocusPoint::Ship(id) => self.uncertainty_envelopes.get(&id)?,
+        };
+        let range = |envelope: &Envelope, format: &dyn Fn(f64) -> String| {
+            format!("{} to {}", format(envelope.min), format(envelope.max))
+        };
+        let meters = |x: f64| format!("{:.0}", x);
+
+        let mut panel = Panel::new(format!("Uncertainty: {} extremes", envelope.num_extremes))
+            .field("Periapsis", range(&envelope.periapsis, &meters))
+            .field("Apoapsis", range(&envelope.apoapsis, &meters));
+        if envelope.encounters.is_empty() {
+            panel = panel.line("No encounters");
+        }
+        for window in envelope.encounters.iter() {
+            let name = &self.orrery.get_body(window.body_id).info.name;
+            panel = panel.field(
+                &format!(
+                    "{} ({}/{})",
+                    name, window.num_extremes, envelope.num_extremes
+                ),
+                range(&window.time, &format_seconds),
+            );
+        }
+        Some(panel)
+    }
+
+    fn inspector_panel(&self) -> Option<Panel> {
+        let inspector = self.inspector.as_ref()?;
+        let id = inspector.body_id;
+        let info = &self.orrery.get_body(id).info;
+        let edit = &inspector.edit;
+
+        let mut rows = vec![
+            (
+                InspectorField::Mu,
+                format!("Mu: {:.4e} m^3/s^2", edit.mu.unwrap_or(info.mu)),
+            ),
+            (
+                InspectorField::Radius,
+                format!("Radius: {:.0} m", edit.radius.unwrap_or(info.radius)),
+            ),
+        ];
+        if let Some(soi_radius) = self.orrery.get_soi_radius(id) {
+            let soi_radius = edit.soi_radius.unwrap_or(soi_radius);
+            rows.push((InspectorField::Soi, format!("SOI: {:.0} m", soi_radius)));
+        }
+
+        let fields = self.inspector_fields(id);
+        let mut panel = Panel::new(format!("Editing {}", info.name));
+        for (field, text) in rows {
+            let marker = if field == inspector.selected {
+                ">"
+            } else {
+                " "
+            };
+            let suffix = if fields.contains(&field) {
+                ""
+            } else {
+                " (computed)"
+            };
+            panel = panel.line(format!("{} {}{}", marker, text, suffix));
+        }
+        Some(
+            panel
+                .line("Up/Down: select, Left/Right: change")
+                .line("Enter: apply from now on, Escape: cancel"),
+        )
+    }
+
+    fn node_editor_panel(&self) -> Option<Panel> {
+        let editor = self.node_editor.as_ref()?;
+        let rows = [
+            (
+                NodeField::Time,
+                format!(
+                    "Time ({}): {} s",
+                    editor.time_reference,
+                    editor.text(NodeField::Time)
+                ),
+            ),
+            (
+                NodeField::Prograde,
+                format!("Prograde: {} m/s", editor.text(NodeField::Prograde)),
+            ),
+            (
+                NodeField::Normal,
+                format!("Normal: {} m/s", editor.text(NodeField::Normal)),
+            ),
+            (
+                NodeField::Radial,
+                format!("Radial: {} m/s", editor.text(NodeField::Radial)),
+            ),
+        ];
+
+        let name = self.point_name(FocusPoint::Ship(editor.ship_id));
+        let mut panel = Panel::new(format!("Maneuver node for {}", name));
+        for (field, text) in rows {
+            let marker = if field == editor.selected { ">" } else { " " };
+            panel = panel.line(format!("{} {}", marker, text));
+        }
+        if editor.time_reference != TimeReference::Absolute {
+            panel = match editor.landmark_time {
+                Some(time) => panel.field("Landmark at", format_seconds(time)),
+                None => panel.line("Landmark not reached (or no target)"),
+            };
+        }
+        panel = match editor.node {
+            Some(node) => {
+                panel = panel
+                    .field("Burn at", format_seconds(node.time))
+                    .field("Delta-v", format!("{:.1} m/s", node.delta_v.norm()));
+                // The node's already in the ship's plan
+                let ship = self.orrery.get_ship(editor.ship_id);
+                match ship.burn_estimate_at(node.time) {
+                    Some(estimate) => panel.field("Burn time", estimate.describe()),
+                    None => panel,
+                }
+            }
+            None => panel.line("No burn (needs a nonzero burn in the future)"),
+        };
+        Some(
+            panel
+                .line("Type numbers, Backspace: delete")
+                .line("Up/Down: select, Tab: time from")
+                .line("Enter: keep, Escape: cancel"),
+        )
+    }
+
+    fn orbit_editor_panel(&self) -> Option<Panel> {
+        let editor = self.orbit_editor.as_ref()?;
+        let mut panel = Panel::new(format!(
+            "Orbit of {} at {}",
+            self.point_name(editor.point),
+            format_seconds(editor.time)
+        ));
+        for field in OrbitField::ALL {
+            let marker = if field == editor.selected { ">" } else { " " };
+            panel = panel.line(format!(
+                "{} {}: {}",
+                marker,
+                field.label(),
+                editor.text(field)
+            ));
+        }
+        if let Some(elements) = editor.elements() {
+            let periapsis = elements.semimajor_axis * (1.0 - elements.eccentricity);
+            let apoapsis = elements.semimajor_axis * (1.0 + elements.eccentricity);
+            panel = panel
+                .field("Periapsis radius", format!("{:.0} km", periapsis / 1000.0))
+                .field("Apoapsis radius", format!("{:.0} km", apoapsis / 1000.0));
+        } else {
+            panel = panel.line("Not an elliptic orbit");
+        }
+        Some(
+            panel
+                .line("Type numbers, Backspace: delete")
+                .line("Up/Down: select")
+                .line("Enter: apply from then on, Escape: cancel"),
+        )
+    }
+
+    fn note_editor_panel(&self) -> Option<Panel> {
+        let editor = self.note_editor.as_ref()?;
+        let target = editor.choices[editor.selected];
+        let next_target = editor.choices[(editor.selected + 1) % editor.choices.len()];
+        let mut panel = Panel::new(format!("Note on {}", self.describe_note_target(target)))
+            .line(format!("{}_", editor.text));
+        if next_target != target {
+            panel = panel.line(format!(
+                "Tab: attach to {}",
+                self.describe_note_target(next_target)
+            ));
+        }
+        Some(panel.line("Enter: keep (blank removes), Escape: cancel"))
+    }
+
+    fn describe_note_target(&self, target: NoteTarget) -> String {
+        match target {
+            NoteTarget::Ship(id) => self.point_name(FocusPoint::Ship(id)),
+            NoteTarget::Maneuver(id, time) => format!(
+                "{}'s burn at {}",
+                self.point_name(FocusPoint::Ship(id)),
+                format_seconds(time)
+            ),
+            NoteTarget::Time(time) => format!("time {}", format_seconds(time)),
+        }
+    }
+
+    fn focused_body_name(&self) -> String {
+        match self.camera_focus.point() {
+            FocusPoint::Body(id) => {
+                let body = self.orrery.get_body(id);
+                body.info.name.clone()
+            }
+            FocusPoint::Ship(id) => {
+                format!(
+                    "{} ({})",
+                    self.orrery.get_ship(id).display_name(),
+                    if self.ship_camera_inertial {
+                        "inertial"
+                    } else {
+                        "orbital"
+                    }
+                )
+            }
+        }
+    }
+
+    fn orbit_panel(&self) -> Panel {
         let orbit = match self.camera_focus.point() {
             FocusPoint::Body(id) => match self.orrery.orbit_of_body(id) {
-                None => return String::from("N/A"),
+                None => return Panel::new("Orbiting: N/A"),
                 Some(orbit) => orbit.with_secondary(()),
             },
-            FocusPoint::Ship(id) => self.orrery.orbit_of_ship(id).with_secondary(()),
+            FocusPoint::Ship(id) => {
+                let ship = self.orrery.get_ship(id);
+                if ship.has_crashed() {
+                    let parent_body = self.orrery.get_body(ship.parent_id());
+                    return Panel::new(format!(
+                        "Orbiting: N/A (crashed into {})",
+                        parent_body.info.name
+                    ));
+                }
+                self.orrery.orbit_of_ship(id).with_secondary(())
+            }
         };
 
         let parent_body = self.orrery.get_body(orbit.primary().id);
+        Panel::new(format!("Orbiting: {}", parent_body.info.name))
+            .field("SMA", format!("{:.0}", orbit.semimajor_axis()))
+            .field("Eccentricity", format!("{:.3}", orbit.eccentricity()))
+            .field(
+                "Inclination",
+                format!("{:.3}", orbit.inclination().to_degrees()),
+            )
+            .field("LAN", format!("{:.1}", orbit.long_asc_node().to_degrees()))
+            .field(
+                "Arg PE",
+                format!("{:.1}", orbit.arg_periapse().to_degrees()),
+            )
+    }
 
-        // Indentation is intentional
-        format!(
-            "{}
-    SMA: {:.0}
-    Eccentricity: {:.3}
-    Inclination: {:.3}
-    LAN: {:.1}
-    Arg PE: {:.1}",
-            parent_body.info.name,
-            orbit.semimajor_axis(),
-            orbit.eccentricity(),
-            orbit.inclination().to_degrees(),
-            orbit.long_asc_node().to_degrees(),
-            orbit.arg_periapse().to_degrees(),
-        )
+    /// What the camera is focused on.
+    pub fn focus_point(&self) -> FocusPoint {
+        self.camera_focus.point()
     }
 
-    fn time_summary_text(&self, timestep: f64, fps: f64) -> String {
-        format!(
-            "Time: {}
-Timestep: {} s/frame
-FPS: {:.0}",
-            format_seconds(self.time),
-            timestep,
-            fps,
-        )
+    /// Period of the focused object's orbit, if it's closed. The root body has
+    /// no orbit at all.
+    pub fn focused_period(&self) -> Option<f64> {
+        match self.camera_focus.point() {
+            FocusPoint::Body(id) => self.orrery.orbit_of_body(id)?.period(),
+            FocusPoint::Ship(id) => self.orrery.orbit_of_ship(id).period(),
+        }
+    }
+
+    fn time_panel(&self, controller: &Controller) -> Panel {
+        let timestep = controller.timestep();
+        let state = if controller.is_paused() {
+            "Paused"
+        } else if timestep < 0.0 {
+            "Rewinding"
+        } else {
+            "Running"
+        };
+
+        Panel::new(format!("Time: {}", format_seconds(self.time)))
+            .field("Timestep", format!("{} s/frame ({})", timestep, state))
+            .field("FPS", format!("{:.0}", controller.fps()))
+            .field(
+                "Pause on SOI changes",
+                if controller.pauses_on_soi_changes() {
+                    "on"
+                } else {
+                    "off"
+                },
+            )
+            .field(
+                "Timestep from focused orbit",
+                if controller.picks_timestep_automatically() {
+                    "on"
+                } else {
+                    "off"
+                },
+            )
+            .line("Space: pause, R: reverse, P: auto-pause")
+            .line("Comma/Period: slower/faster, F: auto-timestep")
+    }
+
+    fn bookmarks_panel(&self, controller: &Controller) -> Option<Panel> {
+        let new_name = controller.bookmark_name();
+        if self.bookmarks.is_empty() && new_name.is_none() {
+            return None;
+        }
+
+        let mut panel = Panel::new("Bookmarks");
+        for (i, bookmark) in self.bookmarks.iter().enumerate().take(MAX_LISTED_BOOKMARKS) {
+            panel = panel.line(format!(
+                "{}: {} ({})",
+                i + 1,
+                bookmark.name,
+                format_seconds(bookmark.time)
+            ));
+        }
+        if let Some(name) = new_name {
+            panel = panel.line(format!("New: {}_", name));
+        }
+        Some(panel)
+    }
+
+    /// Lists the next few events for all ships, skipping apsides, which are
+    /// already marked on the orbits.
+    fn events_panel(&self) -> Panel {
+        let upcoming = self
+            .timeline
+            .events()
+            .filter(|e| e.point.time > self.time)
+            .filter(|e| !matches!(e.data, EventData::Periapsis(_) | EventData::Apoapsis(_)))
+            .take(MAX_LISTED_EVENTS);
+
+        let mut panel = Panel::new("Upcoming events");
+        let mut is_empty = true;
+        for event in upcoming {
+            panel = panel.line(format!(
+                "T-{} {}: {}",
+                format_seconds(event.point.time - self.time),
+                self.point_name(FocusPoint::Ship(event.ship_id)),
+                self.describe_event(event),
+            ));
+            is_empty = false;
+        }
+        if is_empty {
+            panel = panel.line("None found");
+        }
+        panel
+    }
+
+    /// Lists the next few interesting moments, after the current time.
+    fn moments_panel(&self) -> Option<Panel> {
+        let mut upcoming = self
+            .moments
+            .iter()
+            .filter(|m| m.time > self.time)
+            .take(MAX_LISTED_MOMENTS)
+            .peekable();
+        upcoming.peek()?;
+
+        let mut panel = Panel::new("Interesting moments (N/M: jump)");
+        for moment in upcoming {
+            panel = panel.line(format!(
+                "T-{}: {}",
+                format_seconds(moment.time - self.time),
+                self.describe_moment(moment)
+            ));
+        }
+        Some(panel)
     }
 
+    /// Lists the next few notes on times, after the current time.
+    fn notes_panel(&self) -> Option<Panel> {
+        let times = self.notes.times();
+        let mut upcoming = times
+            .iter()
+            .filter(|(time, _)| *time > self.time)
+            .take(MAX_LISTED_NOTES)
+            .peekable();
+        upcoming.peek()?;
+
+        let mut panel = Panel::new("Notes (X: add)");
+        for (time, text) in upcoming {
+            panel = panel.line(format!("T-{}: {}", format_seconds(time - self.time), text));
+        }
+        Some(panel)
+    }
+
+    fn describe_moment(&self, moment: &Moment) -> String {
+        let name = |id: BodyID| &self.orrery.get_body(id).info.name;
+        match &moment.kind {
+            MomentKind::ClosestApproach {
+                ship_id,
+                target,
+                distance,
+            } => {
+                let target_name = match *target {
+                    ApproachTarget::Body(id) => name(id).clone(),
+                    ApproachTarget::Ship(id) => format!("ship {}", id.0),
+                };
+                format!(
+                    "Ship {} closest to {} ({:.0} km)",
+                    ship_id.0,
+                    target_name,
+                    distance / 1000.0
+                )
+            }
+            MomentKind::Eclipse {
+                ship_id,
+                body_id,
+                duration,
+            } => format!(
+                "Ship {} longest {} eclipse ({})",
+                ship_id.0,
+                name(*body_id),
+                format_seconds(*duration)
+            ),
+            MomentKind::Alignment {
+                parent_id,
+                body_ids,
+                spread,
+            } => {
+                let names: Vec<_> = body_ids.iter().map(|id| name(*id).as_str()).collect();
+                format!(
+                    "{} aligned around {} ({:.1} deg)",
+                    names.join(", "),
+                    name(*parent_id),
+                    spread.to_degrees()
+                )
+            }
+            MomentKind::RecordAltitude {
+                ship_id,
+                body_id,
+                altitude,
+                is_highest,
+            } => format!(
+                "Ship {} {} above {} ({:.0} km)",
+                ship_id.0,
+                if *is_highest { "highest" } else { "lowest" },
+                name(*body_id),
+                altitude / 1000.0
+            ),
+        }
+    }
+
+    fn describe_event(&self, event: &Event) -> String {
+        let name = |id: BodyID| &self.orrery.get_body(id).info.name;
+        match &event.data {
+            EventData::EnteringSOI(change) => format!("Encounter {}", name(change.new)),
+            EventData::ExitingSOI(change) => format!("Escape {}", name(change.old)),
+            EventData::Impact(id) => format!("Impact {}", name(*id)),
+            EventData::EnteringAtmosphere(id) => format!("Enter {} atmosphere", name(*id)),
+            EventData::ExitingAtmosphere(id) => format!("Exit {} atmosphere", name(*id)),
+            EventData::Periapsis(id) => format!("Periapsis at {}", name(*id)),
+            EventData::Apoapsis(id) => format!("Apoapsis at {}", name(*id)),
+            EventData::EnteringIntegrationZone(id) => {
+                format!("Start integrating near {}", name(*id))
+            }
+            EventData::ExitingIntegrationZone(change) => {
+                format!("Stop integrating, orbiting {}", name(change.new))
+            }
+            EventData::EnteringShadow(id) => format!("Enter {} shadow", name(*id)),
+            EventData::ExitingShadow(id) => format!("Exit {} shadow", name(*id)),
+            EventData::EnteringEclipse(eclipse) => {
+                format!(
+                    "Enter {} {}",
+                    name(eclipse.body),
+                    region_name(eclipse.region)
+                )
+            }
+            EventData::ExitingEclipse(eclipse) => {
+                format!(
+                    "Exit {} {}",
+                    name(eclipse.body),
+                    region_name(eclipse.region)
+                )
+            }
+            EventData::LosingSight(occultation) => format!(
+                "{} hidden by {}",
+                self.orrery.get_ship(occultation.target).display_name(),
+                name(occultation.body)
+            ),
+            EventData::RegainingSight(occultation) => format!(
+                "{} out from behind {}",
+                self.orrery.get_ship(occultation.target).display_name(),
+                name(occultation.body)
+            ),
+            EventData::SailAttitudeChange => String::from("Turn sail"),
+            EventData::Staging(idx) => format!("Drop stage {}", idx + 1),
+            EventData::Modification => String::from("Edited"),
+            EventData::Maneuver => {
+                let ship = self.orrery.get_ship(event.ship_id);
+                let maneuver = match ship.maneuvers.maneuver_at(event.point.time) {
+                    Some(maneuver) => maneuver,
+                    None => return String::from("Burn"),
+                };
+                let mut text = format!("Burn {:.1} m/s", maneuver.delta_v.norm());
+                if let Some(estimate) = ship.burn_estimate_at(maneuver.time) {
+                    text = format!("{} ({})", text, estimate.describe());
+                }
+                match self
+                    .notes
+                    .get(NoteTarget::Maneuver(event.ship_id, maneuver.time))
+                {
+                    Some(note) => format!("{} - {}", text, note),
+                    None => text,
+                }
+            }
+        }
+    }
+
+    /// The ships, in the order they're listed in the ships panel.
+    fn listed_ships(&self) -> Vec<ShipID> {
+        let mut ship_ids: Vec<_> = self.orrery.ships().map(|s| s.id).collect();
+        ship_ids.sort();
+        ship_ids
+    }
+
+    fn ships_panel(&self, controller: &Controller) -> Panel {
+        // Each ship's next event, leaving out apsides like the events panel
+        let mut next_events: HashMap<ShipID, &Event> = HashMap::new();
+        for event in self
+            .timeline
+            .events()
+            .filter(|e| e.point.time > self.time)
+            .filter(|e| !matches!(e.data, EventData::Periapsis(_) | EventData::Apoapsis(_)))
+        {
+            next_events.entry(event.ship_id).or_insert(event);
+        }
+
+        let mut panel = Panel::new("Ships (click: focus, F2: rename)");
+        for ship_id in self.listed_ships() {
+            let ship = self.orrery.get_ship(ship_id);
+            let point = FocusPoint::Ship(ship.id);
+            let marker = if self.camera_focus.point() == point {
+                "> "
+            } else if self.target == Some(point) {
+                "* "
+            } else {
+                "  "
+            };
+            let status = if ship.has_crashed() {
+                "crashed into"
+            } else {
+                "orbiting"
+            };
+            let name = match controller.ship_name() {
+                Some(new_name) if self.camera_focus.point() == point => format!("{}_", new_name),
+                _ => self.point_name(point),
+            };
+            let next = match next_events.get(&ship.id) {
+                Some(event) => format!(
+                    ", T-{} {}",
+                    format_seconds(event.point.time - self.time),
+                    self.describe_event(event)
+                ),
+                None => String::new(),
+            };
+            panel = panel.line(format!(
+                "{}{} {} {}{}",
+                marker,
+                name,
+                status,
+                self.orrery.get_body(ship.parent_id()).info.name,
+                next,
+            ));
+        }
+        panel
+    }
+
+    #[allow(clippy::type_complexity)]
     pub fn cameras_and_effect_and_renderer(
         &mut self,
     ) -> (
@@ -481,10 +4383,98 @@ FPS: {:.0}",
         Option<&mut dyn Renderer>,
         Option<&mut dyn PostProcessingEffect>,
     ) {
-        (Some(&mut self.camera), None, Some(&mut self.renderer), None)
+        let planar_camera: Option<&mut dyn PlanarCamera> = match self.map_mode {
+            true => Some(&mut self.map_camera),
+            false => None,
+        };
+        (
+            Some(&mut self.camera),
+            planar_camera,
+            Some(&mut self.renderer),
+            None,
+        )
+    }
+}
+
+fn region_name(region: ShadowRegion) -> &'static str {
+    match region {
+        ShadowRegion::Penumbra => "penumbra",
+        ShadowRegion::Umbra => "umbra",
+    }
+}
+
+fn ship_color(ship: &Ship) -> Point3<f32> {
+    ship.color.unwrap_or(Point3::new(1.0, 1.0, 1.0))
+}
+
+/// Which marker, if any, is drawn where the event happens along a
+/// trajectory.
+fn event_marker_type(data: &EventData) -> Option<MarkerType> {
+    match data {
+        EventData::Maneuver => Some(MarkerType::Star),
+        EventData::EnteringSOI(_) | EventData::ExitingSOI(_) => Some(MarkerType::Ring),
+        EventData::Impact(_) => Some(MarkerType::Cross),
+        _ => None,
+    }
+}
+
+/// Brightens a color, for orbits and markers that are picked out.
+fn highlight(color: Point3<f32>) -> Point3<f32> {
+    color + (Point3::new(1.0, 1.0, 1.0) - color) * ORBIT_HIGHLIGHT
+}
+
+/// Keeps whichever orbit is closest to the cursor, as long as it's close
+/// enough to count as hovering over it. `pick` is how far the orbit is from
+/// the cursor, and the true anomaly of the closest point on it.
+fn update_orbit_hover(
+    hover: &mut Option<OrbitHover>,
+    pick: Option<(f32, f64)>,
+    make_hover: impl FnOnce(f32, f64) -> OrbitHover,
+) {
+    let (distance, theta) = match pick {
+        Some((distance, theta)) if distance <= ORBIT_HOVER_DISTANCE => (distance, theta),
+        _ => return,
+    };
+    if hover.as_ref().is_none_or(|h| distance < h.distance) {
+        *hover = Some(make_hover(distance, theta));
     }
 }
 
+/// Tooltip text for an orbit, one element per line.
+fn describe_orbit(name: &str, parent_name: &str, orbit: &PhysicalOrbit) -> String {
+    let period = match orbit.period() {
+        Some(period) => format_seconds(period),
+        None => "N/A (escaping)".to_owned(),
+    };
+    format!(
+        "{}, around {}\nSMA: {:.0}\nEccentricity: {:.3}\nInclination: {:.3}\nPeriod: {}",
+        name,
+        parent_name,
+        orbit.semimajor_axis(),
+        orbit.eccentricity(),
+        orbit.inclination().to_degrees(),
+        period
+    )
+}
+
+/// Converts a point in focus space to window coordinates, with the origin at
+/// the top-left, like the cursor's. If there's a map camera, the point is seen
+/// through that instead.
+fn project_to_window(
+    camera: &ZoomableCamera,
+    map_camera: Option<&MapCamera>,
+    point: &Point3<f32>,
+) -> Point2<f32> {
+    if let Some(map_camera) = map_camera {
+        return map_camera.planar_to_window(Point2::new(point.x, point.y));
+    }
+
+    // The camera projects with the origin at the bottom-left
+    let screen_size = Vector2::new(camera.width() as f32, camera.height() as f32);
+    let projected = camera.project(point, &screen_size);
+    Point2::new(projected.x, screen_size.y - projected.y)
+}
+
 fn format_seconds(seconds: f64) -> String {
     let mut total_seconds = seconds as u64;
     let n_minutes = 60;
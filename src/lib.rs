@@ -1,5 +1,6 @@
 pub mod astro;
 pub mod file;
+pub mod generator;
 pub mod gui;
 pub mod math;
 pub mod model;
@@ -0,0 +1,132 @@
+//! A differential corrector, i.e., Newton's method on a function whose
+//! derivatives we can only get by finite differences.
+//!
+//! This is the usual tool for targeting: the inputs are something we control
+//! (like a burn), the outputs are something we care about (like where we end
+//! up), and the function between them is some expensive propagation.
+
+use nalgebra::{DMatrix, DVector};
+
+#[derive(Debug, Clone, Copy)]
+pub struct CorrectorSettings {
+    pub max_iterations: usize,
+    /// How close the output needs to get to the target, measured with the
+    /// Euclidean norm.
+    pub tolerance: f64,
+    /// Size of the perturbation used for finite differences, in the units of
+    /// the input.
+    pub perturbation: f64,
+}
+
+/// Searches for an input `x`, near `initial`, such that `f(x)` is within
+/// tolerance of `target`. The function may return `None` when it can't be
+/// evaluated, which the corrector will try to back away from.
+///
+/// If there are more inputs than outputs, each step is the smallest change
+/// that should fix the outputs, so the solution tends to stay near `initial`.
+pub fn differential_correction(
+    mut f: impl FnMut(&DVector<f64>) -> Option<DVector<f64>>,
+    initial: DVector<f64>,
+    target: &DVector<f64>,
+    settings: &CorrectorSettings,
+) -> Result<DVector<f64>, String> {
+    let mut x = initial;
+    let mut y = f(&x).ok_or("Couldn't evaluate the function at the initial guess")?;
+
+    for _ in 0..settings.max_iterations {
+        let residual = target - &y;
+        if residual.norm() < settings.tolerance {
+            return Ok(x);
+        }
+
+        // Estimate the Jacobian, one column at a time
+        let mut jacobian = DMatrix::zeros(y.len(), x.len());
+        for i in 0..x.len() {
+            let mut x_perturbed = x.clone();
+            x_perturbed[i] += settings.perturbation;
+            let y_perturbed = f(&x_perturbed).ok_or_else(|| {
+                format!(
+                    "Couldn't evaluate the function when computing derivatives at {}",
+                    x_perturbed
+                )
+            })?;
+            jacobian.set_column(i, &((y_perturbed - &y) / settings.perturbation));
+        }
+
+        // Take a Newton step, using the pseudo-inverse in case the Jacobian isn't
+        // square. If that makes things worse, back off.
+        let step = jacobian.pseudo_inverse(1e-12)? * &residual;
+        let mut scale = 1.0;
+        loop {
+            let x_next = &x + scale * &step;
+            if let Some(y_next) = f(&x_next) {
+                if (target - &y_next).norm() < residual.norm() {
+                    x = x_next;
+                    y = y_next;
+                    break;
+                }
+            }
+
+            scale /= 2.0;
+            if scale < 1e-3 {
+                return Err(format!(
+                    "Got stuck at {}, with residual {}",
+                    x,
+                    residual.norm()
+                ));
+            }
+        }
+    }
+
+    let residual = (target - &y).norm();
+    if residual < settings.tolerance {
+        Ok(x)
+    } else {
+        Err(format!(
+            "Didn't converge after {} iterations; residual is {}",
+            settings.max_iterations, residual
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use nalgebra::dvector;
+
+    use super::*;
+
+    #[test]
+    fn test_square_system() {
+        // Intersection of a circle and a line
+        let f = |x: &DVector<f64>| Some(dvector![x[0] * x[0] + x[1] * x[1], x[0] - x[1]]);
+        let settings = CorrectorSettings {
+            max_iterations: 50,
+            tolerance: 1e-10,
+            perturbation: 1e-7,
+        };
+
+        let solution =
+            differential_correction(f, dvector![1.0, 0.5], &dvector![2.0, 0.0], &settings).unwrap();
+        assert_relative_eq!(solution, dvector![1.0, 1.0], epsilon = 1e-8);
+    }
+
+    #[test]
+    fn test_underdetermined_system() {
+        // Only the sum matters, so the smallest step just splits the difference
+        let f = |x: &DVector<f64>| Some(dvector![x[0] + x[1]]);
+        let settings = CorrectorSettings {
+            max_iterations: 10,
+            tolerance: 1e-10,
+            perturbation: 1e-6,
+        };
+
+        let solution =
+            differential_correction(f, dvector![1.0, 2.0], &dvector![5.0], &settings).unwrap();
+        assert_relative_eq!(solution, dvector![2.0, 3.0], epsilon = 1e-8);
+
+        // Impossible targets don't converge
+        let f = |x: &DVector<f64>| Some(dvector![x[0] * x[0]]);
+        assert!(differential_correction(f, dvector![1.0], &dvector![-1.0], &settings).is_err());
+    }
+}
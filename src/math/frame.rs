@@ -132,6 +132,25 @@ impl<T: RealField> FrameTransform<T> {
     pub fn isometry(&self) -> &Isometry3<T> {
         &self.isometry
     }
+
+    /// Returns a transform to a frame partway between the target frames of
+    /// `self` and `other`, which should share a source frame. The target
+    /// frame's origin moves in a straight line, and its orientation rotates
+    /// steadily, as `t` goes from 0 (giving `self`) to 1 (giving `other`).
+    pub fn interpolate(&self, other: &Self, t: T) -> Self {
+        // Interpolate the active transformations, so that the origin of the
+        // target frame is what moves in a straight line
+        let active = self
+            .isometry
+            .inverse()
+            .lerp_slerp(&other.isometry.inverse(), t.clone());
+        let lerp = |a: &Vector3<T>, b: &Vector3<T>| a.lerp(b, t.clone());
+        FrameTransform {
+            isometry: active.inverse(),
+            relative_velocity: lerp(&self.relative_velocity, &other.relative_velocity),
+            angular_velocity: lerp(&self.angular_velocity, &other.angular_velocity),
+        }
+    }
 }
 
 impl<T1, T2> SubsetOf<FrameTransform<T2>> for FrameTransform<T1>
@@ -378,6 +397,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_interpolation() {
+        let make_xfm1 = || {
+            FrameTransform::from_active(
+                UnitQuaternion::identity(),
+                Vector3::new(2.0, 0.0, 0.0),
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::zeros(),
+            )
+        };
+        let make_xfm2 = || {
+            FrameTransform::from_active(
+                UnitQuaternion::from_axis_angle(&Vector3::z_axis(), PI / 2.0),
+                Vector3::new(0.0, 4.0, 0.0),
+                Vector3::new(0.0, 3.0, 0.0),
+                Vector3::new(0.0, 0.0, 2.0),
+            )
+        };
+        let (xfm1, xfm2) = (make_xfm1(), make_xfm2());
+
+        // The ends are the original transforms
+        check_equality(xfm1.interpolate(&xfm2, 0.0), make_xfm1());
+        check_equality(xfm1.interpolate(&xfm2, 1.0), make_xfm2());
+
+        // Halfway, the origin is halfway between, and the axes are halfway
+        // rotated
+        let halfway = xfm1.interpolate(&xfm2, 0.5);
+        let expected = FrameTransform::from_active(
+            UnitQuaternion::from_axis_angle(&Vector3::z_axis(), PI / 4.0),
+            Vector3::new(1.0, 2.0, 0.0),
+            Vector3::new(0.5, 1.5, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        );
+        check_equality(halfway, expected);
+    }
+
     #[test]
     fn test_composition() {
         // Two arbitrary transformations
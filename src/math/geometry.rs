@@ -8,6 +8,17 @@ pub fn reject(u: &Vector3<f64>, v: &Vector3<f64>) -> Vector3<f64> {
     u - u_proj_v
 }
 
+/// Wraps an angle into the range [0, 2pi).
+pub fn wrap_angle(theta: f64) -> f64 {
+    let wrapped = theta.rem_euclid(2.0 * PI);
+    // rem_euclid can round up to exactly 2pi for tiny negative inputs
+    if wrapped >= 2.0 * PI {
+        0.0
+    } else {
+        wrapped
+    }
+}
+
 pub fn directed_angle(u: &Vector3<f64>, v: &Vector3<f64>, up: &Vector3<f64>) -> f64 {
     // Returns the angle between u and v, measured as a positive angle around 'up'.
     let theta = u.angle(v);
@@ -28,7 +39,7 @@ pub fn directed_angle(u: &Vector3<f64>, v: &Vector3<f64>, up: &Vector3<f64>) ->
 /// - if new_z is small:
 ///   - R(z) will point as much along the z-axis as possible, while remaining
 ///     perpendicular to R(x) = new_x
-///   - if this is ill-defined (new_x ~= z), then R(z) = y
+///   - if this is ill-defined (new_x ~= z or -z), then R(z) = y
 /// - similarly, if new_x is small:
 ///   - R(x) will point as much along the x-axis as possible, while remaining
 ///     perpendicular to R(z) = new_z
@@ -103,6 +114,15 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_wrap_angle() {
+        approx::assert_relative_eq!(wrap_angle(1.0), 1.0);
+        approx::assert_relative_eq!(wrap_angle(-1.0), 2.0 * PI - 1.0);
+        approx::assert_relative_eq!(wrap_angle(7.0 * PI), PI, max_relative = 1e-15);
+        assert_eq!(wrap_angle(2.0 * PI), 0.0);
+        assert_eq!(wrap_angle(-1e-300), 0.0);
+    }
+
     #[test]
     fn test_rotation_code() {
         fn test_rotation(r: Rotation3<f64>, expected_z: &Vector3<f64>, expected_x: &Vector3<f64>) {
@@ -132,13 +152,39 @@ mod tests {
             &v,
         );
 
-        // new-z is too small, and new-x points along z
-        // TODO should we treat new-x = kz and new-x = -kz differently?
+        // new-z is too small, and new-x points along z. We pick the same new-z
+        // whether new-x is z or -z; this comes up with radial orbits, which can
+        // point either way.
         test_rotation(
             always_find_rotation(&Vector3::zeros(), &Vector3::z(), 1e-20),
             &Vector3::y(),
             &Vector3::z(),
         );
+        test_rotation(
+            always_find_rotation(&Vector3::zeros(), &-Vector3::z(), 1e-20),
+            &Vector3::y(),
+            &-Vector3::z(),
+        );
+
+        // new-z points along -z, like an equatorial retrograde orbit. This isn't
+        // degenerate, and the rotation should be a half-turn around new-x.
+        test_rotation(
+            always_find_rotation(&-Vector3::z(), &Vector3::y(), 1e-20),
+            &-Vector3::z(),
+            &Vector3::y(),
+        );
+        approx::assert_relative_eq!(
+            always_find_rotation(&-Vector3::z(), &Vector3::y(), 1e-20) * Vector3::y(),
+            Vector3::x(),
+            epsilon = 1e-15
+        );
+
+        // new-x is too small, and new-z points along -z: new-x should still be x
+        test_rotation(
+            always_find_rotation(&-Vector3::z(), &Vector3::zeros(), 1e-20),
+            &-Vector3::z(),
+            &Vector3::x(),
+        );
 
         // new-x is too small
         test_rotation(
@@ -1,3 +1,4 @@
+pub mod corrector;
 pub mod frame;
 pub mod geometry;
 pub mod intervals;
@@ -168,6 +168,6 @@ mod tests {
             Interval::new(-1.0, 1.0),
             100,
         );
-        assert_relative_eq!(root, 0.73908513321516064);
+        assert_relative_eq!(root, 0.7390851332151606);
     }
 }
@@ -0,0 +1,170 @@
+//! Searches for times when bodies line up, such as eclipses of one body by
+//! another, or planets bunched up on one side of the Sun.
+
+use std::f64::consts::TAU;
+
+use nalgebra::Vector3;
+
+use super::orrery::{BodyID, Frame, Orrery};
+
+/// How finely to sample, relative to the period of the fastest body involved.
+const SAMPLES_PER_ORBIT: f64 = 64.0;
+/// Number of golden-section iterations used to refine each alignment.
+const NUM_ITERATIONS_ALIGNMENT: usize = 60;
+
+/// Which bodies to look for alignments of.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlignmentSearch {
+    /// The bodies that should line up. There should be at least two, since
+    /// the observer counts as part of the line.
+    pub body_ids: Vec<BodyID>,
+    /// The body the alignment is seen from.
+    pub observer: BodyID,
+    /// How far apart, in radians, the lines of sight to the bodies can be.
+    /// Bodies on opposite sides of the observer are on the same line.
+    pub tolerance: f64,
+}
+
+/// A time when the bodies were as closely lined up as they'd be for a while.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Alignment {
+    pub time: f64,
+    /// The largest angle, in radians, between the lines of sight to any two
+    /// of the bodies.
+    pub spread: f64,
+}
+
+/// Returns the angle, in radians, between the lines of sight from the
+/// observer to the two bodies that are furthest out of line.
+pub fn alignment_spread(orrery: &Orrery, body_ids: &[BodyID], observer: BodyID, time: f64) -> f64 {
+    let snapshot = orrery.snapshot(time, Frame::Root);
+    let position = |id: BodyID| snapshot.body(id).position;
+    let observer_position = position(observer);
+    let directions: Vec<Vector3<f64>> = body_ids
+        .iter()
+        .map(|id| (position(*id) - observer_position).normalize())
+        .collect();
+
+    let mut spread: f64 = 0.0;
+    for (i, a) in directions.iter().enumerate() {
+        for b in directions[i + 1..].iter() {
+            // Lines, not rays, so the sign doesn't matter
+            let angle = a.dot(b).abs().min(1.0).acos();
+            spread = spread.max(angle);
+        }
+    }
+    spread
+}
+
+/// Finds every time between `start_time` and `end_time` that the bodies line
+/// up within the tolerance, at the moment they're most closely aligned.
+///
+/// The search samples often enough that the fastest body can't move more than
+/// about the tolerance between samples, so alignments much briefer than that
+/// can be missed.
+pub fn search_for_alignments(
+    orrery: &Orrery,
+    search: &AlignmentSearch,
+    start_time: f64,
+    end_time: f64,
+) -> Vec<Alignment> {
+    assert!(start_time <= end_time);
+    let spread_at = |time: f64| alignment_spread(orrery, &search.body_ids, search.observer, time);
+
+    // Bodies without an orbit don't move, so don't count them
+    let min_period = search
+        .body_ids
+        .iter()
+        .chain(std::iter::once(&search.observer))
+        .filter_map(|id| orrery.orbit_of_body(*id)?.period())
+        .fold(f64::INFINITY, f64::min);
+    if !min_period.is_finite() {
+        return vec![];
+    }
+    let step = f64::min(
+        min_period / SAMPLES_PER_ORBIT,
+        min_period * search.tolerance / TAU,
+    );
+
+    let num_samples = ((end_time - start_time) / step).ceil() as usize;
+    let times: Vec<f64> = (0..=num_samples)
+        .map(|i| f64::min(start_time + step * i as f64, end_time))
+        .collect();
+    let spreads: Vec<f64> = times.iter().map(|t| spread_at(*t)).collect();
+
+    // Refine each local minimum that's anywhere close
+    let mut alignments: Vec<Alignment> = vec![];
+    for i in 0..times.len() {
+        let prev = spreads[i.saturating_sub(1)];
+        let next = spreads[usize::min(i + 1, times.len() - 1)];
+        if spreads[i] > prev || spreads[i] > next || spreads[i] > 2.0 * search.tolerance {
+            continue;
+        }
+
+        let mut lo = times[i.saturating_sub(1)];
+        let mut hi = times[usize::min(i + 1, times.len() - 1)];
+        let ratio = (5.0_f64.sqrt() - 1.0) / 2.0;
+        for _ in 0..NUM_ITERATIONS_ALIGNMENT {
+            let left = hi - ratio * (hi - lo);
+            let right = lo + ratio * (hi - lo);
+            if spread_at(left) < spread_at(right) {
+                hi = right;
+            } else {
+                lo = left;
+            }
+        }
+        let time = (lo + hi) / 2.0;
+        let spread = spread_at(time);
+
+        // Flat stretches can have several minimal samples in a row
+        let is_duplicate = alignments
+            .last()
+            .is_some_and(|a| time - a.time < 2.0 * step);
+        if spread <= search.tolerance && !is_duplicate {
+            alignments.push(Alignment { time, spread });
+        }
+    }
+    alignments
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::file::read_file;
+
+    const KERBOL: BodyID = BodyID(0);
+    const KERBIN: BodyID = BodyID(4);
+    const MUN: BodyID = BodyID(5);
+
+    #[test]
+    fn test_new_and_full_mun() {
+        let orrery = read_file("ksp-bodies.txt");
+        let search = AlignmentSearch {
+            body_ids: vec![KERBOL, MUN],
+            observer: KERBIN,
+            tolerance: 1f64.to_radians(),
+        };
+        let end_time = 10.0 * 86400.0;
+        let alignments = search_for_alignments(&orrery, &search, 0.0, end_time);
+
+        // The Mun lines up with the Sun twice per synodic month, and since
+        // its orbit is equatorial, it does so exactly
+        let mun_period = orrery.orbit_of_body(MUN).unwrap().period().unwrap();
+        let kerbin_period = orrery.orbit_of_body(KERBIN).unwrap().period().unwrap();
+        let synodic_period = 1.0 / (1.0 / mun_period - 1.0 / kerbin_period);
+        let expected = (end_time / (synodic_period / 2.0)) as usize;
+        assert!(alignments.len() == expected || alignments.len() == expected + 1);
+        for alignment in alignments.iter() {
+            assert!(alignment.spread < 1e-6);
+        }
+        for pair in alignments.windows(2) {
+            assert_relative_eq!(
+                pair[1].time - pair[0].time,
+                synodic_period / 2.0,
+                max_relative = 0.01
+            );
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Rough costs of what a ship does once its plan is over, like capturing into
+//! orbit around wherever it ends up, or landing there, before there are any
+//! burns planned for them.
+
+use super::orrery::{Arrival, Body, DeltaVLedger, Ship};
+use super::timeline::Timeline;
+use crate::astro::{HasMass, TimedOrbit};
+
+/// Delta-v to make the arrival from the given orbit, with impulsive burns, or
+/// `None` if the ship can't be captured because it hits the surface first.
+///
+/// Capturing circularizes at periapsis. Landing then lowers the periapsis to
+/// the surface, and cancels the speed left when it gets there, less what the
+/// ground has from the body's rotation, as if the orbit were prograde and
+/// equatorial. An atmosphere is taken to do that last part for free. If the
+/// orbit already hits the surface, only the last part is needed.
+pub fn arrival_delta_v(arrival: Arrival, orbit: &TimedOrbit<Body, ()>) -> Option<f64> {
+    let body = orbit.primary();
+    let mu = body.mu();
+    let radius = body.info.radius as f64;
+    let touchdown = |speed: f64| match body.info.atmosphere_height {
+        Some(_) => 0.0,
+        None => f64::max(speed - body.info.angular_velocity() * radius, 0.0),
+    };
+
+    let periapsis = orbit.periapsis();
+    if periapsis <= radius {
+        let impact_speed = (2.0 * (orbit.energy() + mu / radius)).sqrt();
+        return match arrival {
+            Arrival::Capture => None,
+            Arrival::Land => Some(touchdown(impact_speed)),
+        };
+    }
+
+    let circular_speed = (mu / periapsis).sqrt();
+    let capture = orbit.periapsis_velocity() - circular_speed;
+    match arrival {
+        Arrival::Capture => Some(capture),
+        Arrival::Land => {
+            // Half an ellipse from the circular orbit down to the surface
+            let transfer = 2.0 / (periapsis + radius);
+            let deorbit = circular_speed - (mu * (2.0 / periapsis - transfer)).sqrt();
+            let impact_speed = (mu * (2.0 / radius - transfer)).sqrt();
+            Some(capture + deorbit + touchdown(impact_speed))
+        }
+    }
+}
+
+/// The ship's delta-v ledger at the given time, with the cost of its
+/// [Arrival] estimated from where it is at the end of the timeline, as far
+/// as that's been extended. Nothing's estimated if the ship's being
+/// integrated numerically then, or if the arrival can't be made from there.
+pub fn mission_ledger(timeline: &Timeline, ship: &Ship, time: f64) -> DeltaVLedger {
+    let mut ledger = ship.delta_v_ledger(time);
+    let arrival = match ship.arrival {
+        Some(arrival) => arrival,
+        None => return ledger,
+    };
+
+    let trajectory = timeline.trajectory(ship.id);
+    let estimate = trajectory
+        .patches()
+        .last()
+        .filter(|patch| patch.numeric_arc().is_none())
+        .and_then(|patch| arrival_delta_v(arrival, patch.orbit()));
+    if let Some(estimate) = estimate {
+        ledger.estimated = estimate;
+    }
+    ledger
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use nalgebra::Vector3;
+
+    use super::*;
+    use crate::file::read_file;
+    use crate::model::orrery::{BodyID, Maneuver, ManeuverPlan};
+
+    const KERBIN: BodyID = BodyID(4);
+    const MUN: BodyID = BodyID(5);
+
+    #[test]
+    fn test_arrival_delta_v() {
+        // Coming in to the Mun on a hyperbola, with periapsis 30 km up
+        let mut orrery = read_file("ksp-bodies.txt");
+        let mun = orrery.get_body(MUN).info.clone();
+        let periapsis = mun.radius as f64 + 30000.0;
+        let speed = 1.5 * (2.0 * mun.mu / periapsis).sqrt();
+        let ship_id = orrery.add_ship(Vector3::x() * periapsis, Vector3::y() * speed, 0.0, MUN);
+        let orbit = orrery.get_ship(ship_id).orbit.clone();
+        assert!(!orbit.is_closed());
+
+        // Capturing is the circularization burn at periapsis
+        let capture = arrival_delta_v(Arrival::Capture, &orbit).unwrap();
+        assert_relative_eq!(capture, speed - (mun.mu / periapsis).sqrt());
+
+        // Landing costs more than that, but not more than killing the whole
+        // orbital speed at the surface on top of it
+        let land = arrival_delta_v(Arrival::Land, &orbit).unwrap();
+        let surface_speed = (mun.mu / mun.radius as f64).sqrt();
+        assert!(land > capture + surface_speed * 0.9);
+        assert!(land < capture + surface_speed * 1.1);
+
+        // Once it's heading into the ground, it can only land
+        let velocity = Vector3::new(-100.0, 10.0, 0.0);
+        let ship_id = orrery.add_ship(Vector3::x() * periapsis, velocity, 0.0, MUN);
+        let orbit = orrery.get_ship(ship_id).orbit.clone();
+        assert_eq!(arrival_delta_v(Arrival::Capture, &orbit), None);
+        assert!(arrival_delta_v(Arrival::Land, &orbit).unwrap() > 100.0);
+    }
+
+    #[test]
+    fn test_mission_ledger() {
+        // A ship in low Kerbin orbit, with a burn that raises its apoapsis
+        let mut orrery = read_file("ksp-bodies.txt");
+        let mu = orrery.get_body(KERBIN).info.mu;
+        let radius = 700000.0;
+        let speed = (mu / radius).sqrt();
+        let ship_id = orrery.add_ship(Vector3::x() * radius, Vector3::y() * speed, 0.0, KERBIN);
+        let orbit = orrery.get_ship(ship_id).orbit.clone();
+        let ship_velocity_at = |time| orbit.state_at_time(time).velocity();
+        let mut plan = ManeuverPlan::new();
+        plan.set_maneuver(Maneuver {
+            time: 10.0,
+            delta_v: ship_velocity_at(10.0) * 0.3,
+        });
+        orrery.set_maneuver_plan(ship_id, plan);
+        orrery.set_ship_delta_v_capacity(ship_id, Some(1000.0));
+
+        // Without an arrival, it's just the plan
+        let mut timeline = Timeline::new(orrery.clone(), 0.0);
+        timeline.extend_until(100.0);
+        let ship = timeline
+            .get_orrery_at(100.0)
+            .unwrap()
+            .get_ship(ship_id)
+            .clone();
+        let ledger = mission_ledger(&timeline, &ship, 100.0);
+        assert_eq!(ledger, ship.delta_v_ledger(100.0));
+
+        // Circularizing at periapsis costs as much as the burn did, which
+        // puts it over capacity
+        orrery.set_ship_arrival(ship_id, Some(Arrival::Capture));
+        let mut timeline = Timeline::new(orrery, 0.0);
+        timeline.extend_until(100.0);
+        let ship = timeline
+            .get_orrery_at(100.0)
+            .unwrap()
+            .get_ship(ship_id)
+            .clone();
+        let ledger = mission_ledger(&timeline, &ship, 100.0);
+        assert_relative_eq!(ledger.estimated, 0.3 * speed, max_relative = 1e-6);
+        assert_relative_eq!(ledger.total(), 0.6 * speed, max_relative = 1e-6);
+        assert!(ledger.is_over_capacity());
+    }
+}
@@ -0,0 +1,165 @@
+//! Burns that make a ship's orbit circular, at one of its apsides.
+
+use std::f64::consts::PI;
+use std::fmt;
+
+use nalgebra::Vector3;
+
+use super::orrery::{Maneuver, ShipID};
+use super::timeline::Timeline;
+use crate::astro::{CartesianState, HasMass, TimedOrbit};
+
+/// Where along an orbit to circularize.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Apsis {
+    Periapsis,
+    Apoapsis,
+}
+
+impl Apsis {
+    /// The true anomaly of the apsis.
+    fn theta(&self) -> f64 {
+        match self {
+            Apsis::Periapsis => 0.0,
+            Apsis::Apoapsis => PI,
+        }
+    }
+}
+
+impl fmt::Display for Apsis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Apsis::Periapsis => write!(f, "periapsis"),
+            Apsis::Apoapsis => write!(f, "apoapsis"),
+        }
+    }
+}
+
+/// The change in velocity that puts something at the given state on a
+/// circular orbit, in the same plane, through where it is now. At an apsis,
+/// this is purely prograde or retrograde.
+pub fn circularization_delta_v<P: HasMass>(state: &CartesianState<P>) -> Vector3<f64> {
+    let position = state.position();
+    let normal = position.cross(&state.velocity()).normalize();
+    let speed = (state.primary().mu() / position.norm()).sqrt();
+    normal.cross(&position).normalize() * speed - state.velocity()
+}
+
+/// The impulsive burn that circularizes the orbit at the first pass through
+/// the given apsis after `time`, or `None` if it doesn't get there again,
+/// like the apoapsis of an open orbit.
+pub fn circularization_burn<P: HasMass, S>(
+    orbit: &TimedOrbit<P, S>,
+    apsis: Apsis,
+    time: f64,
+) -> Option<Maneuver> {
+    let burn_time = orbit.next_time_at_theta(apsis.theta(), time)?;
+    Some(Maneuver {
+        time: burn_time,
+        delta_v: circularization_delta_v(&orbit.state_at_time(burn_time)),
+    })
+}
+
+/// Adds a burn to the ship's plan that circularizes its orbit at the next
+/// pass through the given apsis after `time`, replacing any burn already
+/// planned then, and returns it.
+///
+/// The timeline is extended as far as the burn. It's an error if the ship
+/// crashes or leaves its primary's SOI before it gets there.
+pub fn add_circularization(
+    timeline: &mut Timeline,
+    ship_id: ShipID,
+    apsis: Apsis,
+    time: f64,
+) -> Result<Maneuver, String> {
+    timeline.extend_until(time);
+    let orrery = timeline
+        .get_orrery_at(time)
+        .ok_or_else(|| format!("Can't plan before the timeline starts, at {}", time))?;
+    let ship = orrery.get_ship(ship_id);
+    if ship.has_crashed() {
+        return Err(format!("Ship has crashed by {}", time));
+    }
+    let primary_id = ship.orbit.primary().id;
+    let burn_time = circularization_burn(&ship.orbit, apsis, time)
+        .ok_or_else(|| format!("Ship never reaches {} again", apsis))?
+        .time;
+
+    // Aim using wherever the ship actually is then, in case something's
+    // nudged it off its orbit on the way
+    timeline.extend_until(burn_time);
+    let orrery = timeline.get_orrery_at(burn_time).unwrap();
+    let ship = orrery.get_ship(ship_id);
+    if ship.has_crashed() {
+        return Err(format!("Ship crashes before its {}", apsis));
+    }
+    if ship.orbit.primary().id != primary_id {
+        return Err(format!("Ship leaves its SOI before its {}", apsis));
+    }
+    let maneuver = Maneuver {
+        time: burn_time,
+        delta_v: circularization_delta_v(&ship.state_at_time(burn_time)),
+    };
+
+    let mut plan = ship.maneuvers.clone();
+    plan.set_maneuver(maneuver);
+    timeline.set_maneuver_plan(ship_id, plan);
+    Ok(maneuver)
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::file::read_file;
+    use crate::model::orrery::BodyID;
+
+    const KERBIN: BodyID = BodyID(4);
+
+    #[test]
+    fn test_circularize() {
+        // An elliptical orbit, starting at periapsis
+        let mut orrery = read_file("ksp-bodies.txt");
+        let mu = orrery.get_body(KERBIN).info.mu;
+        let periapsis = 700000.0;
+        let speed = 1.2 * (mu / periapsis).sqrt();
+        let ship_id = orrery.add_ship(Vector3::x() * periapsis, Vector3::y() * speed, 0.0, KERBIN);
+        let orbit = orrery.get_ship(ship_id).orbit.clone();
+        let apoapsis = orbit.apoapsis().unwrap();
+        let period = orbit.period().unwrap();
+
+        // Circularizing at apoapsis is a prograde burn half an orbit later
+        let burn = circularization_burn(&orbit, Apsis::Apoapsis, 0.0).unwrap();
+        assert_relative_eq!(burn.time, period / 2.0, max_relative = 1e-9);
+        let velocity = orbit.state_at_time(burn.time).velocity();
+        assert_relative_eq!(
+            burn.delta_v.dot(&velocity.normalize()),
+            (mu / apoapsis).sqrt() - orbit.apoapsis_velocity().unwrap(),
+            max_relative = 1e-6
+        );
+        assert_relative_eq!(burn.delta_v.cross(&velocity).norm(), 0.0, epsilon = 1e-3);
+
+        // The periapsis is next passed a whole orbit later, not now
+        let burn = circularization_burn(&orbit, Apsis::Periapsis, 0.0).unwrap();
+        assert_relative_eq!(burn.time, period, max_relative = 1e-9);
+
+        // Adding it to the plan leaves the ship in a circle at apoapsis
+        let mut timeline = Timeline::new(orrery, 0.0);
+        let burn = add_circularization(&mut timeline, ship_id, Apsis::Apoapsis, 0.0).unwrap();
+        timeline.extend_until(burn.time + period);
+        let orrery = timeline.get_orrery_at(burn.time + period).unwrap();
+        let orbit = &orrery.get_ship(ship_id).orbit;
+        assert!(
+            orbit.eccentricity() < 1e-6,
+            "{} {:?}",
+            orbit.eccentricity(),
+            orrery.get_ship(ship_id).maneuvers
+        );
+        assert_relative_eq!(orbit.semimajor_axis(), apoapsis, max_relative = 1e-6);
+        assert_eq!(
+            orrery.get_ship(ship_id).maneuvers.maneuver_at(burn.time),
+            Some(&burn)
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! The angles that say when and where to leave for another body: the phase
+//! angle, between where something is now and where its target is, and the
+//! ejection angle, between where a departure burn is made and the direction
+//! its primary is moving in.
+
+use std::f64::consts::PI;
+
+use nalgebra::{Unit, Vector3};
+
+use crate::math::geometry::wrap_angle;
+
+/// The angle from `position` to `target_position`, going around `normal`,
+/// after flattening both onto the plane perpendicular to it. It's in
+/// (-pi, pi], and positive when the target is ahead.
+pub fn phase_angle(
+    position: &Vector3<f64>,
+    target_position: &Vector3<f64>,
+    normal: &Unit<Vector3<f64>>,
+) -> f64 {
+    let angle = wrap_angle(signed_angle(position, target_position, normal));
+    if angle > PI {
+        angle - 2.0 * PI
+    } else {
+        angle
+    }
+}
+
+/// The phase angle to leave at for a Hohmann transfer between two circular,
+/// coplanar orbits around a primary with the given mu: the target has to be
+/// this far ahead, so that it gets to the far side of the transfer just as
+/// the ship does. Negative when the target is further in, since it's then
+/// moving faster and has to start behind.
+pub fn hohmann_phase_angle(mu: f64, radius: f64, target_radius: f64) -> f64 {
+    let transfer_axis = (radius + target_radius) / 2.0;
+    let transfer_time = PI * (transfer_axis.powi(3) / mu).sqrt();
+    let target_rate = (mu / target_radius.powi(3)).sqrt();
+    PI - target_rate * transfer_time
+}
+
+/// How far behind the given direction, going around `normal` and flattened
+/// onto the plane perpendicular to it, a burn at `position` is made, in
+/// [0, 2pi). For a departure, the direction is the
+/// primary's velocity, or the opposite for one heading further in.
+pub fn ejection_angle(
+    position: &Vector3<f64>,
+    direction: &Vector3<f64>,
+    normal: &Unit<Vector3<f64>>,
+) -> f64 {
+    wrap_angle(signed_angle(position, direction, normal))
+}
+
+/// The ejection angle at which a burn at periapsis, at the given radius,
+/// leaves with the given hyperbolic excess speed heading in the desired
+/// direction. The hyperbola bends the path around the primary, so this is
+/// the angle between its periapsis and its asymptote, and is always more
+/// than 90 degrees.
+pub fn ideal_ejection_angle(mu: f64, radius: f64, excess_speed: f64) -> f64 {
+    let eccentricity = 1.0 + radius * excess_speed.powi(2) / mu;
+    (-1.0 / eccentricity).acos()
+}
+
+/// The angle from one vector to the other around the normal, once they're
+/// flattened onto the plane perpendicular to it.
+fn signed_angle(from: &Vector3<f64>, to: &Vector3<f64>, normal: &Unit<Vector3<f64>>) -> f64 {
+    let flatten = |v: &Vector3<f64>| v - normal.into_inner() * normal.dot(v);
+    let (from, to) = (flatten(from), flatten(to));
+    normal.dot(&from.cross(&to)).atan2(from.dot(&to))
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_phase_angle() {
+        let normal = Vector3::z_axis();
+        let position = Vector3::x() * 2.0;
+        assert_relative_eq!(
+            phase_angle(&position, &Vector3::new(0.0, 3.0, 1.0), &normal),
+            PI / 2.0
+        );
+        assert_relative_eq!(
+            phase_angle(&position, &Vector3::new(1.0, -1.0, 0.0), &normal),
+            -PI / 4.0
+        );
+        assert_relative_eq!(
+            ejection_angle(&position, &Vector3::new(1.0, -1.0, 0.0), &normal),
+            1.75 * PI
+        );
+
+        // Going to the same orbit needs no phasing, and the target leads
+        // going out and lags going in
+        assert_relative_eq!(hohmann_phase_angle(1.0, 2.0, 2.0), 0.0, epsilon = 1e-12);
+        assert!(hohmann_phase_angle(1.0, 2.0, 3.0) > 0.0);
+        assert!(hohmann_phase_angle(1.0, 3.0, 2.0) < 0.0);
+
+        // Kerbin to Duna, which is famously about 44 degrees
+        let mu_sun = 1.1723328e18;
+        let angle = hohmann_phase_angle(mu_sun, 13_599_840_256.0, 20_726_155_264.0);
+        assert_relative_eq!(angle.to_degrees(), 44.4, epsilon = 0.1);
+    }
+
+    #[test]
+    fn test_ejection_angle() {
+        // Barely escaping means burning on the opposite side, and escaping
+        // fast means burning nearly at right angles to the way out
+        assert_relative_eq!(ideal_ejection_angle(1.0, 1.0, 0.0), PI);
+        assert_relative_eq!(
+            ideal_ejection_angle(1.0, 1.0, 1e6),
+            PI / 2.0,
+            epsilon = 1e-6
+        );
+
+        // Low Kerbin orbit to Duna, at about 1 km/s excess
+        let angle = ideal_ejection_angle(3.5316e12, 680_000.0, 1000.0);
+        assert_relative_eq!(angle.to_degrees(), 147.0, epsilon = 1.0);
+    }
+}
@@ -0,0 +1,67 @@
+use nalgebra::Point3;
+
+use super::{Event, EventData, EventPoint, SearchResult};
+use crate::model::orrery::{Orrery, ShipID};
+
+// Apses found within this many seconds after the start of the search are
+// treated as already passed. Otherwise, rounding error in the epoch could make
+// us find the same apsis twice.
+const MIN_SEPARATION: f64 = 1e-6;
+
+/// Searches for the first time after `start_time` that the ship passes through
+/// periapsis.
+pub fn search_for_periapsis(orrery: &Orrery, ship_id: ShipID, start_time: f64) -> SearchResult {
+    search_for_apsis(orrery, ship_id, start_time, false)
+}
+
+/// Searches for the first time after `start_time` that the ship passes through
+/// apoapsis. Open orbits never do.
+pub fn search_for_apoapsis(orrery: &Orrery, ship_id: ShipID, start_time: f64) -> SearchResult {
+    search_for_apsis(orrery, ship_id, start_time, true)
+}
+
+fn search_for_apsis(
+    orrery: &Orrery,
+    ship_id: ShipID,
+    start_time: f64,
+    is_apoapsis: bool,
+) -> SearchResult {
+    let ship_orbit = orrery.orbit_of_ship(ship_id);
+    let body = ship_orbit.primary();
+    let period = ship_orbit.period();
+
+    // Apoapsis is half an orbit after periapsis
+    let mut time = match (is_apoapsis, period) {
+        (false, _) => ship_orbit.time_at_periapsis(),
+        (true, Some(period)) => ship_orbit.time_at_periapsis() + period / 2.0,
+        (true, None) => return SearchResult::Never,
+    };
+
+    // Skip ahead to the next pass, if this one has already happened
+    let min_time = start_time + MIN_SEPARATION;
+    if time < min_time {
+        let period = match period {
+            Some(p) => p,
+            None => return SearchResult::Never,
+        };
+        time += ((min_time - time) / period).ceil() * period;
+    }
+
+    let s = ship_orbit.s_at_time(time);
+    let state = ship_orbit.get_state_at_universal_anomaly(s);
+    let data = if is_apoapsis {
+        EventData::Apoapsis(body.id)
+    } else {
+        EventData::Periapsis(body.id)
+    };
+
+    SearchResult::Found(Event {
+        ship_id,
+        data,
+        point: EventPoint {
+            time,
+            anomaly: s,
+            location: Point3::from(state.position()),
+        },
+    })
+}
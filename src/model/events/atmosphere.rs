@@ -0,0 +1,61 @@
+use nalgebra::Point3;
+
+use super::radius_crossing::{next_radius_crossing, CrossingDirection};
+use super::{Event, EventData, EventPoint, SearchResult};
+use crate::model::orrery::{Orrery, ShipID};
+
+/// Searches for the first time after `start_time` that the ship enters the
+/// atmosphere of the body it's orbiting.
+pub fn search_for_atmosphere_entry(
+    orrery: &Orrery,
+    ship_id: ShipID,
+    start_time: f64,
+) -> SearchResult {
+    search_for_atmosphere_crossing(orrery, ship_id, start_time, CrossingDirection::Inbound)
+}
+
+/// Searches for the first time after `start_time` that the ship leaves the
+/// atmosphere of the body it's orbiting.
+pub fn search_for_atmosphere_exit(
+    orrery: &Orrery,
+    ship_id: ShipID,
+    start_time: f64,
+) -> SearchResult {
+    search_for_atmosphere_crossing(orrery, ship_id, start_time, CrossingDirection::Outbound)
+}
+
+fn search_for_atmosphere_crossing(
+    orrery: &Orrery,
+    ship_id: ShipID,
+    start_time: f64,
+    direction: CrossingDirection,
+) -> SearchResult {
+    let ship_orbit = orrery.orbit_of_ship(ship_id);
+    let body = ship_orbit.primary();
+    let atmosphere_height = match body.info.atmosphere_height {
+        Some(h) => h,
+        None => return SearchResult::Never,
+    };
+    let radius = body.info.radius as f64 + atmosphere_height;
+
+    let (time, s) = match next_radius_crossing(&ship_orbit, radius, direction, start_time) {
+        Some(x) => x,
+        None => return SearchResult::Never,
+    };
+    let state = ship_orbit.get_state_at_universal_anomaly(s);
+
+    let data = match direction {
+        CrossingDirection::Inbound => EventData::EnteringAtmosphere(body.id),
+        CrossingDirection::Outbound => EventData::ExitingAtmosphere(body.id),
+    };
+
+    SearchResult::Found(Event {
+        ship_id,
+        data,
+        point: EventPoint {
+            time,
+            anomaly: s,
+            location: Point3::from(state.position()),
+        },
+    })
+}
@@ -0,0 +1,188 @@
+use nalgebra::Point3;
+
+use super::{Eclipse, Event, EventData, EventPoint, SearchResult};
+use crate::math::intervals::Interval;
+use crate::math::root_finding::bisection;
+use crate::model::orrery::{Orrery, ShadowRegion, ShipID};
+
+// Crossings found within this many seconds after the start of the search are
+// treated as already passed, so that we don't find the same one twice.
+const MIN_SEPARATION: f64 = 1e-6;
+/// How finely to sample closed orbits when looking for shadow crossings. This
+/// needs to be fine enough that we don't step over a whole eclipse.
+const SAMPLES_PER_ORBIT: f64 = 64.0;
+/// Sampling interval for open orbits, in seconds.
+const MAX_SAMPLE_INTERVAL: f64 = 600.0;
+const NUM_ITERATIONS_SHADOW: usize = 100;
+
+/// Searches for the first time in the window that the ship passes into the
+/// shadow of the body it's orbiting.
+pub fn search_for_shadow_entry(
+    orrery: &Orrery,
+    ship_id: ShipID,
+    start_time: f64,
+    end_time: f64,
+) -> SearchResult {
+    search_for_shadow_crossing(orrery, ship_id, start_time, end_time, true)
+}
+
+/// Searches for the first time in the window that the ship comes out of the
+/// shadow of the body it's orbiting.
+pub fn search_for_shadow_exit(
+    orrery: &Orrery,
+    ship_id: ShipID,
+    start_time: f64,
+    end_time: f64,
+) -> SearchResult {
+    search_for_shadow_crossing(orrery, ship_id, start_time, end_time, false)
+}
+
+/// Searches for the first time in the window that the ship passes into the
+/// given part of the shadow of the body it's orbiting.
+pub fn search_for_eclipse_entry(
+    orrery: &Orrery,
+    ship_id: ShipID,
+    start_time: f64,
+    end_time: f64,
+    region: ShadowRegion,
+) -> SearchResult {
+    search_for_eclipse_crossing(orrery, ship_id, start_time, end_time, region, true)
+}
+
+/// Searches for the first time in the window that the ship comes out of the
+/// given part of the shadow of the body it's orbiting.
+pub fn search_for_eclipse_exit(
+    orrery: &Orrery,
+    ship_id: ShipID,
+    start_time: f64,
+    end_time: f64,
+    region: ShadowRegion,
+) -> SearchResult {
+    search_for_eclipse_crossing(orrery, ship_id, start_time, end_time, region, false)
+}
+
+fn search_for_shadow_crossing(
+    orrery: &Orrery,
+    ship_id: ShipID,
+    start_time: f64,
+    end_time: f64,
+    is_entry: bool,
+) -> SearchResult {
+    let ship = orrery.get_ship(ship_id);
+    let parent_id = ship.parent_id();
+
+    let depth = |time: f64| {
+        let position = ship.state_at_time(time).position();
+        orrery.shadow_depth(parent_id, &position, time)
+    };
+    let data = if is_entry {
+        EventData::EnteringShadow(parent_id)
+    } else {
+        EventData::ExitingShadow(parent_id)
+    };
+    search_for_depth_crossing(orrery, ship_id, start_time, end_time, is_entry, depth, data)
+}
+
+fn search_for_eclipse_crossing(
+    orrery: &Orrery,
+    ship_id: ShipID,
+    start_time: f64,
+    end_time: f64,
+    region: ShadowRegion,
+    is_entry: bool,
+) -> SearchResult {
+    let ship = orrery.get_ship(ship_id);
+    let parent_id = ship.parent_id();
+
+    let depth = |time: f64| {
+        let position = ship.state_at_time(time).position();
+        orrery.eclipse_depth(parent_id, &position, time, region)
+    };
+    let eclipse = Eclipse {
+        body: parent_id,
+        region,
+    };
+    let data = if is_entry {
+        EventData::EnteringEclipse(eclipse)
+    } else {
+        EventData::ExitingEclipse(eclipse)
+    };
+    search_for_depth_crossing(orrery, ship_id, start_time, end_time, is_entry, depth, data)
+}
+
+/// Looks for the shadow depth crossing zero, in the direction given by
+/// `is_entry`. The depth is `None` if the parent doesn't cast a shadow.
+fn search_for_depth_crossing(
+    orrery: &Orrery,
+    ship_id: ShipID,
+    start_time: f64,
+    end_time: f64,
+    is_entry: bool,
+    depth: impl Fn(f64) -> Option<f64>,
+    data: EventData,
+) -> SearchResult {
+    if depth(start_time).is_none() {
+        // The Sun doesn't cast a shadow
+        return SearchResult::Never;
+    }
+    let signed_depth = |time: f64| {
+        let depth = depth(time).unwrap();
+        if is_entry {
+            depth
+        } else {
+            -depth
+        }
+    };
+
+    let ship = orrery.get_ship(ship_id);
+    search_for_sign_change(
+        start_time,
+        end_time,
+        sample_step(ship.orbit.period()),
+        signed_depth,
+        |time| Event {
+            ship_id,
+            data,
+            point: EventPoint {
+                time,
+                anomaly: ship.orbit.s_at_time(time),
+                location: Point3::from(ship.state_at_time(time).position()),
+            },
+        },
+    )
+}
+
+/// How often to sample an orbit with the given period, so that we don't step
+/// over anything that happens once per orbit.
+pub(super) fn sample_step(period: Option<f64>) -> f64 {
+    match period {
+        Some(period) => f64::min(period / SAMPLES_PER_ORBIT, MAX_SAMPLE_INTERVAL),
+        None => MAX_SAMPLE_INTERVAL,
+    }
+}
+
+/// Steps along until the function goes from non-positive to positive, and
+/// then narrows down where it happened, making an event there.
+pub(super) fn search_for_sign_change(
+    start_time: f64,
+    end_time: f64,
+    step: f64,
+    f: impl Fn(f64) -> f64,
+    make_event: impl FnOnce(f64) -> Event,
+) -> SearchResult {
+    let mut time = start_time + MIN_SEPARATION;
+    let mut value = f(time);
+    while time < end_time {
+        let next_time = f64::min(time + step, end_time);
+        let next_value = f(next_time);
+        if value <= 0.0 && next_value > 0.0 {
+            let crossing_time =
+                bisection(&f, Interval::new(time, next_time), NUM_ITERATIONS_SHADOW);
+            return SearchResult::Found(make_event(crossing_time));
+        }
+        time = next_time;
+        value = next_value;
+    }
+
+    SearchResult::NotFound(end_time)
+}
@@ -0,0 +1,121 @@
+use nalgebra::Point3;
+
+use super::soi_change::search_for_close_approach;
+use super::{Event, EventData, EventPoint, SOIChange, SearchResult};
+use crate::model::orrery::{BodyID, Orrery, ShipID};
+
+/// Searches for the first time the ship comes within `soi_multiple` times the
+/// SOI radius of the target body, at which point a ship using
+/// [hybrid propagation](crate::model::orrery::Propagation::Hybrid) should
+/// start being integrated numerically.
+pub fn search_for_integration_zone_entry(
+    orrery: &Orrery,
+    ship_id: ShipID,
+    target_id: BodyID,
+    soi_multiple: f64,
+    start_time: f64,
+    end_time: f64,
+) -> SearchResult {
+    search_for_close_approach(
+        orrery,
+        ship_id,
+        target_id,
+        soi_multiple,
+        start_time,
+        end_time,
+        |_| EventData::EnteringIntegrationZone(target_id),
+    )
+}
+
+/// Ships using [numeric propagation](crate::model::orrery::Propagation::Numeric)
+/// are always on a numeric arc. If one isn't, it needs to start one right away,
+/// around its current parent.
+pub fn search_for_integration_start(
+    orrery: &Orrery,
+    ship_id: ShipID,
+    start_time: f64,
+) -> SearchResult {
+    let ship = orrery.get_ship(ship_id);
+    if ship.numeric_arc.is_some() || ship.has_crashed() {
+        return SearchResult::Never;
+    }
+
+    let state = ship.state_at_time(start_time);
+    SearchResult::Found(Event {
+        ship_id,
+        data: EventData::EnteringIntegrationZone(ship.parent_id()),
+        point: EventPoint {
+            time: start_time,
+            anomaly: ship.orbit.s_at_time(start_time),
+            location: Point3::from(state.position()),
+        },
+    })
+}
+
+/// Finds the end of the ship's current numeric arc, if it's on one. By then,
+/// the whole arc has already been integrated, so there's no searching to do.
+pub fn search_for_integration_zone_exit(orrery: &Orrery, ship_id: ShipID) -> SearchResult {
+    let ship = orrery.get_ship(ship_id);
+    let arc = match &ship.numeric_arc {
+        Some(arc) => arc,
+        None => return SearchResult::Never,
+    };
+
+    let parent_id = ship.parent_id();
+    let exit_time = arc.path.end_time();
+    let exit_state = arc.path.end_state();
+
+    let new_parent_id = match arc.perturber {
+        // If we're still inside the perturber's SOI (e.g., we got captured, or crashed
+        // into it), then it becomes our new parent.
+        Some(perturber_id) => {
+            let perturber_position = orrery
+                .orbit_of_body(perturber_id)
+                .expect("Perturbing body should be orbiting our parent")
+                .state_at_time(exit_time)
+                .position();
+            let perturber_soi = orrery
+                .get_soi_radius(perturber_id)
+                .expect("Orbiting bodies should have an SOI");
+            if (exit_state.position - perturber_position).norm() < perturber_soi {
+                perturber_id
+            } else {
+                parent_id
+            }
+        }
+        // Otherwise, we might have stopped at an SOI boundary
+        None => {
+            let escaped_to = orrery.get_parent(parent_id).filter(|_| {
+                orrery
+                    .get_soi_radius(parent_id)
+                    .is_some_and(|soi| exit_state.position.norm() >= soi)
+            });
+            let encountered = orrery
+                .body_orbits()
+                .filter(|orbit| orbit.primary().id == parent_id)
+                .find(|orbit| {
+                    let body_id = orbit.secondary().id;
+                    let displacement =
+                        exit_state.position - orbit.state_at_time(exit_time).position();
+                    orrery
+                        .get_soi_radius(body_id)
+                        .is_some_and(|soi| displacement.norm() <= soi)
+                })
+                .map(|orbit| orbit.secondary().id);
+            escaped_to.or(encountered).unwrap_or(parent_id)
+        }
+    };
+
+    SearchResult::Found(Event {
+        ship_id,
+        data: EventData::ExitingIntegrationZone(SOIChange {
+            old: parent_id,
+            new: new_parent_id,
+        }),
+        point: EventPoint {
+            time: exit_time,
+            anomaly: ship.orbit.s_at_time(exit_time),
+            location: Point3::from(exit_state.position),
+        },
+    })
+}
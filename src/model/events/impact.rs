@@ -0,0 +1,31 @@
+use nalgebra::Point3;
+
+use super::radius_crossing::{next_radius_crossing, CrossingDirection};
+use super::{Event, EventData, EventPoint, SearchResult};
+use crate::model::orrery::{Orrery, ShipID};
+
+/// Searches for the first time after `start_time` that the ship hits the
+/// surface of the body it's orbiting.
+pub fn search_for_impact(orrery: &Orrery, ship_id: ShipID, start_time: f64) -> SearchResult {
+    let ship_orbit = orrery.orbit_of_ship(ship_id);
+    let body = ship_orbit.primary();
+    let radius = body.info.radius as f64;
+
+    // We hit the surface on the way down
+    let (impact_time, impact_s) =
+        match next_radius_crossing(&ship_orbit, radius, CrossingDirection::Inbound, start_time) {
+            Some(x) => x,
+            None => return SearchResult::Never,
+        };
+    let impact_state = ship_orbit.get_state_at_universal_anomaly(impact_s);
+
+    SearchResult::Found(Event {
+        ship_id,
+        data: EventData::Impact(body.id),
+        point: EventPoint {
+            time: impact_time,
+            anomaly: impact_s,
+            location: Point3::from(impact_state.position()),
+        },
+    })
+}
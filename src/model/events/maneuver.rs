@@ -0,0 +1,64 @@
+use nalgebra::Point3;
+
+use super::{Event, EventData, EventPoint, SearchResult};
+use crate::model::orrery::{Orrery, ShipID};
+
+/// Finds the ship's next planned maneuver, strictly after the given time.
+pub fn search_for_maneuver(orrery: &Orrery, ship_id: ShipID, start_time: f64) -> SearchResult {
+    let ship = orrery.get_ship(ship_id);
+    let time = match ship.maneuvers.next_after(start_time) {
+        Some(maneuver) => maneuver.time,
+        None => return SearchResult::Never,
+    };
+
+    let state = ship.state_at_time(time);
+    SearchResult::Found(Event {
+        ship_id,
+        data: EventData::Maneuver,
+        point: EventPoint {
+            time,
+            anomaly: ship.orbit.s_at_time(time),
+            location: Point3::from(state.position()),
+        },
+    })
+}
+
+/// Finds the next time one of the ship's stages runs dry, strictly after the
+/// given time, partway through the burn it's integrated through. Burns made
+/// all at once don't have one; their stages are dropped along with the burn.
+pub fn search_for_staging(orrery: &Orrery, ship_id: ShipID, start_time: f64) -> SearchResult {
+    let ship = orrery.get_ship(ship_id);
+    let (maneuver, arc) = match &ship.numeric_arc {
+        Some(arc) => match arc.burn {
+            Some(maneuver) => (maneuver, arc),
+            None => return SearchResult::Never,
+        },
+        None => return SearchResult::Never,
+    };
+    let estimate = match ship.burn_estimate_at(maneuver.time) {
+        Some(estimate) => estimate,
+        None => return SearchResult::Never,
+    };
+
+    // The arc might have been cut short by the next burn, or an impact
+    let staging = estimate
+        .staged
+        .iter()
+        .map(|(idx, after)| (*idx, maneuver.time + after))
+        .find(|(_, t)| *t > start_time && *t <= arc.path.end_time());
+    let (idx, time) = match staging {
+        Some(staging) => staging,
+        None => return SearchResult::Never,
+    };
+
+    let state = ship.state_at_time(time);
+    SearchResult::Found(Event {
+        ship_id,
+        data: EventData::Staging(idx),
+        point: EventPoint {
+            time,
+            anomaly: ship.orbit.s_at_time(time),
+            location: Point3::from(state.position()),
+        },
+    })
+}
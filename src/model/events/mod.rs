@@ -2,10 +2,33 @@ use std::borrow::Borrow;
 
 use nalgebra::Point3;
 
-use crate::model::orrery::{BodyID, ShipID};
+use crate::model::orrery::{BodyID, ShadowRegion, ShipID};
 
+mod apsis;
+mod atmosphere;
+mod eclipse;
+mod hybrid;
+mod impact;
+mod maneuver;
+mod occultation;
+mod radius_crossing;
+mod sail;
 mod soi_change;
 
+pub use apsis::{search_for_apoapsis, search_for_periapsis};
+pub use atmosphere::{search_for_atmosphere_entry, search_for_atmosphere_exit};
+pub use eclipse::{
+    search_for_eclipse_entry, search_for_eclipse_exit, search_for_shadow_entry,
+    search_for_shadow_exit,
+};
+pub use hybrid::{
+    search_for_integration_start, search_for_integration_zone_entry,
+    search_for_integration_zone_exit,
+};
+pub use impact::search_for_impact;
+pub use maneuver::{search_for_maneuver, search_for_staging};
+pub use occultation::{search_for_sight_loss, search_for_sight_regain};
+pub use sail::search_for_sail_attitude_change;
 pub use soi_change::{search_for_soi_encounter, search_for_soi_escape};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -14,10 +37,65 @@ pub struct SOIChange {
     pub new: BodyID,
 }
 
+/// A ship passing into or out of part of a body's shadow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Eclipse {
+    pub body: BodyID,
+    pub region: ShadowRegion,
+}
+
+/// A body getting between a ship and the ship it's keeping sight of.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Occultation {
+    pub target: ShipID,
+    pub body: BodyID,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum EventData {
     EnteringSOI(SOIChange),
     ExitingSOI(SOIChange),
+    /// The ship hit the surface of the given body.
+    Impact(BodyID),
+    EnteringAtmosphere(BodyID),
+    ExitingAtmosphere(BodyID),
+    /// The ship passed through the lowest point of its orbit around the given
+    /// body.
+    Periapsis(BodyID),
+    /// The ship passed through the highest point of its orbit around the given
+    /// body.
+    Apoapsis(BodyID),
+    /// The ship came close enough to the given body that it needs to be
+    /// integrated numerically. If the body is the ship's parent, the ship is
+    /// integrated around it alone, since it's using numeric propagation.
+    EnteringIntegrationZone(BodyID),
+    /// The ship left the zone where it was being integrated numerically, and
+    /// now follows a conic around the new body.
+    ExitingIntegrationZone(SOIChange),
+    /// The given body started blocking the Sun.
+    EnteringShadow(BodyID),
+    /// The given body stopped blocking the Sun.
+    ExitingShadow(BodyID),
+    /// Like [EventData::EnteringShadow], but with the Sun as a disc, so that
+    /// partial and total eclipses are told apart.
+    EnteringEclipse(Eclipse),
+    ExitingEclipse(Eclipse),
+    /// The body got in the way of the ship's line of sight to its target.
+    LosingSight(Occultation),
+    /// The body stopped being in the way of the ship's line of sight to its
+    /// target.
+    RegainingSight(Occultation),
+    /// The ship's sail turned to a new attitude, according to its schedule,
+    /// partway through a numeric arc.
+    SailAttitudeChange,
+    /// The ship made one of its planned burns.
+    Maneuver,
+    /// The given stage ran dry partway through a burn the ship is integrated
+    /// through, and was dropped. Stages are counted from 0.
+    Staging(usize),
+    /// A body, or the ship's orbit, was edited by hand. The segment before it
+    /// has things as they were, so the edit doesn't change the past.
+    Modification,
 }
 
 /// Used for tracking the type of event within [UpcomingEvents]. Events with
@@ -26,6 +104,23 @@ pub enum EventData {
 pub enum EventTag {
     EscapeSOI,
     EncounterSOI(BodyID),
+    Impact,
+    EnterAtmosphere,
+    ExitAtmosphere,
+    Periapsis,
+    Apoapsis,
+    EnterIntegrationZone(BodyID),
+    ExitIntegrationZone,
+    EnterShadow,
+    ExitShadow,
+    EnterEclipse(ShadowRegion),
+    ExitEclipse(ShadowRegion),
+    LoseSight,
+    RegainSight,
+    SailAttitudeChange,
+    Maneuver,
+    Staging,
+    Modification,
 }
 
 impl EventData {
@@ -33,11 +128,90 @@ impl EventData {
         match &self {
             EventData::EnteringSOI(soi_change) => EventTag::EncounterSOI(soi_change.new),
             EventData::ExitingSOI(_) => EventTag::EscapeSOI,
+            EventData::Impact(_) => EventTag::Impact,
+            EventData::EnteringAtmosphere(_) => EventTag::EnterAtmosphere,
+            EventData::ExitingAtmosphere(_) => EventTag::ExitAtmosphere,
+            EventData::Periapsis(_) => EventTag::Periapsis,
+            EventData::Apoapsis(_) => EventTag::Apoapsis,
+            EventData::EnteringIntegrationZone(id) => EventTag::EnterIntegrationZone(*id),
+            EventData::ExitingIntegrationZone(_) => EventTag::ExitIntegrationZone,
+            EventData::EnteringShadow(_) => EventTag::EnterShadow,
+            EventData::ExitingShadow(_) => EventTag::ExitShadow,
+            EventData::EnteringEclipse(eclipse) => EventTag::EnterEclipse(eclipse.region),
+            EventData::ExitingEclipse(eclipse) => EventTag::ExitEclipse(eclipse.region),
+            EventData::LosingSight(_) => EventTag::LoseSight,
+            EventData::RegainingSight(_) => EventTag::RegainSight,
+            EventData::SailAttitudeChange => EventTag::SailAttitudeChange,
+            EventData::Maneuver => EventTag::Maneuver,
+            EventData::Staging(_) => EventTag::Staging,
+            EventData::Modification => EventTag::Modification,
+        }
+    }
+
+    /// Whether this event changes the path the ship follows. Events that don't
+    /// are just markers along the way.
+    pub fn changes_trajectory(&self) -> bool {
+        match self {
+            EventData::EnteringSOI(_)
+            | EventData::ExitingSOI(_)
+            | EventData::Impact(_)
+            | EventData::EnteringIntegrationZone(_)
+            | EventData::ExitingIntegrationZone(_)
+            | EventData::SailAttitudeChange
+            | EventData::Maneuver
+            | EventData::Modification => true,
+            EventData::EnteringAtmosphere(_)
+            | EventData::ExitingAtmosphere(_)
+            | EventData::Periapsis(_)
+            | EventData::Apoapsis(_)
+            | EventData::EnteringShadow(_)
+            | EventData::ExitingShadow(_)
+            | EventData::EnteringEclipse(_)
+            | EventData::ExitingEclipse(_)
+            | EventData::LosingSight(_)
+            | EventData::RegainingSight(_)
+            | EventData::Staging(_) => false,
+        }
+    }
+
+    /// What this event looks like with time running backwards, e.g., entering
+    /// an SOI becomes leaving it. Returns `None` for events that can't be run
+    /// backwards: impacts, maneuvers, staging, edits, and anything to do with
+    /// numeric arcs.
+    pub fn reversed(&self) -> Option<EventData> {
+        let swap = |soi_change: &SOIChange| SOIChange {
+            old: soi_change.new,
+            new: soi_change.old,
+        };
+        match self {
+            EventData::EnteringSOI(soi_change) => Some(EventData::ExitingSOI(swap(soi_change))),
+            EventData::ExitingSOI(soi_change) => Some(EventData::EnteringSOI(swap(soi_change))),
+            EventData::EnteringAtmosphere(id) => Some(EventData::ExitingAtmosphere(*id)),
+            EventData::ExitingAtmosphere(id) => Some(EventData::EnteringAtmosphere(*id)),
+            EventData::Periapsis(id) => Some(EventData::Periapsis(*id)),
+            EventData::Apoapsis(id) => Some(EventData::Apoapsis(*id)),
+            EventData::EnteringShadow(id) => Some(EventData::ExitingShadow(*id)),
+            EventData::ExitingShadow(id) => Some(EventData::EnteringShadow(*id)),
+            EventData::EnteringEclipse(eclipse) => Some(EventData::ExitingEclipse(eclipse.clone())),
+            EventData::ExitingEclipse(eclipse) => Some(EventData::EnteringEclipse(eclipse.clone())),
+            EventData::LosingSight(occultation) => {
+                Some(EventData::RegainingSight(occultation.clone()))
+            }
+            EventData::RegainingSight(occultation) => {
+                Some(EventData::LosingSight(occultation.clone()))
+            }
+            EventData::Impact(_)
+            | EventData::EnteringIntegrationZone(_)
+            | EventData::ExitingIntegrationZone(_)
+            | EventData::SailAttitudeChange
+            | EventData::Maneuver
+            | EventData::Staging(_)
+            | EventData::Modification => None,
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct EventPoint {
     pub time: f64,
     pub anomaly: f64,
@@ -50,7 +224,7 @@ impl EventPoint {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Event {
     pub ship_id: ShipID,
     pub data: EventData,
@@ -61,7 +235,7 @@ pub fn first_event<B: Borrow<Event>>(it: impl Iterator<Item = B>) -> Option<B> {
     it.min_by(|a, b| a.borrow().point.compare_time(&b.borrow().point))
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum SearchResult {
     Found(Event),
     NotFound(f64),
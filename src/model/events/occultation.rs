@@ -0,0 +1,89 @@
+use nalgebra::Point3;
+
+use super::eclipse::{sample_step, search_for_sign_change};
+use super::{Event, EventData, EventPoint, Occultation, SearchResult};
+use crate::model::orrery::{Frame, Orrery, ShipID};
+use crate::model::visibility::occlusion;
+
+/// Searches for the first time in the window that a body comes between the
+/// ship and the ship it's keeping line of sight to.
+pub fn search_for_sight_loss(
+    orrery: &Orrery,
+    ship_id: ShipID,
+    start_time: f64,
+    end_time: f64,
+) -> SearchResult {
+    search_for_occultation(orrery, ship_id, start_time, end_time, true)
+}
+
+/// Searches for the first time in the window that the ship's target comes
+/// out from behind a body.
+pub fn search_for_sight_regain(
+    orrery: &Orrery,
+    ship_id: ShipID,
+    start_time: f64,
+    end_time: f64,
+) -> SearchResult {
+    search_for_occultation(orrery, ship_id, start_time, end_time, false)
+}
+
+fn search_for_occultation(
+    orrery: &Orrery,
+    ship_id: ShipID,
+    start_time: f64,
+    end_time: f64,
+    is_loss: bool,
+) -> SearchResult {
+    let ship = orrery.get_ship(ship_id);
+    let target_id = match ship.line_of_sight_target {
+        Some(id) => id,
+        None => return SearchResult::Never,
+    };
+    // We can only see the target if it's being simulated with us
+    let target = match orrery.ships().find(|s| s.id == target_id) {
+        Some(target) => target,
+        None => return SearchResult::Never,
+    };
+    if ship.has_crashed() || target.has_crashed() {
+        return SearchResult::Never;
+    }
+
+    let occlusion_at = |time: f64| {
+        let snapshot = orrery.snapshot(time, Frame::Root);
+        occlusion(orrery, &snapshot, ship_id, target_id)
+    };
+    let signed_depth = |time: f64| {
+        let (_, depth) = occlusion_at(time);
+        if is_loss {
+            depth
+        } else {
+            -depth
+        }
+    };
+
+    // Either ship moving could change what's in the way
+    let step = f64::min(
+        sample_step(ship.orbit.period()),
+        sample_step(target.orbit.period()),
+    );
+    search_for_sign_change(start_time, end_time, step, signed_depth, |time| {
+        let (body, _) = occlusion_at(time);
+        let occultation = Occultation {
+            target: target_id,
+            body,
+        };
+        Event {
+            ship_id,
+            data: if is_loss {
+                EventData::LosingSight(occultation)
+            } else {
+                EventData::RegainingSight(occultation)
+            },
+            point: EventPoint {
+                time,
+                anomaly: ship.orbit.s_at_time(time),
+                location: Point3::from(ship.state_at_time(time).position()),
+            },
+        }
+    })
+}
@@ -0,0 +1,48 @@
+use crate::astro::{HasMass, TimedOrbit};
+
+/// Which way the orbit is going when it crosses a given radius.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrossingDirection {
+    Inbound,
+    Outbound,
+}
+
+/// Finds the first time strictly after `start_time` that the orbit crosses the
+/// given radius in the given direction. Returns the time and the anomaly at
+/// that time.
+pub fn next_radius_crossing<P: HasMass, S>(
+    orbit: &TimedOrbit<P, S>,
+    radius: f64,
+    direction: CrossingDirection,
+    start_time: f64,
+) -> Option<(f64, f64)> {
+    // If the orbit never reaches that radius, or never leaves it, there's no
+    // crossing.
+    if orbit.periapsis() >= radius {
+        return None;
+    }
+    if let Some(r_a) = orbit.apoapsis() {
+        if r_a <= radius {
+            return None;
+        }
+    }
+
+    // get_s_at_radius returns the outbound crossing; the inbound one is at the
+    // opposite anomaly.
+    let s = orbit.get_s_at_radius(radius)?;
+    let s = match direction {
+        CrossingDirection::Inbound => -s,
+        CrossingDirection::Outbound => s,
+    };
+    let mut time = orbit.time_at_s(s);
+
+    // If that's already passed, then on a closed orbit, we'll cross it next time
+    // around. On an open orbit, it'll never happen again.
+    if time <= start_time {
+        let period = orbit.period()?;
+        let num_periods = ((start_time - time) / period).floor() + 1.0;
+        time += num_periods * period;
+    }
+
+    Some((time, orbit.s_at_time(time)))
+}
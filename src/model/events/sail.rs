@@ -0,0 +1,37 @@
+use nalgebra::Point3;
+
+use super::{Event, EventData, EventPoint, SearchResult};
+use crate::model::orrery::{Orrery, ShipID};
+
+/// Finds the next time the ship's sail changes attitude, partway through its
+/// current numeric arc. Arcs are normally integrated only up to the next
+/// change, so this only happens when the schedule was edited after the arc
+/// was integrated.
+pub fn search_for_sail_attitude_change(
+    orrery: &Orrery,
+    ship_id: ShipID,
+    start_time: f64,
+) -> SearchResult {
+    let ship = orrery.get_ship(ship_id);
+    let (sail, arc) = match (&ship.sail, &ship.numeric_arc) {
+        (Some(sail), Some(arc)) if arc.perturber.is_none() => (sail, arc),
+        _ => return SearchResult::Never,
+    };
+
+    // If the change is at the end of the arc, that's handled by leaving the arc
+    let change_time = match sail.schedule.next_change_after(start_time) {
+        Some(t) if t < arc.path.end_time() => t,
+        _ => return SearchResult::Never,
+    };
+
+    let state = ship.state_at_time(change_time);
+    SearchResult::Found(Event {
+        ship_id,
+        data: EventData::SailAttitudeChange,
+        point: EventPoint {
+            time: change_time,
+            anomaly: ship.orbit.s_at_time(change_time),
+            location: Point3::from(state.position()),
+        },
+    })
+}
@@ -1,5 +1,4 @@
 use std::f64::consts::{FRAC_PI_2, PI};
-use std::f64::INFINITY;
 
 use nalgebra::{Point3, Vector3};
 
@@ -23,7 +22,9 @@ pub fn search_for_soi_escape(orrery: &Orrery, ship_id: ShipID) -> SearchResult {
         // We can never escape the Sun
         None => return SearchResult::Never,
     };
-    let soi_radius = current_body_orbit.soi_radius();
+    let soi_radius = orrery
+        .get_soi_radius(current_body)
+        .expect("Orbiting bodies should have an SOI");
 
     let parent_body = current_body_orbit.primary().id;
 
@@ -56,6 +57,34 @@ pub fn search_for_soi_encounter(
     target_id: BodyID,
     start_time: f64,
     end_time: f64,
+) -> SearchResult {
+    search_for_close_approach(
+        orrery,
+        ship_id,
+        target_id,
+        1.0,
+        start_time,
+        end_time,
+        |parent_id| {
+            EventData::EnteringSOI(SOIChange {
+                old: parent_id,
+                new: target_id,
+            })
+        },
+    )
+}
+
+/// Searches for the first time the ship comes within `soi_multiple` times the
+/// SOI radius of the target body, which must share the ship's parent. The
+/// data for the resulting event is made from the ID of that parent.
+pub(super) fn search_for_close_approach(
+    orrery: &Orrery,
+    ship_id: ShipID,
+    target_id: BodyID,
+    soi_multiple: f64,
+    start_time: f64,
+    end_time: f64,
+    make_data: impl FnOnce(BodyID) -> EventData,
 ) -> SearchResult {
     // We perform a lot of preflight checks. First, is the window the right way
     // around?
@@ -80,7 +109,10 @@ pub fn search_for_soi_encounter(
     }
 
     // Everything seems good, let's start looking for intersections!
-    let soi_radius = target_orbit.soi_radius();
+    let soi_radius = soi_multiple
+        * orrery
+            .get_soi_radius(target_id)
+            .expect("Orbiting bodies should have an SOI");
     let soi_radius_sq = soi_radius * soi_radius;
 
     // Quick check: if one orbit is much smaller than the other, then there's no
@@ -201,10 +233,7 @@ pub fn search_for_soi_encounter(
 
     let event = Event {
         ship_id,
-        data: EventData::EnteringSOI(SOIChange {
-            old: parent_id,
-            new: target_id,
-        }),
+        data: make_data(parent_id),
         point: EventPoint {
             time: entry_time,
             anomaly: new_anomaly,
@@ -260,7 +289,7 @@ impl SoiEncounterHelper<'_> {
 
 fn get_apsis_interval<P, S>(timed_orbit: &TimedOrbit<P, S>) -> Interval {
     let lo = timed_orbit.periapsis();
-    let hi = timed_orbit.apoapsis().unwrap_or(INFINITY);
+    let hi = timed_orbit.apoapsis().unwrap_or(f64::INFINITY);
     Interval::new(lo, hi)
 }
 
@@ -0,0 +1,385 @@
+//! Still pictures of an [Orrery], drawn without a window or a GPU, so that
+//! figures for reports can be made from the command line.
+//!
+//! This is a much simpler picture than the interactive view: bodies are flat
+//! discs, ships are dots, and orbits are lines. Bodies hide whatever passes
+//! behind them, but orbits and ships in front of a body are drawn over it.
+
+use std::f64::consts::{FRAC_PI_4, TAU};
+use std::path::Path;
+
+use image::ColorType;
+use nalgebra::{Isometry3, Perspective3, Point2, Point3, Vector3};
+
+use super::orrery::{BodyID, Frame, Orrery};
+
+/// Same field of view as the interactive camera.
+const FIELD_OF_VIEW: f64 = FRAC_PI_4;
+/// How close to the camera things can be, as a fraction of its distance from
+/// the focus. Also like the interactive camera.
+const NEAR_MULTIPLIER: f64 = 0.1;
+/// How many points each orbit is drawn with.
+const NUM_ORBIT_POINTS: usize = 512;
+/// How much dimmer orbits are than the things on them.
+const ORBIT_BRIGHTNESS: f32 = 0.6;
+/// Smallest size of a body, in pixels, so that distant ones don't vanish.
+const MIN_BODY_RADIUS: f64 = 2.0;
+const SHIP_RADIUS: f64 = 3.0;
+/// Points further than this off the edge of the picture, in multiples of its
+/// size, are left out, rather than spend ages drawing lines that are nowhere
+/// near it.
+const MAX_OFFSCREEN: f64 = 10.0;
+
+/// Where a figure is seen from. Like the interactive camera, it looks at the
+/// center of the focused body, with the z-axis up.
+#[derive(Debug, Clone, Copy)]
+pub struct FigureCamera {
+    pub focus: BodyID,
+    /// Azimuthal angle, in radians.
+    pub theta: f64,
+    /// Polar angle, in radians.
+    pub phi: f64,
+    pub distance: f64,
+}
+
+/// A picture of the system at a single time.
+#[derive(Debug, Clone, Copy)]
+pub struct Figure {
+    pub camera: FigureCamera,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Something round to draw, at its depth in front of the camera.
+struct Disc {
+    depth: f64,
+    center: Point2<f64>,
+    radius: f64,
+    color: [u8; 3],
+}
+
+impl Disc {
+    /// Whether this disc covers up something at the given pixel and depth.
+    /// The disc is treated as flat, at the depth of its center, which is close
+    /// enough for anything that isn't inside the body.
+    fn hides(&self, pixel: Point2<f64>, depth: f64) -> bool {
+        self.depth < depth && (pixel - self.center).norm() <= self.radius
+    }
+}
+
+/// Rows of RGB pixels, starting from the top left.
+struct Raster {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Raster {
+    fn new(width: u32, height: u32) -> Self {
+        Raster {
+            width,
+            height,
+            pixels: vec![0; 3 * (width * height) as usize],
+        }
+    }
+
+    fn set(&mut self, x: i64, y: i64, color: [u8; 3]) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let idx = 3 * (y as usize * self.width as usize + x as usize);
+        self.pixels[idx..idx + 3].copy_from_slice(&color);
+    }
+
+    fn draw_line(&mut self, a: Point2<f64>, b: Point2<f64>, color: [u8; 3]) {
+        let num_steps = (b - a).abs().max().ceil().max(1.0);
+        for i in 0..=num_steps as i64 {
+            let p = a + (b - a) * (i as f64 / num_steps);
+            self.set(p.x.round() as i64, p.y.round() as i64, color);
+        }
+    }
+
+    fn fill_disc(&mut self, disc: &Disc) {
+        let (x0, x1) = (disc.center.x - disc.radius, disc.center.x + disc.radius);
+        let (y0, y1) = (disc.center.y - disc.radius, disc.center.y + disc.radius);
+        // Only bother with the part that's in the picture
+        let x0 = x0.floor().max(0.0) as i64;
+        let y0 = y0.floor().max(0.0) as i64;
+        let x1 = x1.ceil().min(self.width as f64) as i64;
+        let y1 = y1.ceil().min(self.height as f64) as i64;
+        for y in y0..y1 {
+            for x in x0..x1 {
+                // Test the middle of each pixel
+                let offset = Point2::new(x as f64 + 0.5, y as f64 + 0.5) - disc.center;
+                if offset.norm() <= disc.radius {
+                    self.set(x, y, disc.color);
+                }
+            }
+        }
+    }
+}
+
+impl Figure {
+    /// Draws the system as it is at `time` to a PNG file.
+    pub fn save_png(&self, orrery: &Orrery, time: f64, path: &Path) -> Result<(), String> {
+        let pixels = self.render_rgb(orrery, time);
+        image::save_buffer(path, &pixels, self.width, self.height, ColorType::Rgb8)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Draws the system as it is at `time` into a buffer of RGB pixels, row
+    /// by row.
+    pub fn render_rgb(&self, orrery: &Orrery, time: f64) -> Vec<u8> {
+        let snapshot = orrery.snapshot(time, Frame::BodyInertial(self.camera.focus));
+        let mut raster = Raster::new(self.width, self.height);
+
+        // Bodies go first, furthest first, so that nearer ones cover them up
+        let mut bodies = vec![];
+        for body in orrery.bodies() {
+            let position = snapshot.body(body.id).position;
+            if let Some((center, depth)) = self.project(&position) {
+                let radius = self.pixels_across(body.info.radius as f64, depth);
+                bodies.push(Disc {
+                    depth,
+                    center,
+                    radius: radius.max(MIN_BODY_RADIUS),
+                    color: to_rgb(body.info.color),
+                });
+            }
+        }
+        bodies.sort_by(|a, b| b.depth.total_cmp(&a.depth));
+        for disc in bodies.iter() {
+            raster.fill_disc(disc);
+        }
+        let is_hidden =
+            |pixel: Point2<f64>, depth: f64| bodies.iter().any(|d| d.hides(pixel, depth));
+
+        // Then orbits, centered wherever their primary is, leaving out the
+        // pieces that pass behind a body
+        let draw_orbit = |raster: &mut Raster, paths: Vec<Vec<(Point2<f64>, f64)>>, color| {
+            for path in paths {
+                for pair in path.windows(2) {
+                    let ((a, a_depth), (b, b_depth)) = (pair[0], pair[1]);
+                    if !is_hidden(a + (b - a) / 2.0, (a_depth + b_depth) / 2.0) {
+                        raster.draw_line(a, b, color);
+                    }
+                }
+            }
+        };
+        for orbit in orrery.body_orbits() {
+            let center = snapshot.body(orbit.primary().id).position;
+            let max_radius = orrery.get_soi_radius(orbit.primary().id);
+            let color = to_rgb(orbit.secondary().info.color * ORBIT_BRIGHTNESS);
+            let paths = self.orbit_paths(center, max_radius, |theta| {
+                orbit.get_position_at_theta(theta)
+            });
+            draw_orbit(&mut raster, paths, color);
+        }
+        for ship in orrery.ships().filter(|s| !s.has_crashed()) {
+            let orbit = &ship.orbit;
+            let center = snapshot.body(orbit.primary().id).position;
+            let max_radius = orrery.get_soi_radius(orbit.primary().id);
+            let color = to_rgb(ship_color(ship.color) * ORBIT_BRIGHTNESS);
+            let paths = self.orbit_paths(center, max_radius, |theta| {
+                orbit.get_position_at_theta(theta)
+            });
+            draw_orbit(&mut raster, paths, color);
+        }
+
+        // And ships on top, unless they're behind a body
+        for ship in orrery.ships().filter(|s| !s.has_crashed()) {
+            let position = snapshot.ship(ship.id).position;
+            if let Some((center, depth)) = self.project(&position) {
+                if !is_hidden(center, depth) {
+                    raster.fill_disc(&Disc {
+                        depth,
+                        center,
+                        radius: SHIP_RADIUS,
+                        color: to_rgb(ship_color(ship.color)),
+                    });
+                }
+            }
+        }
+
+        raster.pixels
+    }
+
+    /// Breaks an orbit around `center` into lines, at the places where it
+    /// leaves the picture or its primary's SOI. Each point comes with its
+    /// depth, so that the lines can be hidden behind bodies.
+    fn orbit_paths(
+        &self,
+        center: Point3<f64>,
+        max_radius: Option<f64>,
+        position_at_theta: impl Fn(f64) -> Option<Vector3<f64>>,
+    ) -> Vec<Vec<(Point2<f64>, f64)>> {
+        let mut paths = vec![];
+        let mut current = vec![];
+        for i in 0..=NUM_ORBIT_POINTS {
+            let theta = TAU * i as f64 / NUM_ORBIT_POINTS as f64 - TAU / 2.0;
+            let pixel = position_at_theta(theta)
+                .filter(|v| max_radius.is_none_or(|r| v.norm() <= r))
+                .and_then(|v| self.project(&(center + v)));
+            match pixel {
+                Some(point) => current.push(point),
+                None => {
+                    if current.len() > 1 {
+                        paths.push(std::mem::take(&mut current));
+                    }
+                    current.clear();
+                }
+            }
+        }
+        if current.len() > 1 {
+            paths.push(current);
+        }
+        paths
+    }
+
+    fn projection(&self) -> Perspective3<f64> {
+        let near = self.camera.distance * NEAR_MULTIPLIER;
+        Perspective3::new(
+            self.width as f64 / self.height as f64,
+            FIELD_OF_VIEW,
+            near,
+            // Nothing is cut off for being too far away; this only matters
+            // for depth, which we don't use
+            2.0 * near,
+        )
+    }
+
+    fn view(&self) -> Isometry3<f64> {
+        let FigureCamera {
+            theta,
+            phi,
+            distance,
+            ..
+        } = self.camera;
+        let eye = Point3::new(
+            distance * theta.cos() * phi.sin(),
+            distance * theta.sin() * phi.sin(),
+            distance * phi.cos(),
+        );
+        Isometry3::look_at_rh(&eye, &Point3::origin(), &Vector3::z())
+    }
+
+    /// Returns where a point relative to the focus ends up in the picture,
+    /// and how far in front of the camera it is, or None if it's behind the
+    /// camera or too far off the edge.
+    fn project(&self, point: &Point3<f64>) -> Option<(Point2<f64>, f64)> {
+        let eye_point = self.view() * point;
+        let depth = -eye_point.z;
+        if depth < self.camera.distance * NEAR_MULTIPLIER {
+            return None;
+        }
+
+        let ndc = self.projection().project_point(&eye_point);
+        if ndc.x.abs() > MAX_OFFSCREEN || ndc.y.abs() > MAX_OFFSCREEN {
+            return None;
+        }
+        let x = (ndc.x + 1.0) / 2.0 * self.width as f64;
+        let y = (1.0 - ndc.y) / 2.0 * self.height as f64;
+        Some((Point2::new(x, y), depth))
+    }
+
+    /// How many pixels something of the given size covers, at the given
+    /// depth.
+    fn pixels_across(&self, size: f64, depth: f64) -> f64 {
+        size / depth * (self.height as f64 / 2.0) / (FIELD_OF_VIEW / 2.0).tan()
+    }
+}
+
+fn ship_color(color: Option<Point3<f32>>) -> Point3<f32> {
+    color.unwrap_or(Point3::new(1.0, 1.0, 1.0))
+}
+
+fn to_rgb(color: Point3<f32>) -> [u8; 3] {
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    [channel(color.x), channel(color.y), channel(color.z)]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_2;
+
+    use super::*;
+    use crate::file::read_file;
+
+    fn pixel(buffer: &[u8], figure: &Figure, x: u32, y: u32) -> [u8; 3] {
+        let idx = 3 * (y * figure.width + x) as usize;
+        [buffer[idx], buffer[idx + 1], buffer[idx + 2]]
+    }
+
+    #[test]
+    fn test_render_focus() {
+        let mut orrery = read_file("ksp-bodies.txt");
+        orrery.add_ship(
+            Vector3::x() * 6000000.0,
+            Vector3::y() * 1000.0,
+            0.0,
+            BodyID(4),
+        );
+        let kerbin = orrery.get_body(BodyID(4));
+        let figure = Figure {
+            camera: FigureCamera {
+                focus: kerbin.id,
+                theta: 0.0,
+                phi: 1.0,
+                distance: 20.0 * kerbin.info.radius as f64,
+            },
+            width: 200,
+            height: 100,
+        };
+
+        // The focus is in the middle, and the corners are empty
+        let buffer = figure.render_rgb(&orrery, 0.0);
+        let color = to_rgb(kerbin.info.color);
+        assert_eq!(pixel(&buffer, &figure, 100, 50), color);
+        assert_eq!(pixel(&buffer, &figure, 0, 0), [0, 0, 0]);
+        assert_eq!(pixel(&buffer, &figure, 199, 99), [0, 0, 0]);
+
+        // Kerbin's radius is a twentieth of the distance to it, which is
+        // about an eighth of the half-height at this field of view
+        let (center, depth) = figure.project(&Point3::origin()).unwrap();
+        approx::assert_relative_eq!(center, Point2::new(100.0, 50.0), epsilon = 1e-9);
+        let across = figure.pixels_across(kerbin.info.radius as f64, depth);
+        approx::assert_relative_eq!(across, 6.04, epsilon = 0.01);
+
+        // Nothing behind the camera gets drawn
+        let behind = Point3::new(100.0, 0.0, 0.0) * kerbin.info.radius as f64;
+        assert!(figure.project(&behind).is_none());
+    }
+
+    #[test]
+    fn test_render_behind_body() {
+        let mut orrery = read_file("ksp-bodies.txt");
+        let kerbin = orrery.get_body(BodyID(4));
+        let radius = kerbin.info.radius as f64;
+        let kerbin_color = to_rgb(kerbin.info.color);
+        // Looking along the equator, at a ship on the far side of Kerbin
+        orrery.add_ship(
+            -Vector3::x() * 3.0 * radius,
+            Vector3::y() * 1400.0,
+            0.0,
+            BodyID(4),
+        );
+        let figure = Figure {
+            camera: FigureCamera {
+                focus: BodyID(4),
+                theta: 0.0,
+                phi: FRAC_PI_2,
+                distance: 20.0 * radius,
+            },
+            width: 200,
+            height: 100,
+        };
+
+        // The ship is hidden, but the near side of its orbit crosses in front
+        // of Kerbin. Just above and below the orbit, Kerbin shows.
+        let buffer = figure.render_rgb(&orrery, 0.0);
+        let orbit_color = to_rgb(ship_color(None) * ORBIT_BRIGHTNESS);
+        assert_eq!(pixel(&buffer, &figure, 100, 50), orbit_color);
+        assert_eq!(pixel(&buffer, &figure, 100, 47), kerbin_color);
+        assert_eq!(pixel(&buffer, &figure, 100, 53), kerbin_color);
+    }
+}
@@ -0,0 +1,482 @@
+//! Schedule-oriented summaries of a [Timeline], as a Gantt chart over mission
+//! elapsed time.
+
+use std::fmt::Write;
+
+use nalgebra::Point3;
+
+use super::arrival::mission_ledger;
+use super::events::EventData;
+use super::orrery::{BodyID, DeltaVLedger, Maneuver, Orrery, Ship, ShipID};
+use super::timeline::Timeline;
+
+// Layout of the SVG, in pixels
+const LABEL_WIDTH: f64 = 160.0;
+const CHART_WIDTH: f64 = 1000.0;
+const HEADER_HEIGHT: f64 = 40.0;
+const ROW_HEIGHT: f64 = 36.0;
+const BAR_HEIGHT: f64 = 20.0;
+const MILESTONE_SIZE: f64 = 6.0;
+const MARGIN: f64 = 10.0;
+// How far a row's label and note are from its middle
+const NOTE_OFFSET: f64 = 7.0;
+/// Roughly how many ticks to put on the time axis.
+const TARGET_NUM_TICKS: f64 = 10.0;
+
+/// A Gantt chart of what each ship is doing over part of a timeline. Times are
+/// absolute, but the chart is drawn relative to its start.
+#[derive(Debug, Clone)]
+pub struct GanttChart {
+    pub start_time: f64,
+    pub end_time: f64,
+    pub rows: Vec<GanttRow>,
+}
+
+/// One lane of the chart.
+#[derive(Debug, Clone)]
+pub struct GanttRow {
+    pub label: String,
+    /// A smaller line under the label, like how much delta-v the ship's
+    /// plan uses.
+    pub note: Option<String>,
+    pub bars: Vec<GanttBar>,
+    pub milestones: Vec<GanttMilestone>,
+}
+
+/// Something that takes up a span of time, like coasting around a body.
+#[derive(Debug, Clone)]
+pub struct GanttBar {
+    pub start: f64,
+    pub end: f64,
+    pub label: String,
+    pub color: Point3<f32>,
+}
+
+/// Something that happens at an instant, like an SOI change.
+#[derive(Debug, Clone)]
+pub struct GanttMilestone {
+    pub time: f64,
+    pub label: String,
+}
+
+impl GanttChart {
+    /// Summarizes the timeline from its start until `end_time`. The timeline
+    /// should already have been extended that far.
+    ///
+    /// Each ship gets a row for its coast legs, one bar for each body it
+    /// orbits (numerically integrated stretches are marked as such), with
+    /// SOI changes and other significant events as milestones. Ships that go
+    /// through eclipses get a second row for them.
+    pub fn from_timeline(timeline: &Timeline, end_time: f64) -> Self {
+        let start_time = timeline.start_time();
+        let orrery = timeline
+            .get_orrery_at(start_time)
+            .expect("Timeline should contain its start time");
+
+        let mut ship_ids: Vec<_> = orrery.ships().map(|s| s.id).collect();
+        ship_ids.sort();
+
+        let mut rows = vec![];
+        for ship_id in ship_ids {
+            rows.push(coast_row(timeline, &orrery, ship_id, end_time));
+            if let Some(row) = eclipse_row(timeline, &orrery, ship_id, start_time, end_time) {
+                rows.push(row);
+            }
+        }
+
+        Self {
+            start_time,
+            end_time,
+            rows,
+        }
+    }
+
+    /// Renders the chart as a standalone SVG document.
+    pub fn to_svg(&self) -> String {
+        let duration = self.end_time - self.start_time;
+        let x_of = |time: f64| {
+            let fraction = ((time - self.start_time) / duration).clamp(0.0, 1.0);
+            LABEL_WIDTH + fraction * CHART_WIDTH
+        };
+        let width = LABEL_WIDTH + CHART_WIDTH + MARGIN;
+        let height = HEADER_HEIGHT + ROW_HEIGHT * self.rows.len() as f64 + MARGIN;
+
+        // Writing to a String can't fail, so we ignore the results
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="sans-serif" font-size="12">"#,
+            width, height
+        );
+        let _ = writeln!(
+            svg,
+            r#"<rect width="{}" height="{}" fill="white"/>"#,
+            width, height
+        );
+
+        // Time axis, with grid lines
+        let tick_spacing = tick_spacing(duration);
+        let mut tick = 0.0;
+        while tick <= duration {
+            let x = x_of(self.start_time + tick);
+            let _ = writeln!(
+                svg,
+                r##"<line x1="{x:.1}" y1="{}" x2="{x:.1}" y2="{}" stroke="#ddd"/>"##,
+                HEADER_HEIGHT - 5.0,
+                height - MARGIN,
+            );
+            let _ = writeln!(
+                svg,
+                r#"<text x="{x:.1}" y="{}" text-anchor="middle">{}</text>"#,
+                HEADER_HEIGHT - 10.0,
+                format_elapsed(tick),
+            );
+            tick += tick_spacing;
+        }
+
+        for (i, row) in self.rows.iter().enumerate() {
+            let top = HEADER_HEIGHT + ROW_HEIGHT * i as f64;
+            let middle = top + ROW_HEIGHT / 2.0;
+            // Make room for the note under the label, if there is one
+            let label_y = match row.note {
+                Some(_) => middle - NOTE_OFFSET,
+                None => middle,
+            };
+            let _ = writeln!(
+                svg,
+                r#"<text x="{}" y="{:.1}" dominant-baseline="middle">{}</text>"#,
+                MARGIN,
+                label_y,
+                escape(&row.label),
+            );
+            if let Some(note) = &row.note {
+                let _ = writeln!(
+                    svg,
+                    r##"<text x="{}" y="{:.1}" dominant-baseline="middle" font-size="10" fill="#555">{}</text>"##,
+                    MARGIN,
+                    middle + NOTE_OFFSET,
+                    escape(note),
+                );
+            }
+
+            for bar in row.bars.iter() {
+                let (x0, x1) = (x_of(bar.start), x_of(bar.end));
+                let _ = writeln!(
+                    svg,
+                    r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{}" fill="{}" stroke="black" stroke-width="0.5"><title>{}</title></rect>"#,
+                    x0,
+                    middle - BAR_HEIGHT / 2.0,
+                    x1 - x0,
+                    BAR_HEIGHT,
+                    hex_color(&bar.color),
+                    escape(&bar.label),
+                );
+                // Only label bars with room for it
+                if x1 - x0 > 7.0 * bar.label.len() as f64 {
+                    let _ = writeln!(
+                        svg,
+                        r#"<text x="{:.1}" y="{:.1}" dominant-baseline="middle">{}</text>"#,
+                        x0 + 4.0,
+                        middle,
+                        escape(&bar.label),
+                    );
+                }
+            }
+
+            for milestone in row.milestones.iter() {
+                let x = x_of(milestone.time);
+                let _ = writeln!(
+                    svg,
+                    r#"<path d="M {:.1} {:.1} l {s} {s} l -{s} {s} l -{s} -{s} z" fill="black"><title>{}</title></path>"#,
+                    x,
+                    middle - BAR_HEIGHT / 2.0 - MILESTONE_SIZE,
+                    escape(&milestone.label),
+                    s = MILESTONE_SIZE,
+                );
+            }
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+fn coast_row(timeline: &Timeline, orrery: &Orrery, ship_id: ShipID, end_time: f64) -> GanttRow {
+    let trajectory = timeline.trajectory(ship_id);
+    let body_name = |id: BodyID| orrery.get_body(id).info.name.clone();
+
+    // Merge consecutive patches that look the same, like the arcs of a
+    // numerically propagated ship
+    let mut bars: Vec<GanttBar> = vec![];
+    for patch in trajectory.patches() {
+        if patch.start_time() >= end_time {
+            break;
+        }
+        let end = patch.end_time().map_or(end_time, |t| t.min(end_time));
+        let label = match patch.numeric_arc() {
+            Some(arc) if arc.burn.is_some() => {
+                format!("{} (burning)", body_name(patch.parent_id()))
+            }
+            Some(_) => format!("{} (integrated)", body_name(patch.parent_id())),
+            None => body_name(patch.parent_id()),
+        };
+        match bars.last_mut() {
+            Some(bar) if bar.label == label && bar.end == patch.start_time() => bar.end = end,
+            _ => bars.push(GanttBar {
+                start: patch.start_time(),
+                end,
+                label,
+                color: orrery.get_body(patch.parent_id()).info.color,
+            }),
+        }
+    }
+
+    let milestones = timeline
+        .events()
+        .filter(|e| e.ship_id == ship_id && e.point.time <= end_time)
+        .filter_map(|e| {
+            let label = match &e.data {
+                EventData::EnteringSOI(change) => format!("Encounter {}", body_name(change.new)),
+                EventData::ExitingSOI(change) => format!("Escape {}", body_name(change.old)),
+                EventData::Impact(id) => format!("Impact {}", body_name(*id)),
+                EventData::EnteringAtmosphere(id) => {
+                    format!("Enter {} atmosphere", body_name(*id))
+                }
+                EventData::ExitingAtmosphere(id) => format!("Exit {} atmosphere", body_name(*id)),
+                EventData::SailAttitudeChange => String::from("Turn sail"),
+                EventData::Staging(idx) => format!("Drop stage {}", idx + 1),
+                EventData::Maneuver => {
+                    let ship = orrery.get_ship(ship_id);
+                    match ship.maneuvers.maneuver_at(e.point.time) {
+                        Some(maneuver) => describe_burn(ship, maneuver),
+                        None => String::from("Burn"),
+                    }
+                }
+                // These are either too frequent to be interesting, or already
+                // shown by the bars
+                EventData::Periapsis(_)
+                | EventData::Apoapsis(_)
+                | EventData::EnteringIntegrationZone(_)
+                | EventData::ExitingIntegrationZone(_)
+                | EventData::EnteringShadow(_)
+                | EventData::ExitingShadow(_)
+                | EventData::EnteringEclipse(_)
+                | EventData::ExitingEclipse(_)
+                | EventData::LosingSight(_)
+                | EventData::RegainingSight(_)
+                | EventData::Modification => return None,
+            };
+            Some(GanttMilestone {
+                time: e.point.time,
+                label,
+            })
+        })
+        .collect();
+
+    let ship = orrery.get_ship(ship_id);
+    GanttRow {
+        label: ship.display_name(),
+        note: delta_v_note(&mission_ledger(timeline, ship, timeline.start_time())),
+        bars,
+        milestones,
+    }
+}
+
+fn eclipse_row(
+    timeline: &Timeline,
+    orrery: &Orrery,
+    ship_id: ShipID,
+    start_time: f64,
+    end_time: f64,
+) -> Option<GanttRow> {
+    let mut bars = vec![];
+    let mut shadow_start: Option<(f64, BodyID)> = None;
+    let shadow_events = timeline
+        .events()
+        .filter(|e| e.ship_id == ship_id && e.point.time <= end_time);
+    for event in shadow_events {
+        match &event.data {
+            EventData::EnteringShadow(id) => shadow_start = Some((event.point.time, *id)),
+            EventData::ExitingShadow(id) => {
+                // If we never saw it enter, we started out in the shadow
+                let start = shadow_start.take().map_or(start_time, |(t, _)| t);
+                bars.push(eclipse_bar(orrery, start, event.point.time, *id));
+            }
+            _ => {}
+        }
+    }
+    if let Some((start, id)) = shadow_start {
+        bars.push(eclipse_bar(orrery, start, end_time, id));
+    }
+
+    if bars.is_empty() {
+        return None;
+    }
+    Some(GanttRow {
+        label: format!("{} eclipses", orrery.get_ship(ship_id).display_name()),
+        note: None,
+        bars,
+        milestones: vec![],
+    })
+}
+
+/// Describes a burn by its delta-v, and what it takes out of the ship's
+/// stages, if it has any.
+fn describe_burn(ship: &Ship, maneuver: &Maneuver) -> String {
+    let text = format!("Burn {:.1} m/s", maneuver.delta_v.norm());
+    match ship.burn_estimate_at(maneuver.time) {
+        Some(estimate) => format!("{} ({})", text, estimate.describe()),
+        None => text,
+    }
+}
+
+/// Sums up the delta-v a ship's plan uses, if it makes any burns or has a
+/// limited amount to use.
+fn delta_v_note(ledger: &DeltaVLedger) -> Option<String> {
+    match (ledger.capacity, ledger.remaining()) {
+        (Some(capacity), Some(remaining)) if remaining < 0.0 => Some(format!(
+            "Δv {:.0} of {:.0} m/s, {:.0} short!",
+            ledger.total(),
+            capacity,
+            -remaining
+        )),
+        (Some(capacity), _) => Some(format!("Δv {:.0} of {:.0} m/s", ledger.total(), capacity)),
+        (None, _) if ledger.total() > 0.0 => Some(format!("Δv {:.0} m/s", ledger.total())),
+        (None, _) => None,
+    }
+}
+
+fn eclipse_bar(orrery: &Orrery, start: f64, end: f64, body_id: BodyID) -> GanttBar {
+    GanttBar {
+        start,
+        end,
+        label: format!("{} shadow", orrery.get_body(body_id).info.name),
+        color: Point3::new(0.3, 0.3, 0.3),
+    }
+}
+
+/// Picks a round spacing for ticks on a time axis of the given length.
+fn tick_spacing(duration: f64) -> f64 {
+    const CANDIDATES: [f64; 12] = [
+        60.0,
+        600.0,
+        3600.0,
+        6.0 * 3600.0,
+        86400.0,
+        7.0 * 86400.0,
+        30.0 * 86400.0,
+        90.0 * 86400.0,
+        365.0 * 86400.0,
+        5.0 * 365.0 * 86400.0,
+        10.0 * 365.0 * 86400.0,
+        100.0 * 365.0 * 86400.0,
+    ];
+    let ideal = duration / TARGET_NUM_TICKS;
+    CANDIDATES
+        .into_iter()
+        .find(|c| *c >= ideal)
+        .unwrap_or(CANDIDATES[CANDIDATES.len() - 1])
+}
+
+fn format_elapsed(seconds: f64) -> String {
+    let (minutes, hours, days) = (seconds / 60.0, seconds / 3600.0, seconds / 86400.0);
+    if days >= 1.0 {
+        format!("{:.0}d", days)
+    } else if hours >= 1.0 {
+        format!("{:.0}h", hours)
+    } else {
+        format!("{:.0}m", minutes)
+    }
+}
+
+fn hex_color(color: &Point3<f32>) -> String {
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        channel(color.x),
+        channel(color.y),
+        channel(color.z)
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+
+    use super::*;
+    use crate::file::read_file;
+
+    #[test]
+    fn test_favorite_scenario() {
+        let mut orrery = read_file("ksp-bodies.txt");
+        orrery.add_ship(
+            Vector3::x() * 6000000.0,
+            Vector3::y() * 1000.0,
+            0.0,
+            BodyID(4),
+        );
+        let mut timeline = Timeline::new(orrery, 0.0);
+        timeline.extend_until(3000000.0);
+
+        // Kerbin, Mun, Kerbin, Mun, ...
+        let chart = GanttChart::from_timeline(&timeline, 3000000.0);
+        assert_eq!(chart.rows.len(), 1);
+        let row = &chart.rows[0];
+        let labels: Vec<_> = row.bars.iter().map(|b| b.label.as_str()).collect();
+        assert_eq!(labels[..4], ["Kerbin", "Mun", "Kerbin", "Mun"]);
+        assert_eq!(row.bars[0].start, 0.0);
+        assert_eq!(row.bars.last().unwrap().end, 3000000.0);
+        for (bar, next) in row.bars.iter().zip(row.bars.iter().skip(1)) {
+            assert_eq!(bar.end, next.start);
+        }
+        assert_eq!(row.milestones.len(), row.bars.len() - 1);
+        assert_eq!(row.milestones[0].label, "Encounter Mun");
+        assert_eq!(row.milestones[0].time, row.bars[1].start);
+
+        let svg = chart.to_svg();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert_eq!(svg.matches("<rect").count(), 1 + row.bars.len());
+        assert!(svg.contains(">Encounter Mun</title>"));
+        assert_eq!(row.note, None);
+    }
+
+    #[test]
+    fn test_delta_v_note() {
+        let ledger = DeltaVLedger {
+            spent: 0.0,
+            planned: 0.0,
+            estimated: 0.0,
+            capacity: None,
+        };
+        assert_eq!(delta_v_note(&ledger), None);
+
+        let ledger = DeltaVLedger {
+            planned: 1200.0,
+            ..ledger
+        };
+        assert_eq!(delta_v_note(&ledger).unwrap(), "Δv 1200 m/s");
+        let ledger = DeltaVLedger {
+            capacity: Some(1000.0),
+            ..ledger
+        };
+        assert_eq!(
+            delta_v_note(&ledger).unwrap(),
+            "Δv 1200 of 1000 m/s, 200 short!"
+        );
+    }
+
+    #[test]
+    fn test_tick_spacing() {
+        assert_eq!(tick_spacing(5000.0), 600.0);
+        assert_eq!(tick_spacing(30.0 * 86400.0), 7.0 * 86400.0);
+        assert_eq!(format_elapsed(7200.0), "2h");
+        assert_eq!(format_elapsed(3.0 * 86400.0), "3d");
+    }
+}
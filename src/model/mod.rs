@@ -1,3 +1,19 @@
+pub mod alignment;
+pub mod arrival;
+pub mod circularize;
+pub mod departure;
 pub mod events;
+pub mod figure;
+pub mod gantt;
+pub mod moments;
 pub mod orrery;
+pub mod plane_change;
+pub mod readouts;
+pub mod stability;
+pub mod targeting;
+pub mod templates;
 pub mod timeline;
+pub mod transfers;
+pub mod uncertainty;
+pub mod visibility;
+pub mod visits;
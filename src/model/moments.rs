@@ -0,0 +1,479 @@
+//! Picks out moments in a [Timeline] that are worth a look, such as close
+//! approaches and long eclipses, so that they can be listed and jumped to.
+
+use std::f64::consts::PI;
+
+use super::events::EventData;
+use super::orrery::{BodyID, Frame, Orrery, ShipID};
+use super::targeting::{closest_approach, ApproachTarget};
+use super::timeline::Timeline;
+
+/// Closest approaches to a body are only interesting if they get within this
+/// many of its SOI radii.
+const APPROACH_SOI_MULTIPLE: f64 = 5.0;
+/// Closest approaches this close to either end of the window are just the
+/// window cutting off an approach or departure, rather than a real minimum.
+const APPROACH_EDGE_MARGIN: f64 = 60.0;
+/// How many bodies (not counting their parent) have to line up to count as an
+/// alignment.
+const MIN_ALIGNED_BODIES: usize = 3;
+/// How far apart, in radians, the directions to aligned bodies can be, as
+/// seen from their parent. Bodies on opposite sides count as aligned too.
+const ALIGNMENT_TOLERANCE: f64 = 5.0 * PI / 180.0;
+/// How finely to sample alignments, relative to the period of the fastest
+/// body involved.
+const ALIGNMENT_SAMPLES_PER_ORBIT: f64 = 64.0;
+
+/// Something that happens at a moment worth looking at.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MomentKind {
+    /// The ship came closer to the target than at any other time in the
+    /// window.
+    ClosestApproach {
+        ship_id: ShipID,
+        target: ApproachTarget,
+        distance: f64,
+    },
+    /// The ship's longest stay in the shadow of the given body started.
+    Eclipse {
+        ship_id: ShipID,
+        body_id: BodyID,
+        duration: f64,
+    },
+    /// The bodies, which all orbit the same parent, lined up with it as
+    /// closely as they will for a while. The spread is the angle, in radians,
+    /// between the directions to the least aligned pair.
+    Alignment {
+        parent_id: BodyID,
+        body_ids: Vec<BodyID>,
+        spread: f64,
+    },
+    /// The ship was at its highest or lowest altitude above the given body,
+    /// out of all its apsides around that body.
+    RecordAltitude {
+        ship_id: ShipID,
+        body_id: BodyID,
+        altitude: f64,
+        is_highest: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Moment {
+    pub time: f64,
+    pub kind: MomentKind,
+}
+
+/// Looks for interesting moments between the two times, returning them in
+/// chronological order. Only the part of the timeline that's already been
+/// searched is meaningful.
+pub fn find_interesting_moments(
+    timeline: &Timeline,
+    start_time: f64,
+    end_time: f64,
+) -> Vec<Moment> {
+    let orrery = match timeline.get_orrery_at(start_time) {
+        Some(orrery) => orrery,
+        None => return vec![],
+    };
+
+    let mut moments = vec![];
+    moments.extend(closest_approaches(timeline, &orrery, start_time, end_time));
+    moments.extend(longest_eclipses(timeline, start_time, end_time));
+    moments.extend(alignments(&orrery, start_time, end_time));
+    moments.extend(record_altitudes(timeline, &orrery, start_time, end_time));
+    moments.sort_by(|a, b| a.time.total_cmp(&b.time));
+    moments
+}
+
+fn closest_approaches(
+    timeline: &Timeline,
+    orrery: &Orrery,
+    start_time: f64,
+    end_time: f64,
+) -> Vec<Moment> {
+    let mut ship_ids: Vec<_> = orrery.ships().map(|s| s.id).collect();
+    ship_ids.sort();
+
+    // Bodies only count if we get near them, but any approach between ships
+    // is interesting
+    let mut targets: Vec<(ApproachTarget, f64)> = orrery
+        .bodies()
+        .filter_map(|b| {
+            let soi_radius = orrery.get_soi_radius(b.id)?;
+            Some((
+                ApproachTarget::Body(b.id),
+                APPROACH_SOI_MULTIPLE * soi_radius,
+            ))
+        })
+        .chain(
+            ship_ids
+                .iter()
+                .map(|id| (ApproachTarget::Ship(*id), f64::INFINITY)),
+        )
+        .collect();
+    targets.sort_by_key(|(target, _)| match target {
+        ApproachTarget::Body(id) => (0, id.0),
+        ApproachTarget::Ship(id) => (1, id.0),
+    });
+
+    let mut moments = vec![];
+    for ship_id in ship_ids.iter().copied() {
+        for (target, max_distance) in targets.iter().copied() {
+            match target {
+                // Approaching the body you're orbiting is just periapsis
+                ApproachTarget::Body(id) if id == orrery.get_ship(ship_id).parent_id() => continue,
+                // Only count each pair of ships once
+                ApproachTarget::Ship(id) if id <= ship_id => continue,
+                _ => {}
+            }
+
+            let approach = match closest_approach(timeline, ship_id, target, start_time, end_time) {
+                Some(approach) => approach,
+                None => continue,
+            };
+            let is_interior = approach.time > start_time + APPROACH_EDGE_MARGIN
+                && approach.time < end_time - APPROACH_EDGE_MARGIN;
+            if is_interior && approach.distance < max_distance {
+                moments.push(Moment {
+                    time: approach.time,
+                    kind: MomentKind::ClosestApproach {
+                        ship_id,
+                        target,
+                        distance: approach.distance,
+                    },
+                });
+            }
+        }
+    }
+    moments
+}
+
+fn longest_eclipses(timeline: &Timeline, start_time: f64, end_time: f64) -> Vec<Moment> {
+    // Pair up each entry with the next exit for the same ship
+    let events: Vec<_> = timeline
+        .events()
+        .filter(|e| (start_time..=end_time).contains(&e.point.time))
+        .collect();
+    let mut longest: Vec<Moment> = vec![];
+    for (i, entry) in events.iter().enumerate() {
+        let body_id = match entry.data {
+            EventData::EnteringShadow(id) => id,
+            _ => continue,
+        };
+        let exit_time = events[i + 1..]
+            .iter()
+            .find(|e| e.ship_id == entry.ship_id && e.data == EventData::ExitingShadow(body_id))
+            .map_or(end_time, |e| e.point.time);
+        let duration = exit_time - entry.point.time;
+
+        let kind = MomentKind::Eclipse {
+            ship_id: entry.ship_id,
+            body_id,
+            duration,
+        };
+        let existing = longest.iter_mut().find(|m| match m.kind {
+            MomentKind::Eclipse {
+                ship_id,
+                body_id: b,
+                ..
+            } => ship_id == entry.ship_id && b == body_id,
+            _ => false,
+        });
+        match existing {
+            Some(moment) => {
+                if let MomentKind::Eclipse {
+                    duration: longest_duration,
+                    ..
+                } = moment.kind
+                {
+                    if duration > longest_duration {
+                        moment.time = entry.point.time;
+                        moment.kind = kind;
+                    }
+                }
+            }
+            None => longest.push(Moment {
+                time: entry.point.time,
+                kind,
+            }),
+        }
+    }
+    longest
+}
+
+fn alignments(orrery: &Orrery, start_time: f64, end_time: f64) -> Vec<Moment> {
+    // Only look at the systems the ships are in; alignments elsewhere happen
+    // all the time, and nobody's there to see them
+    let mut parents: Vec<BodyID> = vec![];
+    for ship in orrery.ships() {
+        let mut body_id = Some(ship.parent_id());
+        while let Some(id) = body_id {
+            parents.push(id);
+            body_id = orrery.get_parent(id);
+        }
+    }
+    parents.sort();
+    parents.dedup();
+
+    let mut moments = vec![];
+    for parent_id in parents {
+        let mut children: Vec<BodyID> = orrery
+            .bodies()
+            .filter(|b| orrery.get_parent(b.id) == Some(parent_id))
+            .map(|b| b.id)
+            .collect();
+        if children.len() < MIN_ALIGNED_BODIES {
+            continue;
+        }
+        children.sort();
+
+        let min_period = children
+            .iter()
+            .filter_map(|id| orrery.orbit_of_body(*id)?.period())
+            .fold(f64::INFINITY, f64::min);
+        if !min_period.is_finite() {
+            continue;
+        }
+        let step = min_period / ALIGNMENT_SAMPLES_PER_ORBIT;
+
+        // Follow each stretch of time where the same bodies are lined up, and
+        // report when they're closest
+        let mut run: Option<Moment> = None;
+        let mut time = start_time;
+        while time <= end_time {
+            let angles: Vec<(BodyID, f64)> = children
+                .iter()
+                .map(|id| {
+                    let position = orrery
+                        .get_body_state(*id, time)
+                        .get_position(Frame::BodyInertial(parent_id), time);
+                    (*id, position.y.atan2(position.x))
+                })
+                .collect();
+            let aligned = largest_aligned_group(&angles)
+                .filter(|(ids, _)| ids.len() >= MIN_ALIGNED_BODIES)
+                .map(|(body_ids, spread)| Moment {
+                    time,
+                    kind: MomentKind::Alignment {
+                        parent_id,
+                        body_ids,
+                        spread,
+                    },
+                });
+
+            run = match (run, aligned) {
+                (Some(current), Some(new)) if same_bodies(&current.kind, &new.kind) => {
+                    if alignment_spread(&new.kind) < alignment_spread(&current.kind) {
+                        Some(new)
+                    } else {
+                        Some(current)
+                    }
+                }
+                (current, new) => {
+                    moments.extend(current);
+                    new
+                }
+            };
+            time += step;
+        }
+        moments.extend(run);
+    }
+    moments
+}
+
+/// Finds the largest group of bodies whose directions lie within
+/// [ALIGNMENT_TOLERANCE] of a common line through the parent, along with how
+/// spread out they are. Ties are broken by the spread.
+fn largest_aligned_group(angles: &[(BodyID, f64)]) -> Option<(Vec<BodyID>, f64)> {
+    // Directions on opposite sides are on the same line
+    let mut lines: Vec<(BodyID, f64)> = angles
+        .iter()
+        .map(|(id, angle)| (*id, angle.rem_euclid(PI)))
+        .collect();
+    lines.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    // Slide a window around the circle of lines
+    let n = lines.len();
+    let mut best: Option<(Vec<BodyID>, f64)> = None;
+    for i in 0..n {
+        let mut group = vec![lines[i].0];
+        let mut spread = 0.0;
+        for j in 1..n {
+            let (id, angle) = lines[(i + j) % n];
+            let mut gap = angle - lines[i].1;
+            if i + j >= n {
+                gap += PI;
+            }
+            if gap > ALIGNMENT_TOLERANCE {
+                break;
+            }
+            group.push(id);
+            spread = gap;
+        }
+
+        let is_better = match &best {
+            None => true,
+            Some((best_group, best_spread)) => {
+                group.len() > best_group.len()
+                    || (group.len() == best_group.len() && spread < *best_spread)
+            }
+        };
+        if is_better {
+            group.sort();
+            best = Some((group, spread));
+        }
+    }
+    best
+}
+
+fn same_bodies(a: &MomentKind, b: &MomentKind) -> bool {
+    match (a, b) {
+        (MomentKind::Alignment { body_ids: a, .. }, MomentKind::Alignment { body_ids: b, .. }) => {
+            a == b
+        }
+        _ => false,
+    }
+}
+
+fn alignment_spread(kind: &MomentKind) -> f64 {
+    match kind {
+        MomentKind::Alignment { spread, .. } => *spread,
+        _ => f64::INFINITY,
+    }
+}
+
+fn record_altitudes(
+    timeline: &Timeline,
+    orrery: &Orrery,
+    start_time: f64,
+    end_time: f64,
+) -> Vec<Moment> {
+    // Keyed by ship, body, and whether it's the highest
+    let mut records: Vec<((ShipID, BodyID, bool), Moment, usize)> = vec![];
+    let apsides = timeline
+        .events()
+        .filter(|e| (start_time..=end_time).contains(&e.point.time));
+    for event in apsides {
+        let (body_id, is_highest) = match event.data {
+            EventData::Apoapsis(id) => (id, true),
+            EventData::Periapsis(id) => (id, false),
+            _ => continue,
+        };
+        let altitude =
+            event.point.location.coords.norm() - orrery.get_body(body_id).info.radius as f64;
+        let moment = Moment {
+            time: event.point.time,
+            kind: MomentKind::RecordAltitude {
+                ship_id: event.ship_id,
+                body_id,
+                altitude,
+                is_highest,
+            },
+        };
+
+        let key = (event.ship_id, body_id, is_highest);
+        match records.iter_mut().find(|(k, _, _)| *k == key) {
+            Some((_, record, count)) => {
+                *count += 1;
+                let record_altitude = match record.kind {
+                    MomentKind::RecordAltitude { altitude, .. } => altitude,
+                    _ => unreachable!(),
+                };
+                if (is_highest && altitude > record_altitude)
+                    || (!is_highest && altitude < record_altitude)
+                {
+                    *record = moment;
+                }
+            }
+            None => records.push((key, moment, 1)),
+        }
+    }
+
+    // If there's only one apsis of a kind, it's not much of a record
+    records
+        .into_iter()
+        .filter(|(_, _, count)| *count > 1)
+        .map(|(_, moment, _)| moment)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+
+    use super::*;
+    use crate::file::read_file;
+
+    const KERBIN: BodyID = BodyID(4);
+    const MUN: BodyID = BodyID(5);
+
+    #[test]
+    fn test_favorite_scenario() {
+        let mut orrery = read_file("ksp-bodies.txt");
+        let ship_id = orrery.add_ship(Vector3::x() * 6000000.0, Vector3::y() * 1000.0, 0.0, KERBIN);
+        let mut timeline = Timeline::new(orrery, 0.0);
+        let end_time = 1500000.0;
+        timeline.extend_until(end_time);
+
+        let moments = find_interesting_moments(&timeline, 0.0, end_time);
+        assert!(moments.windows(2).all(|w| w[0].time <= w[1].time));
+
+        // The closest approach to the Mun is during one of the encounters,
+        // deep inside its SOI
+        let mun_approach = moments
+            .iter()
+            .find_map(|m| match m.kind {
+                MomentKind::ClosestApproach {
+                    target: ApproachTarget::Body(MUN),
+                    distance,
+                    ..
+                } => Some((m.time, distance)),
+                _ => None,
+            })
+            .expect("Should approach the Mun");
+        let mun_soi = timeline.get_orrery_at(0.0).unwrap().get_soi_radius(MUN);
+        assert!(mun_approach.1 < 0.5 * mun_soi.unwrap());
+        let encounters: Vec<_> = timeline
+            .events()
+            .filter(|e| matches!(e.data, EventData::EnteringSOI(_) | EventData::ExitingSOI(_)))
+            .map(|e| e.point.time)
+            .collect();
+        assert!(encounters
+            .chunks(2)
+            .any(|c| c[0] < mun_approach.0 && mun_approach.0 < c[1]));
+
+        // The encounters raised the orbit, so the highest apoapsis is after them
+        let highest = moments
+            .iter()
+            .find(|m| {
+                matches!(
+                    m.kind,
+                    MomentKind::RecordAltitude {
+                        ship_id: id,
+                        body_id: KERBIN,
+                        is_highest: true,
+                        ..
+                    } if id == ship_id
+                )
+            })
+            .expect("Should have a highest apoapsis");
+        assert!(highest.time > encounters[0]);
+    }
+
+    #[test]
+    fn test_aligned_group() {
+        let degrees = |d: f64| d.to_radians();
+        let angles = [
+            (BodyID(1), degrees(1.0)),
+            (BodyID(2), degrees(183.0)),
+            (BodyID(3), degrees(100.0)),
+            (BodyID(4), degrees(359.0)),
+        ];
+        // Up to direction, these are at 1, 3, 100, and 179 degrees, and the
+        // first, second, and last are within 5 degrees of each other
+        let (group, spread) = largest_aligned_group(&angles).unwrap();
+        assert_eq!(group, vec![BodyID(1), BodyID(2), BodyID(4)]);
+        approx::assert_relative_eq!(spread, degrees(4.0), epsilon = 1e-12);
+    }
+}
@@ -1,6 +1,10 @@
+use std::f64::consts::PI;
+
 use nalgebra::Point3;
 
+use super::OrbitElements;
 use crate::astro::HasMass;
+use crate::math::geometry::wrap_angle;
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct BodyID(pub usize);
@@ -13,6 +17,56 @@ pub struct BodyInfo {
     pub mu: f64,
     pub radius: f32,
     pub color: Point3<f32>,
+    /// A tabulated SOI radius, used instead of a computed one when the orrery
+    /// is using [SoiModel::Fixed](super::SoiModel::Fixed).
+    pub soi_radius: Option<f64>,
+    /// Sidereal rotation period, in seconds.
+    pub rotation_period: Option<f64>,
+    /// Angle the body has rotated about its z-axis at time zero, in radians.
+    pub initial_rotation: f64,
+    /// Altitude of the top of the atmosphere, if the body has one.
+    pub atmosphere_height: Option<f64>,
+    /// Path to an image to wrap around the body, instead of coloring it
+    /// flat. It's an equirectangular map, with north at the top, and the
+    /// body-fixed x-axis at the left and right edges.
+    pub texture: Option<String>,
+    pub rings: Option<Rings>,
+}
+
+/// A flat ring system around a body's equator. Purely for looks; rings
+/// don't affect ships.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rings {
+    /// Distances from the body's center to the edges of the rings.
+    pub inner_radius: f32,
+    pub outer_radius: f32,
+    pub color: Point3<f32>,
+    /// Path to an image to color the rings with instead, where the left edge
+    /// is the inner edge of the rings, and the right edge is the outer edge.
+    /// Only the middle row is used, and transparent parts show as gaps.
+    pub texture: Option<String>,
+}
+
+impl BodyInfo {
+    /// Rate of rotation about the z-axis, in radians per second. Bodies
+    /// without a rotation period don't rotate.
+    pub fn angular_velocity(&self) -> f64 {
+        match self.rotation_period {
+            Some(period) => 2.0 * PI / period,
+            None => 0.0,
+        }
+    }
+
+    /// Acceleration due to gravity at the body's surface, in m/s^2.
+    pub fn surface_gravity(&self) -> f64 {
+        self.mu / (self.radius as f64).powi(2)
+    }
+
+    /// Angle the body has rotated about its z-axis at the given time, in the
+    /// range [0, 2pi).
+    pub fn rotation_angle_at(&self, time: f64) -> f64 {
+        wrap_angle(self.initial_rotation + self.angular_velocity() * time)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -26,3 +80,36 @@ impl HasMass for Body {
         self.info.mu
     }
 }
+
+/// A change to some of a body's physical properties, for trying out what
+/// would happen if the body were different. Properties left as `None` are
+/// kept as they are.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BodyEdit {
+    pub mu: Option<f64>,
+    pub radius: Option<f32>,
+    /// Replaces the tabulated SOI radius, so like it, this only matters when
+    /// using [SoiModel::Fixed](super::SoiModel::Fixed).
+    pub soi_radius: Option<f64>,
+    /// Moves the body onto a new orbit around the same parent, with the mean
+    /// anomaly as of the time of the edit.
+    pub orbit: Option<OrbitElements>,
+}
+
+impl BodyEdit {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn apply_to(&self, info: &mut BodyInfo) {
+        if let Some(mu) = self.mu {
+            info.mu = mu;
+        }
+        if let Some(radius) = self.radius {
+            info.radius = radius;
+        }
+        if let Some(soi_radius) = self.soi_radius {
+            info.soi_radius = Some(soi_radius);
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use std::f64::consts::TAU;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use nalgebra::Point3;
+
+use super::{BodyID, ShipPlacement};
+
+/// A Walker delta constellation, written i:t/p/f: `total` ships in circular
+/// orbits of inclination i, spread evenly over `planes` orbital planes, whose
+/// ascending nodes are spread evenly around the equator. Going from one plane
+/// to the next, the ships are shifted along their orbits by `phasing` times
+/// 360/t degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WalkerConstellation {
+    /// In radians
+    pub inclination: f64,
+    pub total: usize,
+    pub planes: usize,
+    pub phasing: usize,
+}
+
+/// One ship of a constellation, and where it goes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConstellationSlot {
+    /// Which plane the ship is in, counting from zero.
+    pub plane: usize,
+    /// Where the ship is within its plane, counting from zero.
+    pub index: usize,
+    pub placement: ShipPlacement,
+}
+
+impl WalkerConstellation {
+    pub fn ships_per_plane(&self) -> usize {
+        self.total / self.planes
+    }
+
+    /// Returns where each ship goes, for a constellation at the given radius
+    /// around the given body. Ships are listed plane by plane.
+    pub fn slots(&self, parent_id: BodyID, radius: f64, epoch: f64) -> Vec<ConstellationSlot> {
+        let per_plane = self.ships_per_plane();
+        let mut slots = Vec::with_capacity(self.total);
+        for plane in 0..self.planes {
+            for index in 0..per_plane {
+                let mut placement = ShipPlacement {
+                    inclination: self.inclination,
+                    long_asc_node: TAU * plane as f64 / self.planes as f64,
+                    ..ShipPlacement::circular(parent_id, radius, epoch)
+                };
+                placement.set_mean_anomaly(
+                    TAU * index as f64 / per_plane as f64
+                        + TAU * (self.phasing * plane) as f64 / self.total as f64,
+                );
+                slots.push(ConstellationSlot {
+                    plane,
+                    index,
+                    placement,
+                });
+            }
+        }
+        slots
+    }
+
+    /// A color for each plane, going around the color wheel.
+    pub fn plane_color(&self, plane: usize) -> Point3<f32> {
+        hue_to_color(plane as f32 / self.planes as f32)
+    }
+}
+
+impl ConstellationSlot {
+    /// Names the ship after its plane and position in it, both counting from
+    /// one, e.g. "GPS 2-3" is the third ship in the second plane.
+    pub fn name(&self, prefix: &str) -> String {
+        format!("{} {}-{}", prefix, self.plane + 1, self.index + 1)
+    }
+}
+
+/// Returns a fairly bright color with the given hue, from 0 to 1.
+fn hue_to_color(hue: f32) -> Point3<f32> {
+    const SATURATION: f32 = 0.7;
+    // The usual HSV to RGB conversion, with a value of 1
+    let channel = |n: f32| {
+        let k = (n + 6.0 * hue).rem_euclid(6.0);
+        1.0 - SATURATION * k.min(4.0 - k).clamp(0.0, 1.0)
+    };
+    Point3::new(channel(5.0), channel(3.0), channel(1.0))
+}
+
+impl FromStr for WalkerConstellation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("Expected a constellation like 56:24/3/1, but got {:?}", s);
+        let (inclination, rest) = s.split_once(':').ok_or_else(error)?;
+        let mut fields = rest.split('/');
+        let mut next = || -> Result<usize, String> {
+            fields
+                .next()
+                .ok_or_else(error)?
+                .parse()
+                .map_err(|_| error())
+        };
+        let (total, planes, phasing) = (next()?, next()?, next()?);
+        let inclination: f64 = inclination.parse().map_err(|_| error())?;
+
+        if planes == 0 || total == 0 || !total.is_multiple_of(planes) {
+            return Err(format!(
+                "Can't spread {} ships evenly over {} planes",
+                total, planes
+            ));
+        }
+        if phasing >= planes {
+            return Err(format!(
+                "Phasing must be less than the number of planes, but was {}",
+                phasing
+            ));
+        }
+        Ok(Self {
+            inclination: inclination.to_radians(),
+            total,
+            planes,
+            phasing,
+        })
+    }
+}
+
+impl Display for WalkerConstellation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}/{}/{}",
+            self.inclination.to_degrees(),
+            self.total,
+            self.planes,
+            self.phasing
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    const KERBIN: BodyID = BodyID(4);
+
+    #[test]
+    fn test_galileo() {
+        // Galileo is a 56:24/3/1 constellation
+        let walker: WalkerConstellation = "56:24/3/1".parse().unwrap();
+        assert_eq!(walker.to_string(), "56:24/3/1");
+        assert_eq!(walker.ships_per_plane(), 8);
+
+        let slots = walker.slots(KERBIN, 1e6, 0.0);
+        assert_eq!(slots.len(), 24);
+        for slot in slots.iter() {
+            assert_relative_eq!(slot.placement.inclination, 56f64.to_radians());
+            assert_relative_eq!(
+                slot.placement.long_asc_node,
+                TAU * slot.plane as f64 / 3.0,
+                epsilon = 1e-12
+            );
+        }
+
+        // Within a plane, ships are 45 degrees apart, and each plane is
+        // shifted by 15 degrees from the last
+        assert_relative_eq!(slots[1].placement.mean_anomaly, 45f64.to_radians());
+        assert_eq!(slots[8].name("Galileo"), "Galileo 2-1");
+        assert_relative_eq!(slots[8].placement.mean_anomaly, 15f64.to_radians());
+        assert_relative_eq!(slots[17].placement.mean_anomaly, 75f64.to_radians());
+
+        // Each plane gets its own color
+        assert_ne!(walker.plane_color(0), walker.plane_color(1));
+        assert_ne!(walker.plane_color(1), walker.plane_color(2));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!("56:24/5/1".parse::<WalkerConstellation>().is_err());
+        assert!("56:24/3/3".parse::<WalkerConstellation>().is_err());
+        assert!("56:24/3".parse::<WalkerConstellation>().is_err());
+        assert!("24/3/1".parse::<WalkerConstellation>().is_err());
+    }
+}
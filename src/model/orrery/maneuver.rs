@@ -0,0 +1,230 @@
+use std::fmt;
+use std::str::FromStr;
+
+use nalgebra::{Matrix3, Vector3};
+
+/// An instantaneous change in a ship's velocity, at a planned time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Maneuver {
+    pub time: f64,
+    /// In the ship's parent's inertial frame at the time of the burn, like
+    /// [Orrery::apply_impulse](super::Orrery::apply_impulse).
+    pub delta_v: Vector3<f64>,
+}
+
+/// Returns the matrix taking prograde, normal and radial-out components of a
+/// burn to the frame the position and velocity are in.
+pub fn orbit_frame(position: Vector3<f64>, velocity: Vector3<f64>) -> Matrix3<f64> {
+    let prograde = velocity.normalize();
+    let normal = position.cross(&velocity).normalize();
+    let radial = prograde.cross(&normal);
+    Matrix3::from_columns(&[prograde, normal, radial])
+}
+
+/// What a ship does once its plan is over, that it doesn't have burns
+/// planned for yet. Its cost is estimated from the orbit the ship ends up on,
+/// and counted in its [DeltaVLedger].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arrival {
+    /// Circularize at periapsis, around whatever body it ends up at.
+    Capture,
+    /// Come down from that circular orbit, and touch down on the body.
+    Land,
+}
+
+impl fmt::Display for Arrival {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arrival::Capture => write!(f, "capture"),
+            Arrival::Land => write!(f, "land"),
+        }
+    }
+}
+
+impl FromStr for Arrival {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "capture" => Ok(Arrival::Capture),
+            "land" => Ok(Arrival::Land),
+            _ => Err(format!("Expected capture or land, but got {:?}", s)),
+        }
+    }
+}
+
+/// A running total of the delta-v a ship's plan uses, split at some time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeltaVLedger {
+    /// Used by the burns up to and including that time.
+    pub spent: f64,
+    /// Still to be used by the burns after it.
+    pub planned: f64,
+    /// Estimated cost of the ship's [Arrival], which isn't in the plan yet.
+    pub estimated: f64,
+    /// How much the ship has for all its burns, if that's limited.
+    pub capacity: Option<f64>,
+}
+
+impl DeltaVLedger {
+    /// Delta-v used by the whole plan, and the estimated cost of what comes
+    /// after it.
+    pub fn total(&self) -> f64 {
+        self.spent + self.planned + self.estimated
+    }
+
+    /// How much would be left after the whole plan, if the ship's capacity
+    /// is known. Negative if the plan needs more than the ship has.
+    pub fn remaining(&self) -> Option<f64> {
+        self.capacity.map(|capacity| capacity - self.total())
+    }
+
+    pub fn is_over_capacity(&self) -> bool {
+        self.remaining().is_some_and(|r| r < 0.0)
+    }
+}
+
+/// The maneuvers planned for a ship, in the order they happen.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ManeuverPlan {
+    // Invariant: sorted by time, with no two at the same time
+    maneuvers: Vec<Maneuver>,
+}
+
+impl ManeuverPlan {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn maneuvers(&self) -> &[Maneuver] {
+        &self.maneuvers
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.maneuvers.is_empty()
+    }
+
+    /// Returns the maneuver at exactly the given time, if there is one.
+    pub fn maneuver_at(&self, time: f64) -> Option<&Maneuver> {
+        self.maneuvers.iter().find(|m| m.time == time)
+    }
+
+    /// Returns the first maneuver strictly after the given time.
+    pub fn next_after(&self, time: f64) -> Option<&Maneuver> {
+        self.upcoming(time).first()
+    }
+
+    /// Returns the maneuvers strictly after the given time, in order.
+    pub fn upcoming(&self, time: f64) -> &[Maneuver] {
+        let idx = self.maneuvers.partition_point(|m| m.time <= time);
+        &self.maneuvers[idx..]
+    }
+
+    /// Adds the maneuver, replacing any that's already planned for the same
+    /// time.
+    pub fn set_maneuver(&mut self, maneuver: Maneuver) {
+        let idx = self.maneuvers.partition_point(|m| m.time < maneuver.time);
+        match self.maneuvers.get_mut(idx) {
+            Some(m) if m.time == maneuver.time => *m = maneuver,
+            _ => self.maneuvers.insert(idx, maneuver),
+        }
+    }
+
+    /// Removes the maneuver at the given time, if there is one. Returns
+    /// whether one was removed.
+    pub fn remove_maneuver(&mut self, time: f64) -> bool {
+        match self.maneuvers.iter().position(|m| m.time == time) {
+            Some(idx) => {
+                self.maneuvers.remove(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Adds up the delta-v of the burns before and after the given time. A
+    /// burn at exactly that time counts as already made.
+    pub fn ledger(&self, time: f64, capacity: Option<f64>) -> DeltaVLedger {
+        let idx = self.maneuvers.partition_point(|m| m.time <= time);
+        let sum = |maneuvers: &[Maneuver]| maneuvers.iter().map(|m| m.delta_v.norm()).sum();
+        DeltaVLedger {
+            spent: sum(&self.maneuvers[..idx]),
+            planned: sum(&self.maneuvers[idx..]),
+            estimated: 0.0,
+            capacity,
+        }
+    }
+
+    /// Returns the earliest time at which the two plans differ, or `None` if
+    /// they're the same.
+    pub fn first_difference(&self, other: &ManeuverPlan) -> Option<f64> {
+        let mut times: Vec<f64> = self
+            .maneuvers
+            .iter()
+            .chain(other.maneuvers.iter())
+            .map(|m| m.time)
+            .collect();
+        times.sort_by(f64::total_cmp);
+        times
+            .into_iter()
+            .find(|t| self.maneuver_at(*t) != other.maneuver_at(*t))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn burn(time: f64, prograde: f64) -> Maneuver {
+        Maneuver {
+            time,
+            delta_v: Vector3::new(0.0, prograde, 0.0),
+        }
+    }
+
+    #[test]
+    fn test_plan() {
+        let mut plan = ManeuverPlan::new();
+        plan.set_maneuver(burn(100.0, 10.0));
+        plan.set_maneuver(burn(50.0, 5.0));
+        assert_eq!(plan.maneuvers(), &[burn(50.0, 5.0), burn(100.0, 10.0)]);
+        assert_eq!(plan.next_after(0.0), Some(&burn(50.0, 5.0)));
+        assert_eq!(plan.next_after(50.0), Some(&burn(100.0, 10.0)));
+        assert_eq!(plan.next_after(100.0), None);
+        assert_eq!(plan.upcoming(50.0), &[burn(100.0, 10.0)]);
+        assert!(plan.upcoming(100.0).is_empty());
+
+        // Editing and removing maneuvers
+        let original = plan.clone();
+        plan.set_maneuver(burn(100.0, 20.0));
+        assert_eq!(plan.maneuvers().len(), 2);
+        assert_eq!(plan.first_difference(&original), Some(100.0));
+        assert!(plan.remove_maneuver(50.0));
+        assert!(!plan.remove_maneuver(50.0));
+        assert_eq!(plan.first_difference(&original), Some(50.0));
+        assert_eq!(original.first_difference(&original), None);
+    }
+
+    #[test]
+    fn test_ledger() {
+        let mut plan = ManeuverPlan::new();
+        plan.set_maneuver(burn(50.0, 5.0));
+        plan.set_maneuver(Maneuver {
+            time: 100.0,
+            delta_v: Vector3::new(3.0, 0.0, -4.0),
+        });
+
+        let ledger = plan.ledger(50.0, None);
+        assert_eq!(ledger.spent, 5.0);
+        assert_eq!(ledger.planned, 5.0);
+        assert_eq!(ledger.remaining(), None);
+        assert!(!ledger.is_over_capacity());
+
+        let ledger = plan.ledger(0.0, Some(8.0));
+        assert_eq!(ledger.spent, 0.0);
+        assert_eq!(ledger.total(), 10.0);
+        assert_eq!(ledger.remaining(), Some(-2.0));
+        assert!(ledger.is_over_capacity());
+        assert!(!plan.ledger(0.0, Some(10.0)).is_over_capacity());
+    }
+}
@@ -1,15 +1,34 @@
 use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
 
 use nalgebra::{Point3, UnitQuaternion, Vector3};
 
+use crate::astro::integrate::{
+    measure_energy_drift, propagate_until, EnergyDrift, IntegratorSettings, PhaseState, SampledPath,
+};
 use crate::astro::{CartesianState, HasMass, Orbit, PointMass, TimedOrbit};
 use crate::math::frame::FrameTransform;
 
 mod body;
+mod constellation;
+mod maneuver;
+mod placement;
+mod sail;
 mod ship;
+mod snapshot;
+mod srp;
+mod stages;
 
-pub use body::{Body, BodyID, BodyInfo};
-pub use ship::{Ship, ShipID};
+pub use body::{Body, BodyEdit, BodyID, BodyInfo, Rings};
+pub use constellation::{ConstellationSlot, WalkerConstellation};
+pub use maneuver::{orbit_frame, Arrival, DeltaVLedger, Maneuver, ManeuverPlan};
+pub use placement::{OrbitElements, ShipPlacement};
+pub use sail::{SailAttitude, SailSchedule, SolarSail};
+pub use ship::{Impact, NumericArc, Propagation, Ship, ShipID};
+pub use snapshot::{ObjectState, SystemSnapshot};
+pub use srp::{cone_shadow_depth, shadow_depth, ShadowRegion, SolarRadiationPressure};
+pub use stages::{BurnEstimate, Stage, StageStack, StageSummary};
 
 use super::events::{Event, EventData};
 
@@ -17,10 +36,27 @@ use super::events::{Event, EventData};
 pub enum Frame {
     Root,
     BodyInertial(BodyID),
+    /// Centered on the body, and rotating with it, so that points on the
+    /// surface stay fixed.
+    BodyFixed(BodyID),
     ShipInertial(ShipID),
     ShipOrbital(ShipID),
 }
 
+/// Which formula to use when computing the radius of a body's sphere of
+/// influence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SoiModel {
+    /// The Laplace sphere, a (m/M)^(2/5). This is what KSP uses.
+    #[default]
+    Laplace,
+    /// The Hill sphere, a (1-e) (m/3M)^(1/3).
+    Hill,
+    /// Use the radius given in each body's [BodyInfo], falling back to the
+    /// Laplace sphere if none is given.
+    Fixed,
+}
+
 pub struct FramedState<'orr> {
     orrery: &'orr Orrery,
     position: Point3<f64>,
@@ -42,6 +78,10 @@ pub struct Orrery {
     next_body_id: usize,
     ships: HashMap<ShipID, Ship>,
     next_ship_id: usize,
+    soi_model: SoiModel,
+    relativistic_precession: bool,
+    // The last time precession was applied up to
+    precession_epoch: Option<f64>,
 }
 
 impl FramedState<'_> {
@@ -67,6 +107,12 @@ impl BodyState {
     }
 }
 
+impl Default for Orrery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Orrery {
     pub fn new() -> Self {
         Orrery {
@@ -74,9 +120,32 @@ impl Orrery {
             next_body_id: 0,
             ships: HashMap::new(),
             next_ship_id: 0,
+            soi_model: SoiModel::default(),
+            relativistic_precession: false,
+            precession_epoch: None,
         }
     }
 
+    pub fn soi_model(&self) -> SoiModel {
+        self.soi_model
+    }
+
+    pub fn set_soi_model(&mut self, soi_model: SoiModel) {
+        self.soi_model = soi_model;
+    }
+
+    pub fn relativistic_precession(&self) -> bool {
+        self.relativistic_precession
+    }
+
+    /// Whether to advance the periapsis of every orbit to account for general
+    /// relativity. This only matters for real-scale systems over long spans of
+    /// time (e.g. Mercury's 43 arcseconds per century); see
+    /// [Orrery::advance_precession].
+    pub fn set_relativistic_precession(&mut self, enabled: bool) {
+        self.relativistic_precession = enabled;
+    }
+
     pub fn get_parent(&self, id: BodyID) -> Option<BodyID> {
         self.bodies[&id]
             .orbit
@@ -119,6 +188,91 @@ impl Orrery {
         self.insert_new_body(body_info, Some(orbit))
     }
 
+    /// Changes some of a body's physical properties at the given time.
+    ///
+    /// A new orbit is around the body's current parent, with the body at the
+    /// given mean anomaly at that time. Orbits around the body depend on its
+    /// mass, so everything orbiting it keeps its position and velocity at that
+    /// time, but moves onto a new orbit. Ships being integrated are taken off their arcs, like in
+    /// [Orrery::apply_impulse]. SOIs are always computed from the current
+    /// masses, so they need no updating.
+    ///
+    /// This doesn't know when the orrery starts being valid, so objects only
+    /// follow their new orbits from this time on if the orrery starts then;
+    /// to change a body partway through, use
+    /// [Timeline::edit_body](crate::model::timeline::Timeline::edit_body).
+    pub fn edit_body(&mut self, id: BodyID, edit: &BodyEdit, time: f64) {
+        if let Some(elements) = edit.orbit {
+            let parent_id = self
+                .get_parent(id)
+                .expect("Root body can't be given an orbit");
+            let parent = self.bodies[&parent_id].body.clone();
+            self.bodies.get_mut(&id).unwrap().orbit = Some(elements.orbit(parent, time));
+        }
+
+        let state = self.bodies.get_mut(&id).unwrap();
+        edit.apply_to(&mut state.body.info);
+        let body = state.body.clone();
+
+        // Orbits keep their own copy of their primary, so replace those too
+        let child_orbits = self
+            .bodies
+            .values_mut()
+            .filter_map(|b| b.orbit.as_mut())
+            .filter(|orbit| orbit.primary().id == id);
+        for orbit in child_orbits {
+            let state = orbit.state_at_time(time);
+            *orbit = TimedOrbit::from_state(
+                CartesianState::new(body.clone(), state.position(), state.velocity()),
+                time,
+            );
+        }
+        let child_ships = self
+            .ships
+            .values_mut()
+            .filter(|s| s.parent_id() == id && !s.has_crashed());
+        for ship in child_ships {
+            let state = ship.state_at_time(time);
+            ship.orbit = TimedOrbit::from_state(
+                CartesianState::new(body.clone(), state.position(), state.velocity()),
+                time,
+            );
+            ship.numeric_arc = None;
+        }
+    }
+
+    /// Returns why the edit can't be made at the given time, if it can't.
+    /// Bodies can only follow closed orbits, so edits that would leave the
+    /// body, or any body around it, on an open one are refused.
+    pub fn check_edit(&self, id: BodyID, edit: &BodyEdit, time: f64) -> Result<(), String> {
+        let name = &self.get_body(id).info.name;
+        if let Some(elements) = &edit.orbit {
+            if self.get_parent(id).is_none() {
+                return Err(format!("{} can't be given an orbit", name));
+            }
+            if !elements.is_elliptic() {
+                return Err(format!("{} can only be given an elliptic orbit", name));
+            }
+        }
+
+        let mut edited = self.clone();
+        edited.edit_body(id, edit, time);
+        let escaping: Vec<_> = edited
+            .bodies()
+            .filter(|b| edited.get_parent(b.id) == Some(id))
+            .filter(|b| !edited.orbit_of_body(b.id).unwrap().is_closed())
+            .map(|b| b.info.name.as_str())
+            .collect();
+        if !escaping.is_empty() {
+            return Err(format!(
+                "{} would no longer hold onto {}",
+                name,
+                escaping.join(", ")
+            ));
+        }
+        Ok(())
+    }
+
     pub fn add_fixed_body(&mut self, body_info: BodyInfo) -> BodyID {
         self.insert_new_body(body_info, None)
     }
@@ -144,6 +298,133 @@ impl Orrery {
         &self.ships[&id]
     }
 
+    pub fn set_ship_integrator(&mut self, id: ShipID, settings: IntegratorSettings) {
+        self.ships.get_mut(&id).unwrap().integrator = settings;
+    }
+
+    pub fn set_ship_propagation(&mut self, id: ShipID, propagation: Propagation) {
+        if let Propagation::Hybrid { soi_multiple } = propagation {
+            // Otherwise we'd hit the SOI before (or as) we started integrating
+            assert!(
+                soi_multiple > 1.0,
+                "Integration zone must be larger than the SOI, but multiple was {}",
+                soi_multiple
+            );
+        }
+        self.ships.get_mut(&id).unwrap().propagation = propagation;
+    }
+
+    pub fn set_ship_name(&mut self, id: ShipID, name: Option<String>) {
+        self.ships.get_mut(&id).unwrap().name = name;
+    }
+
+    pub fn set_ship_eclipse_tracking(&mut self, id: ShipID, enabled: bool) {
+        self.ships.get_mut(&id).unwrap().track_eclipses = enabled;
+    }
+
+    pub fn set_ship_line_of_sight_target(&mut self, id: ShipID, target: Option<ShipID>) {
+        assert_ne!(Some(id), target, "Ship {} can't target itself", id.0);
+        self.ships.get_mut(&id).unwrap().line_of_sight_target = target;
+    }
+
+    pub fn set_ship_color(&mut self, id: ShipID, color: Option<Point3<f32>>) {
+        self.ships.get_mut(&id).unwrap().color = color;
+    }
+
+    pub fn set_ship_delta_v_capacity(&mut self, id: ShipID, capacity: Option<f64>) {
+        self.ships.get_mut(&id).unwrap().delta_v_capacity = capacity;
+    }
+
+    pub fn set_ship_arrival(&mut self, id: ShipID, arrival: Option<Arrival>) {
+        self.ships.get_mut(&id).unwrap().arrival = arrival;
+    }
+
+    pub fn set_ship_stages(&mut self, id: ShipID, stages: Option<StageStack>) {
+        self.ships.get_mut(&id).unwrap().stages = stages;
+    }
+
+    pub fn set_ship_finite_burns(&mut self, id: ShipID, finite_burns: bool) {
+        self.ships.get_mut(&id).unwrap().finite_burns = finite_burns;
+    }
+
+    /// Adds a ship for each slot of the constellation, in circular orbits of
+    /// the given radius. Ships are named after their slots, with the given
+    /// prefix, and colored by plane.
+    pub fn add_constellation(
+        &mut self,
+        walker: &WalkerConstellation,
+        parent_id: BodyID,
+        radius: f64,
+        epoch: f64,
+        prefix: &str,
+    ) -> Vec<ShipID> {
+        walker
+            .slots(parent_id, radius, epoch)
+            .into_iter()
+            .map(|slot| {
+                let (position, velocity) = slot.placement.state(self);
+                let id = self.add_ship(position, velocity, epoch, parent_id);
+                self.set_ship_name(id, Some(slot.name(prefix)));
+                self.set_ship_color(id, Some(walker.plane_color(slot.plane)));
+                id
+            })
+            .collect()
+    }
+
+    pub fn set_ship_srp(&mut self, id: ShipID, srp: Option<SolarRadiationPressure>) {
+        self.ships.get_mut(&id).unwrap().srp = srp;
+    }
+
+    pub fn set_ship_sail(&mut self, id: ShipID, sail: Option<SolarSail>) {
+        self.ships.get_mut(&id).unwrap().sail = sail;
+    }
+
+    /// Replaces the schedule of the ship's sail. This doesn't touch any arc
+    /// that's already been integrated; to replan, use
+    /// [Timeline::set_sail_schedule](crate::model::timeline::Timeline::set_sail_schedule).
+    pub fn set_sail_schedule(&mut self, id: ShipID, schedule: SailSchedule) {
+        let ship = self.ships.get_mut(&id).unwrap();
+        match &mut ship.sail {
+            Some(sail) => sail.schedule = schedule,
+            None => panic!("Ship {} doesn't have a sail", id.0),
+        }
+    }
+
+    /// Replaces the ship's planned maneuvers. Like [Orrery::set_sail_schedule],
+    /// this doesn't change the ship's current path; to replan, use
+    /// [Timeline::set_maneuver_plan](crate::model::timeline::Timeline::set_maneuver_plan).
+    pub fn set_maneuver_plan(&mut self, id: ShipID, plan: ManeuverPlan) {
+        self.ships.get_mut(&id).unwrap().maneuvers = plan;
+    }
+
+    /// Instantaneously changes the ship's velocity at the given time, by an
+    /// amount given in its parent's inertial frame.
+    pub fn apply_impulse(&mut self, id: ShipID, delta_v: Vector3<f64>, time: f64) {
+        let ship = self.ships.get_mut(&id).unwrap();
+        assert!(!ship.has_crashed(), "Ship {} has crashed", id.0);
+
+        // Any numeric arc in progress is no longer valid
+        let state = ship.state_at_time(time);
+        let (position, velocity) = (state.position(), state.velocity() + delta_v);
+        ship.orbit = TimedOrbit::from_state(
+            CartesianState::new(ship.orbit.primary().clone(), position, velocity),
+            time,
+        );
+        ship.numeric_arc = None;
+    }
+
+    /// Moves the ship onto the given orbit, taking it off any numeric arc,
+    /// and out of the ground if it had crashed. Like
+    /// [Orrery::set_maneuver_plan], this changes the ship's whole path; to
+    /// change it partway through, use
+    /// [Timeline::set_ship_orbit](crate::model::timeline::Timeline::set_ship_orbit).
+    pub fn set_ship_orbit(&mut self, id: ShipID, orbit: TimedOrbit<Body, ()>) {
+        let ship = self.ships.get_mut(&id).unwrap();
+        ship.orbit = orbit;
+        ship.numeric_arc = None;
+        ship.impact = None;
+    }
+
     pub fn add_ship(
         &mut self,
         position: Vector3<f64>,
@@ -162,12 +443,55 @@ impl Orrery {
                 CartesianState::new(primary, position, velocity),
                 current_time,
             ),
+            integrator: IntegratorSettings::default(),
+            propagation: Propagation::default(),
+            srp: None,
+            sail: None,
+            maneuvers: ManeuverPlan::new(),
+            delta_v_capacity: None,
+            arrival: None,
+            stages: None,
+            finite_burns: false,
+            numeric_arc: None,
+            impact: None,
+            name: None,
+            color: None,
+            track_eclipses: false,
+            line_of_sight_target: None,
         };
 
         self.ships.insert(new_id, ship);
         new_id
     }
 
+    /// Takes a ship out of the orrery, returning it if it was there. Its ID
+    /// isn't given to any ship added later.
+    pub fn remove_ship(&mut self, id: ShipID) -> Option<Ship> {
+        self.ships.remove(&id)
+    }
+
+    /// Returns a copy of this orrery with the same bodies, but no ships. Ships
+    /// added to it later still get new IDs.
+    pub fn without_ships(&self) -> Orrery {
+        Orrery {
+            bodies: self.bodies.clone(),
+            next_body_id: self.next_body_id,
+            ships: HashMap::new(),
+            next_ship_id: self.next_ship_id,
+            soi_model: self.soi_model,
+            relativistic_precession: self.relativistic_precession,
+            precession_epoch: self.precession_epoch,
+        }
+    }
+
+    /// Adds a ship taken from another orrery, keeping its ID. Its orbit keeps
+    /// its own copy of its primary, so the other orrery should have the same
+    /// bodies as this one.
+    pub fn insert_ship(&mut self, ship: Ship) {
+        self.next_ship_id = usize::max(self.next_ship_id, ship.id.0 + 1);
+        self.ships.insert(ship.id, ship);
+    }
+
     pub fn convert_frames(&self, src: Frame, dst: Frame, time: f64) -> FrameTransform<f64> {
         // TODO : do this in a more clever way
         let src_to_root = self.convert_from_root(src, time).inverse();
@@ -200,6 +524,22 @@ impl Orrery {
                     }
                 }
             }
+            Frame::BodyFixed(k) => {
+                let root_to_inertial = self.convert_from_root(Frame::BodyInertial(k), time);
+
+                // Bodies rotate around their z-axis
+                let info = &self.bodies[&k].body.info;
+                let inertial_to_fixed = FrameTransform::from_active(
+                    UnitQuaternion::from_axis_angle(
+                        &Vector3::z_axis(),
+                        info.rotation_angle_at(time),
+                    ),
+                    Vector3::zeros(),
+                    Vector3::zeros(),
+                    Vector3::z() * info.angular_velocity(),
+                );
+                root_to_inertial.append_transformation(&inertial_to_fixed)
+            }
             Frame::ShipInertial(k) => {
                 let ship = &self.ships[&k];
                 let parent_frame = Frame::BodyInertial(ship.parent_id());
@@ -207,8 +547,8 @@ impl Orrery {
 
                 let parent_to_self = FrameTransform::from_active(
                     UnitQuaternion::identity(),
-                    ship.orbit.state_at_time(time).position(),
-                    ship.orbit.state_at_time(time).velocity(),
+                    ship.state_at_time(time).position(),
+                    ship.state_at_time(time).velocity(),
                     Vector3::zeros(),
                 );
                 root_to_parent.append_transformation(&parent_to_self)
@@ -218,7 +558,7 @@ impl Orrery {
                 let root_to_parent = self.convert_from_root(Frame::ShipInertial(k), time);
                 let orientation = crate::math::geometry::always_find_rotation(
                     &ship.orbit.normal_vector(),
-                    &ship.orbit.state_at_time(time).velocity(),
+                    &ship.state_at_time(time).velocity(),
                     1e-20,
                 );
                 let parent_to_self = FrameTransform::from_active(
@@ -255,15 +595,88 @@ impl Orrery {
 
         FramedState {
             orrery: self,
-            position: Point3::from(ship.orbit.state_at_time(time).position()),
-            velocity: ship.orbit.state_at_time(time).velocity(),
+            position: Point3::from(ship.state_at_time(time).position()),
+            velocity: ship.state_at_time(time).velocity(),
             native_frame: Frame::BodyInertial(ship.parent_id()),
         }
     }
 
+    /// Returns the state of every body and ship at the given time, in the given
+    /// frame. Cheaper than asking for each one separately, since each body's
+    /// position is only worked out once, instead of once per descendant.
+    pub fn snapshot(&self, time: f64, frame: Frame) -> SystemSnapshot {
+        // Inertial frames don't rotate, so the state relative to the root is
+        // just the sum of the states along the chain of parents.
+        let mut root_states = HashMap::new();
+        for id in self.bodies.keys() {
+            self.body_root_state(*id, time, &mut root_states);
+        }
+
+        let root_to_dst = self.convert_from_root(frame, time);
+        let to_dst = |(position, velocity): (Vector3<f64>, Vector3<f64>)| {
+            let position = Point3::from(position);
+            ObjectState {
+                position: root_to_dst.convert_point(&position),
+                velocity: root_to_dst.convert_velocity(&position, &velocity),
+            }
+        };
+
+        let ships = self
+            .ships
+            .values()
+            .map(|ship| {
+                let (parent_p, parent_v) = root_states[&ship.parent_id()];
+                let state = ship.state_at_time(time);
+                let root_state = (parent_p + state.position(), parent_v + state.velocity());
+                (ship.id, to_dst(root_state))
+            })
+            .collect();
+        let bodies = root_states
+            .into_iter()
+            .map(|(id, root_state)| (id, to_dst(root_state)))
+            .collect();
+
+        SystemSnapshot {
+            time,
+            frame,
+            bodies,
+            ships,
+        }
+    }
+
+    fn body_root_state(
+        &self,
+        id: BodyID,
+        time: f64,
+        cache: &mut HashMap<BodyID, (Vector3<f64>, Vector3<f64>)>,
+    ) -> (Vector3<f64>, Vector3<f64>) {
+        if let Some(state) = cache.get(&id) {
+            return *state;
+        }
+
+        let root_state = match &self.bodies[&id].orbit {
+            None => (Vector3::zeros(), Vector3::zeros()),
+            Some(orbit) => {
+                let (parent_p, parent_v) = self.body_root_state(orbit.primary().id, time, cache);
+                let state = orbit.state_at_time(time);
+                (parent_p + state.position(), parent_v + state.velocity())
+            }
+        };
+        cache.insert(id, root_state);
+        root_state
+    }
+
     pub fn get_soi_radius(&self, id: BodyID) -> Option<f64> {
         let orbit = self.bodies[&id].two_body_orbit()?;
-        Some(orbit.soi_radius())
+        let radius = match self.soi_model {
+            SoiModel::Laplace => orbit.soi_radius(),
+            SoiModel::Hill => orbit.hill_radius(),
+            SoiModel::Fixed => match orbit.secondary().info.soi_radius {
+                Some(r) => r,
+                None => orbit.soi_radius(),
+            },
+        };
+        Some(radius)
     }
 
     pub fn change_soi(&mut self, ship_id: ShipID, new_parent_id: BodyID, event_time: f64) {
@@ -275,8 +688,8 @@ impl Orrery {
         let old_parent_id = ship.parent_id();
         let state = FramedState {
             orrery: self,
-            position: Point3::from(ship.orbit.state_at_time(event_time).position()),
-            velocity: ship.orbit.state_at_time(event_time).velocity(),
+            position: Point3::from(ship.state_at_time(event_time).position()),
+            velocity: ship.state_at_time(event_time).velocity(),
             native_frame: Frame::BodyInertial(old_parent_id),
         };
 
@@ -292,7 +705,8 @@ impl Orrery {
                 new_velocity,
             ),
             event_time,
-        );
+        )
+        .normalized();
         println!(
             "Rerooted ship {} from {} to {}",
             ship_id.0,
@@ -301,6 +715,83 @@ impl Orrery {
         );
     }
 
+    /// Rebases the epochs of all orbits in this orrery to the given time. This
+    /// doesn't change any positions, but keeps long-running simulations from
+    /// accumulating large time offsets. See [TimedOrbit::rebase_epoch].
+    pub fn rebase_epochs(&mut self, time: f64) {
+        for orbit in self.bodies.values_mut().filter_map(|b| b.orbit.as_mut()) {
+            orbit.rebase_epoch(time);
+        }
+        for ship in self.ships.values_mut() {
+            ship.orbit.rebase_epoch(time);
+        }
+    }
+
+    /// Returns a copy of this orrery with time running backwards: at
+    /// `reversed_time`, everything is where it is in this one at `time`, but
+    /// moving the other way. More generally, the copy's state at
+    /// `reversed_time + dt` mirrors this one's state at `time - dt`, so
+    /// reversing the copy gets back the original orbits.
+    ///
+    /// Only conics can be run backwards, so ships on numeric arcs are put on
+    /// their osculating orbits, and every ship is switched to
+    /// [Propagation::PatchedConics]. Crashed ships stay where they are, and
+    /// planned maneuvers are dropped.
+    pub fn time_reversed(&self, time: f64, reversed_time: f64) -> Orrery {
+        let reverse = |orbit: &TimedOrbit<Body, ()>, state: CartesianState<&Body>| {
+            TimedOrbit::from_state(
+                CartesianState::new(orbit.primary().clone(), state.position(), -state.velocity()),
+                reversed_time,
+            )
+        };
+
+        let mut reversed = self.clone();
+        for body in reversed.bodies.values_mut() {
+            if let Some(orbit) = body.orbit.as_mut() {
+                *orbit = reverse(orbit, orbit.state_at_time(time));
+            }
+        }
+        for ship in reversed.ships.values_mut().filter(|s| !s.has_crashed()) {
+            ship.orbit = reverse(&ship.orbit, ship.state_at_time(time));
+            ship.numeric_arc = None;
+            ship.propagation = Propagation::PatchedConics;
+            ship.maneuvers = ManeuverPlan::new();
+        }
+        reversed.precession_epoch = self.precession_epoch.map(|_| reversed_time);
+        reversed
+    }
+
+    /// Applies the relativistic precession that has accumulated since this was
+    /// last called, by rotating every orbit about its normal. The first call
+    /// just sets the starting point.
+    ///
+    /// This is a secular correction, applied in discrete steps, so it's only
+    /// as fine-grained as the calls to this function. The timeline calls it at
+    /// every event.
+    pub fn advance_precession(&mut self, time: f64) {
+        let elapsed = match self.precession_epoch.replace(time) {
+            Some(epoch) => time - epoch,
+            None => return,
+        };
+        if !self.relativistic_precession || elapsed == 0.0 {
+            return;
+        }
+
+        for orbit in self.bodies.values_mut().filter_map(|b| b.orbit.as_mut()) {
+            let angle = orbit.relativistic_precession_rate() * elapsed;
+            orbit.advance_periapsis(angle, time);
+        }
+        // Orbits of ships on numeric arcs aren't meaningful, so leave them alone
+        for ship in self
+            .ships
+            .values_mut()
+            .filter(|s| !s.has_crashed() && s.numeric_arc.is_none())
+        {
+            let angle = ship.orbit.relativistic_precession_rate() * elapsed;
+            ship.orbit.advance_periapsis(angle, time);
+        }
+    }
+
     pub fn process_event(&mut self, event: &Event) {
         // Dispatch to the appropriate handler
         let ship_id = event.ship_id;
@@ -308,9 +799,70 @@ impl Orrery {
             EventData::EnteringSOI(soi_change) | EventData::ExitingSOI(soi_change) => {
                 self.change_soi(ship_id, soi_change.new, event.point.time);
             }
+            EventData::Impact(_) => {
+                self.crash_ship(ship_id, event.point.location, event.point.time);
+            }
+            // Without drag, the atmosphere doesn't affect the ship, and apses are
+            // just markers
+            EventData::EnteringAtmosphere(_)
+            | EventData::ExitingAtmosphere(_)
+            | EventData::Periapsis(_)
+            | EventData::Apoapsis(_)
+            | EventData::EnteringShadow(_)
+            | EventData::ExitingShadow(_)
+            | EventData::EnteringEclipse(_)
+            | EventData::ExitingEclipse(_)
+            | EventData::LosingSight(_)
+            | EventData::RegainingSight(_)
+            | EventData::Staging(_) => {}
+            EventData::EnteringIntegrationZone(body_id) => {
+                // Entering the parent's zone means integrating around the parent alone
+                let perturber_id =
+                    Some(*body_id).filter(|id| *id != self.ships[&ship_id].parent_id());
+                self.start_numeric_arc(ship_id, perturber_id, event.point.time);
+            }
+            // The timeline makes the change itself, since it's not in the orrery
+            EventData::Modification => {}
+            EventData::SailAttitudeChange => {
+                // The rest of the arc was integrated with the old attitude
+                let parent_id = self.ships[&ship_id].parent_id();
+                self.end_numeric_arc(ship_id, parent_id, event.point.time);
+                if !self.ships[&ship_id].has_crashed() {
+                    self.start_numeric_arc(ship_id, None, event.point.time);
+                }
+            }
+            EventData::Maneuver => {
+                let ship = &self.ships[&ship_id];
+                let maneuver = *ship
+                    .maneuvers
+                    .maneuver_at(event.point.time)
+                    .expect("Maneuver event with no maneuver planned");
+                if ship.makes_finite_burns() {
+                    self.start_burn_arc(ship_id, maneuver);
+                } else {
+                    self.apply_impulse(ship_id, maneuver.delta_v, maneuver.time);
+                }
+            }
+            EventData::ExitingIntegrationZone(soi_change) => {
+                self.end_numeric_arc(ship_id, soi_change.new, event.point.time);
+
+                // Numeric ships go straight into the next arc
+                let ship = &self.ships[&ship_id];
+                if matches!(ship.propagation, Propagation::Numeric { .. }) && !ship.has_crashed() {
+                    self.start_numeric_arc(ship_id, None, event.point.time);
+                }
+            }
         }
     }
 
+    /// Undoes [process_event](Self::process_event), for events that only
+    /// change things we can get back from the event itself.
+    ///
+    /// Panics for events that replace the ship's orbit or numeric arc
+    /// (leaving an integration zone, maneuvers, sail attitude changes and
+    /// edits),
+    /// since the old one isn't kept. To go back past those, use an earlier
+    /// copy of the orrery instead.
     pub fn revert_event(&mut self, event: &Event) {
         // Dispatch to the appropriate handler
         let ship_id = event.ship_id;
@@ -318,6 +870,584 @@ impl Orrery {
             EventData::EnteringSOI(soi_change) | EventData::ExitingSOI(soi_change) => {
                 self.change_soi(ship_id, soi_change.old, event.point.time);
             }
+            EventData::Impact(_) => {
+                self.ships.get_mut(&ship_id).unwrap().impact = None;
+            }
+            EventData::EnteringAtmosphere(_)
+            | EventData::ExitingAtmosphere(_)
+            | EventData::Periapsis(_)
+            | EventData::Apoapsis(_)
+            | EventData::EnteringShadow(_)
+            | EventData::ExitingShadow(_)
+            | EventData::EnteringEclipse(_)
+            | EventData::ExitingEclipse(_)
+            | EventData::LosingSight(_)
+            | EventData::RegainingSight(_)
+            | EventData::Staging(_) => {}
+            EventData::EnteringIntegrationZone(_) => {
+                self.ships.get_mut(&ship_id).unwrap().numeric_arc = None;
+            }
+            EventData::ExitingIntegrationZone(_)
+            | EventData::Maneuver
+            | EventData::SailAttitudeChange
+            | EventData::Modification => {
+                panic!(
+                    "Can't revert {:?}, the ship's old path isn't kept",
+                    event.data
+                )
+            }
+        }
+    }
+
+    /// Integrates the ship's path, starting at the given time, under the
+    /// gravity of its parent, and of the perturbing body if there is one. Solar
+    /// radiation pressure and sails are included if the ship has them.
+    ///
+    /// With a perturber, the arc ends when the ship leaves the perturber's
+    /// integration zone, hits the surface of either body, or after one orbit of
+    /// the perturber (at which point we assume it's been captured). Without
+    /// one, the arc ends when the ship changes SOI, hits the surface, or after
+    /// the ship's maximum arc duration, or when its sail changes attitude.
+    fn start_numeric_arc(&mut self, ship_id: ShipID, perturber_id: Option<BodyID>, time: f64) {
+        let ship = &self.ships[&ship_id];
+        let parent = ship.orbit.primary();
+
+        let perturber_info = perturber_id.map(|perturber_id| {
+            let soi_multiple = match ship.propagation {
+                Propagation::Hybrid { soi_multiple } => soi_multiple,
+                _ => panic!("Ship {} isn't using hybrid propagation", ship_id.0),
+            };
+            let orbit = self
+                .orbit_of_body(perturber_id)
+                .expect("Perturbing body should be orbiting our parent");
+            let zone_radius = soi_multiple
+                * self
+                    .get_soi_radius(perturber_id)
+                    .expect("Orbiting bodies should have an SOI");
+            (orbit, zone_radius)
+        });
+        let max_time = match (&perturber_info, ship.propagation) {
+            (Some((orbit, _)), _) => {
+                time + orbit
+                    .period()
+                    .expect("Orbiting bodies should have closed orbits")
+            }
+            (None, Propagation::Numeric { max_arc_duration }) => time + max_arc_duration,
+            (None, _) => panic!("Ship {} isn't using numeric propagation", ship_id.0),
+        };
+        // Numeric ships don't integrate across a change in the sail's attitude
+        let max_time = match ship
+            .sail
+            .as_ref()
+            .filter(|_| perturber_info.is_none())
+            .and_then(|sail| sail.schedule.next_change_after(time))
+        {
+            Some(change_time) => f64::min(max_time, change_time),
+            None => max_time,
+        };
+
+        // For numeric ships, the arc stops at any SOI boundary
+        let parent_soi = self.get_soi_radius(parent.id);
+        let children: Vec<_> = self
+            .body_orbits()
+            .filter(|orbit| orbit.primary().id == parent.id)
+            .map(|orbit| {
+                let soi_radius = self
+                    .get_soi_radius(orbit.secondary().id)
+                    .expect("Orbiting bodies should have an SOI");
+                (orbit, soi_radius)
+            })
+            .collect();
+
+        // We work in the parent's inertial frame. Since the perturber also pulls on
+        // the parent, that frame is accelerating, which we have to correct for.
+        let accel = |t: f64, s: &PhaseState| {
+            let mut accel = -parent.mu() / s.position.norm().powi(3) * s.position;
+            if let Some((perturber_orbit, _)) = &perturber_info {
+                let perturber_mu = perturber_orbit.secondary().mu();
+                let perturber_position = perturber_orbit.state_at_time(t).position();
+                let displacement = s.position - perturber_position;
+                accel -= perturber_mu / displacement.norm().powi(3) * displacement
+                    + perturber_mu / perturber_position.norm().powi(3) * perturber_position;
+            }
+            if ship.is_sunlight_sensitive() {
+                accel += self.sunlight_acceleration(ship, parent.id, s, t);
+            }
+            accel
+        };
+        let stop = |t: f64, s: &PhaseState| {
+            if s.position.norm() < parent.info.radius as f64 {
+                return true;
+            }
+
+            match &perturber_info {
+                Some((perturber_orbit, zone_radius)) => {
+                    let perturber_state = perturber_orbit.state_at_time(t);
+                    let displacement = s.position - perturber_state.position();
+                    let rel_velocity = s.velocity - perturber_state.velocity();
+                    let is_leaving =
+                        displacement.norm() > *zone_radius && displacement.dot(&rel_velocity) > 0.0;
+                    let has_crashed =
+                        displacement.norm() < perturber_orbit.secondary().info.radius as f64;
+                    is_leaving || has_crashed
+                }
+                None => {
+                    let has_escaped = parent_soi.is_some_and(|soi| s.position.norm() > soi);
+                    let has_encountered = children.iter().any(|(orbit, soi_radius)| {
+                        (s.position - orbit.state_at_time(t).position()).norm() < *soi_radius
+                    });
+                    has_escaped || has_encountered
+                }
+            }
+        };
+
+        let start_state = ship.state_at_time(time);
+        let path = propagate_until(
+            ship.integrator.build().as_mut(),
+            &accel,
+            time,
+            PhaseState::new(start_state.position(), start_state.velocity()),
+            max_time,
+            stop,
+        );
+        let near = match &perturber_info {
+            Some((orbit, _)) => &orbit.secondary().info.name,
+            None => &parent.info.name,
+        };
+        println!(
+            "Integrated ship {} near {} from {} to {}",
+            ship_id.0,
+            near,
+            time,
+            path.end_time()
+        );
+        let energy_drift = arc_energy_drift(ship, &accel, &path);
+
+        self.ships.get_mut(&ship_id).unwrap().numeric_arc = Some(NumericArc {
+            perturber: perturber_id,
+            burn: None,
+            path,
+            energy_drift,
+        });
+    }
+
+    /// Integrates the ship's path through one of its planned burns, made with
+    /// the engines of its current stage at full thrust, pointed along the
+    /// burn's delta-v the whole time. Each stage is dropped as it runs dry,
+    /// and the next one takes over. The arc ends when the burn's done, or the
+    /// last stage runs dry, and the ship goes back to following a conic,
+    /// around whatever body it's in the SOI of by then.
+    ///
+    /// The arc also stops if the ship hits the surface. If the ship's next
+    /// burn comes before this one's done, that one takes over from there, and
+    /// the rest of this one isn't made.
+    fn start_burn_arc(&mut self, ship_id: ShipID, maneuver: Maneuver) {
+        let ship = &self.ships[&ship_id];
+        let parent = ship.orbit.primary();
+        let time = maneuver.time;
+
+        let delta_v = maneuver.delta_v.norm();
+        let stages = ship
+            .stages_before_burn(time)
+            .expect("Finite burn without stages");
+        let end_time = time + stages.clone().burn(delta_v).duration;
+        if end_time <= time {
+            return;
+        }
+        let direction = maneuver.delta_v / delta_v;
+
+        let accel = |t: f64, s: &PhaseState| {
+            let mut accel = -parent.mu() / s.position.norm().powi(3) * s.position;
+            let (thrust, mass) = stages.firing_after(t - time);
+            accel += thrust / mass * direction;
+            if ship.is_sunlight_sensitive() {
+                accel += self.sunlight_acceleration(ship, parent.id, s, t);
+            }
+            accel
+        };
+        let stop = |_: f64, s: &PhaseState| s.position.norm() < parent.info.radius as f64;
+
+        let start_state = ship.state_at_time(time);
+        let path = propagate_until(
+            ship.integrator.build().as_mut(),
+            &accel,
+            time,
+            PhaseState::new(start_state.position(), start_state.velocity()),
+            end_time,
+            stop,
+        );
+        println!(
+            "Integrated ship {}'s burn near {} from {} to {}",
+            ship_id.0,
+            parent.info.name,
+            time,
+            path.end_time()
+        );
+        let energy_drift = arc_energy_drift(ship, &accel, &path);
+
+        self.ships.get_mut(&ship_id).unwrap().numeric_arc = Some(NumericArc {
+            perturber: None,
+            burn: Some(maneuver),
+            path,
+            energy_drift,
+        });
+    }
+
+    /// Acceleration due to sunlight, from radiation pressure on the ship and
+    /// its sail, given its state relative to its parent. The parent can block
+    /// the Sun, but other bodies are ignored.
+    fn sunlight_acceleration(
+        &self,
+        ship: &Ship,
+        parent_id: BodyID,
+        state: &PhaseState,
+        time: f64,
+    ) -> Vector3<f64> {
+        let sun_to_ship = state.position - self.sun_position(parent_id, time);
+        let illumination = match self.shadow_depth(parent_id, &state.position, time) {
+            Some(depth) if depth > 0.0 => 0.0,
+            _ => 1.0,
+        };
+
+        let mut accel = Vector3::zeros();
+        if let Some(srp) = &ship.srp {
+            accel += srp.acceleration(&sun_to_ship, illumination);
+        }
+        if let Some(sail) = &ship.sail {
+            accel += sail.acceleration(&sun_to_ship, &state.velocity, illumination, time);
+        }
+        accel
+    }
+
+    /// Position of the Sun (the root of the body tree), in the given body's
+    /// inertial frame.
+    fn sun_position(&self, body_id: BodyID, time: f64) -> Vector3<f64> {
+        self.convert_frames(Frame::Root, Frame::BodyInertial(body_id), time)
+            .convert_point(&Point3::origin())
+            .coords
+    }
+
+    /// How deep a point, relative to the given body, is in that body's shadow
+    /// (see [shadow_depth]). Returns `None` for the Sun itself, which doesn't
+    /// cast one.
+    pub fn shadow_depth(&self, body_id: BodyID, position: &Vector3<f64>, time: f64) -> Option<f64> {
+        self.get_parent(body_id)?;
+        let radius = self.bodies[&body_id].body.info.radius as f64;
+        Some(shadow_depth(
+            position,
+            &self.sun_position(body_id, time),
+            radius,
+        ))
+    }
+
+    /// How deep a point, relative to the given body, is in one part of that
+    /// body's shadow, treating the Sun as a disc (see [cone_shadow_depth]).
+    /// Returns `None` for the Sun itself.
+    pub fn eclipse_depth(
+        &self,
+        body_id: BodyID,
+        position: &Vector3<f64>,
+        time: f64,
+        region: ShadowRegion,
+    ) -> Option<f64> {
+        self.get_parent(body_id)?;
+        let sun_radius = self
+            .bodies
+            .values()
+            .find(|b| b.orbit.is_none())
+            .map_or(0.0, |b| b.body.info.radius as f64);
+        Some(cone_shadow_depth(
+            position,
+            &self.sun_position(body_id, time),
+            self.bodies[&body_id].body.info.radius as f64,
+            sun_radius,
+            region,
+        ))
+    }
+
+    /// Fits a conic, around the given body, to the end of the ship's numeric
+    /// arc. If the arc ended at the surface, the ship crashes there instead.
+    fn end_numeric_arc(&mut self, ship_id: ShipID, new_parent_id: BodyID, time: f64) {
+        self.change_soi(ship_id, new_parent_id, time);
+        let ship = self.ships.get_mut(&ship_id).unwrap();
+        ship.numeric_arc = None;
+
+        let position = ship.orbit.state_at_time(time).position();
+        if position.norm() < ship.orbit.primary().info.radius as f64 {
+            self.crash_ship(ship_id, Point3::from(position), time);
+        }
+    }
+
+    /// Stops the ship at the given location (in its parent's inertial frame),
+    /// and attaches it to the surface of its parent.
+    pub fn crash_ship(&mut self, ship_id: ShipID, location: Point3<f64>, time: f64) {
+        let ship = &self.ships[&ship_id];
+        let parent_id = ship.parent_id();
+        let surface_position = self
+            .convert_frames(
+                Frame::BodyInertial(parent_id),
+                Frame::BodyFixed(parent_id),
+                time,
+            )
+            .convert_point(&location);
+
+        let ship = self.ships.get_mut(&ship_id).unwrap();
+        ship.impact = Some(Impact {
+            time,
+            surface_position: surface_position.coords,
+        });
+        println!(
+            "Ship {} crashed into {}",
+            ship_id.0, self.bodies[&parent_id].body.info.name,
+        );
+    }
+}
+
+/// Measures the energy drift along an arc the ship's just been integrated
+/// along, with the given acceleration, and complains if it's too high.
+fn arc_energy_drift(
+    ship: &Ship,
+    accel: &dyn Fn(f64, &PhaseState) -> Vector3<f64>,
+    path: &SampledPath,
+) -> Option<EnergyDrift> {
+    let mu = ship.orbit.primary().mu();
+    let drift = measure_energy_drift(&ship.integrator, mu, accel, path)?;
+    if let Some(recommendation) = drift.recommendation {
+        println!(
+            "Energy drift for ship {} is {:.1e}/rev, too high with {}; try {}",
+            ship.id.0, drift.per_revolution, ship.integrator, recommendation
+        );
+    }
+    Some(drift)
+}
+
+impl FromStr for SoiModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "laplace" => Ok(SoiModel::Laplace),
+            "hill" => Ok(SoiModel::Hill),
+            "fixed" => Ok(SoiModel::Fixed),
+            _ => Err(format!(
+                "Unknown SOI model {:?}; expected laplace, hill, or fixed",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for SoiModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            SoiModel::Laplace => "laplace",
+            SoiModel::Hill => "hill",
+            SoiModel::Fixed => "fixed",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_body_fixed_frame() {
+        let mut orrery = Orrery::new();
+        let id = orrery.add_fixed_body(BodyInfo {
+            name: String::from("Spinny"),
+            mu: 1.0,
+            radius: 10.0,
+            color: Point3::new(1.0, 1.0, 1.0),
+            soi_radius: None,
+            rotation_period: Some(100.0),
+            initial_rotation: PI / 2.0,
+            atmosphere_height: None,
+            texture: None,
+            rings: None,
+        });
+        let inertial = Frame::BodyInertial(id);
+        let fixed = Frame::BodyFixed(id);
+
+        // At time zero, the body has rotated a quarter turn, so its x-axis points
+        // along inertial y.
+        let transform = orrery.convert_frames(fixed, inertial, 0.0);
+        assert_relative_eq!(
+            transform.convert_point(&Point3::new(10.0, 0.0, 0.0)),
+            Point3::new(0.0, 10.0, 0.0),
+            epsilon = 1e-12
+        );
+
+        // A point on the surface moves along with the surface
+        let omega = 2.0 * PI / 100.0;
+        assert_relative_eq!(
+            transform.convert_velocity(&Point3::new(10.0, 0.0, 0.0), &Vector3::zeros()),
+            Vector3::new(-10.0 * omega, 0.0, 0.0),
+            epsilon = 1e-12
+        );
+
+        // After another quarter period, x points along inertial -x.
+        let transform = orrery.convert_frames(fixed, inertial, 25.0);
+        assert_relative_eq!(
+            transform.convert_point(&Point3::new(10.0, 0.0, 0.0)),
+            Point3::new(-10.0, 0.0, 0.0),
+            epsilon = 1e-12
+        );
+
+        // And a point fixed in inertial space drifts backwards in the fixed frame.
+        let transform = orrery.convert_frames(inertial, fixed, 25.0);
+        assert_relative_eq!(
+            transform.convert_velocity(&Point3::new(0.0, 10.0, 0.0), &Vector3::zeros()),
+            Vector3::new(-10.0 * omega, 0.0, 0.0),
+            epsilon = 1e-12
+        );
+    }
+
+    #[test]
+    fn test_relativistic_precession() {
+        let sun_mu = 1.32712440018e20;
+        let info = |name: &str, mu: f64| BodyInfo {
+            name: String::from(name),
+            mu,
+            radius: 1.0,
+            color: Point3::new(1.0, 1.0, 1.0),
+            soi_radius: None,
+            rotation_period: None,
+            initial_rotation: 0.0,
+            atmosphere_height: None,
+            texture: None,
+            rings: None,
+        };
+
+        let mut orrery = Orrery::new();
+        let sun = orrery.add_fixed_body(info("Sun", sun_mu));
+        let orbit = Orbit::from_kepler(
+            PointMass::with_mu(sun_mu),
+            (),
+            57_909_050_000.0,
+            0.2056,
+            0.0,
+            0.0,
+            0.0,
+        );
+        let mercury = orrery.add_body(info("Mercury", 2.2032e13), orbit, 0.0, sun);
+        let periapse_vector =
+            |orrery: &Orrery| orrery.orbit_of_body(mercury).unwrap().periapse_vector();
+        let initial = periapse_vector(&orrery);
+
+        // Nothing happens unless it's turned on
+        let century = 100.0 * 365.25 * 86400.0;
+        let mut disabled = orrery.clone();
+        disabled.advance_precession(0.0);
+        disabled.advance_precession(century);
+        assert_eq!(periapse_vector(&disabled), initial);
+
+        orrery.set_relativistic_precession(true);
+        orrery.advance_precession(0.0);
+        orrery.advance_precession(century);
+        let arcseconds = initial.angle(&periapse_vector(&orrery)).to_degrees() * 3600.0;
+        assert_relative_eq!(arcseconds, 43.0, epsilon = 0.1);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut orrery = crate::file::read_file("ksp-bodies.txt");
+        let kerbin = BodyID(4);
+        let mun = BodyID(5);
+        orrery.add_ship(Vector3::x() * 400000.0, Vector3::y() * 300.0, 0.0, mun);
+        orrery.add_ship(Vector3::x() * 800000.0, Vector3::y() * 2300.0, 0.0, kerbin);
+
+        // Matches what we'd get by asking about each object separately
+        let time = 12345.0;
+        for frame in [
+            Frame::Root,
+            Frame::BodyInertial(mun),
+            Frame::BodyFixed(kerbin),
+        ] {
+            let snapshot = orrery.snapshot(time, frame);
+            for body in orrery.bodies() {
+                let state = orrery.get_body_state(body.id, time);
+                let snapped = snapshot.body(body.id);
+                assert_relative_eq!(
+                    snapped.position,
+                    state.get_position(frame, time),
+                    max_relative = 1e-9,
+                    epsilon = 1e-3
+                );
+                assert_relative_eq!(
+                    snapped.velocity,
+                    state.get_velocity(frame, time),
+                    max_relative = 1e-9,
+                    epsilon = 1e-6
+                );
+            }
+            for ship in orrery.ships() {
+                let state = orrery.get_ship_state(ship.id, time);
+                let snapped = snapshot.ship(ship.id);
+                assert_relative_eq!(
+                    snapped.position,
+                    state.get_position(frame, time),
+                    max_relative = 1e-9,
+                    epsilon = 1e-3
+                );
+                assert_relative_eq!(
+                    snapped.velocity,
+                    state.get_velocity(frame, time),
+                    max_relative = 1e-9,
+                    epsilon = 1e-6
+                );
+            }
         }
     }
+
+    #[test]
+    fn test_edit_body() {
+        let mut orrery = crate::file::read_file("ksp-bodies.txt");
+        let mun = BodyID(5);
+        let mun_info = orrery.get_body(mun).info.clone();
+        let ship = orrery.add_ship(Vector3::x() * 400000.0, Vector3::y() * 300.0, 0.0, mun);
+        let old_soi = orrery.get_soi_radius(mun).unwrap();
+        let old_period = orrery.orbit_of_ship(ship).period().unwrap();
+
+        let time = 1000.0;
+        let position = orrery
+            .get_ship_state(ship, time)
+            .get_position(Frame::Root, time);
+        orrery.edit_body(
+            mun,
+            &BodyEdit {
+                mu: Some(2.0 * mun_info.mu),
+                ..Default::default()
+            },
+            time,
+        );
+        assert_eq!(orrery.get_body(mun).info.mu, 2.0 * mun_info.mu);
+        assert_eq!(orrery.get_body(mun).info.radius, mun_info.radius);
+
+        // The ship stays where it was, but orbits faster
+        assert_relative_eq!(
+            orrery
+                .get_ship_state(ship, time)
+                .get_position(Frame::Root, time),
+            position,
+            max_relative = 1e-9
+        );
+        assert_eq!(
+            orrery.orbit_of_ship(ship).primary().info.mu,
+            2.0 * mun_info.mu
+        );
+        assert!(orrery.orbit_of_ship(ship).period().unwrap() < old_period);
+
+        // And the Laplace sphere grows as (m/M)^(2/5)
+        assert_relative_eq!(
+            orrery.get_soi_radius(mun).unwrap(),
+            old_soi * 2f64.powf(0.4),
+            max_relative = 1e-9
+        );
+    }
 }
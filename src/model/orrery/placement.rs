@@ -0,0 +1,206 @@
+use std::f64::consts::TAU;
+
+use nalgebra::Vector3;
+
+use super::{Body, BodyID, Orrery};
+use crate::astro::{HasMass, Orbit, TimedOrbit};
+
+/// The Keplerian elements of an elliptic orbit, along with where on it the
+/// object is at some epoch, which is kept separately.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitElements {
+    pub semimajor_axis: f64,
+    pub eccentricity: f64,
+    /// Angles are in radians.
+    pub inclination: f64,
+    pub long_asc_node: f64,
+    pub arg_periapsis: f64,
+    /// The mean anomaly at the epoch, in [0, 2pi).
+    pub mean_anomaly: f64,
+}
+
+impl OrbitElements {
+    /// Returns the elements of the given orbit, with the mean anomaly at the
+    /// given time, or `None` if it's not elliptic.
+    pub fn of_orbit<P: HasMass, S>(orbit: &TimedOrbit<P, S>, time: f64) -> Option<Self> {
+        let period = orbit.period()?;
+        let elements = orbit.elements();
+        Some(Self {
+            semimajor_axis: elements.semimajor_axis,
+            eccentricity: elements.eccentricity,
+            inclination: elements.inclination,
+            long_asc_node: elements.long_asc_node,
+            arg_periapsis: elements.arg_periapse,
+            mean_anomaly: (TAU * (time - orbit.time_at_periapsis()) / period).rem_euclid(TAU),
+        })
+    }
+
+    pub fn is_elliptic(&self) -> bool {
+        self.semimajor_axis > 0.0 && (0.0..1.0).contains(&self.eccentricity)
+    }
+
+    /// Returns the orbit around the given primary with these elements, where
+    /// the object is at the mean anomaly at the given epoch.
+    pub fn orbit<P: HasMass>(&self, primary: P, epoch: f64) -> TimedOrbit<P, ()> {
+        assert!(self.is_elliptic(), "Orbit elements must be elliptic");
+        let orbit = Orbit::from_kepler(
+            primary,
+            (),
+            self.semimajor_axis,
+            self.eccentricity,
+            self.inclination,
+            self.long_asc_node,
+            self.arg_periapsis,
+        );
+        // M = 2pi/P (t - t_periapse)
+        let time_since_periapsis = self.mean_anomaly * orbit.period().unwrap() / TAU;
+        TimedOrbit::from_orbit(orbit, epoch - time_since_periapsis)
+    }
+}
+
+/// Where to put a new ship, given as Keplerian elements around a body. Unlike
+/// a position and velocity, these can each be tweaked independently, which
+/// makes them handy for previewing a ship before committing to it.
+///
+/// Only elliptic orbits are supported.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShipPlacement {
+    pub parent_id: BodyID,
+    pub semimajor_axis: f64,
+    pub eccentricity: f64,
+    /// Angles are in radians.
+    pub inclination: f64,
+    pub long_asc_node: f64,
+    pub arg_periapsis: f64,
+    /// The mean anomaly at the epoch, in [0, 2pi).
+    pub mean_anomaly: f64,
+    /// The time at which the ship is placed.
+    pub epoch: f64,
+}
+
+impl ShipPlacement {
+    /// An equatorial circular orbit of the given radius, starting on the
+    /// reference direction.
+    pub fn circular(parent_id: BodyID, radius: f64, epoch: f64) -> Self {
+        Self {
+            parent_id,
+            semimajor_axis: radius,
+            eccentricity: 0.0,
+            inclination: 0.0,
+            long_asc_node: 0.0,
+            arg_periapsis: 0.0,
+            mean_anomaly: 0.0,
+            epoch,
+        }
+    }
+
+    /// Sets the mean anomaly at the epoch, wrapping it into [0, 2pi).
+    pub fn set_mean_anomaly(&mut self, mean_anomaly: f64) {
+        self.mean_anomaly = mean_anomaly.rem_euclid(TAU);
+    }
+
+    /// Returns the elements of the orbit the ship would be on.
+    pub fn elements(&self) -> OrbitElements {
+        OrbitElements {
+            semimajor_axis: self.semimajor_axis,
+            eccentricity: self.eccentricity,
+            inclination: self.inclination,
+            long_asc_node: self.long_asc_node,
+            arg_periapsis: self.arg_periapsis,
+            mean_anomaly: self.mean_anomaly,
+        }
+    }
+
+    /// Returns the orbit the ship would be on.
+    pub fn orbit(&self, orrery: &Orrery) -> TimedOrbit<Body, ()> {
+        let parent = orrery.get_body(self.parent_id).clone();
+        self.elements().orbit(parent, self.epoch)
+    }
+
+    /// Returns the position and velocity of the ship at the epoch, relative to
+    /// its parent.
+    pub fn state(&self, orrery: &Orrery) -> (Vector3<f64>, Vector3<f64>) {
+        let orbit = self.orbit(orrery);
+        let state = orbit.state_at_time(self.epoch);
+        (state.position(), state.velocity())
+    }
+
+    /// Moves the epoch to the given time, without changing the orbit, so that
+    /// the ship stays where it would have been then.
+    pub fn move_epoch(&mut self, orrery: &Orrery, epoch: f64) {
+        let period = self.orbit(orrery).period().unwrap();
+        self.set_mean_anomaly(self.mean_anomaly + TAU * (epoch - self.epoch) / period);
+        self.epoch = epoch;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::file::read_file;
+
+    const KERBIN: BodyID = BodyID(4);
+
+    #[test]
+    fn test_anomaly_moves_along_orbit() {
+        let orrery = read_file("ksp-bodies.txt");
+        let mut placement = ShipPlacement {
+            eccentricity: 0.2,
+            arg_periapsis: 1.0,
+            ..ShipPlacement::circular(KERBIN, 1e6, 1000.0)
+        };
+
+        // Mean anomaly zero is at periapsis, and pi at apoapsis
+        let (position, _) = placement.state(&orrery);
+        assert_relative_eq!(position.norm(), 0.8e6, max_relative = 1e-9);
+        placement.set_mean_anomaly(3.0 * PI);
+        assert_relative_eq!(placement.mean_anomaly, PI);
+        let (position, _) = placement.state(&orrery);
+        assert_relative_eq!(position.norm(), 1.2e6, max_relative = 1e-9);
+
+        // Changing the anomaly doesn't change the orbit's shape
+        let orbit = placement.orbit(&orrery);
+        assert_relative_eq!(orbit.semimajor_axis(), 1e6, max_relative = 1e-9);
+        assert_relative_eq!(orbit.eccentricity(), 0.2, max_relative = 1e-9);
+
+        // Moving the epoch keeps the ship in the same place at all times
+        let mut moved = placement;
+        moved.move_epoch(&orrery, 12345.0);
+        let time = 20000.0;
+        let expected = placement.orbit(&orrery).state_at_time(time).position();
+        let actual = moved.orbit(&orrery).state_at_time(time).position();
+        assert_relative_eq!(actual, expected, max_relative = 1e-6);
+    }
+
+    #[test]
+    fn test_elements_round_trip() {
+        let orrery = read_file("ksp-bodies.txt");
+        let placement = ShipPlacement {
+            eccentricity: 0.3,
+            inclination: 0.4,
+            long_asc_node: 2.0,
+            arg_periapsis: 1.0,
+            mean_anomaly: 5.0,
+            ..ShipPlacement::circular(KERBIN, 2e6, 1000.0)
+        };
+        let orbit = placement.orbit(&orrery);
+
+        // Reading the elements back at another time gives the same orbit, with
+        // the object in the same place
+        let elements = OrbitElements::of_orbit(&orbit, 5000.0).unwrap();
+        assert_relative_eq!(elements.semimajor_axis, 2e6, max_relative = 1e-9);
+        assert_relative_eq!(elements.eccentricity, 0.3, max_relative = 1e-9);
+        assert_relative_eq!(elements.inclination, 0.4, max_relative = 1e-9);
+        assert_relative_eq!(elements.long_asc_node, 2.0, max_relative = 1e-9);
+        assert_relative_eq!(elements.arg_periapsis, 1.0, max_relative = 1e-9);
+        let rebuilt = elements.orbit(orrery.get_body(KERBIN).clone(), 5000.0);
+        let time = 12345.0;
+        let expected = orbit.state_at_time(time).position();
+        let actual = rebuilt.state_at_time(time).position();
+        assert_relative_eq!(actual, expected, max_relative = 1e-6);
+    }
+}
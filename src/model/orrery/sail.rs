@@ -0,0 +1,243 @@
+use nalgebra::Vector3;
+
+use super::srp::solar_pressure_at;
+
+/// How the sail is pointed, relative to the Sun.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SailAttitude {
+    /// Angle between the sail's normal and the Sun-to-ship direction, in
+    /// radians. Zero faces the sail straight at the Sun, and a right angle
+    /// turns it edge-on.
+    pub cone: f64,
+    /// Angle of the sail's normal around the Sun-to-ship direction, in
+    /// radians. Zero tilts it towards the ship's direction of motion, and a
+    /// quarter turn tilts it out of the orbital plane.
+    pub clock: f64,
+}
+
+/// A piecewise-constant schedule of sail attitudes. Each step holds from its
+/// start time until the start of the next one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SailSchedule {
+    // Invariant: sorted by start time, with no duplicates. The first step
+    // applies at all times before it too.
+    steps: Vec<(f64, SailAttitude)>,
+}
+
+impl SailSchedule {
+    /// A schedule that holds the same attitude forever.
+    pub fn new(attitude: SailAttitude) -> Self {
+        Self {
+            steps: vec![(f64::NEG_INFINITY, attitude)],
+        }
+    }
+
+    pub fn steps(&self) -> &[(f64, SailAttitude)] {
+        &self.steps
+    }
+
+    pub fn attitude_at(&self, time: f64) -> SailAttitude {
+        let idx = self.steps.partition_point(|(t, _)| *t <= time);
+        // The first step covers everything before it
+        self.steps[idx.saturating_sub(1)].1
+    }
+
+    /// Returns the start of the first step strictly after the given time.
+    pub fn next_change_after(&self, time: f64) -> Option<f64> {
+        let idx = self.steps.partition_point(|(t, _)| *t <= time);
+        self.steps.get(idx).map(|(t, _)| *t)
+    }
+
+    /// Switches to the given attitude at the given time, replacing any step
+    /// that already starts then. Later steps are unaffected.
+    pub fn set_attitude(&mut self, time: f64, attitude: SailAttitude) {
+        let idx = self.steps.partition_point(|(t, _)| *t < time);
+        match self.steps.get_mut(idx) {
+            Some(step) if step.0 == time => step.1 = attitude,
+            _ => self.steps.insert(idx, (time, attitude)),
+        }
+    }
+
+    /// Removes the step starting at the given time, if there is one. The
+    /// initial step can't be removed. Returns whether a step was removed.
+    pub fn remove_step(&mut self, time: f64) -> bool {
+        match self.steps.iter().position(|(t, _)| *t == time) {
+            Some(idx) if idx > 0 => {
+                self.steps.remove(idx);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the earliest time at which the two schedules call for
+    /// different attitudes, or `None` if they always agree.
+    pub fn first_difference(&self, other: &SailSchedule) -> Option<f64> {
+        // Only step boundaries can introduce a difference
+        let mut times: Vec<f64> = self
+            .steps
+            .iter()
+            .chain(other.steps.iter())
+            .map(|(t, _)| *t)
+            .collect();
+        times.sort_by(f64::total_cmp);
+        times
+            .into_iter()
+            .find(|t| self.attitude_at(*t) != other.attitude_at(*t))
+    }
+}
+
+/// A flat solar sail, which turns sunlight into a small but continuous thrust.
+/// Only the sail itself is modeled; the rest of the ship can be given
+/// [SolarRadiationPressure](super::SolarRadiationPressure) as well.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolarSail {
+    /// In m^2
+    pub area: f64,
+    /// Mass of the whole ship, in kg
+    pub mass: f64,
+    /// Fraction of light that is reflected specularly, between 0 and 1. The
+    /// rest is assumed to be absorbed.
+    pub reflectivity: f64,
+    pub schedule: SailSchedule,
+}
+
+impl SolarSail {
+    /// Returns the acceleration on the ship at the given time, given its
+    /// position relative to the Sun, and its velocity relative to its parent
+    /// (which orients the clock angle). `illumination` scales the result, and
+    /// is 0 in full shadow and 1 in full sunlight.
+    pub fn acceleration(
+        &self,
+        sun_to_ship: &Vector3<f64>,
+        velocity: &Vector3<f64>,
+        illumination: f64,
+        time: f64,
+    ) -> Vector3<f64> {
+        let attitude = self.schedule.attitude_at(time);
+        let sun_dir = sun_to_ship.normalize();
+        let normal = sail_normal(&sun_dir, velocity, &attitude);
+
+        // Absorbed light pushes along the sunlight, reflected light pushes
+        // along the normal. Both scale with the area the sail presents.
+        let cos_cone = normal.dot(&sun_dir).max(0.0);
+        let pressure = solar_pressure_at(sun_to_ship.norm()) * self.area / self.mass;
+        illumination
+            * pressure
+            * cos_cone
+            * ((1.0 - self.reflectivity) * sun_dir + 2.0 * self.reflectivity * cos_cone * normal)
+    }
+}
+
+/// The sail's normal, for the given attitude. The clock angle is measured from
+/// the part of the velocity perpendicular to the Sun direction; if there isn't
+/// one, any perpendicular will do.
+fn sail_normal(
+    sun_dir: &Vector3<f64>,
+    velocity: &Vector3<f64>,
+    attitude: &SailAttitude,
+) -> Vector3<f64> {
+    let along_track = velocity - velocity.dot(sun_dir) * sun_dir;
+    let along_track = along_track.try_normalize(1e-12).unwrap_or_else(|| {
+        sun_dir
+            .cross(&Vector3::z())
+            .try_normalize(1e-12)
+            .unwrap_or(Vector3::x())
+    });
+    let out_of_plane = sun_dir.cross(&along_track);
+
+    let (sin_cone, cos_cone) = attitude.cone.sin_cos();
+    let (sin_clock, cos_clock) = attitude.clock.sin_cos();
+    cos_cone * sun_dir + sin_cone * (cos_clock * along_track + sin_clock * out_of_plane)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    fn attitude(cone: f64, clock: f64) -> SailAttitude {
+        SailAttitude { cone, clock }
+    }
+
+    #[test]
+    fn test_schedule() {
+        let mut schedule = SailSchedule::new(attitude(0.0, 0.0));
+        schedule.set_attitude(100.0, attitude(1.0, 0.0));
+        schedule.set_attitude(50.0, attitude(0.5, 0.0));
+        assert_eq!(schedule.attitude_at(-1e9), attitude(0.0, 0.0));
+        assert_eq!(schedule.attitude_at(50.0), attitude(0.5, 0.0));
+        assert_eq!(schedule.attitude_at(99.0), attitude(0.5, 0.0));
+        assert_eq!(schedule.attitude_at(1e9), attitude(1.0, 0.0));
+        assert_eq!(schedule.next_change_after(0.0), Some(50.0));
+        assert_eq!(schedule.next_change_after(50.0), Some(100.0));
+        assert_eq!(schedule.next_change_after(100.0), None);
+
+        // Overwriting and removing steps
+        let original = schedule.clone();
+        schedule.set_attitude(100.0, attitude(1.0, 1.0));
+        assert_eq!(schedule.steps().len(), 3);
+        assert_eq!(schedule.first_difference(&original), Some(100.0));
+        assert!(schedule.remove_step(50.0));
+        assert!(!schedule.remove_step(50.0));
+        assert!(!schedule.remove_step(f64::NEG_INFINITY));
+        assert_eq!(schedule.first_difference(&original), Some(50.0));
+        assert_eq!(original.first_difference(&original), None);
+    }
+
+    #[test]
+    fn test_acceleration() {
+        let sun_to_ship = Vector3::new(1.5e11, 0.0, 0.0);
+        let velocity = Vector3::new(0.0, 1000.0, 0.0);
+        let mut sail = SolarSail {
+            area: 1.0,
+            mass: 1.0,
+            reflectivity: 1.0,
+            schedule: SailSchedule::new(attitude(0.0, 0.0)),
+        };
+        let full = 2.0 * solar_pressure_at(1.5e11);
+
+        // Facing the Sun, a perfect reflector gets twice the pressure, straight back
+        let accel = sail.acceleration(&sun_to_ship, &velocity, 1.0, 0.0);
+        assert_relative_eq!(accel, Vector3::new(full, 0.0, 0.0));
+        assert_eq!(
+            sail.acceleration(&sun_to_ship, &velocity, 0.0, 0.0),
+            Vector3::zeros()
+        );
+
+        // Tilting it pushes along the normal, with less force
+        let cone = 35f64.to_radians();
+        sail.schedule.set_attitude(10.0, attitude(cone, 0.0));
+        let accel = sail.acceleration(&sun_to_ship, &velocity, 1.0, 10.0);
+        let magnitude = full * cone.cos().powi(2);
+        assert_relative_eq!(
+            accel,
+            magnitude * Vector3::new(cone.cos(), cone.sin(), 0.0),
+            epsilon = 1e-15
+        );
+
+        // Clock angle rotates it out of plane, and edge-on does nothing
+        sail.schedule.set_attitude(20.0, attitude(cone, PI / 2.0));
+        let accel = sail.acceleration(&sun_to_ship, &velocity, 1.0, 20.0);
+        assert_relative_eq!(
+            accel,
+            magnitude * Vector3::new(cone.cos(), 0.0, cone.sin()),
+            epsilon = 1e-15
+        );
+        sail.schedule.set_attitude(30.0, attitude(PI / 2.0, 0.0));
+        let accel = sail.acceleration(&sun_to_ship, &velocity, 1.0, 30.0);
+        assert_relative_eq!(accel, Vector3::zeros(), epsilon = 1e-20);
+
+        // A black sail only absorbs
+        sail.reflectivity = 0.0;
+        let accel = sail.acceleration(&sun_to_ship, &velocity, 1.0, 10.0);
+        assert_relative_eq!(
+            accel,
+            Vector3::new(full / 2.0 * cone.cos(), 0.0, 0.0),
+            epsilon = 1e-15
+        );
+    }
+}
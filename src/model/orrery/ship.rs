@@ -1,5 +1,11 @@
-use super::{Body, BodyID};
-use crate::astro::TimedOrbit;
+use nalgebra::{Point3, Rotation3, Vector3};
+
+use super::{
+    Arrival, Body, BodyID, BurnEstimate, DeltaVLedger, Maneuver, ManeuverPlan,
+    SolarRadiationPressure, SolarSail, StageStack,
+};
+use crate::astro::integrate::{EnergyDrift, IntegratorSettings, SampledPath};
+use crate::astro::{CartesianState, TimedOrbit};
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ShipID(pub usize);
@@ -9,10 +15,196 @@ pub struct ShipID(pub usize);
 pub struct Ship {
     pub id: ShipID,
     pub orbit: TimedOrbit<Body, ()>,
+    /// How to propagate this ship whenever it can't be done analytically.
+    pub integrator: IntegratorSettings,
+    pub propagation: Propagation,
+    /// If set, solar radiation pressure pushes on the ship whenever it's being
+    /// integrated numerically. Conics can't account for it.
+    pub srp: Option<SolarRadiationPressure>,
+    /// If set, the ship is pushed by a solar sail whenever it's being
+    /// integrated numerically. Only [Numeric](Propagation::Numeric) ships
+    /// follow the sail's schedule exactly.
+    pub sail: Option<SolarSail>,
+    /// Burns the ship will make. These are found as
+    /// [Maneuver](crate::model::events::EventData::Maneuver) events, and
+    /// applied when the timeline reaches them.
+    pub maneuvers: ManeuverPlan,
+    /// How much delta-v the ship has for all its burns, in m/s, if that's
+    /// limited. Plans that need more are still made, but are flagged.
+    pub delta_v_capacity: Option<f64>,
+    /// What the ship does once its plan is over, if anything. Its cost is
+    /// estimated by [mission_ledger](crate::model::arrival::mission_ledger).
+    pub arrival: Option<Arrival>,
+    /// The ship's stages, before it's made any of its burns. If it has
+    /// them, they set its delta-v capacity, unless that's given separately.
+    pub stages: Option<StageStack>,
+    /// If set, along with its stages, the ship's burns are made over time
+    /// with their engines, and integrated numerically, instead of all at
+    /// once. Ships using [Numeric](Propagation::Numeric) propagation still
+    /// burn all at once.
+    pub finite_burns: bool,
+    /// Set while the ship is being integrated numerically. During that time,
+    /// this determines the ship's state, and its orbit is not meaningful.
+    pub numeric_arc: Option<NumericArc>,
+    /// Set if the ship has crashed into its parent body. After that, it no
+    /// longer follows its orbit, and instead rotates along with the body.
+    pub impact: Option<Impact>,
+    /// Shown instead of the ship's ID, if set.
+    pub name: Option<String>,
+    /// What color to draw the ship and its orbit in. White if not set.
+    pub color: Option<Point3<f32>>,
+    /// Whether to look for the ship going in and out of its parent's umbra
+    /// and penumbra.
+    pub track_eclipses: bool,
+    /// Another ship to look for losing and regaining sight of, as bodies get
+    /// in the way. This has to be set before the ship goes into a
+    /// [Timeline](crate::model::timeline::Timeline), which simulates the
+    /// target alongside it.
+    pub line_of_sight_target: Option<ShipID>,
+}
+
+/// How a ship's motion is computed.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Propagation {
+    /// Follow a conic around the parent body, switching to a new one at SOI
+    /// boundaries.
+    #[default]
+    PatchedConics,
+    /// Like [PatchedConics](Propagation::PatchedConics), but when the ship
+    /// comes within `soi_multiple` times the SOI radius of a body that shares
+    /// its parent, integrate its motion under the gravity of both. Once it
+    /// leaves that zone, fit a new conic to it. The multiple must be greater
+    /// than one.
+    Hybrid { soi_multiple: f64 },
+    /// Always integrate the ship's motion around its parent, so that forces
+    /// other than gravity (such as [SolarRadiationPressure]) can act on it.
+    /// The path is integrated in arcs of at most `max_arc_duration` seconds,
+    /// which also end at SOI changes; after each one, a conic is fit to the
+    /// ship's state, for display and SOI calculations.
+    Numeric { max_arc_duration: f64 },
+}
+
+/// A stretch of a ship's path that is integrated numerically, relative to its
+/// parent body.
+#[derive(Debug, Clone)]
+pub struct NumericArc {
+    /// The body whose gravity is included, in addition to the parent's. Ships
+    /// using [Numeric](Propagation::Numeric) propagation don't have one.
+    pub perturber: Option<BodyID>,
+    /// The planned burn being made along the arc, if the ship makes
+    /// [finite burns](Ship::finite_burns). The arc ends when the burn does.
+    pub burn: Option<Maneuver>,
+    pub path: SampledPath,
+    /// How well the ship's integrator kept to the energy it should have
+    /// along the arc, or `None` if the arc isn't on a closed orbit.
+    pub energy_drift: Option<EnergyDrift>,
+}
+
+/// Where and when a ship hit the surface of its parent body.
+#[derive(Debug, Clone)]
+pub struct Impact {
+    pub time: f64,
+    /// Location of the impact, in the parent body's
+    /// [BodyFixed](super::Frame::BodyFixed) frame.
+    pub surface_position: Vector3<f64>,
 }
 
 impl Ship {
     pub fn parent_id(&self) -> BodyID {
         self.orbit.primary().id
     }
+
+    /// Returns the ship's name, or its ID if it doesn't have one.
+    pub fn display_name(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("Ship {}", self.id.0),
+        }
+    }
+
+    /// The delta-v the ship's plan uses, before and after the given time. The
+    /// cost of its [Arrival] isn't included, since that depends on where the
+    /// plan leaves it.
+    pub fn delta_v_ledger(&self, time: f64) -> DeltaVLedger {
+        let capacity = self
+            .delta_v_capacity
+            .or_else(|| self.stages.as_ref().map(|s| s.delta_v()));
+        self.maneuvers.ledger(time, capacity)
+    }
+
+    /// The ship's stages after the burns up to and including the given
+    /// time, if it has any.
+    pub fn stages_at(&self, time: f64) -> Option<StageStack> {
+        self.stages_after_burns(|m| m.time <= time)
+    }
+
+    /// The ship's stages just before the burn at the given time, if it has
+    /// any.
+    pub fn stages_before_burn(&self, time: f64) -> Option<StageStack> {
+        self.stages_after_burns(|m| m.time < time)
+    }
+
+    fn stages_after_burns(&self, include: impl Fn(&Maneuver) -> bool) -> Option<StageStack> {
+        let mut stages = self.stages.clone()?;
+        for maneuver in self.maneuvers.maneuvers().iter().take_while(|m| include(m)) {
+            stages.burn(maneuver.delta_v.norm());
+        }
+        Some(stages)
+    }
+
+    /// What the planned burn at the given time takes out of the ship's
+    /// stages, if it has both.
+    pub fn burn_estimate_at(&self, time: f64) -> Option<BurnEstimate> {
+        let stages = self.stages.as_ref()?;
+        stages
+            .burn_plan(&self.maneuvers)
+            .into_iter()
+            .find(|(m, _)| m.time == time)
+            .map(|(_, estimate)| estimate)
+    }
+
+    pub fn has_crashed(&self) -> bool {
+        self.impact.is_some()
+    }
+
+    /// Whether sunlight pushes on the ship, and so whether it cares about
+    /// being in shadow.
+    pub fn is_sunlight_sensitive(&self) -> bool {
+        self.srp.is_some() || self.sail.is_some()
+    }
+
+    /// Returns the state of the ship, relative to its parent body.
+    pub fn state_at_time(&self, time: f64) -> CartesianState<&Body> {
+        if let Some(impact) = &self.impact {
+            // Rotate the impact site along with the body
+            let body = self.orbit.primary();
+            let rotation =
+                Rotation3::from_axis_angle(&Vector3::z_axis(), body.info.rotation_angle_at(time));
+            let position = rotation * impact.surface_position;
+            let velocity = Vector3::z().cross(&position) * body.info.angular_velocity();
+            return CartesianState::new(body, position, velocity);
+        }
+
+        match &self.numeric_arc {
+            Some(arc) if arc.path.contains_time(time) => {
+                let state = arc.path.state_at_time(time);
+                CartesianState::new(self.orbit.primary(), state.position, state.velocity)
+            }
+            _ => self.orbit.state_at_time(time),
+        }
+    }
+
+    /// Whether the ship is ever integrated numerically, rather than always
+    /// following conics.
+    pub fn is_integrated(&self) -> bool {
+        !matches!(self.propagation, Propagation::PatchedConics) || self.makes_finite_burns()
+    }
+
+    /// Whether the ship's burns are integrated through, using its stages,
+    /// rather than made all at once.
+    pub fn makes_finite_burns(&self) -> bool {
+        self.finite_burns
+            && self.stages.is_some()
+            && !matches!(self.propagation, Propagation::Numeric { .. })
+    }
 }
@@ -0,0 +1,41 @@
+use std::collections::HashMap;
+
+use nalgebra::{Point3, Vector3};
+
+use super::{BodyID, Frame, ShipID};
+
+/// The position and velocity of a single object, as seen from the frame the
+/// snapshot was taken in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObjectState {
+    pub position: Point3<f64>,
+    pub velocity: Vector3<f64>,
+}
+
+/// The states of every body and ship at one moment, all in the same frame.
+/// See [Orrery::snapshot](super::Orrery::snapshot).
+#[derive(Debug, Clone)]
+pub struct SystemSnapshot {
+    pub time: f64,
+    pub frame: Frame,
+    pub(super) bodies: HashMap<BodyID, ObjectState>,
+    pub(super) ships: HashMap<ShipID, ObjectState>,
+}
+
+impl SystemSnapshot {
+    pub fn body(&self, id: BodyID) -> &ObjectState {
+        &self.bodies[&id]
+    }
+
+    pub fn ship(&self, id: ShipID) -> &ObjectState {
+        &self.ships[&id]
+    }
+
+    pub fn bodies(&self) -> impl Iterator<Item = (BodyID, &ObjectState)> + '_ {
+        self.bodies.iter().map(|(id, state)| (*id, state))
+    }
+
+    pub fn ships(&self) -> impl Iterator<Item = (ShipID, &ObjectState)> + '_ {
+        self.ships.iter().map(|(id, state)| (*id, state))
+    }
+}
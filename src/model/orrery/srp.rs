@@ -0,0 +1,217 @@
+use nalgebra::Vector3;
+
+/// Radiation pressure on a perfectly absorbing surface facing the Sun, at one
+/// astronomical unit, in N/m^2.
+const SOLAR_PRESSURE_AT_1_AU: f64 = 4.56e-6;
+/// In meters
+const ASTRONOMICAL_UNIT: f64 = 1.495_978_707e11;
+
+/// A cannonball model of solar radiation pressure: the ship presents the same
+/// area to the Sun no matter how it's oriented, and is pushed directly away
+/// from it.
+///
+/// The pressure is the real-world value, which falls off with the square of
+/// the distance from the Sun. It's negligible in the stock Kerbol system, and
+/// really only matters for long-duration, real-scale planning.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SolarRadiationPressure {
+    /// Cross-sectional area facing the Sun, in m^2
+    pub area: f64,
+    /// In kg
+    pub mass: f64,
+    /// Between 1 (absorbs everything) and 2 (reflects everything straight
+    /// back).
+    pub reflectivity: f64,
+}
+
+impl SolarRadiationPressure {
+    /// Returns the acceleration on the ship, given its position relative to
+    /// the Sun. `illumination` scales the result, and is 0 in full shadow and 1
+    /// in full sunlight.
+    pub fn acceleration(&self, sun_to_ship: &Vector3<f64>, illumination: f64) -> Vector3<f64> {
+        let distance = sun_to_ship.norm();
+        let pressure = solar_pressure_at(distance);
+        let magnitude = illumination * pressure * self.reflectivity * self.area / self.mass;
+        magnitude * sun_to_ship / distance
+    }
+}
+
+/// Radiation pressure on a perfectly absorbing surface facing the Sun, at the
+/// given distance from it.
+pub(super) fn solar_pressure_at(distance: f64) -> f64 {
+    SOLAR_PRESSURE_AT_1_AU * (ASTRONOMICAL_UNIT / distance).powi(2)
+}
+
+/// Measures how deep a point is in the shadow cast by a body, modeled as a
+/// cylinder behind the body, pointing away from the Sun. Both positions are
+/// relative to the shadowing body.
+///
+/// The result is positive in shadow and negative in sunlight, and varies
+/// continuously with the position, so that eclipse entry and exit can be found
+/// as its roots.
+pub fn shadow_depth(position: &Vector3<f64>, sun_position: &Vector3<f64>, radius: f64) -> f64 {
+    let sun_direction = sun_position.normalize();
+    let along = position.dot(&sun_direction);
+    if along < 0.0 {
+        // Behind the body: compare the distance from the shadow's axis
+        let off_axis = position - along * sun_direction;
+        radius - off_axis.norm()
+    } else {
+        // In front, everything outside the body is lit. This matches the case
+        // above at the terminator.
+        radius - position.norm()
+    }
+}
+
+/// The two parts of a body's shadow, when the Sun is treated as a disc.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShadowRegion {
+    /// Where the body hides part of the Sun.
+    Penumbra,
+    /// Where the body hides all of the Sun.
+    Umbra,
+}
+
+/// Like [shadow_depth], but with the Sun as a disc of the given radius,
+/// instead of a point infinitely far away. This makes the shadow a cone:
+/// the umbra narrows behind the body, and the penumbra widens.
+///
+/// As with [shadow_depth], the result is positive inside the region, and
+/// continuous. As the Sun gets smaller and further away, both regions turn
+/// into the cylinder.
+pub fn cone_shadow_depth(
+    position: &Vector3<f64>,
+    sun_position: &Vector3<f64>,
+    radius: f64,
+    sun_radius: f64,
+    region: ShadowRegion,
+) -> f64 {
+    let sun_distance = sun_position.norm();
+    let sun_direction = sun_position / sun_distance;
+    let along = position.dot(&sun_direction);
+    if along >= 0.0 {
+        // In front, everything outside the body is lit, as before
+        return radius - position.norm();
+    }
+
+    // The edges of the cones run along lines touching both the Sun and the
+    // body, on the same side for the umbra, and opposite sides for the
+    // penumbra
+    let radius_difference = match region {
+        ShadowRegion::Umbra => sun_radius - radius,
+        ShadowRegion::Penumbra => -(sun_radius + radius),
+    };
+    let slope = radius_difference / (sun_distance.powi(2) - radius_difference.powi(2)).sqrt();
+    let shadow_radius = radius + along * slope;
+    let off_axis = position - along * sun_direction;
+    shadow_radius - off_axis.norm()
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_acceleration() {
+        // A 1 m^2, 1 kg perfect absorber at 1 AU
+        let srp = SolarRadiationPressure {
+            area: 1.0,
+            mass: 1.0,
+            reflectivity: 1.0,
+        };
+        let position = Vector3::new(0.0, ASTRONOMICAL_UNIT, 0.0);
+        assert_relative_eq!(
+            srp.acceleration(&position, 1.0),
+            Vector3::new(0.0, SOLAR_PRESSURE_AT_1_AU, 0.0)
+        );
+
+        // Twice as far out is a quarter as strong, and shadow cuts it off
+        assert_relative_eq!(
+            srp.acceleration(&(2.0 * position), 1.0),
+            Vector3::new(0.0, SOLAR_PRESSURE_AT_1_AU / 4.0, 0.0)
+        );
+        assert_eq!(srp.acceleration(&position, 0.0), Vector3::zeros());
+    }
+
+    #[test]
+    fn test_shadow_depth() {
+        let sun = Vector3::new(1e11, 0.0, 0.0);
+        let radius = 10.0;
+
+        // Directly behind the body
+        assert_relative_eq!(
+            shadow_depth(&Vector3::new(-50.0, 0.0, 0.0), &sun, radius),
+            10.0
+        );
+        // Behind, but off to the side
+        assert_relative_eq!(
+            shadow_depth(&Vector3::new(-50.0, 4.0, 3.0), &sun, radius),
+            5.0
+        );
+        assert_relative_eq!(
+            shadow_depth(&Vector3::new(-50.0, 12.0, 0.0), &sun, radius),
+            -2.0
+        );
+        // In front of the body
+        assert!(shadow_depth(&Vector3::new(50.0, 0.0, 0.0), &sun, radius) < 0.0);
+        // Continuous across the terminator
+        assert_relative_eq!(
+            shadow_depth(&Vector3::new(-1e-9, 15.0, 0.0), &sun, radius),
+            shadow_depth(&Vector3::new(1e-9, 15.0, 0.0), &sun, radius),
+            epsilon = 1e-9
+        );
+    }
+
+    #[test]
+    fn test_cone_shadow_depth() {
+        let sun = Vector3::new(1000.0, 0.0, 0.0);
+        let radius = 10.0;
+        let depth = |position: Vector3<f64>, sun_radius: f64, region: ShadowRegion| {
+            cone_shadow_depth(&position, &sun, radius, sun_radius, region)
+        };
+
+        // A distant point Sun gives the same shadow as the cylinder
+        let far_sun = 1e8 * sun;
+        for position in [
+            Vector3::new(-50.0, 4.0, 3.0),
+            Vector3::new(-50.0, 12.0, 0.0),
+            Vector3::new(50.0, 0.0, 0.0),
+        ] {
+            for region in [ShadowRegion::Umbra, ShadowRegion::Penumbra] {
+                assert_relative_eq!(
+                    cone_shadow_depth(&position, &far_sun, radius, 0.0, region),
+                    shadow_depth(&position, &far_sun, radius),
+                    epsilon = 1e-6
+                );
+            }
+        }
+
+        // The Sun is ten times as big as the body, so the umbra ends about a
+        // ninth of the way back to it. By then, the penumbra is a bit more than
+        // twice as wide as the body.
+        let sun_radius = 100.0;
+        let apex = Vector3::new(-1000.0 / 9.0, 0.0, 0.0);
+        assert_relative_eq!(
+            depth(apex, sun_radius, ShadowRegion::Umbra),
+            0.0,
+            epsilon = 0.1
+        );
+        assert!(depth(1.1 * apex, sun_radius, ShadowRegion::Umbra) < 0.0);
+        assert_relative_eq!(
+            depth(
+                apex + Vector3::new(0.0, 22.3, 0.0),
+                sun_radius,
+                ShadowRegion::Penumbra
+            ),
+            0.0,
+            epsilon = 1e-1
+        );
+
+        // Just outside the cylinder is in the penumbra but not the umbra
+        let position = Vector3::new(-20.0, 10.5, 0.0);
+        assert!(depth(position, sun_radius, ShadowRegion::Umbra) < 0.0);
+        assert!(depth(position, sun_radius, ShadowRegion::Penumbra) > 0.0);
+    }
+}
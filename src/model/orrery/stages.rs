@@ -0,0 +1,392 @@
+use std::str::FromStr;
+
+use super::{Maneuver, ManeuverPlan};
+
+/// Standard gravity, which turns specific impulse into exhaust velocity, in
+/// m/s^2.
+pub(super) const STANDARD_GRAVITY: f64 = 9.80665;
+
+/// One stage of a rocket: some propellant tanks and the engines that burn
+/// them. Stages are burned from the bottom up, and each one is dropped as soon
+/// as it runs dry; the last stage's dry mass is what's left at the end.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stage {
+    /// Mass of the stage alone once its propellant is gone, in kg
+    pub dry_mass: f64,
+    /// Mass of the stage alone when it's full, in kg
+    pub wet_mass: f64,
+    /// Specific impulse of its engines, in seconds
+    pub isp: f64,
+    /// Total thrust of its engines, in N
+    pub thrust: f64,
+}
+
+impl Stage {
+    pub fn propellant_mass(&self) -> f64 {
+        self.wet_mass - self.dry_mass
+    }
+
+    pub fn exhaust_velocity(&self) -> f64 {
+        self.isp * STANDARD_GRAVITY
+    }
+}
+
+impl FromStr for Stage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || {
+            format!(
+                "Expected a stage like dry,wet,isp,thrust (kg, kg, s, N), but got {:?}",
+                s
+            )
+        };
+        let fields: Vec<f64> = s
+            .split(',')
+            .map(|field| field.trim().parse().map_err(|_| error()))
+            .collect::<Result<_, _>>()?;
+        let stage = match fields[..] {
+            [dry_mass, wet_mass, isp, thrust] => Stage {
+                dry_mass,
+                wet_mass,
+                isp,
+                thrust,
+            },
+            _ => return Err(error()),
+        };
+
+        if !fields.iter().all(|x| x.is_finite()) {
+            return Err(format!(
+                "Stage can't have NaNs or infinities, but got {:?}",
+                s
+            ));
+        }
+        if !(stage.dry_mass > 0.0 && stage.isp > 0.0 && stage.thrust > 0.0) {
+            return Err(format!(
+                "Stage masses, Isp and thrust must be positive, but got {:?}",
+                s
+            ));
+        }
+        if stage.wet_mass < stage.dry_mass {
+            return Err(format!(
+                "Stage can't weigh less full than empty, but got {:?}",
+                s
+            ));
+        }
+        Ok(stage)
+    }
+}
+
+/// A ship's stages, along with which one is being burned and how much
+/// propellant it has left.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StageStack {
+    // In the order they're burned
+    stages: Vec<Stage>,
+    // The stage being burned; the ones before it have been dropped
+    current: usize,
+    // Propellant left in the current stage, in kg
+    propellant: f64,
+}
+
+/// What making one burn takes out of a [StageStack].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BurnEstimate {
+    /// How long the engines fire for, in seconds.
+    pub duration: f64,
+    /// The stages that ran dry and were dropped during the burn, each with
+    /// how long into the burn that was, in seconds.
+    pub staged: Vec<(usize, f64)>,
+    /// How much of the burn couldn't be made because the ship ran out of
+    /// propellant, in m/s. Zero if it had enough.
+    pub shortfall: f64,
+}
+
+/// How much one of the remaining stages of a [StageStack] can do.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StageSummary {
+    /// Which stage this is, counting from 0 in the order they're burned.
+    pub index: usize,
+    /// Delta-v from burning the rest of the stage's propellant, in m/s.
+    pub delta_v: f64,
+    /// Thrust-to-weight ratio when the stage starts burning, against the
+    /// given surface gravity. Below 1, the stage can't lift the ship off the
+    /// ground.
+    pub twr: f64,
+}
+
+impl BurnEstimate {
+    /// A short description, like "35 s, dropping stage 1". Stages are
+    /// numbered from 1.
+    pub fn describe(&self) -> String {
+        let mut parts = vec![format!("{:.0} s", self.duration)];
+        if !self.staged.is_empty() {
+            let numbers: Vec<_> = self
+                .staged
+                .iter()
+                .map(|(i, _)| (i + 1).to_string())
+                .collect();
+            parts.push(format!("dropping stage {}", numbers.join(", ")));
+        }
+        if self.shortfall > 0.0 {
+            parts.push(format!("{:.1} m/s short", self.shortfall));
+        }
+        parts.join(", ")
+    }
+}
+
+impl StageStack {
+    /// A full set of stages, in the order they're burned.
+    pub fn new(stages: Vec<Stage>) -> Self {
+        let propellant = stages.first().map_or(0.0, |s| s.propellant_mass());
+        Self {
+            stages,
+            current: 0,
+            propellant,
+        }
+    }
+
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+
+    /// Propellant left in the current stage, in kg.
+    pub fn propellant(&self) -> f64 {
+        self.propellant
+    }
+
+    /// The index of the stage being burned, or `None` if they're all spent.
+    pub fn current_stage(&self) -> Option<usize> {
+        (self.current < self.stages.len()).then_some(self.current)
+    }
+
+    /// Mass of the whole ship as it is now, in kg.
+    pub fn mass(&self) -> f64 {
+        match self.stages.get(self.current..) {
+            Some([current, rest @ ..]) => {
+                current.dry_mass + self.propellant + rest.iter().map(|s| s.wet_mass).sum::<f64>()
+            }
+            // Whatever's left after the last stage runs dry
+            _ => self.stages.last().map_or(0.0, |s| s.dry_mass),
+        }
+    }
+
+    /// How much delta-v the remaining stages can give, in m/s.
+    pub fn delta_v(&self) -> f64 {
+        let mut stack = self.clone();
+        let mut total = 0.0;
+        while stack.current_stage().is_some() {
+            total += stack.current_stage_delta_v();
+            stack.drop_stage();
+        }
+        total
+    }
+
+    /// The delta-v and thrust-to-weight ratio of each remaining stage, with
+    /// weight measured at a body's surface gravity, in m/s^2.
+    pub fn summaries(&self, surface_gravity: f64) -> Vec<StageSummary> {
+        let mut stack = self.clone();
+        let mut summaries = vec![];
+        while let Some(index) = stack.current_stage() {
+            summaries.push(StageSummary {
+                index,
+                delta_v: stack.current_stage_delta_v(),
+                twr: stack.stages[index].thrust / (stack.mass() * surface_gravity),
+            });
+            stack.drop_stage();
+        }
+        summaries
+    }
+
+    /// Delta-v left in the current stage, by the rocket equation.
+    fn current_stage_delta_v(&self) -> f64 {
+        let stage = &self.stages[self.current];
+        let mass = self.mass();
+        stage.exhaust_velocity() * (mass / (mass - self.propellant)).ln()
+    }
+
+    fn drop_stage(&mut self) {
+        self.current += 1;
+        self.propellant = self
+            .stages
+            .get(self.current)
+            .map_or(0.0, |s| s.propellant_mass());
+    }
+
+    /// Thrust and mass of the ship, in N and kg, the given number of seconds
+    /// into a burn at full thrust, starting from the stages as they are now.
+    /// Stages are dropped as they run dry, and once they're all spent, there's
+    /// no thrust left.
+    pub fn firing_after(&self, mut elapsed: f64) -> (f64, f64) {
+        let mut current = self.current;
+        let mut propellant = self.propellant;
+        let mut mass = self.mass();
+        while let Some(stage) = self.stages.get(current) {
+            let mass_flow = stage.thrust / stage.exhaust_velocity();
+            let burn_time = propellant / mass_flow;
+            if elapsed < burn_time {
+                return (stage.thrust, mass - mass_flow * elapsed);
+            }
+            elapsed -= burn_time;
+            mass -= propellant + stage.dry_mass;
+            current += 1;
+            propellant = self
+                .stages
+                .get(current)
+                .map_or(0.0, |s| s.propellant_mass());
+        }
+        // Whatever's left after the last stage runs dry
+        (0.0, self.stages.last().map_or(0.0, |s| s.dry_mass))
+    }
+
+    /// Makes a burn, using up propellant from each stage in turn and dropping
+    /// them as they run dry.
+    pub fn burn(&mut self, delta_v: f64) -> BurnEstimate {
+        let mut remaining = delta_v;
+        let mut duration = 0.0;
+        let mut staged = vec![];
+        while remaining > 0.0 {
+            let stage = match self.stages.get(self.current) {
+                Some(stage) => *stage,
+                None => break,
+            };
+            let available = self.current_stage_delta_v();
+            let exhaust_velocity = stage.exhaust_velocity();
+            if remaining < available {
+                let mass = self.mass();
+                let used = mass * (1.0 - (-remaining / exhaust_velocity).exp());
+                duration += used * exhaust_velocity / stage.thrust;
+                self.propellant -= used;
+                remaining = 0.0;
+            } else {
+                duration += self.propellant * exhaust_velocity / stage.thrust;
+                remaining -= available;
+                staged.push((self.current, duration));
+                self.drop_stage();
+            }
+        }
+
+        BurnEstimate {
+            duration,
+            staged,
+            shortfall: remaining,
+        }
+    }
+
+    /// Makes each burn of the plan in turn, starting from the stages as they
+    /// are now, and returns what each one takes.
+    pub fn burn_plan(&self, plan: &ManeuverPlan) -> Vec<(Maneuver, BurnEstimate)> {
+        let mut stack = self.clone();
+        plan.maneuvers()
+            .iter()
+            .map(|m| (*m, stack.burn(m.delta_v.norm())))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use nalgebra::Vector3;
+
+    use super::*;
+
+    fn stage(dry_mass: f64, wet_mass: f64) -> Stage {
+        Stage {
+            dry_mass,
+            wet_mass,
+            isp: 300.0,
+            thrust: 100_000.0,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let parsed: Stage = "1000, 4000,300,100000".parse().unwrap();
+        assert_eq!(parsed, stage(1000.0, 4000.0));
+        assert!("1000,4000,300".parse::<Stage>().is_err());
+        assert!("1000,500,300,100000".parse::<Stage>().is_err());
+        assert!("1000,4000,0,100000".parse::<Stage>().is_err());
+        assert!("1000,NaN,300,100000".parse::<Stage>().is_err());
+        assert!("1000,4000,300,inf".parse::<Stage>().is_err());
+    }
+
+    #[test]
+    fn test_staging() {
+        let stack = StageStack::new(vec![stage(1000.0, 4000.0), stage(500.0, 1500.0)]);
+        assert_eq!(stack.mass(), 5500.0);
+        let ve = 300.0 * STANDARD_GRAVITY;
+        let first_dv = ve * (5500.0_f64 / 2500.0).ln();
+        let second_dv = ve * 3.0_f64.ln();
+        assert_relative_eq!(stack.delta_v(), first_dv + second_dv, max_relative = 1e-12);
+
+        // A small burn that doesn't empty the first stage
+        let mut partial = stack.clone();
+        let estimate = partial.burn(100.0);
+        assert!(estimate.staged.is_empty());
+        assert_eq!(estimate.shortfall, 0.0);
+        let used = stack.mass() - partial.mass();
+        assert_relative_eq!(
+            estimate.duration,
+            used * ve / 100_000.0,
+            max_relative = 1e-12
+        );
+        assert_relative_eq!(
+            partial.delta_v(),
+            first_dv + second_dv - 100.0,
+            max_relative = 1e-12
+        );
+
+        // One that drops the first stage, and then one that's too big
+        let burn = |time, dv| Maneuver {
+            time,
+            delta_v: Vector3::new(dv, 0.0, 0.0),
+        };
+        let mut plan = ManeuverPlan::new();
+        plan.set_maneuver(burn(10.0, first_dv + 50.0));
+        plan.set_maneuver(burn(20.0, second_dv));
+        let estimates = stack.burn_plan(&plan);
+        assert_eq!(estimates[0].1.staged[0].0, 0);
+        assert_eq!(estimates[0].1.shortfall, 0.0);
+        assert_eq!(estimates[1].1.staged[0].0, 1);
+        assert_relative_eq!(estimates[1].1.shortfall, 50.0, max_relative = 1e-9);
+        assert!(estimates[1]
+            .1
+            .describe()
+            .ends_with("dropping stage 2, 50.0 m/s short"));
+
+        // Each stage's share of the delta-v, and its TWR on Kerbin
+        let summaries = stack.summaries(STANDARD_GRAVITY);
+        assert_eq!(summaries.len(), 2);
+        assert_relative_eq!(summaries[0].delta_v, first_dv, max_relative = 1e-12);
+        assert_relative_eq!(summaries[1].delta_v, second_dv, max_relative = 1e-12);
+        assert_relative_eq!(summaries[0].twr, 100_000.0 / (5500.0 * STANDARD_GRAVITY));
+        assert_relative_eq!(summaries[1].twr, 100_000.0 / (1500.0 * STANDARD_GRAVITY));
+        let summaries = partial.summaries(STANDARD_GRAVITY);
+        assert_relative_eq!(summaries[0].delta_v, first_dv - 100.0, max_relative = 1e-12);
+        assert!(summaries[0].twr > stack.summaries(STANDARD_GRAVITY)[0].twr);
+
+        // Firing at full thrust, the first stage runs dry partway through the
+        // first burn, and the second takes over
+        let first_burn_time = 3000.0 * ve / 100_000.0;
+        assert_relative_eq!(estimates[0].1.staged[0].1, first_burn_time);
+        let (thrust, mass) = stack.firing_after(0.5 * first_burn_time);
+        assert_eq!(thrust, 100_000.0);
+        assert_relative_eq!(mass, 4000.0, max_relative = 1e-12);
+        let (thrust, mass) = stack.firing_after(first_burn_time + 1.0);
+        assert_eq!(thrust, 100_000.0);
+        assert_relative_eq!(mass, 1500.0 - 100_000.0 / ve, max_relative = 1e-12);
+        let mut after = stack.clone();
+        after.burn(first_dv + 50.0);
+        let (_, mass) = stack.firing_after(estimates[0].1.duration);
+        assert_relative_eq!(mass, after.mass(), max_relative = 1e-12);
+        assert_eq!(stack.firing_after(1e6), (0.0, 500.0));
+
+        let mut spent = stack.clone();
+        spent.burn(1e6);
+        assert_eq!(spent.current_stage(), None);
+        assert_eq!(spent.mass(), 500.0);
+        assert_eq!(spent.delta_v(), 0.0);
+        assert!(spent.summaries(STANDARD_GRAVITY).is_empty());
+    }
+}
@@ -0,0 +1,254 @@
+//! Burns that turn a ship's orbit into the plane of another orbit, at one of
+//! the nodes where the two planes cross.
+
+use std::fmt;
+
+use nalgebra::{Unit, Vector3};
+
+use super::orrery::{Maneuver, ShipID};
+use super::targeting::ApproachTarget;
+use super::timeline::Timeline;
+use crate::astro::{CartesianState, HasMass, OrbitBase, TimedOrbit};
+
+/// Where one orbit crosses the plane of another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Node {
+    /// Where it crosses to the north side of the other plane
+    Ascending,
+    Descending,
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Node::Ascending => write!(f, "ascending node"),
+            Node::Descending => write!(f, "descending node"),
+        }
+    }
+}
+
+/// A burn that puts an orbit into another plane.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlaneChange {
+    pub node: Node,
+    /// When to burn, and the change in velocity, whose size is the cost of
+    /// the plane change
+    pub maneuver: Maneuver,
+    /// Where the node is, relative to the primary
+    pub position: Vector3<f64>,
+    /// Angle between the two planes before the burn, in radians
+    pub inclination: f64,
+}
+
+/// The change in velocity that turns something at the given state into the
+/// plane with the given normal, keeping its speed and flight path angle. The
+/// state has to be on that plane already, i.e., at a node. The new orbit goes
+/// around the same way as the plane's normal, so matching a plane that's
+/// going the other way turns the ship around.
+pub fn plane_change_delta_v<P: HasMass>(
+    state: &CartesianState<P>,
+    normal: &Unit<Vector3<f64>>,
+) -> Vector3<f64> {
+    let radial = state.position().normalize();
+    let radial_velocity = state.velocity().dot(&radial) * radial;
+    let horizontal_speed = (state.velocity() - radial_velocity).norm();
+    radial_velocity + normal.cross(&radial) * horizontal_speed - state.velocity()
+}
+
+/// The burn that puts the orbit into the target's plane, at the first pass
+/// through the given node after `time`. Returns `None` if the orbit doesn't
+/// pass through it again, or if it's already in the target's plane, so that
+/// there are no nodes.
+pub fn plane_change_burn<P: HasMass, S, P2, S2, E2>(
+    orbit: &TimedOrbit<P, S>,
+    target: &OrbitBase<P2, S2, E2>,
+    node: Node,
+    time: f64,
+) -> Option<PlaneChange> {
+    let nodes = orbit.nodes_relative_to(target);
+    let theta = match node {
+        Node::Ascending => nodes.asc_node_anomaly,
+        Node::Descending => nodes.desc_node_anomaly(),
+    }?;
+    let burn_time = orbit.next_time_at_theta(theta, time)?;
+    let state = orbit.state_at_time(burn_time);
+    Some(PlaneChange {
+        node,
+        maneuver: Maneuver {
+            time: burn_time,
+            delta_v: plane_change_delta_v(&state, &target.normal_vector()),
+        },
+        position: state.position(),
+        inclination: nodes.inclination,
+    })
+}
+
+/// Whichever of the plane changes at the two nodes takes less delta-v. That's
+/// the node further from the primary, where the ship is slower.
+pub fn cheapest_plane_change<P: HasMass, S, P2, S2, E2>(
+    orbit: &TimedOrbit<P, S>,
+    target: &OrbitBase<P2, S2, E2>,
+    time: f64,
+) -> Option<PlaneChange> {
+    [Node::Ascending, Node::Descending]
+        .into_iter()
+        .filter_map(|node| plane_change_burn(orbit, target, node, time))
+        .min_by(|a, b| {
+            let cost = |p: &PlaneChange| p.maneuver.delta_v.norm();
+            cost(a).total_cmp(&cost(b))
+        })
+}
+
+/// Adds a burn to the ship's plan that puts it in the plane of the target's
+/// orbit, at the next pass through the given node after `time`, or whichever
+/// node is cheaper if it's `None`. Any burn already planned then is replaced.
+///
+/// The timeline is extended as far as the burn. It's an error if the target
+/// doesn't have an orbit, or if the ship crashes or leaves its primary's SOI
+/// before it gets there.
+pub fn add_plane_change(
+    timeline: &mut Timeline,
+    ship_id: ShipID,
+    target: ApproachTarget,
+    node: Option<Node>,
+    time: f64,
+) -> Result<PlaneChange, String> {
+    timeline.extend_until(time);
+    let orrery = timeline
+        .get_orrery_at(time)
+        .ok_or_else(|| format!("Can't plan before the timeline starts, at {}", time))?;
+    let ship = orrery.get_ship(ship_id);
+    if ship.has_crashed() {
+        return Err(format!("Ship has crashed by {}", time));
+    }
+    // Only the target's plane matters, not its primary
+    let target_plane = match target {
+        ApproachTarget::Body(id) => orrery
+            .orbit_of_body(id)
+            .ok_or("Target doesn't orbit anything")?
+            .to_bare(),
+        ApproachTarget::Ship(id) => {
+            if orrery.get_ship(id).has_crashed() {
+                return Err(String::from("Target has crashed"));
+            }
+            orrery.get_ship(id).orbit.to_bare()
+        }
+    };
+
+    let primary_id = ship.orbit.primary().id;
+    let mut plane_change = match node {
+        Some(node) => plane_change_burn(&ship.orbit, &target_plane, node, time),
+        None => cheapest_plane_change(&ship.orbit, &target_plane, time),
+    }
+    .ok_or("Ship is already in the target's plane, or never reaches the node")?;
+    let burn_time = plane_change.maneuver.time;
+
+    // Aim using wherever the ship actually is then, in case something's
+    // nudged it off its orbit on the way
+    timeline.extend_until(burn_time);
+    let orrery = timeline.get_orrery_at(burn_time).unwrap();
+    let ship = orrery.get_ship(ship_id);
+    if ship.has_crashed() {
+        return Err(format!("Ship crashes before its {}", plane_change.node));
+    }
+    if ship.orbit.primary().id != primary_id {
+        return Err(format!(
+            "Ship leaves its SOI before its {}",
+            plane_change.node
+        ));
+    }
+    let state = ship.state_at_time(burn_time);
+    plane_change.maneuver.delta_v = plane_change_delta_v(&state, &target_plane.normal_vector());
+    plane_change.position = state.position();
+
+    let mut plan = ship.maneuvers.clone();
+    plan.set_maneuver(plane_change.maneuver);
+    timeline.set_maneuver_plan(ship_id, plan);
+    Ok(plane_change)
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::file::read_file;
+    use crate::model::orrery::BodyID;
+
+    const KERBIN: BodyID = BodyID(4);
+    const MUN: BodyID = BodyID(5);
+
+    #[test]
+    fn test_plane_change() {
+        // An orbit inclined 30 degrees to the Mun's, starting at periapsis,
+        // which is the ascending node
+        let mut orrery = read_file("ksp-bodies.txt");
+        let mu = orrery.get_body(KERBIN).info.mu;
+        let periapsis = 700000.0;
+        let speed = 1.2 * (mu / periapsis).sqrt();
+        let incl = 30.0_f64.to_radians();
+        let ship_id = orrery.add_ship(
+            Vector3::x() * periapsis,
+            Vector3::new(0.0, incl.cos(), incl.sin()) * speed,
+            0.0,
+            KERBIN,
+        );
+        let orbit = orrery.get_ship(ship_id).orbit.clone();
+        let target = orrery.orbit_of_body(MUN).unwrap().to_bare();
+        let period = orbit.period().unwrap();
+        assert_relative_eq!(
+            orbit.nodes_relative_to(&target).inclination,
+            incl,
+            epsilon = 1e-9
+        );
+
+        // The descending node is at apoapsis, where it's cheaper, and the
+        // burn there just turns the velocity through the angle between the
+        // planes
+        let an = plane_change_burn(&orbit, &target, Node::Ascending, 0.0).unwrap();
+        let dn = plane_change_burn(&orbit, &target, Node::Descending, 0.0).unwrap();
+        assert_relative_eq!(an.maneuver.time, period, max_relative = 1e-9);
+        assert_relative_eq!(dn.maneuver.time, period / 2.0, max_relative = 1e-9);
+        assert_relative_eq!(
+            dn.maneuver.delta_v.norm(),
+            2.0 * orbit.apoapsis_velocity().unwrap() * (incl / 2.0).sin(),
+            max_relative = 1e-6
+        );
+        assert_relative_eq!(
+            dn.position.norm(),
+            orbit.apoapsis().unwrap(),
+            max_relative = 1e-9
+        );
+        assert!(an.maneuver.delta_v.norm() > dn.maneuver.delta_v.norm());
+        assert_eq!(
+            cheapest_plane_change(&orbit, &target, 0.0).map(|p| p.node),
+            Some(Node::Descending)
+        );
+
+        // Adding it to the plan puts the ship in the Mun's plane, without
+        // changing the shape of its orbit
+        let mut timeline = Timeline::new(orrery, 0.0);
+        let plane_change =
+            add_plane_change(&mut timeline, ship_id, ApproachTarget::Body(MUN), None, 0.0).unwrap();
+        assert_eq!(plane_change.node, Node::Descending);
+        let after = plane_change.maneuver.time + 1.0;
+        timeline.extend_until(after);
+        let orrery = timeline.get_orrery_at(after).unwrap();
+        let new_orbit = &orrery.get_ship(ship_id).orbit;
+        assert_relative_eq!(
+            new_orbit.nodes_relative_to(&target).inclination,
+            0.0,
+            epsilon = 1e-6
+        );
+        assert_relative_eq!(
+            new_orbit.semimajor_axis(),
+            orbit.semimajor_axis(),
+            max_relative = 1e-6
+        );
+        assert_relative_eq!(
+            new_orbit.eccentricity(),
+            orbit.eccentricity(),
+            epsilon = 1e-6
+        );
+    }
+}
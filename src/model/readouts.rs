@@ -0,0 +1,411 @@
+//! Extra HUD readouts defined by the user, as small arithmetic expressions
+//! over quantities like the focused ship's apoapsis, e.g.,
+//! `apoapsis - atmosphere_height`.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// The quantities that expressions can use, and what they mean. Distances are
+/// in meters, speeds in meters per second, angles in degrees, and times in
+/// seconds.
+pub const QUANTITIES: &[(&str, &str)] = &[
+    ("time", "time since the epoch"),
+    ("altitude", "height above the parent body's surface"),
+    ("radius", "distance from the parent body's center"),
+    ("speed", "speed relative to the parent body"),
+    ("vertical_speed", "how fast the radius is changing"),
+    ("apoapsis", "altitude of the highest point of the orbit"),
+    ("periapsis", "altitude of the lowest point of the orbit"),
+    ("sma", "semi-major axis"),
+    ("eccentricity", "eccentricity"),
+    ("inclination", "inclination"),
+    ("period", "orbital period"),
+    ("body_radius", "radius of the parent body"),
+    (
+        "atmosphere_height",
+        "height of the parent body's atmosphere",
+    ),
+    (
+        "soi_radius",
+        "radius of the parent body's sphere of influence",
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    Sqrt,
+    Abs,
+    Min,
+    Max,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sqrt" => Some(Function::Sqrt),
+            "abs" => Some(Function::Abs),
+            "min" => Some(Function::Min),
+            "max" => Some(Function::Max),
+            _ => None,
+        }
+    }
+
+    fn num_args(&self) -> usize {
+        match self {
+            Function::Sqrt | Function::Abs => 1,
+            Function::Min | Function::Max => 2,
+        }
+    }
+
+    fn apply(&self, args: &[f64]) -> f64 {
+        match self {
+            Function::Sqrt => args[0].sqrt(),
+            Function::Abs => args[0].abs(),
+            Function::Min => args[0].min(args[1]),
+            Function::Max => args[0].max(args[1]),
+        }
+    }
+}
+
+/// A parsed arithmetic expression. Supports numbers, the [QUANTITIES],
+/// `+ - * / ^`, parentheses, and the functions `sqrt`, `abs`, `min`, and
+/// `max`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Number(f64),
+    Quantity(String),
+    Negate(Box<Expression>),
+    Binary(BinaryOp, Box<Expression>, Box<Expression>),
+    Call(Function, Vec<Expression>),
+}
+
+impl Expression {
+    /// Evaluates the expression, looking up quantities with the given
+    /// function. Returns `None` if any quantity it uses isn't available, like
+    /// the apoapsis of a hyperbolic orbit.
+    pub fn evaluate(&self, lookup: &dyn Fn(&str) -> Option<f64>) -> Option<f64> {
+        let value = match self {
+            Expression::Number(x) => *x,
+            Expression::Quantity(name) => lookup(name)?,
+            Expression::Negate(inner) => -inner.evaluate(lookup)?,
+            Expression::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.evaluate(lookup)?, rhs.evaluate(lookup)?);
+                match op {
+                    BinaryOp::Add => lhs + rhs,
+                    BinaryOp::Subtract => lhs - rhs,
+                    BinaryOp::Multiply => lhs * rhs,
+                    BinaryOp::Divide => lhs / rhs,
+                    BinaryOp::Power => lhs.powf(rhs),
+                }
+            }
+            Expression::Call(function, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.evaluate(lookup))
+                    .collect::<Option<Vec<_>>>()?;
+                function.apply(&args)
+            }
+        };
+        Some(value)
+    }
+}
+
+impl FromStr for Expression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            idx: 0,
+        };
+        let expression = parser.expression()?;
+        match parser.peek() {
+            None => Ok(expression),
+            Some(token) => Err(format!("Unexpected {} in {:?}", token, s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Symbol(char),
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(x) => write!(f, "{}", x),
+            Token::Name(name) => write!(f, "{:?}", name),
+            Token::Symbol(c) => write!(f, "'{}'", c),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = s.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        if "+-*/^(),".contains(c) {
+            tokens.push(Token::Symbol(c));
+            continue;
+        }
+
+        // Numbers and names run until the next character that can't be in them
+        let is_number = c.is_ascii_digit() || c == '.';
+        if !(is_number || c.is_alphabetic() || c == '_') {
+            return Err(format!("Unexpected '{}' in {:?}", c, s));
+        }
+        let mut end = start + c.len_utf8();
+        let mut prev = c;
+        while let Some(&(idx, next)) = chars.peek() {
+            let continues = if is_number {
+                // Including exponents, like 1.5e-3
+                next.is_ascii_digit()
+                    || next == '.'
+                    || next == 'e'
+                    || (prev == 'e' && (next == '-' || next == '+'))
+            } else {
+                next.is_alphanumeric() || next == '_'
+            };
+            if !continues {
+                break;
+            }
+            end = idx + next.len_utf8();
+            prev = next;
+            chars.next();
+        }
+
+        let word = &s[start..end];
+        if is_number {
+            let x = word
+                .parse()
+                .map_err(|_| format!("Couldn't read {:?} as a number", word))?;
+            tokens.push(Token::Number(x));
+        } else {
+            tokens.push(Token::Name(word.to_owned()));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser, with the usual precedence: `^` binds tightest,
+/// and is right-associative, then negation, then `* /`, then `+ -`.
+struct Parser {
+    tokens: Vec<Token>,
+    idx: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.idx)
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self
+            .tokens
+            .get(self.idx)
+            .cloned()
+            .ok_or_else(|| String::from("Expression ended too soon"))?;
+        self.idx += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.idx += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), String> {
+        match self.next()? {
+            Token::Symbol(c) if c == symbol => Ok(()),
+            token => Err(format!("Expected '{}', but found {}", symbol, token)),
+        }
+    }
+
+    fn expression(&mut self) -> Result<Expression, String> {
+        let mut lhs = self.term()?;
+        loop {
+            let op = if self.eat('+') {
+                BinaryOp::Add
+            } else if self.eat('-') {
+                BinaryOp::Subtract
+            } else {
+                return Ok(lhs);
+            };
+            lhs = Expression::Binary(op, Box::new(lhs), Box::new(self.term()?));
+        }
+    }
+
+    fn term(&mut self) -> Result<Expression, String> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = if self.eat('*') {
+                BinaryOp::Multiply
+            } else if self.eat('/') {
+                BinaryOp::Divide
+            } else {
+                return Ok(lhs);
+            };
+            lhs = Expression::Binary(op, Box::new(lhs), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expression, String> {
+        if self.eat('-') {
+            return Ok(Expression::Negate(Box::new(self.unary()?)));
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<Expression, String> {
+        let base = self.primary()?;
+        if self.eat('^') {
+            let exponent = self.unary()?;
+            return Ok(Expression::Binary(
+                BinaryOp::Power,
+                Box::new(base),
+                Box::new(exponent),
+            ));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<Expression, String> {
+        match self.next()? {
+            Token::Number(x) => Ok(Expression::Number(x)),
+            Token::Symbol('(') => {
+                let inner = self.expression()?;
+                self.expect(')')?;
+                Ok(inner)
+            }
+            Token::Name(name) if self.eat('(') => {
+                let function = Function::from_name(&name)
+                    .ok_or_else(|| format!("Unknown function {:?}", name))?;
+                let mut args = vec![self.expression()?];
+                while self.eat(',') {
+                    args.push(self.expression()?);
+                }
+                self.expect(')')?;
+                if args.len() != function.num_args() {
+                    return Err(format!(
+                        "{} takes {} arguments, but was given {}",
+                        name,
+                        function.num_args(),
+                        args.len()
+                    ));
+                }
+                Ok(Expression::Call(function, args))
+            }
+            Token::Name(name) => {
+                if !QUANTITIES.iter().any(|(q, _)| *q == name) {
+                    let known: Vec<_> = QUANTITIES.iter().map(|(q, _)| *q).collect();
+                    return Err(format!(
+                        "Unknown quantity {:?}; expected one of {}",
+                        name,
+                        known.join(", ")
+                    ));
+                }
+                Ok(Expression::Quantity(name))
+            }
+            token => Err(format!("Unexpected {}", token)),
+        }
+    }
+}
+
+/// A readout with a label, written `label=expression`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomReadout {
+    pub label: String,
+    pub expression: Expression,
+}
+
+impl FromStr for CustomReadout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (label, expression) = s
+            .split_once('=')
+            .ok_or_else(|| format!("Expected label=expression, but got {:?}", s))?;
+        Ok(CustomReadout {
+            label: label.trim().to_owned(),
+            expression: expression.parse()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    fn evaluate(s: &str) -> Option<f64> {
+        let lookup = |name: &str| match name {
+            "apoapsis" => Some(100_000.0),
+            "atmosphere_height" => Some(70_000.0),
+            "eccentricity" => Some(0.5),
+            _ => None,
+        };
+        s.parse::<Expression>().unwrap().evaluate(&lookup)
+    }
+
+    #[test]
+    fn test_evaluate() {
+        assert_eq!(evaluate("apoapsis - atmosphere_height"), Some(30_000.0));
+        assert_eq!(evaluate("1 + 2 * 3"), Some(7.0));
+        assert_eq!(evaluate("(1 + 2) * 3"), Some(9.0));
+        assert_eq!(evaluate("8 / 4 / 2"), Some(1.0));
+        assert_eq!(evaluate("-2 ^ 2"), Some(-4.0));
+        assert_eq!(evaluate("2 ^ 3 ^ 2"), Some(512.0));
+        assert_eq!(evaluate("max(apoapsis, 1.5e5) / 1e3"), Some(150.0));
+        assert_eq!(evaluate("2e-3 - 1e-3"), Some(1e-3));
+        assert_relative_eq!(
+            evaluate("sqrt(1 - eccentricity^2)").unwrap(),
+            0.75f64.sqrt()
+        );
+
+        // Unavailable quantities make the whole thing unavailable
+        assert_eq!(evaluate("periapsis - atmosphere_height"), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for bad in [
+            "",
+            "1 +",
+            "(1 + 2",
+            "1 2",
+            "apoapsys",
+            "sqrt(1, 2)",
+            "cos(1)",
+            "1 $ 2",
+        ] {
+            assert!(bad.parse::<Expression>().is_err(), "{:?} parsed", bad);
+        }
+    }
+
+    #[test]
+    fn test_readout() {
+        let readout: CustomReadout = "Apo margin = apoapsis - atmosphere_height".parse().unwrap();
+        assert_eq!(readout.label, "Apo margin");
+        assert!("apoapsis".parse::<CustomReadout>().is_err());
+    }
+}
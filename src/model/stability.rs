@@ -0,0 +1,247 @@
+//! Sanity checks for a loaded system, looking for setups that patched conics
+//! can't really handle, like moons that wander out of their planet's SOI.
+
+use std::f64::consts::TAU;
+use std::fmt::Write;
+
+use nalgebra::Vector3;
+
+use super::orrery::{Body, BodyID, Orrery};
+use crate::astro::{OrbitBase, TimedOrbit};
+
+// How many points each orbit is sampled at, when looking for how close two
+// orbits come to each other
+const NUM_SAMPLES: usize = 360;
+
+/// Something about a system that probably won't behave physically.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StabilityIssue {
+    /// Two bodies orbiting the same parent can come close enough that their
+    /// SOIs overlap, without their orbits crossing.
+    OverlappingSois {
+        a: BodyID,
+        b: BodyID,
+        closest_distance: f64,
+    },
+    /// Two bodies orbiting the same parent have crossing orbits: the inner
+    /// one's apoapsis is beyond the outer one's periapsis, and their orbits
+    /// actually come close enough for their SOIs to touch. `period_ratio` is
+    /// the outer one's period divided by the inner one's.
+    CrossingOrbits {
+        inner: BodyID,
+        outer: BodyID,
+        period_ratio: f64,
+    },
+    /// A body's orbit goes beyond its parent's SOI, or doesn't come back at
+    /// all.
+    OutsideParentSoi {
+        body: BodyID,
+        apoapsis: Option<f64>,
+        parent_soi: f64,
+    },
+}
+
+impl StabilityIssue {
+    /// Explains the issue, naming the bodies involved.
+    pub fn describe(&self, orrery: &Orrery) -> String {
+        let name = |id: BodyID| &orrery.get_body(id).info.name;
+        match *self {
+            StabilityIssue::OverlappingSois {
+                a,
+                b,
+                closest_distance,
+            } => format!(
+                "{} and {} come within {:.0} m of each other, close enough for their SOIs to overlap",
+                name(a),
+                name(b),
+                closest_distance
+            ),
+            StabilityIssue::CrossingOrbits {
+                inner,
+                outer,
+                period_ratio,
+            } => format!(
+                "{} and {} have crossing orbits (period ratio {:.3})",
+                name(inner),
+                name(outer),
+                period_ratio
+            ),
+            StabilityIssue::OutsideParentSoi {
+                body,
+                apoapsis,
+                parent_soi,
+            } => {
+                let parent = orrery.get_parent(body).expect("Issue with root body");
+                let mut text = format!("{} leaves the SOI of {} ", name(body), name(parent));
+                match apoapsis {
+                    Some(apoapsis) => write!(
+                        text,
+                        "(apoapsis {:.0} m, SOI {:.0} m)",
+                        apoapsis, parent_soi
+                    ),
+                    None => write!(text, "(escape orbit)"),
+                }
+                .unwrap();
+                text
+            }
+        }
+    }
+}
+
+/// Looks for anything about the bodies of the system that patched conics
+/// won't handle well. Ships aren't checked.
+pub fn check_stability(orrery: &Orrery) -> Vec<StabilityIssue> {
+    let mut issues = vec![];
+
+    let mut orbits: Vec<_> = orrery.body_orbits().collect();
+    orbits.sort_by_key(|orbit| orbit.secondary().id);
+
+    for orbit in orbits.iter() {
+        let parent_soi = match orrery.get_soi_radius(orbit.primary().id) {
+            Some(soi) => soi,
+            None => continue,
+        };
+        let apoapsis = orbit.apoapsis();
+        if apoapsis.is_none_or(|apoapsis| apoapsis > parent_soi) {
+            issues.push(StabilityIssue::OutsideParentSoi {
+                body: orbit.secondary().id,
+                apoapsis,
+                parent_soi,
+            });
+        }
+    }
+
+    for (i, a) in orbits.iter().enumerate() {
+        for b in orbits[i + 1..].iter() {
+            if a.primary().id != b.primary().id {
+                continue;
+            }
+            // Escaping bodies were already reported
+            let (period_a, period_b) = match (a.period(), b.period()) {
+                (Some(period_a), Some(period_b)) => (period_a, period_b),
+                _ => continue,
+            };
+            let soi = |orbit: &TimedOrbit<&Body, &Body>| {
+                orrery.get_soi_radius(orbit.secondary().id).unwrap()
+            };
+            let closest_distance = closest_distance(a, b);
+            if closest_distance > soi(a) + soi(b) {
+                continue;
+            }
+
+            let (inner, outer) = if period_a < period_b { (a, b) } else { (b, a) };
+            let crossing = inner.apoapsis().unwrap() >= outer.periapsis();
+            issues.push(if crossing {
+                StabilityIssue::CrossingOrbits {
+                    inner: inner.secondary().id,
+                    outer: outer.secondary().id,
+                    period_ratio: period_a.max(period_b) / period_a.min(period_b),
+                }
+            } else {
+                StabilityIssue::OverlappingSois {
+                    a: a.secondary().id,
+                    b: b.secondary().id,
+                    closest_distance,
+                }
+            });
+        }
+    }
+
+    issues
+}
+
+/// Roughly how close two closed orbits around the same body come to each
+/// other, regardless of where the bodies on them actually are.
+fn closest_distance(a: &TimedOrbit<&Body, &Body>, b: &TimedOrbit<&Body, &Body>) -> f64 {
+    let points_b: Vec<_> = orbit_points(b).collect();
+    orbit_points(a)
+        .flat_map(|pa| points_b.iter().map(move |pb| (pa - pb).norm()))
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// Evenly spaced points (by true anomaly) around a closed orbit.
+fn orbit_points<P, S, E>(orbit: &OrbitBase<P, S, E>) -> impl Iterator<Item = Vector3<f64>> + '_ {
+    let rotation = orbit.rotation();
+    let (slr, ecc) = (orbit.semilatus_rectum(), orbit.eccentricity());
+    (0..NUM_SAMPLES).map(move |i| {
+        let theta = TAU * i as f64 / NUM_SAMPLES as f64;
+        let radius = slr / (1.0 + ecc * theta.cos());
+        rotation * Vector3::new(radius * theta.cos(), radius * theta.sin(), 0.0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::read_file;
+    use crate::model::orrery::{BodyEdit, SoiModel};
+
+    const KERBIN: BodyID = BodyID(4);
+    const MUN: BodyID = BodyID(5);
+
+    fn body_named(orrery: &Orrery, name: &str) -> BodyID {
+        orrery.bodies().find(|b| b.info.name == name).unwrap().id
+    }
+
+    fn set_soi(orrery: &mut Orrery, id: BodyID, soi_radius: f64) {
+        let edit = BodyEdit {
+            soi_radius: Some(soi_radius),
+            ..BodyEdit::default()
+        };
+        orrery.edit_body(id, &edit, 0.0);
+    }
+
+    #[test]
+    fn test_stock_system() {
+        let mut orrery = read_file("ksp-bodies.txt");
+        for model in [SoiModel::Laplace, SoiModel::Fixed] {
+            orrery.set_soi_model(model);
+            assert_eq!(check_stability(&orrery), vec![]);
+        }
+
+        // Hill spheres are bigger, and Eeloo's orbit comes close enough to
+        // Jool's for them to touch, just like Pluto and Neptune's
+        orrery.set_soi_model(SoiModel::Hill);
+        let issues = check_stability(&orrery);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(
+            issues[0].describe(&orrery),
+            "Jool and Eeloo have crossing orbits (period ratio 1.500)"
+        );
+    }
+
+    #[test]
+    fn test_issues() {
+        let mut orrery = read_file("ksp-bodies.txt");
+        orrery.set_soi_model(SoiModel::Fixed);
+        let minmus = body_named(&orrery, "Minmus");
+        let (jool, eeloo) = (body_named(&orrery, "Jool"), body_named(&orrery, "Eeloo"));
+
+        // Minmus orbits at 47,000 km, so it escapes a smaller Kerbin SOI, and
+        // if the Mun's SOI were big enough, it'd reach Minmus's orbit
+        set_soi(&mut orrery, KERBIN, 3e7);
+        set_soi(&mut orrery, MUN, 4e7);
+        // Eeloo's orbit dips inside Jool's, and gets within a few million km
+        // of it, so a big enough Jool SOI would reach it
+        set_soi(&mut orrery, jool, 2e10);
+
+        let issues = check_stability(&orrery);
+        assert_eq!(issues.len(), 3, "{:?}", issues);
+        assert!(matches!(
+            issues[0],
+            StabilityIssue::OutsideParentSoi { body, .. } if body == minmus
+        ));
+        assert!(matches!(
+            issues[1],
+            StabilityIssue::OverlappingSois { a, b, .. } if a == MUN && b == minmus
+        ));
+        assert!(matches!(
+            issues[2],
+            StabilityIssue::CrossingOrbits { inner, outer, .. } if inner == jool && outer == eeloo
+        ));
+        assert_eq!(
+            issues[0].describe(&orrery),
+            "Minmus leaves the SOI of Kerbin (apoapsis 47000000 m, SOI 30000000 m)"
+        );
+    }
+}
@@ -0,0 +1,177 @@
+//! Tools for aiming a ship at a particular encounter.
+
+use nalgebra::{dvector, DVector, Vector3};
+
+use super::events::EventTag;
+use super::orrery::{BodyID, Frame, Orrery, ShipID};
+use super::timeline::Timeline;
+use crate::astro::BPlane;
+use crate::math::corrector::{differential_correction, CorrectorSettings};
+
+/// Number of evenly-spaced times at which [closest_approach] checks the
+/// distance, before refining the best one.
+const CLOSEST_APPROACH_SAMPLES: usize = 256;
+const CLOSEST_APPROACH_ITERATIONS: usize = 60;
+
+/// Something a ship can approach.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApproachTarget {
+    Body(BodyID),
+    Ship(ShipID),
+}
+
+impl ApproachTarget {
+    fn frame(&self) -> Frame {
+        match *self {
+            ApproachTarget::Body(id) => Frame::BodyInertial(id),
+            ApproachTarget::Ship(id) => Frame::ShipInertial(id),
+        }
+    }
+}
+
+/// The moment when a ship is closest to its target.
+#[derive(Debug, Clone, Copy)]
+pub struct ClosestApproach {
+    pub time: f64,
+    /// Distance between the centers of the ship and the target
+    pub distance: f64,
+    pub relative_speed: f64,
+}
+
+/// Desired B-plane coordinates for an encounter, along with the settings for
+/// finding a burn that achieves them.
+#[derive(Debug, Clone, Copy)]
+pub struct BPlaneTarget {
+    pub target_id: BodyID,
+    pub b_dot_t: f64,
+    pub b_dot_r: f64,
+    /// How long after the burn to look for the encounter.
+    pub search_horizon: f64,
+    pub settings: CorrectorSettings,
+}
+
+/// Finds the B-plane parameters of the ship's next encounter with the given
+/// body, strictly after the given time. Only the part of the timeline that's
+/// already been searched is considered.
+///
+/// The B-plane is measured relative to the target's equator.
+pub fn next_encounter_b_plane(
+    timeline: &Timeline,
+    ship_id: ShipID,
+    target_id: BodyID,
+    time: f64,
+) -> Option<BPlane> {
+    let event = timeline.next_event(ship_id, &EventTag::EncounterSOI(target_id), time)?;
+    // Right at the event, the ship has just switched to orbiting the target
+    let orrery = timeline.get_orrery_at(event.point.time)?;
+    orrery.get_ship(ship_id).orbit.b_plane(&Vector3::z_axis())
+}
+
+/// Searches for an impulse, applied at `burn_time`, that puts the ship's next
+/// encounter at the given B-plane coordinates. The orrery should be the one in
+/// effect at `burn_time`.
+///
+/// The burn is given in the ship's parent's inertial frame. Since there are
+/// two targets and three components, this picks the smallest burn it can.
+pub fn target_b_plane(
+    orrery: &Orrery,
+    ship_id: ShipID,
+    burn_time: f64,
+    target: &BPlaneTarget,
+) -> Result<Vector3<f64>, String> {
+    let evaluate = |delta_v: &DVector<f64>| {
+        let mut orrery = orrery.clone();
+        orrery.apply_impulse(
+            ship_id,
+            Vector3::new(delta_v[0], delta_v[1], delta_v[2]),
+            burn_time,
+        );
+        let mut timeline = Timeline::new(orrery, burn_time);
+        timeline.extend_until(burn_time + target.search_horizon);
+
+        let b_plane = next_encounter_b_plane(&timeline, ship_id, target.target_id, burn_time)?;
+        Some(dvector![b_plane.b_dot_t, b_plane.b_dot_r])
+    };
+
+    let solution = differential_correction(
+        evaluate,
+        DVector::zeros(3),
+        &dvector![target.b_dot_t, target.b_dot_r],
+        &target.settings,
+    )?;
+    Ok(Vector3::new(solution[0], solution[1], solution[2]))
+}
+
+/// Finds when the ship comes closest to the target between the two times. Only
+/// the part of the timeline that's already been searched is meaningful.
+///
+/// The distance is sampled at regular intervals, and at each of the ship's
+/// events (so that encounters with the target aren't skipped over), and then
+/// the closest sample is refined with a golden-section search. Approaches
+/// shorter than the sampling interval can still be missed.
+pub fn closest_approach(
+    timeline: &Timeline,
+    ship_id: ShipID,
+    target: ApproachTarget,
+    start_time: f64,
+    end_time: f64,
+) -> Option<ClosestApproach> {
+    assert!(start_time <= end_time);
+
+    let distance_at = |time: f64| -> Option<f64> {
+        let orrery = timeline.get_orrery_at(time)?;
+        let position = orrery
+            .get_ship_state(ship_id, time)
+            .get_position(target.frame(), time);
+        Some(position.coords.norm())
+    };
+
+    let step = (end_time - start_time) / CLOSEST_APPROACH_SAMPLES as f64;
+    let mut sample_times: Vec<f64> = (0..=CLOSEST_APPROACH_SAMPLES)
+        .map(|i| start_time + step * i as f64)
+        .chain(
+            timeline
+                .events()
+                .filter(|e| e.ship_id == ship_id)
+                .map(|e| e.point.time)
+                .filter(|t| (start_time..=end_time).contains(t)),
+        )
+        .collect();
+    sample_times.sort_by(f64::total_cmp);
+
+    let distances: Vec<f64> = sample_times
+        .iter()
+        .map(|t| distance_at(*t))
+        .collect::<Option<_>>()?;
+    let best_idx = (0..distances.len()).min_by(|i, j| distances[*i].total_cmp(&distances[*j]))?;
+
+    // The minimum is somewhere between the neighboring samples
+    let mut lo = sample_times[best_idx.saturating_sub(1)];
+    let mut hi = sample_times[usize::min(best_idx + 1, sample_times.len() - 1)];
+    let ratio = (5.0_f64.sqrt() - 1.0) / 2.0;
+    for _ in 0..CLOSEST_APPROACH_ITERATIONS {
+        let left = hi - ratio * (hi - lo);
+        let right = lo + ratio * (hi - lo);
+        if distance_at(left)? < distance_at(right)? {
+            hi = right;
+        } else {
+            lo = left;
+        }
+    }
+
+    // Refining can't make things worse than the best sample
+    let refined_time = (lo + hi) / 2.0;
+    let time = if distance_at(refined_time)? < distances[best_idx] {
+        refined_time
+    } else {
+        sample_times[best_idx]
+    };
+
+    let orrery = timeline.get_orrery_at(time)?;
+    let state = orrery.get_ship_state(ship_id, time);
+    Some(ClosestApproach {
+        time,
+        distance: state.get_position(target.frame(), time).coords.norm(),
+        relative_speed: state.get_velocity(target.frame(), time).norm(),
+    })
+}
@@ -0,0 +1,400 @@
+//! Maneuvers saved for reuse. A template remembers its burns relative to the
+//! ship's orbit at the time, rather than as absolute vectors, so that it can
+//! be applied to another ship, or at another time, like circularizing after
+//! every transfer.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use nalgebra::Vector3;
+
+use super::orrery::{orbit_frame, Maneuver, ShipID};
+use super::timeline::Timeline;
+
+const HEADER: &str = "# reference-speed reference-period offset,prograde,normal,radial;... name";
+
+/// One burn of a template.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TemplateBurn {
+    /// Seconds after the template's first burn.
+    pub offset: f64,
+    /// Change in velocity along the prograde, normal and radial-out
+    /// directions of the orbit just before the burn.
+    pub delta_v: Vector3<f64>,
+}
+
+/// A named sequence of burns, saved from one ship's plan, which can be
+/// applied to others.
+///
+/// When it's applied, the burns are scaled to fit the new orbit: the changes
+/// in velocity in proportion to the orbital speed at the first burn, and the
+/// times between them in proportion to the period, if both orbits have one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManeuverTemplate {
+    pub name: String,
+    pub burns: Vec<TemplateBurn>,
+    /// Speed just before the first burn, when the template was saved.
+    pub reference_speed: f64,
+    /// Period of the orbit just before the first burn, if it's closed.
+    pub reference_period: Option<f64>,
+}
+
+impl ManeuverTemplate {
+    /// Saves the given ship's planned burns between the two times, inclusive,
+    /// as a template. Returns `None` if there aren't any.
+    ///
+    /// The timeline is extended as far as the last burn, so that the orbit
+    /// before each burn is known.
+    pub fn from_plan(
+        timeline: &mut Timeline,
+        ship_id: ShipID,
+        name: &str,
+        start_time: f64,
+        end_time: f64,
+    ) -> Option<Self> {
+        let orrery = timeline.get_orrery_at(start_time)?;
+        let maneuvers: Vec<Maneuver> = orrery
+            .get_ship(ship_id)
+            .maneuvers
+            .maneuvers()
+            .iter()
+            .filter(|m| start_time <= m.time && m.time <= end_time)
+            .copied()
+            .collect();
+        let first = maneuvers.first()?;
+        timeline.extend_until(maneuvers.last()?.time);
+
+        let trajectory = timeline.trajectory(ship_id);
+        let mut burns = vec![];
+        let mut reference = None;
+        for maneuver in maneuvers.iter() {
+            // The orbit before the burn is the last one to start before it. The
+            // burn may not have ended it yet, if another event happens at the
+            // same time and the timeline stopped there.
+            let patch = trajectory
+                .patches()
+                .iter()
+                .rev()
+                .find(|p| p.start_time() < maneuver.time)
+                .filter(|p| p.end_time().is_none_or(|end| end >= maneuver.time))?;
+            let before = patch.state_at_time(maneuver.time);
+            if reference.is_none() {
+                reference = Some((before.velocity().norm(), patch.orbit().period()));
+            }
+            let frame = orbit_frame(before.position(), before.velocity());
+            burns.push(TemplateBurn {
+                offset: maneuver.time - first.time,
+                delta_v: frame.transpose() * maneuver.delta_v,
+            });
+        }
+
+        let (reference_speed, reference_period) = reference?;
+        Some(Self {
+            name: name.to_owned(),
+            burns,
+            reference_speed,
+            reference_period,
+        })
+    }
+
+    /// Adds the template's burns to the given ship's plan, with the first one
+    /// at the given time, replacing any burns already planned at the same
+    /// times. Each burn is aimed using the orbit the earlier ones put the
+    /// ship on, so the timeline is extended as far as the last one.
+    pub fn apply(&self, timeline: &mut Timeline, ship_id: ShipID, time: f64) -> Result<(), String> {
+        let mut speed_scale = None;
+        let mut time_scale = 1.0;
+        for burn in self.burns.iter() {
+            let burn_time = time + burn.offset * time_scale;
+            timeline.extend_until(burn_time);
+            let orrery = timeline.get_orrery_at(burn_time).ok_or_else(|| {
+                format!("Can't burn before the timeline starts, at {}", burn_time)
+            })?;
+            let ship = orrery.get_ship(ship_id);
+            if ship.has_crashed() {
+                return Err(format!("Ship has crashed before the burn at {}", burn_time));
+            }
+
+            let state = ship.state_at_time(burn_time);
+            let speed_scale = *speed_scale.get_or_insert_with(|| {
+                // Everything after the first burn is spaced out to fit this orbit
+                if let (Some(new), Some(old)) = (ship.orbit.period(), self.reference_period) {
+                    time_scale = new / old;
+                }
+                state.velocity().norm() / self.reference_speed
+            });
+
+            let mut plan = ship.maneuvers.clone();
+            plan.set_maneuver(Maneuver {
+                time: burn_time,
+                delta_v: orbit_frame(state.position(), state.velocity())
+                    * burn.delta_v
+                    * speed_scale,
+            });
+            timeline.set_maneuver_plan(ship_id, plan);
+        }
+        Ok(())
+    }
+
+    fn to_line(&self) -> String {
+        let burns: Vec<_> = self
+            .burns
+            .iter()
+            .map(|b| {
+                format!(
+                    "{},{},{},{}",
+                    b.offset, b.delta_v.x, b.delta_v.y, b.delta_v.z
+                )
+            })
+            .collect();
+        let period = match self.reference_period {
+            Some(period) => period.to_string(),
+            None => String::from("-"),
+        };
+        format!(
+            "{} {} {} {}",
+            self.reference_speed,
+            period,
+            burns.join(";"),
+            self.name
+        )
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        // The name comes last, since it may contain spaces
+        let mut fields = line.splitn(4, ' ');
+        let mut next = || fields.next();
+        let reference_speed = next()?.parse().ok()?;
+        let reference_period = match next()? {
+            "-" => None,
+            s => Some(s.parse().ok()?),
+        };
+        let burns = next()?
+            .split(';')
+            .map(|burn| {
+                let values: Vec<f64> = burn
+                    .split(',')
+                    .map(|x| x.parse().ok())
+                    .collect::<Option<_>>()?;
+                match values[..] {
+                    [offset, prograde, normal, radial] => Some(TemplateBurn {
+                        offset,
+                        delta_v: Vector3::new(prograde, normal, radial),
+                    }),
+                    _ => None,
+                }
+            })
+            .collect::<Option<_>>()?;
+        let name = next()?.to_owned();
+        Some(Self {
+            name,
+            burns,
+            reference_speed,
+            reference_period,
+        })
+    }
+}
+
+/// A template to apply, and when to start it, written `name@time`, with the
+/// time in seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateUse {
+    pub name: String,
+    pub time: f64,
+}
+
+impl FromStr for TemplateUse {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, time) = s
+            .rsplit_once('@')
+            .ok_or_else(|| format!("Expected name@time, but got {:?}", s))?;
+        let time = time
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid time {:?}", time))?;
+        Ok(TemplateUse {
+            name: name.trim().to_owned(),
+            time,
+        })
+    }
+}
+
+/// The user's maneuver templates, kept in sync with a file if one was given.
+#[derive(Debug, Default)]
+pub struct TemplateLibrary {
+    path: Option<PathBuf>,
+    templates: Vec<ManeuverTemplate>,
+}
+
+impl TemplateLibrary {
+    /// Templates that aren't saved anywhere.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads templates from the given file, which is created when the first
+    /// template is saved if it doesn't exist yet. Lines that can't be parsed
+    /// are skipped.
+    pub fn load(path: PathBuf) -> io::Result<Self> {
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+
+        let mut templates = vec![];
+        for line in contents.lines() {
+            if line.starts_with('#') || line.trim().is_empty() {
+                continue;
+            }
+            match ManeuverTemplate::from_line(line) {
+                Some(template) => templates.push(template),
+                None => println!("Skipping malformed maneuver template: {}", line),
+            }
+        }
+
+        Ok(Self {
+            path: Some(path),
+            templates,
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ManeuverTemplate> {
+        self.templates.iter().find(|t| t.name == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ManeuverTemplate> {
+        self.templates.iter()
+    }
+
+    /// Adds the template, replacing any with the same name, and saves the
+    /// templates to their file.
+    pub fn add(&mut self, template: ManeuverTemplate) -> io::Result<()> {
+        match self.templates.iter_mut().find(|t| t.name == template.name) {
+            Some(existing) => *existing = template,
+            None => self.templates.push(template),
+        }
+        self.save()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let mut contents = format!("{}\n", HEADER);
+        for template in self.templates.iter() {
+            contents.push_str(&template.to_line());
+            contents.push('\n');
+        }
+        fs::write(path, contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::file::read_file;
+    use crate::model::orrery::{BodyID, ManeuverPlan};
+
+    const KERBIN: BodyID = BodyID(4);
+
+    #[test]
+    fn test_template() {
+        // Two ships in circular orbits of different sizes
+        let mut orrery = read_file("ksp-bodies.txt");
+        let mu = orrery.get_body(KERBIN).info.mu;
+        let circular = |radius: f64| (mu / radius).sqrt();
+        let low_id = orrery.add_ship(
+            Vector3::x() * 700000.0,
+            Vector3::y() * circular(700000.0),
+            0.0,
+            KERBIN,
+        );
+        let high_id = orrery.add_ship(
+            Vector3::x() * 1400000.0,
+            Vector3::y() * circular(1400000.0),
+            0.0,
+            KERBIN,
+        );
+        let low_period = orrery.get_ship(low_id).orbit.period().unwrap();
+        let high_period = orrery.get_ship(high_id).orbit.period().unwrap();
+
+        // The low one raises its orbit, and then turns a bit out of plane
+        // half an orbit later
+        let mut timeline = Timeline::new(orrery, 0.0);
+        let prograde_at = |timeline: &Timeline, id, time| {
+            let orrery = timeline.get_orrery_at(time).unwrap();
+            let velocity = orrery.get_ship(id).state_at_time(time).velocity();
+            velocity.normalize()
+        };
+        let mut plan = ManeuverPlan::new();
+        plan.set_maneuver(Maneuver {
+            time: 100.0,
+            delta_v: prograde_at(&timeline, low_id, 100.0) * 50.0,
+        });
+        timeline.set_maneuver_plan(low_id, plan);
+        timeline.extend_until(100.0);
+        let orrery = timeline.get_orrery_at(100.0).unwrap();
+        let next_time = 100.0 + orrery.get_ship(low_id).orbit.period().unwrap() / 2.0;
+        let mut plan = orrery.get_ship(low_id).maneuvers.clone();
+        plan.set_maneuver(Maneuver {
+            time: next_time,
+            delta_v: Vector3::z() * 20.0,
+        });
+        timeline.set_maneuver_plan(low_id, plan);
+
+        let template =
+            ManeuverTemplate::from_plan(&mut timeline, low_id, "raise", 0.0, 1e6).unwrap();
+        assert_eq!(template.burns.len(), 2);
+        assert_relative_eq!(
+            template.burns[0].delta_v,
+            Vector3::x() * 50.0,
+            epsilon = 1e-9
+        );
+        assert_relative_eq!(template.burns[1].offset, next_time - 100.0);
+        // Normal to the orbit is still +z
+        assert_relative_eq!(
+            template.burns[1].delta_v,
+            Vector3::y() * 20.0,
+            epsilon = 1e-6
+        );
+        assert_relative_eq!(template.reference_speed, circular(700000.0));
+        assert_relative_eq!(template.reference_period.unwrap(), low_period);
+
+        // It survives being written out and read back
+        let parsed = ManeuverTemplate::from_line(&template.to_line()).unwrap();
+        assert_eq!(parsed, template);
+
+        // Applied to the high ship, the burns are slower and further apart,
+        // but in the same directions relative to its orbit
+        let start = 500.0;
+        template.apply(&mut timeline, high_id, start).unwrap();
+        let orrery = timeline.get_orrery_at(start).unwrap();
+        let maneuvers = orrery.get_ship(high_id).maneuvers.maneuvers().to_vec();
+        assert_eq!(maneuvers.len(), 2);
+        let speed_ratio = circular(1400000.0) / circular(700000.0);
+        assert_relative_eq!(maneuvers[0].time, start);
+        assert_relative_eq!(
+            maneuvers[0].delta_v,
+            prograde_at(&timeline, high_id, start) * 50.0 * speed_ratio,
+            max_relative = 1e-9
+        );
+        assert_relative_eq!(
+            maneuvers[1].time - start,
+            (next_time - 100.0) * high_period / low_period,
+            max_relative = 1e-9
+        );
+        assert_relative_eq!(
+            maneuvers[1].delta_v,
+            Vector3::z() * 20.0 * speed_ratio,
+            epsilon = 1e-6
+        );
+    }
+}
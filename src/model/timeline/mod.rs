@@ -1,22 +1,71 @@
+use std::collections::BTreeMap;
+
+use nalgebra::Point3;
+
 use self::upcoming_events::EventSearchHorizons;
-use super::events::{search_for_soi_encounter, search_for_soi_escape, Event, EventTag};
-use super::orrery::Orrery;
+use super::events::{
+    search_for_apoapsis, search_for_atmosphere_entry, search_for_atmosphere_exit,
+    search_for_eclipse_entry, search_for_eclipse_exit, search_for_impact,
+    search_for_integration_start, search_for_integration_zone_entry,
+    search_for_integration_zone_exit, search_for_maneuver, search_for_periapsis,
+    search_for_sail_attitude_change, search_for_shadow_entry, search_for_shadow_exit,
+    search_for_sight_loss, search_for_sight_regain, search_for_soi_encounter,
+    search_for_soi_escape, search_for_staging, Event, EventData, EventPoint, EventTag,
+};
+use super::orrery::{
+    BodyEdit, BodyID, ManeuverPlan, OrbitElements, Orrery, Propagation, SailSchedule, ShadowRegion,
+    ShipID, ShipPlacement,
+};
 
+mod trajectory;
 mod upcoming_events;
 
+pub use trajectory::{Trajectory, TrajectoryPatch};
+
 /// Models the state of the universe as a sequence of [Orrery]s separated by
 /// [Event]s.
 ///
-/// The timeline consists of a sequence of [ClosedSegment]s followed by an
-/// [OpenSegment]; segments are considered half-open, including the start time
-/// but not the end.
+/// Since ships don't affect each other, each ship has its own [ShipHistory]:
+/// a sequence of [ClosedSegment]s followed by an [OpenSegment], whose orreries
+/// have the bodies and that ship alone. An event only splits the segments of
+/// the ship it happened to, and the state of the whole universe is put back
+/// together when it's asked for. Segments are considered half-open, including
+/// the start time but not the end.
 #[derive(Debug)]
 pub struct Timeline {
+    start_time: f64,
+    // The bodies as of the start, before any modifications, without any ships
+    bodies: Orrery,
+    histories: BTreeMap<ShipID, ShipHistory>,
+    // How far we've been asked to search for events
+    searched_until: f64,
+    // Sorted by time
+    modifications: Vec<BodyModification>,
+}
+
+/// A change made to a body partway through the timeline, for trying out
+/// hypotheticals. Unlike events, these are made by the user, rather than found,
+/// but each ship's segment is still split where it's made, with an
+/// [EventData::Modification], so that the ship is unaffected before then.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BodyModification {
+    pub time: f64,
+    pub body_id: BodyID,
+    pub edit: BodyEdit,
+}
+
+#[derive(Debug)]
+struct ShipHistory {
     // Invariants:
     //   - The `start_time` of each closed segment are sorted in ascending order, and the open
     //     segment is later than all of them.
+    //   - Each segment's orrery has the modifications made up to when the segment starts, and no
+    //     later ones. A later modification splits the segment when the history is extended.
     closed_segments: Vec<ClosedSegment>,
     open_segment: OpenSegment,
+    // Events aren't searched for before this, e.g., for ships added partway
+    // through, which just follow their initial orbit until then
+    search_start: f64,
 }
 
 #[derive(Debug)]
@@ -42,14 +91,458 @@ enum SegmentLookup {
 
 impl Timeline {
     /// Create a new Timeline with the given starting state.
-    pub fn new(orrery: Orrery, start_time: f64) -> Self {
+    pub fn new(mut orrery: Orrery, start_time: f64) -> Self {
+        orrery.advance_precession(start_time);
+        let histories = orrery
+            .ships()
+            .map(|ship| {
+                let mut ship_orrery = orrery.without_ships();
+                ship_orrery.insert_ship(ship.clone());
+                // Ships keeping line of sight to another one need to know where
+                // it is, so they simulate it alongside themselves
+                if let Some(target_id) = ship.line_of_sight_target {
+                    ship_orrery.insert_ship(orrery.get_ship(target_id).clone());
+                }
+                (
+                    ship.id,
+                    ShipHistory::new(start_time, ship_orrery, start_time),
+                )
+            })
+            .collect();
+        Self {
+            start_time,
+            bodies: orrery.without_ships(),
+            histories,
+            searched_until: start_time,
+            modifications: vec![],
+        }
+    }
+
+    /// Get the orrery corresponding to the given time. It's put together from
+    /// each ship's segment at that time, with the bodies as modified up to
+    /// then.
+    pub fn get_orrery_at(&self, time: f64) -> Option<Orrery> {
+        if time < self.start_time {
+            return None;
+        }
+
+        let mut orrery = self.bodies_at(time);
+        orrery.advance_precession(time);
+        for (id, history) in self.histories.iter() {
+            let ship_orrery = history.orrery_at(time)?;
+            orrery.insert_ship(ship_orrery.get_ship(*id).clone());
+        }
+        Some(orrery)
+    }
+
+    /// Returns the bodies, without any ships, with all the modifications made
+    /// up to the given time.
+    fn bodies_at(&self, time: f64) -> Orrery {
+        let mut orrery = self.bodies.clone();
+        for m in self.modifications.iter().filter(|m| m.time <= time) {
+            orrery.edit_body(m.body_id, &m.edit, m.time);
+        }
+        orrery
+    }
+
+    /// Return the start time of this timeline.
+    pub fn start_time(&self) -> f64 {
+        self.start_time
+    }
+
+    /// Return how far into the future this timeline is known. This is at least
+    /// as far as it's been extended, and may be a little past that, since the
+    /// last event found for each ship can be later than the time searched for.
+    pub fn end_time(&self) -> f64 {
+        let known_until = self
+            .histories
+            .values()
+            .map(|h| h.open_segment.start_time)
+            .reduce(f64::min);
+        match known_until {
+            Some(t) => f64::max(self.searched_until, t),
+            None => self.searched_until,
+        }
+    }
+
+    /// Returns the times at which one segment ends and the next begins, in
+    /// ascending order. These are the times of the events in the timeline.
+    pub fn segment_boundaries(&self) -> impl Iterator<Item = f64> + '_ {
+        self.events().map(|e| e.point.time)
+    }
+
+    /// Search until the given time for any new events, potentially creating
+    /// new segments if events are found.
+    pub fn extend_until(&mut self, time: f64) {
+        for (id, history) in self.histories.iter_mut() {
+            history.extend_until(*id, time, &self.modifications);
+        }
+        self.searched_until = f64::max(self.searched_until, time);
+    }
+
+    /// Search backwards from the start of the timeline until the given time,
+    /// adding segments for any events found. This reconstructs the history of
+    /// ships that are only known as of the start, like ones imported with
+    /// their present-day orbits.
+    ///
+    /// The search is done by extending a [time-reversed](Orrery::time_reversed)
+    /// copy of the start of the timeline, and mirroring whatever it finds. Only
+    /// conics can be run backwards, so ships are assumed to have been following
+    /// them the whole time, and the search stops at any impact, since there's
+    /// no telling when the ship took off. Modifications only apply from when
+    /// they're made, so the history has the bodies as they were at the start.
+    pub fn extend_back_until(&mut self, time: f64) {
+        let start_time = self.start_time;
+        if time >= start_time {
+            return;
+        }
+
+        // Reversed time runs forwards from our start time
+        let mirror = |t: f64| 2.0 * start_time - t;
+        let first_orrery = self.get_orrery_at(start_time).unwrap();
+        let mut reversed = Timeline::new(
+            first_orrery.time_reversed(start_time, start_time),
+            start_time,
+        );
+        reversed.extend_until(mirror(time));
+
+        // Events can be found past the time we searched until, so ignore those,
+        // and stop at the first one that can't be run backwards, for any ship
+        let irreversible_time = reversed
+            .events()
+            .take_while(|e| e.point.time <= mirror(time))
+            .find(|e| e.data.reversed().is_none())
+            .map(|e| e.point.time);
+        let is_kept = |e: &&Event| {
+            e.point.time <= mirror(time) && irreversible_time.is_none_or(|t| e.point.time < t)
+        };
+        let new_start_time = irreversible_time.map_or(time, mirror);
+
+        for (id, history) in self.histories.iter_mut() {
+            let reversed_history = &reversed.histories[id];
+            let reversed_orreries: Vec<_> = reversed_history.orreries().collect();
+            let reversed_events: Vec<_> = reversed_history.events().filter(is_kept).collect();
+            // Where the segment after the given reversed event starts, once mirrored
+            let mirrored_start = |idx: usize| match reversed_events.get(idx) {
+                Some(event) => mirror(event.point.time),
+                None => new_start_time,
+            };
+
+            // The first reversed segment is our first segment, just run
+            // backwards, and the ones after it become the new history, latest
+            // first
+            let mut segments = vec![];
+            for (idx, event) in reversed_events.iter().enumerate() {
+                let end_time = mirror(event.point.time);
+                let orrery = reversed_orreries[idx + 1].time_reversed(event.point.time, end_time);
+                // This may be an event for a ship simulated alongside this one
+                let ship = orrery.get_ship(event.ship_id);
+                let ending_event = Event {
+                    ship_id: event.ship_id,
+                    data: event.data.reversed().unwrap(),
+                    point: EventPoint {
+                        time: end_time,
+                        anomaly: ship.orbit.s_at_time(end_time),
+                        location: Point3::from(ship.state_at_time(end_time).position()),
+                    },
+                };
+                segments.push(ClosedSegment {
+                    start_time: mirrored_start(idx + 1),
+                    orrery,
+                    ending_event,
+                });
+            }
+
+            // The first segment now starts earlier, so its searches have to as well
+            let first_start = mirrored_start(0);
+            match history.closed_segments.first_mut() {
+                Some(segment) => segment.start_time = first_start,
+                None => {
+                    let orrery = std::mem::take(&mut history.open_segment.orrery);
+                    history.open_segment = OpenSegment::new(first_start, orrery);
+                    self.searched_until = f64::min(self.searched_until, first_start);
+                }
+            }
+            history.search_start = new_start_time;
+
+            segments.reverse();
+            segments.append(&mut history.closed_segments);
+            history.closed_segments = segments;
+        }
+        self.start_time = new_start_time;
+    }
+
+    /// Replaces the schedule of the given ship's sail. The ship's history is
+    /// kept up to the first time the new schedule differs from the old one,
+    /// and everything after that is discarded, to be recomputed when the
+    /// timeline is extended again.
+    pub fn set_sail_schedule(&mut self, ship_id: ShipID, schedule: SailSchedule) {
+        let history = &self.histories[&ship_id];
+        let change_time = match &history.open_segment.orrery.get_ship(ship_id).sail {
+            Some(sail) => sail.schedule.first_difference(&schedule),
+            None => panic!("Ship {} doesn't have a sail", ship_id.0),
+        };
+        let change_time = match change_time {
+            Some(t) => t,
+            None => return,
+        };
+
+        for history in histories_simulating(&mut self.histories, ship_id) {
+            // Reopen the segment where the change happens. Its orrery is still
+            // valid, since the schedules agree up to then, but any arc it has
+            // may need to be cut short.
+            history.reopen_at(change_time);
+            self.searched_until = f64::min(self.searched_until, history.open_segment.start_time);
+
+            // Earlier segments are unaffected, but keep them consistent anyways
+            for segment in history.closed_segments.iter_mut() {
+                segment.orrery.set_sail_schedule(ship_id, schedule.clone());
+            }
+            history
+                .open_segment
+                .orrery
+                .set_sail_schedule(ship_id, schedule.clone());
+        }
+    }
+
+    /// Replaces the given ship's planned maneuvers. Like with
+    /// [Timeline::set_sail_schedule], the ship's history is kept up to the
+    /// first maneuver that's different, and everything after that is
+    /// discarded. Only that ship's path can change, so the other ships are
+    /// left alone, which keeps editing a maneuver quick, even partway through
+    /// a long timeline.
+    ///
+    /// Maneuvers at or before the start of the timeline are never made.
+    pub fn set_maneuver_plan(&mut self, ship_id: ShipID, plan: ManeuverPlan) {
+        let change_time = self.histories[&ship_id]
+            .open_segment
+            .orrery
+            .get_ship(ship_id)
+            .maneuvers
+            .first_difference(&plan);
+        let change_time = match change_time {
+            Some(t) => t,
+            None => return,
+        };
+
+        for history in histories_simulating(&mut self.histories, ship_id) {
+            // The segment ending with the changed maneuver has to be reopened,
+            // so that it's found again
+            history.reopen_at(change_time);
+            self.searched_until = f64::min(self.searched_until, history.open_segment.start_time);
+
+            for segment in history.closed_segments.iter_mut() {
+                segment.orrery.set_maneuver_plan(ship_id, plan.clone());
+            }
+            history
+                .open_segment
+                .orrery
+                .set_maneuver_plan(ship_id, plan.clone());
+        }
+    }
+
+    /// Renames the given ship, everywhere in its history. Names don't affect
+    /// anything else, so nothing needs to be recomputed.
+    pub fn set_ship_name(&mut self, ship_id: ShipID, name: Option<String>) {
+        for history in histories_simulating(&mut self.histories, ship_id) {
+            for segment in history.closed_segments.iter_mut() {
+                segment.orrery.set_ship_name(ship_id, name.clone());
+            }
+            history
+                .open_segment
+                .orrery
+                .set_ship_name(ship_id, name.clone());
+        }
+    }
+
+    /// Moves the given ship onto a new orbit around its parent at the given
+    /// time, with the mean anomaly as of then. Like [Timeline::edit_body],
+    /// everything after that is discarded, and the ship's segment is split
+    /// there, so it follows its old path up to then. The ship's planned burns
+    /// are kept.
+    pub fn set_ship_orbit(&mut self, ship_id: ShipID, elements: OrbitElements, time: f64) {
+        for history in histories_simulating(&mut self.histories, ship_id) {
+            let orrery = history.split_at(ship_id, time, &self.modifications);
+            let parent_id = orrery.get_ship(ship_id).parent_id();
+            let parent = orrery.get_body(parent_id).clone();
+            orrery.set_ship_orbit(ship_id, elements.orbit(parent, time));
+            self.searched_until = f64::min(self.searched_until, time);
+        }
+    }
+
+    /// Adds a new ship to the timeline, placed as given. The other ships'
+    /// histories are unaffected.
+    ///
+    /// The ship has a trajectory over the whole timeline, but before the
+    /// placement's epoch it just follows its initial orbit, and no events are
+    /// searched for.
+    pub fn add_ship(&mut self, placement: &ShipPlacement) -> ShipID {
+        let search_start = f64::max(placement.epoch, self.start_time);
+        let mut orrery = self.get_orrery_at(search_start).unwrap();
+        let (position, velocity) = placement.state(&orrery);
+        let id = orrery.add_ship(position, velocity, placement.epoch, placement.parent_id);
+
+        // The bodies already have the modifications up to the epoch, and the
+        // later ones split the history when it's extended
+        let mut ship_orrery = orrery.without_ships();
+        ship_orrery.insert_ship(orrery.get_ship(id).clone());
+
+        self.histories.insert(
+            id,
+            ShipHistory::new(self.start_time, ship_orrery, search_start),
+        );
+        self.searched_until = f64::min(self.searched_until, search_start);
+        id
+    }
+
+    /// Takes a ship out of the timeline, as if it had never been there. The
+    /// other ships' histories are unaffected.
+    ///
+    /// Ships that other ships keep line of sight to can't be removed, since
+    /// they're simulated alongside them.
+    pub fn remove_ship(&mut self, ship_id: ShipID) -> Result<(), String> {
+        let tracker = self.histories.iter().find(|(id, history)| {
+            **id != ship_id
+                && history
+                    .open_segment
+                    .orrery
+                    .ships()
+                    .any(|ship| ship.id == ship_id)
+        });
+        if let Some((tracker_id, _)) = tracker {
+            return Err(format!(
+                "Ship {} keeps line of sight to ship {}",
+                tracker_id.0, ship_id.0
+            ));
+        }
+
+        let history = match self.histories.remove(&ship_id) {
+            Some(history) => history,
+            None => return Err(format!("No ship {}", ship_id.0)),
+        };
+        // New ships get their IDs from the bodies' orrery, so have it keep
+        // track of this one, so that it isn't reused
+        let mut ship_orrery = history.open_segment.orrery;
+        if let Some(ship) = ship_orrery.remove_ship(ship_id) {
+            self.bodies.insert_ship(ship);
+            self.bodies.remove_ship(ship_id);
+        }
+        Ok(())
+    }
+
+    /// Changes some of a body's physical properties, from the given time on.
+    /// Everything after that is discarded, to be recomputed when the timeline
+    /// is extended again, since the new body changes the orbits around it, and
+    /// the SOIs of it and its children. See [Orrery::edit_body].
+    ///
+    /// When the timeline is extended, each ship's segment containing the given
+    /// time is split there, so objects around the body only follow their new
+    /// orbits from then on.
+    ///
+    /// Fails, without changing anything, if this or any later edit couldn't be
+    /// made anymore. See [Orrery::check_edit].
+    pub fn edit_body(&mut self, body_id: BodyID, edit: BodyEdit, time: f64) -> Result<(), String> {
+        let idx = self.modifications.partition_point(|m| m.time <= time);
+        let modification = BodyModification {
+            time,
+            body_id,
+            edit,
+        };
+        let (before, after) = self.modifications.split_at(idx);
+        let mut orrery = self.bodies.clone();
+        for m in before
+            .iter()
+            .chain(std::iter::once(&modification))
+            .chain(after)
+        {
+            orrery.check_edit(m.body_id, &m.edit, m.time)?;
+            orrery.edit_body(m.body_id, &m.edit, m.time);
+        }
+
+        for history in self.histories.values_mut() {
+            history.reopen_at(time);
+            self.searched_until = f64::min(self.searched_until, history.open_segment.start_time);
+        }
+
+        self.modifications.insert(idx, modification);
+        Ok(())
+    }
+
+    /// Returns the changes made to bodies, in order of when they happen.
+    pub fn modifications(&self) -> impl Iterator<Item = &BodyModification> {
+        self.modifications.iter()
+    }
+
+    /// Returns every ship's events, in order of when they happen. Edits are
+    /// left out, since they're in [Timeline::modifications], rather than
+    /// something the ship did.
+    pub fn events(&self) -> impl Iterator<Item = &Event> {
+        // Leave out the events of ships simulated alongside others, which are
+        // already in their own histories
+        let mut events: Vec<_> = self
+            .histories
+            .iter()
+            .flat_map(|(id, h)| h.events().filter(move |e| e.ship_id == *id))
+            .filter(|e| e.data != EventData::Modification)
+            .collect();
+        events.sort_by(|a, b| a.point.compare_time(&b.point));
+        events.into_iter()
+    }
+
+    /// Returns the first event with the given tag for the given ship, strictly
+    /// after the given time. Only events found so far are considered, so the
+    /// timeline may need to be extended first.
+    pub fn next_event(&self, ship_id: ShipID, tag: &EventTag, time: f64) -> Option<&Event> {
+        self.histories[&ship_id]
+            .events()
+            .find(|e| e.ship_id == ship_id && e.point.time > time && e.data.tag() == *tag)
+    }
+
+    /// Get the path of the given ship over the whole timeline, as far as it
+    /// has been extended.
+    pub fn trajectory(&self, ship_id: ShipID) -> Trajectory {
+        let history = &self.histories[&ship_id];
+        let closed = history.closed_segments.iter().map(|seg| {
+            (
+                seg.start_time,
+                seg.orrery.get_ship(ship_id),
+                Some(&seg.ending_event),
+            )
+        });
+        let open = std::iter::once((
+            history.open_segment.start_time,
+            history.open_segment.orrery.get_ship(ship_id),
+            None,
+        ));
+        Trajectory::from_segments(ship_id, closed.chain(open))
+    }
+}
+
+/// Returns the histories that the given ship is simulated in: its own, and
+/// those of any ships keeping line of sight to it.
+fn histories_simulating(
+    histories: &mut BTreeMap<ShipID, ShipHistory>,
+    ship_id: ShipID,
+) -> impl Iterator<Item = &mut ShipHistory> {
+    histories
+        .values_mut()
+        .filter(move |h| h.open_segment.orrery.ships().any(|ship| ship.id == ship_id))
+}
+
+impl ShipHistory {
+    fn new(start_time: f64, orrery: Orrery, search_start: f64) -> Self {
         Self {
             closed_segments: vec![],
-            open_segment: OpenSegment::new(start_time, orrery),
+            open_segment: OpenSegment {
+                start_time,
+                orrery,
+                search_horizons: EventSearchHorizons::new(search_start),
+            },
+            search_start,
         }
     }
 
-    /// Search the timeline for the segment containing the given time.
+    /// Search the history for the segment containing the given time.
     fn lookup_segment(&self, time: f64) -> SegmentLookup {
         // Check whether it's in the open segment
         if time >= self.open_segment.start_time {
@@ -74,48 +567,122 @@ impl Timeline {
         SegmentLookup::Closed(segment_idx)
     }
 
-    /// Get the orrery corresponding to the given time.
-    pub fn get_orrery_at(&self, time: f64) -> Option<&Orrery> {
+    fn orrery_at(&self, time: f64) -> Option<&Orrery> {
         match self.lookup_segment(time) {
-            SegmentLookup::Closed(idx) => {
-                let orrery = &self.closed_segments[idx].orrery;
-                Some(orrery)
-            }
-            SegmentLookup::Open => {
-                let orrery = &self.open_segment.orrery;
-                Some(orrery)
-            }
+            SegmentLookup::Closed(idx) => Some(&self.closed_segments[idx].orrery),
+            SegmentLookup::Open => Some(&self.open_segment.orrery),
             SegmentLookup::BeforeStart => None,
         }
     }
 
-    /// Return the start time of this timeline.
-    pub fn start_time(&self) -> f64 {
-        if let Some(closed_segment) = self.closed_segments.first() {
-            closed_segment.start_time
-        } else {
-            self.open_segment.start_time
-        }
+    /// The orrery of each segment, in order.
+    fn orreries(&self) -> impl Iterator<Item = &Orrery> {
+        self.closed_segments
+            .iter()
+            .map(|seg| &seg.orrery)
+            .chain(std::iter::once(&self.open_segment.orrery))
     }
 
-    /// Search until the given time for any new events, potentially creating
-    /// new segments if events are found.
-    pub fn extend_until(&mut self, time: f64) {
+    fn events(&self) -> impl Iterator<Item = &Event> {
+        self.closed_segments.iter().map(|seg| &seg.ending_event)
+    }
+
+    /// Searches for events until the given time, splitting the open segment at
+    /// each one, and at each modification, which is made to the new segment.
+    /// The ship's own history is the one given.
+    fn extend_until(&mut self, ship_id: ShipID, time: f64, modifications: &[BodyModification]) {
         // Search for the next event. If we find one, add a new segment and repeat!
         // Otherwise, do nothing; the UpcomingEvents struct will save our progress.
-        while let Some(closed_segment) = self.open_segment.split_at_next_event(time) {
+        loop {
+            // Modifications up to here are already in the open segment's orrery
+            let made_until = f64::max(self.open_segment.start_time, self.search_start);
+            let next_modification = modifications
+                .iter()
+                .map(|m| m.time)
+                .find(|t| *t > made_until);
+
+            // Events can be found past the given time, and modifications
+            // before them still have to split the segment. An event at the
+            // same time comes first, since it might not be found again from
+            // the start of a new segment.
+            self.open_segment.search_for_events_until(time);
+            let next_event = self
+                .open_segment
+                .search_horizons
+                .get_next_event()
+                .map(|e| e.point.time);
+            let closed_segment = match (next_modification, next_event) {
+                (Some(t), Some(event_time)) if t < event_time => {
+                    self.open_segment.split_at(ship_id, t, self.search_start)
+                }
+                (Some(t), None) if t <= time => {
+                    self.open_segment.split_at(ship_id, t, self.search_start)
+                }
+                (_, Some(_)) => self.open_segment.split_at_next_event(time).unwrap(),
+                _ => break,
+            };
+
             let event = &closed_segment.ending_event;
             println!(
                 "When extending end time to {}, found event at time {} for ship {}: {:?}",
                 time, event.point.time, event.ship_id.0, event.data
             );
+            let split_time = event.point.time;
+            for m in modifications
+                .iter()
+                .filter(|m| made_until < m.time && m.time <= split_time)
+            {
+                self.open_segment
+                    .orrery
+                    .edit_body(m.body_id, &m.edit, m.time);
+            }
 
             self.closed_segments.push(closed_segment);
         }
     }
 
-    pub fn events(&self) -> impl Iterator<Item = &Event> {
-        self.closed_segments.iter().map(|seg| &seg.ending_event)
+    /// Makes the segment containing the given time the open segment, throwing
+    /// away all later segments, and any events found after its start.
+    fn reopen_at(&mut self, time: f64) {
+        let idx = self
+            .closed_segments
+            .partition_point(|seg| seg.start_time < time);
+        let in_open_segment =
+            idx == self.closed_segments.len() && (self.open_segment.start_time < time || idx == 0);
+        if !in_open_segment {
+            self.closed_segments.truncate(usize::max(idx, 1));
+            let reopened = self.closed_segments.pop().unwrap();
+            self.open_segment.start_time = reopened.start_time;
+            self.open_segment.orrery = reopened.orrery;
+        }
+
+        let search_start = f64::max(self.open_segment.start_time, self.search_start);
+        self.open_segment.search_horizons = EventSearchHorizons::new(search_start);
+    }
+
+    /// Throws away everything after the given time, and makes the open segment
+    /// start at exactly that time, splitting the segment it's in if needed.
+    /// Returns the open segment's orrery, so that a change can be made to it
+    /// without changing the given ship's path before then.
+    fn split_at(
+        &mut self,
+        ship_id: ShipID,
+        time: f64,
+        modifications: &[BodyModification],
+    ) -> &mut Orrery {
+        // Bring the ship up to this time, with any events and modifications
+        // along the way, but not past it
+        self.reopen_at(time);
+        self.extend_until(ship_id, time, modifications);
+        if self.open_segment.start_time > time {
+            self.reopen_at(time);
+        }
+
+        if self.open_segment.start_time < time {
+            let closed_segment = self.open_segment.split_at(ship_id, time, self.search_start);
+            self.closed_segments.push(closed_segment);
+        }
+        &mut self.open_segment.orrery
     }
 }
 
@@ -128,14 +695,46 @@ impl OpenSegment {
         }
     }
 
+    /// Ends this segment at the given time with an [EventData::Modification]
+    /// for the given ship, and starts a new one with the same orrery, ready
+    /// for the change to be made to it.
+    fn split_at(&mut self, ship_id: ShipID, time: f64, search_start: f64) -> ClosedSegment {
+        let ship = self.orrery.get_ship(ship_id);
+        let event = Event {
+            ship_id,
+            data: EventData::Modification,
+            point: EventPoint {
+                time,
+                anomaly: ship.orbit.s_at_time(time),
+                location: Point3::from(ship.state_at_time(time).position()),
+            },
+        };
+
+        let mut new_open = OpenSegment::new(time, self.orrery.clone());
+        new_open.search_horizons = EventSearchHorizons::new(f64::max(time, search_start));
+        new_open.orrery.advance_precession(time);
+        new_open.orrery.rebase_epochs(time);
+
+        let old_open = std::mem::replace(self, new_open);
+        ClosedSegment {
+            start_time: old_open.start_time,
+            orrery: old_open.orrery,
+            ending_event: event,
+        }
+    }
+
     fn split_at_next_event(&mut self, time: f64) -> Option<ClosedSegment> {
         self.search_for_events_until(time);
         let event = self.search_horizons.get_next_event()?.clone();
         let event_time = event.point.time;
 
         // Make a new open segment to replace this one
+        // Precess before processing the event, so that any orbits it creates
+        // aren't precessed for time they didn't exist.
         let mut new_open = OpenSegment::new(event_time, self.orrery.clone());
+        new_open.orrery.advance_precession(event_time);
         new_open.orrery.process_event(&event);
+        new_open.orrery.rebase_epochs(event_time);
 
         // Swap in the new one, and decompose the old one into a closed segment
         let old_open = std::mem::replace(self, new_open);
@@ -154,9 +753,195 @@ impl OpenSegment {
             return;
         }
 
-        for id in self.orrery.ships().map(|s| s.id) {
+        // Crashed ships can't have any more events
+        let ship_ids: Vec<_> = self
+            .orrery
+            .ships()
+            .filter(|s| !s.has_crashed())
+            .map(|s| s.id)
+            .collect();
+
+        for id in ship_ids {
             // TODO: can i skip the search if i've advanced all horizons far enough?
 
+            // Planned burns happen however the ship is being propagated
+            self.search_horizons.search_until(
+                id,
+                EventTag::Maneuver,
+                end_time,
+                |search_start, _| search_for_maneuver(&self.orrery, id, search_start),
+            );
+
+            // Ships pushed by sunlight care about when they're in shadow
+            let ship = self.orrery.get_ship(id);
+            if ship.is_sunlight_sensitive() {
+                self.search_horizons.search_until(
+                    id,
+                    EventTag::EnterShadow,
+                    end_time,
+                    |search_start, search_end| {
+                        search_for_shadow_entry(&self.orrery, id, search_start, search_end)
+                    },
+                );
+                self.search_horizons.search_until(
+                    id,
+                    EventTag::ExitShadow,
+                    end_time,
+                    |search_start, search_end| {
+                        search_for_shadow_exit(&self.orrery, id, search_start, search_end)
+                    },
+                );
+            }
+
+            if ship.track_eclipses {
+                for region in [ShadowRegion::Penumbra, ShadowRegion::Umbra] {
+                    self.search_horizons.search_until(
+                        id,
+                        EventTag::EnterEclipse(region),
+                        end_time,
+                        |search_start, search_end| {
+                            search_for_eclipse_entry(
+                                &self.orrery,
+                                id,
+                                search_start,
+                                search_end,
+                                region,
+                            )
+                        },
+                    );
+                    self.search_horizons.search_until(
+                        id,
+                        EventTag::ExitEclipse(region),
+                        end_time,
+                        |search_start, search_end| {
+                            search_for_eclipse_exit(
+                                &self.orrery,
+                                id,
+                                search_start,
+                                search_end,
+                                region,
+                            )
+                        },
+                    );
+                }
+            }
+
+            if ship.line_of_sight_target.is_some() {
+                self.search_horizons.search_until(
+                    id,
+                    EventTag::LoseSight,
+                    end_time,
+                    |search_start, search_end| {
+                        search_for_sight_loss(&self.orrery, id, search_start, search_end)
+                    },
+                );
+                self.search_horizons.search_until(
+                    id,
+                    EventTag::RegainSight,
+                    end_time,
+                    |search_start, search_end| {
+                        search_for_sight_regain(&self.orrery, id, search_start, search_end)
+                    },
+                );
+            }
+
+            // Numerically propagated ships should always be on an arc, and if
+            // they're not, the next thing they do is start one
+            if ship.numeric_arc.is_none() {
+                if let Propagation::Numeric { .. } = ship.propagation {
+                    self.search_horizons.search_until(
+                        id,
+                        EventTag::EnterIntegrationZone(ship.parent_id()),
+                        end_time,
+                        |search_start, _| {
+                            search_for_integration_start(&self.orrery, id, search_start)
+                        },
+                    );
+                    continue;
+                }
+            }
+
+            // While a ship is being integrated, its orbit doesn't mean anything, so
+            // the only thing to look for is the end of the numeric arc
+            if ship.numeric_arc.is_some() {
+                self.search_horizons.search_until(
+                    id,
+                    EventTag::ExitIntegrationZone,
+                    end_time,
+                    |_, _| search_for_integration_zone_exit(&self.orrery, id),
+                );
+                self.search_horizons.search_until(
+                    id,
+                    EventTag::SailAttitudeChange,
+                    end_time,
+                    |search_start, _| {
+                        search_for_sail_attitude_change(&self.orrery, id, search_start)
+                    },
+                );
+                self.search_horizons.search_until(
+                    id,
+                    EventTag::Staging,
+                    end_time,
+                    |search_start, _| search_for_staging(&self.orrery, id, search_start),
+                );
+                continue;
+            }
+
+            // Hybrid ships start integrating when they get close to another body
+            if let Propagation::Hybrid { soi_multiple } = ship.propagation {
+                for body in self.orrery.bodies() {
+                    self.search_horizons.search_until(
+                        id,
+                        EventTag::EnterIntegrationZone(body.id),
+                        end_time,
+                        |search_start, search_end| {
+                            search_for_integration_zone_entry(
+                                &self.orrery,
+                                id,
+                                body.id,
+                                soi_multiple,
+                                search_start,
+                                search_end,
+                            )
+                        },
+                    );
+                }
+            }
+
+            // Check for hitting the surface
+            self.search_horizons
+                .search_until(id, EventTag::Impact, end_time, |search_start, _| {
+                    search_for_impact(&self.orrery, id, search_start)
+                });
+
+            // Check for crossing the edge of the atmosphere, both ways
+            self.search_horizons.search_until(
+                id,
+                EventTag::EnterAtmosphere,
+                end_time,
+                |search_start, _| search_for_atmosphere_entry(&self.orrery, id, search_start),
+            );
+            self.search_horizons.search_until(
+                id,
+                EventTag::ExitAtmosphere,
+                end_time,
+                |search_start, _| search_for_atmosphere_exit(&self.orrery, id, search_start),
+            );
+
+            // Check for passing through the apses
+            self.search_horizons.search_until(
+                id,
+                EventTag::Periapsis,
+                end_time,
+                |search_start, _| search_for_periapsis(&self.orrery, id, search_start),
+            );
+            self.search_horizons.search_until(
+                id,
+                EventTag::Apoapsis,
+                end_time,
+                |search_start, _| search_for_apoapsis(&self.orrery, id, search_start),
+            );
+
             // Check for an SOI escape event
             self.search_horizons
                 .search_until(id, EventTag::EscapeSOI, end_time, |_, _| {
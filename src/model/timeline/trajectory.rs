@@ -0,0 +1,169 @@
+use crate::astro::{CartesianState, TimedOrbit};
+use crate::model::events::{Event, EventData};
+use crate::model::orrery::{Body, BodyID, NumericArc, Ship, ShipID};
+
+/// The path of a single ship through a [Timeline](super::Timeline), as a
+/// sequence of patches, each of which is either a conic or a numerically
+/// integrated arc.
+///
+/// Patches are half-open, like segments of the timeline: each one includes its
+/// start time but not its end. Consecutive patches are separated by an event
+/// belonging to this ship that [changes its trajectory](EventData::changes_trajectory);
+/// other events don't split patches. If the ship crashes, the trajectory ends
+/// at the impact.
+#[derive(Debug, Clone)]
+pub struct Trajectory {
+    ship_id: ShipID,
+    // Invariants:
+    //   - There is at least one patch.
+    //   - Patches are sorted by start time, and each patch ends where the next one starts.
+    //   - Only the last patch can be open-ended, and the last patch is the only one that can end
+    //     with an impact.
+    patches: Vec<TrajectoryPatch>,
+}
+
+/// A single piece of a [Trajectory].
+#[derive(Debug, Clone)]
+pub struct TrajectoryPatch {
+    // The ship as of the start of this patch
+    ship: Ship,
+    start_time: f64,
+    ending_event: Option<Event>,
+}
+
+impl Trajectory {
+    /// Builds a trajectory from the state of the ship in each segment of a
+    /// timeline. Each item should be the start time of the segment, the
+    /// ship during it, and the event ending it (`None` for the final, open,
+    /// segment).
+    pub(super) fn from_segments<'a>(
+        ship_id: ShipID,
+        segments: impl Iterator<Item = (f64, &'a Ship, Option<&'a Event>)>,
+    ) -> Self {
+        let mut patches: Vec<TrajectoryPatch> = vec![];
+
+        for (start_time, ship, ending_event) in segments {
+            // If the previous patch is still going (i.e., it didn't end on one of our
+            // events), this segment just extends it.
+            let ship_event = ending_event
+                .filter(|e| e.ship_id == ship_id && e.data.changes_trajectory())
+                .cloned();
+            let is_impact = matches!(
+                ship_event,
+                Some(Event {
+                    data: EventData::Impact(_),
+                    ..
+                })
+            );
+            match patches.last_mut() {
+                Some(patch) if patch.ending_event.is_none() => {
+                    patch.ending_event = ship_event;
+                }
+                _ => patches.push(TrajectoryPatch {
+                    ship: ship.clone(),
+                    start_time,
+                    ending_event: ship_event,
+                }),
+            }
+
+            // Nothing happens after a crash
+            if is_impact {
+                break;
+            }
+        }
+
+        assert!(
+            !patches.is_empty(),
+            "Trajectory must have at least one patch"
+        );
+        Self { ship_id, patches }
+    }
+
+    pub fn ship_id(&self) -> ShipID {
+        self.ship_id
+    }
+
+    pub fn patches(&self) -> &[TrajectoryPatch] {
+        &self.patches
+    }
+
+    pub fn start_time(&self) -> f64 {
+        self.patches[0].start_time
+    }
+
+    /// Returns the patch the ship is following at the given time, or `None` if
+    /// the time is outside the trajectory (before the start, or after an
+    /// impact).
+    pub fn patch_at_time(&self, time: f64) -> Option<&TrajectoryPatch> {
+        // The first patch to start strictly after this time is right after the one
+        // we're looking for.
+        let next_patch_idx = self.patches.partition_point(|p| p.start_time <= time);
+        if next_patch_idx == 0 {
+            return None;
+        }
+        Some(&self.patches[next_patch_idx - 1]).filter(|p| p.contains_time(time))
+    }
+
+    /// Returns the time the ship crashed, if it did.
+    pub fn impact_time(&self) -> Option<f64> {
+        let last_event = self.patches.last().unwrap().ending_event.as_ref()?;
+        match last_event.data {
+            EventData::Impact(_) => Some(last_event.point.time),
+            _ => None,
+        }
+    }
+
+    /// Returns the state of the ship at the given time, relative to whichever
+    /// body it is orbiting at that time.
+    pub fn state_at_time(&self, time: f64) -> Option<CartesianState<&Body>> {
+        self.patch_at_time(time)
+            .map(|patch| patch.state_at_time(time))
+    }
+
+    /// Returns the events that separate the patches of this trajectory.
+    pub fn events(&self) -> impl Iterator<Item = &Event> {
+        self.patches.iter().filter_map(|p| p.ending_event.as_ref())
+    }
+}
+
+impl TrajectoryPatch {
+    /// The conic the ship follows during this patch. Not meaningful if the
+    /// patch is a [numeric arc](Self::numeric_arc).
+    pub fn orbit(&self) -> &TimedOrbit<Body, ()> {
+        &self.ship.orbit
+    }
+
+    pub fn numeric_arc(&self) -> Option<&NumericArc> {
+        self.ship.numeric_arc.as_ref()
+    }
+
+    pub fn parent_id(&self) -> BodyID {
+        self.ship.parent_id()
+    }
+
+    /// Returns the state of the ship along this patch, relative to the patch's
+    /// parent. Unlike [Trajectory::state_at_time], this doesn't check that the
+    /// time is within the patch, so it can be used to evaluate the patch at
+    /// the instant it ends.
+    pub fn state_at_time(&self, time: f64) -> CartesianState<&Body> {
+        self.ship.state_at_time(time)
+    }
+
+    pub fn start_time(&self) -> f64 {
+        self.start_time
+    }
+
+    /// The time this patch ends, or `None` if no event ending it has been
+    /// found yet.
+    pub fn end_time(&self) -> Option<f64> {
+        self.ending_event.as_ref().map(|e| e.point.time)
+    }
+
+    pub fn ending_event(&self) -> Option<&Event> {
+        self.ending_event.as_ref()
+    }
+
+    pub fn contains_time(&self, time: f64) -> bool {
+        self.start_time <= time && self.end_time().is_none_or(|end| time < end)
+    }
+}
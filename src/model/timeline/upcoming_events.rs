@@ -1,12 +1,12 @@
 use std::collections::HashMap;
 
-use crate::model::events::{first_event, Event, EventTag, SearchResult};
+use crate::model::events::{Event, EventTag, SearchResult};
 use crate::model::orrery::ShipID;
 
 /// When we search for events, we often want to remember that we've already
 /// searched a particular time window, and we want to cache that information
 /// for later. This struct manages this cached information.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EventSearchHorizons {
     horizons: HashMap<(ShipID, EventTag), SearchResult>,
     start_time: f64,
@@ -20,8 +20,26 @@ impl EventSearchHorizons {
         }
     }
 
+    /// Returns the earliest event found so far. Burns come before anything
+    /// else at the same time, like the apoapsis a circularization burn is
+    /// made at: burns are only searched for strictly after the start of a
+    /// segment, so one that started with the other event would skip the burn.
+    /// For the same reason, a stage running dry comes before the end of the
+    /// burn arc it's at the end of.
     pub fn get_next_event(&self) -> Option<&Event> {
-        first_event(self.horizons.values().filter_map(SearchResult::event))
+        let priority = |e: &Event| match e.data.tag() {
+            EventTag::Maneuver => 0,
+            EventTag::Staging => 1,
+            _ => 2,
+        };
+        self.horizons
+            .values()
+            .filter_map(SearchResult::event)
+            .min_by(|a, b| {
+                a.point
+                    .compare_time(&b.point)
+                    .then_with(|| priority(a).cmp(&priority(b)))
+            })
     }
 
     pub fn search_until(
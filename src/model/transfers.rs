@@ -0,0 +1,385 @@
+//! Rough costs of getting between orbits, for comparing mission architectures
+//! before planning any of them in detail.
+
+use std::fmt::Write;
+use std::str::FromStr;
+
+use super::orrery::{BodyID, Orrery};
+use crate::astro::HasMass;
+
+/// An orbit to compare transfers between, like low Kerbin orbit, given by the
+/// body it's around and the altitudes of its apsides.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrbitSpec {
+    pub name: String,
+    pub body: String,
+    /// Above the surface, in meters
+    pub periapsis_altitude: f64,
+    pub apoapsis_altitude: f64,
+    /// Relative to the body's equator, in radians
+    pub inclination: f64,
+}
+
+impl FromStr for OrbitSpec {
+    type Err = String;
+
+    /// Parses `NAME=BODY:ALTITUDE`, with the altitude in meters. An elliptic
+    /// orbit is written with both altitudes, as `PERIAPSIS-APOAPSIS`, and an
+    /// inclination in degrees can be added at the end with `@INCLINATION`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || {
+            format!(
+                "Expected an orbit like LKO=Kerbin:80000 or GTO=Kerbin:80000-2863334@5, but got {:?}",
+                s
+            )
+        };
+        let (name, rest) = s.split_once('=').ok_or_else(error)?;
+        let (body, rest) = rest.split_once(':').ok_or_else(error)?;
+        let (altitudes, inclination) = match rest.split_once('@') {
+            Some((altitudes, inclination)) => {
+                (altitudes, inclination.parse().map_err(|_| error())?)
+            }
+            None => (rest, 0.0),
+        };
+        let (periapsis_altitude, apoapsis_altitude) = match altitudes.split_once('-') {
+            Some((peri, apo)) => (
+                peri.parse().map_err(|_| error())?,
+                apo.parse().map_err(|_| error())?,
+            ),
+            None => {
+                let altitude = altitudes.parse().map_err(|_| error())?;
+                (altitude, altitude)
+            }
+        };
+        if name.trim().is_empty() || body.trim().is_empty() {
+            return Err(error());
+        }
+        if apoapsis_altitude < periapsis_altitude {
+            return Err(format!(
+                "Orbit {} has its apoapsis below its periapsis",
+                name
+            ));
+        }
+
+        Ok(Self {
+            name: name.trim().to_owned(),
+            body: body.trim().to_owned(),
+            periapsis_altitude,
+            apoapsis_altitude,
+            inclination: f64::to_radians(inclination),
+        })
+    }
+}
+
+/// An orbit from an [OrbitSpec], once its body has been found.
+#[derive(Debug, Clone, Copy)]
+struct ResolvedOrbit {
+    body_id: BodyID,
+    mu: f64,
+    periapsis: f64,
+    apoapsis: f64,
+    inclination: f64,
+}
+
+impl ResolvedOrbit {
+    fn resolve(orrery: &Orrery, spec: &OrbitSpec) -> Result<Self, String> {
+        let body = orrery
+            .bodies()
+            .find(|b| b.info.name.eq_ignore_ascii_case(&spec.body))
+            .ok_or_else(|| format!("No body named {}", spec.body))?;
+        let radius = body.info.radius as f64;
+        Ok(Self {
+            body_id: body.id,
+            mu: body.mu(),
+            periapsis: radius + spec.periapsis_altitude,
+            apoapsis: radius + spec.apoapsis_altitude,
+            inclination: spec.inclination,
+        })
+    }
+
+    fn semimajor_axis(&self) -> f64 {
+        (self.periapsis + self.apoapsis) / 2.0
+    }
+
+    /// Speed at the given distance from the body, which should be one of the
+    /// apsides.
+    fn speed_at(&self, radius: f64) -> f64 {
+        vis_viva(self.mu, radius, self.semimajor_axis())
+    }
+}
+
+fn vis_viva(mu: f64, radius: f64, semimajor_axis: f64) -> f64 {
+    f64::sqrt(mu * (2.0 / radius - 1.0 / semimajor_axis))
+}
+
+/// Size of a burn that turns one velocity into another, at an angle to it.
+fn burn(v1: f64, v2: f64, angle: f64) -> f64 {
+    f64::sqrt(v1 * v1 + v2 * v2 - 2.0 * v1 * v2 * angle.cos()).max(0.0)
+}
+
+/// How one end of a transfer looks from the body the transfer ellipse goes
+/// around.
+#[derive(Debug, Clone, Copy)]
+enum Leg {
+    /// The orbit is around that body itself, and is burned from or into at
+    /// one of its apsides.
+    Direct(ResolvedOrbit),
+    /// The orbit is around one of that body's satellites, like a planet's
+    /// moon or the sun's planet, whose own orbit is taken to be circular. It's
+    /// left or entered on a hyperbola through its periapsis.
+    ViaSatellite {
+        orbit: ResolvedOrbit,
+        satellite_distance: f64,
+    },
+}
+
+impl Leg {
+    /// Distances from the central body that the transfer ellipse can touch
+    /// this end at.
+    fn radii(&self) -> Vec<f64> {
+        match *self {
+            Leg::Direct(orbit) => vec![orbit.periapsis, orbit.apoapsis],
+            Leg::ViaSatellite {
+                satellite_distance, ..
+            } => vec![satellite_distance],
+        }
+    }
+
+    /// The burn needed at this end, when the transfer ellipse passes at the
+    /// given speed and distance, along with any plane change done then.
+    fn delta_v(&self, central_mu: f64, radius: f64, transfer_speed: f64, plane_change: f64) -> f64 {
+        match *self {
+            Leg::Direct(orbit) => burn(transfer_speed, orbit.speed_at(radius), plane_change),
+            Leg::ViaSatellite { orbit, .. } => {
+                let satellite_speed = f64::sqrt(central_mu / radius);
+                let v_infinity = (transfer_speed - satellite_speed).abs();
+                let hyperbolic_speed =
+                    f64::sqrt(v_infinity * v_infinity + 2.0 * orbit.mu / orbit.periapsis);
+                hyperbolic_speed - orbit.speed_at(orbit.periapsis)
+            }
+        }
+    }
+}
+
+/// Total delta-v of the cheapest two-impulse transfer between the orbits, or
+/// `None` if they're too far apart in the hierarchy of bodies to estimate.
+///
+/// The transfer ellipse is tangent to both ends, and goes around either the
+/// orbits' body, or the body whose satellites they're around. So transfers
+/// between two orbits around the same body, between a planet and its moons,
+/// or between sibling moons (or planets) are covered; anything deeper, like
+/// from one planet's moon to another planet, isn't.
+/// Orbits are taken to share their line of apsides and line of nodes, and
+/// bodies to be where they need to be, so this is a lower bound for a real
+/// mission. Between orbits around the same body, the plane change is done all
+/// at once, with whichever burn makes it cheapest; between different bodies,
+/// it's left out, since the departure hyperbola can be aimed.
+fn transfer_delta_v(orrery: &Orrery, from: &ResolvedOrbit, to: &ResolvedOrbit) -> Option<f64> {
+    let leg = |orbit: &ResolvedOrbit, central_id: BodyID| {
+        if orbit.body_id == central_id {
+            Some(Leg::Direct(*orbit))
+        } else if orrery.get_parent(orbit.body_id) == Some(central_id) {
+            let satellite_orbit = orrery.orbit_of_body(orbit.body_id)?;
+            Some(Leg::ViaSatellite {
+                orbit: *orbit,
+                satellite_distance: satellite_orbit.semimajor_axis(),
+            })
+        } else {
+            None
+        }
+    };
+
+    // Go around whichever body both ends can reach
+    let candidates = [
+        Some(from.body_id),
+        Some(to.body_id),
+        orrery.get_parent(from.body_id),
+    ];
+    let (central_id, from_leg, to_leg) = candidates
+        .into_iter()
+        .flatten()
+        .find_map(|id| Some((id, leg(from, id)?, leg(to, id)?)))?;
+    let central_mu = orrery.get_body(central_id).mu();
+    let plane_change = match (from_leg, to_leg) {
+        (Leg::Direct(_), Leg::Direct(_)) => (from.inclination - to.inclination).abs(),
+        _ => 0.0,
+    };
+
+    let mut best: Option<f64> = None;
+    for r1 in from_leg.radii() {
+        for r2 in to_leg.radii() {
+            let a = (r1 + r2) / 2.0;
+            let (v1, v2) = (vis_viva(central_mu, r1, a), vis_viva(central_mu, r2, a));
+            let total = f64::min(
+                from_leg.delta_v(central_mu, r1, v1, plane_change)
+                    + to_leg.delta_v(central_mu, r2, v2, 0.0),
+                from_leg.delta_v(central_mu, r1, v1, 0.0)
+                    + to_leg.delta_v(central_mu, r2, v2, plane_change),
+            );
+            if best.is_none_or(|best| total < best) {
+                best = Some(total);
+            }
+        }
+    }
+    best
+}
+
+/// Two-impulse transfer costs between every pair of some orbits.
+#[derive(Debug, Clone)]
+pub struct TransferMatrix {
+    pub names: Vec<String>,
+    /// Delta-v from the orbit in each row to the orbit in each column, if it
+    /// could be estimated
+    pub delta_v: Vec<Vec<Option<f64>>>,
+}
+
+impl TransferMatrix {
+    pub fn new(orrery: &Orrery, specs: &[OrbitSpec]) -> Result<Self, String> {
+        let orbits = specs
+            .iter()
+            .map(|spec| ResolvedOrbit::resolve(orrery, spec))
+            .collect::<Result<Vec<_>, _>>()?;
+        let delta_v = orbits
+            .iter()
+            .map(|from| {
+                orbits
+                    .iter()
+                    .map(|to| transfer_delta_v(orrery, from, to))
+                    .collect()
+            })
+            .collect();
+        Ok(Self {
+            names: specs.iter().map(|spec| spec.name.clone()).collect(),
+            delta_v,
+        })
+    }
+
+    /// Lays the matrix out as a table, in m/s, with the orbits transferred
+    /// from down the side.
+    pub fn to_table(&self) -> String {
+        let width = self
+            .names
+            .iter()
+            .map(|name| name.len())
+            .max()
+            .unwrap_or(0)
+            .max(7);
+        let mut table = format!("{:width$}", "from\\to");
+        for name in self.names.iter() {
+            write!(table, "  {:>width$}", name).unwrap();
+        }
+        table.push('\n');
+
+        for (name, row) in self.names.iter().zip(self.delta_v.iter()) {
+            write!(table, "{:width$}", name).unwrap();
+            for delta_v in row {
+                match delta_v {
+                    Some(delta_v) => write!(table, "  {:>width$.0}", delta_v).unwrap(),
+                    None => write!(table, "  {:>width$}", "-").unwrap(),
+                }
+            }
+            table.push('\n');
+        }
+        table
+    }
+
+    /// Writes the matrix as CSV, in m/s, with the orbits transferred from
+    /// down the first column. Transfers that couldn't be estimated are left
+    /// blank.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("from");
+        for name in self.names.iter() {
+            write!(csv, ",{}", name).unwrap();
+        }
+        csv.push('\n');
+
+        for (name, row) in self.names.iter().zip(self.delta_v.iter()) {
+            csv.push_str(name);
+            for delta_v in row {
+                csv.push(',');
+                if let Some(delta_v) = delta_v {
+                    write!(csv, "{:.1}", delta_v).unwrap();
+                }
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::file::read_file;
+
+    fn matrix(specs: &[&str]) -> TransferMatrix {
+        let orrery = read_file("ksp-bodies-exact.txt");
+        let specs: Vec<OrbitSpec> = specs.iter().map(|s| s.parse().unwrap()).collect();
+        TransferMatrix::new(&orrery, &specs).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let spec: OrbitSpec = "GTO=Kerbin:80000-2863334@5".parse().unwrap();
+        assert_eq!(spec.name, "GTO");
+        assert_eq!(spec.body, "Kerbin");
+        assert_eq!(spec.periapsis_altitude, 80000.0);
+        assert_eq!(spec.apoapsis_altitude, 2863334.0);
+        assert_relative_eq!(spec.inclination, 5f64.to_radians());
+
+        assert!("Kerbin:80000".parse::<OrbitSpec>().is_err());
+        assert!("LKO=Kerbin:lots".parse::<OrbitSpec>().is_err());
+        assert!("Bad=Kerbin:90000-80000".parse::<OrbitSpec>().is_err());
+    }
+
+    #[test]
+    fn test_same_body() {
+        let m = matrix(&[
+            "LKO=Kerbin:80000",
+            "KSO=Kerbin:2863334",
+            "Polar=Kerbin:80000@90",
+        ]);
+        assert_eq!(m.delta_v[0][0], Some(0.0));
+
+        // Known values for a Hohmann transfer from LKO to keostationary
+        let to_kso = m.delta_v[0][1].unwrap();
+        assert_relative_eq!(to_kso, 1099.0, epsilon = 5.0);
+        assert_relative_eq!(m.delta_v[1][0].unwrap(), to_kso, max_relative = 1e-9);
+
+        // A right-angle plane change costs sqrt(2) times the orbital speed
+        let orrery = read_file("ksp-bodies-exact.txt");
+        let kerbin = orrery.get_body(BodyID(4));
+        let speed = f64::sqrt(kerbin.mu() / (kerbin.info.radius as f64 + 80000.0));
+        assert_relative_eq!(
+            m.delta_v[0][2].unwrap(),
+            speed * f64::sqrt(2.0),
+            max_relative = 1e-9
+        );
+    }
+
+    #[test]
+    fn test_between_bodies() {
+        let m = matrix(&[
+            "LKO=Kerbin:80000",
+            "Munar=Mun:100000",
+            "Minmus=Minmus:100000",
+            "Low Duna=Duna:100000",
+        ]);
+        // Around 860 m/s to get to the Mun, and 290 m/s to be captured there
+        let to_mun = m.delta_v[0][1].unwrap();
+        assert_relative_eq!(to_mun, 1143.0, epsilon = 10.0);
+        assert_relative_eq!(m.delta_v[1][0].unwrap(), to_mun, max_relative = 1e-9);
+        // Between moons of Kerbin, and between planets
+        assert!(m.delta_v[1][2].is_some());
+        assert!(m.delta_v[0][3].is_some());
+        // From the Mun to Duna is too far up and back down to estimate
+        assert_eq!(m.delta_v[1][3], None);
+
+        let csv = m.to_csv();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("from,LKO,Munar,Minmus,Low Duna"));
+        assert!(lines.nth(1).unwrap().ends_with(','));
+    }
+}
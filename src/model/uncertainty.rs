@@ -0,0 +1,242 @@
+//! Rough uncertainty in where a ship ends up, from uncertainty in the elements
+//! it's placed with. Instead of tracking a full covariance, the extreme
+//! combinations of the elements are each propagated, and the spread in the
+//! results is reported.
+
+use std::f64::consts::PI;
+
+use super::events::EventData;
+use super::orrery::{BodyID, Orrery, ShipPlacement};
+use super::timeline::Timeline;
+
+/// How far off each of a placement's elements could be, in either direction.
+/// Zero means the element is known exactly.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ElementUncertainty {
+    pub semimajor_axis: f64,
+    pub eccentricity: f64,
+    /// Angles are in radians.
+    pub inclination: f64,
+    pub mean_anomaly: f64,
+}
+
+impl ElementUncertainty {
+    pub fn is_zero(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns every combination of the elements being at the top or bottom
+    /// of their ranges. Elements with no uncertainty are left alone, so if
+    /// there's none at all, this is just the placement itself.
+    ///
+    /// Elements are kept in range: eccentricity stays elliptic, and
+    /// inclination between 0 and pi.
+    pub fn extremes(&self, placement: &ShipPlacement) -> Vec<ShipPlacement> {
+        let mut extremes = vec![*placement];
+        let mut vary = |delta: f64, apply: &dyn Fn(&mut ShipPlacement, f64)| {
+            if delta == 0.0 {
+                return;
+            }
+            extremes = extremes
+                .iter()
+                .flat_map(|p| {
+                    [-delta, delta].map(|d| {
+                        let mut p = *p;
+                        apply(&mut p, d);
+                        p
+                    })
+                })
+                .collect();
+        };
+        vary(self.semimajor_axis, &|p, d| {
+            p.semimajor_axis = (p.semimajor_axis + d).max(0.0)
+        });
+        vary(self.eccentricity, &|p, d| {
+            p.eccentricity = (p.eccentricity + d).clamp(0.0, 0.999)
+        });
+        vary(self.inclination, &|p, d| {
+            p.inclination = (p.inclination + d).clamp(0.0, PI)
+        });
+        vary(self.mean_anomaly, &|p, d| {
+            p.set_mean_anomaly(p.mean_anomaly + d)
+        });
+        extremes
+    }
+}
+
+/// The smallest and largest values something took over the extremes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Envelope {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Envelope {
+    fn of(values: impl Iterator<Item = f64>) -> Option<Self> {
+        values.fold(None, |envelope, x| {
+            Some(match envelope {
+                None => Envelope { min: x, max: x },
+                Some(Envelope { min, max }) => Envelope {
+                    min: min.min(x),
+                    max: max.max(x),
+                },
+            })
+        })
+    }
+}
+
+/// When a ship placed somewhere in the uncertain range first enters a body's
+/// SOI.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EncounterWindow {
+    pub body_id: BodyID,
+    pub time: Envelope,
+    /// How many of the extremes make this their first encounter.
+    pub num_extremes: usize,
+}
+
+/// How much a ship's orbit and encounters could vary, given the uncertainty
+/// in its elements.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UncertaintyEnvelope {
+    pub uncertainty: ElementUncertainty,
+    pub num_extremes: usize,
+    /// Of the initial orbit.
+    pub periapsis: Envelope,
+    pub apoapsis: Envelope,
+    /// First encounters, ordered by their earliest time.
+    pub encounters: Vec<EncounterWindow>,
+}
+
+impl UncertaintyEnvelope {
+    /// Propagates each of the extremes of the placement through the given
+    /// system, until `horizon` seconds after the placement's epoch, and
+    /// collects the spread in their apsides and first encounters.
+    ///
+    /// Any ships already in the orrery are propagated too, so this can be slow
+    /// if there are a lot of them.
+    pub fn propagate(
+        orrery: &Orrery,
+        placement: &ShipPlacement,
+        uncertainty: ElementUncertainty,
+        horizon: f64,
+    ) -> Self {
+        let extremes = uncertainty.extremes(placement);
+        let end_time = placement.epoch + horizon;
+
+        let orbits: Vec<_> = extremes.iter().map(|p| p.orbit(orrery)).collect();
+        let periapsis = Envelope::of(orbits.iter().map(|o| o.periapsis())).unwrap();
+        let apoapsis = Envelope::of(orbits.iter().map(|o| o.apoapsis().unwrap())).unwrap();
+
+        let mut encounters: Vec<EncounterWindow> = vec![];
+        for extreme in extremes.iter() {
+            let mut timeline = Timeline::new(orrery.clone(), placement.epoch);
+            let ship_id = timeline.add_ship(extreme);
+            timeline.extend_until(end_time);
+
+            let encounter = timeline.events().find_map(|e| match &e.data {
+                EventData::EnteringSOI(soi_change)
+                    if e.ship_id == ship_id && e.point.time <= end_time =>
+                {
+                    Some((soi_change.new, e.point.time))
+                }
+                _ => None,
+            });
+            let (body_id, time) = match encounter {
+                Some(encounter) => encounter,
+                None => continue,
+            };
+            match encounters.iter_mut().find(|w| w.body_id == body_id) {
+                Some(window) => {
+                    window.time.min = window.time.min.min(time);
+                    window.time.max = window.time.max.max(time);
+                    window.num_extremes += 1;
+                }
+                None => encounters.push(EncounterWindow {
+                    body_id,
+                    time: Envelope {
+                        min: time,
+                        max: time,
+                    },
+                    num_extremes: 1,
+                }),
+            }
+        }
+        encounters.sort_by(|a, b| a.time.min.total_cmp(&b.time.min));
+
+        Self {
+            uncertainty,
+            num_extremes: extremes.len(),
+            periapsis,
+            apoapsis,
+            encounters,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::file::read_file;
+
+    const KERBIN: BodyID = BodyID(4);
+    const MUN: BodyID = BodyID(5);
+
+    #[test]
+    fn test_extremes() {
+        let placement = ShipPlacement::circular(KERBIN, 1e6, 0.0);
+        assert_eq!(
+            ElementUncertainty::default().extremes(&placement),
+            vec![placement]
+        );
+
+        let uncertainty = ElementUncertainty {
+            semimajor_axis: 5000.0,
+            eccentricity: 0.01,
+            inclination: 0.1,
+            mean_anomaly: 0.0,
+        };
+        let extremes = uncertainty.extremes(&placement);
+        assert_eq!(extremes.len(), 8);
+        // Circular orbits can't get any less eccentric, or inclined
+        assert!(extremes.iter().all(|p| p.eccentricity >= 0.0));
+        assert!(extremes.iter().all(|p| p.inclination >= 0.0));
+        assert_relative_eq!(extremes[0].semimajor_axis, 995000.0);
+        assert_relative_eq!(extremes[7].semimajor_axis, 1005000.0);
+        assert_relative_eq!(extremes[7].eccentricity, 0.01);
+    }
+
+    #[test]
+    fn test_mun_transfer() {
+        // A transfer out to the Mun's orbit, which is at 12,000 km, timed so
+        // that the ship meets the Mun either way, but the bigger orbit gets
+        // there later
+        let orrery = read_file("ksp-bodies.txt");
+        let placement = ShipPlacement {
+            eccentricity: 0.8,
+            ..ShipPlacement::circular(KERBIN, 6.8e6, 0.0)
+        };
+        let uncertainty = ElementUncertainty {
+            semimajor_axis: 1e5,
+            ..ElementUncertainty::default()
+        };
+
+        let envelope = UncertaintyEnvelope::propagate(&orrery, &placement, uncertainty, 86400.0);
+        assert_eq!(envelope.num_extremes, 2);
+        assert_relative_eq!(envelope.periapsis.min, 0.2 * 6.7e6, max_relative = 1e-9);
+        assert_relative_eq!(envelope.periapsis.max, 0.2 * 6.9e6, max_relative = 1e-9);
+        assert_relative_eq!(envelope.apoapsis.max, 1.8 * 6.9e6, max_relative = 1e-9);
+        assert_eq!(envelope.encounters.len(), 1);
+        let window = envelope.encounters[0];
+        assert_eq!(window.body_id, MUN);
+        assert_eq!(window.num_extremes, 2);
+        assert!(
+            (20000.0..window.time.max).contains(&window.time.min),
+            "{:?}",
+            window
+        );
+        assert!(window.time.max < 30000.0, "{:?}", window);
+    }
+}
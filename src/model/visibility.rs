@@ -0,0 +1,202 @@
+//! Which ships can see each other, and when, for working out how a network of
+//! ships could relay signals between them.
+
+use std::fmt::Write;
+
+use super::orrery::{BodyID, Frame, Orrery, ShipID, SystemSnapshot};
+use super::timeline::Timeline;
+
+/// Whether the straight line between the two ships misses every body. Bodies
+/// are treated as spheres, without atmospheres.
+pub fn has_line_of_sight(orrery: &Orrery, a: ShipID, b: ShipID, time: f64) -> bool {
+    let snapshot = orrery.snapshot(time, Frame::Root);
+    line_of_sight_in(orrery, &snapshot, a, b)
+}
+
+/// Like [has_line_of_sight], but using positions that have already been
+/// worked out, so that checking many pairs doesn't recompute them.
+fn line_of_sight_in(orrery: &Orrery, snapshot: &SystemSnapshot, a: ShipID, b: ShipID) -> bool {
+    let (_, depth) = occlusion(orrery, snapshot, a, b);
+    depth <= 0.0
+}
+
+/// Finds the body that cuts deepest into the straight line between the two
+/// ships, and how far inside its surface the line passes. This is negative if
+/// the line misses every body.
+pub fn occlusion(
+    orrery: &Orrery,
+    snapshot: &SystemSnapshot,
+    a: ShipID,
+    b: ShipID,
+) -> (BodyID, f64) {
+    let (pos_a, pos_b) = (snapshot.ship(a).position, snapshot.ship(b).position);
+    let segment = pos_b - pos_a;
+
+    orrery
+        .bodies()
+        .map(|body| {
+            let center = snapshot.body(body.id).position;
+
+            // Find the closest point on the segment to the body's center
+            let t = match segment.norm_squared() {
+                0.0 => 0.0,
+                length_sq => ((center - pos_a).dot(&segment) / length_sq).clamp(0.0, 1.0),
+            };
+            let closest = pos_a + t * segment;
+            (body.id, body.info.radius as f64 - (closest - center).norm())
+        })
+        .max_by(|(_, x), (_, y)| x.total_cmp(y))
+        .expect("Orrery has no bodies")
+}
+
+/// Pairwise visibility between some ships, sampled over part of a timeline.
+#[derive(Debug, Clone)]
+pub struct VisibilityMatrix {
+    pub ship_ids: Vec<ShipID>,
+    pub times: Vec<f64>,
+    // For each time, whether each pair of ships (i, j), with i < j, can see
+    // each other, in row-major order
+    visible: Vec<Vec<bool>>,
+}
+
+impl VisibilityMatrix {
+    /// Samples visibility between the given ships every `step` seconds, from
+    /// `start_time` to `end_time` inclusive. The timeline should already have
+    /// been extended that far.
+    pub fn from_timeline(
+        timeline: &Timeline,
+        ship_ids: Vec<ShipID>,
+        start_time: f64,
+        end_time: f64,
+        step: f64,
+    ) -> Self {
+        assert!(step > 0.0, "Step must be positive, but was {}", step);
+        let num_steps = ((end_time - start_time) / step).floor() as usize;
+        let times: Vec<f64> = (0..=num_steps)
+            .map(|i| start_time + step * i as f64)
+            .collect();
+
+        let visible = times
+            .iter()
+            .map(|&time| {
+                let orrery = timeline
+                    .get_orrery_at(time)
+                    .expect("Lookup before timeline start");
+                let snapshot = orrery.snapshot(time, Frame::Root);
+                let mut row = vec![];
+                for (i, a) in ship_ids.iter().enumerate() {
+                    for b in ship_ids[i + 1..].iter() {
+                        row.push(line_of_sight_in(&orrery, &snapshot, *a, *b));
+                    }
+                }
+                row
+            })
+            .collect();
+
+        Self {
+            ship_ids,
+            times,
+            visible,
+        }
+    }
+
+    fn pair_index(&self, i: usize, j: usize) -> usize {
+        let (i, j) = if i < j { (i, j) } else { (j, i) };
+        let n = self.ship_ids.len();
+        // Rows before i have n-1, n-2, ... entries
+        i * (2 * n - i - 1) / 2 + (j - i - 1)
+    }
+
+    /// Whether the ships at positions `i` and `j` in `ship_ids` can see each
+    /// other at the `time_idx`th sample. Ships can always see themselves.
+    pub fn is_visible(&self, time_idx: usize, i: usize, j: usize) -> bool {
+        i == j || self.visible[time_idx][self.pair_index(i, j)]
+    }
+
+    /// Returns the full matrix at the `time_idx`th sample, indexed like
+    /// `ship_ids`.
+    pub fn matrix_at(&self, time_idx: usize) -> Vec<Vec<bool>> {
+        let n = self.ship_ids.len();
+        (0..n)
+            .map(|i| (0..n).map(|j| self.is_visible(time_idx, i, j)).collect())
+            .collect()
+    }
+
+    /// Writes the samples as CSV, with one row per time, and one column per
+    /// pair of ships, holding 1 if they can see each other and 0 if not.
+    /// Columns are named after the ships' IDs, like `3-7`.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("time");
+        for (i, a) in self.ship_ids.iter().enumerate() {
+            for b in self.ship_ids[i + 1..].iter() {
+                write!(csv, ",{}-{}", a.0, b.0).unwrap();
+            }
+        }
+        csv.push('\n');
+
+        for (time, row) in self.times.iter().zip(self.visible.iter()) {
+            write!(csv, "{}", time).unwrap();
+            for visible in row {
+                csv.push_str(if *visible { ",1" } else { ",0" });
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+
+    use super::*;
+    use crate::file::read_file;
+    use crate::model::orrery::BodyID;
+
+    const KERBIN: BodyID = BodyID(4);
+
+    #[test]
+    fn test_opposite_ships() {
+        // Three ships in the same high circular orbit, two on opposite sides
+        // of Kerbin, and one a quarter of the way around from them
+        let mut orrery = read_file("ksp-bodies.txt");
+        let radius = 2e6;
+        let speed = (orrery.get_body(KERBIN).info.mu / radius).sqrt();
+        let mut add_ship = |angle: f64| {
+            let (sin, cos) = angle.sin_cos();
+            orrery.add_ship(
+                Vector3::new(cos, sin, 0.0) * radius,
+                Vector3::new(-sin, cos, 0.0) * speed,
+                0.0,
+                KERBIN,
+            )
+        };
+        let ids = vec![
+            add_ship(0.0),
+            add_ship(std::f64::consts::PI),
+            add_ship(std::f64::consts::FRAC_PI_2),
+        ];
+
+        let mut timeline = Timeline::new(orrery, 0.0);
+        timeline.extend_until(20000.0);
+        let matrix = VisibilityMatrix::from_timeline(&timeline, ids, 0.0, 20000.0, 1000.0);
+        assert_eq!(matrix.times.len(), 21);
+
+        // They stay where they are relative to each other, so this is the same
+        // at all times
+        let expected = vec![
+            vec![true, false, true],
+            vec![false, true, true],
+            vec![true, true, true],
+        ];
+        for idx in 0..matrix.times.len() {
+            assert_eq!(matrix.matrix_at(idx), expected);
+        }
+
+        let csv = matrix.to_csv();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("time,0-1,0-2,1-2"));
+        assert_eq!(lines.next(), Some("0,0,1,1"));
+        assert_eq!(lines.count(), 20);
+    }
+}
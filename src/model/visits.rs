@@ -0,0 +1,169 @@
+//! Which ships have passed through each body's SOI, and when, worked out from
+//! the SOI changes in a timeline.
+
+use std::collections::{HashMap, HashSet};
+
+use super::events::{Event, EventData};
+use super::orrery::{BodyID, ShipID};
+
+/// One ship's stay in a body's SOI.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Visit {
+    pub ship_id: ShipID,
+    /// When the ship entered the SOI, or `None` if it was already there
+    /// before its first SOI change.
+    pub entered: Option<f64>,
+    /// When it left, or `None` if it hasn't yet.
+    pub exited: Option<f64>,
+}
+
+/// Every body's visits, from earliest to latest.
+#[derive(Debug, Clone, Default)]
+pub struct VisitLog {
+    visits: HashMap<BodyID, Vec<Visit>>,
+}
+
+impl VisitLog {
+    /// Goes through the given events, which must be in order, and records
+    /// each SOI change as the ship leaving one body's SOI and entering
+    /// another's. Ships that never change SOI don't show up at all.
+    pub fn from_events<'a>(events: impl Iterator<Item = &'a Event>) -> Self {
+        let mut visits: HashMap<BodyID, Vec<Visit>> = HashMap::new();
+        // Which body each ship is visiting now, and where that visit is in
+        // its list
+        let mut current: HashMap<ShipID, (BodyID, usize)> = HashMap::new();
+
+        for event in events {
+            let soi_change = match &event.data {
+                EventData::EnteringSOI(soi_change) | EventData::ExitingSOI(soi_change) => {
+                    soi_change
+                }
+                _ => continue,
+            };
+            let ship_id = event.ship_id;
+            let time = event.point.time;
+
+            match current.get(&ship_id) {
+                Some((body, idx)) if *body == soi_change.old => {
+                    visits.get_mut(body).unwrap()[*idx].exited = Some(time);
+                }
+                _ => visits.entry(soi_change.old).or_default().push(Visit {
+                    ship_id,
+                    entered: None,
+                    exited: Some(time),
+                }),
+            }
+
+            let list = visits.entry(soi_change.new).or_default();
+            list.push(Visit {
+                ship_id,
+                entered: Some(time),
+                exited: None,
+            });
+            current.insert(ship_id, (soi_change.new, list.len() - 1));
+        }
+
+        // Visits found from their exits go before any that were entered
+        for list in visits.values_mut() {
+            list.sort_by(|a, b| {
+                let entered = |v: &Visit| v.entered.unwrap_or(f64::NEG_INFINITY);
+                entered(a).total_cmp(&entered(b))
+            });
+        }
+        Self { visits }
+    }
+
+    /// The visits to the given body's SOI, from earliest to latest.
+    pub fn visits(&self, body_id: BodyID) -> &[Visit] {
+        self.visits.get(&body_id).map_or(&[], |v| v.as_slice())
+    }
+
+    /// How many different ships have visited the given body's SOI.
+    pub fn num_ships(&self, body_id: BodyID) -> usize {
+        let ships: HashSet<_> = self.visits(body_id).iter().map(|v| v.ship_id).collect();
+        ships.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point3;
+
+    use super::*;
+    use crate::model::events::{EventPoint, SOIChange};
+
+    const KERBIN: BodyID = BodyID(4);
+    const MUN: BodyID = BodyID(5);
+
+    fn soi_change(ship_id: ShipID, time: f64, old: BodyID, new: BodyID) -> Event {
+        let soi_change = SOIChange { old, new };
+        let data = if new == MUN {
+            EventData::EnteringSOI(soi_change)
+        } else {
+            EventData::ExitingSOI(soi_change)
+        };
+        Event {
+            ship_id,
+            data,
+            point: EventPoint {
+                time,
+                anomaly: 0.0,
+                location: Point3::origin(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_visits() {
+        // One ship goes to the Mun and back, and another goes there twice,
+        // and is still there
+        let (first, second) = (ShipID(0), ShipID(1));
+        let events = [
+            soi_change(first, 100.0, KERBIN, MUN),
+            soi_change(second, 150.0, KERBIN, MUN),
+            soi_change(first, 200.0, MUN, KERBIN),
+            soi_change(second, 250.0, MUN, KERBIN),
+            soi_change(second, 300.0, KERBIN, MUN),
+        ];
+        let log = VisitLog::from_events(events.iter());
+
+        let mun_visits = log.visits(MUN);
+        assert_eq!(
+            mun_visits,
+            [
+                Visit {
+                    ship_id: first,
+                    entered: Some(100.0),
+                    exited: Some(200.0)
+                },
+                Visit {
+                    ship_id: second,
+                    entered: Some(150.0),
+                    exited: Some(250.0)
+                },
+                Visit {
+                    ship_id: second,
+                    entered: Some(300.0),
+                    exited: None
+                },
+            ]
+        );
+        assert_eq!(log.num_ships(MUN), 2);
+
+        // Both ships were around Kerbin from the start, and came back
+        let kerbin_visits = log.visits(KERBIN);
+        assert_eq!(kerbin_visits.len(), 4);
+        assert_eq!(kerbin_visits[0].entered, None);
+        assert_eq!(kerbin_visits[1].entered, None);
+        assert_eq!(
+            kerbin_visits[2],
+            Visit {
+                ship_id: first,
+                entered: Some(200.0),
+                exited: None
+            }
+        );
+        assert_eq!(log.num_ships(KERBIN), 2);
+        assert!(log.visits(BodyID(1)).is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+//! Runs each scenario in tests/scenarios, and checks the events it produces
+//! against the golden log next to it, so that changes to the event search
+//! that move, add or drop events don't go unnoticed.
+//!
+//! A scenario is a text file of directives, one per line:
+//! - `preset NAME`: the bodies to use, as for the simulator's `--preset`
+//! - `ship BODY X Y Z VX VY VZ`: a ship starting at time 0, with its position
+//!   and velocity relative to the named body. Ships are numbered from 0, in
+//!   the order they're given.
+//! - `burn SHIP TIME PROGRADE NORMAL RADIAL`: a planned burn, in m/s
+//! - `eclipses SHIP`: mark the ship's passes through shadows
+//! - `until TIME`: how far to run, in seconds
+//!
+//! When a change to the events is intended, write the logs again with
+//! `UPDATE_GOLDEN=1 cargo test --test scenarios`, and check the differences.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use nalgebra::Vector3;
+use rust_ksp::file::{read_preset, BodiesPreset};
+use rust_ksp::model::orrery::{orbit_frame, Maneuver, ShipID};
+use rust_ksp::model::timeline::Timeline;
+
+// How far an event's time can move before it counts as a different event
+const MAX_RELATIVE_ERROR: f64 = 1e-6;
+const MAX_ABSOLUTE_ERROR: f64 = 1e-3;
+
+/// One line of an event log: when, which ship, and what happened.
+#[derive(Debug, Clone, PartialEq)]
+struct LoggedEvent {
+    time: f64,
+    ship_id: usize,
+    description: String,
+}
+
+impl LoggedEvent {
+    fn to_line(&self) -> String {
+        format!("{:?} {} {}", self.time, self.ship_id, self.description)
+    }
+
+    fn from_line(line: &str) -> Self {
+        let mut parts = line.splitn(3, ' ');
+        let mut next = || {
+            parts
+                .next()
+                .unwrap_or_else(|| panic!("Bad line {:?}", line))
+        };
+        Self {
+            time: next().parse().unwrap(),
+            ship_id: next().parse().unwrap(),
+            description: next().to_owned(),
+        }
+    }
+
+    fn matches(&self, other: &LoggedEvent) -> bool {
+        let tolerance = MAX_ABSOLUTE_ERROR.max(MAX_RELATIVE_ERROR * self.time.abs());
+        self.ship_id == other.ship_id
+            && self.description == other.description
+            && (self.time - other.time).abs() <= tolerance
+    }
+}
+
+fn scenario_path(name: &str, extension: &str) -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "tests", "scenarios", name]
+        .iter()
+        .collect::<PathBuf>()
+        .with_extension(extension)
+}
+
+/// Sets up the scenario, runs it, and returns the events it produced, in
+/// order.
+fn run_scenario(name: &str) -> Vec<LoggedEvent> {
+    let path = scenario_path(name, "txt");
+    let contents = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Couldn't read {}: {}", path.display(), e));
+
+    let mut orrery = None;
+    // Burns are aimed once the ships are all there, and the timeline's set up
+    let mut burns = vec![];
+    let mut end_time = None;
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (directive, args) = line.split_once(' ').unwrap_or((line, ""));
+        let args: Vec<_> = args.split_whitespace().collect();
+        let number = |i: usize| -> f64 {
+            args.get(i)
+                .and_then(|s| s.parse().ok())
+                .unwrap_or_else(|| panic!("Expected a number in {:?}", line))
+        };
+        let ship_id = |i: usize| ShipID(number(i) as usize);
+
+        if directive == "preset" {
+            let preset: BodiesPreset = args[0].parse().unwrap();
+            orrery = Some(read_preset(preset));
+            continue;
+        }
+        let orrery = orrery
+            .as_mut()
+            .unwrap_or_else(|| panic!("{:?} comes before the preset", line));
+        match directive {
+            "ship" => {
+                let parent_id = orrery
+                    .bodies()
+                    .find(|b| b.info.name == args[0])
+                    .unwrap_or_else(|| panic!("No body named {}", args[0]))
+                    .id;
+                orrery.add_ship(
+                    Vector3::new(number(1), number(2), number(3)),
+                    Vector3::new(number(4), number(5), number(6)),
+                    0.0,
+                    parent_id,
+                );
+            }
+            "burn" => burns.push((
+                ship_id(0),
+                number(1),
+                Vector3::new(number(2), number(3), number(4)),
+            )),
+            "eclipses" => orrery.set_ship_eclipse_tracking(ship_id(0), true),
+            "until" => end_time = Some(number(0)),
+            _ => panic!("Unknown directive {:?}", directive),
+        }
+    }
+
+    let mut timeline = Timeline::new(orrery.expect("Scenario has no preset"), 0.0);
+    burns.sort_by(|a, b| a.1.total_cmp(&b.1));
+    for (id, time, components) in burns {
+        timeline.extend_until(time);
+        let orrery = timeline.get_orrery_at(time).unwrap();
+        let ship = orrery.get_ship(id);
+        let state = ship.state_at_time(time);
+        let mut plan = ship.maneuvers.clone();
+        plan.set_maneuver(Maneuver {
+            time,
+            delta_v: orbit_frame(state.position(), state.velocity()) * components,
+        });
+        timeline.set_maneuver_plan(id, plan);
+    }
+    let end_time = end_time.expect("Scenario has no end time");
+    timeline.extend_until(end_time);
+    timeline
+        .events()
+        .filter(|e| e.point.time <= end_time)
+        .map(|e| LoggedEvent {
+            time: e.point.time,
+            ship_id: e.ship_id.0,
+            description: format!("{:?}", e.data),
+        })
+        .collect()
+}
+
+/// Runs the scenario and compares it to its golden log, or writes the log
+/// if `UPDATE_GOLDEN` is set.
+fn check_scenario(name: &str) {
+    let actual = run_scenario(name);
+    let golden_path = scenario_path(name, "events");
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        let mut contents = String::new();
+        for event in actual.iter() {
+            contents.push_str(&event.to_line());
+            contents.push('\n');
+        }
+        fs::write(&golden_path, contents).unwrap();
+        return;
+    }
+
+    let contents = fs::read_to_string(&golden_path).unwrap_or_else(|e| {
+        panic!(
+            "Couldn't read {}: {}. Run with UPDATE_GOLDEN=1 to write it.",
+            golden_path.display(),
+            e
+        )
+    });
+    let expected: Vec<_> = contents.lines().map(LoggedEvent::from_line).collect();
+
+    // Report the first difference, which the rest usually follow from
+    for (i, (expected, actual)) in expected.iter().zip(actual.iter()).enumerate() {
+        assert!(
+            expected.matches(actual),
+            "Event {} of {} differs:\n  expected {}\n  found    {}",
+            i,
+            name,
+            expected.to_line(),
+            actual.to_line()
+        );
+    }
+    assert_eq!(
+        expected.len(),
+        actual.len(),
+        "Expected {} events from {}, but found {}",
+        expected.len(),
+        name,
+        actual.len()
+    );
+}
+
+#[test]
+fn test_mun_encounters() {
+    check_scenario("mun_encounters");
+}
+
+#[test]
+fn test_impact() {
+    check_scenario("impact");
+}
+
+#[test]
+fn test_eclipses() {
+    check_scenario("eclipses");
+}
+
+#[test]
+fn test_escape_burn() {
+    check_scenario("escape_burn");
+}